@@ -28,7 +28,7 @@ use jsonrpsee::PendingSubscriptionSink;
 use parking_lot::Mutex;
 use sc_client_api::{AuxStore, BlockBackend};
 use sc_consensus_subspace::archiver::{
-    recreate_genesis_segment, ArchivedSegmentNotification, SegmentHeadersStore,
+    recreate_genesis_segment, ArchivedSegmentNotification, ObjectMappingsStore, SegmentHeadersStore,
 };
 use sc_consensus_subspace::notification::SubspaceNotificationStream;
 use sc_consensus_subspace::slot_worker::{
@@ -54,7 +54,7 @@ use std::sync::{Arc, Weak};
 use std::time::Duration;
 use subspace_archiving::archiver::NewArchivedSegment;
 use subspace_core_primitives::crypto::kzg::Kzg;
-use subspace_core_primitives::objects::GlobalObjectMapping;
+use subspace_core_primitives::objects::{GlobalObject, GlobalObjectMapping};
 use subspace_core_primitives::{
     Blake3Hash, BlockHash, HistorySize, Piece, PieceIndex, PublicKey, SegmentHeader, SegmentIndex,
     SlotNumber, Solution,
@@ -182,6 +182,16 @@ pub trait SubspaceRpcApi {
         item = GlobalObjectMapping,
     )]
     fn subscribe_filtered_object_mappings(&self, hashes: Vec<Blake3Hash>);
+
+    /// Get object mapping by object hash from the object mappings index, returns `None` if object
+    /// is not known
+    #[method(name = "subspace_getObjectMapping")]
+    fn get_object_mapping(&self, hash: Blake3Hash) -> Result<Option<GlobalObject>, Error>;
+
+    /// Get the index of the last segment up to which object mappings of all segments were indexed,
+    /// returns `None` if nothing was indexed yet
+    #[method(name = "subspace_getObjectMappingsCursor")]
+    fn get_object_mappings_cursor(&self) -> Result<Option<SegmentIndex>, Error>;
 }
 
 #[derive(Default)]
@@ -237,6 +247,8 @@ where
     pub dsn_bootstrap_nodes: Vec<Multiaddr>,
     /// Segment headers store
     pub segment_headers_store: SegmentHeadersStore<AS>,
+    /// Object mappings store, object mappings lookup is disabled if not provided
+    pub object_mappings_store: Option<ObjectMappingsStore<AS>>,
     /// Subspace sync oracle
     pub sync_oracle: SubspaceSyncOracle<SO>,
     /// Signifies whether a potentially unsafe RPC should be denied
@@ -263,6 +275,7 @@ where
     reward_signature_senders: Arc<Mutex<BlockSignatureSenders>>,
     dsn_bootstrap_nodes: Vec<Multiaddr>,
     segment_headers_store: SegmentHeadersStore<AS>,
+    object_mappings_store: Option<ObjectMappingsStore<AS>>,
     cached_archived_segment: Arc<Mutex<Option<CachedArchivedSegment>>>,
     archived_segment_acknowledgement_senders:
         Arc<Mutex<ArchivedSegmentHeaderAcknowledgementSenders>>,
@@ -322,6 +335,7 @@ where
             reward_signature_senders: Arc::default(),
             dsn_bootstrap_nodes: config.dsn_bootstrap_nodes,
             segment_headers_store: config.segment_headers_store,
+            object_mappings_store: config.object_mappings_store,
             cached_archived_segment: Arc::default(),
             archived_segment_acknowledgement_senders: Arc::default(),
             next_subscription_id: AtomicU64::default(),
@@ -337,6 +351,22 @@ where
     }
 }
 
+impl<Block, Client, SO, AS> SubspaceRpc<Block, Client, SO, AS>
+where
+    Block: BlockT,
+    SO: SyncOracle + Send + Sync + Clone + 'static,
+{
+    fn object_mappings_store(&self) -> Result<&ObjectMappingsStore<AS>, Error> {
+        self.object_mappings_store.as_ref().ok_or_else(|| {
+            Error::StringError(
+                "Object mappings index is disabled, start node with `--index-object-mappings` to \
+                enable it"
+                    .to_string(),
+            )
+        })
+    }
+}
+
 #[async_trait]
 impl<Block, Client, SO, AS> SubspaceRpcApiServer for SubspaceRpc<Block, Client, SO, AS>
 where
@@ -817,6 +847,28 @@ where
         Ok(last_segment_headers)
     }
 
+    fn get_object_mapping(&self, hash: Blake3Hash) -> Result<Option<GlobalObject>, Error> {
+        let object_mappings_store = self.object_mappings_store()?;
+
+        object_mappings_store
+            .get_object_mapping(&hash)
+            .map_err(|error| {
+                error!(?hash, %error, "Failed to get object mapping");
+
+                Error::StringError(format!("Failed to get object mapping: {error}"))
+            })
+    }
+
+    fn get_object_mappings_cursor(&self) -> Result<Option<SegmentIndex>, Error> {
+        let object_mappings_store = self.object_mappings_store()?;
+
+        object_mappings_store.cursor().map_err(|error| {
+            error!(%error, "Failed to get object mappings cursor");
+
+            Error::StringError(format!("Failed to get object mappings cursor: {error}"))
+        })
+    }
+
     // TODO:
     // - the number of object mappings in each segment can be very large (hundreds or thousands).
     //   To avoid RPC connection failures, limit the number of mappings returned in each response,
@@ -43,6 +43,9 @@
 //! segment that is special case since we don't have enough data in the blockchain history itself
 //! during genesis in order to do the archiving.
 //!
+//! [`ObjectMappingsStore`] optionally persists object mappings of archived segments as they are
+//! archived, such that objects can be looked up by their hash later.
+//!
 //! [`encode_block`] and [`decode_block`] are symmetric encoding/decoding functions turning
 //! [`SignedBlock`]s into bytes and back.
 
@@ -77,9 +80,9 @@ use std::sync::Arc;
 use std::time::Duration;
 use subspace_archiving::archiver::{Archiver, NewArchivedSegment};
 use subspace_core_primitives::crypto::kzg::Kzg;
-use subspace_core_primitives::objects::BlockObjectMapping;
+use subspace_core_primitives::objects::{BlockObjectMapping, CompactGlobalObject, GlobalObject};
 use subspace_core_primitives::{
-    BlockNumber, PublicKey, RecordedHistorySegment, SegmentHeader, SegmentIndex,
+    Blake3Hash, BlockNumber, PublicKey, RecordedHistorySegment, SegmentHeader, SegmentIndex,
 };
 use subspace_erasure_coding::ErasureCoding;
 use tracing::{debug, info, trace, warn};
@@ -335,6 +338,133 @@ where
     }
 }
 
+/// Persistent storage of object mappings created during archiving.
+///
+/// Object mappings of each segment archived by this node are stored before its segment header, so
+/// they are never lost, even if node is restarted in the middle of archiving. Segments archived
+/// before the store was enabled or not archived locally (during sync from DSN) are not indexed.
+///
+/// Cursor is the index of the last segment such that object mappings of all segments starting with
+/// the first indexed one up to and including it are stored.
+#[derive(Debug)]
+pub struct ObjectMappingsStore<AS> {
+    aux_store: Arc<AS>,
+}
+
+impl<AS> Clone for ObjectMappingsStore<AS> {
+    fn clone(&self) -> Self {
+        Self {
+            aux_store: Arc::clone(&self.aux_store),
+        }
+    }
+}
+
+impl<AS> ObjectMappingsStore<AS>
+where
+    AS: AuxStore,
+{
+    const KEY_PREFIX: &'static [u8] = b"object-mapping";
+    const CURSOR_KEY: &'static [u8] = b"object-mappings-cursor";
+
+    /// Create new instance
+    pub fn new(aux_store: Arc<AS>) -> Self {
+        Self { aux_store }
+    }
+
+    /// Get object mapping by object hash, returns `None` if object is not known
+    pub fn get_object_mapping(
+        &self,
+        hash: &Blake3Hash,
+    ) -> sp_blockchain::Result<Option<GlobalObject>> {
+        let Some(bytes) = self.aux_store.get_aux(&Self::key(hash))? else {
+            return Ok(None);
+        };
+
+        let object = CompactGlobalObject::decode(&mut bytes.as_slice()).map_err(|error| {
+            sp_blockchain::Error::Backend(format!("Failed to decode object mapping: {error}"))
+        })?;
+
+        Ok(Some(object.into()))
+    }
+
+    /// Returns cursor, `None` if nothing was indexed yet
+    pub fn cursor(&self) -> sp_blockchain::Result<Option<SegmentIndex>> {
+        let Some(bytes) = self.aux_store.get_aux(Self::CURSOR_KEY)? else {
+            return Ok(None);
+        };
+
+        let cursor = SegmentIndex::decode(&mut bytes.as_slice()).map_err(|error| {
+            sp_blockchain::Error::Backend(format!(
+                "Failed to decode object mappings cursor: {error}"
+            ))
+        })?;
+
+        Ok(Some(cursor))
+    }
+
+    /// Store object mappings of archived segment, advancing cursor to it if it directly follows the
+    /// cursor.
+    ///
+    /// Mappings and cursor are stored atomically.
+    pub fn add_archived_segment(
+        &self,
+        archived_segment: &NewArchivedSegment,
+    ) -> sp_blockchain::Result<()> {
+        let segment_index = archived_segment.segment_header.segment_index();
+        let maybe_cursor = self.cursor()?;
+        let advance_cursor = match maybe_cursor {
+            Some(cursor) => {
+                if segment_index > cursor + SegmentIndex::ONE {
+                    warn!(
+                        %cursor,
+                        %segment_index,
+                        "Object mappings of segments in between were not archived by this node, \
+                        object mappings cursor will not advance"
+                    );
+                }
+
+                segment_index == cursor + SegmentIndex::ONE
+            }
+            None => true,
+        };
+
+        let objects = archived_segment
+            .global_object_mappings()
+            .map(|object| {
+                (
+                    Self::key(&object.hash),
+                    CompactGlobalObject::from(object).encode(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let cursor = advance_cursor.then(|| segment_index.encode());
+
+        let insert_data = objects
+            .iter()
+            .map(|(key, value)| (key.as_slice(), value.as_slice()))
+            .chain(
+                cursor
+                    .as_ref()
+                    .map(|cursor| (Self::CURSOR_KEY, cursor.as_slice())),
+            )
+            .collect::<Vec<_>>();
+
+        self.aux_store.insert_aux(&insert_data, &[])?;
+
+        debug!(
+            %segment_index,
+            count = %objects.len(),
+            "Stored object mappings of archived segment"
+        );
+
+        Ok(())
+    }
+
+    fn key(hash: &Blake3Hash) -> Vec<u8> {
+        (Self::KEY_PREFIX, hash).encode()
+    }
+}
+
 /// Notification with block header hash that needs to be signed and sender for signature.
 #[derive(Debug, Clone)]
 pub struct ArchivedSegmentNotification {
@@ -522,6 +652,7 @@ where
 
 fn initialize_archiver<Block, Client, AS>(
     segment_headers_store: &SegmentHeadersStore<AS>,
+    maybe_object_mappings_store: Option<&ObjectMappingsStore<AS>>,
     subspace_link: &SubspaceLink<Block>,
     client: &Client,
 ) -> sp_blockchain::Result<InitializedArchiver<Block>>
@@ -674,6 +805,11 @@ where
 
                 let archived_segments =
                     archiver.add_block(encoded_block, block_object_mappings, false);
+                if let Some(object_mappings_store) = maybe_object_mappings_store {
+                    for archived_segment in &archived_segments {
+                        object_mappings_store.add_archived_segment(archived_segment)?;
+                    }
+                }
                 let new_segment_headers: Vec<SegmentHeader> = archived_segments
                     .iter()
                     .map(|archived_segment| archived_segment.segment_header)
@@ -756,11 +892,15 @@ fn finalize_block<Block, Backend, Client>(
 /// will be sent and archiver will be paused until all receivers have provided an acknowledgement
 /// for it.
 ///
+/// When `maybe_object_mappings_store` is provided, object mappings of archived segments are stored
+/// in it before corresponding segment headers.
+///
 /// Archiving will be incremental during normal operation to decrease impact on block import and
 /// non-incremental heavily parallel during sync process since parallel implementation is more
 /// efficient overall and during sync only total sync time matters.
 pub fn create_subspace_archiver<Block, Backend, Client, AS, SO>(
     segment_headers_store: SegmentHeadersStore<AS>,
+    maybe_object_mappings_store: Option<ObjectMappingsStore<AS>>,
     subspace_link: SubspaceLink<Block>,
     client: Arc<Client>,
     sync_oracle: SubspaceSyncOracle<SO>,
@@ -785,6 +925,7 @@ where
     let maybe_archiver = if segment_headers_store.max_segment_index().is_none() {
         Some(initialize_archiver(
             &segment_headers_store,
+            maybe_object_mappings_store.as_ref(),
             &subspace_link,
             client.as_ref(),
         )?)
@@ -800,7 +941,12 @@ where
     Ok(async move {
         let archiver = match maybe_archiver {
             Some(archiver) => archiver,
-            None => initialize_archiver(&segment_headers_store, &subspace_link, client.as_ref())?,
+            None => initialize_archiver(
+                &segment_headers_store,
+                maybe_object_mappings_store.as_ref(),
+                &subspace_link,
+                client.as_ref(),
+            )?,
         };
         let confirmation_depth_k = subspace_link.chain_constants.confirmation_depth_k().into();
 
@@ -864,7 +1010,12 @@ where
                 InitializedArchiver {
                     archiver,
                     best_archived_block: (best_archived_block_hash, best_archived_block_number),
-                } = initialize_archiver(&segment_headers_store, &subspace_link, client.as_ref())?;
+                } = initialize_archiver(
+                    &segment_headers_store,
+                    maybe_object_mappings_store.as_ref(),
+                    &subspace_link,
+                    client.as_ref(),
+                )?;
 
                 if best_archived_block_number + One::one() == block_number_to_archive {
                     // As expected, can continue now
@@ -898,6 +1049,7 @@ where
             (best_archived_block_hash, best_archived_block_number) = archive_block(
                 &mut archiver,
                 segment_headers_store.clone(),
+                maybe_object_mappings_store.as_ref(),
                 &*client,
                 &sync_oracle,
                 telemetry.clone(),
@@ -917,6 +1069,7 @@ where
 async fn archive_block<Block, Backend, Client, AS, SO>(
     archiver: &mut Archiver,
     segment_headers_store: SegmentHeadersStore<AS>,
+    maybe_object_mappings_store: Option<&ObjectMappingsStore<AS>>,
     client: &Client,
     sync_oracle: &SubspaceSyncOracle<SO>,
     telemetry: Option<TelemetryHandle>,
@@ -991,6 +1144,9 @@ where
     ) {
         let segment_header = archived_segment.segment_header;
 
+        if let Some(object_mappings_store) = maybe_object_mappings_store {
+            object_mappings_store.add_archived_segment(&archived_segment)?;
+        }
         segment_headers_store.add_segment_headers(slice::from_ref(&segment_header))?;
 
         send_archived_segment_notification(&archived_segment_notification_sender, archived_segment)
@@ -1,11 +1,17 @@
-use crate::archiver::SegmentHeadersStore;
+use crate::archiver::{ObjectMappingsStore, SegmentHeadersStore};
 use parking_lot::RwLock;
 use sc_client_api::AuxStore;
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
+use subspace_archiving::archiver::{Archiver, NewArchivedSegment};
+use subspace_core_primitives::crypto::kzg::{embedded_kzg_settings, Kzg};
+use subspace_core_primitives::objects::{BlockObject, BlockObjectMapping};
 use subspace_core_primitives::{
-    ArchivedBlockProgress, LastArchivedBlock, SegmentHeader, SegmentIndex,
+    ArchivedBlockProgress, Blake3Hash, LastArchivedBlock, Record, RecordedHistorySegment,
+    SegmentHeader, SegmentIndex,
 };
+use subspace_erasure_coding::ErasureCoding;
 
 struct MemAuxStore {
     store: RwLock<HashMap<Vec<u8>, Vec<u8>>>,
@@ -166,3 +172,105 @@ fn segment_headers_store_block_number_queries_work() {
     let result = segment_headers.segment_headers_for_block(907u32);
     assert_eq!(result, vec![segment_header3, segment_header4]);
 }
+
+/// Archive a block that contains a single object at the very beginning, returns the first segment
+fn archived_segment_with_object(hash: Blake3Hash) -> NewArchivedSegment {
+    let erasure_coding = ErasureCoding::new(
+        NonZeroUsize::new(Record::NUM_S_BUCKETS.next_power_of_two().ilog2() as usize)
+            .expect("Not zero; qed"),
+    )
+    .unwrap();
+    let mut archiver = Archiver::new(Kzg::new(embedded_kzg_settings()), erasure_coding);
+
+    let block_object_mapping = BlockObjectMapping::V0 {
+        objects: vec![BlockObject { hash, offset: 0 }],
+    };
+    archiver
+        .add_block(
+            vec![1; RecordedHistorySegment::SIZE],
+            block_object_mapping,
+            false,
+        )
+        .into_iter()
+        .next()
+        .unwrap()
+}
+
+/// The same archived segment, but with a different segment index
+fn with_segment_index(
+    archived_segment: &NewArchivedSegment,
+    segment_index: SegmentIndex,
+) -> NewArchivedSegment {
+    let mut archived_segment = archived_segment.clone();
+    archived_segment.segment_header = SegmentHeader::V0 {
+        segment_index,
+        segment_commitment: archived_segment.segment_header.segment_commitment(),
+        prev_segment_header_hash: Default::default(),
+        last_archived_block: archived_segment.segment_header.last_archived_block(),
+    };
+    archived_segment
+}
+
+#[test]
+fn object_mappings_store() {
+    let object_mappings_store = ObjectMappingsStore::new(Arc::new(MemAuxStore::new()));
+    let hash = Blake3Hash::from([1; Blake3Hash::SIZE]);
+    let archived_segment = archived_segment_with_object(hash);
+    let object = archived_segment.global_object_mappings().next().unwrap();
+    assert_eq!(object.hash, hash);
+
+    assert_eq!(object_mappings_store.cursor().unwrap(), None);
+    assert_eq!(
+        object_mappings_store.get_object_mapping(&hash).unwrap(),
+        None
+    );
+
+    object_mappings_store
+        .add_archived_segment(&archived_segment)
+        .unwrap();
+    assert_eq!(
+        object_mappings_store.cursor().unwrap(),
+        Some(SegmentIndex::ZERO)
+    );
+    assert_eq!(
+        object_mappings_store.get_object_mapping(&hash).unwrap(),
+        Some(object)
+    );
+    assert_eq!(
+        object_mappings_store
+            .get_object_mapping(&Blake3Hash::from([2; Blake3Hash::SIZE]))
+            .unwrap(),
+        None
+    );
+
+    // Mappings of segment after a gap are stored, but cursor doesn't move past the gap
+    let segment_2 = with_segment_index(&archived_segment, SegmentIndex::from(2));
+    object_mappings_store
+        .add_archived_segment(&segment_2)
+        .unwrap();
+    assert_eq!(
+        object_mappings_store.cursor().unwrap(),
+        Some(SegmentIndex::ZERO)
+    );
+    assert_eq!(
+        object_mappings_store.get_object_mapping(&hash).unwrap(),
+        segment_2.global_object_mappings().next()
+    );
+
+    object_mappings_store
+        .add_archived_segment(&with_segment_index(&archived_segment, SegmentIndex::ONE))
+        .unwrap();
+    assert_eq!(
+        object_mappings_store.cursor().unwrap(),
+        Some(SegmentIndex::ONE)
+    );
+
+    // Segment archived again after restart doesn't move cursor back
+    object_mappings_store
+        .add_archived_segment(&archived_segment)
+        .unwrap();
+    assert_eq!(
+        object_mappings_store.cursor().unwrap(),
+        Some(SegmentIndex::ONE)
+    );
+}
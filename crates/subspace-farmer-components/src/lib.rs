@@ -24,51 +24,15 @@ pub mod sector;
 mod segment_reconstruction;
 
 use crate::file_ext::FileExt;
-use async_trait::async_trait;
 use parity_scale_codec::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 use static_assertions::const_assert;
-use std::error::Error;
 use std::fs::File;
 use std::future::Future;
 use std::io;
-use std::sync::Arc;
-use subspace_core_primitives::{ArchivedHistorySegment, HistorySize, Piece, PieceIndex};
-
-/// Trait representing a way to get pieces
-#[async_trait]
-pub trait PieceGetter {
-    /// Get piece by index
-    async fn get_piece(
-        &self,
-        piece_index: PieceIndex,
-    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>>;
-}
+use subspace_core_primitives::HistorySize;
 
-#[async_trait]
-impl<T> PieceGetter for Arc<T>
-where
-    T: PieceGetter + Send + Sync,
-{
-    async fn get_piece(
-        &self,
-        piece_index: PieceIndex,
-    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
-        self.as_ref().get_piece(piece_index).await
-    }
-}
-
-#[async_trait]
-impl PieceGetter for ArchivedHistorySegment {
-    async fn get_piece(
-        &self,
-        piece_index: PieceIndex,
-    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
-        let position = usize::try_from(u64::from(piece_index))?;
-
-        Ok(self.pieces().nth(position))
-    }
-}
+pub use subspace_data_retrieval::piece_fetcher::PieceGetter;
 
 /// Enum to encapsulate the selection between [`ReadAtSync`] and [`ReadAtAsync]` variants
 #[derive(Debug, Copy, Clone)]
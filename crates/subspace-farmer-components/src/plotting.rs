@@ -20,6 +20,7 @@ use futures::StreamExt;
 use parity_scale_codec::{Decode, Encode};
 use parking_lot::Mutex;
 use rayon::prelude::*;
+use std::collections::HashMap;
 use std::simd::Simd;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -27,7 +28,7 @@ use std::time::Duration;
 use subspace_core_primitives::crypto::kzg::Kzg;
 use subspace_core_primitives::crypto::{blake3_hash, blake3_hash_parallel, Scalar};
 use subspace_core_primitives::{
-    Blake3Hash, HistorySize, PieceIndex, PieceOffset, PosSeed, PublicKey, Record, SBucket,
+    Blake3Hash, HistorySize, Piece, PieceIndex, PieceOffset, PosSeed, PublicKey, Record, SBucket,
     SectorId, SectorIndex,
 };
 use subspace_erasure_coding::ErasureCoding;
@@ -157,7 +158,7 @@ pub async fn plot_sector<RE, PG>(
 ) -> Result<PlottedSector, PlottingError>
 where
     RE: RecordsEncoder,
-    PG: PieceGetter,
+    PG: PieceGetter + Sync,
 {
     let PlotSectorOptions {
         public_key,
@@ -248,7 +249,7 @@ pub async fn download_sector<PG>(
     options: DownloadSectorOptions<'_, PG>,
 ) -> Result<DownloadedSector, PlottingError>
 where
-    PG: PieceGetter,
+    PG: PieceGetter + Sync,
 {
     let DownloadSectorOptions {
         public_key,
@@ -722,86 +723,123 @@ fn record_encoding<PosTable>(
         });
 }
 
-async fn download_sector_internal<PG: PieceGetter>(
+async fn download_sector_internal<PG>(
     raw_sector: &mut RawSector,
     piece_getter: &PG,
     kzg: &Kzg,
     erasure_coding: &ErasureCoding,
     piece_indexes: &mut [Option<PieceIndex>],
-) -> Result<(), PlottingError> {
+) -> Result<(), PlottingError>
+where
+    PG: PieceGetter + Sync,
+{
     // TODO: Make configurable, likely allowing user to specify RAM usage expectations and inferring
     //  concurrency from there
     let recovery_semaphore = Semaphore::new(RECONSTRUCTION_CONCURRENCY_LIMIT);
 
-    let mut pieces_receiving_futures = piece_indexes
-        .iter_mut()
-        .zip(raw_sector.records.iter_mut().zip(&mut raw_sector.metadata))
-        .map(|(maybe_piece_index, (record, metadata))| async {
-            // We skip pieces that we have already processed previously
-            let Some(piece_index) = *maybe_piece_index else {
-                return Ok(());
+    // The same piece might be present in the sector more than once, so there might be multiple
+    // positions for each piece index. We skip pieces that we have already processed previously.
+    let mut piece_positions = HashMap::<PieceIndex, Vec<usize>>::new();
+    for (position, maybe_piece_index) in piece_indexes.iter().enumerate() {
+        if let Some(piece_index) = maybe_piece_index {
+            piece_positions
+                .entry(*piece_index)
+                .or_default()
+                .push(position);
+        }
+    }
+
+    {
+        let mut pieces_stream = piece_getter.get_pieces(piece_positions.keys().copied().collect());
+
+        while let Some((piece_index, piece_result)) = pieces_stream.next().await {
+            let piece = match piece_result {
+                Ok(Some(piece)) => piece,
+                Ok(None) => {
+                    trace!(%piece_index, "Piece not found, will try to recover it");
+                    continue;
+                }
+                Err(error) => {
+                    trace!(%error, %piece_index, "Failed to download piece, will try to recover it");
+                    continue;
+                }
             };
 
-            let mut piece_result = piece_getter.get_piece(piece_index).await;
-
-            let succeeded = piece_result
-                .as_ref()
-                .map(|piece| piece.is_some())
-                .unwrap_or_default();
-
-            // All retries failed
-            if !succeeded {
-                let _permit = match recovery_semaphore.acquire().await {
-                    Ok(permit) => permit,
-                    Err(error) => {
-                        let error = format!("Recovery semaphore was closed: {error}").into();
-                        return Err(PlottingError::FailedToRetrievePiece { piece_index, error });
-                    }
-                };
-                let recovered_piece = recover_missing_piece(
-                    piece_getter,
-                    kzg.clone(),
-                    erasure_coding.clone(),
-                    piece_index,
-                )
-                .await;
-
-                piece_result = recovered_piece.map(Some).map_err(Into::into);
+            if let Some(positions) = piece_positions.remove(&piece_index) {
+                store_piece(raw_sector, piece_indexes, &positions, &piece);
             }
+        }
+    }
 
-            let piece = piece_result
-                .map_err(|error| PlottingError::FailedToRetrievePiece { piece_index, error })?
-                .ok_or(PlottingError::PieceNotFound { piece_index })?;
-
-            // Fancy way to insert value in order to avoid going through stack (if naive de-referencing
-            // is used) and potentially causing stack overflow as the result
-            record
-                .as_flattened_mut()
-                .copy_from_slice(piece.record().as_flattened());
-            *metadata = RecordMetadata {
-                commitment: *piece.commitment(),
-                witness: *piece.witness(),
-                piece_checksum: blake3_hash(piece.as_ref()),
+    // Pieces that were not retrieved are recovered from other pieces of the same segment
+    let mut pieces_recovering_futures = piece_positions
+        .into_iter()
+        .map(|(piece_index, positions)| async {
+            let _permit = match recovery_semaphore.acquire().await {
+                Ok(permit) => permit,
+                Err(error) => {
+                    let error = format!("Recovery semaphore was closed: {error}").into();
+                    return Err(PlottingError::FailedToRetrievePiece { piece_index, error });
+                }
             };
+            let piece = recover_missing_piece(
+                piece_getter,
+                kzg.clone(),
+                erasure_coding.clone(),
+                piece_index,
+            )
+            .await
+            .map_err(|error| PlottingError::FailedToRetrievePiece {
+                piece_index,
+                error: error.into(),
+            })?;
 
-            // We have processed this piece index, clear it
-            maybe_piece_index.take();
-
-            Ok(())
+            Ok((piece, positions))
         })
         .collect::<FuturesUnordered<_>>();
 
     let mut final_result = Ok(());
 
-    while let Some(result) = pieces_receiving_futures.next().await {
-        if let Err(error) = result {
-            trace!(%error, "Failed to download piece");
+    while let Some(result) = pieces_recovering_futures.next().await {
+        match result {
+            Ok((piece, positions)) => {
+                store_piece(raw_sector, piece_indexes, &positions, &piece);
+            }
+            Err(error) => {
+                trace!(%error, "Failed to download piece");
 
-            if final_result.is_ok() {
-                final_result = Err(error);
+                if final_result.is_ok() {
+                    final_result = Err(error);
+                }
             }
         }
     }
 
     final_result
 }
+
+/// Store piece at specified positions of the raw sector and clear corresponding piece indexes
+fn store_piece(
+    raw_sector: &mut RawSector,
+    piece_indexes: &mut [Option<PieceIndex>],
+    positions: &[usize],
+    piece: &Piece,
+) {
+    let metadata = RecordMetadata {
+        commitment: *piece.commitment(),
+        witness: *piece.witness(),
+        piece_checksum: blake3_hash(piece.as_ref()),
+    };
+
+    for &position in positions {
+        // Fancy way to insert value in order to avoid going through stack (if naive de-referencing
+        // is used) and potentially causing stack overflow as the result
+        raw_sector.records[position]
+            .as_flattened_mut()
+            .copy_from_slice(piece.record().as_flattened());
+        raw_sector.metadata[position] = metadata.clone();
+
+        // We have processed this piece index, clear it
+        piece_indexes[position].take();
+    }
+}
@@ -1,9 +1,16 @@
 pub(crate) mod benchmark;
 pub(crate) mod cluster;
+pub(crate) mod export_history;
 pub(crate) mod farm;
+#[cfg(unix)]
+pub(crate) mod farm_control;
+pub(crate) mod identities;
 mod info;
-mod scrub;
+pub(crate) mod migrate_farm;
+pub(crate) mod resize;
+#[cfg(unix)]
+pub(crate) mod reward_signer;
+pub(crate) mod scrub;
 mod shared;
 
 pub(crate) use info::info;
-pub(crate) use scrub::scrub;
@@ -11,17 +11,22 @@ use crate::utils::shutdown_signal;
 use anyhow::anyhow;
 use async_nats::ServerAddr;
 use backoff::ExponentialBackoff;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueHint};
 use futures::stream::FuturesUnordered;
 use futures::{select, FutureExt, StreamExt};
 use prometheus_client::registry::Registry;
 use std::env::current_exe;
-use std::mem;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::{fs, mem};
 use subspace_farmer::cluster::nats_client::NatsClient;
+use subspace_farmer::cluster::transport::tcp::TcpTransport;
+use subspace_farmer::cluster::transport::REQUEST_TIMEOUT;
 use subspace_farmer::utils::AsyncJoinOnDrop;
 use subspace_metrics::{start_prometheus_metrics_server, RegistryAdapter};
 use subspace_proof_of_space::Table;
+use zeroize::Zeroizing;
 
 /// Arguments for cluster
 #[derive(Debug, Parser)]
@@ -50,8 +55,45 @@ struct SharedArgs {
     ///
     /// NOTE: NATS must be configured for message sizes of 2MiB or larger (1MiB is the default),
     /// which can be done by starting NATS server with config file containing `max_payload = 2MB`.
-    #[arg(long = "nats-server", required = true)]
+    #[arg(
+        long = "nats-server",
+        required_unless_present_any = ["direct_listen_on", "direct_peers"],
+        conflicts_with_all = ["direct_listen_on", "direct_peers"]
+    )]
     nats_servers: Vec<ServerAddr>,
+    /// Use direct TCP connections between cluster components instead of NATS server and listen
+    /// for connections from other components on specified addresses, can be specified multiple
+    /// times. Format: 0.0.0.0:40000
+    ///
+    /// NOTE: Messages are only exchanged between directly connected components, so every
+    /// component must either listen for or connect to (with `--direct-peer`) every other
+    /// component in the cluster, it is sufficient for connection to be established in one
+    /// direction.
+    ///
+    /// WARNING: Components and messages they exchange are authenticated with pre-shared key (see
+    /// `--direct-pre-shared-key`), but traffic is not encrypted, so these addresses must not be
+    /// exposed outside of trusted private network.
+    #[arg(long)]
+    direct_listen_on: Vec<SocketAddr>,
+    /// Address of another cluster component to connect to directly in `host:port` format, can be
+    /// specified multiple times. See `--direct-listen-on` for details.
+    #[arg(long = "direct-peer")]
+    direct_peers: Vec<String>,
+    /// Pre-shared key used by cluster components to authenticate each other when using direct TCP
+    /// connections, must be the same for all components of the cluster.
+    ///
+    /// Prefer environment variable or `--direct-pre-shared-key-file` over command line argument,
+    /// since command line is visible to other users of the system.
+    #[arg(
+        long,
+        env = "SUBSPACE_FARMER_CLUSTER_PRE_SHARED_KEY",
+        hide_env_values = true
+    )]
+    direct_pre_shared_key: Option<String>,
+    /// Path to the file with pre-shared key (trailing newline is ignored), alternative to
+    /// `--direct-pre-shared-key`
+    #[arg(long, value_hint = ValueHint::FilePath, conflicts_with = "direct_pre_shared_key")]
+    direct_pre_shared_key_file: Option<PathBuf>,
     /// Defines endpoints for the prometheus metrics server. It doesn't start without at least
     /// one specified endpoint. Format: 127.0.0.1:8080
     #[arg(long, aliases = ["metrics-endpoint", "metrics-endpoints"])]
@@ -94,20 +136,67 @@ where
     } = cluster_args;
     let SharedArgs {
         nats_servers,
+        direct_listen_on,
+        direct_peers,
+        direct_pre_shared_key,
+        direct_pre_shared_key_file,
         prometheus_listen_on,
     } = shared_args;
     let ClusterSubcommands { mut subcommand } = subcommands;
 
-    let nats_client = NatsClient::new(
-        nats_servers,
-        ExponentialBackoff {
-            max_elapsed_time: None,
-            ..ExponentialBackoff::default()
-        },
-    )
-    .await
-    .map_err(|error| anyhow!("Failed to connect to NATS server: {error}"))?;
+    let request_retry_backoff_policy = ExponentialBackoff {
+        max_elapsed_time: None,
+        ..ExponentialBackoff::default()
+    };
     let mut registry = Registry::with_prefix("subspace_farmer");
+    let nats_client = if nats_servers.is_empty() {
+        let pre_shared_key = match (direct_pre_shared_key, direct_pre_shared_key_file) {
+            (Some(pre_shared_key), _) => Zeroizing::new(pre_shared_key),
+            (None, Some(path)) => {
+                let mut contents = Zeroizing::new(fs::read_to_string(&path).map_err(|error| {
+                    anyhow!(
+                        "Failed to read pre-shared key file {}: {error}",
+                        path.display()
+                    )
+                })?);
+                let pre_shared_key_length = contents.trim_end_matches(['\r', '\n']).len();
+                contents.truncate(pre_shared_key_length);
+                contents
+            }
+            (None, None) => {
+                return Err(anyhow!(
+                    "Pre-shared key is required for direct cluster connections, use \
+                    `--direct-pre-shared-key` or `--direct-pre-shared-key-file`"
+                ));
+            }
+        };
+        if pre_shared_key.is_empty() {
+            return Err(anyhow!("Pre-shared key must not be empty"));
+        }
+
+        let transport = TcpTransport::new(
+            &direct_listen_on,
+            direct_peers,
+            pre_shared_key.as_bytes(),
+            REQUEST_TIMEOUT,
+        )
+        .await
+        .map_err(|error| anyhow!("Failed to start direct cluster transport: {error}"))?;
+
+        NatsClient::from_transport(
+            Arc::new(transport),
+            request_retry_backoff_policy,
+            Some(&mut registry),
+        )?
+    } else {
+        NatsClient::new(
+            nats_servers,
+            request_retry_backoff_policy,
+            Some(&mut registry),
+        )
+        .await
+        .map_err(|error| anyhow!("Failed to connect to NATS server: {error}"))?
+    };
 
     let mut tasks = FuturesUnordered::new();
 
@@ -4,6 +4,7 @@ mod farms;
 use crate::commands::cluster::controller::caches::maintain_caches;
 use crate::commands::cluster::controller::farms::{maintain_farms, FarmIndex};
 use crate::commands::shared::derive_libp2p_keypair;
+use crate::commands::shared::identity::IdentityPassphraseArgs;
 use crate::commands::shared::network::{configure_network, NetworkArgs};
 use anyhow::anyhow;
 use async_lock::RwLock as AsyncRwLock;
@@ -77,6 +78,9 @@ pub(super) struct ControllerArgs {
     /// Run temporary controller identity
     #[arg(long, conflicts_with = "base_path")]
     tmp: bool,
+    /// Identity encryption options
+    #[clap(flatten)]
+    identity_passphrase_args: IdentityPassphraseArgs,
     /// Additional cluster components
     #[clap(raw = true)]
     pub(super) additional_components: Vec<String>,
@@ -96,6 +100,7 @@ pub(super) async fn controller(
         mut network_args,
         dev,
         tmp,
+        identity_passphrase_args,
         additional_components: _,
     } = controller_args;
 
@@ -129,7 +134,8 @@ pub(super) async fn controller(
         .await
         .map_err(|error| anyhow!("Failed to get farmer app info: {error}"))?;
 
-    let identity = Identity::open_or_create(&base_path)
+    let identity_passphrase = identity_passphrase_args.passphrase()?;
+    let identity = Identity::open_or_create(&base_path, identity_passphrase.as_ref())
         .map_err(|error| anyhow!("Failed to open or create identity: {error}"))?;
     let keypair = derive_libp2p_keypair(identity.secret_key());
     let peer_id = keypair.public().to_peer_id();
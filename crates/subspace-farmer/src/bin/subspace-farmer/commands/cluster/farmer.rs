@@ -1,5 +1,9 @@
 //! Metrics specific for single disk farm
 
+use crate::commands::farm::PLOTTING_RETRY_INTERVAL;
+use crate::commands::shared::identity::{
+    IdentityPassphraseArgs, MasterIdentityArgs, RemoteRewardSignerArgs,
+};
 use crate::commands::shared::DiskFarm;
 use anyhow::anyhow;
 use async_lock::Mutex as AsyncMutex;
@@ -26,6 +30,7 @@ use subspace_farmer::cluster::plotter::ClusterPlotter;
 use subspace_farmer::farm::Farm;
 use subspace_farmer::node_client::caching_proxy_node_client::CachingProxyNodeClient;
 use subspace_farmer::node_client::NodeClient;
+use subspace_farmer::plotter::pool::{PlotterLocation, PoolPlotter};
 use subspace_farmer::single_disk_farm::{
     SingleDiskFarm, SingleDiskFarmError, SingleDiskFarmOptions,
 };
@@ -112,6 +117,15 @@ pub(super) struct FarmerArgs {
     /// beyond number of CPU cores doesn't make sense and will likely hurt performance instead.
     #[arg(long, default_value = "32")]
     service_instances: NonZeroUsize,
+    /// Identity encryption options
+    #[clap(flatten)]
+    identity_passphrase_args: IdentityPassphraseArgs,
+    /// Remote reward signer options
+    #[clap(flatten)]
+    remote_reward_signer_args: RemoteRewardSignerArgs,
+    /// Master identity options
+    #[clap(flatten)]
+    master_identity_args: MasterIdentityArgs,
     /// Additional cluster components
     #[clap(raw = true)]
     pub(super) additional_components: Vec<String>,
@@ -137,9 +151,17 @@ where
         create,
         exit_on_farm_error,
         service_instances,
+        identity_passphrase_args,
+        remote_reward_signer_args,
+        master_identity_args,
         additional_components: _,
     } = farmer_args;
 
+    let identity_passphrase = identity_passphrase_args.passphrase()?;
+    let reward_signer = remote_reward_signer_args.reward_signer();
+    let master_identity =
+        master_identity_args.master_identity(identity_passphrase.as_ref(), create)?;
+
     let tmp_directory = if let Some(plot_size) = tmp {
         let tmp_directory = tempfile::Builder::new()
             .prefix("subspace-farmer-")
@@ -211,13 +233,19 @@ where
         .unwrap_or_else(recommended_number_of_farming_threads);
 
     let global_mutex = Arc::default();
-    let plotter = Arc::new(ClusterPlotter::new(
+    let cluster_plotter = ClusterPlotter::new(
         nats_client.clone(),
         sector_encoding_concurrency,
         ExponentialBackoff {
             max_elapsed_time: None,
             ..ExponentialBackoff::default()
         },
+    );
+    // Wrapped into pool plotter, such that sectors are rescheduled when plotter they were sent to
+    // goes away
+    let plotter = Arc::new(PoolPlotter::new(
+        vec![(Box::new(cluster_plotter), PlotterLocation::Remote)],
+        PLOTTING_RETRY_INTERVAL,
     ));
 
     let farms = {
@@ -243,6 +271,9 @@ where
                     Arc::clone(&faster_read_sector_record_chunks_mode_barrier);
                 let faster_read_sector_record_chunks_mode_concurrency =
                     Arc::clone(&faster_read_sector_record_chunks_mode_concurrency);
+                let identity_passphrase = identity_passphrase.clone();
+                let reward_signer = reward_signer.clone();
+                let master_identity = master_identity.clone();
 
                 async move {
                     let farm_fut = SingleDiskFarm::new::<_, PosTable>(
@@ -268,6 +299,9 @@ where
                             faster_read_sector_record_chunks_mode_concurrency,
                             registry: Some(registry),
                             create,
+                            identity_passphrase,
+                            reward_signer,
+                            master_identity,
                         },
                         farm_index,
                     );
@@ -1,4 +1,4 @@
-use crate::commands::shared::PlottingThreadPriority;
+use crate::commands::shared::{PlottingCheckpointOptions, PlottingThreadPriority};
 use anyhow::anyhow;
 use async_lock::Mutex as AsyncMutex;
 use clap::Parser;
@@ -14,12 +14,13 @@ use subspace_erasure_coding::ErasureCoding;
 use subspace_farmer::cluster::controller::ClusterPieceGetter;
 use subspace_farmer::cluster::nats_client::NatsClient;
 use subspace_farmer::cluster::plotter::plotter_service;
+use subspace_farmer::plotter::checkpoint::PlottingCheckpoints;
 use subspace_farmer::plotter::cpu::CpuPlotter;
 #[cfg(feature = "cuda")]
 use subspace_farmer::plotter::gpu::cuda::CudaRecordsEncoder;
 #[cfg(feature = "_gpu")]
 use subspace_farmer::plotter::gpu::GpuPlotter;
-use subspace_farmer::plotter::pool::PoolPlotter;
+use subspace_farmer::plotter::pool::{PlotterLocation, PoolPlotter};
 use subspace_farmer::plotter::Plotter;
 use subspace_farmer::utils::{
     create_plotting_thread_pool_manager, parse_cpu_cores_sets, thread_pool_core_indices,
@@ -109,6 +110,9 @@ pub(super) struct PlotterArgs {
     /// are not processed quickly enough for some reason.
     #[arg(long, default_value = "32")]
     piece_getter_concurrency: NonZeroUsize,
+    /// Plotting checkpoint options
+    #[clap(flatten)]
+    plotting_checkpoint_options: PlottingCheckpointOptions,
     /// Plotting options only used by CPU plotter
     #[clap(flatten)]
     cpu_plotting_options: CpuPlottingOptions,
@@ -131,6 +135,7 @@ where
 {
     let PlotterArgs {
         piece_getter_concurrency,
+        plotting_checkpoint_options,
         cpu_plotting_options,
         #[cfg(feature = "cuda")]
         cuda_plotting_options,
@@ -147,7 +152,9 @@ where
 
     let global_mutex = Arc::default();
 
-    let mut plotters = Vec::<Box<dyn Plotter + Send + Sync>>::new();
+    let plotting_checkpoints = plotting_checkpoint_options.open()?;
+
+    let mut plotters = Vec::<(Box<dyn Plotter + Send + Sync>, PlotterLocation)>::new();
 
     #[cfg(feature = "cuda")]
     {
@@ -157,11 +164,12 @@ where
             Arc::clone(&global_mutex),
             kzg.clone(),
             erasure_coding.clone(),
+            plotting_checkpoints.clone(),
             registry,
         )?;
 
         if let Some(cuda_plotter) = maybe_cuda_plotter {
-            plotters.push(Box::new(cuda_plotter));
+            plotters.push((Box::new(cuda_plotter), PlotterLocation::Local));
         }
     }
     {
@@ -172,6 +180,7 @@ where
             global_mutex,
             kzg,
             erasure_coding,
+            plotting_checkpoints,
             registry,
         )?;
 
@@ -179,7 +188,7 @@ where
             if !plotters.is_empty() && cpu_sector_encoding_concurrency.is_none() {
                 info!("CPU plotting was disabled due to detected faster plotting with GPU");
             } else {
-                plotters.push(Box::new(cpu_plotter));
+                plotters.push((Box::new(cpu_plotter), PlotterLocation::Local));
             }
         }
     }
@@ -199,6 +208,7 @@ fn init_cpu_plotter<PG, PosTable>(
     global_mutex: Arc<AsyncMutex<()>>,
     kzg: Kzg,
     erasure_coding: ErasureCoding,
+    plotting_checkpoints: Option<PlottingCheckpoints>,
     registry: &mut Registry,
 ) -> anyhow::Result<Option<CpuPlotter<PG, PosTable>>>
 where
@@ -289,6 +299,7 @@ where
         global_mutex,
         kzg,
         erasure_coding,
+        plotting_checkpoints,
         Some(registry),
     );
 
@@ -302,6 +313,7 @@ fn init_cuda_plotter<PG>(
     global_mutex: Arc<AsyncMutex<()>>,
     kzg: Kzg,
     erasure_coding: ErasureCoding,
+    plotting_checkpoints: Option<PlottingCheckpoints>,
     registry: &mut Registry,
 ) -> anyhow::Result<Option<GpuPlotter<PG, CudaRecordsEncoder>>>
 where
@@ -371,6 +383,7 @@ where
             global_mutex,
             kzg,
             erasure_coding,
+            plotting_checkpoints,
             Some(registry),
         )
         .map_err(|error| anyhow::anyhow!("Failed to initialize CUDA plotter: {error}"))?,
@@ -0,0 +1,240 @@
+use crate::commands::shared::dsn_client::{dsn_piece_fetcher, DsnClientArgs};
+use crate::utils::shutdown_signal;
+use anyhow::anyhow;
+use clap::{Parser, ValueHint};
+use futures::{select, FutureExt};
+use std::collections::HashSet;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use subspace_archiving::reconstructor::Reconstructor;
+use subspace_core_primitives::crypto::kzg::{embedded_kzg_settings, Kzg};
+use subspace_core_primitives::{Record, SegmentHeader, SegmentIndex};
+use subspace_data_retrieval::exported_history::{
+    history_files, write_history_file, ExportedSegment,
+};
+use subspace_data_retrieval::piece_fetcher::PieceGetter;
+use subspace_data_retrieval::segment_fetcher::{SegmentFetcher, SegmentStore};
+use subspace_erasure_coding::ErasureCoding;
+use subspace_farmer::node_client::rpc_node_client::RpcNodeClient;
+use subspace_farmer::node_client::{NodeClient, NodeClientExt};
+use tracing::info;
+
+/// Directory inside export directory where pieces of segments being downloaded are stored.
+const SEGMENT_STORE_DIRECTORY: &str = "segment-store";
+
+/// Arguments for history export
+#[derive(Debug, Parser)]
+pub(crate) struct ExportHistoryArgs {
+    /// Directory where history files are written, created if doesn't exist.
+    ///
+    /// If directory already contains history files, export resumes from the first segment that
+    /// wasn't exported yet.
+    #[arg(value_hint = ValueHint::DirPath)]
+    directory: PathBuf,
+    /// WebSocket RPC URL of the Subspace node to connect to
+    #[arg(long, value_hint = ValueHint::Url, default_value = "ws://127.0.0.1:9944")]
+    node_rpc_url: String,
+    /// Index of the first segment to export
+    #[arg(long, default_value_t = 0)]
+    from_segment: u64,
+    /// Index of the last segment to export, defaults to the last segment archived by the node
+    #[arg(long)]
+    to_segment: Option<u64>,
+    /// DSN client configuration
+    #[clap(flatten)]
+    dsn_client_args: DsnClientArgs,
+}
+
+pub(crate) async fn export_history(
+    ExportHistoryArgs {
+        directory,
+        node_rpc_url,
+        from_segment,
+        to_segment,
+        dsn_client_args,
+    }: ExportHistoryArgs,
+) -> anyhow::Result<()> {
+    if let Some(to_segment) = to_segment
+        && to_segment < from_segment
+    {
+        return Err(anyhow!(
+            "Last segment {to_segment} must not be smaller than the first segment {from_segment}"
+        ));
+    }
+
+    fs::create_dir_all(&directory).map_err(|error| {
+        anyhow!(
+            "Directory {} doesn't exist and can't be created: {error}",
+            directory.display()
+        )
+    })?;
+
+    info!(url = %node_rpc_url, "Connecting to node RPC");
+    let node_client = RpcNodeClient::new(&node_rpc_url)
+        .await
+        .map_err(|error| anyhow!("Failed to connect to node RPC: {error}"))?;
+
+    let farmer_app_info = node_client
+        .farmer_app_info()
+        .await
+        .map_err(|error| anyhow!("Failed to get farmer app info: {error}"))?;
+
+    let kzg = Kzg::new(embedded_kzg_settings());
+    let erasure_coding = ErasureCoding::new(
+        NonZeroUsize::new(Record::NUM_S_BUCKETS.next_power_of_two().ilog2() as usize)
+            .expect("Not zero; qed"),
+    )
+    .map_err(|error| anyhow!("Failed to instantiate erasure coding: {error}"))?;
+    let (piece_fetcher, mut node_runner) = dsn_piece_fetcher(
+        dsn_client_args,
+        &farmer_app_info,
+        node_client.clone(),
+        kzg.clone(),
+    )?;
+
+    let segment_store = SegmentStore::open(&directory.join(SEGMENT_STORE_DIRECTORY))
+        .map_err(|error| anyhow!("Failed to open segment store: {error}"))?;
+    let segment_fetcher =
+        SegmentFetcher::new(piece_fetcher, segment_store, kzg, erasure_coding.clone());
+
+    let export_fut = export_segments(
+        &directory,
+        &node_client,
+        &segment_fetcher,
+        erasure_coding,
+        SegmentIndex::from(from_segment),
+        to_segment.map(SegmentIndex::from),
+    );
+
+    select! {
+        result = export_fut.fuse() => {
+            result?;
+        },
+        _ = node_runner.run().fuse() => {
+            info!("DSN network runner exited.");
+        },
+        _ = shutdown_signal().fuse() => {
+            info!("Received shutdown signal, exiting...");
+        },
+    }
+
+    Ok(())
+}
+
+async fn export_segments<NC, PG>(
+    directory: &Path,
+    node_client: &NC,
+    segment_fetcher: &SegmentFetcher<PG>,
+    erasure_coding: ErasureCoding,
+    from_segment: SegmentIndex,
+    to_segment: Option<SegmentIndex>,
+) -> anyhow::Result<()>
+where
+    NC: NodeClientExt,
+    PG: PieceGetter + Send + Sync,
+{
+    let to_segment = match to_segment {
+        Some(to_segment) => to_segment,
+        None => node_client
+            .last_segment_headers(1)
+            .await
+            .map_err(|error| anyhow!("Failed to get last segment header: {error}"))?
+            .into_iter()
+            .flatten()
+            .next()
+            .ok_or_else(|| anyhow!("Node didn't archive any segments yet"))?
+            .segment_index(),
+    };
+
+    let exported_segments = history_files(directory)
+        .map_err(|error| anyhow!("Failed to read history files: {error}"))?
+        .into_iter()
+        .map(|(segment_index, _path)| segment_index)
+        .collect::<HashSet<_>>();
+    let Some(first_segment) = (from_segment..=to_segment)
+        .find(|segment_index| !exported_segments.contains(segment_index))
+    else {
+        info!(%from_segment, %to_segment, "All segments were already exported");
+        return Ok(());
+    };
+
+    let mut reconstructor = Reconstructor::new(erasure_coding);
+
+    if first_segment > SegmentIndex::ZERO {
+        let previous_segment = first_segment - SegmentIndex::ONE;
+        info!(
+            %previous_segment,
+            "Fetching previous segment to restore partial block"
+        );
+
+        // Beginning of the block that continues in the first segment is in the previous segment,
+        // reconstructor needs to see it in order to return that block. This is needed both when
+        // resuming export and when starting from a non-genesis segment.
+        let previous_segment_header = segment_header(node_client, previous_segment).await?;
+        segment_fetcher
+            .fetch_segment(&previous_segment_header, &mut reconstructor)
+            .await?;
+        segment_fetcher
+            .store()
+            .remove_segment(previous_segment)
+            .await?;
+    }
+
+    info!(%first_segment, %to_segment, "Exporting segments");
+
+    for segment_index in first_segment..=to_segment {
+        let segment_header = segment_header(node_client, segment_index).await?;
+
+        let reconstructed_contents = segment_fetcher
+            .fetch_segment(&segment_header, &mut reconstructor)
+            .await?;
+
+        let blocks = reconstructed_contents.blocks.len();
+        let path = tokio::task::spawn_blocking({
+            let directory = directory.to_path_buf();
+            let exported_segment = ExportedSegment {
+                segment_header,
+                blocks: reconstructed_contents.blocks,
+            };
+
+            move || write_history_file(&directory, &exported_segment)
+        })
+        .await?
+        .map_err(|error| anyhow!("Failed to write history file: {error}"))?;
+
+        segment_fetcher
+            .store()
+            .remove_segment(segment_index)
+            .await?;
+
+        info!(
+            %segment_index,
+            %blocks,
+            path = %path.display(),
+            "Segment exported"
+        );
+    }
+
+    info!("Export finished");
+
+    Ok(())
+}
+
+/// Get segment header from the node, pieces of the segment are verified against it
+async fn segment_header<NC>(
+    node_client: &NC,
+    segment_index: SegmentIndex,
+) -> anyhow::Result<SegmentHeader>
+where
+    NC: NodeClient,
+{
+    node_client
+        .segment_headers(vec![segment_index])
+        .await
+        .map_err(|error| anyhow!("Failed to get segment header: {error}"))?
+        .into_iter()
+        .next()
+        .flatten()
+        .ok_or_else(|| anyhow!("Segment header {segment_index} wasn't found on node"))
+}
@@ -1,5 +1,17 @@
+mod admin;
+mod control;
+
+use crate::commands::farm::admin::start_admin_server;
+use crate::commands::farm::control::{print_farm_info, FarmOptions, FarmsController};
+#[cfg(unix)]
+use crate::commands::shared::bind_unix_socket;
+use crate::commands::shared::identity::{
+    IdentityPassphraseArgs, MasterIdentityArgs, RemoteRewardSignerArgs,
+};
 use crate::commands::shared::network::{configure_network, NetworkArgs};
-use crate::commands::shared::{derive_libp2p_keypair, DiskFarm, PlottingThreadPriority};
+use crate::commands::shared::{
+    derive_libp2p_keypair, DiskFarm, PlottingCheckpointOptions, PlottingThreadPriority,
+};
 use crate::utils::shutdown_signal;
 use anyhow::anyhow;
 use async_lock::{Mutex as AsyncMutex, RwLock as AsyncRwLock};
@@ -14,6 +26,7 @@ use prometheus_client::registry::Registry;
 use std::fs;
 use std::net::SocketAddr;
 use std::num::{NonZeroU8, NonZeroUsize};
+use std::path::PathBuf;
 use std::pin::pin;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -22,24 +35,24 @@ use subspace_core_primitives::crypto::kzg::{embedded_kzg_settings, Kzg};
 use subspace_core_primitives::{PublicKey, Record};
 use subspace_erasure_coding::ErasureCoding;
 use subspace_farmer::farm::plotted_pieces::PlottedPieces;
-use subspace_farmer::farm::{PlottedSectors, SectorPlottingDetails, SectorUpdate};
+#[cfg(unix)]
+use subspace_farmer::farm_control::unix_socket::serve;
 use subspace_farmer::farmer_cache::FarmerCache;
 use subspace_farmer::farmer_piece_getter::piece_validator::SegmentCommitmentPieceValidator;
 use subspace_farmer::farmer_piece_getter::{DsnCacheRetryPolicy, FarmerPieceGetter};
 use subspace_farmer::node_client::caching_proxy_node_client::CachingProxyNodeClient;
 use subspace_farmer::node_client::rpc_node_client::RpcNodeClient;
 use subspace_farmer::node_client::NodeClient;
+use subspace_farmer::plotter::checkpoint::PlottingCheckpoints;
 use subspace_farmer::plotter::cpu::CpuPlotter;
 #[cfg(feature = "cuda")]
 use subspace_farmer::plotter::gpu::cuda::CudaRecordsEncoder;
 #[cfg(feature = "_gpu")]
 use subspace_farmer::plotter::gpu::GpuPlotter;
-use subspace_farmer::plotter::pool::PoolPlotter;
+use subspace_farmer::plotter::pool::{PlotterLocation, PoolPlotter};
+use subspace_farmer::plotter::schedule::{AuditThrottle, PlottingSchedule, PlottingWindow};
 use subspace_farmer::plotter::Plotter;
 use subspace_farmer::single_disk_farm::identity::Identity;
-use subspace_farmer::single_disk_farm::{
-    SingleDiskFarm, SingleDiskFarmError, SingleDiskFarmOptions,
-};
 use subspace_farmer::utils::ss58::parse_ss58_reward_address;
 use subspace_farmer::utils::{
     create_plotting_thread_pool_manager, parse_cpu_cores_sets,
@@ -53,6 +66,7 @@ use subspace_networking::utils::piece_provider::PieceProvider;
 use subspace_proof_of_space::Table;
 use tokio::sync::{Barrier, Semaphore};
 use tracing::{error, info, info_span, warn, Instrument};
+use zeroize::Zeroizing;
 
 /// Get piece retry attempts number.
 const PIECE_GETTER_MAX_RETRIES: u16 = 7;
@@ -63,13 +77,12 @@ const GET_PIECE_MAX_INTERVAL: Duration = Duration::from_secs(40);
 /// NOTE: for large gaps between the plotted part and the end of the file plot cache will result in
 /// very long period of writing zeroes on Windows, see https://stackoverflow.com/q/78058306/3806795
 const MAX_SPACE_PLEDGED_FOR_PLOT_CACHE_ON_WINDOWS: u64 = 7 * 1024 * 1024 * 1024 * 1024;
-const FARM_ERROR_PRINT_INTERVAL: Duration = Duration::from_secs(30);
-const PLOTTING_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+pub(super) const PLOTTING_RETRY_INTERVAL: Duration = Duration::from_secs(5);
 
 type CacheIndex = u8;
 
 #[derive(Debug, Parser)]
-struct CpuPlottingOptions {
+pub(super) struct CpuPlottingOptions {
     /// Defines how many sectors farmer will download concurrently, allows to limit memory usage of
     /// the plotting process, defaults to `--cpu-sector-encoding-concurrency` + 1 to download future
     /// sector ahead of time.
@@ -143,7 +156,7 @@ struct CpuPlottingOptions {
 
 #[cfg(feature = "cuda")]
 #[derive(Debug, Parser)]
-struct CudaPlottingOptions {
+pub(super) struct CudaPlottingOptions {
     /// Defines how many sectors farmer will download concurrently during plotting with CUDA GPU,
     /// allows to limit memory usage of the plotting process, defaults to number of CUDA GPUs found
     /// + 1 to download future sector ahead of time.
@@ -214,6 +227,23 @@ pub(crate) struct FarmingArgs {
     /// one specified endpoint. Format: 127.0.0.1:8080
     #[arg(long, aliases = ["metrics-endpoint", "metrics-endpoints"])]
     prometheus_listen_on: Vec<SocketAddr>,
+    /// Defines endpoints for the admin HTTP API server, which exposes status of farms, plotting and
+    /// farmer cache and allows to pause/resume plotting and scrub farms. It doesn't start without
+    /// at least one specified endpoint. Format: 127.0.0.1:8081
+    ///
+    /// WARNING: Use loopback addresses only. Status is available without authentication and
+    /// traffic is not encrypted, so API must never be exposed on public or untrusted networks.
+    /// Actions are only allowed from loopback addresses (with loopback IP address as host and not
+    /// from browsers) unless `--admin-token` is specified.
+    #[arg(long)]
+    admin_listen_on: Vec<SocketAddr>,
+    /// Token required in `Authorization: Bearer <token>` header for admin API actions (pausing and
+    /// resuming plotting, scrubbing farms), allows actions from non-loopback addresses.
+    ///
+    /// Prefer environment variable over command line argument, since command line is visible to
+    /// other users of the system.
+    #[arg(long, env = "SUBSPACE_FARMER_ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
     /// Piece getter concurrency.
     ///
     /// Increase will result in higher memory usage.
@@ -225,6 +255,25 @@ pub(crate) struct FarmingArgs {
     /// not more than 32 threads
     #[arg(long)]
     farming_thread_pool_size: Option<NonZeroUsize>,
+    /// Plotting checkpoint options
+    #[clap(flatten)]
+    plotting_checkpoint_options: PlottingCheckpointOptions,
+    /// Time windows during which plotting is allowed in `HH:MM-HH:MM` format (UTC), can be
+    /// specified multiple times.
+    ///
+    /// Window that ends before it starts wraps around midnight. Plotting is allowed at any time
+    /// if not specified.
+    ///
+    /// Example:
+    ///   --plotting-schedule 22:00-06:00 --plotting-schedule 12:00-13:00
+    #[arg(long)]
+    plotting_schedule: Vec<PlottingWindow>,
+    /// Do not pause plotting automatically when farming audits approach farming deadline.
+    ///
+    /// By default plotting is paused when auditing takes half of farming timeout or more and
+    /// resumed once it takes less than a quarter of farming timeout again.
+    #[arg(long)]
+    disable_plotting_throttling: bool,
     /// Plotting options only used by CPU plotter
     #[clap(flatten)]
     cpu_plotting_options: CpuPlottingOptions,
@@ -254,9 +303,26 @@ pub(crate) struct FarmingArgs {
     /// By default, farmer will continue running if there are still other working farms.
     #[arg(long)]
     exit_on_farm_error: bool,
+    /// Path to Unix socket for controlling farms of the running farmer, existing socket at this
+    /// path will be replaced.
+    ///
+    /// Allows to attach and detach farms without restarting the farmer with `farm-control`
+    /// command, socket is only accessible by the user running the farmer.
+    #[cfg(unix)]
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    control_socket: Option<PathBuf>,
+    /// Identity encryption options
+    #[clap(flatten)]
+    identity_passphrase_args: IdentityPassphraseArgs,
+    /// Remote reward signer options
+    #[clap(flatten)]
+    remote_reward_signer_args: RemoteRewardSignerArgs,
+    /// Master identity options
+    #[clap(flatten)]
+    master_identity_args: MasterIdentityArgs,
 }
 
-fn cache_percentage_parser(s: &str) -> anyhow::Result<NonZeroU8> {
+pub(super) fn cache_percentage_parser(s: &str) -> anyhow::Result<NonZeroU8> {
     let cache_percentage = NonZeroU8::from_str(s)?;
 
     if cache_percentage.get() > 99 {
@@ -285,8 +351,13 @@ where
         tmp,
         mut disk_farms,
         prometheus_listen_on,
+        admin_listen_on,
+        admin_token,
         piece_getter_concurrency,
         farming_thread_pool_size,
+        plotting_checkpoint_options,
+        plotting_schedule,
+        disable_plotting_throttling,
         cpu_plotting_options,
         #[cfg(feature = "cuda")]
         cuda_plotting_options,
@@ -294,6 +365,11 @@ where
         disable_farm_locking,
         create,
         exit_on_farm_error,
+        #[cfg(unix)]
+        control_socket,
+        identity_passphrase_args,
+        remote_reward_signer_args,
+        master_identity_args,
     } = farming_args;
 
     let plot_cache = plot_cache.unwrap_or_else(|| {
@@ -325,6 +401,11 @@ where
         if disk_farms.is_empty() {
             return Err(anyhow!("There must be at least one disk farm provided"));
         }
+        if disk_farms.len() > usize::from(u8::MAX) + 1 {
+            return Err(anyhow!(
+                "More than 256 farms are not supported, consider running multiple farmer instances"
+            ));
+        }
 
         for farm in &disk_farms {
             if !farm.directory.exists() {
@@ -357,11 +438,16 @@ where
         .expect("Disk farm collection is not be empty as checked above; qed")
         .directory;
 
+    let identity_passphrase = identity_passphrase_args.passphrase()?;
+    let reward_signer = remote_reward_signer_args.reward_signer();
+    let master_identity =
+        master_identity_args.master_identity(identity_passphrase.as_ref(), create)?;
+
     let identity = if create {
-        Identity::open_or_create(first_farm_directory)
+        Identity::open_or_create(first_farm_directory, identity_passphrase.as_ref())
             .map_err(|error| anyhow!("Failed to open or create identity: {error}"))?
     } else {
-        Identity::open(first_farm_directory)
+        Identity::open(first_farm_directory, identity_passphrase.as_ref())
             .map_err(|error| anyhow!("Failed to open identity of the first farm: {error}"))?
             .ok_or_else(|| {
                 anyhow!(
@@ -467,43 +553,66 @@ where
         .unwrap_or_else(recommended_number_of_farming_threads);
     let global_mutex = Arc::default();
 
-    let mut plotters = Vec::<Box<dyn Plotter + Send + Sync>>::new();
+    let plotting_checkpoints = plotting_checkpoint_options.open()?;
 
-    #[cfg(feature = "cuda")]
-    {
-        let maybe_cuda_plotter = init_cuda_plotter(
-            cuda_plotting_options,
-            piece_getter.clone(),
-            Arc::clone(&global_mutex),
-            kzg.clone(),
-            erasure_coding.clone(),
-            &mut registry,
-        )?;
+    let plotters = init_local_plotters::<_, PosTable>(
+        cpu_plotting_options,
+        #[cfg(feature = "cuda")]
+        cuda_plotting_options,
+        piece_getter.clone(),
+        Arc::clone(&global_mutex),
+        kzg.clone(),
+        erasure_coding.clone(),
+        plotting_checkpoints.clone(),
+        &mut registry,
+    )?;
+    let plotter = Arc::new(PoolPlotter::new(plotters, PLOTTING_RETRY_INTERVAL));
 
-        if let Some(cuda_plotter) = maybe_cuda_plotter {
-            plotters.push(Box::new(cuda_plotter));
-        }
-    }
-    {
-        let cpu_sector_encoding_concurrency = cpu_plotting_options.cpu_sector_encoding_concurrency;
-        let maybe_cpu_plotter = init_cpu_plotter::<_, PosTable>(
-            cpu_plotting_options,
-            piece_getter.clone(),
-            Arc::clone(&global_mutex),
-            kzg.clone(),
-            erasure_coding.clone(),
-            &mut registry,
-        )?;
+    let _plotting_schedule_worker = if plotting_schedule.is_empty() {
+        None
+    } else {
+        info!(
+            "Plotting is limited to schedule (UTC): {}",
+            plotting_schedule
+                .iter()
+                .map(PlottingWindow::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
 
-        if let Some(cpu_plotter) = maybe_cpu_plotter {
-            if !plotters.is_empty() && cpu_sector_encoding_concurrency.is_none() {
-                info!("CPU plotting was disabled due to detected faster plotting with GPU");
-            } else {
-                plotters.push(Box::new(cpu_plotter));
-            }
-        }
-    }
-    let plotter = Arc::new(PoolPlotter::new(plotters, PLOTTING_RETRY_INTERVAL));
+        // Schedule is applied right away, such that farms don't start plotting outside of it
+        let plotting_schedule_fut =
+            PlottingSchedule::new(plotting_schedule).run(Arc::clone(&plotter));
+
+        Some(AsyncJoinOnDrop::new(
+            tokio::spawn(plotting_schedule_fut),
+            true,
+        ))
+    };
+    let audit_throttle = (!disable_plotting_throttling).then(|| {
+        Arc::new(AuditThrottle::new(
+            Arc::clone(&plotter),
+            farmer_app_info.farming_timeout,
+        ))
+    });
+
+    let farm_options = FarmOptions {
+        farmer_app_info,
+        max_pieces_in_sector,
+        node_client,
+        reward_address,
+        plotter: Arc::clone(&plotter) as Arc<dyn Plotter + Send + Sync>,
+        kzg,
+        erasure_coding,
+        cache_percentage,
+        farming_thread_pool_size,
+        global_mutex,
+        disable_farm_locking,
+        create,
+        identity_passphrase,
+        reward_signer,
+        master_identity,
+    };
 
     let (farms, plotting_delay_senders) = {
         let info_mutex = &AsyncMutex::new(());
@@ -514,92 +623,42 @@ where
             .map(|_| oneshot::channel())
             .unzip::<_, _, Vec<_>, Vec<_>>();
         let registry = &Mutex::new(&mut registry);
+        let farm_options = &farm_options;
 
         let mut farms = Vec::with_capacity(disk_farms.len());
-        let mut farms_stream = disk_farms
-            .into_iter()
-            .zip(plotting_delay_receivers)
-            .enumerate()
+        let mut farms_stream = (0u8..)
+            .zip(disk_farms.into_iter().zip(plotting_delay_receivers))
             .map(|(farm_index, (disk_farm, plotting_delay_receiver))| {
-                let node_client = node_client.clone();
-                let farmer_app_info = farmer_app_info.clone();
-                let kzg = kzg.clone();
-                let erasure_coding = erasure_coding.clone();
-                let plotter = Arc::clone(&plotter);
-                let global_mutex = Arc::clone(&global_mutex);
                 let faster_read_sector_record_chunks_mode_barrier =
                     Arc::clone(&faster_read_sector_record_chunks_mode_barrier);
                 let faster_read_sector_record_chunks_mode_concurrency =
                     Arc::clone(&faster_read_sector_record_chunks_mode_concurrency);
 
                 async move {
-                    let farm_fut = SingleDiskFarm::new::<_, PosTable>(
-                        SingleDiskFarmOptions {
-                            directory: disk_farm.directory.clone(),
-                            farmer_app_info,
-                            allocated_space: disk_farm.allocated_space,
-                            max_pieces_in_sector,
-                            node_client,
-                            reward_address,
-                            plotter,
-                            kzg,
-                            erasure_coding,
-                            cache_percentage: cache_percentage.get(),
-                            farming_thread_pool_size,
-                            plotting_delay: Some(plotting_delay_receiver),
-                            global_mutex,
-                            disable_farm_locking,
-                            read_sector_record_chunks_mode: disk_farm
-                                .read_sector_record_chunks_mode,
+                    let farm = match farm_options
+                        .create_farm::<PosTable>(
+                            farm_index,
+                            &disk_farm,
+                            Some(plotting_delay_receiver),
                             faster_read_sector_record_chunks_mode_barrier,
                             faster_read_sector_record_chunks_mode_concurrency,
-                            registry: Some(registry),
-                            create,
-                        },
-                        farm_index,
-                    );
-
-                    let farm = match farm_fut.await {
+                            Some(registry),
+                        )
+                        .await
+                    {
                         Ok(farm) => farm,
-                        Err(SingleDiskFarmError::InsufficientAllocatedSpace {
-                            min_space,
-                            allocated_space,
-                        }) => {
-                            return (
-                                farm_index,
-                                Err(anyhow!(
-                                    "Allocated space {} ({}) is not enough, minimum is ~{} (~{}, \
-                                    {} bytes to be exact)",
-                                    bytesize::to_string(allocated_space, true),
-                                    bytesize::to_string(allocated_space, false),
-                                    bytesize::to_string(min_space, true),
-                                    bytesize::to_string(min_space, false),
-                                    min_space
-                                )),
-                            );
-                        }
                         Err(error) => {
-                            return (farm_index, Err(error.into()));
+                            return (farm_index, Err(error));
                         }
                     };
 
                     if !no_info {
                         let _info_guard = info_mutex.lock().await;
 
-                        let info = farm.info();
-                        info!("Farm {farm_index}:");
-                        info!("  ID: {}", info.id());
-                        info!("  Genesis hash: 0x{}", hex::encode(info.genesis_hash()));
-                        info!("  Public key: 0x{}", hex::encode(info.public_key()));
-                        info!(
-                            "  Allocated space: {} ({})",
-                            bytesize::to_string(info.allocated_space(), true),
-                            bytesize::to_string(info.allocated_space(), false)
-                        );
-                        info!("  Directory: {}", disk_farm.directory.display());
+                        print_farm_info(farm_index, &farm, &disk_farm.directory);
                     }
 
-                    (farm_index, Ok(farm))
+                    (farm_index, Ok((disk_farm, farm)))
                 }
                 .instrument(info_span!("", %farm_index))
             })
@@ -618,15 +677,11 @@ where
         // Restore order after unordered initialization
         farms.sort_unstable_by_key(|(farm_index, _farm)| *farm_index);
 
-        let farms = farms
-            .into_iter()
-            .map(|(_farm_index, farm)| farm)
-            .collect::<Vec<_>>();
-
         (farms, plotting_delay_senders)
     };
 
-    {
+    let _plotting_delay_worker = {
+        let (farmer_cache_synced_sender, farmer_cache_synced_receiver) = oneshot::channel::<()>();
         let handler_id = Arc::new(Mutex::new(None));
         // Wait for piece cache to read already cached contents before starting plotting to improve
         // cache hit ratio
@@ -634,103 +689,89 @@ where
             .lock()
             .replace(farmer_cache.on_sync_progress(Arc::new({
                 let handler_id = Arc::clone(&handler_id);
-                let plotting_delay_senders = Mutex::new(plotting_delay_senders);
+                let farmer_cache_synced_sender = Mutex::new(Some(farmer_cache_synced_sender));
 
                 move |_progress| {
-                    for plotting_delay_sender in plotting_delay_senders.lock().drain(..) {
+                    if let Some(farmer_cache_synced_sender) =
+                        farmer_cache_synced_sender.lock().take()
+                    {
                         // Doesn't matter if receiver is gone
-                        let _ = plotting_delay_sender.send(());
+                        let _ = farmer_cache_synced_sender.send(());
                     }
 
                     // Unsubscribe from this event
                     handler_id.lock().take();
                 }
             })));
-    }
-    farmer_cache
-        .replace_backing_caches(
-            farms
-                .iter()
-                .map(|farm| Arc::new(farm.piece_cache()) as Arc<_>)
-                .collect(),
-            if plot_cache {
-                farms
-                    .iter()
-                    .map(|farm| Arc::new(farm.plot_cache()) as Arc<_>)
-                    .collect()
-            } else {
-                Vec::new()
-            },
+
+        let plotter = Arc::clone(&plotter);
+        AsyncJoinOnDrop::new(
+            tokio::spawn(async move {
+                if farmer_cache_synced_receiver.await.is_err() {
+                    return;
+                }
+
+                // Farms don't start plotting while plotting is paused (for example, outside of
+                // plotting schedule)
+                plotter.wait_resumed().await;
+
+                for plotting_delay_sender in plotting_delay_senders {
+                    // Doesn't matter if receiver is gone
+                    let _ = plotting_delay_sender.send(());
+                }
+            }),
+            true,
         )
-        .await;
-    drop(farmer_cache);
+    };
+
+    let (farms_controller, farms_fut) = FarmsController::<_, PosTable>::new(
+        farm_options,
+        farmer_cache,
+        plot_cache,
+        plotted_pieces,
+        audit_throttle,
+        exit_on_farm_error,
+    );
 
     info!("Collecting already plotted pieces (this will take some time)...");
 
     // Collect already plotted pieces
-    for (farm_index, farm) in farms.iter().enumerate() {
-        let mut plotted_pieces = plotted_pieces.write().await;
-        let farm_index = farm_index.try_into().map_err(|_error| {
-            anyhow!(
-                "More than 256 plots are not supported, consider running multiple farmer \
-                instances"
-            )
-        })?;
-
-        plotted_pieces.add_farm(farm_index, Arc::new(farm.piece_reader()));
-
-        let plotted_sectors = farm.plotted_sectors();
-        let mut plotted_sectors = plotted_sectors.get().await.map_err(|error| {
-            anyhow!("Failed to get plotted sectors for farm {farm_index}: {error}")
-        })?;
-
-        while let Some(plotted_sector_result) = plotted_sectors.next().await {
-            plotted_pieces.add_sector(
-                farm_index,
-                &plotted_sector_result.map_err(|error| {
-                    anyhow!(
-                        "Failed reading plotted sector on startup for farm {farm_index}: {error}"
-                    )
-                })?,
-            )
-        }
+    for (farm_index, (disk_farm, farm)) in farms {
+        farms_controller
+            .add_farm(farm_index, disk_farm, farm)
+            .await?;
     }
 
     info!("Finished collecting already plotted pieces successfully");
 
-    let mut farms_stream = (0u8..)
-        .zip(farms)
-        .map(|(farm_index, farm)| {
-            let plotted_pieces = Arc::clone(&plotted_pieces);
-            let span = info_span!("", %farm_index);
-
-            farm.on_sector_update(Arc::new(move |(_sector_index, sector_state)| {
-                // Collect newly plotted pieces
-                if let SectorUpdate::Plotting(SectorPlottingDetails::Finished {
-                    plotted_sector,
-                    old_plotted_sector,
-                    time: _,
-                }) = sector_state
-                {
-                    let _span_guard = span.enter();
-
-                    let mut plotted_pieces = plotted_pieces.write_blocking();
-
-                    if let Some(old_plotted_sector) = &old_plotted_sector {
-                        plotted_pieces.delete_sector(farm_index, old_plotted_sector);
-                    }
-                    plotted_pieces.add_sector(farm_index, plotted_sector);
-                }
-            }))
-            .detach();
+    farms_controller.update_backing_caches().await;
+
+    #[cfg(unix)]
+    let _farm_control_server = if let Some(control_socket) = &control_socket {
+        let listener = bind_unix_socket(control_socket)?;
 
-            farm.run().map(move |result| (farm_index, result))
-        })
-        .collect::<FuturesUnordered<_>>();
+        info!(socket = %control_socket.display(), "Farm control socket started");
 
-    // Drop original instance such that the only remaining instances are in `SingleDiskFarm`
-    // event handlers
-    drop(plotted_pieces);
+        Some(AsyncJoinOnDrop::new(
+            tokio::spawn(serve(listener, farms_controller.clone())),
+            true,
+        ))
+    } else {
+        None
+    };
+
+    let _admin_worker = if admin_listen_on.is_empty() {
+        None
+    } else {
+        let admin_task = start_admin_server(
+            admin_listen_on,
+            admin_token.map(Zeroizing::new),
+            farms_controller.clone(),
+            plotter,
+        )?;
+
+        Some(AsyncJoinOnDrop::new(tokio::spawn(admin_task), true))
+    };
 
     let _prometheus_worker = if should_start_prometheus_server {
         let prometheus_task = start_prometheus_metrics_server(
@@ -744,43 +785,7 @@ where
         None
     };
 
-    let mut farm_errors = Vec::new();
-
-    let farm_fut = run_future_in_dedicated_thread(
-        move || async move {
-            while let Some((farm_index, result)) = farms_stream.next().await {
-                match result {
-                    Ok(()) => {
-                        info!(%farm_index, "Farm exited successfully");
-                    }
-                    Err(error) => {
-                        error!(%farm_index, %error, "Farm exited with error");
-
-                        if farms_stream.is_empty() || exit_on_farm_error {
-                            return Err(error);
-                        } else {
-                            farm_errors.push(AsyncJoinOnDrop::new(
-                                tokio::spawn(async move {
-                                    loop {
-                                        tokio::time::sleep(FARM_ERROR_PRINT_INTERVAL).await;
-
-                                        error!(
-                                            %farm_index,
-                                            %error,
-                                            "Farm errored and stopped"
-                                        );
-                                    }
-                                }),
-                                true,
-                            ))
-                        }
-                    }
-                }
-            }
-            anyhow::Ok(())
-        },
-        "farmer-farm".to_string(),
-    )?;
+    let farm_fut = run_future_in_dedicated_thread(move || farms_fut, "farmer-farm".to_string())?;
 
     let networking_fut = run_future_in_dedicated_thread(
         move || async move { node_runner.run().await },
@@ -816,9 +821,72 @@ where
         },
     }
 
+    #[cfg(unix)]
+    if let Some(control_socket) = &control_socket {
+        let _ = fs::remove_file(control_socket);
+    }
+
     anyhow::Ok(())
 }
 
+/// Create local plotters according to plotting options, GPU plotters come first
+#[allow(clippy::too_many_arguments)]
+pub(super) fn init_local_plotters<PG, PosTable>(
+    cpu_plotting_options: CpuPlottingOptions,
+    #[cfg(feature = "cuda")] cuda_plotting_options: CudaPlottingOptions,
+    piece_getter: PG,
+    global_mutex: Arc<AsyncMutex<()>>,
+    kzg: Kzg,
+    erasure_coding: ErasureCoding,
+    plotting_checkpoints: Option<PlottingCheckpoints>,
+    registry: &mut Registry,
+) -> anyhow::Result<Vec<(Box<dyn Plotter + Send + Sync>, PlotterLocation)>>
+where
+    PG: PieceGetter + Clone + Send + Sync + 'static,
+    PosTable: Table,
+{
+    let mut plotters = Vec::<(Box<dyn Plotter + Send + Sync>, PlotterLocation)>::new();
+
+    #[cfg(feature = "cuda")]
+    {
+        let maybe_cuda_plotter = init_cuda_plotter(
+            cuda_plotting_options,
+            piece_getter.clone(),
+            Arc::clone(&global_mutex),
+            kzg.clone(),
+            erasure_coding.clone(),
+            plotting_checkpoints.clone(),
+            registry,
+        )?;
+
+        if let Some(cuda_plotter) = maybe_cuda_plotter {
+            plotters.push((Box::new(cuda_plotter), PlotterLocation::Local));
+        }
+    }
+    {
+        let cpu_sector_encoding_concurrency = cpu_plotting_options.cpu_sector_encoding_concurrency;
+        let maybe_cpu_plotter = init_cpu_plotter::<_, PosTable>(
+            cpu_plotting_options,
+            piece_getter,
+            global_mutex,
+            kzg,
+            erasure_coding,
+            plotting_checkpoints,
+            registry,
+        )?;
+
+        if let Some(cpu_plotter) = maybe_cpu_plotter {
+            if !plotters.is_empty() && cpu_sector_encoding_concurrency.is_none() {
+                info!("CPU plotting was disabled due to detected faster plotting with GPU");
+            } else {
+                plotters.push((Box::new(cpu_plotter), PlotterLocation::Local));
+            }
+        }
+    }
+
+    Ok(plotters)
+}
+
 #[allow(clippy::type_complexity)]
 fn init_cpu_plotter<PG, PosTable>(
     cpu_plotting_options: CpuPlottingOptions,
@@ -826,6 +894,7 @@ fn init_cpu_plotter<PG, PosTable>(
     global_mutex: Arc<AsyncMutex<()>>,
     kzg: Kzg,
     erasure_coding: ErasureCoding,
+    plotting_checkpoints: Option<PlottingCheckpoints>,
     registry: &mut Registry,
 ) -> anyhow::Result<Option<CpuPlotter<PG, PosTable>>>
 where
@@ -936,6 +1005,7 @@ where
         global_mutex,
         kzg,
         erasure_coding,
+        plotting_checkpoints,
         Some(registry),
     );
 
@@ -949,6 +1019,7 @@ fn init_cuda_plotter<PG>(
     global_mutex: Arc<AsyncMutex<()>>,
     kzg: Kzg,
     erasure_coding: ErasureCoding,
+    plotting_checkpoints: Option<PlottingCheckpoints>,
     registry: &mut Registry,
 ) -> anyhow::Result<Option<GpuPlotter<PG, CudaRecordsEncoder>>>
 where
@@ -1018,6 +1089,7 @@ where
             global_mutex,
             kzg,
             erasure_coding,
+            plotting_checkpoints,
             Some(registry),
         )
         .map_err(|error| anyhow::anyhow!("Failed to initialize CUDA plotter: {error}"))?,
@@ -0,0 +1,681 @@
+//! Admin HTTP API of the farmer, exposes status of farms, plotting and farmer cache along with some
+//! actions like pausing plotting or scrubbing a farm.
+//!
+//! Actions require `Authorization: Bearer <token>` header when token is configured, otherwise they
+//! are only allowed from loopback addresses with loopback IP address in `Host` header and without
+//! `Origin` header, such that web pages opened in a browser on the same machine can't trigger them
+//! (neither directly nor through DNS rebinding).
+
+#[cfg(test)]
+mod tests;
+
+use crate::commands::farm::control::{FarmStatus, FarmsController};
+use actix_web::http::header::{AUTHORIZATION, HOST, ORIGIN};
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Data, Query};
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use subspace_core_primitives::SectorIndex;
+use subspace_farmer::farm::{
+    FarmingNotification, SectorExpirationDetails, SectorPlottingDetails, SectorUpdate,
+};
+use subspace_farmer::farm_control::FarmDetails;
+use subspace_farmer::node_client::NodeClient;
+use subspace_farmer::plotter::pool::{PauseReason, PoolPlotter};
+use subspace_farmer::single_disk_farm::{ScrubReport, ScrubTarget, SingleDiskFarmInfo};
+use subspace_proof_of_space::Table;
+use subspace_rpc_primitives::SolutionResponse;
+use tokio::runtime::Handle;
+use tracing::{error, info, info_span, warn, Instrument};
+use zeroize::Zeroizing;
+
+/// Number of recent farming notifications and solutions stored for every farm
+const RECENT_EVENTS_LIMIT: usize = 100;
+
+/// Stage of sector plotting
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) enum PlottingStage {
+    Starting,
+    Downloading,
+    Downloaded,
+    Encoding,
+    Encoded,
+    Writing,
+    Written,
+    Failed { error: String },
+}
+
+/// State of the sector as observed through sector updates
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "state")]
+pub(super) enum SectorState {
+    NotPlotted,
+    #[serde(rename_all = "camelCase")]
+    Plotted {
+        /// Segment index at which sector expires, if already known
+        expires_at: Option<u64>,
+    },
+    AboutToExpire,
+    Expired,
+    Plotting {
+        replotting: bool,
+        stage: PlottingStage,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub(super) enum FarmingNotificationDetails {
+    /// Time is in seconds
+    #[serde(rename_all = "camelCase")]
+    Auditing {
+        sectors_count: SectorIndex,
+        time: f64,
+    },
+    /// Time is in seconds
+    Proving {
+        result: String,
+        time: f64,
+    },
+    NonFatalError {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SolutionDetails {
+    slot_number: u64,
+    public_key: String,
+    sector_index: SectorIndex,
+}
+
+/// Event along with Unix timestamp (in seconds) of when it happened
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct RecentEvent<T> {
+    timestamp: u64,
+    event: T,
+}
+
+impl<T> RecentEvent<T> {
+    fn now(event: T) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            event,
+        }
+    }
+}
+
+/// Recent activity of the farm, populated from farm's event handlers
+#[derive(Debug)]
+pub(super) struct FarmActivity {
+    sectors: Mutex<Vec<SectorState>>,
+    farming_notifications: Mutex<VecDeque<RecentEvent<FarmingNotificationDetails>>>,
+    solutions: Mutex<VecDeque<RecentEvent<SolutionDetails>>>,
+}
+
+impl FarmActivity {
+    pub(super) fn new(total_sectors_count: SectorIndex) -> Self {
+        Self {
+            sectors: Mutex::new(vec![
+                SectorState::NotPlotted;
+                usize::from(total_sectors_count)
+            ]),
+            farming_notifications: Mutex::default(),
+            solutions: Mutex::default(),
+        }
+    }
+
+    /// Mark sector that was already plotted before farm started
+    pub(super) fn sector_plotted(&self, sector_index: SectorIndex) {
+        if let Some(sector_state) = self.sectors.lock().get_mut(usize::from(sector_index)) {
+            *sector_state = SectorState::Plotted { expires_at: None };
+        }
+    }
+
+    pub(super) fn on_sector_update(&self, sector_index: SectorIndex, sector_update: &SectorUpdate) {
+        let mut sectors = self.sectors.lock();
+        let Some(sector_state) = sectors.get_mut(usize::from(sector_index)) else {
+            return;
+        };
+
+        let replotting = match sector_state {
+            SectorState::Plotting { replotting, .. } => *replotting,
+            _ => false,
+        };
+        let plotting = |stage| SectorState::Plotting { replotting, stage };
+
+        *sector_state = match sector_update {
+            SectorUpdate::Plotting(plotting_details) => match plotting_details {
+                SectorPlottingDetails::Starting { replotting, .. } => SectorState::Plotting {
+                    replotting: *replotting,
+                    stage: PlottingStage::Starting,
+                },
+                SectorPlottingDetails::Downloading => plotting(PlottingStage::Downloading),
+                SectorPlottingDetails::Downloaded(_) => plotting(PlottingStage::Downloaded),
+                SectorPlottingDetails::Encoding => plotting(PlottingStage::Encoding),
+                SectorPlottingDetails::Encoded(_) => plotting(PlottingStage::Encoded),
+                SectorPlottingDetails::Writing => plotting(PlottingStage::Writing),
+                SectorPlottingDetails::Written(_) => plotting(PlottingStage::Written),
+                SectorPlottingDetails::Finished { .. } => SectorState::Plotted { expires_at: None },
+                SectorPlottingDetails::Error(error) => plotting(PlottingStage::Failed {
+                    error: error.clone(),
+                }),
+            },
+            SectorUpdate::Expiration(expiration_details) => {
+                if matches!(sector_state, SectorState::Plotting { .. }) {
+                    // Expiration of the old sector is not interesting while it is being replotted
+                    return;
+                }
+
+                match expiration_details {
+                    SectorExpirationDetails::Determined { expires_at } => SectorState::Plotted {
+                        expires_at: Some(u64::from(*expires_at)),
+                    },
+                    SectorExpirationDetails::AboutToExpire => SectorState::AboutToExpire,
+                    SectorExpirationDetails::Expired => SectorState::Expired,
+                }
+            }
+        };
+    }
+
+    pub(super) fn on_farming_notification(&self, farming_notification: &FarmingNotification) {
+        let details = match farming_notification {
+            FarmingNotification::Auditing(auditing_details) => {
+                FarmingNotificationDetails::Auditing {
+                    sectors_count: auditing_details.sectors_count,
+                    time: auditing_details.time.as_secs_f64(),
+                }
+            }
+            FarmingNotification::Proving(proving_details) => FarmingNotificationDetails::Proving {
+                result: proving_details.result.to_string(),
+                time: proving_details.time.as_secs_f64(),
+            },
+            FarmingNotification::NonFatalError(error) => {
+                FarmingNotificationDetails::NonFatalError {
+                    error: error.to_string(),
+                }
+            }
+        };
+
+        push_recent(&mut self.farming_notifications.lock(), details);
+    }
+
+    pub(super) fn on_solution(&self, solution_response: &SolutionResponse) {
+        let details = SolutionDetails {
+            slot_number: solution_response.slot_number,
+            public_key: solution_response.solution.public_key.to_string(),
+            sector_index: solution_response.solution.sector_index,
+        };
+
+        push_recent(&mut self.solutions.lock(), details);
+    }
+
+    fn sectors(&self) -> Vec<SectorState> {
+        self.sectors.lock().clone()
+    }
+
+    fn plotting_sectors(&self) -> Vec<PlottingSector> {
+        self.sectors
+            .lock()
+            .iter()
+            .zip(0..)
+            .filter_map(|(sector_state, sector_index)| match sector_state {
+                SectorState::Plotting { replotting, stage } => Some(PlottingSector {
+                    sector_index,
+                    replotting: *replotting,
+                    stage: stage.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn push_recent<T>(events: &mut VecDeque<RecentEvent<T>>, event: T) {
+    if events.len() == RECENT_EVENTS_LIMIT {
+        events.pop_front();
+    }
+    events.push_back(RecentEvent::now(event));
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FarmResponse<'a> {
+    #[serde(flatten)]
+    details: &'a FarmDetails,
+    info: &'a SingleDiskFarmInfo,
+    last_scrub_report: Option<&'a ScrubReport>,
+}
+
+impl<'a> From<&'a FarmStatus> for FarmResponse<'a> {
+    fn from(farm_status: &'a FarmStatus) -> Self {
+        Self {
+            details: &farm_status.details,
+            info: &farm_status.info,
+            last_scrub_report: farm_status.last_scrub_report.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlottingSector {
+    sector_index: SectorIndex,
+    replotting: bool,
+    stage: PlottingStage,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FarmPlottingResponse {
+    farm_index: u8,
+    plotted_sectors_count: SectorIndex,
+    total_sectors_count: SectorIndex,
+    plotting_sectors: Vec<PlottingSector>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlottingResponse {
+    paused: bool,
+    pause_reasons: Vec<PauseReason>,
+    farms: Vec<FarmPlottingResponse>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FarmerCacheResponse {
+    /// Sync progress in %
+    sync_progress: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScrubQuery {
+    /// Scrub target, `all` by default
+    target: Option<String>,
+}
+
+struct AdminState<NC, PosTable> {
+    farms_controller: FarmsController<NC, PosTable>,
+    plotter: Arc<PoolPlotter>,
+    /// Token required for actions, actions are only allowed from loopback addresses (and not from
+    /// browsers) if not set
+    token: Option<Zeroizing<String>>,
+    /// Farms are started on the main runtime rather than on HTTP server workers
+    runtime_handle: Handle,
+}
+
+impl<NC, PosTable> AdminState<NC, PosTable> {
+    /// Check whether request is allowed to perform an action, returns error response otherwise
+    fn authorize_action(&self, request: &HttpRequest) -> Result<(), HttpResponse> {
+        authorize_action(self.token.as_ref().map(|token| token.as_str()), request)
+    }
+}
+
+/// Check whether request is allowed to perform an action with provided admin token (if any),
+/// returns error response otherwise
+fn authorize_action(token: Option<&str>, request: &HttpRequest) -> Result<(), HttpResponse> {
+    match token {
+        Some(token) => {
+            let provided_token = request
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+
+            // Hashes are compared in constant time
+            if provided_token.is_some_and(|provided_token| {
+                blake3::hash(provided_token.as_bytes()) == blake3::hash(token.as_bytes())
+            }) {
+                Ok(())
+            } else {
+                Err(HttpResponse::Unauthorized().json(ErrorResponse {
+                    error: "Valid admin token is required".to_string(),
+                }))
+            }
+        }
+        None => {
+            if !request
+                .peer_addr()
+                .is_some_and(|address| address.ip().is_loopback())
+            {
+                return Err(HttpResponse::Forbidden().json(ErrorResponse {
+                    error: "Actions are only allowed from loopback addresses unless admin token \
+                        is configured"
+                        .to_string(),
+                }));
+            }
+
+            // Browsers always send `Origin` with cross-origin and `POST` requests, while DNS
+            // rebinding results in a non-IP `Host`, neither is expected from local tools
+            let host_is_loopback = request
+                .headers()
+                .get(HOST)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(is_loopback_host);
+            if request.headers().contains_key(ORIGIN) || !host_is_loopback {
+                return Err(HttpResponse::Forbidden().json(ErrorResponse {
+                    error: "Actions without admin token are only allowed with loopback IP \
+                        address in Host header and without Origin header"
+                        .to_string(),
+                }));
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Whether `Host` header value (with optional port) is a loopback IP address literal
+fn is_loopback_host(host: &str) -> bool {
+    if let Ok(address) = SocketAddr::from_str(host) {
+        return address.ip().is_loopback();
+    }
+
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+
+    IpAddr::from_str(host).is_ok_and(|address| address.is_loopback())
+}
+
+fn farm_not_found(farm: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorResponse {
+        error: format!("Farm {farm} not found"),
+    })
+}
+
+async fn farms<NC, PosTable>(state: Data<AdminState<NC, PosTable>>) -> HttpResponse
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    let farm_statuses = state.farms_controller.farm_statuses();
+
+    HttpResponse::Ok().json(
+        farm_statuses
+            .iter()
+            .map(FarmResponse::from)
+            .collect::<Vec<_>>(),
+    )
+}
+
+async fn farm<NC, PosTable>(
+    state: Data<AdminState<NC, PosTable>>,
+    farm: web::Path<String>,
+) -> HttpResponse
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    match state.farms_controller.farm_status(&farm) {
+        Some(farm_status) => HttpResponse::Ok().json(FarmResponse::from(&farm_status)),
+        None => farm_not_found(&farm),
+    }
+}
+
+async fn farm_sectors<NC, PosTable>(
+    state: Data<AdminState<NC, PosTable>>,
+    farm: web::Path<String>,
+) -> HttpResponse
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    match state.farms_controller.farm_status(&farm) {
+        Some(farm_status) => HttpResponse::Ok().json(farm_status.activity.sectors()),
+        None => farm_not_found(&farm),
+    }
+}
+
+async fn farm_farming_notifications<NC, PosTable>(
+    state: Data<AdminState<NC, PosTable>>,
+    farm: web::Path<String>,
+) -> HttpResponse
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    match state.farms_controller.farm_status(&farm) {
+        Some(farm_status) => {
+            HttpResponse::Ok().json(&*farm_status.activity.farming_notifications.lock())
+        }
+        None => farm_not_found(&farm),
+    }
+}
+
+async fn farm_solutions<NC, PosTable>(
+    state: Data<AdminState<NC, PosTable>>,
+    farm: web::Path<String>,
+) -> HttpResponse
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    match state.farms_controller.farm_status(&farm) {
+        Some(farm_status) => HttpResponse::Ok().json(&*farm_status.activity.solutions.lock()),
+        None => farm_not_found(&farm),
+    }
+}
+
+async fn scrub_farm<NC, PosTable>(
+    state: Data<AdminState<NC, PosTable>>,
+    request: HttpRequest,
+    farm: web::Path<String>,
+    query: Query<ScrubQuery>,
+) -> HttpResponse
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    if let Err(response) = state.authorize_action(&request) {
+        return response;
+    }
+
+    let target = match query.target.as_deref().map(ScrubTarget::from_str) {
+        Some(Ok(target)) => target,
+        Some(Err(error)) => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: error.to_string(),
+            });
+        }
+        None => ScrubTarget::All,
+    };
+
+    let Some(farm_status) = state.farms_controller.farm_status(&farm) else {
+        return farm_not_found(&farm);
+    };
+    let farm_index = farm_status.details.farm_index;
+
+    // Scrubbing takes a long time, so it happens in the background and report is available in farm
+    // details afterwards
+    state.runtime_handle.spawn({
+        let farms_controller = state.farms_controller.clone();
+
+        async move {
+            match farms_controller
+                .scrub_farm(&farm_index.to_string(), target)
+                .await
+            {
+                Ok(_report) => {
+                    info!("Farm scrubbed successfully");
+                }
+                Err(error) => {
+                    error!(%error, "Failed to scrub farm");
+                }
+            }
+        }
+        .instrument(info_span!("", %farm_index))
+    });
+
+    HttpResponse::Accepted().json(&farm_status.details)
+}
+
+async fn plotting<NC, PosTable>(state: Data<AdminState<NC, PosTable>>) -> HttpResponse
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    let farms = state
+        .farms_controller
+        .farm_statuses()
+        .into_iter()
+        .map(|farm_status| FarmPlottingResponse {
+            farm_index: farm_status.details.farm_index,
+            plotted_sectors_count: farm_status.details.plotted_sectors_count,
+            total_sectors_count: farm_status.details.total_sectors_count,
+            plotting_sectors: farm_status.activity.plotting_sectors(),
+        })
+        .collect();
+
+    HttpResponse::Ok().json(PlottingResponse {
+        paused: state.plotter.is_paused(),
+        pause_reasons: state.plotter.pause_reasons(),
+        farms,
+    })
+}
+
+async fn pause_plotting<NC, PosTable>(
+    state: Data<AdminState<NC, PosTable>>,
+    request: HttpRequest,
+) -> HttpResponse
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    if let Err(response) = state.authorize_action(&request) {
+        return response;
+    }
+
+    state.plotter.pause();
+
+    HttpResponse::new(StatusCode::NO_CONTENT)
+}
+
+async fn resume_plotting<NC, PosTable>(
+    state: Data<AdminState<NC, PosTable>>,
+    request: HttpRequest,
+) -> HttpResponse
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    if let Err(response) = state.authorize_action(&request) {
+        return response;
+    }
+
+    state.plotter.resume();
+
+    HttpResponse::new(StatusCode::NO_CONTENT)
+}
+
+async fn farmer_cache<NC, PosTable>(state: Data<AdminState<NC, PosTable>>) -> HttpResponse
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    HttpResponse::Ok().json(FarmerCacheResponse {
+        sync_progress: state.farms_controller.farmer_cache_sync_progress(),
+    })
+}
+
+/// Start admin HTTP API server on the provided endpoints.
+///
+/// Actions require `token` if provided, otherwise they are only allowed from loopback addresses and
+/// not from browsers.
+///
+/// Must be called from within Tokio runtime that farms will be running on.
+pub(super) fn start_admin_server<NC, PosTable>(
+    endpoints: Vec<SocketAddr>,
+    token: Option<Zeroizing<String>>,
+    farms_controller: FarmsController<NC, PosTable>,
+    plotter: Arc<PoolPlotter>,
+) -> io::Result<impl Future<Output = io::Result<()>>>
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    if token.as_ref().is_some_and(|token| token.is_empty()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Admin token must not be empty",
+        ));
+    }
+    if token.is_none()
+        && endpoints
+            .iter()
+            .any(|endpoint| !endpoint.ip().is_loopback())
+    {
+        warn!(
+            ?endpoints,
+            "Admin API is listening on non-loopback address without token, actions will only be \
+            allowed from loopback addresses"
+        );
+    }
+
+    let data = Data::new(AdminState {
+        farms_controller,
+        plotter,
+        token,
+        runtime_handle: Handle::current(),
+    });
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(data.clone())
+            .route("/farms", web::get().to(farms::<NC, PosTable>))
+            .route("/farms/{farm}", web::get().to(farm::<NC, PosTable>))
+            .route(
+                "/farms/{farm}/sectors",
+                web::get().to(farm_sectors::<NC, PosTable>),
+            )
+            .route(
+                "/farms/{farm}/farming-notifications",
+                web::get().to(farm_farming_notifications::<NC, PosTable>),
+            )
+            .route(
+                "/farms/{farm}/solutions",
+                web::get().to(farm_solutions::<NC, PosTable>),
+            )
+            .route(
+                "/farms/{farm}/scrub",
+                web::post().to(scrub_farm::<NC, PosTable>),
+            )
+            .route("/plotting", web::get().to(plotting::<NC, PosTable>))
+            .route(
+                "/plotting/pause",
+                web::post().to(pause_plotting::<NC, PosTable>),
+            )
+            .route(
+                "/plotting/resume",
+                web::post().to(resume_plotting::<NC, PosTable>),
+            )
+            .route("/farmer-cache", web::get().to(farmer_cache::<NC, PosTable>))
+    })
+    .workers(1)
+    .bind(endpoints.as_slice())?;
+
+    info!(endpoints = ?server.addrs(), "Admin API server started");
+
+    Ok(server.run())
+}
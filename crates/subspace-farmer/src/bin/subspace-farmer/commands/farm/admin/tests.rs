@@ -0,0 +1,144 @@
+use crate::commands::farm::admin::{authorize_action, is_loopback_host};
+use actix_web::http::header::{AUTHORIZATION, HOST, ORIGIN};
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use std::net::SocketAddr;
+
+const LOOPBACK: &str = "127.0.0.1:34567";
+const REMOTE: &str = "192.168.1.2:34567";
+
+fn request(peer_addr: &str, headers: &[(&str, &str)]) -> actix_web::HttpRequest {
+    let mut request = TestRequest::post()
+        .uri("/plotting/pause")
+        .peer_addr(peer_addr.parse::<SocketAddr>().unwrap());
+    for &(name, value) in headers {
+        request = request.insert_header((name, value));
+    }
+    request.to_http_request()
+}
+
+fn status(result: Result<(), actix_web::HttpResponse>) -> Option<StatusCode> {
+    result.err().map(|response| response.status())
+}
+
+#[test]
+fn loopback_host() {
+    assert!(is_loopback_host("127.0.0.1"));
+    assert!(is_loopback_host("127.0.0.1:8081"));
+    assert!(is_loopback_host("[::1]"));
+    assert!(is_loopback_host("[::1]:8081"));
+    assert!(is_loopback_host("::1"));
+
+    assert!(!is_loopback_host("localhost:8081"));
+    assert!(!is_loopback_host("attacker.example:8081"));
+    assert!(!is_loopback_host("192.168.1.2:8081"));
+    assert!(!is_loopback_host(""));
+}
+
+#[test]
+fn without_token() {
+    let host = (HOST.as_str(), "127.0.0.1:8081");
+
+    // Local tool
+    assert_eq!(
+        status(authorize_action(None, &request(LOOPBACK, &[host]))),
+        None
+    );
+    assert_eq!(
+        status(authorize_action(
+            None,
+            &request(LOOPBACK, &[(HOST.as_str(), "[::1]:8081")])
+        )),
+        None
+    );
+
+    // Remote client
+    assert_eq!(
+        status(authorize_action(None, &request(REMOTE, &[host]))),
+        Some(StatusCode::FORBIDDEN)
+    );
+
+    // Web page opened in a local browser
+    assert_eq!(
+        status(authorize_action(
+            None,
+            &request(
+                LOOPBACK,
+                &[host, (ORIGIN.as_str(), "https://attacker.example")]
+            )
+        )),
+        Some(StatusCode::FORBIDDEN)
+    );
+    assert_eq!(
+        status(authorize_action(
+            None,
+            &request(LOOPBACK, &[host, (ORIGIN.as_str(), "null")])
+        )),
+        Some(StatusCode::FORBIDDEN)
+    );
+
+    // DNS rebinding
+    assert_eq!(
+        status(authorize_action(
+            None,
+            &request(LOOPBACK, &[(HOST.as_str(), "attacker.example:8081")])
+        )),
+        Some(StatusCode::FORBIDDEN)
+    );
+
+    // No host at all
+    assert_eq!(
+        status(authorize_action(None, &request(LOOPBACK, &[]))),
+        Some(StatusCode::FORBIDDEN)
+    );
+}
+
+#[test]
+fn with_token() {
+    let token = Some("secret");
+    let authorization = (AUTHORIZATION.as_str(), "Bearer secret");
+
+    assert_eq!(
+        status(authorize_action(token, &request(REMOTE, &[authorization]))),
+        None
+    );
+    // Host and origin don't matter when token is used, browsers can't attach it to cross-origin
+    // requests without CORS approval
+    assert_eq!(
+        status(authorize_action(
+            token,
+            &request(
+                REMOTE,
+                &[
+                    authorization,
+                    (HOST.as_str(), "farmer.example:8081"),
+                    (ORIGIN.as_str(), "https://farmer.example")
+                ]
+            )
+        )),
+        None
+    );
+
+    // Loopback is not enough when token is configured
+    assert_eq!(
+        status(authorize_action(
+            token,
+            &request(LOOPBACK, &[(HOST.as_str(), "127.0.0.1:8081")])
+        )),
+        Some(StatusCode::UNAUTHORIZED)
+    );
+    assert_eq!(
+        status(authorize_action(
+            token,
+            &request(LOOPBACK, &[(AUTHORIZATION.as_str(), "Bearer wrong")])
+        )),
+        Some(StatusCode::UNAUTHORIZED)
+    );
+    assert_eq!(
+        status(authorize_action(
+            token,
+            &request(LOOPBACK, &[(AUTHORIZATION.as_str(), "secret")])
+        )),
+        Some(StatusCode::UNAUTHORIZED)
+    );
+}
@@ -0,0 +1,855 @@
+//! Management of farms in a running farmer, allows attaching, detaching and scrubbing farms at
+//! runtime
+
+use crate::commands::farm::admin::FarmActivity;
+use crate::commands::shared::DiskFarm;
+use anyhow::anyhow;
+use async_lock::{Mutex as AsyncMutex, RwLock as AsyncRwLock};
+use async_trait::async_trait;
+use event_listener_primitives::HandlerId;
+use futures::channel::{mpsc, oneshot};
+use futures::future::Either;
+use futures::stream::FuturesUnordered;
+use futures::{future, select, Future, StreamExt};
+use parking_lot::Mutex;
+use prometheus_client::registry::Registry;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::num::NonZeroU8;
+use std::path::Path;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, fs, mem};
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::PublicKey;
+use subspace_erasure_coding::ErasureCoding;
+use subspace_farmer::farm::plotted_pieces::PlottedPieces;
+use subspace_farmer::farm::{
+    FarmId, FarmingNotification, PieceCache, PlotCache, PlottedSectors, SectorPlottingDetails,
+    SectorUpdate,
+};
+use subspace_farmer::farm_control::{FarmControlError, FarmController, FarmDetails, FarmState};
+use subspace_farmer::farmer_cache::FarmerCache;
+use subspace_farmer::node_client::NodeClient;
+use subspace_farmer::plotter::schedule::AuditThrottle;
+use subspace_farmer::plotter::Plotter;
+use subspace_farmer::reward_signer::RewardSigner;
+use subspace_farmer::single_disk_farm::identity::{IdentityPassphrase, MasterIdentity};
+use subspace_farmer::single_disk_farm::{
+    ScrubReport, ScrubTarget, SingleDiskFarm, SingleDiskFarmError, SingleDiskFarmInfo,
+    SingleDiskFarmOptions,
+};
+use subspace_farmer::utils::AsyncJoinOnDrop;
+use subspace_farmer_components::FarmerAppInfo;
+use subspace_proof_of_space::Table;
+use tokio::sync::{Barrier, Semaphore};
+use tokio::task;
+use tokio::time::sleep;
+use tracing::{error, info, info_span, warn, Instrument};
+
+const FARM_ERROR_PRINT_INTERVAL: Duration = Duration::from_secs(30);
+
+type FarmFuture = Pin<Box<dyn Future<Output = (u8, Option<anyhow::Result<()>>)> + Send>>;
+
+/// Options shared by all farms of the farmer, used to create farms both on startup and when
+/// attached later
+pub(super) struct FarmOptions<NC> {
+    pub(super) farmer_app_info: FarmerAppInfo,
+    pub(super) max_pieces_in_sector: u16,
+    pub(super) node_client: NC,
+    pub(super) reward_address: PublicKey,
+    pub(super) plotter: Arc<dyn Plotter + Send + Sync>,
+    pub(super) kzg: Kzg,
+    pub(super) erasure_coding: ErasureCoding,
+    pub(super) cache_percentage: NonZeroU8,
+    pub(super) farming_thread_pool_size: usize,
+    pub(super) global_mutex: Arc<AsyncMutex<()>>,
+    pub(super) disable_farm_locking: bool,
+    pub(super) create: bool,
+    pub(super) identity_passphrase: Option<IdentityPassphrase>,
+    pub(super) reward_signer: Option<Arc<dyn RewardSigner>>,
+    pub(super) master_identity: Option<MasterIdentity>,
+}
+
+impl<NC> FarmOptions<NC>
+where
+    NC: NodeClient + Clone,
+{
+    /// Create farm in specified disk farm
+    pub(super) async fn create_farm<PosTable>(
+        &self,
+        farm_index: u8,
+        disk_farm: &DiskFarm,
+        plotting_delay: Option<oneshot::Receiver<()>>,
+        faster_read_sector_record_chunks_mode_barrier: Arc<Barrier>,
+        faster_read_sector_record_chunks_mode_concurrency: Arc<Semaphore>,
+        registry: Option<&Mutex<&mut Registry>>,
+    ) -> anyhow::Result<SingleDiskFarm>
+    where
+        PosTable: Table,
+    {
+        let farm_fut = SingleDiskFarm::new::<_, PosTable>(
+            SingleDiskFarmOptions {
+                directory: disk_farm.directory.clone(),
+                farmer_app_info: self.farmer_app_info.clone(),
+                allocated_space: disk_farm.allocated_space,
+                max_pieces_in_sector: self.max_pieces_in_sector,
+                node_client: self.node_client.clone(),
+                reward_address: self.reward_address,
+                plotter: Arc::clone(&self.plotter),
+                kzg: self.kzg.clone(),
+                erasure_coding: self.erasure_coding.clone(),
+                cache_percentage: self.cache_percentage.get(),
+                farming_thread_pool_size: self.farming_thread_pool_size,
+                plotting_delay,
+                global_mutex: Arc::clone(&self.global_mutex),
+                disable_farm_locking: self.disable_farm_locking,
+                read_sector_record_chunks_mode: disk_farm.read_sector_record_chunks_mode,
+                faster_read_sector_record_chunks_mode_barrier,
+                faster_read_sector_record_chunks_mode_concurrency,
+                registry,
+                create: self.create,
+                identity_passphrase: self.identity_passphrase.clone(),
+                reward_signer: self.reward_signer.clone(),
+                master_identity: self.master_identity.clone(),
+            },
+            usize::from(farm_index),
+        );
+
+        match farm_fut.await {
+            Ok(farm) => Ok(farm),
+            Err(SingleDiskFarmError::InsufficientAllocatedSpace {
+                min_space,
+                allocated_space,
+            }) => Err(anyhow!(
+                "Allocated space {} ({}) is not enough, minimum is ~{} (~{}, {} bytes to be exact)",
+                bytesize::to_string(allocated_space, true),
+                bytesize::to_string(allocated_space, false),
+                bytesize::to_string(min_space, true),
+                bytesize::to_string(min_space, false),
+                min_space
+            )),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+/// Print information about the farm
+pub(super) fn print_farm_info(farm_index: u8, farm: &SingleDiskFarm, directory: &Path) {
+    let info = farm.info();
+    info!("Farm {farm_index}:");
+    info!("  ID: {}", info.id());
+    info!("  Genesis hash: 0x{}", hex::encode(info.genesis_hash()));
+    info!("  Public key: 0x{}", hex::encode(info.public_key()));
+    info!(
+        "  Allocated space: {} ({})",
+        bytesize::to_string(info.allocated_space(), true),
+        bytesize::to_string(info.allocated_space(), false)
+    );
+    info!("  Directory: {}", directory.display());
+}
+
+/// Status of the farm as exposed by admin API
+pub(super) struct FarmStatus {
+    pub(super) details: FarmDetails,
+    pub(super) info: SingleDiskFarmInfo,
+    pub(super) last_scrub_report: Option<ScrubReport>,
+    pub(super) activity: Arc<FarmActivity>,
+}
+
+struct AttachedFarm {
+    farm_id: FarmId,
+    disk_farm: DiskFarm,
+    info: SingleDiskFarmInfo,
+    total_sectors_count: u16,
+    plotted_sectors_count: Arc<AtomicU16>,
+    state: FarmState,
+    piece_cache: Arc<dyn PieceCache>,
+    plot_cache: Arc<dyn PlotCache>,
+    activity: Arc<FarmActivity>,
+    last_scrub_report: Option<ScrubReport>,
+    /// Sends a sender that will be notified once farm is stopped
+    detach_sender: Option<oneshot::Sender<oneshot::Sender<()>>>,
+    handler_ids: Vec<HandlerId>,
+}
+
+impl AttachedFarm {
+    fn details(&self, farm_index: u8) -> FarmDetails {
+        FarmDetails {
+            farm_index,
+            farm_id: self.farm_id.to_string(),
+            directory: self.disk_farm.directory.display().to_string(),
+            allocated_space: self.info.allocated_space(),
+            total_sectors_count: self.total_sectors_count,
+            plotted_sectors_count: self.plotted_sectors_count.load(Ordering::Relaxed),
+            state: self.state.clone(),
+        }
+    }
+
+    fn matches(&self, farm_index: u8, farm: &str) -> bool {
+        farm.parse::<u8>() == Ok(farm_index)
+            || self.farm_id.to_string() == farm
+            || self.disk_farm.directory == Path::new(farm)
+    }
+
+    fn status(&self, farm_index: u8) -> FarmStatus {
+        FarmStatus {
+            details: self.details(farm_index),
+            info: self.info,
+            last_scrub_report: self.last_scrub_report.clone(),
+            activity: Arc::clone(&self.activity),
+        }
+    }
+}
+
+struct Inner<NC> {
+    farm_options: FarmOptions<NC>,
+    farms: Arc<Mutex<BTreeMap<u8, AttachedFarm>>>,
+    farmer_cache: FarmerCache<u8>,
+    plot_cache: bool,
+    plotted_pieces: Arc<AsyncRwLock<PlottedPieces<u8>>>,
+    audit_throttle: Option<Arc<AuditThrottle>>,
+    farms_sender: mpsc::UnboundedSender<FarmFuture>,
+    /// Attaching, detaching and restarting of farms happens one at a time
+    control_mutex: AsyncMutex<()>,
+    farmer_cache_sync_progress: Arc<Mutex<f32>>,
+    _farmer_cache_sync_progress_handler_id: HandlerId,
+}
+
+/// Controller of farms running in this farmer
+pub(super) struct FarmsController<NC, PosTable> {
+    inner: Arc<Inner<NC>>,
+    _phantom: PhantomData<fn() -> PosTable>,
+}
+
+impl<NC, PosTable> fmt::Debug for FarmsController<NC, PosTable> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FarmsController").finish_non_exhaustive()
+    }
+}
+
+impl<NC, PosTable> Clone for FarmsController<NC, PosTable> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<NC, PosTable> FarmController for FarmsController<NC, PosTable>
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    async fn attach_farm(&self, disk_farm: &str) -> Result<FarmDetails, FarmControlError> {
+        let _control_guard = self.inner.control_mutex.lock().await;
+
+        let disk_farm = DiskFarm::from_str(disk_farm).map_err(FarmControlError::Failed)?;
+
+        let farm_index = {
+            let farms = self.inner.farms.lock();
+
+            if farms
+                .values()
+                .any(|farm| farm.disk_farm.directory == disk_farm.directory)
+            {
+                return Err(FarmControlError::AlreadyAttached {
+                    directory: disk_farm.directory.display().to_string(),
+                });
+            }
+
+            (0..=u8::MAX)
+                .find(|farm_index| !farms.contains_key(farm_index))
+                .ok_or_else(|| {
+                    FarmControlError::Failed(
+                        "More than 256 farms are not supported, consider running multiple farmer \
+                        instances"
+                            .to_string(),
+                    )
+                })?
+        };
+
+        if !disk_farm.directory.exists() {
+            fs::create_dir(&disk_farm.directory).map_err(|error| {
+                FarmControlError::Failed(format!(
+                    "Directory {} doesn't exist and can't be created: {error}",
+                    disk_farm.directory.display()
+                ))
+            })?;
+        }
+
+        info!(%farm_index, directory = %disk_farm.directory.display(), "Attaching farm");
+
+        // Plotting doesn't wait for farmer cache sync since it has typically happened already by
+        // now, farm metrics are not collected since registry was already handed over to Prometheus
+        // server
+        let farm = self
+            .inner
+            .farm_options
+            .create_farm::<PosTable>(
+                farm_index,
+                &disk_farm,
+                None,
+                Arc::new(Barrier::new(1)),
+                Arc::new(Semaphore::new(1)),
+                None,
+            )
+            .instrument(info_span!("", %farm_index))
+            .await
+            .map_err(|error| FarmControlError::Failed(error.to_string()))?;
+
+        print_farm_info(farm_index, &farm, &disk_farm.directory);
+
+        let farm_details = self
+            .add_farm(farm_index, disk_farm, farm)
+            .await
+            .map_err(|error| FarmControlError::Failed(error.to_string()))?;
+        self.update_backing_caches().await;
+
+        info!(%farm_index, "Farm attached successfully");
+
+        Ok(farm_details)
+    }
+
+    async fn detach_farm(&self, farm: &str) -> Result<(), FarmControlError> {
+        let _control_guard = self.inner.control_mutex.lock().await;
+
+        let farm_index = self.stop_farm(farm, FarmState::Detaching).await?;
+
+        self.inner.farms.lock().remove(&farm_index);
+
+        info!(%farm_index, "Farm detached successfully");
+
+        Ok(())
+    }
+
+    async fn farms(&self) -> Result<Vec<FarmDetails>, FarmControlError> {
+        Ok(self
+            .inner
+            .farms
+            .lock()
+            .iter()
+            .map(|(&farm_index, attached_farm)| attached_farm.details(farm_index))
+            .collect())
+    }
+}
+
+impl<NC, PosTable> FarmsController<NC, PosTable>
+where
+    NC: NodeClient + Clone,
+    PosTable: Table,
+{
+    /// Create new instance, returned future runs farms and must be polled for farms to make any
+    /// progress.
+    ///
+    /// Future resolves once farms that exited on their own (as opposed to being detached) leave no
+    /// other farms running, with error of the last farm if it exited with error. With
+    /// `exit_on_farm_error` future resolves with error of the first farm that exits with error.
+    pub(super) fn new(
+        farm_options: FarmOptions<NC>,
+        farmer_cache: FarmerCache<u8>,
+        plot_cache: bool,
+        plotted_pieces: Arc<AsyncRwLock<PlottedPieces<u8>>>,
+        audit_throttle: Option<Arc<AuditThrottle>>,
+        exit_on_farm_error: bool,
+    ) -> (
+        Self,
+        impl Future<Output = anyhow::Result<()>> + Send + 'static,
+    ) {
+        let farms = Arc::<Mutex<BTreeMap<u8, AttachedFarm>>>::default();
+        let (farms_sender, farms_receiver) = mpsc::unbounded();
+
+        let farmer_cache_sync_progress = Arc::<Mutex<f32>>::default();
+        let farmer_cache_sync_progress_handler_id = farmer_cache.on_sync_progress(Arc::new({
+            let farmer_cache_sync_progress = Arc::clone(&farmer_cache_sync_progress);
+
+            move |progress| {
+                *farmer_cache_sync_progress.lock() = *progress;
+            }
+        }));
+
+        let inner = Arc::new(Inner {
+            farm_options,
+            farms: Arc::clone(&farms),
+            farmer_cache,
+            plot_cache,
+            plotted_pieces,
+            audit_throttle,
+            farms_sender,
+            control_mutex: AsyncMutex::new(()),
+            farmer_cache_sync_progress,
+            _farmer_cache_sync_progress_handler_id: farmer_cache_sync_progress_handler_id,
+        });
+
+        let farms_fut = run_farms(farms, farms_receiver, exit_on_farm_error);
+
+        (
+            Self {
+                inner,
+                _phantom: PhantomData,
+            },
+            farms_fut,
+        )
+    }
+
+    /// Add farm that was just created, collects its plotted pieces and starts running it.
+    ///
+    /// Farmer cache is not updated, [`Self::update_backing_caches()`] needs to be called for that.
+    pub(super) async fn add_farm(
+        &self,
+        farm_index: u8,
+        disk_farm: DiskFarm,
+        farm: SingleDiskFarm,
+    ) -> anyhow::Result<FarmDetails> {
+        // Farms with the same public key would plot identical sectors and produce duplicate
+        // solutions
+        if let Some((&other_farm_index, _other_farm)) =
+            self.inner
+                .farms
+                .lock()
+                .iter()
+                .find(|(&other_farm_index, other_farm)| {
+                    other_farm_index != farm_index
+                        && other_farm.info.public_key() == farm.info().public_key()
+                })
+        {
+            return Err(anyhow!(
+                "Farm {farm_index} has the same public key as farm {other_farm_index}, farms \
+                must not share identity"
+            ));
+        }
+
+        let activity = Arc::new(FarmActivity::new(farm.total_sectors_count()));
+
+        let plotted_sectors_count = {
+            let mut plotted_pieces = self.inner.plotted_pieces.write().await;
+
+            plotted_pieces.add_farm(farm_index, Arc::new(farm.piece_reader()));
+
+            let plotted_sectors = farm.plotted_sectors();
+            let mut plotted_sectors = plotted_sectors.get().await.map_err(|error| {
+                anyhow!("Failed to get plotted sectors for farm {farm_index}: {error}")
+            })?;
+
+            let mut plotted_sectors_count = 0;
+            while let Some(plotted_sector_result) = plotted_sectors.next().await {
+                let plotted_sector = plotted_sector_result.map_err(|error| {
+                    anyhow!(
+                        "Failed reading plotted sector on startup for farm {farm_index}: {error}"
+                    )
+                })?;
+                activity.sector_plotted(plotted_sector.sector_index);
+                plotted_pieces.add_sector(farm_index, &plotted_sector);
+                plotted_sectors_count += 1;
+            }
+
+            Arc::new(AtomicU16::new(plotted_sectors_count))
+        };
+
+        let sector_update_handler_id = farm.on_sector_update(Arc::new({
+            let plotted_pieces = Arc::clone(&self.inner.plotted_pieces);
+            let plotted_sectors_count = Arc::clone(&plotted_sectors_count);
+            let activity = Arc::clone(&activity);
+            let span = info_span!("", %farm_index);
+
+            move |(sector_index, sector_state)| {
+                activity.on_sector_update(*sector_index, sector_state);
+
+                // Collect newly plotted pieces
+                if let SectorUpdate::Plotting(SectorPlottingDetails::Finished {
+                    plotted_sector,
+                    old_plotted_sector,
+                    time: _,
+                }) = sector_state
+                {
+                    let _span_guard = span.enter();
+
+                    let mut plotted_pieces = plotted_pieces.write_blocking();
+
+                    if let Some(old_plotted_sector) = &old_plotted_sector {
+                        plotted_pieces.delete_sector(farm_index, old_plotted_sector);
+                    } else {
+                        plotted_sectors_count.fetch_add(1, Ordering::Relaxed);
+                    }
+                    plotted_pieces.add_sector(farm_index, plotted_sector);
+                }
+            }
+        }));
+
+        let farming_notification_handler_id = farm.on_farming_notification(Arc::new({
+            let activity = Arc::clone(&activity);
+            let audit_throttle = self.inner.audit_throttle.clone();
+
+            move |farming_notification| {
+                if let Some(audit_throttle) = &audit_throttle
+                    && let FarmingNotification::Auditing(auditing_details) = farming_notification
+                {
+                    audit_throttle.observe_audit(usize::from(farm_index), auditing_details.time);
+                }
+
+                activity.on_farming_notification(farming_notification);
+            }
+        }));
+        let solution_handler_id = farm.on_solution(Arc::new({
+            let activity = Arc::clone(&activity);
+
+            move |solution_response| {
+                activity.on_solution(solution_response);
+            }
+        }));
+
+        let (detach_sender, detach_receiver) = oneshot::channel::<oneshot::Sender<()>>();
+        let attached_farm = AttachedFarm {
+            farm_id: *farm.id(),
+            disk_farm,
+            info: *farm.info(),
+            total_sectors_count: farm.total_sectors_count(),
+            plotted_sectors_count,
+            state: FarmState::Running,
+            piece_cache: Arc::new(farm.piece_cache()),
+            plot_cache: Arc::new(farm.plot_cache()),
+            activity,
+            last_scrub_report: None,
+            detach_sender: Some(detach_sender),
+            handler_ids: vec![
+                sector_update_handler_id,
+                farming_notification_handler_id,
+                solution_handler_id,
+            ],
+        };
+        let farm_details = attached_farm.details(farm_index);
+
+        self.inner.farms.lock().insert(farm_index, attached_farm);
+
+        let farm_fut = Box::pin(async move {
+            match future::select(Box::pin(farm.run()), detach_receiver).await {
+                Either::Left((result, _detach_receiver)) => (farm_index, Some(result)),
+                Either::Right((maybe_stopped_sender, farm_run_fut)) => {
+                    // Dropping the farm stops it
+                    drop(farm_run_fut);
+
+                    if let Ok(stopped_sender) = maybe_stopped_sender {
+                        // Doesn't matter if receiver is gone
+                        let _ = stopped_sender.send(());
+                    }
+
+                    (farm_index, None)
+                }
+            }
+        });
+
+        self.inner
+            .farms_sender
+            .unbounded_send(farm_fut)
+            .map_err(|_error| anyhow!("Farms are no longer running"))?;
+
+        Ok(farm_details)
+    }
+
+    /// Replace backing caches of farmer cache with caches of all farms that are not being detached
+    /// or scrubbed
+    pub(super) async fn update_backing_caches(&self) {
+        let (piece_caches, plot_caches) = {
+            let farms = self.inner.farms.lock();
+            let farms = farms.values().filter(|attached_farm| {
+                !matches!(
+                    attached_farm.state,
+                    FarmState::Detaching | FarmState::Scrubbing
+                )
+            });
+
+            farms.fold(
+                (Vec::new(), Vec::new()),
+                |(mut piece_caches, mut plot_caches), attached_farm| {
+                    piece_caches.push(Arc::clone(&attached_farm.piece_cache));
+                    if self.inner.plot_cache {
+                        plot_caches.push(Arc::clone(&attached_farm.plot_cache));
+                    }
+
+                    (piece_caches, plot_caches)
+                },
+            )
+        };
+
+        self.inner
+            .farmer_cache
+            .replace_backing_caches(piece_caches, plot_caches)
+            .await;
+    }
+
+    /// Status of all farms, ordered by farm index
+    pub(super) fn farm_statuses(&self) -> Vec<FarmStatus> {
+        self.inner
+            .farms
+            .lock()
+            .iter()
+            .map(|(&farm_index, attached_farm)| attached_farm.status(farm_index))
+            .collect()
+    }
+
+    /// Status of the farm identified by farm index, farm ID or directory
+    pub(super) fn farm_status(&self, farm: &str) -> Option<FarmStatus> {
+        self.inner
+            .farms
+            .lock()
+            .iter()
+            .find(|(farm_index, attached_farm)| attached_farm.matches(**farm_index, farm))
+            .map(|(&farm_index, attached_farm)| attached_farm.status(farm_index))
+    }
+
+    /// Farmer cache sync progress in %
+    pub(super) fn farmer_cache_sync_progress(&self) -> f32 {
+        *self.inner.farmer_cache_sync_progress.lock()
+    }
+
+    /// Stop farm identified by farm index, farm ID or directory, scrub it and start it again.
+    ///
+    /// Sectors that were found corrupted are replaced with dummy expired sectors, which farm replots
+    /// with its plotter once started again.
+    pub(super) async fn scrub_farm(
+        &self,
+        farm: &str,
+        target: ScrubTarget,
+    ) -> Result<ScrubReport, FarmControlError> {
+        let _control_guard = self.inner.control_mutex.lock().await;
+
+        let farm_index = self.stop_farm(farm, FarmState::Scrubbing).await?;
+        let disk_farm = self
+            .inner
+            .farms
+            .lock()
+            .get(&farm_index)
+            .map(|attached_farm| attached_farm.disk_farm.clone())
+            .ok_or_else(|| FarmControlError::FarmNotFound {
+                farm: farm.to_string(),
+            })?;
+
+        info!(%farm_index, %target, "Scrubbing farm");
+
+        let scrub_result = task::spawn_blocking({
+            let directory = disk_farm.directory.clone();
+            let disable_farm_locking = self.inner.farm_options.disable_farm_locking;
+            let span = info_span!("", %farm_index);
+
+            move || {
+                let _span_guard = span.enter();
+
+                let report =
+                    SingleDiskFarm::scrub(&directory, disable_farm_locking, target, false)?;
+
+                anyhow::Ok(report)
+            }
+        })
+        .await
+        .map_err(|error| anyhow!("Scrubbing task failed: {error}"))
+        .and_then(|result| result);
+
+        let report = match scrub_result {
+            Ok(report) => report,
+            Err(error) => {
+                warn!(%farm_index, %error, "Failed to scrub farm, starting it again");
+
+                self.restart_farm(farm_index, disk_farm, None).await?;
+
+                return Err(FarmControlError::Failed(format!(
+                    "Failed to scrub farm: {error}"
+                )));
+            }
+        };
+
+        info!(
+            %farm_index,
+            corrupted_sectors = %report.corrupted_sectors.len(),
+            corrupted_cache_elements = %report.corrupted_cache_elements.len(),
+            "Farm scrubbed, starting it again"
+        );
+
+        self.restart_farm(farm_index, disk_farm, Some(report.clone()))
+            .await?;
+
+        Ok(report)
+    }
+
+    /// Stop farm identified by farm index, farm ID or directory and set its state to `new_state`.
+    ///
+    /// Farm's caches stop being used and its plotted pieces are removed, but farm entry itself
+    /// remains and needs to be removed or replaced by the caller.
+    async fn stop_farm(&self, farm: &str, new_state: FarmState) -> Result<u8, FarmControlError> {
+        let (farm_index, detach_sender, handler_ids) = {
+            let mut farms = self.inner.farms.lock();
+
+            let (&farm_index, attached_farm) = farms
+                .iter_mut()
+                .find(|(farm_index, attached_farm)| attached_farm.matches(**farm_index, farm))
+                .ok_or_else(|| FarmControlError::FarmNotFound {
+                    farm: farm.to_string(),
+                })?;
+
+            if attached_farm.state == FarmState::Scrubbing {
+                return Err(FarmControlError::Failed(format!(
+                    "Farm {farm_index} is being scrubbed"
+                )));
+            }
+
+            attached_farm.state = new_state;
+
+            (
+                farm_index,
+                attached_farm.detach_sender.take(),
+                mem::take(&mut attached_farm.handler_ids),
+            )
+        };
+
+        info!(%farm_index, "Stopping farm");
+
+        // Farm caches should no longer be used by farmer cache
+        self.update_backing_caches().await;
+
+        // Stop tracking newly plotted sectors before plotted pieces of the farm are removed
+        drop(handler_ids);
+        if let Some(audit_throttle) = &self.inner.audit_throttle {
+            // Stopped farm should not keep plotting throttled
+            audit_throttle.remove_farm(usize::from(farm_index));
+        }
+        self.inner
+            .plotted_pieces
+            .write()
+            .await
+            .delete_farm(farm_index);
+
+        if let Some(detach_sender) = detach_sender {
+            let (stopped_sender, stopped_receiver) = oneshot::channel();
+
+            if detach_sender.send(stopped_sender).is_ok() {
+                // Error means farm has already exited on its own
+                let _ = stopped_receiver.await;
+            }
+        }
+
+        Ok(farm_index)
+    }
+
+    /// Create stopped farm again and start it in place of the previous instance.
+    ///
+    /// Farm is removed if it can't be started.
+    async fn restart_farm(
+        &self,
+        farm_index: u8,
+        disk_farm: DiskFarm,
+        scrub_report: Option<ScrubReport>,
+    ) -> Result<(), FarmControlError> {
+        let result = async {
+            let farm = self
+                .inner
+                .farm_options
+                .create_farm::<PosTable>(
+                    farm_index,
+                    &disk_farm,
+                    None,
+                    Arc::new(Barrier::new(1)),
+                    Arc::new(Semaphore::new(1)),
+                    None,
+                )
+                .instrument(info_span!("", %farm_index))
+                .await?;
+
+            self.add_farm(farm_index, disk_farm, farm).await
+        }
+        .await;
+
+        if let Err(error) = result {
+            error!(%farm_index, %error, "Failed to start farm again, removing it");
+
+            self.inner.farms.lock().remove(&farm_index);
+
+            return Err(FarmControlError::Failed(format!(
+                "Failed to start farm again: {error}"
+            )));
+        }
+
+        if let Some(attached_farm) = self.inner.farms.lock().get_mut(&farm_index) {
+            attached_farm.last_scrub_report = scrub_report;
+        }
+        self.update_backing_caches().await;
+
+        info!(%farm_index, "Farm started again");
+
+        Ok(())
+    }
+}
+
+async fn run_farms(
+    farms: Arc<Mutex<BTreeMap<u8, AttachedFarm>>>,
+    mut farms_receiver: mpsc::UnboundedReceiver<FarmFuture>,
+    exit_on_farm_error: bool,
+) -> anyhow::Result<()> {
+    let mut farms_stream = FuturesUnordered::<FarmFuture>::new();
+    let mut farm_errors = Vec::new();
+
+    loop {
+        select! {
+            farm_fut = farms_receiver.select_next_some() => {
+                farms_stream.push(farm_fut);
+            }
+            (farm_index, maybe_result) = farms_stream.select_next_some() => {
+                let Some(result) = maybe_result else {
+                    // Farm was detached, farmer keeps running even without farms since new farms
+                    // can be attached later
+                    continue;
+                };
+
+                match result {
+                    Ok(()) => {
+                        info!(%farm_index, "Farm exited successfully");
+
+                        if let Some(attached_farm) = farms.lock().get_mut(&farm_index) {
+                            attached_farm.state = FarmState::Exited;
+                        }
+
+                        if farms_stream.is_empty() {
+                            return Ok(());
+                        }
+                    }
+                    Err(error) => {
+                        error!(%farm_index, %error, "Farm exited with error");
+
+                        if let Some(attached_farm) = farms.lock().get_mut(&farm_index) {
+                            attached_farm.state = FarmState::Failed {
+                                error: error.to_string(),
+                            };
+                        }
+
+                        if farms_stream.is_empty() || exit_on_farm_error {
+                            return Err(error);
+                        } else {
+                            farm_errors.push(AsyncJoinOnDrop::new(
+                                tokio::spawn(async move {
+                                    loop {
+                                        sleep(FARM_ERROR_PRINT_INTERVAL).await;
+
+                                        error!(
+                                            %farm_index,
+                                            %error,
+                                            "Farm errored and stopped"
+                                        );
+                                    }
+                                }),
+                                true,
+                            ))
+                        }
+                    }
+                }
+            }
+            complete => {
+                return Ok(());
+            }
+        }
+    }
+}
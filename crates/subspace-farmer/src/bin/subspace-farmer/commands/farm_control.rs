@@ -0,0 +1,107 @@
+use crate::commands::shared::DiskFarm;
+use anyhow::anyhow;
+use clap::{Parser, Subcommand, ValueHint};
+use std::path::PathBuf;
+use std::str::FromStr;
+use subspace_farmer::farm_control::unix_socket::UnixSocketFarmController;
+use subspace_farmer::farm_control::{FarmController, FarmDetails};
+
+/// Arguments for farm control
+#[derive(Debug, Parser)]
+pub(crate) struct FarmControlArgs {
+    /// Path to Unix socket of the running farmer (see `--control-socket` option of `farm` command)
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    socket: PathBuf,
+    /// Farm control command
+    #[clap(subcommand)]
+    command: FarmControlCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum FarmControlCommand {
+    /// List farms of the running farmer along with their state
+    List,
+    /// Attach new farm to the running farmer
+    Attach {
+        /// Farm in the same format as for `farm` command, path must be absolute.
+        ///
+        /// Example:
+        ///   path=/path/to/directory,size=5T
+        disk_farm: String,
+    },
+    /// Gracefully detach farm from the running farmer, farm's disk can be removed after this
+    /// command succeeds
+    Detach {
+        /// Farm index, farm ID or directory of the farm
+        farm: String,
+    },
+}
+
+pub(crate) async fn farm_control(
+    FarmControlArgs { socket, command }: FarmControlArgs,
+) -> anyhow::Result<()> {
+    let farm_controller = UnixSocketFarmController::new(&socket);
+
+    match command {
+        FarmControlCommand::List => {
+            let farms = farm_controller
+                .farms()
+                .await
+                .map_err(|error| anyhow!("Failed to list farms: {error}"))?;
+
+            if farms.is_empty() {
+                println!("No farms");
+            }
+            for farm_details in &farms {
+                print_farm_details(farm_details);
+            }
+        }
+        FarmControlCommand::Attach { disk_farm } => {
+            // Validate locally before sending to the farmer, which resolves relative paths against
+            // its own working directory
+            let directory = DiskFarm::from_str(&disk_farm)
+                .map_err(|error| anyhow!("Invalid disk farm: {error}"))?
+                .directory;
+            if !directory.is_absolute() {
+                return Err(anyhow!(
+                    "Farm path must be absolute, got {}",
+                    directory.display()
+                ));
+            }
+
+            let farm_details = farm_controller
+                .attach_farm(&disk_farm)
+                .await
+                .map_err(|error| anyhow!("Failed to attach farm: {error}"))?;
+
+            println!("Attached farm:");
+            print_farm_details(&farm_details);
+        }
+        FarmControlCommand::Detach { farm } => {
+            farm_controller
+                .detach_farm(&farm)
+                .await
+                .map_err(|error| anyhow!("Failed to detach farm: {error}"))?;
+
+            println!("Farm {farm} detached");
+        }
+    }
+
+    Ok(())
+}
+
+fn print_farm_details(farm_details: &FarmDetails) {
+    println!("Farm {}:", farm_details.farm_index);
+    println!("  ID: {}", farm_details.farm_id);
+    println!("  Directory: {}", farm_details.directory);
+    println!(
+        "  Allocated space: {} ({})",
+        bytesize::to_string(farm_details.allocated_space, true),
+        bytesize::to_string(farm_details.allocated_space, false)
+    );
+    println!(
+        "  Plotted sectors: {}/{}",
+        farm_details.plotted_sectors_count, farm_details.total_sectors_count
+    );
+    println!("  State: {}", farm_details.state);
+}
@@ -0,0 +1,123 @@
+use crate::commands::shared::identity::IdentityPassphraseArgs;
+use anyhow::anyhow;
+use clap::{Parser, ValueHint};
+use std::path::PathBuf;
+use subspace_core_primitives::PublicKey;
+use subspace_farmer::single_disk_farm::identity::Identity;
+use subspace_farmer::single_disk_farm::SingleDiskFarmInfo;
+
+/// Arguments for identities
+#[derive(Debug, Parser)]
+pub(crate) struct IdentitiesArgs {
+    /// One or more farm located at specified path.
+    ///
+    /// Example:
+    ///   /path/to/directory
+    #[arg(required = true, value_hint = ValueHint::DirPath)]
+    disk_farms: Vec<PathBuf>,
+    /// Directory with master identity, if specified identities of farms are checked to be derived
+    /// from it
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    master_identity: Option<PathBuf>,
+    /// Identity encryption options
+    #[clap(flatten)]
+    identity_passphrase_args: IdentityPassphraseArgs,
+}
+
+/// Print identities of farms and their derivation paths
+pub(crate) fn identities(
+    IdentitiesArgs {
+        disk_farms,
+        master_identity,
+        identity_passphrase_args,
+    }: IdentitiesArgs,
+) -> anyhow::Result<()> {
+    let master_identity = match master_identity {
+        Some(directory) => {
+            let identity_passphrase = identity_passphrase_args.passphrase()?;
+            let identity = Identity::open(&directory, identity_passphrase.as_ref())
+                .map_err(|error| {
+                    anyhow!(
+                        "Failed to open master identity in {}: {error}",
+                        directory.display()
+                    )
+                })?
+                .ok_or_else(|| anyhow!("Master identity not found in {}", directory.display()))?;
+
+            println!(
+                "Master identity: 0x{}",
+                PublicKey::from(identity.public_key().to_bytes())
+            );
+            println!();
+
+            Some(identity)
+        }
+        None => None,
+    };
+
+    for (farm_index, directory) in disk_farms.into_iter().enumerate() {
+        if farm_index > 0 {
+            println!();
+        }
+
+        println!("Single disk farm {farm_index}:");
+        println!("  Directory: {}", directory.display());
+
+        let info = match SingleDiskFarmInfo::load_from(&directory) {
+            Ok(Some(info)) => info,
+            Ok(None) => {
+                println!("  No farm found here yet");
+                continue;
+            }
+            Err(error) => {
+                println!("  Failed to open farm info: {error}");
+                continue;
+            }
+        };
+
+        println!("  ID: {}", info.id());
+        println!("  Public key: 0x{}", info.public_key());
+
+        match Identity::read_public_key(&directory) {
+            Ok(Some(public_key)) => {
+                if PublicKey::from(public_key.to_bytes()) != *info.public_key() {
+                    println!(
+                        "  Identity file public key 0x{} doesn't match farm info",
+                        PublicKey::from(public_key.to_bytes())
+                    );
+                }
+            }
+            Ok(None) => {
+                println!("  Identity file is missing");
+            }
+            Err(error) => {
+                println!("  Failed to read identity file: {error}");
+            }
+        }
+
+        let Some(derivation_path) = info.identity_derivation_path() else {
+            println!("  Identity derivation path: none (random identity)");
+            continue;
+        };
+        println!("  Identity derivation path: {derivation_path}");
+
+        if let Some(master_identity) = &master_identity {
+            let derived_public_key = PublicKey::from(
+                master_identity
+                    .derive(derivation_path)
+                    .public_key()
+                    .to_bytes(),
+            );
+
+            if derived_public_key == *info.public_key() {
+                println!("  Derived from master identity: yes");
+            } else {
+                println!(
+                    "  Derived from master identity: no (derived public key 0x{derived_public_key})"
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
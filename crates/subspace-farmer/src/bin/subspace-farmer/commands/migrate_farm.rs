@@ -0,0 +1,71 @@
+use crate::commands::farm::cache_percentage_parser;
+use bytesize::ByteSize;
+use clap::{Parser, ValueHint};
+use std::fs;
+use std::num::NonZeroU8;
+use std::path::PathBuf;
+use subspace_farmer::single_disk_farm::SingleDiskFarm;
+use tracing::info;
+
+/// Arguments for farm migration
+#[derive(Debug, Parser)]
+pub(crate) struct MigrateFarmArgs {
+    /// Path to the farm that should be migrated
+    #[arg(value_hint = ValueHint::DirPath)]
+    source: PathBuf,
+    /// Path to the directory where farm should be migrated, created if doesn't exist.
+    ///
+    /// If previous migration to this directory was interrupted, it will be resumed.
+    #[arg(value_hint = ValueHint::DirPath)]
+    destination: PathBuf,
+    /// New allocated space for the farm at destination, the same as source if not specified
+    #[arg(long)]
+    size: Option<ByteSize>,
+    /// Percentage of allocated space dedicated for caching purposes, 99% max
+    #[arg(long, default_value = "1", value_parser = cache_percentage_parser)]
+    cache_percentage: NonZeroU8,
+    /// Disable farm locking, for example if file system doesn't support it
+    #[arg(long)]
+    disable_farm_locking: bool,
+}
+
+pub(crate) fn migrate_farm(migrate_farm_args: MigrateFarmArgs) -> anyhow::Result<()> {
+    let MigrateFarmArgs {
+        source,
+        destination,
+        size,
+        cache_percentage,
+        disable_farm_locking,
+    } = migrate_farm_args;
+
+    info!(
+        source = %source.display(),
+        destination = %destination.display(),
+        "Migrating farm"
+    );
+
+    SingleDiskFarm::migrate(
+        &source,
+        &destination,
+        size.map(|size| size.as_u64()),
+        cache_percentage.get(),
+        disable_farm_locking,
+    )?;
+
+    // Known addresses are stored alongside the farm, but are managed by networking stack rather
+    // than the farm itself
+    let known_addresses_file = source.join("known_addresses.bin");
+    let destination_known_addresses_file = destination.join("known_addresses.bin");
+    if known_addresses_file.exists() && !destination_known_addresses_file.exists() {
+        info!("Copying known addresses");
+        fs::copy(known_addresses_file, destination_known_addresses_file)?;
+    }
+
+    info!(
+        "Farm migrated successfully, source farm at {} can be wiped once destination farm is \
+        confirmed to work",
+        source.display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use crate::commands::farm::cache_percentage_parser;
+use crate::commands::shared::DiskFarm;
+use anyhow::anyhow;
+use clap::Parser;
+use std::num::NonZeroU8;
+use subspace_farmer::single_disk_farm::SingleDiskFarm;
+use tracing::{error, info, info_span};
+
+/// Arguments for farm resizing
+#[derive(Debug, Parser)]
+pub(crate) struct ResizeArgs {
+    /// One or more farms with their new sizes, same format as used by `farm` command.
+    ///
+    /// Example:
+    ///   path=/path/to/directory,size=5T
+    #[arg(required = true)]
+    disk_farms: Vec<DiskFarm>,
+    /// Percentage of allocated space dedicated for caching purposes, must match the value used
+    /// with `farm` command
+    #[arg(long, default_value = "1", value_parser = cache_percentage_parser)]
+    cache_percentage: NonZeroU8,
+    /// Disable farm locking, for example if file system doesn't support it
+    #[arg(long)]
+    disable_farm_locking: bool,
+}
+
+pub(crate) fn resize(resize_args: ResizeArgs) -> anyhow::Result<()> {
+    let ResizeArgs {
+        disk_farms,
+        cache_percentage,
+        disable_farm_locking,
+    } = resize_args;
+
+    let mut failed = false;
+    for (farm_index, disk_farm) in disk_farms.into_iter().enumerate() {
+        let span = info_span!("", %farm_index);
+        let _span_guard = span.enter();
+
+        match SingleDiskFarm::resize(
+            &disk_farm.directory,
+            disk_farm.allocated_space,
+            cache_percentage.get(),
+            disable_farm_locking,
+        ) {
+            Ok(()) => {
+                info!(
+                    path = %disk_farm.directory.display(),
+                    "Farm resized successfully"
+                );
+            }
+            Err(error) => {
+                error!(
+                    path = %disk_farm.directory.display(),
+                    %error,
+                    "Failed to resize farm"
+                );
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        return Err(anyhow!("Some farms failed to resize"));
+    }
+
+    Ok(())
+}
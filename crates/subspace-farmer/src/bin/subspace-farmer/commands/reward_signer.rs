@@ -0,0 +1,82 @@
+use crate::commands::shared::bind_unix_socket;
+use crate::commands::shared::identity::IdentityPassphraseArgs;
+use crate::utils::shutdown_signal;
+use anyhow::anyhow;
+use clap::{Parser, ValueHint};
+use futures::{select, FutureExt};
+use std::fs;
+use std::path::PathBuf;
+use subspace_core_primitives::PublicKey;
+use subspace_farmer::reward_signer::unix_socket::serve;
+use subspace_farmer::reward_signer::LocalRewardSigner;
+use subspace_farmer::single_disk_farm::identity::Identity;
+use tracing::info;
+
+/// Arguments for reward signer
+#[derive(Debug, Parser)]
+pub(crate) struct RewardSignerArgs {
+    /// One or more directories with identities to sign rewards with (typically farm directories).
+    ///
+    /// Example:
+    ///   /path/to/directory
+    #[arg(required = true, value_hint = ValueHint::DirPath)]
+    directories: Vec<PathBuf>,
+    /// Path to Unix socket to listen on, existing socket at this path will be replaced.
+    ///
+    /// Socket is only accessible by the user running reward signer.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    socket: PathBuf,
+    /// Identity encryption options
+    #[clap(flatten)]
+    identity_passphrase_args: IdentityPassphraseArgs,
+}
+
+pub(crate) async fn reward_signer(
+    RewardSignerArgs {
+        directories,
+        socket,
+        identity_passphrase_args,
+    }: RewardSignerArgs,
+) -> anyhow::Result<()> {
+    let identity_passphrase = identity_passphrase_args.passphrase()?;
+
+    let identities = directories
+        .iter()
+        .map(|directory| {
+            let identity = Identity::open(directory, identity_passphrase.as_ref())
+                .map_err(|error| {
+                    anyhow!(
+                        "Failed to open identity in {}: {error}",
+                        directory.display()
+                    )
+                })?
+                .ok_or_else(|| anyhow!("Identity not found in {}", directory.display()))?;
+
+            info!(
+                public_key = %PublicKey::from(identity.public_key().to_bytes()),
+                directory = %directory.display(),
+                "Loaded identity"
+            );
+
+            Ok(identity)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let listener = bind_unix_socket(&socket)?;
+
+    info!(socket = %socket.display(), "Reward signer started");
+
+    let result = select! {
+        result = serve(listener, LocalRewardSigner::new(identities)).fuse() => {
+            result.map_err(|error| anyhow!("Reward signer failed: {error}"))
+        },
+        _ = shutdown_signal().fuse() => {
+            info!("Received shutdown signal, exiting...");
+            Ok(())
+        },
+    };
+
+    let _ = fs::remove_file(&socket);
+
+    result
+}
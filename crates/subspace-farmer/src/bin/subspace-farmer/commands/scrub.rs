@@ -1,40 +1,436 @@
+#[cfg(feature = "cuda")]
+use crate::commands::farm::CudaPlottingOptions;
+use crate::commands::farm::{init_local_plotters, CpuPlottingOptions, PLOTTING_RETRY_INTERVAL};
+use crate::commands::shared::dsn_client::{dsn_piece_fetcher, DsnClientArgs};
+use anyhow::anyhow;
+use clap::{Parser, ValueHint};
+use futures::{select, FutureExt};
+use prometheus_client::registry::Registry;
 use rayon::prelude::*;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, ErrorKind, Write};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
-use subspace_farmer::single_disk_farm::{ScrubTarget, SingleDiskFarm};
-use tracing::{error, info, info_span};
+use std::sync::Arc;
+use subspace_core_primitives::crypto::kzg::{embedded_kzg_settings, Kzg};
+use subspace_core_primitives::Record;
+use subspace_erasure_coding::ErasureCoding;
+use subspace_farmer::node_client::rpc_node_client::RpcNodeClient;
+use subspace_farmer::node_client::NodeClient;
+use subspace_farmer::plotter::pool::PoolPlotter;
+use subspace_farmer::plotter::Plotter;
+use subspace_farmer::single_disk_farm::{
+    ScrubReport, ScrubTarget, SingleDiskFarm, SingleDiskFarmInfo,
+};
+use subspace_proof_of_space::Table;
+use tokio::task;
+use tracing::{error, info, info_span, warn};
 
-pub(crate) fn scrub(
-    disk_farms: &[PathBuf],
+/// Arguments for scrubbing
+#[derive(Debug, Parser)]
+pub(crate) struct ScrubArgs {
+    /// One or more farm located at specified path.
+    ///
+    /// Example:
+    ///   /path/to/directory
+    disk_farms: Vec<PathBuf>,
+    /// Disable farm locking, for example if file system doesn't support it
+    #[arg(long)]
     disable_farm_locking: bool,
+    /// Scrub target
+    ///
+    /// Possible values are: `all`, `metadata`, `plot` and `cache`
+    #[arg(long, default_value_t = ScrubTarget::All)]
     target: ScrubTarget,
+    /// Check for errors, but do not attempt to correct them
+    #[arg(long)]
     dry_run: bool,
-) {
-    disk_farms
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(farm_index, directory)| {
-            let span = info_span!("", %farm_index);
-            let _span_guard = span.enter();
+    /// Restore corrupted data instead of only discarding it: corrupted sectors are plotted again
+    /// and corrupted cache pieces are downloaded again.
+    ///
+    /// Requires connection to the node, farm stays locked from scrubbing until it is repaired.
+    #[arg(long, conflicts_with = "dry_run")]
+    repair: bool,
+    /// WebSocket RPC URL of the Subspace node to connect to, used with `--repair`
+    #[arg(long, value_hint = ValueHint::Url, default_value = "ws://127.0.0.1:9944")]
+    node_rpc_url: String,
+    /// Write machine-readable report (JSON) of what was found and fixed to this file
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    report: Option<PathBuf>,
+    /// DSN client configuration, used with `--repair`
+    #[clap(flatten)]
+    dsn_client_args: DsnClientArgs,
+    /// Plotting options only used by CPU plotter, used with `--repair`
+    #[clap(flatten)]
+    cpu_plotting_options: CpuPlottingOptions,
+    /// Plotting options only used by CUDA GPU plotter, used with `--repair`
+    #[cfg(feature = "cuda")]
+    #[clap(flatten)]
+    cuda_plotting_options: CudaPlottingOptions,
+}
+
+/// Scrubbing results of a single farm
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FarmScrubReport {
+    path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<ScrubReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl FarmScrubReport {
+    /// Whether farm has corrupted data that can be restored with repair
+    fn needs_repair(&self) -> bool {
+        self.report.as_ref().is_some_and(|report| {
+            report
+                .corrupted_sectors
+                .iter()
+                .any(|corrupted_sector| corrupted_sector.replaced)
+                || report
+                    .corrupted_cache_elements
+                    .iter()
+                    .any(|corrupted_element| corrupted_element.piece_index.is_some())
+        })
+    }
+
+    /// Record an error, appending it to previously recorded errors if there are any
+    fn record_error(error: &mut Option<String>, new_error: String) {
+        match error {
+            Some(error) => {
+                error.push_str("; ");
+                error.push_str(&new_error);
+            }
+            None => {
+                error.replace(new_error);
+            }
+        }
+    }
+}
+
+pub(crate) async fn scrub<PosTable>(scrub_args: ScrubArgs) -> anyhow::Result<()>
+where
+    PosTable: Table,
+{
+    let ScrubArgs {
+        disk_farms,
+        disable_farm_locking,
+        target,
+        dry_run,
+        repair,
+        node_rpc_url,
+        report,
+        dsn_client_args,
+        cpu_plotting_options,
+        #[cfg(feature = "cuda")]
+        cuda_plotting_options,
+    } = scrub_args;
+
+    if disk_farms.is_empty() {
+        info!("No farm was specified, so there is nothing to do");
+        return Ok(());
+    }
+
+    // Farms are locked for the whole duration of scrubbing and repair, such that farmer can't
+    // start using farm in between
+    let (mut farm_reports, _farm_locks): (Vec<_>, Vec<_>) = task::block_in_place(|| {
+        disk_farms
+            .into_par_iter()
+            .enumerate()
+            .map(|(farm_index, directory)| {
+                let span = info_span!("", %farm_index);
+                let _span_guard = span.enter();
+                info!(
+                    path = %directory.display(),
+                    "Start scrubbing farm"
+                );
+
+                let farm_lock = if disable_farm_locking {
+                    None
+                } else {
+                    match SingleDiskFarmInfo::try_lock(&directory) {
+                        Ok(farm_lock) => Some(farm_lock),
+                        // Missing farm info file is reported by scrubbing itself
+                        Err(error) if error.kind() == ErrorKind::NotFound => None,
+                        Err(error) => {
+                            error!(
+                                path = %directory.display(),
+                                %error,
+                                "Failed to lock farm, it is likely already in use"
+                            );
+
+                            let farm_report = FarmScrubReport {
+                                path: directory,
+                                report: None,
+                                error: Some(format!(
+                                    "Failed to lock farm, it is likely already in use: {error}"
+                                )),
+                            };
+                            return (farm_report, None);
+                        }
+                    }
+                };
+
+                // Lock is already held, so it is not taken again
+                let farm_report = match SingleDiskFarm::scrub(&directory, true, target, dry_run) {
+                    Ok(report) => {
+                        info!(
+                            path = %directory.display(),
+                            "Farm checked successfully"
+                        );
+
+                        FarmScrubReport {
+                            path: directory,
+                            report: Some(report),
+                            error: None,
+                        }
+                    }
+                    Err(error) => {
+                        error!(
+                            path = %directory.display(),
+                            %error,
+                            "Irrecoverable farm error occurred, your file system might need to \
+                            be repaired or disk might need to be replaced"
+                        );
+
+                        FarmScrubReport {
+                            path: directory,
+                            report: None,
+                            error: Some(error.to_string()),
+                        }
+                    }
+                };
+
+                (farm_report, farm_lock)
+            })
+            .unzip()
+    });
+
+    if repair
+        && let Err(error) = repair_farms::<PosTable>(
+            &mut farm_reports,
+            &node_rpc_url,
+            dsn_client_args,
+            cpu_plotting_options,
+            #[cfg(feature = "cuda")]
+            cuda_plotting_options,
+        )
+        .await
+    {
+        error!(%error, "Failed to repair farms");
+
+        // Report is still written, such that results of scrubbing are not lost
+        for farm_report in &mut farm_reports {
+            if farm_report.needs_repair() {
+                FarmScrubReport::record_error(
+                    &mut farm_report.error,
+                    format!("Failed to repair farm: {error}"),
+                );
+            }
+        }
+    }
+
+    if let Some(report) = report {
+        let file = File::create(&report).map_err(|error| {
+            anyhow!("Failed to create report file {}: {error}", report.display())
+        })?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, &farm_reports)?;
+        writer.flush()?;
+        info!(path = %report.display(), "Scrub report written");
+    }
+
+    let failed_farms = farm_reports
+        .iter()
+        .filter(|farm_report| farm_report.error.is_some())
+        .count();
+    if failed_farms > 0 {
+        return Err(anyhow!(
+            "Scrubbing or repair failed for {failed_farms} farm(s), check logs for details"
+        ));
+    }
+
+    Ok(())
+}
+
+async fn repair_farms<PosTable>(
+    farm_reports: &mut [FarmScrubReport],
+    node_rpc_url: &str,
+    dsn_client_args: DsnClientArgs,
+    cpu_plotting_options: CpuPlottingOptions,
+    #[cfg(feature = "cuda")] cuda_plotting_options: CudaPlottingOptions,
+) -> anyhow::Result<()>
+where
+    PosTable: Table,
+{
+    if !farm_reports.iter().any(FarmScrubReport::needs_repair) {
+        return Ok(());
+    }
+
+    info!(url = %node_rpc_url, "Connecting to node RPC");
+    let node_client = RpcNodeClient::new(node_rpc_url)
+        .await
+        .map_err(|error| anyhow!("Failed to connect to node RPC: {error}"))?;
+
+    let farmer_app_info = node_client
+        .farmer_app_info()
+        .await
+        .map_err(|error| anyhow!("Failed to get farmer app info: {error}"))?;
+
+    let kzg = Kzg::new(embedded_kzg_settings());
+    let erasure_coding = ErasureCoding::new(
+        NonZeroUsize::new(Record::NUM_S_BUCKETS.next_power_of_two().ilog2() as usize)
+            .expect("Not zero; qed"),
+    )
+    .map_err(|error| anyhow!("Failed to instantiate erasure coding: {error}"))?;
+    let (piece_fetcher, mut node_runner) = dsn_piece_fetcher(
+        dsn_client_args,
+        &farmer_app_info,
+        node_client.clone(),
+        kzg.clone(),
+    )?;
+
+    // Metrics are not exposed by scrubbing
+    let mut registry = Registry::default();
+    let plotters = init_local_plotters::<_, PosTable>(
+        cpu_plotting_options,
+        #[cfg(feature = "cuda")]
+        cuda_plotting_options,
+        piece_fetcher.clone(),
+        Arc::default(),
+        kzg,
+        erasure_coding,
+        None,
+        &mut registry,
+    )?;
+    let plotter = (!plotters.is_empty()).then(|| {
+        Arc::new(PoolPlotter::new(plotters, PLOTTING_RETRY_INTERVAL))
+            as Arc<dyn Plotter + Send + Sync>
+    });
+
+    let repair_fut = async {
+        for farm_report in farm_reports.iter_mut() {
+            let FarmScrubReport {
+                path,
+                report: Some(report),
+                error: farm_error,
+            } = farm_report
+            else {
+                continue;
+            };
+
+            let replaced_sectors = report
+                .corrupted_sectors
+                .iter()
+                .filter(|corrupted_sector| corrupted_sector.replaced)
+                .map(|corrupted_sector| corrupted_sector.sector_index)
+                .collect::<Vec<_>>();
+            if let Some(plotter) = &plotter
+                && !replaced_sectors.is_empty()
+            {
+                info!(
+                    path = %path.display(),
+                    sectors = %replaced_sectors.len(),
+                    "Replotting corrupted sectors"
+                );
+                match SingleDiskFarm::replot_sectors(
+                    path,
+                    &replaced_sectors,
+                    Arc::clone(plotter),
+                    &node_client,
+                    // Farm is already locked by the caller
+                    true,
+                )
+                .await
+                {
+                    Ok(replotted_sectors) => {
+                        for corrupted_sector in &mut report.corrupted_sectors {
+                            corrupted_sector.replotted =
+                                replotted_sectors.contains(&corrupted_sector.sector_index);
+                        }
+                    }
+                    Err(error) => {
+                        error!(
+                            path = %path.display(),
+                            %error,
+                            "Failed to replot corrupted sectors"
+                        );
+
+                        FarmScrubReport::record_error(
+                            farm_error,
+                            format!("Failed to replot corrupted sectors: {error}"),
+                        );
+                    }
+                }
+            }
+
+            let not_replotted = report
+                .corrupted_sectors
+                .iter()
+                .filter(|corrupted_sector| corrupted_sector.replaced && !corrupted_sector.replotted)
+                .count();
+            if not_replotted > 0 {
+                warn!(
+                    path = %path.display(),
+                    %not_replotted,
+                    "Some corrupted sectors were not replotted, they will be replotted as expired \
+                    sectors once farm is started"
+                );
+            }
+
+            if report.corrupted_cache_elements.is_empty() {
+                continue;
+            }
+
             info!(
-                path = %directory.display(),
-                "Start scrubbing farm"
+                path = %path.display(),
+                "Downloading pieces for corrupted cache elements"
             );
+            if let Err(error) = SingleDiskFarm::refetch_cache_elements(
+                path,
+                &mut report.corrupted_cache_elements,
+                &piece_fetcher,
+                // Farm is already locked by the caller
+                true,
+            )
+            .await
+            {
+                error!(
+                    path = %path.display(),
+                    %error,
+                    "Failed to restore corrupted cache elements"
+                );
 
-            match SingleDiskFarm::scrub(directory, disable_farm_locking, target, dry_run) {
-                Ok(()) => {
-                    info!(
-                        path = %directory.display(),
-                        "Farm checked successfully"
-                    );
-                }
-                Err(error) => {
-                    error!(
-                        path = %directory.display(),
-                        %error,
-                        "Irrecoverable farm error occurred, your file system might need to be \
-                        repaired or disk might need to be replaced"
-                    );
-                }
+                FarmScrubReport::record_error(
+                    farm_error,
+                    format!("Failed to restore corrupted cache elements: {error}"),
+                );
+            }
+
+            let not_refetched = report
+                .corrupted_cache_elements
+                .iter()
+                .filter(|corrupted_element| !corrupted_element.refetched)
+                .count();
+            if not_refetched > 0 {
+                warn!(
+                    path = %path.display(),
+                    %not_refetched,
+                    "Some corrupted cache elements were not restored, they will be filled by \
+                    farmer cache later"
+                );
             }
-        });
+        }
+    };
+
+    select! {
+        _ = repair_fut.fuse() => {},
+        _ = node_runner.run().fuse() => {
+            return Err(anyhow!("DSN network runner exited before repair was finished"));
+        },
+    }
+
+    Ok(())
 }
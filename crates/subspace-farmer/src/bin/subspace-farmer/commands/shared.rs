@@ -1,14 +1,28 @@
+pub(super) mod dsn_client;
+pub(super) mod identity;
 pub(super) mod network;
+#[cfg(all(test, unix))]
+mod tests;
 
+use anyhow::anyhow;
 use bytesize::ByteSize;
-use clap::Parser;
+use clap::{Parser, ValueHint};
 use std::fmt;
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+#[cfg(unix)]
+use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
+#[cfg(unix)]
+use std::{fs, io};
+use subspace_farmer::plotter::checkpoint::PlottingCheckpoints;
 use subspace_farmer::single_disk_farm::{SingleDiskFarm, SingleDiskFarmSummary};
 use subspace_farmer_components::reading::ReadSectorRecordChunksMode;
 use subspace_networking::libp2p::identity::{ed25519, Keypair};
 use thread_priority::ThreadPriority;
+#[cfg(unix)]
+use tokio::net::UnixListener;
 use zeroize::Zeroizing;
 
 /// Plotting thread priority
@@ -58,6 +72,29 @@ impl From<PlottingThreadPriority> for Option<ThreadPriority> {
     }
 }
 
+/// Plotting checkpoint options
+#[derive(Debug, Parser)]
+pub(in super::super) struct PlottingCheckpointOptions {
+    /// Scratch directory for plotting checkpoints.
+    ///
+    /// When specified, downloaded sector pieces and encoded sectors are checkpointed there, such
+    /// that plotting of in-flight sectors resumes where it stopped after restart instead of
+    /// starting from scratch. Requires up to ~2 GiB of space for every sector being plotted
+    /// concurrently.
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    plotting_scratch_dir: Option<PathBuf>,
+}
+
+impl PlottingCheckpointOptions {
+    /// Open plotting checkpoints if scratch directory was specified
+    pub(in super::super) fn open(self) -> anyhow::Result<Option<PlottingCheckpoints>> {
+        self.plotting_scratch_dir
+            .map(PlottingCheckpoints::open)
+            .transpose()
+            .map_err(|error| anyhow!("Failed to open plotting scratch directory: {error}"))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(in super::super) struct DiskFarm {
     /// Path to directory where farm is stored
@@ -144,6 +181,47 @@ pub(in super::super) fn derive_libp2p_keypair(schnorrkel_sk: &schnorrkel::Secret
     Keypair::from(keypair)
 }
 
+/// Listen on Unix socket at `path` that is only accessible by the current user, existing socket at
+/// this path is replaced
+#[cfg(unix)]
+pub(in super::super) fn bind_unix_socket(path: &Path) -> anyhow::Result<UnixListener> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => {
+            if !metadata.file_type().is_socket() {
+                return Err(anyhow!(
+                    "{} already exists and is not a socket",
+                    path.display()
+                ));
+            }
+            fs::remove_file(path)?;
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => {
+            return Err(error.into());
+        }
+    }
+
+    // Socket is bound in a temporary directory only accessible by the current user (created with
+    // `0700` permissions) and moved to its final location once permissions are restricted, such
+    // that other users can't connect to it in between
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let tmp_directory = tempfile::Builder::new()
+        .prefix(".socket")
+        .permissions(fs::Permissions::from_mode(0o700))
+        .tempdir_in(parent)?;
+    let tmp_path = tmp_directory.path().join("socket");
+
+    let listener = UnixListener::bind(&tmp_path)
+        .map_err(|error| anyhow!("Failed to listen on {}: {error}", path.display()))?;
+    fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o600))?;
+    fs::rename(&tmp_path, path)?;
+
+    Ok(listener)
+}
+
 pub(super) fn print_disk_farm_info(directory: PathBuf, farm_index: usize) {
     println!("Single disk farm {farm_index}:");
     match SingleDiskFarm::collect_summary(directory) {
@@ -151,6 +229,9 @@ pub(super) fn print_disk_farm_info(directory: PathBuf, farm_index: usize) {
             println!("  ID: {}", info.id());
             println!("  Genesis hash: 0x{}", hex::encode(info.genesis_hash()));
             println!("  Public key: 0x{}", hex::encode(info.public_key()));
+            if let Some(identity_derivation_path) = info.identity_derivation_path() {
+                println!("  Identity derivation path: {identity_derivation_path}");
+            }
             println!(
                 "  Allocated space: {} ({})",
                 bytesize::to_string(info.allocated_space(), true),
@@ -0,0 +1,178 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use backoff::ExponentialBackoff;
+use clap::Parser;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{Piece, PieceIndex};
+use subspace_data_retrieval::object_fetcher::BoxError;
+use subspace_data_retrieval::piece_fetcher::{
+    PieceFetcher, PieceGetter, PieceSourceTier, RetryPolicy,
+};
+use subspace_farmer::farmer_piece_getter::piece_validator::SegmentCommitmentPieceValidator;
+use subspace_farmer::node_client::NodeClient;
+use subspace_networking::libp2p::identity::Keypair;
+use subspace_networking::libp2p::kad::Mode;
+use subspace_networking::libp2p::multiaddr::Protocol;
+use subspace_networking::libp2p::Multiaddr;
+use subspace_networking::utils::piece_provider::{PieceProvider, PieceValidator};
+use subspace_networking::{construct, Config, KademliaMode, NodeRunner};
+use subspace_rpc_primitives::FarmerAppInfo;
+
+/// Get piece retry attempts number.
+const PIECE_GETTER_MAX_RETRIES: u16 = 7;
+/// Defines initial duration between get_piece calls.
+const GET_PIECE_INITIAL_INTERVAL: Duration = Duration::from_secs(5);
+/// Defines max duration between get_piece calls.
+const GET_PIECE_MAX_INTERVAL: Duration = Duration::from_secs(40);
+/// Max number of random walk rounds when getting pieces from archival storage.
+const MAX_RANDOM_WALK_ROUNDS: usize = 15;
+/// Number of concurrent piece requests.
+const PIECE_REQUEST_CONCURRENCY: NonZeroUsize = NonZeroUsize::new(128).expect("Not zero; qed");
+
+/// Configuration of DSN client used to retrieve pieces outside of farming
+#[derive(Debug, Parser)]
+pub(in super::super) struct DsnClientArgs {
+    /// Multiaddrs of DSN bootstrap nodes to connect to on startup, multiple are supported.
+    ///
+    /// Defaults to DSN bootstrap nodes of the node farmer is connected to.
+    #[arg(long)]
+    dsn_bootstrap_nodes: Vec<Multiaddr>,
+    /// Multiaddr to listen on for DSN networking, multiple are supported
+    #[arg(long, default_values_t = [
+        Multiaddr::from(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+            .with(Protocol::Tcp(0)),
+        Multiaddr::from(IpAddr::V6(Ipv6Addr::UNSPECIFIED))
+            .with(Protocol::Tcp(0))
+    ])]
+    dsn_listen_on: Vec<Multiaddr>,
+    /// Determines whether we allow keeping non-global (private, shared, loopback..) addresses in
+    /// Kademlia DHT.
+    #[arg(long, default_value_t = false)]
+    dsn_allow_private_ips: bool,
+}
+
+/// Gets pieces from node's piece cache
+#[derive(Debug)]
+struct NodeSource<NC> {
+    node_client: NC,
+}
+
+#[async_trait]
+impl<NC> PieceGetter for NodeSource<NC>
+where
+    NC: NodeClient,
+{
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        self.node_client.piece(piece_index).await
+    }
+}
+
+/// Gets pieces from archival storage (L1)
+struct ArchivalStorageSource<PV> {
+    piece_provider: Arc<PieceProvider<PV>>,
+}
+
+impl<PV> fmt::Debug for ArchivalStorageSource<PV> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArchivalStorageSource")
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<PV> PieceGetter for ArchivalStorageSource<PV>
+where
+    PV: PieceValidator + Send + 'static,
+{
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        Ok(self
+            .piece_provider
+            .get_piece_from_archival_storage(piece_index, MAX_RANDOM_WALK_ROUNDS)
+            .await)
+    }
+}
+
+/// Create client-only DSN node and piece fetcher that retrieves verified pieces from DSN and node,
+/// using archival storage as the last resort.
+///
+/// Returned node runner must be driven for piece fetcher to make progress.
+pub(in super::super) fn dsn_piece_fetcher<NC>(
+    dsn_client_args: DsnClientArgs,
+    farmer_app_info: &FarmerAppInfo,
+    node_client: NC,
+    kzg: Kzg,
+) -> anyhow::Result<(PieceFetcher, NodeRunner<()>)>
+where
+    NC: NodeClient + Clone,
+{
+    let DsnClientArgs {
+        mut dsn_bootstrap_nodes,
+        dsn_listen_on,
+        dsn_allow_private_ips,
+    } = dsn_client_args;
+
+    if dsn_bootstrap_nodes.is_empty() {
+        dsn_bootstrap_nodes = farmer_app_info.dsn_bootstrap_nodes.clone();
+    }
+
+    let config = Config {
+        listen_on: dsn_listen_on,
+        allow_non_global_addresses_in_dht: dsn_allow_private_ips,
+        bootstrap_addresses: dsn_bootstrap_nodes,
+        kademlia_mode: KademliaMode::Static(Mode::Client),
+        ..Config::new(
+            hex::encode(farmer_app_info.genesis_hash),
+            Keypair::generate_ed25519(),
+            (),
+            None,
+        )
+    };
+    let (node, node_runner) =
+        construct(config).map_err(|error| anyhow!("Failed to configure networking: {error}"))?;
+
+    let validator = Some(SegmentCommitmentPieceValidator::new(
+        node.clone(),
+        node_client.clone(),
+        kzg,
+    ));
+    let piece_provider = Arc::new(PieceProvider::new(node, validator));
+
+    let piece_fetcher = PieceFetcher::new(
+        vec![
+            PieceSourceTier {
+                name: "cache",
+                sources: vec![
+                    Arc::clone(&piece_provider) as Arc<dyn PieceGetter + Send + Sync>,
+                    Arc::new(NodeSource { node_client }),
+                ],
+                retry_policy: RetryPolicy {
+                    max_retries: PIECE_GETTER_MAX_RETRIES,
+                    backoff: ExponentialBackoff {
+                        initial_interval: GET_PIECE_INITIAL_INTERVAL,
+                        max_interval: GET_PIECE_MAX_INTERVAL,
+                        // Try until we get a valid piece
+                        max_elapsed_time: None,
+                        multiplier: 1.75,
+                        ..ExponentialBackoff::default()
+                    },
+                },
+                validation_hook: None,
+            },
+            PieceSourceTier {
+                name: "archival storage",
+                sources: vec![Arc::new(ArchivalStorageSource { piece_provider })],
+                retry_policy: RetryPolicy::no_retries(),
+                validation_hook: None,
+            },
+        ],
+        PIECE_REQUEST_CONCURRENCY,
+    );
+
+    Ok((piece_fetcher, node_runner))
+}
@@ -0,0 +1,145 @@
+use anyhow::anyhow;
+use clap::{Parser, ValueHint};
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::{fs, io};
+#[cfg(unix)]
+use subspace_farmer::reward_signer::unix_socket::UnixSocketRewardSigner;
+use subspace_farmer::reward_signer::RewardSigner;
+use subspace_farmer::single_disk_farm::identity::{Identity, IdentityPassphrase, MasterIdentity};
+use zeroize::Zeroizing;
+
+fn parse_identity_passphrase(s: &str) -> Result<IdentityPassphrase, Infallible> {
+    Ok(IdentityPassphrase::from(s.to_string()))
+}
+
+/// Identity encryption options
+#[derive(Debug, Parser)]
+pub(in super::super) struct IdentityPassphraseArgs {
+    /// Passphrase used to encrypt identities.
+    ///
+    /// Identities stored in plain text are encrypted with this passphrase on start and new
+    /// identities are created encrypted. Prefer environment variable or `--identity-passphrase-file`
+    /// over command line argument, since command line is visible to other users of the system.
+    #[arg(
+        long,
+        env = "SUBSPACE_FARMER_IDENTITY_PASSPHRASE",
+        hide_env_values = true,
+        value_parser = parse_identity_passphrase
+    )]
+    identity_passphrase: Option<IdentityPassphrase>,
+    /// Path to the file with passphrase used to encrypt identities (trailing newline is ignored),
+    /// alternative to `--identity-passphrase`
+    #[arg(long, value_hint = ValueHint::FilePath, conflicts_with = "identity_passphrase")]
+    identity_passphrase_file: Option<PathBuf>,
+}
+
+impl IdentityPassphraseArgs {
+    /// Passphrase provided by the user, if any
+    pub(in super::super) fn passphrase(self) -> anyhow::Result<Option<IdentityPassphrase>> {
+        if let Some(identity_passphrase) = self.identity_passphrase {
+            return Ok(Some(identity_passphrase));
+        }
+
+        let Some(identity_passphrase_file) = self.identity_passphrase_file else {
+            return Ok(None);
+        };
+
+        let mut contents = Zeroizing::new(fs::read_to_string(&identity_passphrase_file).map_err(
+            |error| {
+                anyhow!(
+                    "Failed to read identity passphrase file {}: {error}",
+                    identity_passphrase_file.display()
+                )
+            },
+        )?);
+        let passphrase_length = contents.trim_end_matches(['\r', '\n']).len();
+        contents.truncate(passphrase_length);
+
+        if contents.is_empty() {
+            return Err(anyhow!(
+                "Identity passphrase file {} is empty",
+                identity_passphrase_file.display()
+            ));
+        }
+
+        Ok(Some(IdentityPassphrase::from(contents.to_string())))
+    }
+}
+
+/// Remote reward signer options
+#[derive(Debug, Parser)]
+pub(in super::super) struct RemoteRewardSignerArgs {
+    /// Path to Unix socket of remote reward signer (see `reward-signer` command) that will sign
+    /// rewards instead of farmer process.
+    ///
+    /// Identities of farms are not decrypted when remote reward signer is used, only public keys
+    /// are read (except identity of the first farm in `farm` command, networking keypair is derived
+    /// from it). Remote reward signer must have identities of all farms loaded.
+    #[cfg(unix)]
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    reward_signer_socket: Option<PathBuf>,
+}
+
+impl RemoteRewardSignerArgs {
+    /// Remote reward signer, if configured
+    pub(in super::super) fn reward_signer(&self) -> Option<Arc<dyn RewardSigner>> {
+        #[cfg(unix)]
+        if let Some(path) = &self.reward_signer_socket {
+            return Some(Arc::new(UnixSocketRewardSigner::new(path)));
+        }
+
+        None
+    }
+}
+
+/// Master identity options
+#[derive(Debug, Parser)]
+pub(in super::super) struct MasterIdentityArgs {
+    /// Directory with master identity that identities of newly created farms are derived from
+    /// (created if doesn't exist), instead of generating random identity for every farm.
+    ///
+    /// Derivation path is recorded in farm info, such that farm identity can be restored from
+    /// master identity, use `identities` command to see identities of farms and their derivation
+    /// paths. Master identity is encrypted with identity passphrase, if provided.
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    master_identity: Option<PathBuf>,
+}
+
+impl MasterIdentityArgs {
+    /// Open (or create if `create` is `true`) master identity, if configured
+    pub(in super::super) fn master_identity(
+        self,
+        passphrase: Option<&IdentityPassphrase>,
+        create: bool,
+    ) -> anyhow::Result<Option<MasterIdentity>> {
+        let Some(directory) = self.master_identity else {
+            return Ok(None);
+        };
+
+        let identity = if create {
+            fs::create_dir_all(&directory).map_err(|error| {
+                anyhow!(
+                    "Master identity directory {} doesn't exist and can't be created: {error}",
+                    directory.display()
+                )
+            })?;
+            Identity::open_or_create(&directory, passphrase)
+        } else {
+            Identity::open(&directory, passphrase).and_then(|maybe_identity| {
+                maybe_identity.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "Master identity doesn't exist").into()
+                })
+            })
+        }
+        .map_err(|error| {
+            anyhow!(
+                "Failed to open master identity in {}: {error}",
+                directory.display()
+            )
+        })?;
+
+        Ok(Some(MasterIdentity::new(identity)))
+    }
+}
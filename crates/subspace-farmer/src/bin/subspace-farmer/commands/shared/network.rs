@@ -1,10 +1,12 @@
 use async_lock::RwLock as AsyncRwLock;
 use clap::Parser;
+use futures::stream::FuturesUnordered;
 use prometheus_client::registry::Registry;
 use std::collections::HashSet;
 use std::fmt;
 use std::hash::Hash;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::num::NonZeroU64;
 use std::path::Path;
 use std::sync::{Arc, Weak};
 use subspace_farmer::farm::plotted_pieces::PlottedPieces;
@@ -17,9 +19,11 @@ use subspace_networking::libp2p::Multiaddr;
 use subspace_networking::utils::multihash::ToMultihash;
 use subspace_networking::utils::strip_peer_id;
 use subspace_networking::{
-    construct, Config, KademliaMode, KnownPeersManager, KnownPeersManagerConfig, Node, NodeRunner,
-    PieceByIndexRequest, PieceByIndexRequestHandler, PieceByIndexResponse,
-    SegmentHeaderBySegmentIndexesRequestHandler, SegmentHeaderRequest, SegmentHeaderResponse,
+    construct, parse_protocol_bandwidth_limit, BandwidthLimits, Config, KademliaMode,
+    KnownPeersManager, KnownPeersManagerConfig, Node, NodeRunner, PieceByIndexRequest,
+    PieceByIndexRequestHandler, PieceByIndexResponse, PieceByIndexResult, PiecesByIndexRequest,
+    PiecesByIndexRequestHandler, Reachability, SegmentHeaderBySegmentIndexesRequestHandler,
+    SegmentHeaderRequest, SegmentHeaderResponse,
 };
 use subspace_rpc_primitives::MAX_SEGMENT_HEADERS_PER_REQUEST;
 use tracing::{debug, error, info, Instrument};
@@ -66,6 +70,32 @@ pub(in super::super) struct NetworkArgs {
     /// Known external addresses
     #[arg(long = "external-address")]
     pub(in super::super) external_addresses: Vec<Multiaddr>,
+    /// Enable QUIC transport in addition to TCP, QUIC listen addresses need to be specified
+    /// explicitly with `--listen-on`, for instance `/ip4/0.0.0.0/udp/30533/quic-v1`.
+    #[arg(long, default_value_t = false)]
+    pub(in super::super) enable_quic: bool,
+    /// Multiaddresses of circuit relay servers (ending with `/p2p/<peer_id>`) to make reservations
+    /// with if farmer is behind NAT, such that other peers can reach it, multiple are supported.
+    #[arg(long = "relay-server")]
+    pub(in super::super) relay_servers: Vec<Multiaddr>,
+    /// Upload limit for DSN request-response protocols combined in bytes per second, unlimited by
+    /// default.
+    #[arg(long)]
+    pub(in super::super) upload_limit: Option<NonZeroU64>,
+    /// Download limit for DSN request-response protocols combined in bytes per second, unlimited
+    /// by default.
+    #[arg(long)]
+    pub(in super::super) download_limit: Option<NonZeroU64>,
+    /// Upload limit for a particular DSN request-response protocol in format
+    /// `PROTOCOL=BYTES_PER_SECOND`, for instance `/subspace/piece-by-index/0.2.0=1048576`,
+    /// multiple are supported.
+    #[arg(long, value_parser = parse_protocol_bandwidth_limit)]
+    pub(in super::super) protocol_upload_limit: Vec<(String, NonZeroU64)>,
+    /// Download limit for a particular DSN request-response protocol in format
+    /// `PROTOCOL=BYTES_PER_SECOND`, for instance `/subspace/piece-by-index/0.2.0=1048576`,
+    /// multiple are supported.
+    #[arg(long, value_parser = parse_protocol_bandwidth_limit)]
+    pub(in super::super) protocol_download_limit: Vec<(String, NonZeroU64)>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -83,6 +113,12 @@ pub(in super::super) fn configure_network<FarmIndex, CacheIndex, NC>(
         pending_in_connections,
         pending_out_connections,
         external_addresses,
+        enable_quic,
+        relay_servers,
+        upload_limit,
+        download_limit,
+        protocol_upload_limit,
+        protocol_download_limit,
     }: NetworkArgs,
     weak_plotted_pieces: Weak<AsyncRwLock<PlottedPieces<FarmIndex>>>,
     node_client: NC,
@@ -118,7 +154,55 @@ where
         reserved_peers,
         listen_on,
         allow_non_global_addresses_in_dht: allow_private_ips,
+        enable_quic,
         known_peers_registry,
+        pieces_by_index_request_handler: Some(PiecesByIndexRequestHandler::create({
+            let weak_plotted_pieces = weak_plotted_pieces.clone();
+            let farmer_cache = farmer_cache.clone();
+
+            move |_, request| {
+                let PiecesByIndexRequest {
+                    piece_indexes,
+                    cached_only,
+                } = request;
+                debug!(
+                    pieces_count = %piece_indexes.len(),
+                    %cached_only,
+                    "Pieces request received"
+                );
+
+                // Pieces are read concurrently and sent back as soon as each of them is ready
+                piece_indexes
+                    .into_iter()
+                    .map(|piece_index| {
+                        let weak_plotted_pieces = weak_plotted_pieces.clone();
+                        let farmer_cache = farmer_cache.clone();
+
+                        async move {
+                            let mut maybe_piece =
+                                farmer_cache.get_piece(piece_index.to_multihash()).await;
+
+                            if maybe_piece.is_none() && !cached_only {
+                                let maybe_read_piece_fut =
+                                    weak_plotted_pieces.upgrade().and_then(|plotted_pieces| {
+                                        plotted_pieces.try_read()?.read_piece(piece_index)
+                                    });
+
+                                if let Some(read_piece_fut) = maybe_read_piece_fut {
+                                    maybe_piece = read_piece_fut.await;
+                                }
+                            }
+
+                            match maybe_piece {
+                                Some(piece) => PieceByIndexResult::Found { piece_index, piece },
+                                None => PieceByIndexResult::NotFound { piece_index },
+                            }
+                        }
+                        .in_current_span()
+                    })
+                    .collect::<FuturesUnordered<_>>()
+            }
+        })),
         request_response_protocols: vec![
             PieceByIndexRequestHandler::create(move |_, &PieceByIndexRequest { piece_index }| {
                 debug!(?piece_index, "Piece request received. Trying cache...");
@@ -217,9 +301,16 @@ where
         max_pending_outgoing_connections: pending_out_connections,
         max_established_incoming_connections: in_connections,
         max_pending_incoming_connections: pending_in_connections,
+        bandwidth_limits: BandwidthLimits::new(
+            upload_limit,
+            download_limit,
+            protocol_upload_limit,
+            protocol_download_limit,
+        ),
         bootstrap_addresses: bootstrap_nodes,
         kademlia_mode: KademliaMode::Dynamic,
         external_addresses,
+        relay_servers,
         ..default_config
     };
 
@@ -237,6 +328,25 @@ where
             }))
             .detach();
 
+            node.on_reachability_change(Arc::new(|reachability| match reachability {
+                Reachability::Unknown => {
+                    debug!("DSN reachability is unknown");
+                }
+                Reachability::Public { address } => {
+                    info!(%address, "DSN is reachable by other peers directly");
+                }
+                Reachability::Private => {
+                    info!(
+                        "DSN is behind NAT and not reachable by other peers, consider port \
+                        forwarding or specifying relay servers with `--relay-server`"
+                    );
+                }
+                Reachability::Relayed { relays } => {
+                    info!(?relays, "DSN is reachable by other peers through relays");
+                }
+            }))
+            .detach();
+
             // Consider returning HandlerId instead of each `detach()` calls for other usages.
             (node, node_runner)
         })
@@ -0,0 +1,32 @@
+use crate::commands::shared::bind_unix_socket;
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn unix_socket_is_only_accessible_by_current_user() {
+    let directory = tempdir().unwrap();
+    let path = directory.path().join("farmer.sock");
+
+    let _listener = bind_unix_socket(&path).unwrap();
+
+    let metadata = fs::symlink_metadata(&path).unwrap();
+    assert!(metadata.file_type().is_socket());
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    // Temporary directory the socket was bound in is gone
+    assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn unix_socket_replaces_only_existing_socket() {
+    let directory = tempdir().unwrap();
+    let path = directory.path().join("farmer.sock");
+
+    drop(bind_unix_socket(&path).unwrap());
+    let _listener = bind_unix_socket(&path).unwrap();
+
+    let file_path = directory.path().join("file");
+    fs::write(&file_path, b"data").unwrap();
+    assert!(bind_unix_socket(&file_path).is_err());
+    assert_eq!(fs::read(&file_path).unwrap(), b"data");
+}
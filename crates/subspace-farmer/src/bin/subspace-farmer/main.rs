@@ -13,7 +13,7 @@ mod utils;
 use clap::Parser;
 use std::fs;
 use std::path::PathBuf;
-use subspace_farmer::single_disk_farm::{ScrubTarget, SingleDiskFarm};
+use subspace_farmer::single_disk_farm::SingleDiskFarm;
 use subspace_proof_of_space::chia::ChiaTable;
 use tracing::info;
 use tracing_subscriber::filter::LevelFilter;
@@ -44,25 +44,26 @@ enum Command {
         ///   /path/to/directory
         disk_farms: Vec<PathBuf>,
     },
+    /// Prints identities of farms and paths they were derived from master identity with
+    Identities(commands::identities::IdentitiesArgs),
     /// Checks the farm for corruption and repairs errors (caused by disk errors or something else)
-    Scrub {
-        /// One or more farm located at specified path.
-        ///
-        /// Example:
-        ///   /path/to/directory
-        disk_farms: Vec<PathBuf>,
-        /// Disable farm locking, for example if file system doesn't support it
-        #[arg(long)]
-        disable_farm_locking: bool,
-        /// Scrub target
-        ///
-        /// Possible values are: `all`, `metadata`, `plot` and `cache`
-        #[arg(long, default_value_t = ScrubTarget::All)]
-        target: ScrubTarget,
-        /// Check for errors, but do not attempt to correct them
-        #[arg(long)]
-        dry_run: bool,
-    },
+    Scrub(commands::scrub::ScrubArgs),
+    /// Changes allocated space of existing farms without wiping them.
+    ///
+    /// Shrinking drops trailing sectors, growing allows more sectors to be plotted next time farm
+    /// is started.
+    Resize(commands::resize::ResizeArgs),
+    /// Migrates farm to a different location (like another disk), verifying copied data
+    MigrateFarm(commands::migrate_farm::MigrateFarmArgs),
+    /// Signs rewards with identities on behalf of farmers connected over Unix socket, allows to
+    /// keep identities out of farmer process
+    #[cfg(unix)]
+    RewardSigner(commands::reward_signer::RewardSignerArgs),
+    /// Attaches, detaches and lists farms of the farmer running with `--control-socket`
+    #[cfg(unix)]
+    FarmControl(commands::farm_control::FarmControlArgs),
+    /// Exports archived history to files that can be imported by the node
+    ExportHistory(commands::export_history::ExportHistoryArgs),
     /// Wipes the farm
     Wipe {
         /// One or more farm located at specified path.
@@ -113,17 +114,28 @@ async fn main() -> anyhow::Result<()> {
                 commands::info(disk_farms);
             }
         }
-        Command::Scrub {
-            disk_farms,
-            disable_farm_locking,
-            target,
-            dry_run,
-        } => {
-            if disk_farms.is_empty() {
-                info!("No farm was specified, so there is nothing to do");
-            } else {
-                commands::scrub(&disk_farms, disable_farm_locking, target, dry_run);
-            }
+        Command::Identities(identities_args) => {
+            commands::identities::identities(identities_args)?;
+        }
+        Command::Scrub(scrub_args) => {
+            commands::scrub::scrub::<PosTable>(scrub_args).await?;
+        }
+        Command::Resize(resize_args) => {
+            commands::resize::resize(resize_args)?;
+        }
+        Command::MigrateFarm(migrate_farm_args) => {
+            commands::migrate_farm::migrate_farm(migrate_farm_args)?;
+        }
+        #[cfg(unix)]
+        Command::RewardSigner(reward_signer_args) => {
+            commands::reward_signer::reward_signer(reward_signer_args).await?;
+        }
+        #[cfg(unix)]
+        Command::FarmControl(farm_control_args) => {
+            commands::farm_control::farm_control(farm_control_args).await?;
+        }
+        Command::ExportHistory(export_history_args) => {
+            commands::export_history::export_history(export_history_args).await?;
         }
         Command::Wipe { disk_farms } => {
             for disk_farm in &disk_farms {
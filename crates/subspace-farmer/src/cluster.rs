@@ -49,9 +49,16 @@
 //! There could be any number of caches in the cluster, but each cache instance belongs to one of
 //! the controllers. So if multiple controllers are present in the cluster, you'll want at least one
 //! cache connected to each as well for optimal performance.
+//!
+//! ### Transport
+//!
+//! Components communicate with each other through [`nats_client::NatsClient`], which is typically
+//! backed by NATS server, but can also work over direct TCP connections between components or
+//! in-process, see [`transport`] module for details.
 
 pub mod cache;
 pub mod controller;
 pub mod farmer;
 pub mod nats_client;
 pub mod plotter;
+pub mod transport;
@@ -1,7 +1,7 @@
 //! NATS client
 //!
-//! [`NatsClient`] provided here is a wrapper around [`Client`] that provides convenient methods
-//! using domain-specific traits.
+//! [`NatsClient`] provided here is a wrapper around [`ClusterTransport`] (typically NATS
+//! [`Client`]) that provides convenient methods using domain-specific traits.
 //!
 //! Before reading code, make sure to familiarize yourself with NATS documentation, especially with
 //! [subjects](https://docs.nats.io/nats-concepts/subjects) and
@@ -12,20 +12,30 @@
 //! * request/stream of responses (for example a stream of plotted sectors of the farmer)
 //! * notifications (typically targeting a particular instance of an app) and corresponding subscriptions (for example solution notification)
 //! * broadcasts and corresponding subscriptions (for example slot info broadcast)
+//!
+//! When registry is provided during instantiation, client exposes Prometheus metrics for request
+//! latency, message sizes, request timeouts/retries and response streams.
+
+mod metrics;
+#[cfg(test)]
+mod tests;
 
+use crate::cluster::nats_client::metrics::{ActiveStreamGuard, NatsClientMetrics, StreamDirection};
+use crate::cluster::transport::nats::NatsTransport;
+use crate::cluster::transport::{
+    ClusterTransport, TransportError, TransportMessage, TransportSubscription, REQUEST_TIMEOUT,
+};
 use crate::utils::AsyncJoinOnDrop;
 use anyhow::anyhow;
-use async_nats::{
-    Client, ConnectOptions, HeaderMap, HeaderValue, Message, PublishError, RequestError,
-    RequestErrorKind, Subject, SubscribeError, Subscriber, ToServerAddrs,
-};
+use async_nats::{Client, ConnectOptions, HeaderValue, ToServerAddrs};
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoff;
-use derive_more::{Deref, DerefMut};
+use bytes::Bytes;
 use futures::channel::mpsc;
 use futures::stream::FuturesUnordered;
 use futures::{select, FutureExt, Stream, StreamExt};
 use parity_scale_codec::{Decode, Encode};
+use prometheus_client::registry::Registry;
 use std::any::type_name;
 use std::collections::VecDeque;
 use std::future::{pending, Future};
@@ -33,17 +43,13 @@ use std::marker::PhantomData;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{fmt, mem};
-use thiserror::Error;
 use tracing::{debug, error, trace, warn, Instrument};
 use ulid::Ulid;
 
 const EXPECTED_MESSAGE_SIZE: usize = 2 * 1024 * 1024;
 const ACKNOWLEDGEMENT_TIMEOUT: Duration = Duration::from_mins(1);
-/// Requests should time out eventually, but we should set a larger timeout to allow for spikes in
-/// load to be absorbed gracefully
-const REQUEST_TIMEOUT: Duration = Duration::from_mins(5);
 
 /// Generic request with associated response.
 ///
@@ -161,30 +167,20 @@ where
     }
 }
 
-/// Stream request error
-#[derive(Debug, Error)]
-pub enum StreamRequestError {
-    /// Subscribe error
-    #[error("Subscribe error: {0}")]
-    Subscribe(#[from] SubscribeError),
-    /// Publish error
-    #[error("Publish error: {0}")]
-    Publish(#[from] PublishError),
-}
-
 /// Wrapper around subscription that transforms [`GenericStreamResponses<Response>`] messages into a
 /// normal `Response` stream.
-#[derive(Debug, Deref, DerefMut)]
+#[derive(Debug)]
 #[pin_project::pin_project]
 pub struct StreamResponseSubscriber<Response> {
     #[pin]
-    #[deref]
-    #[deref_mut]
-    subscriber: Subscriber,
+    subscriber: TransportSubscription,
+    subject: &'static str,
     response_subject: String,
     buffered_responses: Option<GenericStreamResponses<Response>>,
     next_index: u32,
     acknowledgement_sender: mpsc::UnboundedSender<(String, u32)>,
+    nats_client: NatsClient,
+    _active_stream: Option<ActiveStreamGuard>,
     _background_task: AsyncJoinOnDrop<()>,
     _phantom: PhantomData<Response>,
 }
@@ -210,9 +206,21 @@ where
         let mut projected = self.project();
         match projected.subscriber.poll_next_unpin(cx) {
             Poll::Ready(Some(message)) => {
+                let metrics = projected.nats_client.inner.metrics.as_ref();
+                if let Some(metrics) = metrics {
+                    metrics.observe_received_message(*projected.subject, message.payload.len());
+                }
+
                 match GenericStreamResponses::<Response>::decode(&mut message.payload.as_ref()) {
                     Ok(mut responses) => {
                         if responses.index() != *projected.next_index {
+                            if let Some(metrics) = metrics {
+                                metrics.note_dropped_stream(
+                                    *projected.subject,
+                                    StreamDirection::Incoming,
+                                    "unexpected_index",
+                                );
+                            }
                             warn!(
                                 actual_index = %responses.index(),
                                 expected_index = %*projected.next_index,
@@ -251,6 +259,13 @@ where
                         }
                     }
                     Err(error) => {
+                        if let Some(metrics) = metrics {
+                            metrics.note_dropped_stream(
+                                *projected.subject,
+                                StreamDirection::Incoming,
+                                "decoding_failed",
+                            );
+                        }
                         warn!(
                             %error,
                             response_type = %type_name::<Response>(),
@@ -270,12 +285,24 @@ where
 }
 
 impl<Response> StreamResponseSubscriber<Response> {
-    fn new(subscriber: Subscriber, response_subject: String, nats_client: NatsClient) -> Self {
+    fn new(
+        subscriber: TransportSubscription,
+        subject: &'static str,
+        response_subject: String,
+        nats_client: NatsClient,
+    ) -> Self {
         let (acknowledgement_sender, mut acknowledgement_receiver) =
             mpsc::unbounded::<(String, u32)>();
 
+        let active_stream = nats_client
+            .inner
+            .metrics
+            .as_ref()
+            .map(|metrics| metrics.start_stream(subject, StreamDirection::Incoming));
+
         let ack_publisher_fut = {
             let response_subject = response_subject.clone();
+            let nats_client = nats_client.clone();
 
             async move {
                 while let Some((subject, index)) = acknowledgement_receiver.next().await {
@@ -307,11 +334,14 @@ impl<Response> StreamResponseSubscriber<Response> {
             AsyncJoinOnDrop::new(tokio::spawn(ack_publisher_fut.in_current_span()), true);
 
         Self {
+            subject,
             response_subject,
             subscriber,
             buffered_responses: None,
             next_index: 0,
             acknowledgement_sender,
+            nats_client,
+            _active_stream: active_stream,
             _background_task: background_task,
             _phantom: PhantomData,
         }
@@ -342,13 +372,11 @@ pub trait GenericBroadcast: Encode + Decode + fmt::Debug + Send + Sync + 'static
 }
 
 /// Subscriber wrapper that decodes messages automatically and skips messages that can't be decoded
-#[derive(Debug, Deref, DerefMut)]
+#[derive(Debug)]
 #[pin_project::pin_project]
 pub struct SubscriberWrapper<Message> {
     #[pin]
-    #[deref]
-    #[deref_mut]
-    subscriber: Subscriber,
+    subscriber: TransportSubscription,
     _phantom: PhantomData<Message>,
 }
 
@@ -381,32 +409,27 @@ where
 
 #[derive(Debug)]
 struct Inner {
-    client: Client,
+    transport: Arc<dyn ClusterTransport>,
     request_retry_backoff_policy: ExponentialBackoff,
     approximate_max_message_size: usize,
     max_message_size: usize,
+    metrics: Option<NatsClientMetrics>,
 }
 
-/// NATS client wrapper that can be used to interact with other Subspace-specific clients
+/// NATS client wrapper that can be used to interact with other Subspace-specific clients.
+///
+/// Despite the name, it works over any [`ClusterTransport`], not just NATS.
 #[derive(Debug, Clone)]
 pub struct NatsClient {
     inner: Arc<Inner>,
 }
 
-impl Deref for NatsClient {
-    type Target = Client;
-
-    #[inline]
-    fn deref(&self) -> &Self::Target {
-        &self.inner.client
-    }
-}
-
 impl NatsClient {
     /// Create new instance by connecting to specified addresses
     pub async fn new<A: ToServerAddrs>(
         addrs: A,
         request_retry_backoff_policy: ExponentialBackoff,
+        registry: Option<&mut Registry>,
     ) -> Result<Self, async_nats::Error> {
         let servers = addrs.to_server_addrs()?.collect::<Vec<_>>();
         Self::from_client(
@@ -416,6 +439,7 @@ impl NatsClient {
             )
             .await?,
             request_retry_backoff_policy,
+            registry,
         )
     }
 
@@ -423,6 +447,7 @@ impl NatsClient {
     pub fn from_client(
         client: Client,
         request_retry_backoff_policy: ExponentialBackoff,
+        registry: Option<&mut Registry>,
     ) -> Result<Self, async_nats::Error> {
         let max_payload = client.server_info().max_payload;
         if max_payload < EXPECTED_MESSAGE_SIZE {
@@ -433,13 +458,38 @@ impl NatsClient {
             .into());
         }
 
+        Self::from_transport(
+            Arc::new(NatsTransport::new(client)),
+            request_retry_backoff_policy,
+            registry,
+        )
+        .map_err(Into::into)
+    }
+
+    /// Create new client on top of arbitrary cluster transport
+    pub fn from_transport(
+        transport: Arc<dyn ClusterTransport>,
+        request_retry_backoff_policy: ExponentialBackoff,
+        registry: Option<&mut Registry>,
+    ) -> Result<Self, TransportError> {
+        let max_payload = transport.max_payload();
+        if max_payload < EXPECTED_MESSAGE_SIZE {
+            return Err(TransportError::Other(
+                format!(
+                    "Max payload {max_payload} is smaller than expected {EXPECTED_MESSAGE_SIZE}"
+                )
+                .into(),
+            ));
+        }
+
         let inner = Inner {
-            client,
+            transport,
             request_retry_backoff_policy,
             // Allow up to 90%, the rest will be wrapper data structures, etc.
             approximate_max_message_size: max_payload * 9 / 10,
             // Allow up to 90%, the rest will be wrapper data structures, etc.
             max_message_size: max_payload,
+            metrics: registry.map(NatsClientMetrics::new),
         };
 
         Ok(Self {
@@ -453,33 +503,46 @@ impl NatsClient {
         self.inner.approximate_max_message_size
     }
 
+    /// Underlying cluster transport
+    pub fn transport(&self) -> &Arc<dyn ClusterTransport> {
+        &self.inner.transport
+    }
+
     /// Make request and wait for response
     pub async fn request<Request>(
         &self,
         request: &Request,
         instance: Option<&str>,
-    ) -> Result<Request::Response, RequestError>
+    ) -> Result<Request::Response, TransportError>
     where
         Request: GenericRequest,
     {
         let subject = subject_with_instance(Request::SUBJECT, instance);
+        let payload = Bytes::from(request.encode());
+        self.observe_sent_message(Request::SUBJECT, payload.len());
+        let start = Instant::now();
         let mut maybe_retry_backoff = None;
         let message = loop {
             match self
                 .inner
-                .client
-                .request(subject.clone(), request.encode().into())
+                .transport
+                .request(subject.clone(), payload.clone())
                 .await
             {
                 Ok(message) => {
                     break message;
                 }
                 Err(error) => {
-                    match error.kind() {
-                        RequestErrorKind::TimedOut | RequestErrorKind::NoResponders => {
+                    match error {
+                        TransportError::TimedOut => {
+                            if let Some(metrics) = &self.inner.metrics {
+                                metrics.note_request_timeout(Request::SUBJECT);
+                            }
+                        }
+                        TransportError::NoResponders => {
                             // Continue with retries
                         }
-                        RequestErrorKind::Other => {
+                        _ => {
                             return Err(error);
                         }
                     }
@@ -491,6 +554,9 @@ impl NatsClient {
                     });
 
                     if let Some(delay) = retry_backoff.next_backoff() {
+                        if let Some(metrics) = &self.inner.metrics {
+                            metrics.note_request_retry(Request::SUBJECT);
+                        }
                         debug!(
                             %subject,
                             %error,
@@ -508,6 +574,11 @@ impl NatsClient {
             }
         };
 
+        if let Some(metrics) = &self.inner.metrics {
+            metrics.observe_request_time(Request::SUBJECT, start.elapsed());
+            metrics.observe_received_message(Request::SUBJECT, message.payload.len());
+        }
+
         let response =
             Request::Response::decode(&mut message.payload.as_ref()).map_err(|error| {
                 warn!(
@@ -518,7 +589,7 @@ impl NatsClient {
                     "Response decoding failed"
                 );
 
-                RequestErrorKind::Other
+                error
             })?;
 
         Ok(response)
@@ -555,20 +626,17 @@ impl NatsClient {
         ]);
 
         let subject = subject_with_instance(Request::SUBJECT, instance);
-        let subscription = if let Some(queue_group) = queue_group {
-            self.inner
-                .client
-                .queue_subscribe(subject, queue_group)
-                .await
-        } else {
-            self.inner.client.subscribe(subject).await
-        }
-        .map_err(|error| {
-            anyhow!(
-                "Failed to subscribe to {} requests for {instance:?}: {error}",
-                type_name::<Request>(),
-            )
-        })?;
+        let subscription = self
+            .inner
+            .transport
+            .subscribe(subject, queue_group)
+            .await
+            .map_err(|error| {
+                anyhow!(
+                    "Failed to subscribe to {} requests for {instance:?}: {error}",
+                    type_name::<Request>(),
+                )
+            })?;
 
         debug!(
             request_type = %type_name::<Request>(),
@@ -599,7 +667,7 @@ impl NatsClient {
         Ok(())
     }
 
-    async fn process_request<Request, F, OP>(&self, message: Message, process: OP)
+    async fn process_request<Request, F, OP>(&self, message: TransportMessage, process: OP)
     where
         Request: GenericRequest,
         F: Future<Output = Option<Request::Response>> + Send,
@@ -610,6 +678,9 @@ impl NatsClient {
         };
 
         let message_payload_size = message.payload.len();
+        if let Some(metrics) = &self.inner.metrics {
+            metrics.observe_received_message(Request::SUBJECT, message_payload_size);
+        }
         let request = match Request::decode(&mut message.payload.as_ref()) {
             Ok(request) => {
                 // Free allocation early
@@ -643,9 +714,13 @@ impl NatsClient {
             );
         }
 
-        if let Some(response) = process(request).await
-            && let Err(error) = self.publish(reply_subject, response.encode().into()).await
-        {
+        let Some(response) = process(request).await else {
+            return;
+        };
+        let payload = Bytes::from(response.encode());
+        self.observe_sent_message(Request::SUBJECT, payload.len());
+
+        if let Err(error) = self.publish(reply_subject, payload).await {
             warn!(
                 request_type = %type_name::<Request>(),
                 %error,
@@ -659,7 +734,7 @@ impl NatsClient {
         &self,
         request: Request,
         instance: Option<&str>,
-    ) -> Result<StreamResponseSubscriber<Request::Response>, StreamRequestError>
+    ) -> Result<StreamResponseSubscriber<Request::Response>, TransportError>
     where
         Request: GenericStreamRequest,
     {
@@ -667,8 +742,8 @@ impl NatsClient {
 
         let subscriber = self
             .inner
-            .client
-            .subscribe(stream_request.response_subject.clone())
+            .transport
+            .subscribe(stream_request.response_subject.clone(), None)
             .await?;
 
         let stream_request_subject = subject_with_instance(Request::SUBJECT, instance);
@@ -679,13 +754,13 @@ impl NatsClient {
             "Stream request subscription"
         );
 
-        self.inner
-            .client
-            .publish(stream_request_subject, stream_request.encode().into())
-            .await?;
+        let payload = Bytes::from(stream_request.encode());
+        self.observe_sent_message(Request::SUBJECT, payload.len());
+        self.publish(stream_request_subject, payload).await?;
 
         Ok(StreamResponseSubscriber::new(
             subscriber,
+            Request::SUBJECT,
             stream_request.response_subject,
             self.clone(),
         ))
@@ -701,6 +776,16 @@ impl NatsClient {
             GenericStreamResponses<<Request as GenericStreamRequest>::Response>;
 
         let mut response_stream = response_stream.fuse();
+        let _active_stream = self
+            .inner
+            .metrics
+            .as_ref()
+            .map(|metrics| metrics.start_stream(Request::SUBJECT, StreamDirection::Outgoing));
+        let note_dropped_stream = |reason| {
+            if let Some(metrics) = &self.inner.metrics {
+                metrics.note_dropped_stream(Request::SUBJECT, StreamDirection::Outgoing, reason);
+            }
+        };
 
         // Pull the first element to measure response size
         let first_element = match response_stream.next().await {
@@ -718,6 +803,7 @@ impl NatsClient {
                     )
                     .await
                 {
+                    note_dropped_stream("publish_failed");
                     warn!(
                         %response_subject,
                         %error,
@@ -735,9 +821,15 @@ impl NatsClient {
             self.approximate_max_message_size() / first_element.encoded_size();
 
         let ack_subject = format!("stream-response-ack.{}", Ulid::new());
-        let mut ack_subscription = match self.subscribe(ack_subject.clone()).await {
+        let mut ack_subscription = match self
+            .inner
+            .transport
+            .subscribe(ack_subject.clone(), None)
+            .await
+        {
             Ok(ack_subscription) => ack_subscription,
             Err(error) => {
+                note_dropped_stream("ack_subscription_failed");
                 warn!(
                     %response_subject,
                     %error,
@@ -809,6 +901,7 @@ impl NatsClient {
                         overflow_buffer.push_front(element);
                         continue;
                     } else {
+                        note_dropped_stream("oversized_response");
                         error!(
                             %response_subject,
                             request_type = %type_name::<Request>(),
@@ -827,10 +920,12 @@ impl NatsClient {
                     "Publishing stream response messages",
                 );
 
+                self.observe_sent_message(Request::SUBJECT, encoded_response_len);
                 if let Err(error) = self
                     .publish(response_subject.clone(), encoded_response.into())
                     .await
                 {
+                    note_dropped_stream("publish_failed");
                     warn!(
                         %response_subject,
                         %error,
@@ -878,6 +973,7 @@ impl NatsClient {
                                     "Received acknowledgement"
                                 );
                                 if received_index != expected_index {
+                                    note_dropped_stream("unexpected_ack_index");
                                     warn!(
                                         %response_subject,
                                         %received_index,
@@ -890,6 +986,7 @@ impl NatsClient {
                                     return;
                                 }
                             } else {
+                                note_dropped_stream("unexpected_ack");
                                 warn!(
                                     %response_subject,
                                     request_type = %type_name::<Request>(),
@@ -901,6 +998,7 @@ impl NatsClient {
                             }
                         }
                         Ok(None) => {
+                            note_dropped_stream("ack_stream_ended");
                             warn!(
                                 %response_subject,
                                 request_type = %type_name::<Request>(),
@@ -910,6 +1008,7 @@ impl NatsClient {
                             return;
                         }
                         Err(_error) => {
+                            note_dropped_stream("ack_timeout");
                             warn!(
                                 %response_subject,
                                 %expected_index,
@@ -937,17 +1036,18 @@ impl NatsClient {
         &self,
         notification: &Notification,
         instance: Option<&str>,
-    ) -> Result<(), PublishError>
+    ) -> Result<(), TransportError>
     where
         Notification: GenericNotification,
     {
-        self.inner
-            .client
-            .publish(
-                subject_with_instance(Notification::SUBJECT, instance),
-                notification.encode().into(),
-            )
-            .await
+        let payload = Bytes::from(notification.encode());
+        self.observe_sent_message(Notification::SUBJECT, payload.len());
+
+        self.publish(
+            subject_with_instance(Notification::SUBJECT, instance),
+            payload,
+        )
+        .await
     }
 
     /// Send a broadcast message
@@ -955,22 +1055,20 @@ impl NatsClient {
         &self,
         message: &Broadcast,
         instance: &str,
-    ) -> Result<(), PublishError>
+    ) -> Result<(), TransportError>
     where
         Broadcast: GenericBroadcast,
     {
+        let payload = Bytes::from(message.encode());
+        self.observe_sent_message(Broadcast::SUBJECT, payload.len());
+
         self.inner
-            .client
-            .publish_with_headers(
+            .transport
+            .publish(
                 Broadcast::SUBJECT.replace('*', instance),
-                {
-                    let mut headers = HeaderMap::new();
-                    if let Some(message_id) = message.deterministic_message_id() {
-                        headers.insert("Nats-Msg-Id", message_id);
-                    }
-                    headers
-                },
-                message.encode().into(),
+                None,
+                payload,
+                message.deterministic_message_id(),
             )
             .await
     }
@@ -981,7 +1079,7 @@ impl NatsClient {
         &self,
         instance: Option<&str>,
         queue_group: Option<String>,
-    ) -> Result<SubscriberWrapper<StreamRequest<Request>>, SubscribeError>
+    ) -> Result<SubscriberWrapper<StreamRequest<Request>>, TransportError>
     where
         Request: GenericStreamRequest,
     {
@@ -995,7 +1093,7 @@ impl NatsClient {
         &self,
         instance: Option<&str>,
         queue_group: Option<String>,
-    ) -> Result<SubscriberWrapper<Notification>, SubscribeError>
+    ) -> Result<SubscriberWrapper<Notification>, TransportError>
     where
         Notification: GenericNotification,
     {
@@ -1009,7 +1107,7 @@ impl NatsClient {
         &self,
         instance: Option<&str>,
         queue_group: Option<String>,
-    ) -> Result<SubscriberWrapper<Broadcast>, SubscribeError>
+    ) -> Result<SubscriberWrapper<Broadcast>, TransportError>
     where
         Broadcast: GenericBroadcast,
     {
@@ -1024,21 +1122,15 @@ impl NatsClient {
         subject: &'static str,
         instance: Option<&str>,
         queue_group: Option<String>,
-    ) -> Result<SubscriberWrapper<Message>, SubscribeError>
+    ) -> Result<SubscriberWrapper<Message>, TransportError>
     where
         Message: Decode,
     {
-        let subscriber = if let Some(queue_group) = queue_group {
-            self.inner
-                .client
-                .queue_subscribe(subject_with_instance(subject, instance), queue_group)
-                .await?
-        } else {
-            self.inner
-                .client
-                .subscribe(subject_with_instance(subject, instance))
-                .await?
-        };
+        let subscriber = self
+            .inner
+            .transport
+            .subscribe(subject_with_instance(subject, instance), queue_group)
+            .await?;
         debug!(
             %subject,
             message_type = %type_name::<Message>(),
@@ -1051,12 +1143,26 @@ impl NatsClient {
             _phantom: PhantomData,
         })
     }
+
+    fn observe_sent_message(&self, subject: &'static str, size: usize) {
+        if let Some(metrics) = &self.inner.metrics {
+            metrics.observe_sent_message(subject, size, self.inner.approximate_max_message_size);
+        }
+    }
+
+    /// Publish raw message without reply subject
+    async fn publish(&self, subject: String, payload: Bytes) -> Result<(), TransportError> {
+        self.inner
+            .transport
+            .publish(subject, None, payload, None)
+            .await
+    }
 }
 
-fn subject_with_instance(subject: &'static str, instance: Option<&str>) -> Subject {
+fn subject_with_instance(subject: &'static str, instance: Option<&str>) -> String {
     if let Some(instance) = instance {
-        Subject::from(subject.replace('*', instance))
+        subject.replace('*', instance)
     } else {
-        Subject::from_static(subject)
+        subject.to_string()
     }
 }
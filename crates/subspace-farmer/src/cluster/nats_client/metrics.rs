@@ -0,0 +1,215 @@
+//! Metrics for NATS client
+
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::{Registry, Unit};
+use std::fmt;
+use std::sync::atomic::{AtomicI64, AtomicU64};
+use std::time::Duration;
+
+/// Direction of the stream relatively to this client
+#[derive(Debug, Copy, Clone)]
+pub(super) enum StreamDirection {
+    /// Stream of responses received by this client
+    Incoming,
+    /// Stream of responses sent by this client
+    Outgoing,
+}
+
+impl fmt::Display for StreamDirection {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Incoming => "Incoming",
+            Self::Outgoing => "Outgoing",
+        })
+    }
+}
+
+/// Decrements active streams gauge on drop
+#[derive(Debug)]
+pub(super) struct ActiveStreamGuard {
+    active_streams: Gauge<i64, AtomicI64>,
+}
+
+impl Drop for ActiveStreamGuard {
+    #[inline]
+    fn drop(&mut self) {
+        self.active_streams.dec();
+    }
+}
+
+/// Metrics for NATS client.
+///
+/// Subjects in labels are generic subjects of messages (with `*` in place of instance), such that
+/// number of label values is bounded.
+#[derive(Debug)]
+pub(super) struct NatsClientMetrics {
+    request_time: Family<Vec<(&'static str, String)>, Histogram>,
+    request_timeouts: Family<Vec<(&'static str, String)>, Counter<u64, AtomicU64>>,
+    request_retries: Family<Vec<(&'static str, String)>, Counter<u64, AtomicU64>>,
+    sent_message_size: Family<Vec<(&'static str, String)>, Histogram>,
+    received_message_size: Family<Vec<(&'static str, String)>, Histogram>,
+    oversized_messages: Family<Vec<(&'static str, String)>, Counter<u64, AtomicU64>>,
+    dropped_streams: Family<Vec<(&'static str, String)>, Counter<u64, AtomicU64>>,
+    active_streams: Family<Vec<(&'static str, String)>, Gauge<i64, AtomicI64>>,
+}
+
+impl NatsClientMetrics {
+    /// Create new instance
+    pub(super) fn new(registry: &mut Registry) -> Self {
+        let registry = registry.sub_registry_with_prefix("nats_client");
+
+        let request_time = Family::<_, _>::new_with_constructor(|| {
+            Histogram::new(exponential_buckets(0.001, 2.0, 18))
+        });
+        registry.register_with_unit(
+            "request_time",
+            "Time it took to receive response to request, including retries",
+            Unit::Seconds,
+            request_time.clone(),
+        );
+
+        let request_timeouts = Family::default();
+        registry.register(
+            "request_timeouts",
+            "Number of request attempts that timed out",
+            request_timeouts.clone(),
+        );
+
+        let request_retries = Family::default();
+        registry.register(
+            "request_retries",
+            "Number of request retries",
+            request_retries.clone(),
+        );
+
+        let sent_message_size = Family::<_, _>::new_with_constructor(|| {
+            Histogram::new(exponential_buckets(64.0, 4.0, 10))
+        });
+        registry.register_with_unit(
+            "sent_message_size",
+            "Size of sent messages",
+            Unit::Bytes,
+            sent_message_size.clone(),
+        );
+
+        let received_message_size = Family::<_, _>::new_with_constructor(|| {
+            Histogram::new(exponential_buckets(64.0, 4.0, 10))
+        });
+        registry.register_with_unit(
+            "received_message_size",
+            "Size of received messages",
+            Unit::Bytes,
+            received_message_size.clone(),
+        );
+
+        let oversized_messages = Family::default();
+        registry.register(
+            "oversized_messages",
+            "Number of sent messages that exceeded approximate max message size",
+            oversized_messages.clone(),
+        );
+
+        let dropped_streams = Family::default();
+        registry.register(
+            "dropped_streams",
+            "Number of response streams that were aborted before the end",
+            dropped_streams.clone(),
+        );
+
+        let active_streams = Family::default();
+        registry.register(
+            "active_streams",
+            "Number of response streams currently in progress",
+            active_streams.clone(),
+        );
+
+        Self {
+            request_time,
+            request_timeouts,
+            request_retries,
+            sent_message_size,
+            received_message_size,
+            oversized_messages,
+            dropped_streams,
+            active_streams,
+        }
+    }
+
+    pub(super) fn observe_request_time(&self, subject: &'static str, time: Duration) {
+        self.request_time
+            .get_or_create(&vec![("subject", subject.to_string())])
+            .observe(time.as_secs_f64());
+    }
+
+    pub(super) fn note_request_timeout(&self, subject: &'static str) {
+        self.request_timeouts
+            .get_or_create(&vec![("subject", subject.to_string())])
+            .inc();
+    }
+
+    pub(super) fn note_request_retry(&self, subject: &'static str) {
+        self.request_retries
+            .get_or_create(&vec![("subject", subject.to_string())])
+            .inc();
+    }
+
+    pub(super) fn observe_sent_message(
+        &self,
+        subject: &'static str,
+        size: usize,
+        approximate_max_message_size: usize,
+    ) {
+        let labels = vec![("subject", subject.to_string())];
+
+        self.sent_message_size
+            .get_or_create(&labels)
+            .observe(size as f64);
+
+        if size > approximate_max_message_size {
+            self.oversized_messages.get_or_create(&labels).inc();
+        }
+    }
+
+    pub(super) fn observe_received_message(&self, subject: &'static str, size: usize) {
+        self.received_message_size
+            .get_or_create(&vec![("subject", subject.to_string())])
+            .observe(size as f64);
+    }
+
+    pub(super) fn note_dropped_stream(
+        &self,
+        subject: &'static str,
+        direction: StreamDirection,
+        reason: &'static str,
+    ) {
+        self.dropped_streams
+            .get_or_create(&vec![
+                ("subject", subject.to_string()),
+                ("direction", direction.to_string()),
+                ("reason", reason.to_string()),
+            ])
+            .inc();
+    }
+
+    /// Increase number of active streams, the number is decreased when returned guard is dropped
+    pub(super) fn start_stream(
+        &self,
+        subject: &'static str,
+        direction: StreamDirection,
+    ) -> ActiveStreamGuard {
+        let active_streams = self
+            .active_streams
+            .get_or_create(&vec![
+                ("subject", subject.to_string()),
+                ("direction", direction.to_string()),
+            ])
+            .clone();
+        active_streams.inc();
+
+        ActiveStreamGuard { active_streams }
+    }
+}
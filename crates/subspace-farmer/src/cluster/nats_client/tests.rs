@@ -0,0 +1,189 @@
+use crate::cluster::nats_client::{
+    GenericNotification, GenericRequest, GenericStreamRequest, NatsClient,
+};
+use crate::cluster::transport::memory::MemoryTransport;
+use backoff::ExponentialBackoff;
+use futures::{stream, FutureExt, StreamExt};
+use parity_scale_codec::{Decode, Encode};
+use prometheus_client::encoding::text::encode;
+use prometheus_client::registry::Registry;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Encode, Decode)]
+struct TestRequest(u32);
+
+impl GenericRequest for TestRequest {
+    const SUBJECT: &'static str = "subspace.test.*.request";
+    type Response = u64;
+}
+
+#[derive(Debug, Encode, Decode)]
+struct TestStreamRequest(u32);
+
+impl GenericStreamRequest for TestStreamRequest {
+    const SUBJECT: &'static str = "subspace.test.*.stream-request";
+    type Response = Vec<u8>;
+}
+
+#[derive(Debug, Encode, Decode)]
+struct TestNotification(u32);
+
+impl GenericNotification for TestNotification {
+    const SUBJECT: &'static str = "subspace.test.*.notification";
+}
+
+fn nats_client(transport: &MemoryTransport, registry: Option<&mut Registry>) -> NatsClient {
+    NatsClient::from_transport(
+        Arc::new(transport.clone()),
+        ExponentialBackoff {
+            max_elapsed_time: Some(Duration::from_secs(1)),
+            ..ExponentialBackoff::default()
+        },
+        registry,
+    )
+    .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn request_response() {
+    let transport = MemoryTransport::default();
+    let requester = nats_client(&transport, None);
+    let responder = nats_client(&transport, None);
+
+    let _responder_task = tokio::spawn(async move {
+        responder
+            .request_responder(Some("instance"), None, |request: TestRequest| async move {
+                Some(u64::from(request.0) * 2)
+            })
+            .await
+    });
+
+    // Retried until responder subscribes
+    let response = requester
+        .request(&TestRequest(21), Some("instance"))
+        .await
+        .unwrap();
+    assert_eq!(response, 42);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stream_request_response() {
+    let transport = MemoryTransport::default();
+    let requester = nats_client(&transport, None);
+    let responder = nats_client(&transport, None);
+
+    let mut stream_requests = responder
+        .subscribe_to_stream_requests::<TestStreamRequest>(None, None)
+        .await
+        .unwrap();
+    let _responder_task = tokio::spawn(async move {
+        while let Some(stream_request) = stream_requests.next().await {
+            let responder = responder.clone();
+            tokio::spawn(async move {
+                // Large responses to make sure they are split across multiple messages
+                let responses = (0..stream_request.request.0)
+                    .map(|index| vec![index as u8; 512 * 1024])
+                    .collect::<Vec<_>>();
+                responder
+                    .stream_response::<TestStreamRequest, _>(
+                        stream_request.response_subject,
+                        stream::iter(responses),
+                    )
+                    .await;
+            });
+        }
+    });
+
+    let responses = requester
+        .stream_request(TestStreamRequest(10), Some("instance"))
+        .await
+        .unwrap()
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(responses.len(), 10);
+    for (index, response) in responses.into_iter().enumerate() {
+        assert_eq!(response, vec![index as u8; 512 * 1024]);
+    }
+
+    // Empty stream
+    let responses = requester
+        .stream_request(TestStreamRequest(0), Some("instance"))
+        .await
+        .unwrap()
+        .collect::<Vec<_>>()
+        .await;
+    assert!(responses.is_empty());
+}
+
+#[tokio::test]
+async fn notifications() {
+    let transport = MemoryTransport::default();
+    let sender = nats_client(&transport, None);
+    let receiver = nats_client(&transport, None);
+
+    let mut notifications = receiver
+        .subscribe_to_notifications::<TestNotification>(Some("instance"), None)
+        .await
+        .unwrap();
+
+    sender
+        .notification(&TestNotification(1), Some("other-instance"))
+        .await
+        .unwrap();
+    sender
+        .notification(&TestNotification(2), Some("instance"))
+        .await
+        .unwrap();
+
+    assert_eq!(notifications.next().await.unwrap().0, 2);
+    assert!(notifications.next().now_or_never().is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn metrics() {
+    let transport = MemoryTransport::default();
+    let mut registry = Registry::default();
+    let requester = nats_client(&transport, Some(&mut registry));
+    let responder = nats_client(&transport, None);
+
+    let mut stream_requests = responder
+        .subscribe_to_stream_requests::<TestStreamRequest>(None, None)
+        .await
+        .unwrap();
+    let _responder_task = tokio::spawn(async move {
+        while let Some(stream_request) = stream_requests.next().await {
+            responder
+                .stream_response::<TestStreamRequest, _>(
+                    stream_request.response_subject,
+                    stream::iter([vec![1u8; 16]]),
+                )
+                .await;
+        }
+    });
+
+    let responses = requester
+        .stream_request(TestStreamRequest(1), None)
+        .await
+        .unwrap();
+    {
+        let mut registry_text = String::new();
+        encode(&mut registry_text, &registry).unwrap();
+        assert!(registry_text.contains(
+            r#"nats_client_active_streams{subject="subspace.test.*.stream-request",direction="Incoming"} 1"#
+        ));
+    }
+    assert_eq!(responses.collect::<Vec<_>>().await, vec![vec![1u8; 16]]);
+
+    let mut registry_text = String::new();
+    encode(&mut registry_text, &registry).unwrap();
+    assert!(registry_text.contains(
+        r#"nats_client_active_streams{subject="subspace.test.*.stream-request",direction="Incoming"} 0"#
+    ));
+    assert!(registry_text.contains(
+        r#"nats_client_sent_message_size_bytes_count{subject="subspace.test.*.stream-request"} 1"#
+    ));
+    assert!(registry_text.contains(
+        r#"nats_client_received_message_size_bytes_count{subject="subspace.test.*.stream-request"} 1"#
+    ));
+}
@@ -417,7 +417,7 @@ impl ClusterPlotter {
                             progress_updater
                                 .update_progress_and_events(
                                     &mut progress_sender,
-                                    SectorPlottingProgress::Error {
+                                    SectorPlottingProgress::PlotterGone {
                                         error: "Timed out without ping from plotter".to_string(),
                                     },
                                 )
@@ -958,7 +958,9 @@ async fn send_publish_progress(
 
             return;
         }
-        SectorPlottingProgress::Error { error } => ClusterSectorPlottingProgress::Error { error },
+        SectorPlottingProgress::Error { error } | SectorPlottingProgress::PlotterGone { error } => {
+            ClusterSectorPlottingProgress::Error { error }
+        }
     };
 
     if let Err(error) = response_sender.send(cluster_progress).await {
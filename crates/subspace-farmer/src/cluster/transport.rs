@@ -0,0 +1,185 @@
+//! Cluster transport
+//!
+//! [`ClusterTransport`] is a minimal set of messaging primitives (publish, request and subscribe
+//! with optional queue groups) with semantics of [Core NATS](https://docs.nats.io/nats-concepts/core-nats)
+//! that [`NatsClient`](crate::cluster::nats_client::NatsClient) is built on top of. This allows
+//! cluster components to run unchanged over different transports:
+//! * [`nats::NatsTransport`] uses NATS server for message routing
+//! * [`tcp::TcpTransport`] connects cluster components to each other directly over TCP, without any
+//!   additional infrastructure
+//! * [`memory::MemoryTransport`] routes messages within a single process, primarily for testing
+//!   purposes
+//!
+//! Subjects follow NATS conventions: tokens are separated by `.`, `*` in subscription subject
+//! matches exactly one token and `>` at the end of subscription subject matches one or more
+//! remaining tokens.
+
+pub mod memory;
+pub mod nats;
+mod router;
+pub mod tcp;
+#[cfg(test)]
+mod tests;
+
+use async_nats::HeaderValue;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::Stream;
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Requests should time out eventually, but we should set a larger timeout to allow for spikes in
+/// load to be absorbed gracefully
+pub const REQUEST_TIMEOUT: Duration = Duration::from_mins(5);
+
+/// Transport error
+#[derive(Debug, Error)]
+pub enum TransportError {
+    /// There were no subscribers for request subject
+    #[error("No responders")]
+    NoResponders,
+    /// Request timed out
+    #[error("Request timed out")]
+    TimedOut,
+    /// Payload is larger than transport supports
+    #[error("Payload too large: {size} bytes, max {max_payload} bytes")]
+    PayloadTooLarge {
+        /// Size of the payload
+        size: usize,
+        /// Max payload size supported by transport
+        max_payload: usize,
+    },
+    /// Failed to decode response
+    #[error("Failed to decode response: {0}")]
+    Decoding(#[from] parity_scale_codec::Error),
+    /// Other transport-specific error
+    #[error("Transport error: {0}")]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Message received from transport
+#[derive(Debug, Clone)]
+pub struct TransportMessage {
+    /// Subject message was published to
+    pub subject: String,
+    /// Subject where reply to this message is expected to be published, if any
+    pub reply: Option<String>,
+    /// Message payload
+    pub payload: Bytes,
+}
+
+/// Subscription to messages published to a subject, unsubscribes on drop
+pub struct TransportSubscription {
+    subject: String,
+    queue_group: Option<String>,
+    messages: Pin<Box<dyn Stream<Item = TransportMessage> + Send>>,
+}
+
+impl fmt::Debug for TransportSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransportSubscription")
+            .field("subject", &self.subject)
+            .field("queue_group", &self.queue_group)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Stream for TransportSubscription {
+    type Item = TransportMessage;
+
+    #[inline]
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.messages.as_mut().poll_next(cx)
+    }
+}
+
+impl TransportSubscription {
+    /// Create new subscription from a stream of messages
+    pub fn new<S>(subject: String, queue_group: Option<String>, messages: S) -> Self
+    where
+        S: Stream<Item = TransportMessage> + Send + 'static,
+    {
+        Self {
+            subject,
+            queue_group,
+            messages: Box::pin(messages),
+        }
+    }
+
+    /// Subject of this subscription
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Queue group of this subscription, if any
+    pub fn queue_group(&self) -> Option<&str> {
+        self.queue_group.as_deref()
+    }
+}
+
+/// Abstract cluster transport
+#[async_trait]
+pub trait ClusterTransport: fmt::Debug + Send + Sync + 'static {
+    /// Max payload size supported by transport
+    fn max_payload(&self) -> usize;
+
+    /// Publish message to a subject.
+    ///
+    /// `reply` is a subject where reply is expected to be published. `message_id` is a
+    /// deterministic message ID that transport may use for de-duplication purposes.
+    async fn publish(
+        &self,
+        subject: String,
+        reply: Option<String>,
+        payload: Bytes,
+        message_id: Option<HeaderValue>,
+    ) -> Result<(), TransportError>;
+
+    /// Make request to a subject and wait for the first reply.
+    ///
+    /// Returns [`TransportError::NoResponders`] if nothing is subscribed to the subject and
+    /// [`TransportError::TimedOut`] if no reply was received in time.
+    async fn request(
+        &self,
+        subject: String,
+        payload: Bytes,
+    ) -> Result<TransportMessage, TransportError>;
+
+    /// Subscribe to a subject.
+    ///
+    /// When `queue_group` is specified, each message will be delivered to only one of the
+    /// subscribers in the same queue group.
+    async fn subscribe(
+        &self,
+        subject: String,
+        queue_group: Option<String>,
+    ) -> Result<TransportSubscription, TransportError>;
+}
+
+/// Check whether subject matches subscription subject that may contain wildcards
+pub fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut pattern_tokens = pattern.split('.');
+    let mut subject_tokens = subject.split('.');
+
+    loop {
+        match (pattern_tokens.next(), subject_tokens.next()) {
+            (Some(">"), Some(_)) => {
+                return pattern_tokens.next().is_none();
+            }
+            (Some(pattern_token), Some(subject_token)) => {
+                if pattern_token != "*" && pattern_token != subject_token {
+                    return false;
+                }
+            }
+            (None, None) => {
+                return true;
+            }
+            _ => {
+                return false;
+            }
+        }
+    }
+}
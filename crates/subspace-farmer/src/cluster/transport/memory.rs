@@ -0,0 +1,85 @@
+//! In-process transport
+//!
+//! Routes messages between clones of [`MemoryTransport`] within a single process, which is
+//! primarily useful for testing cluster components without any external infrastructure.
+
+use crate::cluster::transport::router::{check_payload_size, Router, MAX_PAYLOAD};
+use crate::cluster::transport::{
+    ClusterTransport, TransportError, TransportMessage, TransportSubscription, REQUEST_TIMEOUT,
+};
+use async_nats::HeaderValue;
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Transport that routes messages within a single process.
+///
+/// All clones of the instance share the same set of subscriptions.
+#[derive(Debug, Clone)]
+pub struct MemoryTransport {
+    router: Arc<Router>,
+    request_timeout: Duration,
+}
+
+impl Default for MemoryTransport {
+    #[inline]
+    fn default() -> Self {
+        Self::new(REQUEST_TIMEOUT)
+    }
+}
+
+#[async_trait]
+impl ClusterTransport for MemoryTransport {
+    fn max_payload(&self) -> usize {
+        MAX_PAYLOAD
+    }
+
+    async fn publish(
+        &self,
+        subject: String,
+        reply: Option<String>,
+        payload: Bytes,
+        _message_id: Option<HeaderValue>,
+    ) -> Result<(), TransportError> {
+        check_payload_size(&payload)?;
+
+        self.router.publish(TransportMessage {
+            subject,
+            reply,
+            payload,
+        });
+
+        Ok(())
+    }
+
+    async fn request(
+        &self,
+        subject: String,
+        payload: Bytes,
+    ) -> Result<TransportMessage, TransportError> {
+        check_payload_size(&payload)?;
+
+        self.router
+            .request(subject, payload, self.request_timeout)
+            .await
+    }
+
+    async fn subscribe(
+        &self,
+        subject: String,
+        queue_group: Option<String>,
+    ) -> Result<TransportSubscription, TransportError> {
+        Ok(self.router.subscribe(subject, queue_group))
+    }
+}
+
+impl MemoryTransport {
+    /// Create new instance with specified request timeout
+    pub fn new(request_timeout: Duration) -> Self {
+        Self {
+            router: Arc::default(),
+            request_timeout,
+        }
+    }
+}
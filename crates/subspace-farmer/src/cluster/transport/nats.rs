@@ -0,0 +1,113 @@
+//! NATS transport
+//!
+//! Routes messages through NATS server, which is the default transport for the cluster.
+
+use crate::cluster::transport::{
+    ClusterTransport, TransportError, TransportMessage, TransportSubscription,
+};
+use async_nats::{Client, HeaderMap, HeaderValue, RequestErrorKind};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+
+/// Transport that uses NATS server for message routing
+#[derive(Debug, Clone)]
+pub struct NatsTransport {
+    client: Client,
+}
+
+#[async_trait]
+impl ClusterTransport for NatsTransport {
+    fn max_payload(&self) -> usize {
+        self.client.server_info().max_payload
+    }
+
+    async fn publish(
+        &self,
+        subject: String,
+        reply: Option<String>,
+        payload: Bytes,
+        message_id: Option<HeaderValue>,
+    ) -> Result<(), TransportError> {
+        let result = match (reply, message_id) {
+            (None, None) => self.client.publish(subject, payload).await,
+            (Some(reply), None) => {
+                self.client
+                    .publish_with_reply(subject, reply, payload)
+                    .await
+            }
+            (reply, Some(message_id)) => {
+                let mut headers = HeaderMap::new();
+                headers.insert("Nats-Msg-Id", message_id);
+
+                if let Some(reply) = reply {
+                    self.client
+                        .publish_with_reply_and_headers(subject, reply, headers, payload)
+                        .await
+                } else {
+                    self.client
+                        .publish_with_headers(subject, headers, payload)
+                        .await
+                }
+            }
+        };
+
+        result.map_err(|error| TransportError::Other(error.into()))
+    }
+
+    async fn request(
+        &self,
+        subject: String,
+        payload: Bytes,
+    ) -> Result<TransportMessage, TransportError> {
+        match self.client.request(subject, payload).await {
+            Ok(message) => Ok(TransportMessage {
+                subject: message.subject.to_string(),
+                reply: message.reply.map(|reply| reply.to_string()),
+                payload: message.payload,
+            }),
+            Err(error) => Err(match error.kind() {
+                RequestErrorKind::TimedOut => TransportError::TimedOut,
+                RequestErrorKind::NoResponders => TransportError::NoResponders,
+                RequestErrorKind::Other => TransportError::Other(error.into()),
+            }),
+        }
+    }
+
+    async fn subscribe(
+        &self,
+        subject: String,
+        queue_group: Option<String>,
+    ) -> Result<TransportSubscription, TransportError> {
+        let subscriber = if let Some(queue_group) = queue_group.clone() {
+            self.client
+                .queue_subscribe(subject.clone(), queue_group)
+                .await
+        } else {
+            self.client.subscribe(subject.clone()).await
+        }
+        .map_err(|error| TransportError::Other(error.into()))?;
+
+        Ok(TransportSubscription::new(
+            subject,
+            queue_group,
+            subscriber.map(|message| TransportMessage {
+                subject: message.subject.to_string(),
+                reply: message.reply.map(|reply| reply.to_string()),
+                payload: message.payload,
+            }),
+        ))
+    }
+}
+
+impl NatsTransport {
+    /// Create new instance from existing NATS client
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Inner NATS client
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
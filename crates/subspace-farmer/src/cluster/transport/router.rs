@@ -0,0 +1,457 @@
+//! Message router shared by direct transports.
+//!
+//! Router keeps track of local subscriptions and subscriptions of directly connected remote
+//! instances (if any), delivering published messages to matching subscribers with NATS-like
+//! semantics.
+
+use crate::cluster::transport::tcp::Frame;
+use crate::cluster::transport::{
+    subject_matches, TransportError, TransportMessage, TransportSubscription,
+};
+use bytes::Bytes;
+use futures::channel::{mpsc, oneshot};
+use futures::{Stream, StreamExt};
+use parking_lot::Mutex;
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use ulid::Ulid;
+
+/// Max payload size, the same as recommended NATS configuration
+pub(super) const MAX_PAYLOAD: usize = 2 * 1024 * 1024;
+
+/// Identifier of the connection to remote instance
+pub(super) type ConnectionId = u64;
+
+#[derive(Debug)]
+struct LocalSubscription {
+    subject: String,
+    queue_group: Option<String>,
+    sender: mpsc::UnboundedSender<TransportMessage>,
+}
+
+#[derive(Debug)]
+struct RemoteSubscription {
+    subject: String,
+    queue_group: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Target {
+    Local {
+        sid: u64,
+    },
+    /// Reply to a pending request
+    Inbox {
+        request_id: u64,
+    },
+    Remote {
+        connection_id: ConnectionId,
+        sid: u64,
+    },
+}
+
+#[derive(Debug, Default)]
+struct RouterState {
+    next_sid: u64,
+    subscriptions: HashMap<u64, LocalSubscription>,
+    /// Subscription ID of the reply inbox shared by all requests, created on the first request
+    inbox_sid: Option<u64>,
+    next_request_id: u64,
+    pending_requests: HashMap<u64, oneshot::Sender<TransportMessage>>,
+    remote_subscriptions: HashMap<(ConnectionId, u64), RemoteSubscription>,
+    connections: HashMap<ConnectionId, mpsc::Sender<Frame>>,
+}
+
+impl RouterState {
+    fn deliver_reply(&mut self, request_id: u64, message: TransportMessage) -> bool {
+        self.pending_requests
+            .remove(&request_id)
+            .is_some_and(|sender| sender.send(message).is_ok())
+    }
+
+    fn send_to_connections(&mut self, frame: Frame) {
+        let slow_connections = self
+            .connections
+            .iter_mut()
+            .filter_map(|(&connection_id, sender)| {
+                sender
+                    .try_send(frame.clone())
+                    .is_err()
+                    .then_some(connection_id)
+            })
+            .collect::<Vec<_>>();
+
+        for connection_id in slow_connections {
+            self.disconnect(connection_id);
+        }
+    }
+
+    /// Remove connection along with all of its subscriptions, closing the channel will cause
+    /// connection to be closed if it is still alive
+    fn disconnect(&mut self, connection_id: ConnectionId) {
+        if let Some(mut sender) = self.connections.remove(&connection_id) {
+            sender.close_channel();
+        }
+        self.remote_subscriptions
+            .retain(|&(subscription_connection_id, _sid), _subscription| {
+                subscription_connection_id != connection_id
+            });
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct Router {
+    state: Mutex<RouterState>,
+    /// Prefix of reply subjects, replies to requests are sent to `<inbox>.<request_id>`
+    inbox: String,
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Self {
+            state: Mutex::default(),
+            inbox: format!("_INBOX.{}", Ulid::new()),
+        }
+    }
+}
+
+impl Router {
+    /// Subscribe to a subject locally, subscription will be announced to all remote connections
+    pub(super) fn subscribe(
+        self: &Arc<Self>,
+        subject: String,
+        queue_group: Option<String>,
+    ) -> TransportSubscription {
+        let (sender, receiver) = mpsc::unbounded();
+
+        let sid = {
+            let mut state = self.state.lock();
+            let sid = state.next_sid;
+            state.next_sid += 1;
+
+            state.send_to_connections(Frame::Subscribe {
+                sid,
+                subject: subject.clone(),
+                queue_group: queue_group.clone(),
+            });
+            state.subscriptions.insert(
+                sid,
+                LocalSubscription {
+                    subject: subject.clone(),
+                    queue_group: queue_group.clone(),
+                    sender,
+                },
+            );
+
+            sid
+        };
+
+        let guard = UnsubscribeOnDrop {
+            router: Arc::downgrade(self),
+            sid,
+        };
+
+        TransportSubscription::new(
+            subject,
+            queue_group,
+            SubscriptionMessages {
+                receiver,
+                _guard: guard,
+            },
+        )
+    }
+
+    fn unsubscribe(&self, sid: u64) {
+        let mut state = self.state.lock();
+        if state.subscriptions.remove(&sid).is_some() {
+            state.send_to_connections(Frame::Unsubscribe { sid });
+        }
+    }
+
+    /// Publish message to all matching subscribers, returns number of subscribers message was
+    /// delivered to.
+    ///
+    /// Messages without queue group are delivered to every matching subscriber, for each queue
+    /// group message is delivered to one randomly selected subscriber.
+    ///
+    /// Remote connections that can't keep up with sent messages are disconnected.
+    pub(super) fn publish(&self, message: TransportMessage) -> usize {
+        let mut state = self.state.lock();
+        let state = &mut *state;
+
+        let mut targets = Vec::new();
+        let mut queue_groups = HashMap::<&str, Vec<Target>>::new();
+        {
+            let local = state.subscriptions.iter().map(|(&sid, subscription)| {
+                (
+                    Target::Local { sid },
+                    &subscription.subject,
+                    &subscription.queue_group,
+                )
+            });
+            let remote =
+                state
+                    .remote_subscriptions
+                    .iter()
+                    .map(|(&(connection_id, sid), subscription)| {
+                        (
+                            Target::Remote { connection_id, sid },
+                            &subscription.subject,
+                            &subscription.queue_group,
+                        )
+                    });
+            for (target, subject, queue_group) in local.chain(remote) {
+                if !subject_matches(subject, &message.subject) {
+                    continue;
+                }
+
+                if let Some(queue_group) = queue_group {
+                    queue_groups.entry(queue_group).or_default().push(target);
+                } else {
+                    targets.push(target);
+                }
+            }
+        }
+        if state.inbox_sid.is_some()
+            && let Some(request_id) = self.inbox_request_id(&message.subject)
+        {
+            targets.push(Target::Inbox { request_id });
+        }
+        let mut rng = thread_rng();
+        targets.extend(
+            queue_groups
+                .into_values()
+                .filter_map(|members| members.choose(&mut rng).copied()),
+        );
+
+        let mut slow_connections = Vec::new();
+        let delivered = targets
+            .into_iter()
+            .filter(|target| match *target {
+                Target::Local { sid } => {
+                    state.subscriptions.get(&sid).is_some_and(|subscription| {
+                        subscription.sender.unbounded_send(message.clone()).is_ok()
+                    })
+                }
+                Target::Inbox { request_id } => state.deliver_reply(request_id, message.clone()),
+                Target::Remote { connection_id, sid } => state
+                    .connections
+                    .get_mut(&connection_id)
+                    .is_some_and(|sender| {
+                        let sent = sender
+                            .try_send(Frame::Message {
+                                sid,
+                                subject: message.subject.clone(),
+                                reply: message.reply.clone(),
+                                payload: message.payload.to_vec(),
+                            })
+                            .is_ok();
+                        if !sent {
+                            slow_connections.push(connection_id);
+                        }
+
+                        sent
+                    }),
+            })
+            .count();
+
+        for connection_id in slow_connections {
+            state.disconnect(connection_id);
+        }
+
+        delivered
+    }
+
+    /// Make request and wait for the first reply.
+    ///
+    /// All requests share a single wildcard reply inbox, which is announced to remote instances
+    /// once, on the first request.
+    pub(super) async fn request(
+        &self,
+        subject: String,
+        payload: Bytes,
+        timeout: Duration,
+    ) -> Result<TransportMessage, TransportError> {
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        let request_id = {
+            let mut state = self.state.lock();
+            if state.inbox_sid.is_none() {
+                let sid = state.next_sid;
+                state.next_sid += 1;
+
+                state.send_to_connections(Frame::Subscribe {
+                    sid,
+                    subject: format!("{}.*", self.inbox),
+                    queue_group: None,
+                });
+                state.inbox_sid.replace(sid);
+            }
+
+            let request_id = state.next_request_id;
+            state.next_request_id += 1;
+            state.pending_requests.insert(request_id, reply_sender);
+
+            request_id
+        };
+        let _guard = RemovePendingRequestOnDrop {
+            router: self,
+            request_id,
+        };
+
+        let delivered = self.publish(TransportMessage {
+            subject,
+            reply: Some(format!("{}.{request_id}", self.inbox)),
+            payload,
+        });
+        if delivered == 0 {
+            return Err(TransportError::NoResponders);
+        }
+
+        match tokio::time::timeout(timeout, reply_receiver).await {
+            Ok(Ok(message)) => Ok(message),
+            Ok(Err(_)) | Err(_) => Err(TransportError::TimedOut),
+        }
+    }
+
+    /// Deliver message received from remote instance to local subscription
+    pub(super) fn deliver_local(&self, sid: u64, message: TransportMessage) {
+        let mut state = self.state.lock();
+
+        if state.inbox_sid == Some(sid) {
+            if let Some(request_id) = self.inbox_request_id(&message.subject) {
+                state.deliver_reply(request_id, message);
+            }
+        } else if let Some(subscription) = state.subscriptions.get(&sid) {
+            let _ = subscription.sender.unbounded_send(message);
+        }
+    }
+
+    /// Request ID if subject is a reply subject of this router's inbox
+    fn inbox_request_id(&self, subject: &str) -> Option<u64> {
+        subject
+            .strip_prefix(self.inbox.as_str())?
+            .strip_prefix('.')?
+            .parse()
+            .ok()
+    }
+
+    /// Add connection to remote instance.
+    ///
+    /// Returns frames announcing existing local subscriptions that must be sent to remote instance
+    /// before any frames received through `sender`.
+    pub(super) fn add_connection(
+        &self,
+        connection_id: ConnectionId,
+        sender: mpsc::Sender<Frame>,
+    ) -> Vec<Frame> {
+        let mut state = self.state.lock();
+
+        let subscriptions = state
+            .subscriptions
+            .iter()
+            .map(|(&sid, subscription)| Frame::Subscribe {
+                sid,
+                subject: subscription.subject.clone(),
+                queue_group: subscription.queue_group.clone(),
+            })
+            .chain(state.inbox_sid.map(|sid| Frame::Subscribe {
+                sid,
+                subject: format!("{}.*", self.inbox),
+                queue_group: None,
+            }))
+            .collect();
+
+        state.connections.insert(connection_id, sender);
+
+        subscriptions
+    }
+
+    /// Remove connection to remote instance along with all of its subscriptions
+    pub(super) fn remove_connection(&self, connection_id: ConnectionId) {
+        self.state.lock().disconnect(connection_id);
+    }
+
+    pub(super) fn add_remote_subscription(
+        &self,
+        connection_id: ConnectionId,
+        sid: u64,
+        subject: String,
+        queue_group: Option<String>,
+    ) {
+        let mut state = self.state.lock();
+
+        if state.connections.contains_key(&connection_id) {
+            state.remote_subscriptions.insert(
+                (connection_id, sid),
+                RemoteSubscription {
+                    subject,
+                    queue_group,
+                },
+            );
+        }
+    }
+
+    pub(super) fn remove_remote_subscription(&self, connection_id: ConnectionId, sid: u64) {
+        self.state
+            .lock()
+            .remote_subscriptions
+            .remove(&(connection_id, sid));
+    }
+}
+
+struct SubscriptionMessages {
+    receiver: mpsc::UnboundedReceiver<TransportMessage>,
+    _guard: UnsubscribeOnDrop,
+}
+
+impl Stream for SubscriptionMessages {
+    type Item = TransportMessage;
+
+    #[inline]
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+struct RemovePendingRequestOnDrop<'a> {
+    router: &'a Router,
+    request_id: u64,
+}
+
+impl Drop for RemovePendingRequestOnDrop<'_> {
+    fn drop(&mut self) {
+        self.router
+            .state
+            .lock()
+            .pending_requests
+            .remove(&self.request_id);
+    }
+}
+
+struct UnsubscribeOnDrop {
+    router: Weak<Router>,
+    sid: u64,
+}
+
+impl Drop for UnsubscribeOnDrop {
+    fn drop(&mut self) {
+        if let Some(router) = self.router.upgrade() {
+            router.unsubscribe(self.sid);
+        }
+    }
+}
+
+pub(super) fn check_payload_size(payload: &Bytes) -> Result<(), TransportError> {
+    if payload.len() > MAX_PAYLOAD {
+        return Err(TransportError::PayloadTooLarge {
+            size: payload.len(),
+            max_payload: MAX_PAYLOAD,
+        });
+    }
+
+    Ok(())
+}
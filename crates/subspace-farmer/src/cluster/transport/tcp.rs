@@ -0,0 +1,708 @@
+//! Direct TCP transport
+//!
+//! Cluster components connect to each other directly over TCP, forming a full mesh without any
+//! additional infrastructure like NATS server. Every instance listens on some addresses and/or
+//! connects to addresses of other instances, it is enough for a connection between two instances
+//! to be established in one direction. Messages are only routed between directly connected
+//! instances, so every instance must be connected to every other instance it needs to talk to.
+//!
+//! Upon connection instances exchange their node IDs along with random nonces and prove to each
+//! other the knowledge of pre-shared key, after which they announce their subscriptions to each
+//! other and published messages are sent directly to instances with matching subscriptions. Each
+//! frame is SCALE-encoded and prefixed with its length as little-endian `u32`.
+//!
+//! After handshake each side derives a session key per direction from pre-shared key, node IDs and
+//! nonces of both sides. Every frame after handshake is followed by BLAKE3 MAC of the frame and its
+//! sequence number, so frames can't be injected, modified, replayed or reordered without knowledge
+//! of pre-shared key, connection is closed on the first frame that fails verification.
+//!
+//! NOTE: Traffic is authenticated, but not encrypted, so listening addresses must not be exposed
+//! outside of trusted network. Only TCP is supported, there is no QUIC implementation.
+
+use crate::cluster::transport::router::{check_payload_size, ConnectionId, Router, MAX_PAYLOAD};
+use crate::cluster::transport::{
+    ClusterTransport, TransportError, TransportMessage, TransportSubscription,
+};
+use crate::utils::AsyncJoinOnDrop;
+use async_nats::HeaderValue;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::stream::FuturesUnordered;
+use futures::{select, FutureExt, StreamExt};
+use parity_scale_codec::{Decode, Encode};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::future::{pending, Future};
+use std::net::SocketAddr;
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, io};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn, Instrument};
+use ulid::Ulid;
+use zeroize::Zeroizing;
+
+/// Max frame size, leaves some space for subjects and other metadata on top of max payload
+const MAX_FRAME_SIZE: usize = MAX_PAYLOAD + 64 * 1024;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Context for BLAKE3 derivation of authentication key from pre-shared key
+const AUTH_KEY_DERIVATION_CONTEXT: &str = "subspace-farmer 2024 cluster transport authentication";
+/// Context for BLAKE3 derivation of per-direction session keys used for frame authentication
+const SESSION_KEY_DERIVATION_CONTEXT: &str = "subspace-farmer 2024 cluster transport session";
+/// Max number of frames queued for sending to a peer, peers that can't keep up are disconnected
+const FRAME_QUEUE_SIZE: usize = 256;
+/// Peer is disconnected if a single frame can't be written for this long
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Ping is sent when there were no other frames sent for this long
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+/// Connection is considered dead when nothing was received for this long
+const IDLE_TIMEOUT: Duration = Duration::from_mins(1);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Frame sent between directly connected instances
+#[derive(Debug, Clone, Encode, Decode)]
+pub(super) enum Frame {
+    /// The first frame sent by both sides of the connection
+    Hello { node_id: u128, nonce: [u8; 32] },
+    /// Proof of pre-shared key knowledge, sent by both sides in response to [`Frame::Hello`]
+    Auth { proof: [u8; 32] },
+    /// Subscription to a subject
+    Subscribe {
+        sid: u64,
+        subject: String,
+        queue_group: Option<String>,
+    },
+    /// Removal of previously created subscription
+    Unsubscribe { sid: u64 },
+    /// Message for specified subscription
+    Message {
+        sid: u64,
+        subject: String,
+        reply: Option<String>,
+        payload: Vec<u8>,
+    },
+    /// Keep-alive
+    Ping,
+}
+
+/// Authenticates frames sent in one direction of the connection
+pub(super) struct FrameAuthenticator {
+    key: Zeroizing<[u8; 32]>,
+    /// Sequence number of the next frame
+    sequence: u64,
+}
+
+impl FrameAuthenticator {
+    pub(super) fn new(key: Zeroizing<[u8; 32]>) -> Self {
+        Self { key, sequence: 0 }
+    }
+
+    /// MAC of the next frame
+    fn mac(&mut self, frame_bytes: &[u8]) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        hasher.update(&self.sequence.to_le_bytes());
+        hasher.update(frame_bytes);
+        self.sequence += 1;
+        hasher.finalize()
+    }
+}
+
+/// Outcome of successful handshake
+enum Handshake {
+    /// Connection to itself
+    Loopback,
+    Peer {
+        peer_node_id: Ulid,
+        /// Authenticates frames sent to the peer
+        sending: FrameAuthenticator,
+        /// Authenticates frames received from the peer
+        receiving: FrameAuthenticator,
+    },
+}
+
+#[derive(Debug)]
+struct Peer {
+    connection_id: ConnectionId,
+    /// Node ID of the instance that initiated connection
+    initiator: Ulid,
+}
+
+struct Shared {
+    node_id: Ulid,
+    auth_key: Zeroizing<[u8; 32]>,
+    router: Arc<Router>,
+    next_connection_id: AtomicU64,
+    peers: Mutex<HashMap<Ulid, Peer>>,
+}
+
+/// Transport that connects cluster components to each other directly over TCP
+#[derive(Clone)]
+pub struct TcpTransport {
+    shared: Arc<Shared>,
+    listen_addresses: Arc<Vec<SocketAddr>>,
+    request_timeout: Duration,
+    _background_tasks: Arc<Vec<AsyncJoinOnDrop<()>>>,
+}
+
+impl fmt::Debug for TcpTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TcpTransport")
+            .field("node_id", &self.shared.node_id)
+            .field("listen_addresses", &self.listen_addresses)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl ClusterTransport for TcpTransport {
+    fn max_payload(&self) -> usize {
+        MAX_PAYLOAD
+    }
+
+    async fn publish(
+        &self,
+        subject: String,
+        reply: Option<String>,
+        payload: Bytes,
+        _message_id: Option<HeaderValue>,
+    ) -> Result<(), TransportError> {
+        check_payload_size(&payload)?;
+
+        self.shared.router.publish(TransportMessage {
+            subject,
+            reply,
+            payload,
+        });
+
+        Ok(())
+    }
+
+    async fn request(
+        &self,
+        subject: String,
+        payload: Bytes,
+    ) -> Result<TransportMessage, TransportError> {
+        check_payload_size(&payload)?;
+
+        self.shared
+            .router
+            .request(subject, payload, self.request_timeout)
+            .await
+    }
+
+    async fn subscribe(
+        &self,
+        subject: String,
+        queue_group: Option<String>,
+    ) -> Result<TransportSubscription, TransportError> {
+        Ok(self.shared.router.subscribe(subject, queue_group))
+    }
+}
+
+impl TcpTransport {
+    /// Create new instance that listens on `listen_on` addresses and maintains connections to
+    /// `peers` (in `host:port` format), reconnecting when connection is lost.
+    ///
+    /// Only connections with instances that use the same `pre_shared_key` are established.
+    pub async fn new(
+        listen_on: &[SocketAddr],
+        peers: Vec<String>,
+        pre_shared_key: &[u8],
+        request_timeout: Duration,
+    ) -> io::Result<Self> {
+        if pre_shared_key.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Pre-shared key must not be empty",
+            ));
+        }
+
+        let shared = Arc::new(Shared {
+            node_id: Ulid::new(),
+            auth_key: Zeroizing::new(blake3::derive_key(
+                AUTH_KEY_DERIVATION_CONTEXT,
+                pre_shared_key,
+            )),
+            router: Arc::default(),
+            next_connection_id: AtomicU64::new(0),
+            peers: Mutex::default(),
+        });
+
+        let mut listen_addresses = Vec::with_capacity(listen_on.len());
+        let mut background_tasks = Vec::with_capacity(listen_on.len() + peers.len());
+
+        for &address in listen_on {
+            let listener = TcpListener::bind(address).await?;
+            let local_address = listener.local_addr()?;
+            info!(node_id = %shared.node_id, %local_address, "Listening for cluster connections");
+
+            listen_addresses.push(local_address);
+            background_tasks.push(AsyncJoinOnDrop::new(
+                tokio::spawn(accept_connections(Arc::clone(&shared), listener).in_current_span()),
+                true,
+            ));
+        }
+
+        for address in peers {
+            background_tasks.push(AsyncJoinOnDrop::new(
+                tokio::spawn(maintain_connection(Arc::clone(&shared), address).in_current_span()),
+                true,
+            ));
+        }
+
+        Ok(Self {
+            shared,
+            listen_addresses: Arc::new(listen_addresses),
+            request_timeout,
+            _background_tasks: Arc::new(background_tasks),
+        })
+    }
+
+    /// Addresses this instance is listening on
+    pub fn listen_addresses(&self) -> &[SocketAddr] {
+        &self.listen_addresses
+    }
+
+    /// Number of instances this instance is currently connected to
+    pub fn connected_peers(&self) -> usize {
+        self.shared.peers.lock().len()
+    }
+}
+
+async fn accept_connections(shared: Arc<Shared>, listener: TcpListener) {
+    // Initialize with pending future so it never ends
+    let mut connections = FuturesUnordered::from_iter([
+        Box::pin(pending()) as Pin<Box<dyn Future<Output = ()> + Send>>
+    ]);
+
+    loop {
+        select! {
+            result = listener.accept().fuse() => {
+                match result {
+                    Ok((stream, _address)) => {
+                        let shared = Arc::clone(&shared);
+                        connections.push(Box::pin(async move {
+                            handle_connection(&shared, stream, false).await;
+                        }));
+                    }
+                    Err(error) => {
+                        warn!(%error, "Failed to accept cluster connection");
+                    }
+                }
+            }
+            _ = connections.next() => {
+                // Nothing to do here
+            }
+        }
+    }
+}
+
+async fn maintain_connection(shared: Arc<Shared>, address: String) {
+    let mut maybe_peer_node_id = None;
+
+    loop {
+        let connected = maybe_peer_node_id
+            .is_some_and(|peer_node_id| shared.peers.lock().contains_key(&peer_node_id));
+
+        // Peer might have connected to us in the meantime, in which case there is no need to
+        // connect again
+        if !connected {
+            match TcpStream::connect(&address).await {
+                Ok(stream) => {
+                    if let Some(peer_node_id) = handle_connection(&shared, stream, true).await {
+                        if peer_node_id == shared.node_id {
+                            warn!(%address, "Peer address points to this instance, ignoring");
+                            return;
+                        }
+                        maybe_peer_node_id.replace(peer_node_id);
+                    }
+                }
+                Err(error) => {
+                    debug!(%error, %address, "Failed to connect to cluster peer");
+                }
+            }
+        }
+
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+    }
+}
+
+/// Handle connection until it is closed, returns node ID of the peer if handshake succeeded
+async fn handle_connection(shared: &Shared, stream: TcpStream, initiator: bool) -> Option<Ulid> {
+    let peer_address = stream.peer_addr().ok();
+    if let Err(error) = stream.set_nodelay(true) {
+        debug!(%error, ?peer_address, "Failed to set TCP_NODELAY");
+    }
+    let (mut reader, mut writer) = stream.into_split();
+
+    let handshake = handshake(&mut reader, &mut writer, shared.node_id, &shared.auth_key);
+    let (peer_node_id, sending, mut receiving) =
+        match tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake).await {
+            Ok(Ok(Handshake::Peer {
+                peer_node_id,
+                sending,
+                receiving,
+            })) => (peer_node_id, sending, receiving),
+            Ok(Ok(Handshake::Loopback)) => {
+                return Some(shared.node_id);
+            }
+            Ok(Err(error)) => {
+                debug!(%error, ?peer_address, "Cluster connection handshake failed");
+                return None;
+            }
+            Err(_error) => {
+                debug!(?peer_address, "Cluster connection handshake timed out");
+                return None;
+            }
+        };
+
+    let connection_id = shared.next_connection_id.fetch_add(1, Ordering::Relaxed);
+    let (frame_sender, mut frame_receiver) = mpsc::channel(FRAME_QUEUE_SIZE);
+    let initial_frames = {
+        // Both sides might connect to each other at the same time, both sides will see both
+        // connections and deterministically keep the one initiated by instance with smaller node
+        // ID
+        let initiator = if initiator {
+            shared.node_id
+        } else {
+            peer_node_id
+        };
+        let mut peers = shared.peers.lock();
+        if let Some(existing_peer) = peers.get(&peer_node_id) {
+            if existing_peer.initiator < initiator {
+                debug!(
+                    %peer_node_id,
+                    ?peer_address,
+                    "Already connected to cluster peer, closing duplicate connection"
+                );
+                return Some(peer_node_id);
+            }
+
+            shared.router.remove_connection(existing_peer.connection_id);
+        }
+        peers.insert(
+            peer_node_id,
+            Peer {
+                connection_id,
+                initiator,
+            },
+        );
+        shared.router.add_connection(connection_id, frame_sender)
+    };
+
+    info!(%peer_node_id, ?peer_address, "Connected to cluster peer");
+
+    let writer_fut =
+        pin!(write_frames(&mut writer, sending, initial_frames, &mut frame_receiver).fuse());
+    let reader_fut =
+        pin!(read_frames(&mut reader, &mut receiving, &shared.router, connection_id).fuse());
+    let result: io::Result<()> = select! {
+        result = writer_fut => result,
+        result = reader_fut => result,
+    };
+
+    shared.router.remove_connection(connection_id);
+    {
+        let mut peers = shared.peers.lock();
+        if peers
+            .get(&peer_node_id)
+            .is_some_and(|peer| peer.connection_id == connection_id)
+        {
+            peers.remove(&peer_node_id);
+        }
+    }
+
+    match result {
+        Ok(()) => {
+            info!(%peer_node_id, ?peer_address, "Disconnected from cluster peer");
+        }
+        Err(error) => {
+            info!(%error, %peer_node_id, ?peer_address, "Disconnected from cluster peer");
+        }
+    }
+
+    Some(peer_node_id)
+}
+
+/// Exchange node IDs and prove knowledge of pre-shared key to each other, returns node ID of the
+/// peer along with session keys for frame authentication.
+///
+/// Proof and session keys are bound to node IDs of both sides and nonces of both sides, such that
+/// they can't be reused for a different connection.
+async fn handshake<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    node_id: Ulid,
+    auth_key: &[u8; 32],
+) -> io::Result<Handshake>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let nonce = rand::random::<[u8; 32]>();
+    write_frame(
+        writer,
+        &Frame::Hello {
+            node_id: node_id.0,
+            nonce,
+        },
+        None,
+    )
+    .await?;
+
+    let (peer_node_id, peer_nonce) = match read_frame(reader, None).await? {
+        Frame::Hello { node_id, nonce } => (Ulid(node_id), nonce),
+        frame => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Expected hello, received {frame:?}"),
+            ));
+        }
+    };
+
+    // Connection to itself is not used, no need to authenticate it (and proof for the same node ID
+    // on both sides must not be produced, otherwise it can be reflected back)
+    if peer_node_id == node_id {
+        return Ok(Handshake::Loopback);
+    }
+
+    let proof = auth_proof(auth_key, node_id, peer_node_id, &peer_nonce, &nonce);
+    write_frame(
+        writer,
+        &Frame::Auth {
+            proof: *proof.as_bytes(),
+        },
+        None,
+    )
+    .await?;
+
+    let expected_proof = auth_proof(auth_key, peer_node_id, node_id, &nonce, &peer_nonce);
+    match read_frame(reader, None).await? {
+        // Comparison of `blake3::Hash` is constant-time
+        Frame::Auth { proof } if blake3::Hash::from(proof) == expected_proof => {
+            Ok(Handshake::Peer {
+                peer_node_id,
+                sending: FrameAuthenticator::new(session_key(
+                    auth_key,
+                    node_id,
+                    peer_node_id,
+                    &peer_nonce,
+                    &nonce,
+                )),
+                receiving: FrameAuthenticator::new(session_key(
+                    auth_key,
+                    peer_node_id,
+                    node_id,
+                    &nonce,
+                    &peer_nonce,
+                )),
+            })
+        }
+        Frame::Auth { .. } => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "Invalid authentication proof, pre-shared key mismatch",
+        )),
+        frame => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Expected auth, received {frame:?}"),
+        )),
+    }
+}
+
+/// Proof that `sender` knows the key, in response to `receiver_nonce`
+fn auth_proof(
+    auth_key: &[u8; 32],
+    sender: Ulid,
+    receiver: Ulid,
+    receiver_nonce: &[u8; 32],
+    sender_nonce: &[u8; 32],
+) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new_keyed(auth_key);
+    hasher.update(&sender.0.to_le_bytes());
+    hasher.update(&receiver.0.to_le_bytes());
+    hasher.update(receiver_nonce);
+    hasher.update(sender_nonce);
+    hasher.finalize()
+}
+
+/// Key for authentication of frames sent from `sender` to `receiver`
+fn session_key(
+    auth_key: &[u8; 32],
+    sender: Ulid,
+    receiver: Ulid,
+    receiver_nonce: &[u8; 32],
+    sender_nonce: &[u8; 32],
+) -> Zeroizing<[u8; 32]> {
+    let mut hasher = blake3::Hasher::new_derive_key(SESSION_KEY_DERIVATION_CONTEXT);
+    hasher.update(auth_key);
+    hasher.update(&sender.0.to_le_bytes());
+    hasher.update(&receiver.0.to_le_bytes());
+    hasher.update(receiver_nonce);
+    hasher.update(sender_nonce);
+    Zeroizing::new(*hasher.finalize().as_bytes())
+}
+
+/// Write initial frames and then frames from the channel until it is closed, sending pings when
+/// there is nothing else to send
+async fn write_frames<W>(
+    writer: &mut W,
+    mut authenticator: FrameAuthenticator,
+    initial_frames: Vec<Frame>,
+    frame_receiver: &mut mpsc::Receiver<Frame>,
+) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    for frame in initial_frames {
+        write_frame_with_timeout(writer, &frame, &mut authenticator).await?;
+    }
+
+    loop {
+        let frame = match tokio::time::timeout(PING_INTERVAL, frame_receiver.next()).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => {
+                return Ok(());
+            }
+            Err(_error) => Frame::Ping,
+        };
+        write_frame_with_timeout(writer, &frame, &mut authenticator).await?;
+    }
+}
+
+async fn write_frame_with_timeout<W>(
+    writer: &mut W,
+    frame: &Frame,
+    authenticator: &mut FrameAuthenticator,
+) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    tokio::time::timeout(
+        WRITE_TIMEOUT,
+        write_frame(writer, frame, Some(authenticator)),
+    )
+    .await
+    .map_err(|_error| io::Error::new(io::ErrorKind::TimedOut, "Peer is too slow"))?
+}
+
+/// Read frames from remote instance and process them with router
+async fn read_frames<R>(
+    reader: &mut R,
+    authenticator: &mut FrameAuthenticator,
+    router: &Router,
+    connection_id: ConnectionId,
+) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        let frame =
+            tokio::time::timeout(IDLE_TIMEOUT, read_frame(reader, Some(&mut *authenticator)))
+                .await
+                .map_err(|_error| {
+                    io::Error::new(io::ErrorKind::TimedOut, "Nothing received from peer")
+                })??;
+
+        match frame {
+            Frame::Hello { .. } | Frame::Auth { .. } => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Unexpected handshake frame after handshake",
+                ));
+            }
+            Frame::Subscribe {
+                sid,
+                subject,
+                queue_group,
+            } => {
+                router.add_remote_subscription(connection_id, sid, subject, queue_group);
+            }
+            Frame::Unsubscribe { sid } => {
+                router.remove_remote_subscription(connection_id, sid);
+            }
+            Frame::Message {
+                sid,
+                subject,
+                reply,
+                payload,
+            } => {
+                router.deliver_local(
+                    sid,
+                    TransportMessage {
+                        subject,
+                        reply,
+                        payload: payload.into(),
+                    },
+                );
+            }
+            Frame::Ping => {
+                // Nothing to do here
+            }
+        }
+    }
+}
+
+/// Write frame, followed by its MAC if authenticator is provided (always after handshake)
+pub(super) async fn write_frame<S>(
+    stream: &mut S,
+    frame: &Frame,
+    maybe_authenticator: Option<&mut FrameAuthenticator>,
+) -> io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let size = frame.encoded_size();
+    let mut bytes = Vec::with_capacity(4 + size + blake3::OUT_LEN);
+    bytes.extend_from_slice(&(size as u32).to_le_bytes());
+    frame.encode_to(&mut bytes);
+    if let Some(authenticator) = maybe_authenticator {
+        let mac = authenticator.mac(&bytes[4..]);
+        bytes.extend_from_slice(mac.as_bytes());
+    }
+
+    stream.write_all(&bytes).await?;
+    stream.flush().await
+}
+
+/// Read frame, verifying its MAC if authenticator is provided (always after handshake)
+pub(super) async fn read_frame<S>(
+    stream: &mut S,
+    maybe_authenticator: Option<&mut FrameAuthenticator>,
+) -> io::Result<Frame>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut length = [0; 4];
+    stream.read_exact(&mut length).await?;
+
+    let size = u32::from_le_bytes(length) as usize;
+    if size > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Frame size {size} exceeds max frame size {MAX_FRAME_SIZE}"),
+        ));
+    }
+
+    let mut bytes = vec![0; size];
+    stream.read_exact(&mut bytes).await?;
+
+    if let Some(authenticator) = maybe_authenticator {
+        let mut mac = [0; blake3::OUT_LEN];
+        stream.read_exact(&mut mac).await?;
+
+        // Comparison of `blake3::Hash` is constant-time
+        if authenticator.mac(&bytes) != blake3::Hash::from(mac) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Frame authentication failed",
+            ));
+        }
+    }
+
+    Frame::decode(&mut bytes.as_slice())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
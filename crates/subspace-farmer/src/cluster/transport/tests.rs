@@ -0,0 +1,387 @@
+use crate::cluster::transport::memory::MemoryTransport;
+use crate::cluster::transport::router::Router;
+use crate::cluster::transport::tcp::{
+    read_frame, write_frame, Frame, FrameAuthenticator, TcpTransport,
+};
+use crate::cluster::transport::{
+    subject_matches, ClusterTransport, TransportError, TransportMessage,
+};
+use futures::channel::mpsc;
+use futures::{FutureExt, StreamExt};
+use std::assert_matches::assert_matches;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+const PRE_SHARED_KEY: &[u8] = b"pre-shared key";
+
+#[test]
+fn subject_matching() {
+    assert!(subject_matches("a.b.c", "a.b.c"));
+    assert!(!subject_matches("a.b.c", "a.b"));
+    assert!(!subject_matches("a.b", "a.b.c"));
+    assert!(subject_matches("a.*.c", "a.b.c"));
+    assert!(!subject_matches("a.*.c", "a.b.d"));
+    assert!(!subject_matches("a.*", "a.b.c"));
+    assert!(subject_matches("a.>", "a.b.c"));
+    assert!(subject_matches("a.>", "a.b"));
+    assert!(!subject_matches("a.>", "a"));
+}
+
+#[tokio::test]
+async fn memory_subscriptions() {
+    let transport = MemoryTransport::default();
+
+    let mut exact = transport.subscribe("a.b".to_string(), None).await.unwrap();
+    let mut wildcard = transport.subscribe("a.*".to_string(), None).await.unwrap();
+    let mut queue_1 = transport
+        .subscribe("a.*".to_string(), Some("group".to_string()))
+        .await
+        .unwrap();
+    let mut queue_2 = transport
+        .subscribe("a.*".to_string(), Some("group".to_string()))
+        .await
+        .unwrap();
+
+    transport
+        .publish("a.b".to_string(), None, vec![1].into(), None)
+        .await
+        .unwrap();
+
+    assert_eq!(exact.next().await.unwrap().payload.as_ref(), [1]);
+    assert_eq!(wildcard.next().await.unwrap().payload.as_ref(), [1]);
+    // Only one member of the queue group receives the message
+    let queue_1_message = queue_1.next().now_or_never().flatten();
+    let queue_2_message = queue_2.next().now_or_never().flatten();
+    assert!(queue_1_message.is_some() ^ queue_2_message.is_some());
+
+    // Dropped subscription doesn't receive messages
+    drop(exact);
+    transport
+        .publish("a.c".to_string(), None, vec![2].into(), None)
+        .await
+        .unwrap();
+    let message = wildcard.next().await.unwrap();
+    assert_eq!(message.subject, "a.c");
+    assert_eq!(message.payload.as_ref(), [2]);
+}
+
+#[tokio::test]
+async fn memory_request() {
+    let transport = MemoryTransport::new(Duration::from_millis(100));
+
+    assert_matches!(
+        transport.request("echo".to_string(), vec![1].into()).await,
+        Err(TransportError::NoResponders)
+    );
+
+    let mut subscription = transport.subscribe("echo".to_string(), None).await.unwrap();
+    // Nobody replies
+    assert_matches!(
+        transport.request("echo".to_string(), vec![1].into()).await,
+        Err(TransportError::TimedOut)
+    );
+    // Drain unanswered request
+    subscription.next().await.unwrap();
+
+    let responder = {
+        let transport = transport.clone();
+
+        tokio::spawn(async move {
+            while let Some(message) = subscription.next().await {
+                transport
+                    .publish(message.reply.unwrap(), None, message.payload, None)
+                    .await
+                    .unwrap();
+            }
+        })
+    };
+
+    let response = transport
+        .request("echo".to_string(), vec![2].into())
+        .await
+        .unwrap();
+    assert_eq!(response.payload.as_ref(), [2]);
+
+    responder.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tcp() {
+    let transport_a = TcpTransport::new(
+        &["127.0.0.1:0".parse().unwrap()],
+        Vec::new(),
+        PRE_SHARED_KEY,
+        Duration::from_secs(5),
+    )
+    .await
+    .unwrap();
+    let transport_b = TcpTransport::new(
+        &[],
+        vec![transport_a.listen_addresses()[0].to_string()],
+        PRE_SHARED_KEY,
+        Duration::from_secs(5),
+    )
+    .await
+    .unwrap();
+
+    let mut subscription = transport_a
+        .subscribe("echo.*".to_string(), Some("group".to_string()))
+        .await
+        .unwrap();
+    let responder = {
+        let transport_a = transport_a.clone();
+
+        tokio::spawn(async move {
+            while let Some(message) = subscription.next().await {
+                transport_a
+                    .publish(message.reply.unwrap(), None, message.payload, None)
+                    .await
+                    .unwrap();
+            }
+        })
+    };
+
+    // Wait for connection to be established and subscription to be announced
+    let response = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            match transport_b
+                .request("echo.a".to_string(), vec![1, 2, 3].into())
+                .await
+            {
+                Ok(response) => break response,
+                Err(TransportError::NoResponders) => {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                Err(error) => panic!("Unexpected error: {error}"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(response.payload.as_ref(), [1, 2, 3]);
+    assert_eq!(transport_a.connected_peers(), 1);
+    assert_eq!(transport_b.connected_peers(), 1);
+
+    // Messages published on one side are received by subscribers on both sides
+    let mut subscription_a = transport_a
+        .subscribe("broadcast".to_string(), None)
+        .await
+        .unwrap();
+    let mut subscription_b = transport_b
+        .subscribe("broadcast".to_string(), None)
+        .await
+        .unwrap();
+    // Wait for subscription of `b` to be announced to `a` by making another request, which goes
+    // over the same connection
+    transport_b
+        .request("echo.b".to_string(), Vec::new().into())
+        .await
+        .unwrap();
+    transport_a
+        .publish("broadcast".to_string(), None, vec![4].into(), None)
+        .await
+        .unwrap();
+    assert_eq!(subscription_a.next().await.unwrap().payload.as_ref(), [4]);
+    assert_eq!(subscription_b.next().await.unwrap().payload.as_ref(), [4]);
+
+    responder.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tcp_wrong_pre_shared_key() {
+    let transport_a = TcpTransport::new(
+        &["127.0.0.1:0".parse().unwrap()],
+        Vec::new(),
+        PRE_SHARED_KEY,
+        Duration::from_secs(5),
+    )
+    .await
+    .unwrap();
+    let transport_b = TcpTransport::new(
+        &[],
+        vec![transport_a.listen_addresses()[0].to_string()],
+        b"wrong pre-shared key",
+        Duration::from_secs(5),
+    )
+    .await
+    .unwrap();
+
+    let _subscription = transport_a
+        .subscribe("echo".to_string(), None)
+        .await
+        .unwrap();
+
+    // Give instances some time to try to connect
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    assert_eq!(transport_a.connected_peers(), 0);
+    assert_eq!(transport_b.connected_peers(), 0);
+    assert_matches!(
+        transport_b
+            .request("echo".to_string(), Vec::new().into())
+            .await,
+        Err(TransportError::NoResponders)
+    );
+}
+
+#[test]
+fn router_disconnects_slow_connection() {
+    let router = Router::default();
+    let message = TransportMessage {
+        subject: "a".to_string(),
+        reply: None,
+        payload: vec![1].into(),
+    };
+
+    // Channel has capacity for a single frame
+    let (frame_sender, mut frame_receiver) = mpsc::channel(0);
+    assert!(router.add_connection(0, frame_sender).is_empty());
+    router.add_remote_subscription(0, 0, "a".to_string(), None);
+
+    assert_eq!(router.publish(message.clone()), 1);
+    // Queue is full, connection is disconnected along with its subscriptions
+    assert_eq!(router.publish(message.clone()), 0);
+    assert_eq!(router.publish(message), 0);
+
+    // Frame that was already queued is still delivered, after which channel is closed
+    assert_matches!(
+        frame_receiver.next().now_or_never(),
+        Some(Some(Frame::Message { sid: 0, .. }))
+    );
+    assert_matches!(frame_receiver.next().now_or_never(), Some(None));
+}
+
+#[tokio::test]
+async fn tcp_frame_authentication() {
+    let key = [1; 32];
+    let frame = Frame::Message {
+        sid: 0,
+        subject: "a".to_string(),
+        reply: None,
+        payload: vec![1, 2, 3],
+    };
+
+    let mut bytes = Vec::new();
+    let mut sending = FrameAuthenticator::new(Zeroizing::new(key));
+    write_frame(&mut bytes, &frame, Some(&mut sending))
+        .await
+        .unwrap();
+    let frame_size = bytes.len();
+    write_frame(&mut bytes, &frame, Some(&mut sending))
+        .await
+        .unwrap();
+    let (first_frame, second_frame) = bytes.split_at(frame_size);
+
+    // Frames are accepted in the order they were sent
+    let mut receiving = FrameAuthenticator::new(Zeroizing::new(key));
+    let mut reader = bytes.as_slice();
+    for _ in 0..2 {
+        assert_matches!(
+            read_frame(&mut reader, Some(&mut receiving)).await,
+            Ok(Frame::Message { sid: 0, .. })
+        );
+    }
+
+    // Reordered frame is rejected
+    let mut receiving = FrameAuthenticator::new(Zeroizing::new(key));
+    assert_matches!(
+        read_frame(&mut &second_frame[..], Some(&mut receiving)).await,
+        Err(error) if error.kind() == io::ErrorKind::InvalidData
+    );
+
+    // Replayed frame is rejected
+    let mut receiving = FrameAuthenticator::new(Zeroizing::new(key));
+    assert_matches!(
+        read_frame(&mut &first_frame[..], Some(&mut receiving)).await,
+        Ok(Frame::Message { sid: 0, .. })
+    );
+    assert_matches!(
+        read_frame(&mut &first_frame[..], Some(&mut receiving)).await,
+        Err(error) if error.kind() == io::ErrorKind::InvalidData
+    );
+
+    // Modified frame is rejected
+    let mut modified_frame = first_frame.to_vec();
+    modified_frame[frame_size - blake3::OUT_LEN - 1] ^= 1;
+    let mut receiving = FrameAuthenticator::new(Zeroizing::new(key));
+    assert_matches!(
+        read_frame(&mut modified_frame.as_slice(), Some(&mut receiving)).await,
+        Err(error) if error.kind() == io::ErrorKind::InvalidData
+    );
+
+    // Frame authenticated with a different key is rejected
+    let mut receiving = FrameAuthenticator::new(Zeroizing::new([2; 32]));
+    assert_matches!(
+        read_frame(&mut &first_frame[..], Some(&mut receiving)).await,
+        Err(error) if error.kind() == io::ErrorKind::InvalidData
+    );
+}
+
+#[tokio::test]
+async fn router_requests_share_reply_inbox() {
+    let router = Arc::new(Router::default());
+
+    let (frame_sender, mut frame_receiver) = mpsc::channel(16);
+    assert!(router.add_connection(0, frame_sender).is_empty());
+    router.add_remote_subscription(0, 0, "echo".to_string(), None);
+
+    let mut inbox_sid = None;
+    for payload in [1, 2] {
+        let request = tokio::spawn({
+            let router = Arc::clone(&router);
+
+            async move {
+                router
+                    .request(
+                        "echo".to_string(),
+                        vec![payload].into(),
+                        Duration::from_secs(5),
+                    )
+                    .await
+            }
+        });
+
+        // Reply inbox is announced once, before the first request
+        if inbox_sid.is_none() {
+            match frame_receiver.next().await.unwrap() {
+                Frame::Subscribe {
+                    sid,
+                    subject,
+                    queue_group: None,
+                } => {
+                    assert!(subject.starts_with("_INBOX."));
+                    inbox_sid.replace(sid);
+                }
+                frame => panic!("Unexpected frame: {frame:?}"),
+            }
+        }
+        let reply = match frame_receiver.next().await.unwrap() {
+            Frame::Message {
+                sid: 0,
+                reply: Some(reply),
+                ..
+            } => reply,
+            frame => panic!("Unexpected frame: {frame:?}"),
+        };
+
+        router.deliver_local(
+            inbox_sid.unwrap(),
+            TransportMessage {
+                subject: reply,
+                reply: None,
+                payload: vec![payload, payload].into(),
+            },
+        );
+        let response = request.await.unwrap().unwrap();
+        assert_eq!(response.payload.as_ref(), [payload, payload]);
+    }
+
+    // Inbox is announced to new connections
+    let (frame_sender, _frame_receiver) = mpsc::channel(16);
+    assert_matches!(
+        router.add_connection(1, frame_sender).as_slice(),
+        [Frame::Subscribe { sid, .. }] if Some(*sid) == inbox_sid
+    );
+}
@@ -428,6 +428,71 @@ impl DiskPieceCache {
         Ok(Some(piece_index))
     }
 
+    /// Change capacity of the cache stored in `directory` without losing cached pieces where
+    /// possible.
+    ///
+    /// When capacity decreases, pieces stored at offsets beyond new capacity are moved into free
+    /// offsets below it, pieces that don't fit anymore are dropped. Returns number of dropped
+    /// pieces.
+    pub(crate) fn resize(directory: &Path, capacity: u32) -> Result<usize, DiskPieceCacheError> {
+        let old_capacity = match fs::metadata(directory.join(Self::FILE_NAME)) {
+            Ok(metadata) => (metadata.len() / u64::from(Self::element_size())) as u32,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => 0,
+            Err(error) => {
+                return Err(error.into());
+            }
+        };
+
+        let mut dropped_pieces = 0;
+        if capacity < old_capacity {
+            let piece_cache = Self::open(directory, old_capacity, None, None)?;
+
+            // Corrupted elements are treated as free, they would be overwritten later anyway
+            let mut free_offsets = (0..capacity).filter(|&offset| {
+                !matches!(
+                    piece_cache.read_piece_index(PieceCacheOffset(offset)),
+                    Ok(Some(_))
+                )
+            });
+
+            for offset in capacity..old_capacity {
+                let (piece_index, piece) = match piece_cache.read_piece(PieceCacheOffset(offset)) {
+                    Ok(Some(element)) => element,
+                    Ok(None) => {
+                        continue;
+                    }
+                    Err(error) => {
+                        warn!(%error, %offset, "Failed to read cache element, skipping");
+                        continue;
+                    }
+                };
+
+                match free_offsets.next() {
+                    Some(free_offset) => {
+                        debug!(%piece_index, %offset, %free_offset, "Relocating cached piece");
+                        piece_cache.write_piece(
+                            PieceCacheOffset(free_offset),
+                            piece_index,
+                            &piece,
+                        )?;
+                    }
+                    None => {
+                        dropped_pieces += 1;
+                    }
+                }
+            }
+        }
+
+        if capacity == 0 {
+            Self::wipe(directory)?;
+        } else if capacity != old_capacity {
+            // Opening with different capacity resizes the file
+            Self::open(directory, capacity, None, None)?;
+        }
+
+        Ok(dropped_pieces)
+    }
+
     pub(crate) fn wipe(directory: &Path) -> io::Result<()> {
         let piece_cache = directory.join(Self::FILE_NAME);
         if !piece_cache.exists() {
@@ -141,3 +141,58 @@ fn basic() {
         );
     }
 }
+
+#[test]
+fn resize() {
+    let path = tempdir().unwrap();
+    let random_piece = || {
+        let mut piece = Piece::default();
+        thread_rng().fill(piece.as_mut());
+        piece
+    };
+
+    {
+        let disk_piece_cache = DiskPieceCache::open(path.as_ref(), 4, None, None).unwrap();
+        disk_piece_cache
+            .write_piece(PieceCacheOffset(0), PieceIndex::from(1), &random_piece())
+            .unwrap();
+        disk_piece_cache
+            .write_piece(PieceCacheOffset(2), PieceIndex::from(2), &random_piece())
+            .unwrap();
+        disk_piece_cache
+            .write_piece(PieceCacheOffset(3), PieceIndex::from(3), &random_piece())
+            .unwrap();
+    }
+
+    // Piece at offset 2 is moved into free offset 1, piece at offset 3 doesn't fit anymore
+    assert_eq!(DiskPieceCache::resize(path.as_ref(), 2).unwrap(), 1);
+
+    {
+        let disk_piece_cache = DiskPieceCache::open(path.as_ref(), 2, None, None).unwrap();
+        assert_eq!(
+            disk_piece_cache
+                .contents()
+                .map(|(_offset, maybe_piece_index)| maybe_piece_index)
+                .collect::<Vec<_>>(),
+            vec![Some(PieceIndex::from(1)), Some(PieceIndex::from(2))]
+        );
+    }
+
+    // Growing keeps existing pieces
+    assert_eq!(DiskPieceCache::resize(path.as_ref(), 3).unwrap(), 0);
+
+    {
+        let disk_piece_cache = DiskPieceCache::open(path.as_ref(), 3, None, None).unwrap();
+        assert_eq!(
+            disk_piece_cache
+                .contents()
+                .map(|(_offset, maybe_piece_index)| maybe_piece_index)
+                .collect::<Vec<_>>(),
+            vec![Some(PieceIndex::from(1)), Some(PieceIndex::from(2)), None]
+        );
+    }
+
+    // Zero capacity removes the cache
+    DiskPieceCache::resize(path.as_ref(), 0).unwrap();
+    assert!(!path.path().join(DiskPieceCache::FILE_NAME).exists());
+}
@@ -0,0 +1,145 @@
+//! Control of farms in a running farmer process
+//!
+//! Farmer doesn't need to be restarted (and all of its farms re-scanned) in order to add or remove
+//! a disk. [`FarmController`] abstracts attaching new farms, gracefully detaching existing ones and
+//! reporting their state, while [`unix_socket`] provides means to expose controller of a running
+//! farmer to other processes on the same machine.
+
+#[cfg(test)]
+mod tests;
+#[cfg(unix)]
+pub mod unix_socket;
+
+use async_trait::async_trait;
+use parity_scale_codec::{Decode, Encode};
+use serde::Serialize;
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Errors happening during farm control
+#[derive(Debug, Error)]
+pub enum FarmControlError {
+    /// I/O error occurred
+    #[error("Farm control I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Decoding error
+    #[error("Decoding error: {0}")]
+    Decoding(#[from] parity_scale_codec::Error),
+    /// Message is too large
+    #[error("Message of {size} bytes is too large")]
+    MessageTooLarge {
+        /// Message size
+        size: usize,
+    },
+    /// Farm matching provided farm index, ID or directory was not found
+    #[error("Farm {farm} not found")]
+    FarmNotFound {
+        /// Farm index, ID or directory
+        farm: String,
+    },
+    /// Farm is already attached
+    #[error("Farm in {directory} is already attached")]
+    AlreadyAttached {
+        /// Farm directory
+        directory: String,
+    },
+    /// Controller failed to perform requested operation
+    #[error("Farm control failed: {0}")]
+    Failed(String),
+    /// Remote controller returned an error
+    #[error("Remote farm controller error: {0}")]
+    Remote(String),
+}
+
+/// State of the farm
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FarmState {
+    /// Farm is farming and plotting
+    Running,
+    /// Farm is being detached
+    Detaching,
+    /// Farm is stopped for scrubbing and will be started again afterwards
+    Scrubbing,
+    /// Farm has exited on its own
+    Exited,
+    /// Farm has stopped due to an error
+    Failed {
+        /// Error that caused farm to stop
+        error: String,
+    },
+}
+
+impl fmt::Display for FarmState {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Running => f.write_str("Running"),
+            Self::Detaching => f.write_str("Detaching"),
+            Self::Scrubbing => f.write_str("Scrubbing"),
+            Self::Exited => f.write_str("Exited"),
+            Self::Failed { error } => write!(f, "Failed: {error}"),
+        }
+    }
+}
+
+/// Details about farm attached to farmer
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FarmDetails {
+    /// Farm index within farmer, indices of detached farms are reused by farms attached later
+    pub farm_index: u8,
+    /// Farm ID
+    pub farm_id: String,
+    /// Farm directory
+    pub directory: String,
+    /// Allocated space in bytes
+    pub allocated_space: u64,
+    /// Total number of sectors in the farm
+    pub total_sectors_count: u16,
+    /// Number of sectors plotted so far
+    pub plotted_sectors_count: u16,
+    /// Current farm state
+    pub state: FarmState,
+}
+
+/// Abstract farm controller implementation
+#[async_trait]
+pub trait FarmController: fmt::Debug + Send + Sync {
+    /// Attach a new farm.
+    ///
+    /// `disk_farm` uses the same format as disk farms specified on farmer's command line, for
+    /// example `path=/path/to/directory,size=5T`.
+    async fn attach_farm(&self, disk_farm: &str) -> Result<FarmDetails, FarmControlError>;
+
+    /// Gracefully detach farm identified by farm index, farm ID or directory.
+    ///
+    /// Farm's caches stop being used, plotting and farming are stopped and farm is closed by the
+    /// time this method returns, at which point its disk can be safely removed.
+    async fn detach_farm(&self, farm: &str) -> Result<(), FarmControlError>;
+
+    /// Details of all farms attached to farmer, ordered by farm index
+    async fn farms(&self) -> Result<Vec<FarmDetails>, FarmControlError>;
+}
+
+#[async_trait]
+impl<T> FarmController for Arc<T>
+where
+    T: FarmController + ?Sized,
+{
+    #[inline]
+    async fn attach_farm(&self, disk_farm: &str) -> Result<FarmDetails, FarmControlError> {
+        self.as_ref().attach_farm(disk_farm).await
+    }
+
+    #[inline]
+    async fn detach_farm(&self, farm: &str) -> Result<(), FarmControlError> {
+        self.as_ref().detach_farm(farm).await
+    }
+
+    #[inline]
+    async fn farms(&self) -> Result<Vec<FarmDetails>, FarmControlError> {
+        self.as_ref().farms().await
+    }
+}
@@ -0,0 +1,125 @@
+use crate::farm_control::{FarmControlError, FarmController, FarmDetails, FarmState};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+
+#[derive(Debug, Default)]
+struct TestFarmController {
+    farms: Mutex<Vec<FarmDetails>>,
+}
+
+#[async_trait]
+impl FarmController for TestFarmController {
+    async fn attach_farm(&self, disk_farm: &str) -> Result<FarmDetails, FarmControlError> {
+        let mut farms = self.farms.lock();
+
+        if farms.iter().any(|farm| farm.directory == disk_farm) {
+            return Err(FarmControlError::AlreadyAttached {
+                directory: disk_farm.to_string(),
+            });
+        }
+
+        let farm_details = FarmDetails {
+            farm_index: farms.len() as u8,
+            farm_id: format!("farm-{}", farms.len()),
+            directory: disk_farm.to_string(),
+            allocated_space: 1024,
+            total_sectors_count: 10,
+            plotted_sectors_count: 0,
+            state: FarmState::Running,
+        };
+        farms.push(farm_details.clone());
+
+        Ok(farm_details)
+    }
+
+    async fn detach_farm(&self, farm: &str) -> Result<(), FarmControlError> {
+        let mut farms = self.farms.lock();
+        let farms_before = farms.len();
+        farms.retain(|farm_details| farm_details.farm_id != farm);
+
+        if farms.len() == farms_before {
+            return Err(FarmControlError::FarmNotFound {
+                farm: farm.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn farms(&self) -> Result<Vec<FarmDetails>, FarmControlError> {
+        Ok(self.farms.lock().clone())
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket() {
+    use crate::farm_control::unix_socket::{serve, UnixSocketFarmController};
+    use std::assert_matches::assert_matches;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+    use tokio::net::UnixListener;
+
+    let path = tempdir().unwrap();
+    let socket_path = path.path().join("farmer.sock");
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    let server = tokio::spawn(serve(listener, Arc::new(TestFarmController::default())));
+
+    let farm_controller = UnixSocketFarmController::new(&socket_path);
+
+    assert!(farm_controller.farms().await.unwrap().is_empty());
+
+    let farm_details = farm_controller.attach_farm("/mnt/a").await.unwrap();
+    assert_eq!(farm_details.farm_index, 0);
+    assert_eq!(farm_details.directory, "/mnt/a");
+    assert_eq!(farm_details.state, FarmState::Running);
+    farm_controller.attach_farm("/mnt/b").await.unwrap();
+
+    // Errors are propagated to the client
+    assert_matches!(
+        farm_controller.attach_farm("/mnt/a").await,
+        Err(FarmControlError::Remote(_))
+    );
+    assert_matches!(
+        farm_controller.detach_farm("unknown").await,
+        Err(FarmControlError::Remote(_))
+    );
+
+    farm_controller
+        .detach_farm(&farm_details.farm_id)
+        .await
+        .unwrap();
+
+    let farms = farm_controller.farms().await.unwrap();
+    assert_eq!(farms.len(), 1);
+    assert_eq!(farms[0].directory, "/mnt/b");
+
+    server.abort();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket_rejects_large_requests() {
+    use crate::farm_control::unix_socket::serve;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{UnixListener, UnixStream};
+
+    let path = tempdir().unwrap();
+    let socket_path = path.path().join("farmer.sock");
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    let server = tokio::spawn(serve(listener, Arc::new(TestFarmController::default())));
+
+    let mut stream = UnixStream::connect(&socket_path).await.unwrap();
+    stream
+        .write_all(&(1024 * 1024_u32).to_le_bytes())
+        .await
+        .unwrap();
+
+    // Server closes connection without reading the request
+    let mut buffer = Vec::new();
+    assert_eq!(stream.read_to_end(&mut buffer).await.unwrap(), 0);
+
+    server.abort();
+}
@@ -0,0 +1,231 @@
+//! Farm control over Unix socket.
+//!
+//! Each message is a SCALE-encoded request or response prefixed with its length as little-endian
+//! `u32`. [`UnixSocketFarmController`] is the client side, [`serve()`] is the server side that
+//! exposes any other [`FarmController`] implementation (typically the one of a running farmer).
+
+use crate::farm_control::{FarmControlError, FarmController, FarmDetails};
+use async_trait::async_trait;
+use parity_scale_codec::{Decode, Encode};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tracing::{debug, warn};
+
+/// Max size of the request or response message, enough for details of 256 farms with long paths
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+/// Max size of the request message accepted by the server, enough for a farm with long path
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+/// Server closes connections that didn't send the next request within this time
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Encode, Decode)]
+enum FarmControlRequest {
+    AttachFarm { disk_farm: String },
+    DetachFarm { farm: String },
+    Farms,
+}
+
+#[derive(Debug, Encode, Decode)]
+enum FarmControlResponse {
+    Attached(FarmDetails),
+    Detached,
+    Farms(Vec<FarmDetails>),
+    Error(String),
+}
+
+async fn write_message<S, M>(stream: &mut S, message: &M) -> Result<(), FarmControlError>
+where
+    S: AsyncWrite + Unpin,
+    M: Encode,
+{
+    let bytes = message.encode();
+    if bytes.len() > MAX_MESSAGE_SIZE {
+        return Err(FarmControlError::MessageTooLarge { size: bytes.len() });
+    }
+
+    stream
+        .write_all(&(bytes.len() as u32).to_le_bytes())
+        .await?;
+    stream.write_all(&bytes).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+/// Returns `Ok(None)` if stream was closed before the next message
+async fn read_message<S, M>(
+    stream: &mut S,
+    max_message_size: usize,
+) -> Result<Option<M>, FarmControlError>
+where
+    S: AsyncRead + Unpin,
+    M: Decode,
+{
+    let mut length = [0; 4];
+    match stream.read_exact(&mut length).await {
+        Ok(_) => {}
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+            return Ok(None);
+        }
+        Err(error) => {
+            return Err(error.into());
+        }
+    }
+
+    let size = u32::from_le_bytes(length) as usize;
+    if size > max_message_size {
+        return Err(FarmControlError::MessageTooLarge { size });
+    }
+
+    let mut bytes = vec![0; size];
+    stream.read_exact(&mut bytes).await?;
+
+    Ok(Some(M::decode(&mut bytes.as_slice())?))
+}
+
+/// Farm controller that forwards requests to farmer listening on Unix socket.
+///
+/// New connection is established for every request. There is no timeout since attaching a farm
+/// might take a long time (space allocation, benchmarking, etc.).
+#[derive(Debug, Clone)]
+pub struct UnixSocketFarmController {
+    path: PathBuf,
+}
+
+#[async_trait]
+impl FarmController for UnixSocketFarmController {
+    async fn attach_farm(&self, disk_farm: &str) -> Result<FarmDetails, FarmControlError> {
+        let request = FarmControlRequest::AttachFarm {
+            disk_farm: disk_farm.to_string(),
+        };
+
+        match self.send_request(&request).await? {
+            FarmControlResponse::Attached(farm_details) => Ok(farm_details),
+            response => Err(unexpected_response(response)),
+        }
+    }
+
+    async fn detach_farm(&self, farm: &str) -> Result<(), FarmControlError> {
+        let request = FarmControlRequest::DetachFarm {
+            farm: farm.to_string(),
+        };
+
+        match self.send_request(&request).await? {
+            FarmControlResponse::Detached => Ok(()),
+            response => Err(unexpected_response(response)),
+        }
+    }
+
+    async fn farms(&self) -> Result<Vec<FarmDetails>, FarmControlError> {
+        match self.send_request(&FarmControlRequest::Farms).await? {
+            FarmControlResponse::Farms(farms) => Ok(farms),
+            response => Err(unexpected_response(response)),
+        }
+    }
+}
+
+impl UnixSocketFarmController {
+    /// Create new instance that will connect to farmer at `path`
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    async fn send_request(
+        &self,
+        request: &FarmControlRequest,
+    ) -> Result<FarmControlResponse, FarmControlError> {
+        let mut stream = UnixStream::connect(&self.path).await?;
+        write_message(&mut stream, request).await?;
+
+        read_message(&mut stream, MAX_MESSAGE_SIZE)
+            .await?
+            .ok_or_else(|| {
+                FarmControlError::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Farmer closed connection without response",
+                ))
+            })
+    }
+}
+
+fn unexpected_response(response: FarmControlResponse) -> FarmControlError {
+    match response {
+        FarmControlResponse::Error(error) => FarmControlError::Remote(error),
+        response => FarmControlError::Remote(format!("Unexpected response {response:?}")),
+    }
+}
+
+/// Serve farm control requests received on `listener` with `farm_controller`.
+///
+/// Connections that send requests larger than 64 KiB or don't send the next request within
+/// 10 seconds are closed. Runs until accepting new connections fails.
+pub async fn serve<FC>(listener: UnixListener, farm_controller: FC) -> io::Result<()>
+where
+    FC: FarmController + Clone + 'static,
+{
+    loop {
+        let (mut stream, _address) = listener.accept().await?;
+        let farm_controller = farm_controller.clone();
+
+        tokio::spawn(async move {
+            if let Err(error) = handle_connection(&mut stream, &farm_controller).await {
+                warn!(%error, "Failed to handle farm control connection");
+            }
+        });
+    }
+}
+
+async fn handle_connection<FC>(
+    stream: &mut UnixStream,
+    farm_controller: &FC,
+) -> Result<(), FarmControlError>
+where
+    FC: FarmController,
+{
+    loop {
+        let maybe_request = tokio::time::timeout(
+            REQUEST_READ_TIMEOUT,
+            read_message::<_, FarmControlRequest>(stream, MAX_REQUEST_SIZE),
+        )
+        .await
+        .map_err(|_error| {
+            FarmControlError::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Timed out waiting for request",
+            ))
+        })??;
+        let Some(request) = maybe_request else {
+            break;
+        };
+
+        debug!(?request, "Farm control request");
+
+        let response = match request {
+            FarmControlRequest::AttachFarm { disk_farm } => {
+                match farm_controller.attach_farm(&disk_farm).await {
+                    Ok(farm_details) => FarmControlResponse::Attached(farm_details),
+                    Err(error) => FarmControlResponse::Error(error.to_string()),
+                }
+            }
+            FarmControlRequest::DetachFarm { farm } => {
+                match farm_controller.detach_farm(&farm).await {
+                    Ok(()) => FarmControlResponse::Detached,
+                    Err(error) => FarmControlResponse::Error(error.to_string()),
+                }
+            }
+            FarmControlRequest::Farms => match farm_controller.farms().await {
+                Ok(farms) => FarmControlResponse::Farms(farms),
+                Err(error) => FarmControlResponse::Error(error.to_string()),
+            },
+        };
+
+        write_message(stream, &response).await?;
+    }
+
+    Ok(())
+}
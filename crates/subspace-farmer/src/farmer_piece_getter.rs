@@ -3,132 +3,200 @@
 use crate::farm::plotted_pieces::PlottedPieces;
 use crate::farmer_cache::FarmerCache;
 use crate::node_client::NodeClient;
-use async_lock::{
-    Mutex as AsyncMutex, MutexGuardArc as AsyncMutexGuardArc, RwLock as AsyncRwLock, Semaphore,
-};
+use async_lock::RwLock as AsyncRwLock;
 use async_trait::async_trait;
-use backoff::backoff::Backoff;
-use backoff::future::retry;
-use backoff::ExponentialBackoff;
+use futures::stream::FuturesUnordered;
+use futures::{future, stream, Stream, StreamExt};
 use parking_lot::Mutex;
-use std::collections::HashMap;
 use std::error::Error;
-use std::fmt;
 use std::hash::Hash;
 use std::num::NonZeroUsize;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::ops::Range;
 use std::sync::{Arc, Weak};
+use std::{fmt, mem};
 use subspace_core_primitives::{Piece, PieceIndex};
+use subspace_data_retrieval::object_fetcher::BoxError;
+use subspace_data_retrieval::piece_fetcher::{
+    PieceFetcher, PieceSourceTier, PieceStream, RetryPolicy,
+};
 use subspace_farmer_components::PieceGetter;
 use subspace_networking::utils::multihash::ToMultihash;
 use subspace_networking::utils::piece_provider::{PieceProvider, PieceValidator};
-use tracing::{debug, error, trace};
+use tracing::{debug, error};
 
 pub mod piece_validator;
 
 const MAX_RANDOM_WALK_ROUNDS: usize = 15;
+/// Tiers with farmer cache, DSN L2 cache and node RPC
+const FAST_TIERS: Range<usize> = 0..3;
+/// Tiers with local plot and DSN L1 (archival storage)
+const SLOW_TIERS: Range<usize> = 3..5;
+
+/// Retry policy for getting pieces from DSN cache
+pub type DsnCacheRetryPolicy = RetryPolicy;
 
-struct InProgressPieceGetting<'a> {
-    piece_index: PieceIndex,
-    in_progress_piece: AsyncMutexGuardArc<Option<Piece>>,
-    in_progress_pieces: &'a Mutex<HashMap<PieceIndex, Arc<AsyncMutex<Option<Piece>>>>>,
+/// Farmer cache as a source of pieces
+#[derive(Debug)]
+struct FarmerCacheSource<CacheIndex> {
+    farmer_cache: FarmerCache<CacheIndex>,
 }
 
-impl<'a> Drop for InProgressPieceGetting<'a> {
-    fn drop(&mut self) {
-        self.in_progress_pieces.lock().remove(&self.piece_index);
+#[async_trait]
+impl<CacheIndex> PieceGetter for FarmerCacheSource<CacheIndex>
+where
+    CacheIndex: Hash + Eq + Copy + fmt::Debug + fmt::Display + Send + Sync + 'static,
+    usize: From<CacheIndex>,
+    CacheIndex: TryFrom<usize>,
+{
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        Ok(self
+            .farmer_cache
+            .get_piece(piece_index.to_multihash())
+            .await)
     }
 }
 
-impl<'a> InProgressPieceGetting<'a> {
-    fn store_piece_getting_result(mut self, maybe_piece: &Option<Piece>) {
-        self.in_progress_piece.clone_from(maybe_piece);
-    }
+/// DSN as a source of pieces, successfully retrieved pieces are stored in farmer cache if it has
+/// space for them
+struct DsnSource<CacheIndex, PV> {
+    piece_provider: Arc<PieceProvider<PV>>,
+    farmer_cache: FarmerCache<CacheIndex>,
+    /// Get pieces from archival storage (L1) instead of farmers' piece cache (L2)
+    archival_storage: bool,
 }
 
-enum InProgressPiece<'a> {
-    Getting(InProgressPieceGetting<'a>),
-    // If piece is already in progress, just wait for it
-    Waiting {
-        in_progress_piece_mutex: Arc<AsyncMutex<Option<Piece>>>,
-    },
+impl<CacheIndex, PV> fmt::Debug for DsnSource<CacheIndex, PV> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DsnSource")
+            .field("archival_storage", &self.archival_storage)
+            .finish_non_exhaustive()
+    }
 }
 
-impl<'a> InProgressPiece<'a> {
-    fn new(
-        piece_index: PieceIndex,
-        in_progress_pieces: &'a Mutex<HashMap<PieceIndex, Arc<AsyncMutex<Option<Piece>>>>>,
-    ) -> Self {
-        let in_progress_piece_mutex = Arc::new(AsyncMutex::new(None));
-        // Take lock before anything else, set to `None` when another piece getting is already in
-        // progress
-        let mut local_in_progress_piece_guard = Some(
-            in_progress_piece_mutex
-                .try_lock_arc()
-                .expect("Just created; qed"),
-        );
-        let in_progress_piece_mutex = in_progress_pieces
-            .lock()
-            .entry(piece_index)
-            .and_modify(|_mutex| {
-                local_in_progress_piece_guard.take();
-            })
-            .or_insert_with(|| Arc::clone(&in_progress_piece_mutex))
-            .clone();
-
-        if let Some(in_progress_piece) = local_in_progress_piece_guard {
-            // Store guard and details necessary to remove entry from `in_progress_pieces` after
-            // piece getting is complete (in `Drop` impl)
-            Self::Getting(InProgressPieceGetting {
-                piece_index,
-                in_progress_piece,
-                in_progress_pieces,
-            })
+#[async_trait]
+impl<CacheIndex, PV> PieceGetter for DsnSource<CacheIndex, PV>
+where
+    CacheIndex: Hash + Eq + Copy + fmt::Debug + fmt::Display + Send + Sync + 'static,
+    usize: From<CacheIndex>,
+    CacheIndex: TryFrom<usize>,
+    PV: PieceValidator + Send + 'static,
+{
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        let maybe_piece = if self.archival_storage {
+            self.piece_provider
+                .get_piece_from_archival_storage(piece_index, MAX_RANDOM_WALK_ROUNDS)
+                .await
         } else {
-            // Piece getting is already in progress, only mutex is needed to wait for its result
-            Self::Waiting {
-                in_progress_piece_mutex,
+            self.piece_provider.get_piece_from_cache(piece_index).await
+        };
+
+        if let Some(piece) = &maybe_piece {
+            self.farmer_cache
+                .maybe_store_additional_piece(piece_index, piece)
+                .await;
+        }
+
+        Ok(maybe_piece)
+    }
+}
+
+/// Node RPC as a source of pieces, successfully retrieved pieces are stored in farmer cache if it
+/// has space for them
+#[derive(Debug)]
+struct NodeSource<CacheIndex, NC> {
+    node_client: NC,
+    farmer_cache: FarmerCache<CacheIndex>,
+}
+
+#[async_trait]
+impl<CacheIndex, NC> PieceGetter for NodeSource<CacheIndex, NC>
+where
+    CacheIndex: Hash + Eq + Copy + fmt::Debug + fmt::Display + Send + Sync + 'static,
+    usize: From<CacheIndex>,
+    CacheIndex: TryFrom<usize>,
+    NC: NodeClient,
+{
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        match self.node_client.piece(piece_index).await {
+            Ok(Some(piece)) => {
+                self.farmer_cache
+                    .maybe_store_additional_piece(piece_index, &piece)
+                    .await;
+                Ok(Some(piece))
+            }
+            Ok(None) => Ok(None),
+            Err(error) => {
+                error!(
+                    %error,
+                    %piece_index,
+                    "Failed to retrieve piece from node"
+                );
+                Ok(None)
             }
         }
     }
 }
 
-/// Retry policy for getting pieces from DSN cache
+/// Local plot as a source of pieces, successfully retrieved pieces are stored in farmer cache if
+/// it has space for them
 #[derive(Debug)]
-pub struct DsnCacheRetryPolicy {
-    /// Max number of retries when trying to get piece from DSN cache
-    pub max_retries: u16,
-    /// Exponential backoff between retries
-    pub backoff: ExponentialBackoff,
+struct PlottedPiecesSource<FarmIndex, CacheIndex> {
+    plotted_pieces: Arc<AsyncRwLock<PlottedPieces<FarmIndex>>>,
+    farmer_cache: FarmerCache<CacheIndex>,
 }
 
-struct Inner<FarmIndex, CacheIndex, PV, NC> {
-    piece_provider: PieceProvider<PV>,
+#[async_trait]
+impl<FarmIndex, CacheIndex> PieceGetter for PlottedPiecesSource<FarmIndex, CacheIndex>
+where
+    FarmIndex: Hash + Eq + Copy + fmt::Debug + Send + Sync + 'static,
+    usize: From<FarmIndex>,
+    CacheIndex: Hash + Eq + Copy + fmt::Debug + fmt::Display + Send + Sync + 'static,
+    usize: From<CacheIndex>,
+    CacheIndex: TryFrom<usize>,
+{
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        let maybe_read_piece_fut = self
+            .plotted_pieces
+            .try_read()
+            .and_then(|plotted_pieces| plotted_pieces.read_piece(piece_index));
+
+        let Some(read_piece_fut) = maybe_read_piece_fut else {
+            return Ok(None);
+        };
+        let Some(piece) = read_piece_fut.await else {
+            return Ok(None);
+        };
+
+        self.farmer_cache
+            .maybe_store_additional_piece(piece_index, &piece)
+            .await;
+
+        Ok(Some(piece))
+    }
+}
+
+struct Inner<CacheIndex, PV> {
+    piece_fetcher: PieceFetcher,
     farmer_cache: FarmerCache<CacheIndex>,
-    node_client: NC,
-    plotted_pieces: Arc<AsyncRwLock<PlottedPieces<FarmIndex>>>,
-    dsn_cache_retry_policy: DsnCacheRetryPolicy,
-    in_progress_pieces: Mutex<HashMap<PieceIndex, Arc<AsyncMutex<Option<Piece>>>>>,
-    request_semaphore: Arc<Semaphore>,
+    piece_provider: Arc<PieceProvider<PV>>,
 }
 
 /// Farmer-specific piece getter.
 ///
 /// Implements [`PieceGetter`] for plotting purposes, but useful outside of that as well.
-pub struct FarmerPieceGetter<FarmIndex, CacheIndex, PV, NC> {
-    inner: Arc<Inner<FarmIndex, CacheIndex, PV, NC>>,
+pub struct FarmerPieceGetter<CacheIndex, PV> {
+    inner: Arc<Inner<CacheIndex, PV>>,
 }
 
-impl<FarmIndex, CacheIndex, PV, NC> fmt::Debug
-    for FarmerPieceGetter<FarmIndex, CacheIndex, PV, NC>
-{
+impl<CacheIndex, PV> fmt::Debug for FarmerPieceGetter<CacheIndex, PV> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("FarmerPieceGetter").finish_non_exhaustive()
     }
 }
 
-impl<FarmIndex, CacheIndex, PV, NC> Clone for FarmerPieceGetter<FarmIndex, CacheIndex, PV, NC> {
+impl<CacheIndex, PV> Clone for FarmerPieceGetter<CacheIndex, PV> {
     #[inline]
     fn clone(&self) -> Self {
         Self {
@@ -137,235 +205,198 @@ impl<FarmIndex, CacheIndex, PV, NC> Clone for FarmerPieceGetter<FarmIndex, Cache
     }
 }
 
-impl<FarmIndex, CacheIndex, PV, NC> FarmerPieceGetter<FarmIndex, CacheIndex, PV, NC>
+impl<CacheIndex, PV> FarmerPieceGetter<CacheIndex, PV>
 where
-    FarmIndex: Hash + Eq + Copy + fmt::Debug + Send + Sync + 'static,
-    usize: From<FarmIndex>,
     CacheIndex: Hash + Eq + Copy + fmt::Debug + fmt::Display + Send + Sync + 'static,
     usize: From<CacheIndex>,
     CacheIndex: TryFrom<usize>,
     PV: PieceValidator + Send + 'static,
-    NC: NodeClient,
 {
     /// Create new instance
-    pub fn new(
+    pub fn new<FarmIndex, NC>(
         piece_provider: PieceProvider<PV>,
         farmer_cache: FarmerCache<CacheIndex>,
         node_client: NC,
         plotted_pieces: Arc<AsyncRwLock<PlottedPieces<FarmIndex>>>,
         dsn_cache_retry_policy: DsnCacheRetryPolicy,
         request_concurrency: NonZeroUsize,
-    ) -> Self {
-        let request_semaphore = Arc::new(Semaphore::new(request_concurrency.get()));
+    ) -> Self
+    where
+        FarmIndex: Hash + Eq + Copy + fmt::Debug + Send + Sync + 'static,
+        usize: From<FarmIndex>,
+        NC: NodeClient,
+    {
+        let piece_provider = Arc::new(piece_provider);
+        // Only DSN cache is retried, local sources and node RPC either have the piece or not
+        let tiers = vec![
+            PieceSourceTier {
+                name: "farmer cache",
+                sources: vec![Arc::new(FarmerCacheSource {
+                    farmer_cache: farmer_cache.clone(),
+                })],
+                retry_policy: RetryPolicy::no_retries(),
+                validation_hook: None,
+            },
+            PieceSourceTier {
+                name: "dsn cache",
+                sources: vec![Arc::new(DsnSource {
+                    piece_provider: Arc::clone(&piece_provider),
+                    farmer_cache: farmer_cache.clone(),
+                    archival_storage: false,
+                })],
+                retry_policy: dsn_cache_retry_policy,
+                validation_hook: None,
+            },
+            // Try node's RPC before reaching to L1 (archival storage on DSN)
+            PieceSourceTier {
+                name: "node",
+                sources: vec![Arc::new(NodeSource {
+                    node_client,
+                    farmer_cache: farmer_cache.clone(),
+                })],
+                retry_policy: RetryPolicy::no_retries(),
+                validation_hook: None,
+            },
+            PieceSourceTier {
+                name: "plot",
+                sources: vec![Arc::new(PlottedPiecesSource {
+                    plotted_pieces,
+                    farmer_cache: farmer_cache.clone(),
+                })],
+                retry_policy: RetryPolicy::no_retries(),
+                validation_hook: None,
+            },
+            PieceSourceTier {
+                name: "archival storage",
+                sources: vec![Arc::new(DsnSource {
+                    piece_provider: Arc::clone(&piece_provider),
+                    farmer_cache: farmer_cache.clone(),
+                    archival_storage: true,
+                })],
+                retry_policy: RetryPolicy::no_retries(),
+                validation_hook: None,
+            },
+        ];
+
         Self {
             inner: Arc::new(Inner {
-                piece_provider,
+                piece_fetcher: PieceFetcher::new(tiers, request_concurrency),
                 farmer_cache,
-                node_client,
-                plotted_pieces,
-                dsn_cache_retry_policy,
-                in_progress_pieces: Mutex::default(),
-                request_semaphore,
+                piece_provider,
             }),
         }
     }
 
     /// Fast way to get piece using various caches
     pub async fn get_piece_fast(&self, piece_index: PieceIndex) -> Option<Piece> {
-        let _guard = self.inner.request_semaphore.acquire().await;
-
-        match InProgressPiece::new(piece_index, &self.inner.in_progress_pieces) {
-            InProgressPiece::Getting(in_progress_piece_getting) => {
-                // Try to get the piece without releasing lock to make sure successfully
-                // downloaded piece gets stored
-                let maybe_piece = self.get_piece_fast_internal(piece_index).await;
-                // Store the result for others to observe
-                in_progress_piece_getting.store_piece_getting_result(&maybe_piece);
-                maybe_piece
-            }
-            InProgressPiece::Waiting {
-                in_progress_piece_mutex,
-            } => {
-                trace!(%piece_index, "Piece is already in progress, waiting for result #1");
-                // Doesn't matter if it was successful or not here
-                in_progress_piece_mutex.lock().await.clone()
-            }
-        }
-    }
-
-    async fn get_piece_fast_internal(&self, piece_index: PieceIndex) -> Option<Piece> {
-        let inner = &self.inner;
-
-        trace!(%piece_index, "Getting piece from farmer cache");
-        if let Some(piece) = inner
-            .farmer_cache
-            .get_piece(piece_index.to_multihash())
+        self.inner
+            .piece_fetcher
+            .try_get_piece(piece_index, FAST_TIERS)
             .await
-        {
-            trace!(%piece_index, "Got piece from farmer cache successfully");
-            return Some(piece);
-        }
-
-        // L2 piece acquisition
-        trace!(%piece_index, "Getting piece from DSN L2 cache");
-        if let Some(piece) = inner.piece_provider.get_piece_from_cache(piece_index).await {
-            trace!(%piece_index, "Got piece from DSN L2 cache");
-            inner
-                .farmer_cache
-                .maybe_store_additional_piece(piece_index, &piece)
-                .await;
-            return Some(piece);
-        }
-
-        // Try node's RPC before reaching to L1 (archival storage on DSN)
-        trace!(%piece_index, "Getting piece from node");
-        match inner.node_client.piece(piece_index).await {
-            Ok(Some(piece)) => {
-                trace!(%piece_index, "Got piece from node successfully");
-                inner
-                    .farmer_cache
-                    .maybe_store_additional_piece(piece_index, &piece)
-                    .await;
-                return Some(piece);
-            }
-            Ok(None) => {
-                // Nothing to do
-            }
-            Err(error) => {
-                error!(
-                    %error,
-                    %piece_index,
-                    "Failed to retrieve first segment piece from node"
-                );
-            }
-        }
-
-        None
     }
 
     /// Slow way to get piece using archival storage
     pub async fn get_piece_slow(&self, piece_index: PieceIndex) -> Option<Piece> {
-        let _guard = self.inner.request_semaphore.acquire().await;
-
-        match InProgressPiece::new(piece_index, &self.inner.in_progress_pieces) {
-            InProgressPiece::Getting(in_progress_piece_getting) => {
-                // Try to get the piece without releasing lock to make sure successfully
-                // downloaded piece gets stored
-                let maybe_piece = self.get_piece_slow_internal(piece_index).await;
-                // Store the result for others to observe
-                in_progress_piece_getting.store_piece_getting_result(&maybe_piece);
-                maybe_piece
-            }
-            InProgressPiece::Waiting {
-                in_progress_piece_mutex,
-            } => {
-                trace!(%piece_index, "Piece is already in progress, waiting for result #2");
-                if let Some(piece) = in_progress_piece_mutex.lock().await.clone() {
-                    trace!(
-                        %piece_index,
-                        "Piece was already in progress and downloaded successfully #1"
-                    );
-                    return Some(piece);
-                }
-
-                // Try again just in case
-                self.get_piece_slow_internal(piece_index).await
-            }
-        }
+        self.inner
+            .piece_fetcher
+            .try_get_piece(piece_index, SLOW_TIERS)
+            .await
     }
 
-    /// Slow way to get piece using archival storage
-    async fn get_piece_slow_internal(&self, piece_index: PieceIndex) -> Option<Piece> {
-        let inner = &self.inner;
-
-        trace!(%piece_index, "Getting piece from local plot");
-        let maybe_read_piece_fut = inner
-            .plotted_pieces
-            .try_read()
-            .and_then(|plotted_pieces| plotted_pieces.read_piece(piece_index));
+    /// Get multiple pieces, see [`PieceGetter::get_pieces()`].
+    ///
+    /// Pieces are checked in farmer cache first, then requested in batches from farmers' piece
+    /// cache (L2) on DSN and only remaining pieces are retrieved one by one from all tiers.
+    fn get_pieces_owned(
+        self,
+        piece_indexes: Vec<PieceIndex>,
+    ) -> impl Stream<Item = (PieceIndex, Result<Option<Piece>, BoxError>)> + Send + 'static {
+        let inner = self.inner;
+        // Pieces not found by the previous step, taken by the next step once previous is done
+        let missing_pieces = Arc::new(Mutex::new(Vec::<PieceIndex>::new()));
+
+        let from_farmer_cache = {
+            let missing_pieces = Arc::clone(&missing_pieces);
+
+            piece_indexes
+                .into_iter()
+                .map(|piece_index| {
+                    let inner = Arc::clone(&inner);
+
+                    async move {
+                        let maybe_piece = inner
+                            .farmer_cache
+                            .get_piece(piece_index.to_multihash())
+                            .await;
+                        (piece_index, maybe_piece)
+                    }
+                })
+                .collect::<FuturesUnordered<_>>()
+                .filter_map(move |(piece_index, maybe_piece)| {
+                    if maybe_piece.is_none() {
+                        missing_pieces.lock().push(piece_index);
+                    }
 
-        if let Some(read_piece_fut) = maybe_read_piece_fut {
-            if let Some(piece) = read_piece_fut.await {
-                trace!(%piece_index, "Got piece from local plot successfully");
-                inner
-                    .farmer_cache
-                    .maybe_store_additional_piece(piece_index, &piece)
-                    .await;
-                return Some(piece);
-            }
-        }
+                    future::ready(maybe_piece.map(|piece| (piece_index, Ok(Some(piece)))))
+                })
+        };
 
-        // L1 piece acquisition
-        trace!(%piece_index, "Getting piece from DSN L1.");
+        let from_dsn_cache = {
+            let inner = Arc::clone(&inner);
+            let missing_pieces_before = Arc::clone(&missing_pieces);
+            let missing_pieces = Arc::clone(&missing_pieces);
 
-        let archival_storage_search_result = inner
-            .piece_provider
-            .get_piece_from_archival_storage(piece_index, MAX_RANDOM_WALK_ROUNDS)
-            .await;
+            stream::once(async move { mem::take(&mut *missing_pieces_before.lock()) })
+                .flat_map({
+                    let inner = Arc::clone(&inner);
 
-        if let Some(piece) = archival_storage_search_result {
-            trace!(%piece_index, "DSN L1 lookup succeeded");
-            inner
-                .farmer_cache
-                .maybe_store_additional_piece(piece_index, &piece)
-                .await;
-            return Some(piece);
-        }
-
-        None
-    }
-
-    async fn get_piece_internal(&self, piece_index: PieceIndex) -> Option<Piece> {
-        {
-            let retries = AtomicU32::new(0);
-            let max_retries = u32::from(self.inner.dsn_cache_retry_policy.max_retries);
-            let mut backoff = self.inner.dsn_cache_retry_policy.backoff.clone();
-            backoff.reset();
-
-            let maybe_piece_fut = retry(backoff, || async {
-                let current_attempt = retries.fetch_add(1, Ordering::Relaxed);
-
-                if let Some(piece) = self.get_piece_fast_internal(piece_index).await {
-                    trace!(%piece_index, current_attempt, "Got piece fast");
-                    return Ok(Some(piece));
-                }
-                if current_attempt >= max_retries {
-                    if max_retries > 0 {
-                        debug!(
-                            %piece_index,
-                            current_attempt,
-                            max_retries,
-                            "Couldn't get a piece fast. No retries left"
-                        );
+                    move |piece_indexes| {
+                        Arc::clone(&inner.piece_provider).get_pieces_from_cache_owned(piece_indexes)
+                    }
+                })
+                .then(move |(piece_index, maybe_piece)| {
+                    let inner = Arc::clone(&inner);
+
+                    async move {
+                        if let Some(piece) = &maybe_piece {
+                            inner
+                                .farmer_cache
+                                .maybe_store_additional_piece(piece_index, piece)
+                                .await;
+                        }
+
+                        (piece_index, maybe_piece)
+                    }
+                })
+                .filter_map(move |(piece_index, maybe_piece)| {
+                    if maybe_piece.is_none() {
+                        missing_pieces.lock().push(piece_index);
                     }
-                    return Ok(None);
-                }
-
-                trace!(%piece_index, current_attempt, "Couldn't get a piece fast, retrying...");
-
-                Err(backoff::Error::transient("Couldn't get piece fast"))
-            });
 
-            if let Ok(Some(piece)) = maybe_piece_fut.await {
-                trace!(%piece_index, "Got piece from cache successfully");
-                return Some(piece);
-            }
+                    future::ready(maybe_piece.map(|piece| (piece_index, Ok(Some(piece)))))
+                })
         };
 
-        if let Some(piece) = self.get_piece_slow_internal(piece_index).await {
-            return Some(piece);
-        }
+        let from_all_tiers = stream::once(async move { mem::take(&mut *missing_pieces.lock()) })
+            .flat_map(move |piece_indexes| {
+                piece_indexes
+                    .into_iter()
+                    .map(|piece_index| {
+                        let piece_fetcher = inner.piece_fetcher.clone();
 
-        debug!(
-            %piece_index,
-            "Cannot acquire piece: all methods yielded empty result"
-        );
-        None
+                        async move { (piece_index, Ok(piece_fetcher.get_piece(piece_index).await)) }
+                    })
+                    .collect::<FuturesUnordered<_>>()
+            });
+
+        from_farmer_cache
+            .chain(from_dsn_cache)
+            .chain(from_all_tiers)
     }
 
     /// Downgrade to [`WeakFarmerPieceGetter`] in order to break reference cycles with internally
     /// used [`Arc`]
-    pub fn downgrade(&self) -> WeakFarmerPieceGetter<FarmIndex, CacheIndex, PV, NC> {
+    pub fn downgrade(&self) -> WeakFarmerPieceGetter<CacheIndex, PV> {
         WeakFarmerPieceGetter {
             inner: Arc::downgrade(&self.inner),
         }
@@ -373,58 +404,34 @@ where
 }
 
 #[async_trait]
-impl<FarmIndex, CacheIndex, PV, NC> PieceGetter for FarmerPieceGetter<FarmIndex, CacheIndex, PV, NC>
+impl<CacheIndex, PV> PieceGetter for FarmerPieceGetter<CacheIndex, PV>
 where
-    FarmIndex: Hash + Eq + Copy + fmt::Debug + Send + Sync + 'static,
-    usize: From<FarmIndex>,
     CacheIndex: Hash + Eq + Copy + fmt::Debug + fmt::Display + Send + Sync + 'static,
     usize: From<CacheIndex>,
     CacheIndex: TryFrom<usize>,
     PV: PieceValidator + Send + 'static,
-    NC: NodeClient,
 {
     async fn get_piece(
         &self,
         piece_index: PieceIndex,
     ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
-        let _guard = self.inner.request_semaphore.acquire().await;
-
-        match InProgressPiece::new(piece_index, &self.inner.in_progress_pieces) {
-            InProgressPiece::Getting(in_progress_piece_getting) => {
-                // Try to get the piece without releasing lock to make sure successfully
-                // downloaded piece gets stored
-                let maybe_piece = self.get_piece_internal(piece_index).await;
-                // Store the result for others to observe
-                in_progress_piece_getting.store_piece_getting_result(&maybe_piece);
-                Ok(maybe_piece)
-            }
-            InProgressPiece::Waiting {
-                in_progress_piece_mutex,
-            } => {
-                trace!(%piece_index, "Piece is already in progress, waiting for result #3");
-                if let Some(piece) = in_progress_piece_mutex.lock().await.clone() {
-                    trace!(
-                        %piece_index,
-                        "Piece was already in progress and downloaded successfully #2"
-                    );
-                    return Ok(Some(piece));
-                }
-
-                // Try again just in case
-                Ok(self.get_piece_internal(piece_index).await)
-            }
-        }
+        Ok(self.inner.piece_fetcher.get_piece(piece_index).await)
+    }
+
+    fn get_pieces<'a>(&'a self, piece_indexes: Vec<PieceIndex>) -> PieceStream<'a>
+    where
+        Self: Sync,
+    {
+        Box::pin(self.clone().get_pieces_owned(piece_indexes))
     }
 }
 
 /// Weak farmer piece getter, can be upgraded to [`FarmerPieceGetter`]
-pub struct WeakFarmerPieceGetter<FarmIndex, CacheIndex, PV, NC> {
-    inner: Weak<Inner<FarmIndex, CacheIndex, PV, NC>>,
+pub struct WeakFarmerPieceGetter<CacheIndex, PV> {
+    inner: Weak<Inner<CacheIndex, PV>>,
 }
 
-impl<FarmIndex, CacheIndex, PV, NC> fmt::Debug
-    for WeakFarmerPieceGetter<FarmIndex, CacheIndex, PV, NC>
-{
+impl<CacheIndex, PV> fmt::Debug for WeakFarmerPieceGetter<CacheIndex, PV> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("WeakFarmerPieceGetter")
@@ -432,7 +439,7 @@ impl<FarmIndex, CacheIndex, PV, NC> fmt::Debug
     }
 }
 
-impl<FarmIndex, CacheIndex, PV, NC> Clone for WeakFarmerPieceGetter<FarmIndex, CacheIndex, PV, NC> {
+impl<CacheIndex, PV> Clone for WeakFarmerPieceGetter<CacheIndex, PV> {
     #[inline]
     fn clone(&self) -> Self {
         Self {
@@ -442,16 +449,12 @@ impl<FarmIndex, CacheIndex, PV, NC> Clone for WeakFarmerPieceGetter<FarmIndex, C
 }
 
 #[async_trait]
-impl<FarmIndex, CacheIndex, PV, NC> PieceGetter
-    for WeakFarmerPieceGetter<FarmIndex, CacheIndex, PV, NC>
+impl<CacheIndex, PV> PieceGetter for WeakFarmerPieceGetter<CacheIndex, PV>
 where
-    FarmIndex: Hash + Eq + Copy + fmt::Debug + Send + Sync + 'static,
-    usize: From<FarmIndex>,
     CacheIndex: Hash + Eq + Copy + fmt::Debug + fmt::Display + Send + Sync + 'static,
     usize: From<CacheIndex>,
     CacheIndex: TryFrom<usize>,
     PV: PieceValidator + Send + 'static,
-    NC: NodeClient,
 {
     async fn get_piece(
         &self,
@@ -464,11 +467,27 @@ where
 
         piece_getter.get_piece(piece_index).await
     }
+
+    fn get_pieces<'a>(&'a self, piece_indexes: Vec<PieceIndex>) -> PieceStream<'a>
+    where
+        Self: Sync,
+    {
+        let Some(piece_getter) = self.upgrade() else {
+            debug!("Farmer piece getter upgrade didn't succeed");
+            return Box::pin(stream::iter(
+                piece_indexes
+                    .into_iter()
+                    .map(|piece_index| (piece_index, Ok(None))),
+            ));
+        };
+
+        Box::pin(piece_getter.get_pieces_owned(piece_indexes))
+    }
 }
 
-impl<FarmIndex, CacheIndex, PV, NC> WeakFarmerPieceGetter<FarmIndex, CacheIndex, PV, NC> {
+impl<CacheIndex, PV> WeakFarmerPieceGetter<CacheIndex, PV> {
     /// Try to upgrade to [`FarmerPieceGetter`] if there is at least one other instance of it alive
-    pub fn upgrade(&self) -> Option<FarmerPieceGetter<FarmIndex, CacheIndex, PV, NC>> {
+    pub fn upgrade(&self) -> Option<FarmerPieceGetter<CacheIndex, PV>> {
         Some(FarmerPieceGetter {
             inner: self.inner.upgrade()?,
         })
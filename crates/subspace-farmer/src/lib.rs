@@ -56,10 +56,12 @@
 pub mod cluster;
 pub mod disk_piece_cache;
 pub mod farm;
+pub mod farm_control;
 pub mod farmer_cache;
 pub mod farmer_piece_getter;
 pub mod node_client;
 pub mod plotter;
+pub mod reward_signer;
 pub mod single_disk_farm;
 pub mod thread_pool_manager;
 pub mod utils;
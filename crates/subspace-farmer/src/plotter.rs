@@ -5,10 +5,12 @@
 //! trait with async API representing plotting functionality allows composition of different
 //! implementations without the rest of the library being aware of implementation details.
 
+pub mod checkpoint;
 pub mod cpu;
 #[cfg(feature = "_gpu")]
 pub mod gpu;
 pub mod pool;
+pub mod schedule;
 
 use async_trait::async_trait;
 use futures::channel::mpsc;
@@ -45,6 +47,12 @@ pub enum SectorPlottingProgress {
         /// Error message
         error: String,
     },
+    /// Plotter went away (for example remote plotter stopped responding) before plotting was
+    /// finished, plotting of the sector can be retried on another plotter
+    PlotterGone {
+        /// Error message
+        error: String,
+    },
 }
 
 impl fmt::Debug for SectorPlottingProgress {
@@ -73,6 +81,9 @@ impl fmt::Debug for SectorPlottingProgress {
             SectorPlottingProgress::Error { error } => {
                 f.debug_struct_field1_finish("Error", "error", &error)
             }
+            SectorPlottingProgress::PlotterGone { error } => {
+                f.debug_struct_field1_finish("PlotterGone", "error", &error)
+            }
         }
     }
 }
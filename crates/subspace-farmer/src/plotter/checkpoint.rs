@@ -0,0 +1,641 @@
+//! Plotting checkpoints
+//!
+//! Plotting of a sector takes a while, especially when piece downloads are the bottleneck, and
+//! without checkpoints all progress is lost on restart. Checkpoints persist intermediate results
+//! of sector plotting in a scratch directory, such that plotting of the same sector resumes where
+//! it stopped:
+//! * downloaded pieces are stored as they arrive and are not downloaded again
+//! * encoded sector is stored once encoding is complete and is not encoded again if it was not
+//!   delivered before restart
+//!
+//! Encoding itself is not checkpointed at record granularity, so sector that was interrupted in
+//! the middle of encoding will be encoded again from already downloaded pieces.
+//!
+//! Checkpoint is tied to farmer protocol info it was created with (history size in particular,
+//! since it determines which pieces are plotted), so plotting resumes with the same protocol info
+//! as long as sector plotted with it will not be eligible for expiration right away. Checkpoint is
+//! removed once plotted sector is delivered or plotting fails. Checkpoints left behind by sectors
+//! that were never resumed (farm was removed, sector was plotted elsewhere, etc.) are removed when
+//! checkpoints are opened after [`MAX_CHECKPOINT_AGE`] without any progress.
+
+#[cfg(test)]
+mod tests;
+
+use async_trait::async_trait;
+use futures::{stream, FutureExt, StreamExt};
+use parity_scale_codec::{Decode, Encode};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use std::{fs, io};
+use subspace_core_primitives::crypto::blake3_hash;
+use subspace_core_primitives::{Blake3Hash, Piece, PieceIndex, PublicKey, SectorIndex};
+use subspace_data_retrieval::object_fetcher::BoxError;
+use subspace_data_retrieval::piece_fetcher::PieceStream;
+use subspace_farmer_components::plotting::PlottedSector;
+use subspace_farmer_components::{FarmerProtocolInfo, PieceGetter};
+use tokio::task;
+use tracing::{debug, warn};
+
+const CHECKPOINT_INFO_FILE: &str = "checkpoint.bin";
+const PIECES_DIRECTORY: &str = "pieces";
+const SECTOR_FILE: &str = "sector.bin";
+const PLOTTED_SECTOR_FILE: &str = "plotted_sector.bin";
+const TMP_EXTENSION: &str = "tmp";
+/// Checkpoints that didn't make any progress for this long are removed on startup
+pub const MAX_CHECKPOINT_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+#[derive(Debug, Encode, Decode)]
+struct CheckpointInfo {
+    farmer_protocol_info: FarmerProtocolInfo,
+    pieces_in_sector: u16,
+}
+
+impl CheckpointInfo {
+    /// Whether plotting with this checkpoint can continue when `requested` protocol info is used
+    /// for plotting
+    fn is_compatible(&self, requested: &Self) -> bool {
+        let stored = &self.farmer_protocol_info;
+        let requested_info = &requested.farmer_protocol_info;
+
+        if self.pieces_in_sector != requested.pieces_in_sector
+            || stored.max_pieces_in_sector != requested_info.max_pieces_in_sector
+            || stored.recent_segments != requested_info.recent_segments
+            || stored.recent_history_fraction != requested_info.recent_history_fraction
+            || stored.min_sector_lifetime != requested_info.min_sector_lifetime
+            || stored.history_size > requested_info.history_size
+        {
+            return false;
+        }
+
+        // Sector must not be eligible for expiration right after it is plotted
+        stored
+            .history_size
+            .sector_expiration_check(stored.min_sector_lifetime)
+            .is_some_and(|expiration_check_history_size| {
+                expiration_check_history_size > requested_info.history_size
+            })
+    }
+}
+
+/// Information about encoded sector stored in checkpoint
+#[derive(Debug, Encode, Decode)]
+struct EncodedSectorInfo {
+    plotted_sector: PlottedSector,
+    /// Checksum of the sector file, such that corrupted sector is not resumed
+    sector_checksum: Blake3Hash,
+}
+
+/// Plotting checkpoints stored in a scratch directory
+#[derive(Debug, Clone)]
+pub struct PlottingCheckpoints {
+    directory: Arc<PathBuf>,
+}
+
+impl PlottingCheckpoints {
+    /// Open plotting checkpoints in provided scratch directory, directory will be created if it
+    /// doesn't exist yet.
+    ///
+    /// Unusable and stale sector checkpoints as well as leftovers of interrupted writes are
+    /// removed.
+    pub fn open(directory: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&directory)?;
+
+        for entry in fs::read_dir(&directory)? {
+            let entry = entry?;
+
+            if !is_sector_checkpoint_directory(&entry)? {
+                continue;
+            }
+
+            let checkpoint_directory = entry.path();
+            if let Err(error) = collect_garbage(&checkpoint_directory) {
+                warn!(
+                    %error,
+                    directory = %checkpoint_directory.display(),
+                    "Failed to clean up plotting checkpoint"
+                );
+            }
+        }
+
+        Ok(Self {
+            directory: Arc::new(directory),
+        })
+    }
+
+    /// Scratch directory where checkpoints are stored
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Checkpoint of a particular sector
+    pub(super) fn sector(
+        &self,
+        public_key: &PublicKey,
+        sector_index: SectorIndex,
+    ) -> SectorCheckpoint {
+        SectorCheckpoint {
+            directory: self.directory.join(format!("{public_key}-{sector_index}")),
+        }
+    }
+
+    /// Prepare checkpoint of a sector for plotting.
+    ///
+    /// Returns farmer protocol info that must be used for plotting along with sector checkpoint.
+    /// If checkpoint can't be used for some reason, plotting proceeds with requested farmer
+    /// protocol info without checkpoint.
+    pub(super) async fn prepare_sector(
+        &self,
+        public_key: &PublicKey,
+        sector_index: SectorIndex,
+        farmer_protocol_info: FarmerProtocolInfo,
+        pieces_in_sector: u16,
+    ) -> (FarmerProtocolInfo, Option<SectorCheckpoint>) {
+        let checkpoint = self.sector(public_key, sector_index);
+
+        match checkpoint
+            .prepare(farmer_protocol_info, pieces_in_sector)
+            .await
+        {
+            Ok(farmer_protocol_info) => (farmer_protocol_info, Some(checkpoint)),
+            Err(error) => {
+                warn!(
+                    %error,
+                    directory = %checkpoint.directory.display(),
+                    "Failed to prepare plotting checkpoint, plotting without it"
+                );
+
+                (farmer_protocol_info, None)
+            }
+        }
+    }
+}
+
+/// Checkpoint of a single sector
+#[derive(Debug, Clone)]
+pub(super) struct SectorCheckpoint {
+    directory: PathBuf,
+}
+
+impl SectorCheckpoint {
+    /// Prepare checkpoint for plotting, returns farmer protocol info that must be used for plotting.
+    ///
+    /// Existing checkpoint is reused if it is compatible with requested farmer protocol info,
+    /// otherwise it is discarded and a new one is created.
+    pub(super) async fn prepare(
+        &self,
+        farmer_protocol_info: FarmerProtocolInfo,
+        pieces_in_sector: u16,
+    ) -> io::Result<FarmerProtocolInfo> {
+        let directory = self.directory.clone();
+
+        task::spawn_blocking(move || {
+            let requested = CheckpointInfo {
+                farmer_protocol_info,
+                pieces_in_sector,
+            };
+            let info_path = directory.join(CHECKPOINT_INFO_FILE);
+
+            match fs::read(&info_path) {
+                Ok(bytes) => match CheckpointInfo::decode(&mut bytes.as_slice()) {
+                    Ok(stored) => {
+                        if stored.is_compatible(&requested) {
+                            debug!(
+                                directory = %directory.display(),
+                                history_size = %stored.farmer_protocol_info.history_size,
+                                "Resuming plotting from checkpoint"
+                            );
+
+                            return Ok(stored.farmer_protocol_info);
+                        }
+
+                        debug!(
+                            directory = %directory.display(),
+                            "Discarding incompatible plotting checkpoint"
+                        );
+                    }
+                    Err(error) => {
+                        warn!(
+                            %error,
+                            directory = %directory.display(),
+                            "Failed to decode plotting checkpoint, discarding"
+                        );
+                    }
+                },
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    // New checkpoint
+                }
+                Err(error) => {
+                    return Err(error);
+                }
+            }
+
+            remove_dir_if_exists(&directory)?;
+            fs::create_dir_all(directory.join(PIECES_DIRECTORY))?;
+            write_atomically(&info_path, &requested.encode())?;
+
+            Ok(farmer_protocol_info)
+        })
+        .await?
+    }
+
+    /// Load encoded sector from checkpoint, if present.
+    ///
+    /// Encoded sector that doesn't match its checksum is discarded, in which case sector will be
+    /// plotted again.
+    pub(super) async fn load_sector(&self) -> io::Result<Option<(PlottedSector, Vec<u8>)>> {
+        let directory = self.directory.clone();
+
+        task::spawn_blocking(move || {
+            // Plotted sector is written last, so its presence means sector was written fully
+            let encoded_sector_info = match fs::read(directory.join(PLOTTED_SECTOR_FILE)) {
+                Ok(bytes) => EncodedSectorInfo::decode(&mut bytes.as_slice()).map_err(|error| {
+                    warn!(
+                        %error,
+                        directory = %directory.display(),
+                        "Failed to decode checkpointed plotted sector, discarding"
+                    );
+                }),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    return Ok(None);
+                }
+                Err(error) => {
+                    return Err(error);
+                }
+            };
+
+            if let Ok(EncodedSectorInfo {
+                plotted_sector,
+                sector_checksum,
+            }) = encoded_sector_info
+            {
+                let sector = fs::read(directory.join(SECTOR_FILE))?;
+
+                if blake3_hash(&sector) == sector_checksum {
+                    return Ok(Some((plotted_sector, sector)));
+                }
+
+                warn!(
+                    directory = %directory.display(),
+                    "Checkpointed sector checksum mismatch, discarding"
+                );
+            }
+
+            // Pieces will need to be downloaded again
+            fs::remove_file(directory.join(PLOTTED_SECTOR_FILE))?;
+            remove_file_if_exists(&directory.join(SECTOR_FILE))?;
+            fs::create_dir_all(directory.join(PIECES_DIRECTORY))?;
+
+            Ok(None)
+        })
+        .await?
+    }
+
+    /// Store encoded sector in checkpoint, downloaded pieces are no longer needed after this and
+    /// will be removed
+    pub(super) async fn store_sector(
+        &self,
+        plotted_sector: PlottedSector,
+        sector: Arc<Vec<u8>>,
+    ) -> io::Result<()> {
+        let directory = self.directory.clone();
+
+        task::spawn_blocking(move || {
+            let encoded_sector_info = EncodedSectorInfo {
+                plotted_sector,
+                sector_checksum: blake3_hash(&sector),
+            };
+
+            write_atomically(&directory.join(SECTOR_FILE), &sector)?;
+            write_atomically(
+                &directory.join(PLOTTED_SECTOR_FILE),
+                &encoded_sector_info.encode(),
+            )?;
+
+            remove_dir_if_exists(&directory.join(PIECES_DIRECTORY))
+        })
+        .await?
+    }
+
+    /// Remove checkpoint, typically after plotted sector was delivered
+    pub(super) async fn remove(&self) -> io::Result<()> {
+        let directory = self.directory.clone();
+
+        task::spawn_blocking(move || remove_dir_if_exists(&directory)).await?
+    }
+}
+
+/// Remove checkpoint if there is one, failure to remove is logged, but not returned
+pub(super) async fn remove_checkpoint(checkpoint: Option<&SectorCheckpoint>) {
+    if let Some(checkpoint) = checkpoint
+        && let Err(error) = checkpoint.remove().await
+    {
+        warn!(
+            %error,
+            directory = %checkpoint.directory.display(),
+            "Failed to remove plotting checkpoint"
+        );
+    }
+}
+
+/// Piece getter that reads pieces from sector checkpoint when available and stores pieces
+/// retrieved from the inner piece getter in sector checkpoint, works as a pass-through without
+/// checkpoint
+#[derive(Debug, Clone)]
+pub(super) struct CheckpointPieceGetter<PG> {
+    piece_getter: PG,
+    directory: Option<Arc<PathBuf>>,
+}
+
+#[async_trait]
+impl<PG> PieceGetter for CheckpointPieceGetter<PG>
+where
+    PG: PieceGetter + Send + Sync,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let Some(directory) = &self.directory else {
+            return self.piece_getter.get_piece(piece_index).await;
+        };
+
+        if let Some(piece) = read_checkpointed_piece(directory, piece_index).await? {
+            return Ok(Some(piece));
+        }
+
+        let maybe_piece = self.piece_getter.get_piece(piece_index).await?;
+
+        if let Some(piece) = &maybe_piece {
+            checkpoint_piece(directory, piece_index, piece).await?;
+        }
+
+        Ok(maybe_piece)
+    }
+
+    fn get_pieces<'a>(&'a self, piece_indexes: Vec<PieceIndex>) -> PieceStream<'a>
+    where
+        Self: Sync,
+    {
+        let Some(directory) = &self.directory else {
+            return self.piece_getter.get_pieces(piece_indexes);
+        };
+
+        let checkpointed_pieces = async move {
+            let mut checkpointed_pieces = Vec::new();
+            let mut missing_pieces = Vec::new();
+
+            for piece_index in piece_indexes {
+                match read_checkpointed_piece(directory, piece_index).await {
+                    Ok(Some(piece)) => {
+                        checkpointed_pieces.push((piece_index, Ok(Some(piece))));
+                    }
+                    Ok(None) => {
+                        missing_pieces.push(piece_index);
+                    }
+                    Err(error) => {
+                        checkpointed_pieces.push((piece_index, Err(error)));
+                    }
+                }
+            }
+
+            (checkpointed_pieces, missing_pieces)
+        };
+
+        Box::pin(
+            checkpointed_pieces
+                .map(move |(checkpointed_pieces, missing_pieces)| {
+                    let retrieved_pieces = self.piece_getter.get_pieces(missing_pieces).then(
+                        move |(piece_index, result)| async move {
+                            if let Ok(Some(piece)) = &result {
+                                if let Err(error) =
+                                    checkpoint_piece(directory, piece_index, piece).await
+                                {
+                                    return (piece_index, Err(error));
+                                }
+                            }
+
+                            (piece_index, result)
+                        },
+                    );
+
+                    stream::iter(checkpointed_pieces).chain(retrieved_pieces)
+                })
+                .flatten_stream(),
+        )
+    }
+}
+
+impl<PG> CheckpointPieceGetter<PG> {
+    /// Create new instance
+    pub(super) fn new(piece_getter: PG, checkpoint: Option<&SectorCheckpoint>) -> Self {
+        Self {
+            piece_getter,
+            directory: checkpoint
+                .map(|checkpoint| Arc::new(checkpoint.directory.join(PIECES_DIRECTORY))),
+        }
+    }
+}
+
+/// Read piece from checkpoint directory, returns `Ok(None)` if piece was not checkpointed yet or
+/// checkpointed piece is unusable
+async fn read_checkpointed_piece(
+    directory: &Path,
+    piece_index: PieceIndex,
+) -> Result<Option<Piece>, BoxError> {
+    let piece_path = directory.join(piece_index.to_string());
+
+    let read_result = task::spawn_blocking({
+        let piece_path = piece_path.clone();
+
+        move || fs::read(piece_path)
+    })
+    .await?;
+
+    match read_result {
+        Ok(bytes) => match Piece::try_from(bytes) {
+            Ok(piece) => {
+                return Ok(Some(piece));
+            }
+            Err(()) => {
+                warn!(
+                    %piece_index,
+                    path = %piece_path.display(),
+                    "Checkpointed piece has incorrect size, ignoring"
+                );
+            }
+        },
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            // Not downloaded yet
+        }
+        Err(error) => {
+            warn!(
+                %error,
+                %piece_index,
+                path = %piece_path.display(),
+                "Failed to read checkpointed piece, ignoring"
+            );
+        }
+    }
+
+    Ok(None)
+}
+
+/// Store piece in checkpoint directory, failure to write the piece is logged, but not returned
+async fn checkpoint_piece(
+    directory: &Path,
+    piece_index: PieceIndex,
+    piece: &Piece,
+) -> Result<(), BoxError> {
+    let piece_path = directory.join(piece_index.to_string());
+    let piece = piece.clone();
+
+    let write_result = task::spawn_blocking({
+        let piece_path = piece_path.clone();
+
+        move || write_atomically(&piece_path, piece.as_ref())
+    })
+    .await?;
+
+    if let Err(error) = write_result {
+        warn!(
+            %error,
+            %piece_index,
+            path = %piece_path.display(),
+            "Failed to checkpoint piece"
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether directory entry looks like `{public_key}-{sector_index}` sector checkpoint directory,
+/// anything else in scratch directory is left alone
+fn is_sector_checkpoint_directory(entry: &fs::DirEntry) -> io::Result<bool> {
+    if !entry.file_type()?.is_dir() {
+        return Ok(false);
+    }
+
+    let file_name = entry.file_name();
+    let Some((public_key, sector_index)) = file_name
+        .to_str()
+        .and_then(|file_name| file_name.rsplit_once('-'))
+    else {
+        return Ok(false);
+    };
+
+    Ok(public_key.len() == PublicKey::SIZE * 2
+        && public_key.bytes().all(|byte| byte.is_ascii_hexdigit())
+        && sector_index.parse::<SectorIndex>().is_ok())
+}
+
+/// Remove sector checkpoint directory if checkpoint is unusable or stale, otherwise remove
+/// leftovers of interrupted writes
+fn collect_garbage(directory: &Path) -> io::Result<()> {
+    let checkpoint_info = match fs::read(directory.join(CHECKPOINT_INFO_FILE)) {
+        Ok(bytes) => CheckpointInfo::decode(&mut bytes.as_slice()).ok(),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => {
+            return Err(error);
+        }
+    };
+
+    let pieces_directory = directory.join(PIECES_DIRECTORY);
+    // Directory modification time changes whenever pieces or encoded sector are written
+    let mut last_modified = fs::metadata(directory)?.modified()?;
+    match fs::metadata(&pieces_directory) {
+        Ok(metadata) => {
+            last_modified = last_modified.max(metadata.modified()?);
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => {
+            return Err(error);
+        }
+    }
+    let stale = SystemTime::now()
+        .duration_since(last_modified)
+        .is_ok_and(|age| age > MAX_CHECKPOINT_AGE);
+
+    if checkpoint_info.is_none() || stale {
+        debug!(
+            directory = %directory.display(),
+            %stale,
+            "Removing unusable plotting checkpoint"
+        );
+
+        return remove_dir_if_exists(directory);
+    }
+
+    for directory in [directory, &pieces_directory] {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                continue;
+            }
+            Err(error) => {
+                return Err(error);
+            }
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+
+            if path
+                .extension()
+                .is_some_and(|extension| extension == TMP_EXTENSION)
+            {
+                remove_file_if_exists(&path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write file such that it is either fully written or not present at all, even after power loss
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension(TMP_EXTENSION);
+
+    {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+
+    fs::rename(tmp_path, path)?;
+
+    // Make sure rename itself is persisted
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        File::open(parent)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+fn remove_file_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+fn remove_dir_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error),
+    }
+}
@@ -0,0 +1,276 @@
+use crate::plotter::checkpoint::{CheckpointPieceGetter, PlottingCheckpoints};
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::fs;
+use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use subspace_core_primitives::{HistorySize, Piece, PieceIndex, PublicKey, Record, SectorId};
+use subspace_farmer_components::plotting::PlottedSector;
+use subspace_farmer_components::sector::SectorMetadata;
+use subspace_farmer_components::{FarmerProtocolInfo, PieceGetter};
+use tempfile::tempdir;
+
+#[derive(Debug, Default)]
+struct CountingPieceGetter {
+    requests: AtomicUsize,
+}
+
+#[async_trait]
+impl PieceGetter for CountingPieceGetter {
+    async fn get_piece(
+        &self,
+        _piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+
+        Ok(Some(Piece::default()))
+    }
+}
+
+fn farmer_protocol_info(history_size: u64) -> FarmerProtocolInfo {
+    FarmerProtocolInfo {
+        history_size: HistorySize::new(NonZeroU64::new(history_size).unwrap()),
+        max_pieces_in_sector: 1000,
+        recent_segments: HistorySize::new(NonZeroU64::new(5).unwrap()),
+        recent_history_fraction: (
+            HistorySize::new(NonZeroU64::new(1).unwrap()),
+            HistorySize::new(NonZeroU64::new(10).unwrap()),
+        ),
+        min_sector_lifetime: HistorySize::new(NonZeroU64::new(4).unwrap()),
+    }
+}
+
+#[tokio::test]
+async fn resume_compatible_checkpoint() {
+    let directory = tempdir().unwrap();
+    let checkpoints = PlottingCheckpoints::open(directory.path().join("scratch")).unwrap();
+    let public_key = PublicKey::default();
+
+    let (info, checkpoint) = checkpoints
+        .prepare_sector(&public_key, 0, farmer_protocol_info(10), 1000)
+        .await;
+    assert_eq!(info.history_size, farmer_protocol_info(10).history_size);
+    assert!(checkpoint.is_some());
+
+    // History grew a bit, but sector plotted with checkpointed history size is still fine
+    let (info, _checkpoint) = checkpoints
+        .prepare_sector(&public_key, 0, farmer_protocol_info(12), 1000)
+        .await;
+    assert_eq!(info.history_size, farmer_protocol_info(10).history_size);
+
+    // Different sector has its own checkpoint
+    let (info, _checkpoint) = checkpoints
+        .prepare_sector(&public_key, 1, farmer_protocol_info(12), 1000)
+        .await;
+    assert_eq!(info.history_size, farmer_protocol_info(12).history_size);
+
+    // Different number of pieces in sector, checkpoint is discarded
+    let (info, _checkpoint) = checkpoints
+        .prepare_sector(&public_key, 0, farmer_protocol_info(12), 500)
+        .await;
+    assert_eq!(info.history_size, farmer_protocol_info(12).history_size);
+
+    // Sector would be eligible for expiration right away, checkpoint is discarded
+    let (info, _checkpoint) = checkpoints
+        .prepare_sector(&public_key, 0, farmer_protocol_info(20), 500)
+        .await;
+    assert_eq!(info.history_size, farmer_protocol_info(20).history_size);
+}
+
+#[tokio::test]
+async fn pieces_are_checkpointed() {
+    let directory = tempdir().unwrap();
+    let checkpoints = PlottingCheckpoints::open(directory.path().to_path_buf()).unwrap();
+    let public_key = PublicKey::default();
+    let inner = Arc::new(CountingPieceGetter::default());
+
+    let (_info, checkpoint) = checkpoints
+        .prepare_sector(&public_key, 0, farmer_protocol_info(10), 1000)
+        .await;
+    let piece_getter = CheckpointPieceGetter::new(Arc::clone(&inner), checkpoint.as_ref());
+
+    for piece_index in [PieceIndex::ZERO, PieceIndex::ONE] {
+        assert!(piece_getter.get_piece(piece_index).await.unwrap().is_some());
+    }
+    assert_eq!(inner.requests.load(Ordering::SeqCst), 2);
+
+    // After restart pieces are read from checkpoint
+    let (_info, checkpoint) = checkpoints
+        .prepare_sector(&public_key, 0, farmer_protocol_info(10), 1000)
+        .await;
+    let piece_getter = CheckpointPieceGetter::new(Arc::clone(&inner), checkpoint.as_ref());
+
+    for piece_index in [PieceIndex::ZERO, PieceIndex::ONE, PieceIndex::from(2)] {
+        assert!(piece_getter.get_piece(piece_index).await.unwrap().is_some());
+    }
+    assert_eq!(inner.requests.load(Ordering::SeqCst), 3);
+
+    // Without checkpoint all requests go to inner piece getter
+    let piece_getter = CheckpointPieceGetter::new(Arc::clone(&inner), None);
+    assert!(piece_getter
+        .get_piece(PieceIndex::ZERO)
+        .await
+        .unwrap()
+        .is_some());
+    assert_eq!(inner.requests.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn multiple_pieces_are_checkpointed() {
+    let directory = tempdir().unwrap();
+    let checkpoints = PlottingCheckpoints::open(directory.path().to_path_buf()).unwrap();
+    let public_key = PublicKey::default();
+    let inner = Arc::new(CountingPieceGetter::default());
+
+    let (_info, checkpoint) = checkpoints
+        .prepare_sector(&public_key, 0, farmer_protocol_info(10), 1000)
+        .await;
+    let piece_getter = CheckpointPieceGetter::new(Arc::clone(&inner), checkpoint.as_ref());
+
+    assert!(piece_getter
+        .get_piece(PieceIndex::ZERO)
+        .await
+        .unwrap()
+        .is_some());
+    assert_eq!(inner.requests.load(Ordering::SeqCst), 1);
+
+    let piece_indexes = (0..4).map(PieceIndex::from).collect::<Vec<_>>();
+    let pieces = piece_getter
+        .get_pieces(piece_indexes.clone())
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(pieces.len(), piece_indexes.len());
+    assert!(pieces
+        .iter()
+        .all(|(_piece_index, result)| matches!(result, Ok(Some(_)))));
+    // Already checkpointed piece is not requested again
+    assert_eq!(inner.requests.load(Ordering::SeqCst), 4);
+
+    let pieces = piece_getter
+        .get_pieces(piece_indexes)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(pieces.len(), 4);
+    assert_eq!(inner.requests.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn encoded_sector_is_checkpointed() {
+    let directory = tempdir().unwrap();
+    let checkpoints = PlottingCheckpoints::open(directory.path().to_path_buf()).unwrap();
+    let public_key = PublicKey::default();
+    let info = farmer_protocol_info(10);
+
+    let (_info, checkpoint) = checkpoints.prepare_sector(&public_key, 0, info, 1000).await;
+    let checkpoint = checkpoint.unwrap();
+    assert!(checkpoint.load_sector().await.unwrap().is_none());
+
+    let plotted_sector = PlottedSector {
+        sector_id: SectorId::new(public_key.hash(), 0),
+        sector_index: 0,
+        sector_metadata: SectorMetadata {
+            sector_index: 0,
+            pieces_in_sector: 1000,
+            s_bucket_sizes: Box::new([0; Record::NUM_S_BUCKETS]),
+            history_size: info.history_size,
+        }
+        .into(),
+        piece_indexes: vec![PieceIndex::ZERO, PieceIndex::ONE],
+    };
+    let sector = vec![42u8; 1024];
+    checkpoint
+        .store_sector(plotted_sector.clone(), Arc::new(sector.clone()))
+        .await
+        .unwrap();
+
+    let (_info, checkpoint) = checkpoints.prepare_sector(&public_key, 0, info, 1000).await;
+    let checkpoint = checkpoint.unwrap();
+    let (loaded_plotted_sector, loaded_sector) = checkpoint.load_sector().await.unwrap().unwrap();
+    assert_eq!(loaded_plotted_sector.sector_id, plotted_sector.sector_id);
+    assert_eq!(
+        loaded_plotted_sector.piece_indexes,
+        plotted_sector.piece_indexes
+    );
+    assert_eq!(loaded_sector, sector);
+
+    checkpoint.remove().await.unwrap();
+    assert!(checkpoint.load_sector().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn corrupted_encoded_sector_is_discarded() {
+    let directory = tempdir().unwrap();
+    let checkpoints = PlottingCheckpoints::open(directory.path().to_path_buf()).unwrap();
+    let public_key = PublicKey::default();
+    let info = farmer_protocol_info(10);
+
+    let (_info, checkpoint) = checkpoints.prepare_sector(&public_key, 0, info, 1000).await;
+    let checkpoint = checkpoint.unwrap();
+
+    let plotted_sector = PlottedSector {
+        sector_id: SectorId::new(public_key.hash(), 0),
+        sector_index: 0,
+        sector_metadata: SectorMetadata {
+            sector_index: 0,
+            pieces_in_sector: 1000,
+            s_bucket_sizes: Box::new([0; Record::NUM_S_BUCKETS]),
+            history_size: info.history_size,
+        }
+        .into(),
+        piece_indexes: vec![PieceIndex::ZERO],
+    };
+    checkpoint
+        .store_sector(plotted_sector, Arc::new(vec![42u8; 1024]))
+        .await
+        .unwrap();
+
+    let sector_path = directory
+        .path()
+        .join(format!("{public_key}-0"))
+        .join("sector.bin");
+    let mut sector = fs::read(&sector_path).unwrap();
+    sector[0] ^= 1;
+    fs::write(&sector_path, sector).unwrap();
+
+    assert!(checkpoint.load_sector().await.unwrap().is_none());
+    assert!(!sector_path.exists());
+
+    // Pieces can be checkpointed again for the next attempt
+    let inner = Arc::new(CountingPieceGetter::default());
+    let piece_getter = CheckpointPieceGetter::new(Arc::clone(&inner), Some(&checkpoint));
+    piece_getter.get_piece(PieceIndex::ZERO).await.unwrap();
+    piece_getter.get_piece(PieceIndex::ZERO).await.unwrap();
+    assert_eq!(inner.requests.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn garbage_is_collected_on_open() {
+    let directory = tempdir().unwrap();
+    let public_key = PublicKey::default();
+
+    {
+        let checkpoints = PlottingCheckpoints::open(directory.path().to_path_buf()).unwrap();
+        let (_info, checkpoint) = checkpoints
+            .prepare_sector(&public_key, 0, farmer_protocol_info(10), 1000)
+            .await;
+        assert!(checkpoint.is_some());
+    }
+
+    let usable_checkpoint = directory.path().join(format!("{public_key}-0"));
+    let leftover_tmp_file = usable_checkpoint.join("pieces").join("1.tmp");
+    fs::write(&leftover_tmp_file, b"partial").unwrap();
+    // Checkpoint without checkpoint info can't be resumed
+    let unusable_checkpoint = directory.path().join(format!("{public_key}-1"));
+    fs::create_dir_all(unusable_checkpoint.join("pieces")).unwrap();
+    // Unrelated directory is not touched
+    let unrelated_directory = directory.path().join("something-else");
+    fs::create_dir_all(&unrelated_directory).unwrap();
+
+    PlottingCheckpoints::open(directory.path().to_path_buf()).unwrap();
+
+    assert!(usable_checkpoint.join("checkpoint.bin").exists());
+    assert!(!leftover_tmp_file.exists());
+    assert!(!unusable_checkpoint.exists());
+    assert!(unrelated_directory.exists());
+}
@@ -5,6 +5,7 @@ pub mod cuda;
 mod gpu_encoders_manager;
 pub mod metrics;
 
+use crate::plotter::checkpoint::{remove_checkpoint, CheckpointPieceGetter, PlottingCheckpoints};
 use crate::plotter::gpu::gpu_encoders_manager::GpuRecordsEncoderManager;
 use crate::plotter::gpu::metrics::GpuPlotterMetrics;
 use crate::plotter::{Plotter, SectorPlottingProgress};
@@ -59,6 +60,7 @@ pub struct GpuPlotter<PG, GRE> {
     global_mutex: Arc<AsyncMutex<()>>,
     kzg: Kzg,
     erasure_coding: ErasureCoding,
+    checkpoints: Option<PlottingCheckpoints>,
     handlers: Arc<Handlers>,
     tasks_sender: mpsc::Sender<AsyncJoinOnDrop<()>>,
     _background_tasks: AsyncJoinOnDrop<()>,
@@ -194,6 +196,7 @@ where
         global_mutex: Arc<AsyncMutex<()>>,
         kzg: Kzg,
         erasure_coding: ErasureCoding,
+        checkpoints: Option<PlottingCheckpoints>,
         registry: Option<&mut Registry>,
     ) -> Result<Self, TryFromIntError> {
         let (tasks_sender, mut tasks_receiver) = mpsc::channel(1);
@@ -241,6 +244,7 @@ where
             global_mutex,
             kzg,
             erasure_coding,
+            checkpoints,
             handlers: Arc::default(),
             tasks_sender,
             _background_tasks: background_tasks,
@@ -288,153 +292,206 @@ where
             let global_mutex = Arc::clone(&self.global_mutex);
             let kzg = self.kzg.clone();
             let erasure_coding = self.erasure_coding.clone();
+            let checkpoints = self.checkpoints.clone();
             let abort_early = Arc::clone(&self.abort_early);
             let metrics = self.metrics.clone();
 
             async move {
-                // Downloading
-                let downloaded_sector = {
-                    if !progress_updater
-                        .update_progress_and_events(
-                            &mut progress_sender,
-                            SectorPlottingProgress::Downloading,
-                        )
-                        .await
-                    {
-                        return;
+                let (farmer_protocol_info, checkpoint) = match &checkpoints {
+                    Some(checkpoints) => {
+                        checkpoints
+                            .prepare_sector(
+                                &public_key,
+                                sector_index,
+                                farmer_protocol_info,
+                                pieces_in_sector,
+                            )
+                            .await
                     }
+                    None => (farmer_protocol_info, None),
+                };
 
-                    // Take mutex briefly to make sure plotting is allowed right now
-                    global_mutex.lock().await;
-
-                    let downloading_start = Instant::now();
-
-                    let downloaded_sector_fut = download_sector(DownloadSectorOptions {
-                        public_key: &public_key,
-                        sector_index,
-                        piece_getter: &piece_getter,
-                        farmer_protocol_info,
-                        kzg: &kzg,
-                        erasure_coding: &erasure_coding,
-                        pieces_in_sector,
-                    });
-
-                    let downloaded_sector = match downloaded_sector_fut.await {
-                        Ok(downloaded_sector) => downloaded_sector,
+                let checkpointed_sector = match &checkpoint {
+                    Some(checkpoint) => match checkpoint.load_sector().await {
+                        Ok(maybe_checkpointed_sector) => maybe_checkpointed_sector,
                         Err(error) => {
-                            warn!(%error, "Failed to download sector");
+                            warn!(%error, "Failed to load checkpointed sector, plotting again");
 
-                            progress_updater
-                                .update_progress_and_events(
-                                    &mut progress_sender,
-                                    SectorPlottingProgress::Error {
-                                        error: format!("Failed to download sector: {error}"),
-                                    },
-                                )
-                                .await;
+                            None
+                        }
+                    },
+                    None => None,
+                };
 
+                let (sector, plotted_sector) = if let Some((plotted_sector, sector)) =
+                    checkpointed_sector
+                {
+                    (sector, plotted_sector)
+                } else {
+                    // Downloading
+                    let downloaded_sector = {
+                        if !progress_updater
+                            .update_progress_and_events(
+                                &mut progress_sender,
+                                SectorPlottingProgress::Downloading,
+                            )
+                            .await
+                        {
                             return;
                         }
-                    };
-
-                    if !progress_updater
-                        .update_progress_and_events(
-                            &mut progress_sender,
-                            SectorPlottingProgress::Downloaded(downloading_start.elapsed()),
-                        )
-                        .await
-                    {
-                        return;
-                    }
 
-                    downloaded_sector
-                };
+                        // Take mutex briefly to make sure plotting is allowed right now
+                        global_mutex.lock().await;
+
+                        let downloading_start = Instant::now();
+
+                        let piece_getter =
+                            CheckpointPieceGetter::new(piece_getter, checkpoint.as_ref());
+                        let downloaded_sector_fut = download_sector(DownloadSectorOptions {
+                            public_key: &public_key,
+                            sector_index,
+                            piece_getter: &piece_getter,
+                            farmer_protocol_info,
+                            kzg: &kzg,
+                            erasure_coding: &erasure_coding,
+                            pieces_in_sector,
+                        });
+
+                        let downloaded_sector = match downloaded_sector_fut.await {
+                            Ok(downloaded_sector) => downloaded_sector,
+                            Err(error) => {
+                                warn!(%error, "Failed to download sector");
+
+                                progress_updater
+                                    .update_progress_and_events(
+                                        &mut progress_sender,
+                                        SectorPlottingProgress::Error {
+                                            error: format!("Failed to download sector: {error}"),
+                                        },
+                                    )
+                                    .await;
+
+                                remove_checkpoint(checkpoint.as_ref()).await;
 
-                // Plotting
-                let (sector, plotted_sector) = {
-                    let mut records_encoder = gpu_records_encoders_manager.get_encoder().await;
-                    if let Some(metrics) = &metrics {
-                        metrics.plotting_capacity_used.inc();
-                    }
+                                return;
+                            }
+                        };
+
+                        if !progress_updater
+                            .update_progress_and_events(
+                                &mut progress_sender,
+                                SectorPlottingProgress::Downloaded(downloading_start.elapsed()),
+                            )
+                            .await
+                        {
+                            return;
+                        }
 
-                    // Give a chance to interrupt plotting if necessary
-                    yield_now().await;
+                        downloaded_sector
+                    };
 
-                    if !progress_updater
-                        .update_progress_and_events(
-                            &mut progress_sender,
-                            SectorPlottingProgress::Encoding,
-                        )
-                        .await
+                    // Plotting
                     {
+                        let mut records_encoder = gpu_records_encoders_manager.get_encoder().await;
                         if let Some(metrics) = &metrics {
-                            metrics.plotting_capacity_used.dec();
+                            metrics.plotting_capacity_used.inc();
+                        }
+
+                        // Give a chance to interrupt plotting if necessary
+                        yield_now().await;
+
+                        if !progress_updater
+                            .update_progress_and_events(
+                                &mut progress_sender,
+                                SectorPlottingProgress::Encoding,
+                            )
+                            .await
+                        {
+                            if let Some(metrics) = &metrics {
+                                metrics.plotting_capacity_used.dec();
+                            }
+                            return;
                         }
-                        return;
-                    }
 
-                    let encoding_start = Instant::now();
+                        let encoding_start = Instant::now();
 
-                    let plotting_result = tokio::task::block_in_place(move || {
-                        let encoded_sector = encode_sector(
-                            downloaded_sector,
-                            EncodeSectorOptions {
-                                sector_index,
-                                records_encoder: &mut *records_encoder,
-                                abort_early: &abort_early,
-                            },
-                        )?;
+                        let plotting_result = tokio::task::block_in_place(move || {
+                            let encoded_sector = encode_sector(
+                                downloaded_sector,
+                                EncodeSectorOptions {
+                                    sector_index,
+                                    records_encoder: &mut *records_encoder,
+                                    abort_early: &abort_early,
+                                },
+                            )?;
 
-                        if abort_early.load(Ordering::Acquire) {
-                            return Err(PlottingError::AbortEarly);
-                        }
+                            if abort_early.load(Ordering::Acquire) {
+                                return Err(PlottingError::AbortEarly);
+                            }
 
-                        drop(records_encoder);
+                            drop(records_encoder);
 
-                        let mut sector = Vec::new();
+                            let mut sector = Vec::new();
 
-                        write_sector(&encoded_sector, &mut sector)?;
+                            write_sector(&encoded_sector, &mut sector)?;
 
-                        Ok((sector, encoded_sector.plotted_sector))
-                    });
+                            Ok((sector, encoded_sector.plotted_sector))
+                        });
 
-                    if let Some(metrics) = &metrics {
-                        metrics.plotting_capacity_used.dec();
-                    }
+                        if let Some(metrics) = &metrics {
+                            metrics.plotting_capacity_used.dec();
+                        }
 
-                    match plotting_result {
-                        Ok(plotting_result) => {
-                            if !progress_updater
-                                .update_progress_and_events(
-                                    &mut progress_sender,
-                                    SectorPlottingProgress::Encoded(encoding_start.elapsed()),
-                                )
-                                .await
-                            {
+                        match plotting_result {
+                            Ok(plotting_result) => {
+                                if !progress_updater
+                                    .update_progress_and_events(
+                                        &mut progress_sender,
+                                        SectorPlottingProgress::Encoded(encoding_start.elapsed()),
+                                    )
+                                    .await
+                                {
+                                    return;
+                                }
+
+                                if let Some(checkpoint) = &checkpoint {
+                                    let (sector, plotted_sector) = plotting_result;
+                                    let sector = Arc::new(sector);
+
+                                    if let Err(error) = checkpoint
+                                        .store_sector(plotted_sector.clone(), Arc::clone(&sector))
+                                        .await
+                                    {
+                                        warn!(%error, "Failed to checkpoint encoded sector");
+                                    }
+
+                                    (Arc::unwrap_or_clone(sector), plotted_sector)
+                                } else {
+                                    plotting_result
+                                }
+                            }
+                            Err(PlottingError::AbortEarly) => {
                                 return;
                             }
+                            Err(error) => {
+                                progress_updater
+                                    .update_progress_and_events(
+                                        &mut progress_sender,
+                                        SectorPlottingProgress::Error {
+                                            error: format!("Failed to encode sector: {error}"),
+                                        },
+                                    )
+                                    .await;
+
+                                remove_checkpoint(checkpoint.as_ref()).await;
 
-                            plotting_result
-                        }
-                        Err(PlottingError::AbortEarly) => {
-                            return;
-                        }
-                        Err(error) => {
-                            progress_updater
-                                .update_progress_and_events(
-                                    &mut progress_sender,
-                                    SectorPlottingProgress::Error {
-                                        error: format!("Failed to encode sector: {error}"),
-                                    },
-                                )
-                                .await;
-
-                            return;
+                                return;
+                            }
                         }
                     }
                 };
 
-                progress_updater
+                let delivered = progress_updater
                     .update_progress_and_events(
                         &mut progress_sender,
                         SectorPlottingProgress::Finished {
@@ -445,6 +502,10 @@ where
                     )
                     .await;
 
+                if delivered {
+                    remove_checkpoint(checkpoint.as_ref()).await;
+                }
+
                 drop(downloading_permit);
             }
         };
@@ -504,7 +565,8 @@ impl ProgressUpdater {
                     metrics.sector_plotting_time.observe(time.as_secs_f64());
                     metrics.sector_plotted.inc();
                 }
-                SectorPlottingProgress::Error { .. } => {
+                SectorPlottingProgress::Error { .. }
+                | SectorPlottingProgress::PlotterGone { .. } => {
                     metrics.sector_plotting_error.inc();
                 }
             }
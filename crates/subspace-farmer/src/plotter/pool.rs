@@ -1,28 +1,334 @@
 //! Pool plotter
 
+#[cfg(test)]
+mod tests;
+
 use crate::plotter::{Plotter, SectorPlottingProgress};
+use crate::utils::AsyncJoinOnDrop;
 use async_trait::async_trait;
 use futures::channel::mpsc;
+use futures::stream::FuturesUnordered;
+use futures::{select, FutureExt, SinkExt, StreamExt};
+use parking_lot::Mutex;
+use rand::prelude::*;
+use serde::Serialize;
 use std::any::type_name_of_val;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::future::pending;
+use std::pin::pin;
+use std::sync::Arc;
 use std::time::Duration;
 use subspace_core_primitives::{PublicKey, SectorIndex};
 use subspace_farmer_components::FarmerProtocolInfo;
-use tracing::{error, trace};
+use tokio::sync::watch;
+use tracing::{debug, error, info, trace, warn, Instrument};
+
+/// Weight of the new sample in exponential moving average of sector encoding time
+const ENCODING_TIME_SMOOTHING_FACTOR: f64 = 0.2;
+/// Maximum number of times a sector is rescheduled after plotter went away before error is reported
+const MAX_SECTOR_RESCHEDULES: usize = 3;
+
+/// Location of the plotter relative to the pool
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum PlotterLocation {
+    /// Plotter that runs in the same process (like CPU or GPU plotter), preferred for latency
+    /// reasons
+    Local,
+    /// Plotter that is accessed over the network (like cluster plotter)
+    Remote,
+}
+
+/// Reason for plotting to be paused, plotting resumes once there are no reasons left
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PauseReason {
+    /// Paused explicitly by the user
+    Manual,
+    /// Current time is outside of plotting schedule
+    Schedule,
+    /// Farming audits take too long and plotting is paused to leave more resources to farming
+    Throttling,
+}
+
+struct PooledPlotter {
+    plotter: Box<dyn Plotter + Send + Sync>,
+    location: PlotterLocation,
+    /// Exponential moving average of sector encoding time in seconds, `None` until the first
+    /// sector is encoded
+    average_encoding_time: Mutex<Option<f64>>,
+}
+
+impl fmt::Debug for PooledPlotter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledPlotter")
+            .field("plotter", &self.plotter)
+            .field("location", &self.location)
+            .field("average_encoding_time", &*self.average_encoding_time.lock())
+            .finish()
+    }
+}
+
+impl PooledPlotter {
+    fn observe_encoding_time(&self, time: Duration) {
+        let mut average_encoding_time = self.average_encoding_time.lock();
+        let time = time.as_secs_f64();
+        *average_encoding_time = Some(match *average_encoding_time {
+            Some(average) => average + (time - average) * ENCODING_TIME_SMOOTHING_FACTOR,
+            None => time,
+        });
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct SectorToPlot {
+    public_key: PublicKey,
+    sector_index: SectorIndex,
+    farmer_protocol_info: FarmerProtocolInfo,
+    pieces_in_sector: u16,
+    replotting: bool,
+}
+
+#[derive(Debug)]
+struct Inner {
+    plotters: Vec<PooledPlotter>,
+    retry_interval: Duration,
+    /// Reasons for plotting of new sectors to be paused, empty if not paused
+    paused: watch::Sender<BTreeSet<PauseReason>>,
+}
+
+impl Inner {
+    /// Order in which plotters should be tried for the next sector.
+    ///
+    /// Local plotters come before remote ones, within each group plotters are shuffled with
+    /// probability proportional to their observed throughput (inverse of average sector encoding
+    /// time), plotters that were not observed yet are treated as the fastest ones. `excluded`
+    /// plotters (for example those that already failed to plot the sector) are not included.
+    fn plotters_order(&self, excluded: &HashSet<usize>) -> Vec<usize> {
+        let average_encoding_times = self
+            .plotters
+            .iter()
+            .map(|plotter| *plotter.average_encoding_time.lock())
+            .collect::<Vec<_>>();
+        let fastest_encoding_time = average_encoding_times
+            .iter()
+            .flatten()
+            .copied()
+            .min_by(f64::total_cmp)
+            .unwrap_or(1.0);
+
+        let mut rng = thread_rng();
+        let mut order = self
+            .plotters
+            .iter()
+            .zip(average_encoding_times)
+            .enumerate()
+            .filter(|(index, _)| !excluded.contains(index))
+            .map(|(index, (plotter, average_encoding_time))| {
+                let weight = 1.0
+                    / average_encoding_time
+                        .unwrap_or(fastest_encoding_time)
+                        .max(f64::EPSILON);
+                // Weighted random sampling without replacement (Efraimidis-Spirakis)
+                let key = rng.gen::<f64>().powf(1.0 / weight);
+
+                (plotter.location, key, index)
+            })
+            .collect::<Vec<_>>();
+        order.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)));
+
+        order.into_iter().map(|(.., index)| index).collect()
+    }
+
+    /// Returns index of the plotter that accepted the sector
+    async fn try_plot_sector(
+        &self,
+        sector: SectorToPlot,
+        excluded: &HashSet<usize>,
+        progress_sender: &mpsc::Sender<SectorPlottingProgress>,
+    ) -> Option<usize> {
+        if !self.paused.borrow().is_empty() {
+            return None;
+        }
+
+        for index in self.plotters_order(excluded) {
+            let SectorToPlot {
+                public_key,
+                sector_index,
+                farmer_protocol_info,
+                pieces_in_sector,
+                replotting,
+            } = sector;
+
+            if self.plotters[index]
+                .plotter
+                .try_plot_sector(
+                    public_key,
+                    sector_index,
+                    farmer_protocol_info,
+                    pieces_in_sector,
+                    replotting,
+                    progress_sender.clone(),
+                )
+                .await
+            {
+                return Some(index);
+            }
+        }
+
+        None
+    }
+
+    /// Returns index of the plotter that accepted the sector
+    async fn plot_sector(
+        &self,
+        sector: SectorToPlot,
+        excluded: &HashSet<usize>,
+        progress_sender: &mpsc::Sender<SectorPlottingProgress>,
+    ) -> usize {
+        let mut paused_receiver = self.paused.subscribe();
+
+        loop {
+            // Sender is stored in `self`, so it can't be dropped
+            let _ = paused_receiver.wait_for(BTreeSet::is_empty).await;
+
+            if let Some(index) = self
+                .try_plot_sector(sector, excluded, progress_sender)
+                .await
+            {
+                return index;
+            }
+
+            trace!(
+                retry_interval = ?self.retry_interval,
+                "All plotters are busy, will wait and try again later"
+            );
+            tokio::time::sleep(self.retry_interval).await;
+        }
+    }
+
+    /// Forward progress of plotting from the plotter to the original sender, observing encoding
+    /// time and rescheduling the sector on another plotter if plotter goes away (reports
+    /// [`SectorPlottingProgress::PlotterGone`] or stops sending progress) before the sector is
+    /// finished.
+    ///
+    /// Local plotters that went away are not tried again for the same sector. Remote plotters
+    /// usually front many plotter instances, so they can be tried again, but the total number of
+    /// reschedules is limited to [`MAX_SECTOR_RESCHEDULES`].
+    ///
+    /// Errors reported by plotter are forwarded as is, since plotting would most likely fail the
+    /// same way on another plotter.
+    async fn forward_progress(
+        &self,
+        sector: SectorToPlot,
+        mut plotter_index: usize,
+        mut plotter_progress_receiver: mpsc::Receiver<SectorPlottingProgress>,
+        mut progress_sender: mpsc::Sender<SectorPlottingProgress>,
+    ) {
+        let mut failed_plotters = HashSet::with_capacity(1);
+        let mut reschedules = 0;
+
+        loop {
+            let reason = loop {
+                let Some(progress) = plotter_progress_receiver.next().await else {
+                    break "Plotter stopped sending progress".to_string();
+                };
+
+                let is_done = match &progress {
+                    SectorPlottingProgress::Encoded(time) => {
+                        self.plotters[plotter_index].observe_encoding_time(*time);
+                        false
+                    }
+                    SectorPlottingProgress::Finished { .. }
+                    | SectorPlottingProgress::Error { .. } => true,
+                    SectorPlottingProgress::PlotterGone { error } => {
+                        break error.clone();
+                    }
+                    _ => false,
+                };
+
+                if let Err(error) = progress_sender.send(progress).await {
+                    debug!(%error, "Failed to forward sector plotting progress");
+                    return;
+                }
+
+                if is_done {
+                    return;
+                }
+            };
+
+            let pooled_plotter = &self.plotters[plotter_index];
+            if pooled_plotter.location == PlotterLocation::Local {
+                failed_plotters.insert(plotter_index);
+            }
+            reschedules += 1;
+
+            if reschedules > MAX_SECTOR_RESCHEDULES || failed_plotters.len() >= self.plotters.len()
+            {
+                let progress = SectorPlottingProgress::Error {
+                    error: format!(
+                        "Plotters went away {reschedules} times before sector plotting was \
+                        finished, last time: {reason}"
+                    ),
+                };
+                if let Err(error) = progress_sender.send(progress).await {
+                    debug!(%error, "Failed to forward sector plotting progress");
+                }
+                return;
+            }
+
+            warn!(
+                %plotter_index,
+                r#type = type_name_of_val(&pooled_plotter.plotter),
+                %reason,
+                "Plotter went away before sector plotting was finished, rescheduling"
+            );
+
+            let (sender, receiver) = mpsc::channel(10);
+            plotter_index = self.plot_sector(sector, &failed_plotters, &sender).await;
+            plotter_progress_receiver = receiver;
+        }
+    }
+}
 
 /// Pool plotter.
 ///
-/// This plotter implementation relies on retries and should only be used with local plotter
-/// implementations (like CPU and GPU).
+/// This plotter implementation relies on retries and is primarily designed to be used with local
+/// plotter implementations (like CPU and GPU), though remote plotters can be added as well.
+///
+/// Sectors are assigned to local plotters first, then to remote plotters, with faster plotters
+/// (according to observed sector encoding time) receiving proportionally more sectors. If plotter
+/// goes away before the sector is finished (for example because remote plotter stopped
+/// responding), the sector is rescheduled. Local plotters that went away are not tried again for
+/// the same sector, while remote plotters (that usually front many plotter instances) can be, up to
+/// a limited number of reschedules. Errors reported by plotters themselves are forwarded without
+/// rescheduling.
+///
+/// Plotting of new sectors can be paused and resumed at runtime for different reasons, see
+/// [`Self::pause()`] and [`Self::set_paused()`].
 #[derive(Debug)]
 pub struct PoolPlotter {
-    plotters: Vec<Box<dyn Plotter + Send + Sync>>,
-    retry_interval: Duration,
+    inner: Arc<Inner>,
+    tasks_sender: mpsc::Sender<AsyncJoinOnDrop<()>>,
+    _background_tasks: AsyncJoinOnDrop<()>,
+}
+
+impl Drop for PoolPlotter {
+    #[inline]
+    fn drop(&mut self) {
+        self.tasks_sender.close_channel();
+    }
 }
 
 #[async_trait]
 impl Plotter for PoolPlotter {
     async fn has_free_capacity(&self) -> Result<bool, String> {
-        for (index, plotter) in self.plotters.iter().enumerate() {
+        if self.is_paused() {
+            return Ok(false);
+        }
+
+        for (index, pooled_plotter) in self.inner.plotters.iter().enumerate() {
+            let plotter = &pooled_plotter.plotter;
             match plotter.has_free_capacity().await {
                 Ok(result) => {
                     if result {
@@ -52,29 +358,22 @@ impl Plotter for PoolPlotter {
         replotting: bool,
         progress_sender: mpsc::Sender<SectorPlottingProgress>,
     ) {
-        loop {
-            for plotter in &self.plotters {
-                if plotter
-                    .try_plot_sector(
-                        public_key,
-                        sector_index,
-                        farmer_protocol_info,
-                        pieces_in_sector,
-                        replotting,
-                        progress_sender.clone(),
-                    )
-                    .await
-                {
-                    return;
-                }
-            }
+        let sector = SectorToPlot {
+            public_key,
+            sector_index,
+            farmer_protocol_info,
+            pieces_in_sector,
+            replotting,
+        };
 
-            trace!(
-                retry_interval = ?self.retry_interval,
-                "All plotters are busy, will wait and try again later"
-            );
-            tokio::time::sleep(self.retry_interval).await;
-        }
+        let (sender, receiver) = mpsc::channel(10);
+        let plotter_index = self
+            .inner
+            .plot_sector(sector, &HashSet::new(), &sender)
+            .await;
+
+        self.forward_progress(sector, plotter_index, receiver, progress_sender)
+            .await;
     }
 
     async fn try_plot_sector(
@@ -86,32 +385,179 @@ impl Plotter for PoolPlotter {
         replotting: bool,
         progress_sender: mpsc::Sender<SectorPlottingProgress>,
     ) -> bool {
-        for plotter in &self.plotters {
-            if plotter
-                .try_plot_sector(
-                    public_key,
-                    sector_index,
-                    farmer_protocol_info,
-                    pieces_in_sector,
-                    replotting,
-                    progress_sender.clone(),
-                )
-                .await
-            {
-                return true;
-            }
-        }
+        let sector = SectorToPlot {
+            public_key,
+            sector_index,
+            farmer_protocol_info,
+            pieces_in_sector,
+            replotting,
+        };
+
+        let (sender, receiver) = mpsc::channel(10);
+        let Some(plotter_index) = self
+            .inner
+            .try_plot_sector(sector, &HashSet::new(), &sender)
+            .await
+        else {
+            return false;
+        };
+
+        self.forward_progress(sector, plotter_index, receiver, progress_sender)
+            .await;
 
-        false
+        true
     }
 }
 
 impl PoolPlotter {
     /// Create new instance
-    pub fn new(plotters: Vec<Box<dyn Plotter + Send + Sync>>, retry_interval: Duration) -> Self {
+    pub fn new(
+        plotters: Vec<(Box<dyn Plotter + Send + Sync>, PlotterLocation)>,
+        retry_interval: Duration,
+    ) -> Self {
+        let (tasks_sender, mut tasks_receiver) = mpsc::channel(1);
+
+        // Basically runs progress forwarding tasks in the background and allows to abort on drop
+        let background_tasks = AsyncJoinOnDrop::new(
+            tokio::spawn(async move {
+                let background_tasks = FuturesUnordered::new();
+                let mut background_tasks = pin!(background_tasks);
+                // Just so that `FuturesUnordered` will never end
+                background_tasks.push(AsyncJoinOnDrop::new(tokio::spawn(pending::<()>()), true));
+
+                loop {
+                    select! {
+                        maybe_background_task = tasks_receiver.next().fuse() => {
+                            let Some(background_task) = maybe_background_task else {
+                                break;
+                            };
+
+                            background_tasks.push(background_task);
+                        },
+                        _ = background_tasks.select_next_some() => {
+                            // Nothing to do
+                        }
+                    }
+                }
+            }),
+            true,
+        );
+
+        let plotters = plotters
+            .into_iter()
+            .map(|(plotter, location)| PooledPlotter {
+                plotter,
+                location,
+                average_encoding_time: Mutex::default(),
+            })
+            .collect();
+
         Self {
-            plotters,
-            retry_interval,
+            inner: Arc::new(Inner {
+                plotters,
+                retry_interval,
+                paused: watch::Sender::default(),
+            }),
+            tasks_sender,
+            _background_tasks: background_tasks,
+        }
+    }
+
+    /// Pause plotting of new sectors on user request.
+    ///
+    /// Sectors that are already being plotted will finish, while new sectors (including sectors
+    /// that need to be rescheduled after plotter failure) will wait until plotting is resumed.
+    pub fn pause(&self) {
+        self.set_paused(PauseReason::Manual, true);
+    }
+
+    /// Resume plotting of new sectors after [`Self::pause()`], plotting might still remain paused
+    /// for other reasons
+    pub fn resume(&self) {
+        self.set_paused(PauseReason::Manual, false);
+    }
+
+    /// Add or remove reason for plotting to be paused, plotting is resumed once all reasons are
+    /// removed
+    pub fn set_paused(&self, reason: PauseReason, paused: bool) {
+        self.inner.paused.send_if_modified(|pause_reasons| {
+            let was_paused = !pause_reasons.is_empty();
+            let modified = if paused {
+                pause_reasons.insert(reason)
+            } else {
+                pause_reasons.remove(&reason)
+            };
+
+            if modified {
+                if pause_reasons.is_empty() {
+                    info!(?reason, "Plotting resumed");
+                } else if !was_paused {
+                    info!(?reason, "Plotting paused");
+                } else {
+                    debug!(?reason, ?pause_reasons, "Plotting pause reasons changed");
+                }
+            }
+
+            modified
+        });
+    }
+
+    /// Whether plotting of new sectors is paused
+    pub fn is_paused(&self) -> bool {
+        !self.inner.paused.borrow().is_empty()
+    }
+
+    /// Reasons for plotting to be paused, empty if not paused
+    pub fn pause_reasons(&self) -> Vec<PauseReason> {
+        self.inner.paused.borrow().iter().copied().collect()
+    }
+
+    /// Wait for plotting to be resumed, resolves immediately if plotting is not paused
+    pub async fn wait_resumed(&self) {
+        // Sender is stored in `self`, so it can't be dropped
+        let _ = self
+            .inner
+            .paused
+            .subscribe()
+            .wait_for(BTreeSet::is_empty)
+            .await;
+    }
+
+    async fn forward_progress(
+        &self,
+        sector: SectorToPlot,
+        plotter_index: usize,
+        plotter_progress_receiver: mpsc::Receiver<SectorPlottingProgress>,
+        mut progress_sender: mpsc::Sender<SectorPlottingProgress>,
+    ) {
+        let forwarding_fut = {
+            let inner = Arc::clone(&self.inner);
+            let progress_sender = progress_sender.clone();
+
+            async move {
+                inner
+                    .forward_progress(
+                        sector,
+                        plotter_index,
+                        plotter_progress_receiver,
+                        progress_sender,
+                    )
+                    .await
+            }
+        };
+
+        let forwarding_task =
+            AsyncJoinOnDrop::new(tokio::spawn(forwarding_fut.in_current_span()), true);
+        if let Err(error) = self.tasks_sender.clone().send(forwarding_task).await {
+            warn!(%error, "Failed to send progress forwarding task");
+
+            let progress = SectorPlottingProgress::Error {
+                error: format!("Failed to send progress forwarding task: {error}"),
+            };
+
+            if let Err(error) = progress_sender.send(progress).await {
+                debug!(%error, "Failed to send sector plotting progress");
+            }
         }
     }
 }
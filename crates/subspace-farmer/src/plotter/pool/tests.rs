@@ -0,0 +1,502 @@
+use crate::plotter::pool::{PlotterLocation, PoolPlotter, MAX_SECTOR_RESCHEDULES};
+use crate::plotter::{Plotter, SectorPlottingProgress};
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use std::assert_matches::assert_matches;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use subspace_core_primitives::{HistorySize, PublicKey, SectorIndex};
+use subspace_farmer_components::FarmerProtocolInfo;
+
+/// Plotter that sends `Downloading`, `Encoded` and then either an error or nothing at all (as if it
+/// went away)
+#[derive(Debug)]
+struct TestPlotter {
+    encoding_time: Duration,
+    error: Option<&'static str>,
+    sectors: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Plotter for TestPlotter {
+    async fn has_free_capacity(&self) -> Result<bool, String> {
+        Ok(true)
+    }
+
+    async fn plot_sector(
+        &self,
+        public_key: PublicKey,
+        sector_index: SectorIndex,
+        farmer_protocol_info: FarmerProtocolInfo,
+        pieces_in_sector: u16,
+        replotting: bool,
+        progress_sender: mpsc::Sender<SectorPlottingProgress>,
+    ) {
+        self.try_plot_sector(
+            public_key,
+            sector_index,
+            farmer_protocol_info,
+            pieces_in_sector,
+            replotting,
+            progress_sender,
+        )
+        .await;
+    }
+
+    async fn try_plot_sector(
+        &self,
+        _public_key: PublicKey,
+        _sector_index: SectorIndex,
+        _farmer_protocol_info: FarmerProtocolInfo,
+        _pieces_in_sector: u16,
+        _replotting: bool,
+        mut progress_sender: mpsc::Sender<SectorPlottingProgress>,
+    ) -> bool {
+        self.sectors.fetch_add(1, Ordering::SeqCst);
+        let encoding_time = self.encoding_time;
+        let error = self.error;
+
+        tokio::spawn(async move {
+            progress_sender
+                .send(SectorPlottingProgress::Downloading)
+                .await
+                .unwrap();
+            progress_sender
+                .send(SectorPlottingProgress::Encoded(encoding_time))
+                .await
+                .unwrap();
+            if let Some(error) = error {
+                progress_sender
+                    .send(SectorPlottingProgress::Error {
+                        error: error.to_string(),
+                    })
+                    .await
+                    .unwrap();
+            }
+        });
+
+        true
+    }
+}
+
+/// Plotter that sends `Downloading` and then reports that it went away
+#[derive(Debug)]
+struct GonePlotter {
+    sectors: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Plotter for GonePlotter {
+    async fn has_free_capacity(&self) -> Result<bool, String> {
+        Ok(true)
+    }
+
+    async fn plot_sector(
+        &self,
+        public_key: PublicKey,
+        sector_index: SectorIndex,
+        farmer_protocol_info: FarmerProtocolInfo,
+        pieces_in_sector: u16,
+        replotting: bool,
+        progress_sender: mpsc::Sender<SectorPlottingProgress>,
+    ) {
+        self.try_plot_sector(
+            public_key,
+            sector_index,
+            farmer_protocol_info,
+            pieces_in_sector,
+            replotting,
+            progress_sender,
+        )
+        .await;
+    }
+
+    async fn try_plot_sector(
+        &self,
+        _public_key: PublicKey,
+        _sector_index: SectorIndex,
+        _farmer_protocol_info: FarmerProtocolInfo,
+        _pieces_in_sector: u16,
+        _replotting: bool,
+        mut progress_sender: mpsc::Sender<SectorPlottingProgress>,
+    ) -> bool {
+        self.sectors.fetch_add(1, Ordering::SeqCst);
+
+        tokio::spawn(async move {
+            progress_sender
+                .send(SectorPlottingProgress::Downloading)
+                .await
+                .unwrap();
+            progress_sender
+                .send(SectorPlottingProgress::PlotterGone {
+                    error: "gone".to_string(),
+                })
+                .await
+                .unwrap();
+            // Keep the channel open, such that only explicit report triggers rescheduling
+            std::future::pending::<()>().await;
+        });
+
+        true
+    }
+}
+
+fn farmer_protocol_info() -> FarmerProtocolInfo {
+    FarmerProtocolInfo {
+        history_size: HistorySize::ONE,
+        max_pieces_in_sector: 1,
+        recent_segments: HistorySize::ONE,
+        recent_history_fraction: (HistorySize::ONE, HistorySize::ONE),
+        min_sector_lifetime: HistorySize::ONE,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn prefers_local_plotters() {
+    let local_sectors = Arc::default();
+    let remote_sectors = Arc::default();
+    let pool_plotter = PoolPlotter::new(
+        vec![
+            (
+                Box::new(TestPlotter {
+                    encoding_time: Duration::from_secs(1),
+                    error: Some("remote"),
+                    sectors: Arc::clone(&remote_sectors),
+                }),
+                PlotterLocation::Remote,
+            ),
+            (
+                Box::new(TestPlotter {
+                    encoding_time: Duration::from_secs(2),
+                    error: None,
+                    sectors: Arc::clone(&local_sectors),
+                }),
+                PlotterLocation::Local,
+            ),
+        ],
+        Duration::from_millis(10),
+    );
+
+    for sector_index in 0..10 {
+        let (progress_sender, progress_receiver) = mpsc::channel(10);
+        pool_plotter
+            .plot_sector(
+                PublicKey::default(),
+                sector_index,
+                farmer_protocol_info(),
+                1,
+                false,
+                progress_sender,
+            )
+            .await;
+
+        // Local plotter is always tried first even though it is slower and remote plotter only
+        // gets sectors after local went away
+        let progress = progress_receiver.collect::<Vec<_>>().await;
+        assert_matches!(
+            progress[1],
+            SectorPlottingProgress::Encoded(time) if time == Duration::from_secs(2)
+        );
+        assert_matches!(
+            &progress[4],
+            SectorPlottingProgress::Error { error } if error == "remote"
+        );
+    }
+
+    assert_eq!(local_sectors.load(Ordering::SeqCst), 10);
+    assert_eq!(remote_sectors.load(Ordering::SeqCst), 10);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reschedules_failed_sectors() {
+    let gone_sectors = Arc::default();
+    let failing_sectors = Arc::default();
+    let pool_plotter = PoolPlotter::new(
+        vec![
+            (
+                Box::new(TestPlotter {
+                    encoding_time: Duration::from_secs(1),
+                    error: None,
+                    sectors: Arc::clone(&gone_sectors),
+                }),
+                PlotterLocation::Local,
+            ),
+            (
+                Box::new(TestPlotter {
+                    encoding_time: Duration::from_secs(1),
+                    error: Some("failed"),
+                    sectors: Arc::clone(&failing_sectors),
+                }),
+                PlotterLocation::Remote,
+            ),
+        ],
+        Duration::from_millis(10),
+    );
+
+    let (progress_sender, progress_receiver) = mpsc::channel(10);
+    assert!(
+        pool_plotter
+            .try_plot_sector(
+                PublicKey::default(),
+                0,
+                farmer_protocol_info(),
+                1,
+                false,
+                progress_sender,
+            )
+            .await
+    );
+
+    let progress = progress_receiver.collect::<Vec<_>>().await;
+    // Events of the first plotter that went away are followed by events of the second plotter,
+    // whose error is forwarded as is
+    assert_eq!(progress.len(), 5);
+    assert_matches!(progress[0], SectorPlottingProgress::Downloading);
+    assert_matches!(progress[2], SectorPlottingProgress::Downloading);
+    assert_matches!(
+        &progress[4],
+        SectorPlottingProgress::Error { error } if error == "failed"
+    );
+    assert_eq!(gone_sectors.load(Ordering::SeqCst), 1);
+    assert_eq!(failing_sectors.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn errors_are_not_rescheduled() {
+    let sectors_a = Arc::new(AtomicUsize::new(0));
+    let sectors_b = Arc::new(AtomicUsize::new(0));
+    let pool_plotter = PoolPlotter::new(
+        vec![
+            (
+                Box::new(TestPlotter {
+                    encoding_time: Duration::from_secs(1),
+                    error: Some("failed"),
+                    sectors: Arc::clone(&sectors_a),
+                }),
+                PlotterLocation::Local,
+            ),
+            (
+                Box::new(TestPlotter {
+                    encoding_time: Duration::from_secs(1),
+                    error: Some("failed"),
+                    sectors: Arc::clone(&sectors_b),
+                }),
+                PlotterLocation::Local,
+            ),
+        ],
+        Duration::from_millis(10),
+    );
+
+    let (progress_sender, progress_receiver) = mpsc::channel(10);
+    pool_plotter
+        .plot_sector(
+            PublicKey::default(),
+            0,
+            farmer_protocol_info(),
+            1,
+            false,
+            progress_sender,
+        )
+        .await;
+
+    let progress = progress_receiver.collect::<Vec<_>>().await;
+    assert_eq!(progress.len(), 3);
+    assert_matches!(
+        &progress[2],
+        SectorPlottingProgress::Error { error } if error == "failed"
+    );
+    // Only one plotter was tried
+    assert_eq!(
+        sectors_a.load(Ordering::SeqCst) + sectors_b.load(Ordering::SeqCst),
+        1
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_plotters_are_not_retried() {
+    let sectors = (0..3)
+        .map(|_| Arc::new(AtomicUsize::new(0)))
+        .collect::<Vec<_>>();
+    let pool_plotter = PoolPlotter::new(
+        sectors
+            .iter()
+            .map(|sectors| {
+                (
+                    Box::new(TestPlotter {
+                        encoding_time: Duration::from_secs(1),
+                        error: None,
+                        sectors: Arc::clone(sectors),
+                    }) as Box<dyn Plotter + Send + Sync>,
+                    PlotterLocation::Local,
+                )
+            })
+            .collect(),
+        Duration::from_millis(10),
+    );
+
+    let (progress_sender, progress_receiver) = mpsc::channel(10);
+    pool_plotter
+        .plot_sector(
+            PublicKey::default(),
+            0,
+            farmer_protocol_info(),
+            1,
+            false,
+            progress_sender,
+        )
+        .await;
+
+    let progress = progress_receiver.collect::<Vec<_>>().await;
+    // Every plotter went away exactly once before error is reported
+    assert_eq!(progress.len(), 7);
+    assert_matches!(progress[6], SectorPlottingProgress::Error { .. });
+    for sectors in &sectors {
+        assert_eq!(sectors.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pause_and_resume() {
+    let sectors = Arc::default();
+    let pool_plotter = Arc::new(PoolPlotter::new(
+        vec![(
+            Box::new(TestPlotter {
+                encoding_time: Duration::from_secs(1),
+                error: Some("done"),
+                sectors: Arc::clone(&sectors),
+            }),
+            PlotterLocation::Local,
+        )],
+        Duration::from_millis(10),
+    ));
+
+    pool_plotter.pause();
+    assert!(pool_plotter.is_paused());
+    assert!(!pool_plotter.has_free_capacity().await.unwrap());
+
+    let (progress_sender, _progress_receiver) = mpsc::channel(10);
+    assert!(
+        !pool_plotter
+            .try_plot_sector(
+                PublicKey::default(),
+                0,
+                farmer_protocol_info(),
+                1,
+                false,
+                progress_sender,
+            )
+            .await
+    );
+
+    // Plotting waits for plotting to be resumed
+    let (progress_sender, _progress_receiver) = mpsc::channel(10);
+    let plotting = tokio::spawn({
+        let pool_plotter = Arc::clone(&pool_plotter);
+
+        async move {
+            pool_plotter
+                .plot_sector(
+                    PublicKey::default(),
+                    1,
+                    farmer_protocol_info(),
+                    1,
+                    false,
+                    progress_sender,
+                )
+                .await;
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!plotting.is_finished());
+    assert_eq!(sectors.load(Ordering::SeqCst), 0);
+
+    pool_plotter.resume();
+    assert!(!pool_plotter.is_paused());
+    plotting.await.unwrap();
+    assert_eq!(sectors.load(Ordering::SeqCst), 1);
+    assert!(pool_plotter.has_free_capacity().await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reschedules_sectors_of_gone_plotters() {
+    let gone_sectors = Arc::default();
+    let other_sectors = Arc::default();
+    let pool_plotter = PoolPlotter::new(
+        vec![
+            (
+                Box::new(GonePlotter {
+                    sectors: Arc::clone(&gone_sectors),
+                }) as Box<dyn Plotter + Send + Sync>,
+                PlotterLocation::Local,
+            ),
+            (
+                Box::new(TestPlotter {
+                    encoding_time: Duration::from_secs(1),
+                    error: Some("done"),
+                    sectors: Arc::clone(&other_sectors),
+                }),
+                PlotterLocation::Remote,
+            ),
+        ],
+        Duration::from_millis(10),
+    );
+
+    let (progress_sender, progress_receiver) = mpsc::channel(10);
+    pool_plotter
+        .plot_sector(
+            PublicKey::default(),
+            0,
+            farmer_protocol_info(),
+            1,
+            false,
+            progress_sender,
+        )
+        .await;
+
+    let progress = progress_receiver.collect::<Vec<_>>().await;
+    // Report of the plotter that went away is not forwarded, sector is plotted by another plotter
+    // instead
+    assert_eq!(progress.len(), 4);
+    assert_matches!(progress[0], SectorPlottingProgress::Downloading);
+    assert_matches!(progress[1], SectorPlottingProgress::Downloading);
+    assert_matches!(
+        &progress[3],
+        SectorPlottingProgress::Error { error } if error == "done"
+    );
+    assert_eq!(gone_sectors.load(Ordering::SeqCst), 1);
+    assert_eq!(other_sectors.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gone_remote_plotters_are_retried() {
+    let sectors = Arc::default();
+    let pool_plotter = PoolPlotter::new(
+        vec![(
+            Box::new(GonePlotter {
+                sectors: Arc::clone(&sectors),
+            }),
+            PlotterLocation::Remote,
+        )],
+        Duration::from_millis(10),
+    );
+
+    let (progress_sender, progress_receiver) = mpsc::channel(10);
+    pool_plotter
+        .plot_sector(
+            PublicKey::default(),
+            0,
+            farmer_protocol_info(),
+            1,
+            false,
+            progress_sender,
+        )
+        .await;
+
+    let progress = progress_receiver.collect::<Vec<_>>().await;
+    // Remote plotter is tried again until the limit of reschedules is reached
+    assert_eq!(sectors.load(Ordering::SeqCst), MAX_SECTOR_RESCHEDULES + 1);
+    assert_eq!(progress.len(), MAX_SECTOR_RESCHEDULES + 2);
+    assert_matches!(progress.last(), Some(SectorPlottingProgress::Error { .. }));
+}
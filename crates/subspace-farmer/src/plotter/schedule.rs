@@ -0,0 +1,271 @@
+//! Plotting schedule and throttling
+//!
+//! Plotting competes with farming (and with anything else running on the machine) for CPU, memory
+//! and disk I/O. [`PlottingSchedule`] restricts plotting to configured time windows of the day,
+//! while [`AuditThrottle`] pauses plotting when farming audits take long enough to put farming
+//! deadline at risk. Both are applied to [`PoolPlotter`] as separate [`PauseReason`]s, such that
+//! they don't interfere with each other or with plotting paused manually.
+
+#[cfg(test)]
+mod tests;
+
+use crate::plotter::pool::{PauseReason, PoolPlotter};
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+const SECONDS_IN_DAY: u32 = 24 * 60 * 60;
+/// Schedule is re-checked at least this often in case system time changes
+const MAX_SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Plotting is paused when auditing takes this fraction of farming timeout or more
+pub const AUDIT_THROTTLE_PAUSE_RATIO: f64 = 0.5;
+/// Plotting is resumed when auditing takes less than this fraction of farming timeout
+pub const AUDIT_THROTTLE_RESUME_RATIO: f64 = 0.25;
+
+/// Errors happening when parsing plotting window
+#[derive(Debug, Error)]
+pub enum PlottingWindowParseError {
+    /// Invalid format of plotting window
+    #[error("Plotting window must be in `HH:MM-HH:MM` format, got `{0}`")]
+    InvalidFormat(String),
+    /// Invalid time of day
+    #[error("Invalid time of day `{0}`, expected `HH:MM` between 00:00 and 23:59")]
+    InvalidTime(String),
+}
+
+/// Time window of the day during which plotting is allowed, times are in UTC.
+///
+/// Window that ends before it starts wraps around midnight (like `22:00-06:00`), window that
+/// starts and ends at the same time covers the whole day.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PlottingWindow {
+    /// Seconds since midnight
+    start: u32,
+    /// Seconds since midnight
+    end: u32,
+}
+
+impl fmt::Display for PlottingWindow {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 3600,
+            self.start % 3600 / 60,
+            self.end / 3600,
+            self.end % 3600 / 60
+        )
+    }
+}
+
+impl FromStr for PlottingWindow {
+    type Err = PlottingWindowParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| PlottingWindowParseError::InvalidFormat(s.to_string()))?;
+
+        Ok(Self {
+            start: parse_time_of_day(start.trim())?,
+            end: parse_time_of_day(end.trim())?,
+        })
+    }
+}
+
+impl PlottingWindow {
+    fn contains(&self, second_of_day: u32) -> bool {
+        if self.start == self.end {
+            true
+        } else if self.start < self.end {
+            (self.start..self.end).contains(&second_of_day)
+        } else {
+            second_of_day >= self.start || second_of_day < self.end
+        }
+    }
+}
+
+fn parse_time_of_day(s: &str) -> Result<u32, PlottingWindowParseError> {
+    let invalid_time = || PlottingWindowParseError::InvalidTime(s.to_string());
+
+    let (hours, minutes) = s.split_once(':').ok_or_else(invalid_time)?;
+    let hours = hours.parse::<u32>().map_err(|_error| invalid_time())?;
+    let minutes = minutes.parse::<u32>().map_err(|_error| invalid_time())?;
+
+    if hours >= 24 || minutes >= 60 {
+        return Err(invalid_time());
+    }
+
+    Ok(hours * 3600 + minutes * 60)
+}
+
+/// Plotting schedule, consists of time windows during which plotting is allowed.
+///
+/// Empty schedule allows plotting at any time.
+#[derive(Debug, Default, Clone)]
+pub struct PlottingSchedule {
+    windows: Vec<PlottingWindow>,
+}
+
+impl PlottingSchedule {
+    /// Create new instance from plotting windows
+    pub fn new(windows: Vec<PlottingWindow>) -> Self {
+        Self { windows }
+    }
+
+    /// Whether schedule has no windows and allows plotting at any time
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Whether plotting is allowed at specified time
+    pub fn allows_plotting_at(&self, time: SystemTime) -> bool {
+        self.allows_plotting_at_second_of_day(second_of_day(time))
+    }
+
+    /// Time after `time` at which plotting allowance changes next, `None` if it never changes
+    pub fn next_change(&self, time: SystemTime) -> Option<Duration> {
+        let second_of_day = second_of_day(time);
+        let allows_plotting = self.allows_plotting_at_second_of_day(second_of_day);
+
+        let mut boundaries = self
+            .windows
+            .iter()
+            .filter(|window| window.start != window.end)
+            .flat_map(|window| [window.start, window.end])
+            .map(
+                |boundary| match (boundary + SECONDS_IN_DAY - second_of_day) % SECONDS_IN_DAY {
+                    0 => SECONDS_IN_DAY,
+                    seconds => seconds,
+                },
+            )
+            .collect::<Vec<_>>();
+        boundaries.sort_unstable();
+
+        boundaries
+            .into_iter()
+            .find(|&seconds| {
+                self.allows_plotting_at_second_of_day((second_of_day + seconds) % SECONDS_IN_DAY)
+                    != allows_plotting
+            })
+            .map(|seconds| Duration::from_secs(u64::from(seconds)))
+    }
+
+    /// Keep plotting of pool plotter paused outside of schedule.
+    ///
+    /// Pause state is applied immediately, returned future keeps it up to date and never resolves.
+    pub fn run(self, plotter: Arc<PoolPlotter>) -> impl Future<Output = ()> {
+        let next_check = self.apply(&plotter);
+
+        async move {
+            let mut next_check = next_check;
+
+            loop {
+                tokio::time::sleep(next_check).await;
+                next_check = self.apply(&plotter);
+            }
+        }
+    }
+
+    /// Apply schedule to plotter at current time, returns time until the next check
+    fn apply(&self, plotter: &PoolPlotter) -> Duration {
+        let now = SystemTime::now();
+        let allows_plotting = self.allows_plotting_at(now);
+
+        debug!(%allows_plotting, "Applying plotting schedule");
+        plotter.set_paused(PauseReason::Schedule, !allows_plotting);
+
+        self.next_change(now)
+            .unwrap_or(MAX_SCHEDULE_CHECK_INTERVAL)
+            .min(MAX_SCHEDULE_CHECK_INTERVAL)
+    }
+
+    fn allows_plotting_at_second_of_day(&self, second_of_day: u32) -> bool {
+        self.windows.is_empty()
+            || self
+                .windows
+                .iter()
+                .any(|window| window.contains(second_of_day))
+    }
+}
+
+fn second_of_day(time: SystemTime) -> u32 {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    (seconds % u64::from(SECONDS_IN_DAY)) as u32
+}
+
+/// Pauses plotting when farming audits approach farming deadline.
+///
+/// Plotting is paused as soon as auditing of any farm takes [`AUDIT_THROTTLE_PAUSE_RATIO`] of
+/// farming timeout or more and resumed once audits of all such farms take less than
+/// [`AUDIT_THROTTLE_RESUME_RATIO`] of farming timeout again.
+#[derive(Debug)]
+pub struct AuditThrottle {
+    plotter: Arc<PoolPlotter>,
+    farming_timeout: Duration,
+    /// Indices of farms whose audits are too slow
+    slow_farms: Mutex<HashSet<usize>>,
+}
+
+impl AuditThrottle {
+    /// Create new instance
+    pub fn new(plotter: Arc<PoolPlotter>, farming_timeout: Duration) -> Self {
+        Self {
+            plotter,
+            farming_timeout,
+            slow_farms: Mutex::default(),
+        }
+    }
+
+    /// Observe time it took to audit the farm
+    pub fn observe_audit(&self, farm_index: usize, time: Duration) {
+        let mut slow_farms = self.slow_farms.lock();
+
+        if time >= self.farming_timeout.mul_f64(AUDIT_THROTTLE_PAUSE_RATIO) {
+            if slow_farms.insert(farm_index) {
+                warn!(
+                    %farm_index,
+                    ?time,
+                    farming_timeout = ?self.farming_timeout,
+                    "Auditing is approaching farming deadline, throttling plotting"
+                );
+            }
+        } else if time < self.farming_timeout.mul_f64(AUDIT_THROTTLE_RESUME_RATIO)
+            && slow_farms.remove(&farm_index)
+        {
+            debug!(%farm_index, ?time, "Auditing is fast again");
+        }
+
+        // Updated under lock so concurrent observations of different farms are applied in order
+        self.plotter
+            .set_paused(PauseReason::Throttling, !slow_farms.is_empty());
+    }
+
+    /// Stop tracking farm (for example, when it was detached)
+    pub fn remove_farm(&self, farm_index: usize) {
+        let mut slow_farms = self.slow_farms.lock();
+
+        if slow_farms.remove(&farm_index) {
+            self.plotter
+                .set_paused(PauseReason::Throttling, !slow_farms.is_empty());
+        }
+    }
+
+    /// Indices of farms whose audits are currently too slow
+    pub fn slow_farms(&self) -> Vec<usize> {
+        let mut slow_farms = self.slow_farms.lock().iter().copied().collect::<Vec<_>>();
+        slow_farms.sort_unstable();
+        slow_farms
+    }
+}
@@ -0,0 +1,105 @@
+use crate::plotter::pool::{PauseReason, PoolPlotter};
+use crate::plotter::schedule::{AuditThrottle, PlottingSchedule, PlottingWindow};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn at(hours: u64, minutes: u64) -> SystemTime {
+    // Some day far from Unix epoch
+    UNIX_EPOCH + Duration::from_secs(20_000 * 86_400 + hours * 3600 + minutes * 60)
+}
+
+#[test]
+fn plotting_window_parsing() {
+    let window = "22:00-06:30".parse::<PlottingWindow>().unwrap();
+    assert_eq!(window.to_string(), "22:00-06:30");
+    assert_eq!(
+        "9:05 - 17:00"
+            .parse::<PlottingWindow>()
+            .unwrap()
+            .to_string(),
+        "09:05-17:00"
+    );
+
+    assert!("22:00".parse::<PlottingWindow>().is_err());
+    assert!("24:00-06:00".parse::<PlottingWindow>().is_err());
+    assert!("22:60-06:00".parse::<PlottingWindow>().is_err());
+    assert!("22-06".parse::<PlottingWindow>().is_err());
+}
+
+#[test]
+fn plotting_schedule() {
+    assert!(PlottingSchedule::default().allows_plotting_at(at(12, 0)));
+    assert_eq!(PlottingSchedule::default().next_change(at(12, 0)), None);
+
+    let whole_day = PlottingSchedule::new(vec!["10:00-10:00".parse().unwrap()]);
+    assert!(whole_day.allows_plotting_at(at(3, 0)));
+    assert_eq!(whole_day.next_change(at(3, 0)), None);
+
+    // Night and lunch break
+    let schedule = PlottingSchedule::new(vec![
+        "22:00-06:00".parse().unwrap(),
+        "12:00-13:00".parse().unwrap(),
+    ]);
+
+    assert!(schedule.allows_plotting_at(at(23, 0)));
+    assert!(schedule.allows_plotting_at(at(0, 0)));
+    assert!(schedule.allows_plotting_at(at(5, 59)));
+    assert!(!schedule.allows_plotting_at(at(6, 0)));
+    assert!(!schedule.allows_plotting_at(at(11, 59)));
+    assert!(schedule.allows_plotting_at(at(12, 30)));
+    assert!(!schedule.allows_plotting_at(at(13, 0)));
+    assert!(!schedule.allows_plotting_at(at(21, 59)));
+
+    assert_eq!(
+        schedule.next_change(at(5, 0)),
+        Some(Duration::from_secs(3600))
+    );
+    assert_eq!(
+        schedule.next_change(at(6, 0)),
+        Some(Duration::from_secs(6 * 3600))
+    );
+    assert_eq!(
+        schedule.next_change(at(13, 0)),
+        Some(Duration::from_secs(9 * 3600))
+    );
+
+    // Adjacent windows don't cause a change at the boundary between them
+    let schedule = PlottingSchedule::new(vec![
+        "08:00-12:00".parse().unwrap(),
+        "12:00-18:00".parse().unwrap(),
+    ]);
+    assert_eq!(
+        schedule.next_change(at(9, 0)),
+        Some(Duration::from_secs(9 * 3600))
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn audit_throttle() {
+    let plotter = Arc::new(PoolPlotter::new(Vec::new(), Duration::from_secs(1)));
+    let audit_throttle = AuditThrottle::new(Arc::clone(&plotter), Duration::from_secs(4));
+
+    audit_throttle.observe_audit(0, Duration::from_millis(500));
+    assert!(!plotter.is_paused());
+
+    audit_throttle.observe_audit(0, Duration::from_secs(2));
+    audit_throttle.observe_audit(1, Duration::from_secs(3));
+    assert_eq!(plotter.pause_reasons(), vec![PauseReason::Throttling]);
+    assert_eq!(audit_throttle.slow_farms(), vec![0, 1]);
+
+    // Still above resume threshold
+    audit_throttle.observe_audit(0, Duration::from_millis(1500));
+    assert!(plotter.is_paused());
+
+    audit_throttle.observe_audit(0, Duration::from_millis(500));
+    assert_eq!(audit_throttle.slow_farms(), vec![1]);
+    assert!(plotter.is_paused());
+
+    // Manual pause is independent of throttling
+    plotter.pause();
+    audit_throttle.remove_farm(1);
+    assert_eq!(plotter.pause_reasons(), vec![PauseReason::Manual]);
+
+    plotter.resume();
+    assert!(!plotter.is_paused());
+}
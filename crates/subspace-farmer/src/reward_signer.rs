@@ -0,0 +1,117 @@
+//! Reward signer abstraction
+//!
+//! Reward signing is abstracted away so that secret keys don't necessarily need to be present in
+//! the farmer process. [`LocalRewardSigner`] signs with identities loaded into the current process,
+//! while [`unix_socket::UnixSocketRewardSigner`] forwards signing requests to a separate signer
+//! process.
+
+#[cfg(test)]
+mod tests;
+#[cfg(unix)]
+pub mod unix_socket;
+
+use crate::single_disk_farm::identity::Identity;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use subspace_core_primitives::{PublicKey, RewardSignature};
+use thiserror::Error;
+
+/// Errors happening during reward signing
+#[derive(Debug, Error)]
+pub enum RewardSignerError {
+    /// I/O error occurred
+    #[error("Reward signer I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Decoding error
+    #[error("Decoding error: {0}")]
+    Decoding(#[from] parity_scale_codec::Error),
+    /// Message is too large
+    #[error("Message of {size} bytes is too large")]
+    MessageTooLarge {
+        /// Message size
+        size: usize,
+    },
+    /// Request timed out
+    #[error("Request timed out")]
+    Timeout,
+    /// Remote signer returned an error
+    #[error("Remote signer error: {0}")]
+    Remote(String),
+}
+
+/// Abstract reward signer implementation
+#[async_trait]
+pub trait RewardSigner: fmt::Debug + Send + Sync {
+    /// Sign reward hash with the secret key that corresponds to `public_key`.
+    ///
+    /// Signature is always created with
+    /// [`REWARD_SIGNING_CONTEXT`](subspace_core_primitives::REWARD_SIGNING_CONTEXT) signing
+    /// context, such that it is only valid as reward signature and signer can't be used to sign
+    /// arbitrary messages (like transactions) with farmer's key.
+    ///
+    /// Returns `Ok(None)` if signer doesn't have the key corresponding to `public_key`.
+    async fn sign_reward_hash(
+        &self,
+        public_key: &PublicKey,
+        hash: &[u8; 32],
+    ) -> Result<Option<RewardSignature>, RewardSignerError>;
+}
+
+#[async_trait]
+impl<T> RewardSigner for Arc<T>
+where
+    T: RewardSigner + ?Sized,
+{
+    #[inline]
+    async fn sign_reward_hash(
+        &self,
+        public_key: &PublicKey,
+        hash: &[u8; 32],
+    ) -> Result<Option<RewardSignature>, RewardSignerError> {
+        self.as_ref().sign_reward_hash(public_key, hash).await
+    }
+}
+
+/// Reward signer that signs with identities loaded into the current process
+#[derive(Debug, Default, Clone)]
+pub struct LocalRewardSigner {
+    identities: Arc<HashMap<PublicKey, Identity>>,
+}
+
+#[async_trait]
+impl RewardSigner for LocalRewardSigner {
+    async fn sign_reward_hash(
+        &self,
+        public_key: &PublicKey,
+        hash: &[u8; 32],
+    ) -> Result<Option<RewardSignature>, RewardSignerError> {
+        Ok(self
+            .identities
+            .get(public_key)
+            .map(|identity| identity.sign_reward_hash(hash).to_bytes().into()))
+    }
+}
+
+impl LocalRewardSigner {
+    /// Create new instance with provided identities
+    pub fn new<I>(identities: I) -> Self
+    where
+        I: IntoIterator<Item = Identity>,
+    {
+        let identities = identities
+            .into_iter()
+            .map(|identity| (identity.public_key().to_bytes().into(), identity))
+            .collect();
+
+        Self {
+            identities: Arc::new(identities),
+        }
+    }
+
+    /// Public keys of identities this signer can sign with
+    pub fn public_keys(&self) -> impl Iterator<Item = &PublicKey> + '_ {
+        self.identities.keys()
+    }
+}
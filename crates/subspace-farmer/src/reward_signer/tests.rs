@@ -0,0 +1,104 @@
+use crate::reward_signer::{LocalRewardSigner, RewardSigner};
+use crate::single_disk_farm::identity::Identity;
+use schnorrkel::Signature;
+use subspace_core_primitives::{PublicKey, REWARD_SIGNING_CONTEXT};
+use tempfile::tempdir;
+
+fn verify(public_key: &PublicKey, hash: &[u8; 32], signature: &[u8]) -> bool {
+    schnorrkel::PublicKey::from_bytes(public_key.as_ref())
+        .unwrap()
+        .verify(
+            schnorrkel::context::signing_context(REWARD_SIGNING_CONTEXT).bytes(hash),
+            &Signature::from_bytes(signature).unwrap(),
+        )
+        .is_ok()
+}
+
+#[tokio::test]
+async fn local() {
+    let path = tempdir().unwrap();
+    let identity = Identity::create(path.as_ref(), None).unwrap();
+    let public_key = PublicKey::from(identity.public_key().to_bytes());
+    let hash = [1; 32];
+
+    let reward_signer = LocalRewardSigner::new([identity]);
+
+    let signature = reward_signer
+        .sign_reward_hash(&public_key, &hash)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(verify(&public_key, &hash, signature.as_ref()));
+
+    // Signature is only valid in reward signing context
+    assert!(schnorrkel::PublicKey::from_bytes(public_key.as_ref())
+        .unwrap()
+        .verify(
+            schnorrkel::context::signing_context(b"substrate").bytes(&hash),
+            &Signature::from_bytes(signature.as_ref()).unwrap(),
+        )
+        .is_err());
+
+    // Unknown public key
+    assert!(reward_signer
+        .sign_reward_hash(&PublicKey::from([0; 32]), &hash)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket() {
+    use crate::reward_signer::unix_socket::{serve, UnixSocketRewardSigner};
+    use tokio::net::UnixListener;
+
+    let path = tempdir().unwrap();
+    let identity = Identity::create(path.as_ref(), None).unwrap();
+    let public_key = PublicKey::from(identity.public_key().to_bytes());
+    let hash = [2; 32];
+
+    let socket_path = path.path().join("signer.sock");
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    let server = tokio::spawn(serve(listener, LocalRewardSigner::new([identity])));
+
+    let reward_signer = UnixSocketRewardSigner::new(&socket_path);
+
+    let signature = reward_signer
+        .sign_reward_hash(&public_key, &hash)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(verify(&public_key, &hash, signature.as_ref()));
+
+    // Unknown public key
+    assert!(reward_signer
+        .sign_reward_hash(&PublicKey::from([0; 32]), &hash)
+        .await
+        .unwrap()
+        .is_none());
+
+    server.abort();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket_rejects_large_requests() {
+    use crate::reward_signer::unix_socket::serve;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{UnixListener, UnixStream};
+
+    let path = tempdir().unwrap();
+    let socket_path = path.path().join("signer.sock");
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    let server = tokio::spawn(serve(listener, LocalRewardSigner::default()));
+
+    let mut stream = UnixStream::connect(&socket_path).await.unwrap();
+    stream.write_all(&1_000_000_u32.to_le_bytes()).await.unwrap();
+
+    // Server closes connection without reading the request
+    let mut buffer = Vec::new();
+    assert_eq!(stream.read_to_end(&mut buffer).await.unwrap(), 0);
+
+    server.abort();
+}
@@ -0,0 +1,198 @@
+//! Reward signer that forwards signing requests to a separate process over Unix socket.
+//!
+//! Each message is a SCALE-encoded request or response prefixed with its length as little-endian
+//! `u32`. [`UnixSocketRewardSigner`] is the client side, [`serve()`] is the server side that can be
+//! used with any other [`RewardSigner`] implementation (typically [`LocalRewardSigner`]).
+//!
+//! [`LocalRewardSigner`]: super::LocalRewardSigner
+
+use crate::reward_signer::{RewardSigner, RewardSignerError};
+use async_trait::async_trait;
+use parity_scale_codec::{Decode, Encode};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use subspace_core_primitives::{PublicKey, RewardSignature};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tracing::{debug, warn};
+
+/// Max size of the request or response message, actual messages are much smaller
+const MAX_MESSAGE_SIZE: usize = 1024;
+/// Reward signature must be submitted quickly, there is no point waiting for remote signer longer
+/// than this
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Server closes connections that didn't send the next request within this time
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Encode, Decode)]
+enum RewardSignerRequest {
+    SignRewardHash {
+        public_key: PublicKey,
+        hash: [u8; 32],
+    },
+}
+
+#[derive(Debug, Encode, Decode)]
+enum RewardSignerResponse {
+    Signature(Option<RewardSignature>),
+    Error(String),
+}
+
+async fn write_message<S, M>(stream: &mut S, message: &M) -> Result<(), RewardSignerError>
+where
+    S: AsyncWrite + Unpin,
+    M: Encode,
+{
+    let bytes = message.encode();
+    if bytes.len() > MAX_MESSAGE_SIZE {
+        return Err(RewardSignerError::MessageTooLarge { size: bytes.len() });
+    }
+
+    stream
+        .write_all(&(bytes.len() as u32).to_le_bytes())
+        .await?;
+    stream.write_all(&bytes).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+/// Returns `Ok(None)` if stream was closed before the next message
+async fn read_message<S, M>(stream: &mut S) -> Result<Option<M>, RewardSignerError>
+where
+    S: AsyncRead + Unpin,
+    M: Decode,
+{
+    let mut length = [0; 4];
+    match stream.read_exact(&mut length).await {
+        Ok(_) => {}
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+            return Ok(None);
+        }
+        Err(error) => {
+            return Err(error.into());
+        }
+    }
+
+    let size = u32::from_le_bytes(length) as usize;
+    if size > MAX_MESSAGE_SIZE {
+        return Err(RewardSignerError::MessageTooLarge { size });
+    }
+
+    let mut bytes = vec![0; size];
+    stream.read_exact(&mut bytes).await?;
+
+    Ok(Some(M::decode(&mut bytes.as_slice())?))
+}
+
+/// Reward signer that forwards signing requests to remote signer listening on Unix socket.
+///
+/// New connection is established for every request, so remote signer can be restarted at any
+/// time.
+#[derive(Debug, Clone)]
+pub struct UnixSocketRewardSigner {
+    path: PathBuf,
+}
+
+#[async_trait]
+impl RewardSigner for UnixSocketRewardSigner {
+    async fn sign_reward_hash(
+        &self,
+        public_key: &PublicKey,
+        hash: &[u8; 32],
+    ) -> Result<Option<RewardSignature>, RewardSignerError> {
+        let request = RewardSignerRequest::SignRewardHash {
+            public_key: *public_key,
+            hash: *hash,
+        };
+
+        let response = tokio::time::timeout(REQUEST_TIMEOUT, self.send_request(&request))
+            .await
+            .map_err(|_error| RewardSignerError::Timeout)??;
+
+        match response {
+            RewardSignerResponse::Signature(maybe_signature) => Ok(maybe_signature),
+            RewardSignerResponse::Error(error) => Err(RewardSignerError::Remote(error)),
+        }
+    }
+}
+
+impl UnixSocketRewardSigner {
+    /// Create new instance that will connect to remote signer at `path`
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    async fn send_request(
+        &self,
+        request: &RewardSignerRequest,
+    ) -> Result<RewardSignerResponse, RewardSignerError> {
+        let mut stream = UnixStream::connect(&self.path).await?;
+        write_message(&mut stream, request).await?;
+
+        read_message(&mut stream).await?.ok_or_else(|| {
+            RewardSignerError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Remote signer closed connection without response",
+            ))
+        })
+    }
+}
+
+/// Serve signing requests received on `listener` with `reward_signer`.
+///
+/// Connections that send requests larger than 1 KiB or don't send the next request within
+/// 10 seconds are closed. Runs until accepting new connections fails.
+pub async fn serve<RS>(listener: UnixListener, reward_signer: RS) -> io::Result<()>
+where
+    RS: RewardSigner + Clone + 'static,
+{
+    loop {
+        let (mut stream, _address) = listener.accept().await?;
+        let reward_signer = reward_signer.clone();
+
+        tokio::spawn(async move {
+            if let Err(error) = handle_connection(&mut stream, &reward_signer).await {
+                warn!(%error, "Failed to handle reward signer connection");
+            }
+        });
+    }
+}
+
+async fn handle_connection<RS>(
+    stream: &mut UnixStream,
+    reward_signer: &RS,
+) -> Result<(), RewardSignerError>
+where
+    RS: RewardSigner,
+{
+    loop {
+        let maybe_request = tokio::time::timeout(
+            REQUEST_READ_TIMEOUT,
+            read_message::<_, RewardSignerRequest>(stream),
+        )
+        .await
+        .map_err(|_error| RewardSignerError::Timeout)??;
+        let Some(request) = maybe_request else {
+            break;
+        };
+
+        let response = match request {
+            RewardSignerRequest::SignRewardHash { public_key, hash } => {
+                debug!(%public_key, hash = %hex::encode(hash), "Signing reward hash");
+
+                match reward_signer.sign_reward_hash(&public_key, &hash).await {
+                    Ok(maybe_signature) => RewardSignerResponse::Signature(maybe_signature),
+                    Err(error) => RewardSignerResponse::Error(error.to_string()),
+                }
+            }
+        };
+
+        write_message(stream, &response).await?;
+    }
+
+    Ok(())
+}
@@ -13,20 +13,25 @@ pub mod plot_cache;
 mod plotted_sectors;
 mod plotting;
 mod reward_signing;
+#[cfg(test)]
+mod tests;
 pub mod unbuffered_io_file_windows;
 
 use crate::disk_piece_cache::{DiskPieceCache, DiskPieceCacheError};
 use crate::farm::{
-    Farm, FarmId, FarmingError, FarmingNotification, HandlerFn, PieceCacheId, PieceReader,
-    PlottedSectors, SectorUpdate,
+    Farm, FarmId, FarmingError, FarmingNotification, HandlerFn, PieceCacheId, PieceCacheOffset,
+    PieceReader, PlottedSectors, SectorUpdate,
 };
 use crate::node_client::NodeClient;
 use crate::plotter::Plotter;
+use crate::reward_signer::{LocalRewardSigner, RewardSigner};
 use crate::single_disk_farm::farming::rayon_files::RayonFiles;
 use crate::single_disk_farm::farming::{
     farming, slot_notification_forwarder, FarmingOptions, PlotAudit,
 };
-use crate::single_disk_farm::identity::{Identity, IdentityError};
+use crate::single_disk_farm::identity::{
+    Identity, IdentityDerivationPath, IdentityError, IdentityPassphrase, MasterIdentity,
+};
 use crate::single_disk_farm::metrics::SingleDiskFarmMetrics;
 use crate::single_disk_farm::piece_cache::SingleDiskPieceCache;
 use crate::single_disk_farm::piece_reader::DiskPieceReader;
@@ -34,7 +39,8 @@ use crate::single_disk_farm::plot_cache::DiskPlotCache;
 use crate::single_disk_farm::plotted_sectors::SingleDiskPlottedSectors;
 pub use crate::single_disk_farm::plotting::PlottingError;
 use crate::single_disk_farm::plotting::{
-    plotting, plotting_scheduler, PlottingOptions, PlottingSchedulerOptions, SectorPlottingOptions,
+    plotting, plotting_scheduler, replot_sectors, PlottingOptions, PlottingSchedulerOptions,
+    SectorPlottingOptions,
 };
 use crate::single_disk_farm::reward_signing::reward_signing;
 #[cfg(windows)]
@@ -60,7 +66,7 @@ use std::collections::HashSet;
 use std::error::Error;
 use std::fs::{File, OpenOptions};
 use std::future::Future;
-use std::io::Write;
+use std::io::{Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::str::FromStr;
@@ -71,7 +77,7 @@ use std::{fmt, fs, io, mem};
 use subspace_core_primitives::crypto::kzg::Kzg;
 use subspace_core_primitives::crypto::{blake3_hash, Scalar};
 use subspace_core_primitives::{
-    Blake3Hash, HistorySize, PublicKey, Record, SectorIndex, SegmentIndex,
+    Blake3Hash, HistorySize, PieceIndex, PublicKey, Record, SectorIndex, SegmentIndex,
 };
 use subspace_erasure_coding::ErasureCoding;
 use subspace_farmer_components::file_ext::FileExt;
@@ -79,7 +85,7 @@ use subspace_farmer_components::file_ext::FileExt;
 use subspace_farmer_components::file_ext::OpenOptionsExt;
 use subspace_farmer_components::reading::ReadSectorRecordChunksMode;
 use subspace_farmer_components::sector::{sector_size, SectorMetadata, SectorMetadataChecksummed};
-use subspace_farmer_components::{FarmerProtocolInfo, ReadAtSync};
+use subspace_farmer_components::{FarmerProtocolInfo, PieceGetter, ReadAtSync};
 use subspace_networking::KnownPeersManager;
 use subspace_proof_of_space::Table;
 use subspace_rpc_primitives::{FarmerAppInfo, SolutionResponse};
@@ -97,6 +103,9 @@ const_assert!(mem::size_of::<usize>() >= mem::size_of::<u64>());
 const RESERVED_PLOT_METADATA: u64 = 1024 * 1024;
 /// Reserve 1M of space for farm info (for potential future expansion)
 const RESERVED_FARM_INFO: u64 = 1024 * 1024;
+/// Piece bytes of corrupted cache elements are split into this many chunks in order to check
+/// whether piece index stored in the element can be trusted
+const CACHE_ELEMENT_PIECE_CHUNKS: usize = 16;
 const NEW_SEGMENT_PROCESSING_DELAY: Duration = Duration::from_secs(30);
 /// Limit for reads in internal benchmark.
 ///
@@ -107,7 +116,22 @@ const INTERNAL_BENCHMARK_READ_TIMEOUT: Duration = Duration::from_millis(3500);
 #[derive(Debug)]
 #[must_use = "Lock file must be kept around or as long as farm is used"]
 pub struct SingleDiskFarmInfoLock {
-    _file: File,
+    file: File,
+}
+
+impl SingleDiskFarmInfoLock {
+    /// Store `SingleDiskFarm` info through the locked file, such that it is updated without
+    /// releasing the lock
+    pub fn store(&self, single_disk_farm_info: &SingleDiskFarmInfo) -> io::Result<()> {
+        let mut file = &self.file;
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(
+            &serde_json::to_vec(single_disk_farm_info)
+                .expect("Info serialization never fails; qed"),
+        )?;
+        file.sync_data()
+    }
 }
 
 /// Important information about the contents of the `SingleDiskFarm`
@@ -128,6 +152,10 @@ pub enum SingleDiskFarmInfo {
         pieces_in_sector: u16,
         /// How much space in bytes is allocated for this farm
         allocated_space: u64,
+        /// Path used to derive identity of the farm from master identity, `None` if identity was
+        /// generated randomly
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        identity_derivation_path: Option<IdentityDerivationPath>,
     },
 }
 
@@ -141,6 +169,7 @@ impl SingleDiskFarmInfo {
         public_key: PublicKey,
         pieces_in_sector: u16,
         allocated_space: u64,
+        identity_derivation_path: Option<IdentityDerivationPath>,
     ) -> Self {
         Self::V0 {
             id,
@@ -148,6 +177,7 @@ impl SingleDiskFarmInfo {
             public_key,
             pieces_in_sector,
             allocated_space,
+            identity_derivation_path,
         }
     }
 
@@ -184,10 +214,14 @@ impl SingleDiskFarmInfo {
     /// Try to acquire exclusive lock on the single disk farm info file, ensuring no concurrent edits by cooperating
     /// processes is done
     pub fn try_lock(directory: &Path) -> io::Result<SingleDiskFarmInfoLock> {
-        let file = File::open(directory.join(Self::FILE_NAME))?;
+        // Opened for writing too, such that info can be updated while the lock is held
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(directory.join(Self::FILE_NAME))?;
         fs4::FileExt::try_lock_exclusive(&file)?;
 
-        Ok(SingleDiskFarmInfoLock { _file: file })
+        Ok(SingleDiskFarmInfoLock { file })
     }
 
     /// ID of the farm
@@ -225,6 +259,16 @@ impl SingleDiskFarmInfo {
             } => *allocated_space,
         }
     }
+
+    /// Path used to derive identity of the farm from master identity, `None` if identity was
+    /// generated randomly
+    pub fn identity_derivation_path(&self) -> Option<&IdentityDerivationPath> {
+        let Self::V0 {
+            identity_derivation_path,
+            ..
+        } = self;
+        identity_derivation_path.as_ref()
+    }
 }
 
 /// Summary of single disk farm for presentational purposes
@@ -318,6 +362,18 @@ where
     pub registry: Option<&'a Mutex<&'a mut Registry>>,
     /// Whether to create a farm if it doesn't yet exist
     pub create: bool,
+    /// Passphrase used to decrypt identity, identity stored in plain text will be encrypted with it
+    /// and newly created identity will be encrypted with it
+    pub identity_passphrase: Option<IdentityPassphrase>,
+    /// Reward signer to use instead of signing with farm's identity in the current process.
+    ///
+    /// When provided, identity is not decrypted and only its public key is read.
+    pub reward_signer: Option<Arc<dyn RewardSigner>>,
+    /// Master identity to derive identity from when farm is created instead of generating random
+    /// identity, derivation path is recorded in [`SingleDiskFarmInfo`].
+    ///
+    /// Also used to restore identity of existing farm if identity file was lost.
+    pub master_identity: Option<MasterIdentity>,
 }
 
 /// Errors happening when trying to create/open single disk farm
@@ -540,6 +596,53 @@ pub enum SingleDiskFarmScrubError {
         /// Low-level error
         error: io::Error,
     },
+    /// Piece cache error
+    #[error("Piece cache error: {0}")]
+    PieceCacheError(#[from] DiskPieceCacheError),
+    /// Failed to read sectors metadata
+    #[error("Failed to read sectors metadata: {0}")]
+    FailedToReadSectorsMetadata(io::Error),
+    /// I/O error occurred
+    #[error("Scrub I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// Plotting error
+    #[error("Plotting error: {0}")]
+    Plotting(#[from] PlottingError),
+}
+
+/// Errors happening during farm migration
+#[derive(Debug, Error)]
+pub enum SingleDiskFarmMigrateError {
+    /// Farm is likely already in use, make sure no other farmer is using it
+    #[error("Farm is likely already in use, make sure no other farmer is using it: {0}")]
+    LikelyAlreadyInUse(io::Error),
+    /// I/O error occurred
+    #[error("Farm migration I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// Farm info file does not exist
+    #[error("Farm info file does not exist at {file}")]
+    FarmInfoFileDoesNotExist {
+        /// Info file
+        file: PathBuf,
+    },
+    /// Destination already contains a different farm
+    #[error("Destination {directory} already contains a different farm")]
+    DestinationNotEmpty {
+        /// Destination directory
+        directory: PathBuf,
+    },
+    /// Failed to decode metadata header
+    #[error("Failed to decode metadata header: {0}")]
+    FailedToDecodeMetadataHeader(parity_scale_codec::Error),
+    /// Unexpected metadata version
+    #[error("Unexpected metadata version {0}")]
+    UnexpectedMetadataVersion(u8),
+    /// Single disk farm error
+    #[error(transparent)]
+    SingleDiskFarm(#[from] SingleDiskFarmError),
+    /// Piece cache error
+    #[error("Piece cache error: {0}")]
+    PieceCache(#[from] DiskPieceCacheError),
 }
 
 /// Errors that happen in background tasks
@@ -625,6 +728,68 @@ impl ScrubTarget {
     }
 }
 
+/// Problem with plotted sector found during scrubbing
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScrubSectorProblem {
+    /// Sector metadata can't be read
+    MetadataUnreadable,
+    /// Sector metadata can't be decoded
+    MetadataCorrupted,
+    /// Sector index in metadata doesn't match sector location
+    SectorIndexMismatch,
+    /// Number of pieces in sector in metadata doesn't match farm
+    PiecesInSectorMismatch,
+    /// Sector contents don't match checksum
+    ChecksumMismatch,
+}
+
+/// Corrupted sector found during scrubbing
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrubbedSector {
+    /// Sector index
+    pub sector_index: SectorIndex,
+    /// What is wrong with the sector
+    pub problem: ScrubSectorProblem,
+    /// Whether sector was replaced with dummy expired sector
+    pub replaced: bool,
+    /// Whether sector was plotted again
+    pub replotted: bool,
+}
+
+/// Corrupted piece cache element found during scrubbing
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrubbedCacheElement {
+    /// Offset of the element in piece cache
+    pub offset: u32,
+    /// Piece index stored in the element, if it could be read, might be corrupted too
+    pub piece_index: Option<PieceIndex>,
+    /// Whether element was replaced with empty element
+    pub replaced: bool,
+    /// Whether piece was downloaded again and written back to the cache
+    pub refetched: bool,
+    /// Hashes of chunks of piece bytes that were stored in the element, used to check whether
+    /// piece index can be trusted
+    #[serde(skip)]
+    piece_chunk_hashes: Vec<Blake3Hash>,
+}
+
+/// Report of what was found and fixed during scrubbing
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrubReport {
+    /// Whether scrubbing was done without writing changes to disk
+    pub dry_run: bool,
+    /// Number of plotted sectors after scrubbing, `None` if sectors were not checked
+    pub plotted_sector_count: Option<SectorIndex>,
+    /// Corrupted sectors
+    pub corrupted_sectors: Vec<ScrubbedSector>,
+    /// Corrupted piece cache elements
+    pub corrupted_cache_elements: Vec<ScrubbedCacheElement>,
+}
+
 struct AllocatedSpaceDistribution {
     piece_cache_file_size: u64,
     piece_cache_capacity: u32,
@@ -727,7 +892,9 @@ struct Handlers {
 }
 
 struct SingleDiskFarmInit {
-    identity: Identity,
+    /// Decrypted identity, `None` if only public key was read because external reward signer is used
+    identity: Option<Identity>,
+    public_key: PublicKey,
     single_disk_farm_info: SingleDiskFarmInfo,
     single_disk_farm_info_lock: Option<SingleDiskFarmInfoLock>,
     #[cfg(not(windows))]
@@ -861,10 +1028,14 @@ impl SingleDiskFarm {
             faster_read_sector_record_chunks_mode_concurrency,
             registry,
             create,
+            identity_passphrase,
+            reward_signer,
+            master_identity,
         } = options;
 
         let single_disk_farm_init_fut = task::spawn_blocking({
             let directory = directory.clone();
+            let decrypt_identity = reward_signer.is_none();
             let farmer_app_info = farmer_app_info.clone();
             let span = span.clone();
 
@@ -878,6 +1049,9 @@ impl SingleDiskFarm {
                     cache_percentage,
                     disable_farm_locking,
                     create,
+                    identity_passphrase.as_ref(),
+                    decrypt_identity,
+                    master_identity.as_ref(),
                 )
             }
         });
@@ -887,6 +1061,7 @@ impl SingleDiskFarm {
 
         let SingleDiskFarmInit {
             identity,
+            public_key,
             single_disk_farm_info,
             single_disk_farm_info_lock,
             plot_file,
@@ -1241,7 +1416,10 @@ impl SingleDiskFarm {
         }));
 
         tasks.push(Box::pin(async move {
-            match reward_signing(node_client, identity).await {
+            let reward_signer =
+                reward_signer.unwrap_or_else(|| Arc::new(LocalRewardSigner::new(identity)));
+
+            match reward_signing(node_client, public_key, reward_signer).await {
                 Ok(reward_signing_fut) => {
                     reward_signing_fut.await;
                 }
@@ -1275,6 +1453,7 @@ impl SingleDiskFarm {
         Ok(farm)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn init(
         directory: &PathBuf,
         farmer_app_info: &FarmerAppInfo,
@@ -1283,23 +1462,70 @@ impl SingleDiskFarm {
         cache_percentage: u8,
         disable_farm_locking: bool,
         create: bool,
+        identity_passphrase: Option<&IdentityPassphrase>,
+        decrypt_identity: bool,
+        master_identity: Option<&MasterIdentity>,
     ) -> Result<SingleDiskFarmInit, SingleDiskFarmError> {
         fs::create_dir_all(directory)?;
 
-        let identity = if create {
-            Identity::open_or_create(directory)?
+        // Farm ID and identity derivation path of the farm that doesn't exist yet
+        let mut new_farm = None;
+
+        let maybe_public_key = if decrypt_identity {
+            None
         } else {
-            Identity::open(directory)?.ok_or_else(|| {
-                IdentityError::Io(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    "Farm does not exist and creation was explicitly disabled",
-                ))
-            })?
+            Identity::read_public_key(directory)?
+        };
+        let (identity, public_key) = match maybe_public_key {
+            Some(public_key) => (None, public_key.to_bytes().into()),
+            None => {
+                let identity = match Identity::open(directory, identity_passphrase)? {
+                    Some(identity) => identity,
+                    None if !create => {
+                        return Err(IdentityError::Io(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            "Farm does not exist and creation was explicitly disabled",
+                        ))
+                        .into());
+                    }
+                    None => match master_identity {
+                        Some(master_identity) => {
+                            let derivation_path = match SingleDiskFarmInfo::load_from(directory)? {
+                                Some(single_disk_farm_info) => {
+                                    // Farm exists, but identity file is missing, it can be restored
+                                    // if identity was derived originally
+                                    single_disk_farm_info.identity_derivation_path().copied()
+                                }
+                                None => {
+                                    let farm_id = FarmId::new();
+                                    let derivation_path = master_identity.derivation_path(farm_id);
+                                    new_farm.replace((farm_id, derivation_path));
+                                    Some(derivation_path)
+                                }
+                            };
+
+                            match derivation_path {
+                                Some(derivation_path) => {
+                                    info!(%derivation_path, "Deriving identity from master identity");
+                                    let identity =
+                                        master_identity.identity().derive(&derivation_path);
+                                    identity.store_to(directory, identity_passphrase)?;
+                                    identity
+                                }
+                                None => Identity::create(directory, identity_passphrase)?,
+                            }
+                        }
+                        None => Identity::create(directory, identity_passphrase)?,
+                    },
+                };
+                let public_key = identity.public_key().to_bytes().into();
+
+                (Some(identity), public_key)
+            }
         };
-        let public_key = identity.public_key().to_bytes().into();
 
-        let single_disk_farm_info = match SingleDiskFarmInfo::load_from(directory)? {
-            Some(mut single_disk_farm_info) => {
+        let mut single_disk_farm_info = match SingleDiskFarmInfo::load_from(directory)? {
+            Some(single_disk_farm_info) => {
                 if &farmer_app_info.genesis_hash != single_disk_farm_info.genesis_hash() {
                     return Err(SingleDiskFarmError::WrongChain {
                         id: *single_disk_farm_info.id(),
@@ -1335,34 +1561,20 @@ impl SingleDiskFarm {
                     );
                 }
 
-                if allocated_space != single_disk_farm_info.allocated_space() {
-                    info!(
-                        old_space = %bytesize::to_string(single_disk_farm_info.allocated_space(), true),
-                        new_space = %bytesize::to_string(allocated_space, true),
-                        "Farm size has changed"
-                    );
-
-                    let new_allocated_space = allocated_space;
-                    match &mut single_disk_farm_info {
-                        SingleDiskFarmInfo::V0 {
-                            allocated_space, ..
-                        } => {
-                            *allocated_space = new_allocated_space;
-                        }
-                    }
-
-                    single_disk_farm_info.store_to(directory)?;
-                }
-
                 single_disk_farm_info
             }
             None => {
+                let (farm_id, identity_derivation_path) = match new_farm {
+                    Some((farm_id, derivation_path)) => (farm_id, Some(derivation_path)),
+                    None => (FarmId::new(), None),
+                };
                 let single_disk_farm_info = SingleDiskFarmInfo::new(
-                    FarmId::new(),
+                    farm_id,
                     farmer_app_info.genesis_hash,
                     public_key,
                     max_pieces_in_sector,
                     allocated_space,
+                    identity_derivation_path,
                 );
 
                 single_disk_farm_info.store_to(directory)?;
@@ -1380,6 +1592,22 @@ impl SingleDiskFarm {
             )
         };
 
+        if allocated_space != single_disk_farm_info.allocated_space() {
+            info!(
+                old_space = %bytesize::to_string(single_disk_farm_info.allocated_space(), true),
+                new_space = %bytesize::to_string(allocated_space, true),
+                "Farm size has changed"
+            );
+
+            Self::resize_files(
+                directory,
+                &mut single_disk_farm_info,
+                allocated_space,
+                cache_percentage,
+                single_disk_farm_info_lock.as_ref(),
+            )?;
+        }
+
         let pieces_in_sector = single_disk_farm_info.pieces_in_sector();
         let sector_size = sector_size(pieces_in_sector) as u64;
         let sector_metadata_size = SectorMetadataChecksummed::encoded_size();
@@ -1529,6 +1757,7 @@ impl SingleDiskFarm {
 
         Ok(SingleDiskFarmInit {
             identity,
+            public_key,
             single_disk_farm_info,
             single_disk_farm_info_lock,
             plot_file,
@@ -1590,7 +1819,7 @@ impl SingleDiskFarm {
             }
         };
 
-        if Identity::open(directory)?.is_some() {
+        if Identity::read_public_key(directory)?.is_some() {
             effective_disk_usage += Identity::file_size() as u64;
         }
 
@@ -1826,20 +2055,200 @@ impl SingleDiskFarm {
         fs::remove_file(single_disk_info_info_path)
     }
 
+    /// Resize farm to `allocated_space` without wiping it.
+    ///
+    /// Growing the farm extends plot, metadata and piece cache files, additional sectors will be
+    /// plotted next time farm is started. Shrinking the farm drops trailing sectors and moves
+    /// cached pieces that no longer fit into free space of the smaller piece cache.
+    ///
+    /// Farm is also resized automatically on start when different size is specified, this allows
+    /// to do it ahead of time, for example to reclaim disk space before starting the farmer.
+    pub fn resize(
+        directory: &Path,
+        allocated_space: u64,
+        cache_percentage: u8,
+        disable_farm_locking: bool,
+    ) -> Result<(), SingleDiskFarmError> {
+        let mut single_disk_farm_info =
+            SingleDiskFarmInfo::load_from(directory)?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "Single disk farm info not found at {}",
+                        directory.join(SingleDiskFarmInfo::FILE_NAME).display()
+                    ),
+                )
+            })?;
+
+        info!(
+            farm_id = %single_disk_farm_info.id(),
+            old_space = %bytesize::to_string(single_disk_farm_info.allocated_space(), true),
+            new_space = %bytesize::to_string(allocated_space, true),
+            "Resizing farm"
+        );
+
+        let single_disk_farm_info_lock = if disable_farm_locking {
+            None
+        } else {
+            Some(
+                SingleDiskFarmInfo::try_lock(directory)
+                    .map_err(SingleDiskFarmError::LikelyAlreadyInUse)?,
+            )
+        };
+
+        Self::resize_files(
+            directory,
+            &mut single_disk_farm_info,
+            allocated_space,
+            cache_percentage,
+            single_disk_farm_info_lock.as_ref(),
+        )
+    }
+
+    /// Resize farm files to match `allocated_space` and store updated farm info.
+    ///
+    /// Farm must be locked by the caller with `single_disk_farm_info_lock` (unless locking is
+    /// disabled), info is stored through the same lock, so the farm is never unlocked in between.
+    ///
+    /// Steps are ordered such that interruption at any point leaves the farm in a consistent state
+    /// and resizing can simply be repeated: metadata header stops referencing dropped sectors
+    /// before files are truncated and farm info is updated last.
+    fn resize_files(
+        directory: &Path,
+        single_disk_farm_info: &mut SingleDiskFarmInfo,
+        allocated_space: u64,
+        cache_percentage: u8,
+        single_disk_farm_info_lock: Option<&SingleDiskFarmInfoLock>,
+    ) -> Result<(), SingleDiskFarmError> {
+        let sector_size = sector_size(single_disk_farm_info.pieces_in_sector()) as u64;
+        let allocated_space_distribution = AllocatedSpaceDistribution::new(
+            allocated_space,
+            sector_size,
+            cache_percentage,
+            SectorMetadataChecksummed::encoded_size() as u64,
+        )?;
+        let target_sector_count = allocated_space_distribution.target_sector_count;
+
+        let metadata_file_path = directory.join(Self::METADATA_FILE);
+        if metadata_file_path.exists() {
+            let metadata_file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&metadata_file_path)?;
+
+            if metadata_file.size()? >= PlotMetadataHeader::encoded_size() as u64 {
+                let mut metadata_header_bytes = vec![0; PlotMetadataHeader::encoded_size()];
+                metadata_file.read_exact_at(&mut metadata_header_bytes, 0)?;
+
+                let mut metadata_header =
+                    PlotMetadataHeader::decode(&mut metadata_header_bytes.as_ref())
+                        .map_err(SingleDiskFarmError::FailedToDecodeMetadataHeader)?;
+
+                if metadata_header.version != SingleDiskFarm::SUPPORTED_PLOT_VERSION {
+                    return Err(SingleDiskFarmError::UnexpectedMetadataVersion(
+                        metadata_header.version,
+                    ));
+                }
+
+                if metadata_header.plotted_sector_count > target_sector_count {
+                    info!(
+                        plotted_sector_count = %metadata_header.plotted_sector_count,
+                        %target_sector_count,
+                        "Dropping trailing sectors"
+                    );
+
+                    metadata_header.plotted_sector_count = target_sector_count;
+                    metadata_file.write_all_at(&metadata_header.encode(), 0)?;
+                    metadata_file.sync_data()?;
+                }
+            }
+
+            // Align metadata file size for disk sector size
+            let expected_metadata_size = allocated_space_distribution
+                .metadata_file_size
+                .div_ceil(DISK_SECTOR_SIZE as u64)
+                * DISK_SECTOR_SIZE as u64;
+            if metadata_file.size()? != expected_metadata_size {
+                metadata_file
+                    .preallocate(expected_metadata_size)
+                    .map_err(SingleDiskFarmError::CantPreallocateMetadataFile)?;
+                metadata_file.set_len(expected_metadata_size)?;
+            }
+        }
+
+        let plot_file_path = directory.join(Self::PLOT_FILE);
+        if plot_file_path.exists() {
+            let plot_file = OpenOptions::new().write(true).open(&plot_file_path)?;
+
+            if plot_file.size()? != allocated_space_distribution.plot_file_size {
+                plot_file
+                    .preallocate(allocated_space_distribution.plot_file_size)
+                    .map_err(SingleDiskFarmError::CantPreallocatePlotFile)?;
+                plot_file.set_len(allocated_space_distribution.plot_file_size)?;
+            }
+        }
+
+        let dropped_pieces =
+            DiskPieceCache::resize(directory, allocated_space_distribution.piece_cache_capacity)?;
+        if dropped_pieces > 0 {
+            info!(
+                %dropped_pieces,
+                "Some cached pieces didn't fit into smaller piece cache and were dropped"
+            );
+        }
+
+        match single_disk_farm_info {
+            SingleDiskFarmInfo::V0 {
+                allocated_space: farm_allocated_space,
+                ..
+            } => {
+                *farm_allocated_space = allocated_space;
+            }
+        }
+        // Info is updated before the lock is released, such that nobody can see resized files
+        // with outdated info
+        match single_disk_farm_info_lock {
+            Some(single_disk_farm_info_lock) => {
+                single_disk_farm_info_lock.store(single_disk_farm_info)?;
+            }
+            None => {
+                single_disk_farm_info.store_to(directory)?;
+            }
+        }
+
+        info!(
+            %target_sector_count,
+            piece_cache_capacity = %allocated_space_distribution.piece_cache_capacity,
+            "Farm resized"
+        );
+
+        Ok(())
+    }
+
     /// Check the farm for corruption and repair errors (caused by disk errors or something else),
     /// returns an error when irrecoverable errors occur.
+    ///
+    /// Corrupted sectors are replaced with dummy expired sectors and corrupted cache elements are
+    /// replaced with empty elements, returned report describes what was found. See
+    /// [`Self::replot_sectors()`] and [`Self::refetch_cache_elements()`] for restoring replaced
+    /// data.
     pub fn scrub(
         directory: &Path,
         disable_farm_locking: bool,
         target: ScrubTarget,
         dry_run: bool,
-    ) -> Result<(), SingleDiskFarmScrubError> {
+    ) -> Result<ScrubReport, SingleDiskFarmScrubError> {
         let span = Span::current();
 
         if dry_run {
             info!("Dry run is used, no changes will be written to disk");
         }
 
+        let mut report = ScrubReport {
+            dry_run,
+            ..ScrubReport::default()
+        };
+
         if target.metadata() || target.plot() {
             let info = {
                 let file = directory.join(SingleDiskFarmInfo::FILE_NAME);
@@ -1865,12 +2274,12 @@ impl SingleDiskFarm {
                 )
             };
 
-            let identity = {
+            let identity_public_key = {
                 let file = directory.join(Identity::FILE_NAME);
                 info!(path = %file.display(), "Checking identity file");
 
-                match Identity::open(directory) {
-                    Ok(Some(identity)) => identity,
+                match Identity::read_public_key(directory) {
+                    Ok(Some(public_key)) => PublicKey::from(public_key.to_bytes()),
                     Ok(None) => {
                         return Err(SingleDiskFarmScrubError::IdentityFileDoesNotExist { file });
                     }
@@ -1880,9 +2289,9 @@ impl SingleDiskFarm {
                 }
             };
 
-            if PublicKey::from(identity.public.to_bytes()) != *info.public_key() {
+            if identity_public_key != *info.public_key() {
                 return Err(SingleDiskFarmScrubError::PublicKeyMismatch {
-                    identity: PublicKey::from(identity.public.to_bytes()),
+                    identity: identity_public_key,
                     info: *info.public_key(),
                 });
             }
@@ -2059,6 +2468,7 @@ impl SingleDiskFarm {
             };
 
             let sector_bytes_range = 0..(sector_size as usize - Blake3Hash::SIZE);
+            let corrupted_sectors = Mutex::new(Vec::new());
 
             info!("Checking sectors and corresponding metadata");
             (0..metadata_header.plotted_sector_count)
@@ -2083,6 +2493,13 @@ impl SingleDiskFarm {
                                 sector metadata"
                             );
 
+                            corrupted_sectors.lock().push(ScrubbedSector {
+                                sector_index,
+                                problem: ScrubSectorProblem::MetadataUnreadable,
+                                replaced: !dry_run,
+                                replotted: false,
+                            });
+
                             if !dry_run {
                                 write_dummy_sector_metadata(
                                     &metadata_file,
@@ -2107,6 +2524,13 @@ impl SingleDiskFarm {
                                     expired sector metadata"
                                 );
 
+                                corrupted_sectors.lock().push(ScrubbedSector {
+                                    sector_index,
+                                    problem: ScrubSectorProblem::MetadataCorrupted,
+                                    replaced: !dry_run,
+                                    replotted: false,
+                                });
+
                                 if !dry_run {
                                     write_dummy_sector_metadata(
                                         &metadata_file,
@@ -2128,6 +2552,13 @@ impl SingleDiskFarm {
                                 metadata"
                             );
 
+                            corrupted_sectors.lock().push(ScrubbedSector {
+                                sector_index,
+                                problem: ScrubSectorProblem::SectorIndexMismatch,
+                                replaced: !dry_run,
+                                replotted: false,
+                            });
+
                             if !dry_run {
                                 write_dummy_sector_metadata(
                                     &metadata_file,
@@ -2149,6 +2580,13 @@ impl SingleDiskFarm {
                                 metadata"
                             );
 
+                            corrupted_sectors.lock().push(ScrubbedSector {
+                                sector_index,
+                                problem: ScrubSectorProblem::PiecesInSectorMismatch,
+                                replaced: !dry_run,
+                                replotted: false,
+                            });
+
                             if !dry_run {
                                 write_dummy_sector_metadata(
                                     &metadata_file,
@@ -2220,6 +2658,13 @@ impl SingleDiskFarm {
                                     expired sector"
                                 );
 
+                                corrupted_sectors.lock().push(ScrubbedSector {
+                                    sector_index,
+                                    problem: ScrubSectorProblem::ChecksumMismatch,
+                                    replaced: !dry_run,
+                                    replotted: false,
+                                });
+
                                 if !dry_run {
                                     write_dummy_sector_metadata(
                                         &metadata_file,
@@ -2306,18 +2751,31 @@ impl SingleDiskFarm {
                         result
                     }
                 })?;
+
+            report.plotted_sector_count = Some(metadata_header.plotted_sector_count);
+            report.corrupted_sectors = corrupted_sectors.into_inner();
+            report
+                .corrupted_sectors
+                .sort_by_key(|corrupted_sector| corrupted_sector.sector_index);
         }
 
         if target.cache() {
-            Self::scrub_cache(directory, dry_run)?;
+            report.corrupted_cache_elements = Self::scrub_cache(directory, dry_run)?;
         }
 
-        info!("Farm check completed");
+        info!(
+            corrupted_sectors = %report.corrupted_sectors.len(),
+            corrupted_cache_elements = %report.corrupted_cache_elements.len(),
+            "Farm check completed"
+        );
 
-        Ok(())
+        Ok(report)
     }
 
-    fn scrub_cache(directory: &Path, dry_run: bool) -> Result<(), SingleDiskFarmScrubError> {
+    fn scrub_cache(
+        directory: &Path,
+        dry_run: bool,
+    ) -> Result<Vec<ScrubbedCacheElement>, SingleDiskFarmScrubError> {
         let span = Span::current();
 
         let file = directory.join(DiskPieceCache::FILE_NAME);
@@ -2331,7 +2789,7 @@ impl SingleDiskFarm {
                         file = %file.display(),
                         "Cache file does not exist, this is expected in farming cluster"
                     );
-                    Ok(())
+                    Ok(Vec::new())
                 } else {
                     Err(SingleDiskFarmScrubError::CacheCantBeOpened { file, error })
                 };
@@ -2351,6 +2809,7 @@ impl SingleDiskFarm {
         let element_size = DiskPieceCache::element_size();
         let number_of_cached_elements = cache_size / u64::from(element_size);
         let dummy_element = vec![0; element_size as usize];
+        let corrupted_cache_elements = Mutex::new(Vec::new());
         (0..number_of_cached_elements)
             .into_par_iter()
             .map_with(vec![0; element_size as usize], |element, cache_offset| {
@@ -2367,6 +2826,14 @@ impl SingleDiskFarm {
                         "Failed to read cached piece, replacing with dummy element"
                     );
 
+                    corrupted_cache_elements.lock().push(ScrubbedCacheElement {
+                        offset: cache_offset as u32,
+                        piece_index: None,
+                        replaced: !dry_run,
+                        refetched: false,
+                        piece_chunk_hashes: Vec::new(),
+                    });
+
                     if !dry_run {
                         if let Err(error) = cache_file.write_all_at(&dummy_element, offset) {
                             return Err(SingleDiskFarmScrubError::FailedToWriteBytes {
@@ -2392,6 +2859,23 @@ impl SingleDiskFarm {
                         "Cached piece checksum mismatch, replacing with dummy element"
                     );
 
+                    // Piece index might be corrupted too, piece chunk hashes are stored to check
+                    // whether it can be trusted once piece is downloaded again
+                    let (piece_index_bytes, piece_bytes) =
+                        index_and_piece_bytes.split_at(PieceIndex::SIZE);
+                    let piece_index = PieceIndex::from_bytes(
+                        piece_index_bytes
+                            .try_into()
+                            .expect("Statically known to have correct size; qed"),
+                    );
+                    corrupted_cache_elements.lock().push(ScrubbedCacheElement {
+                        offset: cache_offset as u32,
+                        piece_index: Some(piece_index),
+                        replaced: !dry_run,
+                        refetched: false,
+                        piece_chunk_hashes: piece_chunk_hashes(piece_bytes),
+                    });
+
                     if !dry_run {
                         if let Err(error) = cache_file.write_all_at(&dummy_element, offset) {
                             return Err(SingleDiskFarmScrubError::FailedToWriteBytes {
@@ -2427,8 +2911,643 @@ impl SingleDiskFarm {
                 }
             })?;
 
+        let mut corrupted_cache_elements = corrupted_cache_elements.into_inner();
+        corrupted_cache_elements.sort_by_key(|corrupted_element| corrupted_element.offset);
+
+        Ok(corrupted_cache_elements)
+    }
+
+    /// Plot sectors that were found to be corrupted by [`Self::scrub()`] again using provided
+    /// plotter, returns sectors that were replotted.
+    ///
+    /// Sectors must have been replaced with dummy expired sectors by scrubbing first, farm is
+    /// locked while sectors are being replotted.
+    pub async fn replot_sectors<NC>(
+        directory: &Path,
+        sector_indices: &[SectorIndex],
+        plotter: Arc<dyn Plotter + Send + Sync>,
+        node_client: &NC,
+        disable_farm_locking: bool,
+    ) -> Result<Vec<SectorIndex>, SingleDiskFarmScrubError>
+    where
+        NC: NodeClient,
+    {
+        let single_disk_farm_info = {
+            let file = directory.join(SingleDiskFarmInfo::FILE_NAME);
+            match SingleDiskFarmInfo::load_from(directory) {
+                Ok(Some(info)) => info,
+                Ok(None) => {
+                    return Err(SingleDiskFarmScrubError::FarmInfoFileDoesNotExist { file });
+                }
+                Err(error) => {
+                    return Err(SingleDiskFarmScrubError::FarmInfoCantBeOpened { file, error });
+                }
+            }
+        };
+
+        let _single_disk_farm_info_lock = if disable_farm_locking {
+            None
+        } else {
+            Some(
+                SingleDiskFarmInfo::try_lock(directory)
+                    .map_err(SingleDiskFarmScrubError::LikelyAlreadyInUse)?,
+            )
+        };
+
+        let metadata_file_path = directory.join(Self::METADATA_FILE);
+        #[cfg(not(windows))]
+        let metadata_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .advise_random_access()
+            .open(&metadata_file_path)?;
+
+        #[cfg(windows)]
+        let metadata_file = UnbufferedIoFileWindows::open(&metadata_file_path)?;
+
+        let metadata_header = {
+            let mut metadata_header_bytes = vec![0; PlotMetadataHeader::encoded_size()];
+            metadata_file.read_exact_at(&mut metadata_header_bytes, 0)?;
+
+            let metadata_header =
+                PlotMetadataHeader::decode(&mut metadata_header_bytes.as_ref())
+                    .map_err(SingleDiskFarmScrubError::FailedToDecodeMetadataHeader)?;
+
+            if metadata_header.version != SingleDiskFarm::SUPPORTED_PLOT_VERSION {
+                return Err(SingleDiskFarmScrubError::UnexpectedMetadataVersion(
+                    metadata_header.version,
+                ));
+            }
+
+            metadata_header
+        };
+
+        let sectors_metadata = Self::read_all_sectors_metadata(directory)
+            .map_err(SingleDiskFarmScrubError::FailedToReadSectorsMetadata)?;
+        // Only sectors that were plotted before can be replotted
+        let sector_indices = sector_indices
+            .iter()
+            .copied()
+            .filter(|&sector_index| usize::from(sector_index) < sectors_metadata.len())
+            .collect::<Vec<_>>();
+        if sector_indices.is_empty() {
+            return Ok(Vec::new());
+        }
+        let old_sectors_metadata = sector_indices
+            .iter()
+            .map(|&sector_index| sectors_metadata[usize::from(sector_index)].encode())
+            .collect::<Vec<_>>();
+        let sectors_metadata = AsyncRwLock::new(sectors_metadata);
+
+        #[cfg(not(windows))]
+        let plot_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .advise_random_access()
+            .open(directory.join(Self::PLOT_FILE))?;
+
+        #[cfg(windows)]
+        let plot_file = UnbufferedIoFileWindows::open(&directory.join(Self::PLOT_FILE))?;
+
+        let pieces_in_sector = single_disk_farm_info.pieces_in_sector();
+        let handlers = Handlers::default();
+        let global_mutex = AsyncMutex::default();
+
+        info!(sectors = %sector_indices.len(), "Replotting sectors");
+
+        replot_sectors(
+            &sector_indices,
+            metadata_header,
+            &sectors_metadata,
+            SectorPlottingOptions {
+                public_key: *single_disk_farm_info.public_key(),
+                node_client,
+                pieces_in_sector,
+                sector_size: sector_size(pieces_in_sector),
+                plot_file: Arc::new(plot_file),
+                metadata_file: Arc::new(metadata_file),
+                handlers: &handlers,
+                global_mutex: &global_mutex,
+                plotter,
+                metrics: None,
+            },
+        )
+        .await?;
+
+        // Sectors that were skipped still have the same metadata as before
+        let sectors_metadata = sectors_metadata.into_inner();
+        Ok(sector_indices
+            .into_iter()
+            .zip(old_sectors_metadata)
+            .filter_map(|(sector_index, old_sector_metadata)| {
+                (sectors_metadata[usize::from(sector_index)].encode() != old_sector_metadata)
+                    .then_some(sector_index)
+            })
+            .collect())
+    }
+
+    /// Download pieces for cache elements that were found to be corrupted by [`Self::scrub()`]
+    /// and write them back to the cache, elements that were restored are marked as refetched.
+    ///
+    /// Only elements whose piece index can be trusted are restored: piece index is read from
+    /// corrupted element, so downloaded piece must match most of the piece bytes that were stored
+    /// in the element. Farm is locked while cache is being written to.
+    pub async fn refetch_cache_elements<PG>(
+        directory: &Path,
+        corrupted_cache_elements: &mut [ScrubbedCacheElement],
+        piece_getter: &PG,
+        disable_farm_locking: bool,
+    ) -> Result<(), SingleDiskFarmScrubError>
+    where
+        PG: PieceGetter,
+    {
+        let _single_disk_farm_info_lock = if disable_farm_locking {
+            None
+        } else {
+            Some(
+                SingleDiskFarmInfo::try_lock(directory)
+                    .map_err(SingleDiskFarmScrubError::LikelyAlreadyInUse)?,
+            )
+        };
+
+        let file = directory.join(DiskPieceCache::FILE_NAME);
+        let capacity = match fs::metadata(&file) {
+            Ok(metadata) => (metadata.len() / u64::from(DiskPieceCache::element_size())) as u32,
+            Err(error) => {
+                return Err(SingleDiskFarmScrubError::CacheCantBeOpened { file, error });
+            }
+        };
+        if capacity == 0 {
+            return Ok(());
+        }
+        let piece_cache = DiskPieceCache::open(directory, capacity, None, None)?;
+
+        for corrupted_element in corrupted_cache_elements {
+            let Some(piece_index) = corrupted_element.piece_index else {
+                continue;
+            };
+            if corrupted_element.offset >= capacity {
+                continue;
+            }
+
+            let piece = match piece_getter.get_piece(piece_index).await {
+                Ok(Some(piece)) => piece,
+                Ok(None) => {
+                    warn!(%piece_index, "Piece for corrupted cache element not found");
+                    continue;
+                }
+                Err(error) => {
+                    warn!(%error, %piece_index, "Failed to get piece for corrupted cache element");
+                    continue;
+                }
+            };
+
+            let offset = PieceCacheOffset(corrupted_element.offset);
+            let matching_chunks = piece_chunk_hashes(piece.as_ref())
+                .into_iter()
+                .zip(&corrupted_element.piece_chunk_hashes)
+                .filter(|(chunk_hash, stored_chunk_hash)| chunk_hash == *stored_chunk_hash)
+                .count();
+            if matching_chunks < CACHE_ELEMENT_PIECE_CHUNKS / 2 {
+                warn!(
+                    %piece_index,
+                    %offset,
+                    %matching_chunks,
+                    "Piece index of corrupted cache element can't be trusted, skipping"
+                );
+                continue;
+            }
+
+            task::block_in_place(|| piece_cache.write_piece(offset, piece_index, &piece))?;
+
+            debug!(%piece_index, %offset, "Corrupted cache element restored");
+            corrupted_element.refetched = true;
+        }
+
         Ok(())
     }
+
+    /// Migrate farm from `source` to `destination` directory, for example to move it to a
+    /// different disk.
+    ///
+    /// Sectors are copied one by one and verified the same way as [`Self::scrub()`] does, corrupted
+    /// sectors are replaced with dummy expired sectors at destination, so they will be plotted
+    /// again once farm is started, this includes sectors with unreadable or corrupted metadata.
+    /// Interrupted migration resumes from the last copied sector when called again. Farm info is
+    /// written to destination last, so destination is not recognized as a farm until migration is
+    /// complete, both source and destination are locked while migration is in progress.
+    ///
+    /// Destination uses `allocated_space` if specified and allocated space of the source farm
+    /// otherwise, sectors that don't fit into destination are not copied.
+    pub fn migrate(
+        source: &Path,
+        destination: &Path,
+        allocated_space: Option<u64>,
+        cache_percentage: u8,
+        disable_farm_locking: bool,
+    ) -> Result<(), SingleDiskFarmMigrateError> {
+        let single_disk_farm_info = SingleDiskFarmInfo::load_from(source)?.ok_or_else(|| {
+            SingleDiskFarmMigrateError::FarmInfoFileDoesNotExist {
+                file: source.join(SingleDiskFarmInfo::FILE_NAME),
+            }
+        })?;
+
+        let destination_info_file = destination.join(SingleDiskFarmInfo::FILE_NAME);
+        // Info file at destination is empty while migration is in progress, see below
+        let migration_in_progress =
+            fs::metadata(&destination_info_file).is_ok_and(|metadata| metadata.len() == 0);
+        if !migration_in_progress {
+            if let Some(destination_info) = SingleDiskFarmInfo::load_from(destination)? {
+                if destination_info.id() == single_disk_farm_info.id() {
+                    info!("Farm was already migrated to destination");
+                    return Ok(());
+                }
+
+                return Err(SingleDiskFarmMigrateError::DestinationNotEmpty {
+                    directory: destination.to_path_buf(),
+                });
+            }
+        }
+
+        let _single_disk_farm_info_lock = if disable_farm_locking {
+            None
+        } else {
+            Some(
+                SingleDiskFarmInfo::try_lock(source)
+                    .map_err(SingleDiskFarmMigrateError::LikelyAlreadyInUse)?,
+            )
+        };
+
+        fs::create_dir_all(destination)?;
+
+        // Empty info file is created and locked at destination right away, such that neither farm
+        // can be started there nor another migration can write into it until this migration is
+        // complete, actual info is written into it last
+        let destination_info_lock = if disable_farm_locking {
+            None
+        } else {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&destination_info_file)?;
+
+            Some(
+                SingleDiskFarmInfo::try_lock(destination)
+                    .map_err(SingleDiskFarmMigrateError::LikelyAlreadyInUse)?,
+            )
+        };
+
+        {
+            let identity_bytes = fs::read(source.join(Identity::FILE_NAME))?;
+            let destination_identity_file = destination.join(Identity::FILE_NAME);
+            match fs::read(&destination_identity_file) {
+                Ok(destination_identity_bytes) => {
+                    if destination_identity_bytes != identity_bytes {
+                        return Err(SingleDiskFarmMigrateError::DestinationNotEmpty {
+                            directory: destination.to_path_buf(),
+                        });
+                    }
+                }
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    fs::write(destination_identity_file, identity_bytes)?;
+                }
+                Err(error) => {
+                    return Err(error.into());
+                }
+            }
+        }
+
+        let allocated_space =
+            allocated_space.unwrap_or_else(|| single_disk_farm_info.allocated_space());
+        let pieces_in_sector = single_disk_farm_info.pieces_in_sector();
+        let sector_size = sector_size(pieces_in_sector) as u64;
+        let sector_metadata_size = SectorMetadataChecksummed::encoded_size();
+        let allocated_space_distribution = AllocatedSpaceDistribution::new(
+            allocated_space,
+            sector_size,
+            cache_percentage,
+            sector_metadata_size as u64,
+        )?;
+        let target_sector_count = allocated_space_distribution.target_sector_count;
+
+        let source_metadata_file = OpenOptions::new()
+            .read(true)
+            .open(source.join(Self::METADATA_FILE))?;
+        let source_plotted_sector_count = {
+            let mut metadata_header_bytes = vec![0; PlotMetadataHeader::encoded_size()];
+            source_metadata_file.read_exact_at(&mut metadata_header_bytes, 0)?;
+
+            let metadata_header =
+                PlotMetadataHeader::decode(&mut metadata_header_bytes.as_ref())
+                    .map_err(SingleDiskFarmMigrateError::FailedToDecodeMetadataHeader)?;
+
+            if metadata_header.version != SingleDiskFarm::SUPPORTED_PLOT_VERSION {
+                return Err(SingleDiskFarmMigrateError::UnexpectedMetadataVersion(
+                    metadata_header.version,
+                ));
+            }
+
+            metadata_header.plotted_sector_count
+        };
+        if source_plotted_sector_count > target_sector_count {
+            warn!(
+                plotted_sector_count = %source_plotted_sector_count,
+                %target_sector_count,
+                "Destination is smaller than source, trailing sectors will not be copied"
+            );
+        }
+        let sector_count = source_plotted_sector_count.min(target_sector_count);
+
+        let metadata_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(destination.join(Self::METADATA_FILE))?;
+        // Align metadata file size for disk sector size
+        let expected_metadata_size = allocated_space_distribution
+            .metadata_file_size
+            .div_ceil(DISK_SECTOR_SIZE as u64)
+            * DISK_SECTOR_SIZE as u64;
+        let mut metadata_header = if metadata_file.size()? == 0 {
+            let metadata_header = PlotMetadataHeader {
+                version: SingleDiskFarm::SUPPORTED_PLOT_VERSION,
+                plotted_sector_count: 0,
+            };
+
+            metadata_file
+                .preallocate(expected_metadata_size)
+                .map_err(SingleDiskFarmError::CantPreallocateMetadataFile)?;
+            metadata_file.write_all_at(&metadata_header.encode(), 0)?;
+
+            metadata_header
+        } else {
+            if metadata_file.size()? != expected_metadata_size {
+                metadata_file
+                    .preallocate(expected_metadata_size)
+                    .map_err(SingleDiskFarmError::CantPreallocateMetadataFile)?;
+                metadata_file.set_len(expected_metadata_size)?;
+            }
+
+            let mut metadata_header_bytes = vec![0; PlotMetadataHeader::encoded_size()];
+            metadata_file.read_exact_at(&mut metadata_header_bytes, 0)?;
+
+            let mut metadata_header =
+                PlotMetadataHeader::decode(&mut metadata_header_bytes.as_ref())
+                    .map_err(SingleDiskFarmMigrateError::FailedToDecodeMetadataHeader)?;
+
+            if metadata_header.version != SingleDiskFarm::SUPPORTED_PLOT_VERSION {
+                return Err(SingleDiskFarmMigrateError::UnexpectedMetadataVersion(
+                    metadata_header.version,
+                ));
+            }
+
+            metadata_header.plotted_sector_count =
+                metadata_header.plotted_sector_count.min(sector_count);
+
+            if metadata_header.plotted_sector_count > 0 {
+                info!(
+                    copied_sectors = %metadata_header.plotted_sector_count,
+                    "Resuming interrupted migration"
+                );
+            }
+
+            metadata_header
+        };
+
+        let source_plot_file = OpenOptions::new()
+            .read(true)
+            .open(source.join(Self::PLOT_FILE))?;
+        // Error doesn't matter here
+        let _ = source_plot_file.advise_sequential_access();
+
+        let plot_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(destination.join(Self::PLOT_FILE))?;
+        if plot_file.size()? != allocated_space_distribution.plot_file_size {
+            plot_file
+                .preallocate(allocated_space_distribution.plot_file_size)
+                .map_err(SingleDiskFarmError::CantPreallocatePlotFile)?;
+            plot_file.set_len(allocated_space_distribution.plot_file_size)?;
+        }
+
+        info!("Copying sectors and corresponding metadata");
+        let sector_bytes_range = 0..(sector_size as usize - Blake3Hash::SIZE);
+        let mut scratch_buffer = vec![0u8; Record::SIZE];
+        let mut sector_metadata_bytes = vec![0u8; sector_metadata_size];
+        for sector_index in metadata_header.plotted_sector_count..sector_count {
+            let sector_metadata_offset =
+                RESERVED_PLOT_METADATA + u64::from(sector_index) * sector_metadata_size as u64;
+            let maybe_sector_metadata = match source_metadata_file
+                .read_exact_at(&mut sector_metadata_bytes, sector_metadata_offset)
+            {
+                Ok(()) => {
+                    match SectorMetadataChecksummed::decode(&mut sector_metadata_bytes.as_slice()) {
+                        Ok(sector_metadata) => Some(sector_metadata),
+                        Err(error) => {
+                            warn!(
+                                %error,
+                                %sector_index,
+                                "Failed to decode sector metadata, replacing with dummy expired \
+                                sector metadata"
+                            );
+                            None
+                        }
+                    }
+                }
+                Err(error) => {
+                    warn!(
+                        %error,
+                        %sector_index,
+                        offset = %sector_metadata_offset,
+                        size = %sector_metadata_size,
+                        "Failed to read sector metadata, replacing with dummy expired sector \
+                        metadata"
+                    );
+                    None
+                }
+            };
+            let mut sector_is_valid = maybe_sector_metadata.is_some();
+
+            if let Some(sector_metadata) = &maybe_sector_metadata {
+                if sector_metadata.sector_index != sector_index {
+                    warn!(
+                        %sector_index,
+                        found_sector_index = sector_metadata.sector_index,
+                        "Sector index mismatch, replacing with dummy expired sector metadata"
+                    );
+                    sector_is_valid = false;
+                }
+
+                if sector_metadata.pieces_in_sector != pieces_in_sector {
+                    warn!(
+                        %sector_index,
+                        %pieces_in_sector,
+                        found_pieces_in_sector = sector_metadata.pieces_in_sector,
+                        "Pieces in sector mismatch, replacing with dummy expired sector metadata"
+                    );
+                    sector_is_valid = false;
+                }
+            }
+
+            let mut hasher = blake3::Hasher::new();
+            // Copy sector bytes and compute checksum
+            for offset_in_sector in sector_bytes_range.clone().step_by(scratch_buffer.len()) {
+                let offset = u64::from(sector_index) * sector_size + offset_in_sector as u64;
+                let bytes_to_copy = (offset_in_sector + scratch_buffer.len())
+                    .min(sector_bytes_range.end)
+                    - offset_in_sector;
+
+                let bytes = &mut scratch_buffer[..bytes_to_copy];
+
+                if let Err(error) = source_plot_file.read_exact_at(bytes, offset) {
+                    warn!(
+                        %error,
+                        %sector_index,
+                        %offset,
+                        size = %bytes.len() as u64,
+                        "Failed to read sector bytes"
+                    );
+
+                    sector_is_valid = false;
+                    bytes.fill(0);
+                }
+
+                hasher.update(bytes);
+                plot_file.write_all_at(bytes, offset)?;
+            }
+
+            {
+                let offset = u64::from(sector_index) * sector_size + sector_bytes_range.end as u64;
+                let mut expected_checksum = [0; Blake3Hash::SIZE];
+                if let Err(error) = source_plot_file.read_exact_at(&mut expected_checksum, offset) {
+                    warn!(
+                        %error,
+                        %sector_index,
+                        %offset,
+                        size = %expected_checksum.len() as u64,
+                        "Failed to read sector checksum bytes"
+                    );
+                }
+
+                let actual_checksum = *hasher.finalize().as_bytes();
+                if actual_checksum != expected_checksum {
+                    warn!(
+                        %sector_index,
+                        actual_checksum = %hex::encode(actual_checksum),
+                        expected_checksum = %hex::encode(expected_checksum),
+                        "Plotted sector checksum mismatch, replacing with dummy expired sector"
+                    );
+                    sector_is_valid = false;
+                }
+
+                // Checksum of copied bytes, such that destination sector is consistent even if
+                // source sector wasn't
+                plot_file.write_all_at(&actual_checksum, offset)?;
+            }
+
+            let sector_metadata = match maybe_sector_metadata {
+                Some(sector_metadata) if sector_is_valid => sector_metadata,
+                _ => SectorMetadataChecksummed::from(SectorMetadata {
+                    sector_index,
+                    pieces_in_sector,
+                    s_bucket_sizes: Box::new([0; Record::NUM_S_BUCKETS]),
+                    history_size: HistorySize::from(SegmentIndex::ZERO),
+                }),
+            };
+            metadata_file.write_all_at(&sector_metadata.encode(), sector_metadata_offset)?;
+
+            // Sector must be persisted before it is accounted for in the header, otherwise
+            // interrupted migration will not copy it again
+            plot_file.sync_data()?;
+            metadata_header.plotted_sector_count = sector_index + 1;
+            metadata_file.write_all_at(&metadata_header.encode(), 0)?;
+            metadata_file.sync_data()?;
+
+            let copied_sectors = sector_index + 1;
+            if copied_sectors % 10 == 0 {
+                info!("Copied {}/{} sectors", copied_sectors, sector_count);
+            }
+        }
+
+        let source_piece_cache_capacity = match fs::metadata(source.join(DiskPieceCache::FILE_NAME))
+        {
+            Ok(metadata) => (metadata.len() / u64::from(DiskPieceCache::element_size())) as u32,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => 0,
+            Err(error) => {
+                return Err(error.into());
+            }
+        };
+        let piece_cache_capacity = allocated_space_distribution.piece_cache_capacity;
+        if source_piece_cache_capacity > 0 && piece_cache_capacity > 0 {
+            info!("Copying piece cache");
+
+            let source_piece_cache =
+                DiskPieceCache::open(source, source_piece_cache_capacity, None, None)?;
+            let piece_cache = DiskPieceCache::open(destination, piece_cache_capacity, None, None)?;
+
+            // Pieces are stored compactly at destination, so the same source element always maps
+            // to the same destination offset, which makes resuming possible
+            let mut next_offset = 0;
+            for offset in 0..source_piece_cache_capacity {
+                if next_offset == piece_cache_capacity {
+                    info!("Destination piece cache is full, remaining cached pieces are skipped");
+                    break;
+                }
+
+                let (piece_index, piece) =
+                    match source_piece_cache.read_piece(PieceCacheOffset(offset)) {
+                        Ok(Some(element)) => element,
+                        Ok(None) => {
+                            continue;
+                        }
+                        Err(error) => {
+                            warn!(%error, %offset, "Failed to read cached piece, skipping");
+                            continue;
+                        }
+                    };
+
+                let destination_offset = PieceCacheOffset(next_offset);
+                next_offset += 1;
+
+                if matches!(
+                    piece_cache.read_piece_index(destination_offset),
+                    Ok(Some(existing_piece_index)) if existing_piece_index == piece_index
+                ) {
+                    continue;
+                }
+
+                piece_cache.write_piece(destination_offset, piece_index, &piece)?;
+            }
+        }
+
+        let destination_info = SingleDiskFarmInfo::new(
+            *single_disk_farm_info.id(),
+            *single_disk_farm_info.genesis_hash(),
+            *single_disk_farm_info.public_key(),
+            pieces_in_sector,
+            allocated_space,
+            single_disk_farm_info.identity_derivation_path().copied(),
+        );
+        match &destination_info_lock {
+            Some(destination_info_lock) => destination_info_lock.store(&destination_info)?,
+            None => destination_info.store_to(destination)?,
+        }
+
+        info!(copied_sectors = %sector_count, "Farm migration completed");
+
+        Ok(())
+    }
+}
+
+/// Hashes of chunks of piece bytes stored in cache element
+fn piece_chunk_hashes(piece_bytes: &[u8]) -> Vec<Blake3Hash> {
+    piece_bytes
+        .chunks(piece_bytes.len().div_ceil(CACHE_ELEMENT_PIECE_CHUNKS))
+        .map(blake3_hash)
+        .collect()
 }
 
 fn write_dummy_sector_metadata(
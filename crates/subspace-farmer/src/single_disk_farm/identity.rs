@@ -1,31 +1,224 @@
 //! Farm identity
+//!
+//! Identity is stored in [`Identity::FILE_NAME`] file either in plain text or encrypted with a
+//! passphrase. Encrypted identity files contain public key in plain text, so that public key can be
+//! read without passphrase (see [`Identity::read_public_key()`]), and entropy encrypted with
+//! ChaCha20-Poly1305 using key derived from passphrase with PBKDF2-HMAC-SHA256.
+//!
+//! Farm identity can also be derived from master identity (see [`Identity::derive()`]), such that
+//! many farms can be restored from a single master identity and their derivation paths.
 
+#[cfg(test)]
+mod tests;
+
+use crate::farm::FarmId;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
 use parity_scale_codec::{Decode, Encode};
 use schnorrkel::context::SigningContext;
-use schnorrkel::{ExpansionMode, Keypair, PublicKey, SecretKey, Signature};
+use schnorrkel::{ExpansionMode, Keypair, PublicKey, SecretKey, Signature, SignatureError};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+#[cfg(unix)]
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::num::NonZeroU32;
 use std::ops::Deref;
-use std::path::Path;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
 use std::{fmt, fs, io};
 use subspace_core_primitives::REWARD_SIGNING_CONTEXT;
 use substrate_bip39::mini_secret_from_entropy;
 use thiserror::Error;
-use tracing::debug;
+use tracing::{debug, info};
 use zeroize::Zeroizing;
 
 /// Entropy used for identity generation.
 const ENTROPY_LENGTH: usize = 32;
+/// Prefix of encrypted identity file.
+///
+/// Plain identity file starts with SCALE-encoded length of the entropy, so it never starts with
+/// this prefix.
+const ENCRYPTED_FILE_PREFIX: &[u8; 4] = b"seid";
+/// Default number of PBKDF2 rounds used for newly encrypted identity files (number of rounds is
+/// stored in the file, so it can be increased in the future without breaking existing files)
+const DEFAULT_PBKDF2_ROUNDS: u32 = 600_000;
+const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+/// Size of Poly1305 authentication tag appended to ciphertext
+const TAG_LENGTH: usize = 16;
+/// Context for BLAKE3 key derivation of farm identities from master identity
+const IDENTITY_DERIVATION_CONTEXT: &str = "subspace-farmer 2024 farm identity derivation";
+/// Version of the key material encoding used for farm identity derivation, must be changed if
+/// encoding changes
+const IDENTITY_DERIVATION_VERSION: u8 = 0;
 
 #[derive(Debug, Encode, Decode)]
 struct IdentityFileContents {
     entropy: Vec<u8>,
 }
 
+#[derive(Debug, Encode, Decode)]
+struct EncryptedIdentityFileContents {
+    public_key: [u8; schnorrkel::PUBLIC_KEY_LENGTH],
+    salt: [u8; SALT_LENGTH],
+    pbkdf2_rounds: u32,
+    nonce: [u8; NONCE_LENGTH],
+    /// Encrypted entropy, public key is used as associated data
+    ciphertext: Vec<u8>,
+}
+
+impl EncryptedIdentityFileContents {
+    fn encrypt(entropy: &[u8], public_key: &PublicKey, passphrase: &IdentityPassphrase) -> Self {
+        let public_key = public_key.to_bytes();
+        let salt = rand::random::<[u8; SALT_LENGTH]>();
+        let nonce = rand::random::<[u8; NONCE_LENGTH]>();
+        let pbkdf2_rounds = passphrase.pbkdf2_rounds;
+        let key = derive_encryption_key(passphrase, &salt, pbkdf2_rounds);
+
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key.as_slice()))
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: entropy,
+                    aad: &public_key,
+                },
+            )
+            .expect("Entropy is much smaller than max message size; qed");
+
+        Self {
+            public_key,
+            salt,
+            pbkdf2_rounds,
+            nonce,
+            ciphertext,
+        }
+    }
+
+    fn decrypt(
+        &self,
+        passphrase: &IdentityPassphrase,
+    ) -> Result<Zeroizing<Vec<u8>>, IdentityError> {
+        if self.pbkdf2_rounds == 0 {
+            return Err(IdentityError::InvalidPassphrase);
+        }
+
+        let key = derive_encryption_key(passphrase, &self.salt, self.pbkdf2_rounds);
+
+        ChaCha20Poly1305::new(Key::from_slice(key.as_slice()))
+            .decrypt(
+                Nonce::from_slice(&self.nonce),
+                Payload {
+                    msg: &self.ciphertext,
+                    aad: &self.public_key,
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_error| IdentityError::InvalidPassphrase)
+    }
+}
+
+#[derive(Debug)]
+enum IdentityFile {
+    Plain(IdentityFileContents),
+    Encrypted(EncryptedIdentityFileContents),
+}
+
+impl IdentityFile {
+    fn read(path: &Path) -> Result<Option<Self>, IdentityError> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => Zeroizing::new(bytes),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(None);
+            }
+            Err(error) => {
+                return Err(error.into());
+            }
+        };
+
+        Ok(Some(
+            if let Some(mut encrypted) = bytes.strip_prefix(ENCRYPTED_FILE_PREFIX.as_slice()) {
+                Self::Encrypted(EncryptedIdentityFileContents::decode(&mut encrypted)?)
+            } else {
+                Self::Plain(IdentityFileContents::decode(&mut bytes.as_slice())?)
+            },
+        ))
+    }
+
+    /// Write file atomically, such that identity is never lost if process is interrupted.
+    ///
+    /// File is only accessible by the current user on Unix.
+    fn write(&self, path: &Path) -> io::Result<()> {
+        let bytes = Zeroizing::new(match self {
+            Self::Plain(contents) => contents.encode(),
+            Self::Encrypted(contents) => {
+                let mut bytes = ENCRYPTED_FILE_PREFIX.to_vec();
+                contents.encode_to(&mut bytes);
+                bytes
+            }
+        });
+
+        let tmp_path = path.with_extension("tmp");
+        // Leftover from interrupted write might have been created with different permissions
+        match fs::remove_file(&tmp_path) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => {
+                return Err(error);
+            }
+        }
+
+        {
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+            let mut file = options.open(&tmp_path)?;
+            file.write_all(bytes.as_slice())?;
+            file.sync_all()?;
+        }
+
+        fs::rename(tmp_path, path)?;
+
+        // Make sure rename itself is persisted
+        #[cfg(unix)]
+        if let Some(parent) = path.parent() {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            File::open(parent)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+}
+
 fn keypair_from_entropy(entropy: &[u8]) -> Keypair {
     mini_secret_from_entropy(entropy, "")
         .expect("32 bytes can always build a key; qed")
         .expand_to_keypair(ExpansionMode::Ed25519)
 }
 
+fn derive_encryption_key(
+    passphrase: &IdentityPassphrase,
+    salt: &[u8],
+    rounds: u32,
+) -> Zeroizing<[u8; KEY_LENGTH]> {
+    let mut key = Zeroizing::new([0; KEY_LENGTH]);
+    pbkdf2::pbkdf2_hmac::<Sha256>(
+        passphrase.passphrase.as_bytes(),
+        salt,
+        rounds,
+        key.as_mut_slice(),
+    );
+    key
+}
+
 /// Errors happening when trying to create/open single disk farm
 #[derive(Debug, Error)]
 pub enum IdentityError {
@@ -35,6 +228,115 @@ pub enum IdentityError {
     /// Decoding error
     #[error("Decoding error: {0}")]
     Decoding(#[from] parity_scale_codec::Error),
+    /// Identity is encrypted, but passphrase wasn't provided
+    #[error("Identity is encrypted, passphrase is required to open it")]
+    PassphraseRequired,
+    /// Invalid passphrase or corrupted identity file
+    #[error("Failed to decrypt identity, invalid passphrase or corrupted identity file")]
+    InvalidPassphrase,
+    /// Invalid public key in encrypted identity file
+    #[error("Invalid public key in encrypted identity file: {0}")]
+    InvalidPublicKey(SignatureError),
+}
+
+/// Passphrase used for identity encryption.
+///
+/// Zeroized on drop and not printed in debug output.
+#[derive(Clone)]
+pub struct IdentityPassphrase {
+    passphrase: Zeroizing<String>,
+    /// Number of PBKDF2 rounds used when encrypting identity, decryption uses number of rounds
+    /// stored in the identity file
+    pbkdf2_rounds: u32,
+}
+
+impl fmt::Debug for IdentityPassphrase {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdentityPassphrase").finish_non_exhaustive()
+    }
+}
+
+impl From<String> for IdentityPassphrase {
+    #[inline]
+    fn from(value: String) -> Self {
+        Self {
+            passphrase: Zeroizing::new(value),
+            pbkdf2_rounds: DEFAULT_PBKDF2_ROUNDS,
+        }
+    }
+}
+
+impl IdentityPassphrase {
+    /// Use custom number of PBKDF2 rounds when encrypting identity.
+    ///
+    /// Primarily used for testing, default number of rounds should be used otherwise.
+    #[doc(hidden)]
+    pub fn with_pbkdf2_rounds(mut self, pbkdf2_rounds: NonZeroU32) -> Self {
+        self.pbkdf2_rounds = pbkdf2_rounds.get();
+        self
+    }
+}
+
+/// Path used to derive farm identity from master identity.
+///
+/// Farms never share identity: identical public keys would result in identical sectors and
+/// duplicate solutions across farms.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IdentityDerivationPath {
+    /// Identity is derived for the farm with specified ID
+    Farm(FarmId),
+}
+
+impl fmt::Display for IdentityDerivationPath {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Farm(farm_id) => write!(f, "//farm//{farm_id}"),
+        }
+    }
+}
+
+impl IdentityDerivationPath {
+    /// Key material that identifies derivation path, derived identity is a function of master
+    /// identity and these bytes.
+    ///
+    /// Encoding is versioned and must never change for existing versions, otherwise farms would
+    /// derive a different identity after upgrade.
+    fn key_material(&self) -> Vec<u8> {
+        match self {
+            Self::Farm(farm_id) => {
+                let mut key_material = vec![IDENTITY_DERIVATION_VERSION];
+                // Farm ID has fixed-size SCALE encoding
+                farm_id.encode_to(&mut key_material);
+                key_material
+            }
+        }
+    }
+}
+
+/// Master identity that identities of newly created farms are derived from
+#[derive(Debug, Clone)]
+pub struct MasterIdentity {
+    identity: Identity,
+}
+
+impl MasterIdentity {
+    /// Create new instance, unique identity is derived for every farm
+    pub fn new(identity: Identity) -> Self {
+        Self { identity }
+    }
+
+    /// Master identity itself
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+
+    /// Derivation path for the farm with specified ID
+    pub fn derivation_path(&self, farm_id: FarmId) -> IdentityDerivationPath {
+        IdentityDerivationPath::Farm(farm_id)
+    }
 }
 
 /// `Identity` struct is an abstraction of public & secret key related operations.
@@ -69,7 +371,13 @@ impl Deref for Identity {
 impl Identity {
     pub(crate) const FILE_NAME: &'static str = "identity.bin";
 
-    /// Size of the identity file on disk
+    /// Size of the identity file reserved in allocated space.
+    ///
+    /// This is the size of plain identity file regardless of the format identity is actually
+    /// stored in, since it affects how allocated space is split between plot and piece cache and
+    /// must not change for existing farms. Encrypted identity file is slightly larger (see
+    /// [`Self::encrypted_file_size()`]), difference is negligible and is covered by space reserved
+    /// for farm info.
     pub fn file_size() -> usize {
         IdentityFileContents {
             entropy: vec![0; ENTROPY_LENGTH],
@@ -77,51 +385,158 @@ impl Identity {
         .encoded_size()
     }
 
+    /// Size of the encrypted identity file on disk
+    pub fn encrypted_file_size() -> usize {
+        ENCRYPTED_FILE_PREFIX.len()
+            + EncryptedIdentityFileContents {
+                public_key: [0; schnorrkel::PUBLIC_KEY_LENGTH],
+                salt: [0; SALT_LENGTH],
+                pbkdf2_rounds: 0,
+                nonce: [0; NONCE_LENGTH],
+                ciphertext: vec![0; ENTROPY_LENGTH + TAG_LENGTH],
+            }
+            .encoded_size()
+    }
+
     /// Opens the existing identity, or creates a new one.
-    pub fn open_or_create<B: AsRef<Path>>(base_directory: B) -> Result<Self, IdentityError> {
-        if let Some(identity) = Self::open(base_directory.as_ref())? {
+    ///
+    /// See [`Self::open()`] and [`Self::create()`] for details about `passphrase`.
+    pub fn open_or_create<B: AsRef<Path>>(
+        base_directory: B,
+        passphrase: Option<&IdentityPassphrase>,
+    ) -> Result<Self, IdentityError> {
+        if let Some(identity) = Self::open(base_directory.as_ref(), passphrase)? {
             Ok(identity)
         } else {
-            Self::create(base_directory)
+            Self::create(base_directory, passphrase)
         }
     }
 
     /// Opens the existing identity, returns `Ok(None)` if it doesn't exist.
-    pub fn open<B: AsRef<Path>>(base_directory: B) -> Result<Option<Self>, IdentityError> {
+    ///
+    /// Passphrase is required for encrypted identity. If passphrase is provided for identity
+    /// stored in plain text, identity file will be encrypted with it.
+    pub fn open<B: AsRef<Path>>(
+        base_directory: B,
+        passphrase: Option<&IdentityPassphrase>,
+    ) -> Result<Option<Self>, IdentityError> {
         let identity_file = base_directory.as_ref().join(Self::FILE_NAME);
-        if identity_file.exists() {
-            debug!("Opening existing keypair");
-            let bytes = Zeroizing::new(fs::read(identity_file)?);
-            let IdentityFileContents { entropy } =
-                IdentityFileContents::decode(&mut bytes.as_ref())?;
-
-            Ok(Some(Self {
-                keypair: Zeroizing::new(keypair_from_entropy(&entropy)),
-                entropy: Zeroizing::new(entropy),
-                substrate_ctx: schnorrkel::context::signing_context(REWARD_SIGNING_CONTEXT),
-            }))
-        } else {
+        let Some(contents) = IdentityFile::read(&identity_file)? else {
             debug!("Existing keypair not found");
-            Ok(None)
-        }
+            return Ok(None);
+        };
+
+        debug!("Opening existing keypair");
+        let entropy = match contents {
+            IdentityFile::Plain(IdentityFileContents { entropy }) => {
+                let entropy = Zeroizing::new(entropy);
+
+                if let Some(passphrase) = passphrase {
+                    info!(
+                        path = %identity_file.display(),
+                        "Encrypting identity file with provided passphrase"
+                    );
+                    let keypair = keypair_from_entropy(&entropy);
+                    IdentityFile::Encrypted(EncryptedIdentityFileContents::encrypt(
+                        &entropy,
+                        &keypair.public,
+                        passphrase,
+                    ))
+                    .write(&identity_file)?;
+                }
+
+                entropy
+            }
+            IdentityFile::Encrypted(contents) => {
+                let passphrase = passphrase.ok_or(IdentityError::PassphraseRequired)?;
+                contents.decrypt(passphrase)?
+            }
+        };
+
+        Ok(Some(Self::from_entropy_inner(entropy)))
+    }
+
+    /// Read public key of the existing identity without decrypting it, returns `Ok(None)` if
+    /// identity doesn't exist.
+    pub fn read_public_key<B: AsRef<Path>>(
+        base_directory: B,
+    ) -> Result<Option<PublicKey>, IdentityError> {
+        let identity_file = base_directory.as_ref().join(Self::FILE_NAME);
+
+        Ok(match IdentityFile::read(&identity_file)? {
+            Some(IdentityFile::Plain(IdentityFileContents { entropy })) => {
+                let entropy = Zeroizing::new(entropy);
+                Some(keypair_from_entropy(&entropy).public)
+            }
+            Some(IdentityFile::Encrypted(contents)) => Some(
+                PublicKey::from_bytes(&contents.public_key)
+                    .map_err(IdentityError::InvalidPublicKey)?,
+            ),
+            None => None,
+        })
     }
 
     /// Creates new identity, overrides identity that might already exist.
-    pub fn create<B: AsRef<Path>>(base_directory: B) -> Result<Self, IdentityError> {
+    ///
+    /// Identity is encrypted if passphrase is provided.
+    pub fn create<B: AsRef<Path>>(
+        base_directory: B,
+        passphrase: Option<&IdentityPassphrase>,
+    ) -> Result<Self, IdentityError> {
         let identity_file = base_directory.as_ref().join(Self::FILE_NAME);
         debug!("Generating new keypair");
-        let entropy = rand::random::<[u8; ENTROPY_LENGTH]>().to_vec();
+        let entropy = Zeroizing::new(rand::random::<[u8; ENTROPY_LENGTH]>().to_vec());
+        let identity = Self::from_entropy_inner(entropy);
 
-        let identity_file_contents = IdentityFileContents { entropy };
-        fs::write(identity_file, identity_file_contents.encode())?;
+        identity.store(identity_file, passphrase)?;
 
-        let IdentityFileContents { entropy } = identity_file_contents;
+        Ok(identity)
+    }
 
-        Ok(Self {
-            keypair: Zeroizing::new(keypair_from_entropy(&entropy)),
-            entropy: Zeroizing::new(entropy),
-            substrate_ctx: schnorrkel::context::signing_context(REWARD_SIGNING_CONTEXT),
-        })
+    /// Store identity in `base_directory`, overrides identity that might already exist.
+    ///
+    /// Identity is encrypted if passphrase is provided.
+    pub fn store_to<B: AsRef<Path>>(
+        &self,
+        base_directory: B,
+        passphrase: Option<&IdentityPassphrase>,
+    ) -> Result<(), IdentityError> {
+        self.store(base_directory.as_ref().join(Self::FILE_NAME), passphrase)
+    }
+
+    fn store(
+        &self,
+        identity_file: PathBuf,
+        passphrase: Option<&IdentityPassphrase>,
+    ) -> Result<(), IdentityError> {
+        let contents = match passphrase {
+            Some(passphrase) => IdentityFile::Encrypted(EncryptedIdentityFileContents::encrypt(
+                &self.entropy,
+                self.public_key(),
+                passphrase,
+            )),
+            None => IdentityFile::Plain(IdentityFileContents {
+                entropy: self.entropy.to_vec(),
+            }),
+        };
+        contents.write(&identity_file)?;
+
+        Ok(())
+    }
+
+    /// Derive identity from this (master) identity using provided derivation path.
+    ///
+    /// Derivation is deterministic, the same master identity and derivation path always result in
+    /// the same identity.
+    pub fn derive(&self, derivation_path: &IdentityDerivationPath) -> Self {
+        let mut key_material = Zeroizing::new(self.entropy.to_vec());
+        key_material.extend_from_slice(&derivation_path.key_material());
+        let entropy = Zeroizing::new(blake3::derive_key(
+            IDENTITY_DERIVATION_CONTEXT,
+            &key_material,
+        ));
+
+        Self::from_entropy_inner(Zeroizing::new(entropy.to_vec()))
     }
 
     /// Create identity from given entropy, overrides identity that might already exist.
@@ -135,16 +550,21 @@ impl Identity {
         let identity_file = base_directory.as_ref().join(Self::FILE_NAME);
         debug!("Creating identity from provided entropy");
 
-        let identity_file_contents = IdentityFileContents { entropy };
-        fs::write(identity_file, identity_file_contents.encode())?;
+        let entropy = Zeroizing::new(entropy);
+        IdentityFile::Plain(IdentityFileContents {
+            entropy: entropy.to_vec(),
+        })
+        .write(&identity_file)?;
 
-        let IdentityFileContents { entropy } = identity_file_contents;
+        Ok(Self::from_entropy_inner(entropy))
+    }
 
-        Ok(Self {
+    fn from_entropy_inner(entropy: Zeroizing<Vec<u8>>) -> Self {
+        Self {
             keypair: Zeroizing::new(keypair_from_entropy(&entropy)),
-            entropy: Zeroizing::new(entropy),
+            entropy,
             substrate_ctx: schnorrkel::context::signing_context(REWARD_SIGNING_CONTEXT),
-        })
+        }
     }
 
     /// Returns the public key of the identity.
@@ -0,0 +1,172 @@
+use crate::farm::FarmId;
+use crate::single_disk_farm::identity::{
+    Identity, IdentityDerivationPath, IdentityError, IdentityPassphrase, MasterIdentity,
+};
+use std::assert_matches::assert_matches;
+use std::fs;
+use std::num::NonZeroU32;
+use tempfile::tempdir;
+use ulid::Ulid;
+
+/// Default number of rounds is too slow for tests
+const TEST_PBKDF2_ROUNDS: NonZeroU32 = NonZeroU32::new(1_000).expect("Not zero; qed");
+
+fn test_passphrase(passphrase: &str) -> IdentityPassphrase {
+    IdentityPassphrase::from(passphrase.to_string()).with_pbkdf2_rounds(TEST_PBKDF2_ROUNDS)
+}
+
+#[test]
+fn plain() {
+    let path = tempdir().unwrap();
+
+    assert!(Identity::open(path.as_ref(), None).unwrap().is_none());
+    assert!(Identity::read_public_key(path.as_ref()).unwrap().is_none());
+
+    let identity = Identity::create(path.as_ref(), None).unwrap();
+    let opened_identity = Identity::open(path.as_ref(), None).unwrap().unwrap();
+
+    assert_eq!(identity.entropy(), opened_identity.entropy());
+    assert_eq!(
+        fs::metadata(path.path().join(Identity::FILE_NAME))
+            .unwrap()
+            .len(),
+        Identity::file_size() as u64
+    );
+    assert_eq!(
+        Identity::read_public_key(path.as_ref()).unwrap().unwrap(),
+        *identity.public_key()
+    );
+}
+
+#[test]
+fn encrypted() {
+    let path = tempdir().unwrap();
+    let passphrase = test_passphrase("correct horse battery staple");
+    let wrong_passphrase = test_passphrase("wrong");
+
+    let identity = Identity::create(path.as_ref(), Some(&passphrase)).unwrap();
+
+    let metadata = fs::metadata(path.path().join(Identity::FILE_NAME)).unwrap();
+    assert_eq!(metadata.len(), Identity::encrypted_file_size() as u64);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+
+    // Entropy must not be stored in plain text
+    assert!(!fs::read(path.path().join(Identity::FILE_NAME))
+        .unwrap()
+        .windows(identity.entropy().len())
+        .any(|window| window == identity.entropy()));
+
+    assert_matches!(
+        Identity::open(path.as_ref(), None),
+        Err(IdentityError::PassphraseRequired)
+    );
+    assert_matches!(
+        Identity::open(path.as_ref(), Some(&wrong_passphrase)),
+        Err(IdentityError::InvalidPassphrase)
+    );
+
+    let opened_identity = Identity::open(path.as_ref(), Some(&passphrase))
+        .unwrap()
+        .unwrap();
+    assert_eq!(identity.entropy(), opened_identity.entropy());
+
+    // Number of rounds is read from identity file rather than from passphrase
+    let opened_identity = Identity::open(
+        path.as_ref(),
+        Some(&IdentityPassphrase::from(
+            "correct horse battery staple".to_string(),
+        )),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(identity.entropy(), opened_identity.entropy());
+
+    // Public key is available without passphrase
+    assert_eq!(
+        Identity::read_public_key(path.as_ref()).unwrap().unwrap(),
+        *identity.public_key()
+    );
+}
+
+#[test]
+fn encrypt_existing() {
+    let path = tempdir().unwrap();
+    let passphrase = test_passphrase("passphrase");
+
+    let identity = Identity::create(path.as_ref(), None).unwrap();
+
+    // Opening plain identity with passphrase encrypts it
+    let opened_identity = Identity::open(path.as_ref(), Some(&passphrase))
+        .unwrap()
+        .unwrap();
+    assert_eq!(identity.entropy(), opened_identity.entropy());
+
+    assert_matches!(
+        Identity::open(path.as_ref(), None),
+        Err(IdentityError::PassphraseRequired)
+    );
+    let opened_identity = Identity::open(path.as_ref(), Some(&passphrase))
+        .unwrap()
+        .unwrap();
+    assert_eq!(identity.entropy(), opened_identity.entropy());
+}
+
+#[test]
+fn derive() {
+    let path = tempdir().unwrap();
+    let master_identity = Identity::create(path.as_ref(), None).unwrap();
+
+    let farm_id_1 = FarmId::new();
+    let farm_id_2 = FarmId::new();
+
+    let derived = MasterIdentity::new(master_identity.clone());
+    let derivation_path_1 = derived.derivation_path(farm_id_1);
+    let derivation_path_2 = derived.derivation_path(farm_id_2);
+    assert_eq!(derivation_path_1, IdentityDerivationPath::Farm(farm_id_1));
+
+    let identity_1 = master_identity.derive(&derivation_path_1);
+    let identity_2 = master_identity.derive(&derivation_path_2);
+
+    // Deterministic
+    assert_eq!(
+        identity_1.public_key(),
+        master_identity.derive(&derivation_path_1).public_key()
+    );
+    // Unique for every farm
+    assert_ne!(identity_1.public_key(), identity_2.public_key());
+    assert_ne!(identity_1.public_key(), master_identity.public_key());
+
+    // Derivation uses fixed versioned encoding of farm ID
+    let ulid = Ulid::from_bytes([1; 16]);
+    let fixed = Identity::from_entropy(tempdir().unwrap().as_ref(), vec![2; 32])
+        .unwrap()
+        .derive(&IdentityDerivationPath::Farm(FarmId::Ulid(ulid)));
+    let mut key_material = vec![2; 32];
+    key_material.push(0);
+    key_material.push(0);
+    key_material.extend_from_slice(&[1; 16]);
+    assert_eq!(
+        fixed.entropy(),
+        blake3::derive_key(
+            "subspace-farmer 2024 farm identity derivation",
+            &key_material
+        )
+        .as_slice()
+    );
+
+    // Derived identity can be stored and opened like any other identity
+    let farm_path = tempdir().unwrap();
+    identity_1.store_to(farm_path.as_ref(), None).unwrap();
+    assert_eq!(
+        Identity::open(farm_path.as_ref(), None)
+            .unwrap()
+            .unwrap()
+            .public_key(),
+        identity_1.public_key()
+    );
+}
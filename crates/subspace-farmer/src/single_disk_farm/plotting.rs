@@ -9,6 +9,7 @@ use crate::single_disk_farm::{
 };
 use async_lock::{Mutex as AsyncMutex, RwLock as AsyncRwLock};
 use futures::channel::{mpsc, oneshot};
+use futures::future::join;
 use futures::stream::FuturesOrdered;
 use futures::{select, FutureExt, SinkExt, StreamExt};
 use parity_scale_codec::Encode;
@@ -192,9 +193,68 @@ where
         }
     }
 
+    // No more sectors to plot, wait for sectors that already started plotting to finish plotting
+    while let Some(sector_plotting_result) = sectors_being_plotted.next().await {
+        process_plotting_result(
+            sector_plotting_result?,
+            &mut metadata_header,
+            Arc::clone(&sector_plotting_options.metadata_file),
+        )
+        .await?;
+    }
+
     Ok(())
 }
 
+/// Plot already plotted sectors again one after another, returns once all of them were replotted.
+///
+/// NOTE: Returned future is async, but does blocking operations and should be running in dedicated
+/// thread.
+pub(super) async fn replot_sectors<NC>(
+    sector_indices: &[SectorIndex],
+    metadata_header: PlotMetadataHeader,
+    sectors_metadata: &AsyncRwLock<Vec<SectorMetadataChecksummed>>,
+    sector_plotting_options: SectorPlottingOptions<'_, NC>,
+) -> Result<(), PlottingError>
+where
+    NC: NodeClient,
+{
+    let sectors_being_modified = AsyncRwLock::default();
+    let (mut sectors_to_plot_sender, sectors_to_plot_receiver) = mpsc::channel(0);
+
+    let plotting_fut = plotting(PlottingOptions {
+        metadata_header,
+        sectors_metadata,
+        sectors_being_modified: &sectors_being_modified,
+        sectors_to_plot_receiver,
+        sector_plotting_options,
+    });
+    let send_sectors_fut = async move {
+        let sectors_queued = sector_indices.len();
+        for (index, &sector_index) in sector_indices.iter().enumerate() {
+            let (acknowledgement_sender, acknowledgement_receiver) = oneshot::channel();
+            if let Err(error) = sectors_to_plot_sender
+                .send(SectorToPlot {
+                    sector_index,
+                    progress: index as f32 / sectors_queued as f32 * 100.0,
+                    last_queued: index + 1 == sectors_queued,
+                    acknowledgement_sender,
+                })
+                .await
+            {
+                warn!(%error, "Failed to send sector index for replotting");
+                return;
+            }
+
+            // We do not care if message was sent back or sender was just dropped
+            let _ = acknowledgement_receiver.await;
+        }
+    };
+
+    let (plotting_result, ()) = join(plotting_fut, send_sectors_fut).await;
+    plotting_result
+}
+
 async fn process_plotting_result(
     sector_plotting_result: SectorPlottingResult,
     metadata_header: &mut PlotMetadataHeader,
@@ -552,7 +612,8 @@ async fn plot_single_sector_internal(
                 } => {
                     return Ok((plotted_sector, sector));
                 }
-                SectorPlottingProgress::Error { error } => {
+                SectorPlottingProgress::Error { error }
+                | SectorPlottingProgress::PlotterGone { error } => {
                     if let Some(metrics) = metrics {
                         metrics.sector_plotting_error.inc();
                     }
@@ -1,13 +1,16 @@
 use crate::node_client::NodeClient;
-use crate::single_disk_farm::identity::Identity;
+use crate::reward_signer::RewardSigner;
 use futures::StreamExt;
 use std::future::Future;
+use std::sync::Arc;
+use subspace_core_primitives::PublicKey;
 use subspace_rpc_primitives::{RewardSignatureResponse, RewardSigningInfo};
 use tracing::{info, warn};
 
 pub(super) async fn reward_signing<NC>(
     node_client: NC,
-    identity: Identity,
+    public_key: PublicKey,
+    reward_signer: Arc<dyn RewardSigner>,
 ) -> Result<impl Future<Output = ()>, Box<dyn std::error::Error + Send + Sync>>
 where
     NC: NodeClient,
@@ -17,20 +20,40 @@ where
     let mut reward_signing_info_notifications = node_client.subscribe_reward_signing().await?;
 
     let reward_signing_fut = async move {
-        while let Some(RewardSigningInfo { hash, public_key }) =
-            reward_signing_info_notifications.next().await
+        while let Some(RewardSigningInfo {
+            hash,
+            public_key: requested_public_key,
+        }) = reward_signing_info_notifications.next().await
         {
             // Multiple plots might have solved, only sign with correct one
-            if identity.public_key().to_bytes() != *public_key {
+            if public_key != requested_public_key {
                 continue;
             }
 
-            let signature = identity.sign_reward_hash(&hash);
+            let signature = match reward_signer.sign_reward_hash(&public_key, &hash).await {
+                Ok(Some(signature)) => signature,
+                Ok(None) => {
+                    warn!(
+                        %public_key,
+                        "Reward signer doesn't have the key to sign reward hash 0x{}",
+                        hex::encode(hash),
+                    );
+                    continue;
+                }
+                Err(error) => {
+                    warn!(
+                        %error,
+                        "Failed to sign reward hash 0x{}",
+                        hex::encode(hash),
+                    );
+                    continue;
+                }
+            };
 
             match node_client
                 .submit_reward_signature(RewardSignatureResponse {
                     hash,
-                    signature: Some(signature.to_bytes().into()),
+                    signature: Some(signature),
                 })
                 .await
             {
@@ -0,0 +1,694 @@
+use crate::disk_piece_cache::DiskPieceCache;
+use crate::farm::{FarmId, PieceCacheOffset};
+use crate::node_client::{Error, NodeClient};
+use crate::plotter::{Plotter, SectorPlottingProgress};
+use crate::single_disk_farm::identity::Identity;
+use crate::single_disk_farm::{
+    PlotMetadataHeader, ScrubTarget, SingleDiskFarm, SingleDiskFarmInfo,
+    SingleDiskFarmMigrateError, SingleDiskFarmScrubError, RESERVED_PLOT_METADATA,
+};
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::{stream, SinkExt, Stream};
+use parity_scale_codec::Encode;
+use parking_lot::Mutex;
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::num::NonZeroU64;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use subspace_core_primitives::{
+    Blake3Hash, HistorySize, Piece, PieceIndex, PublicKey, Record, SectorId, SectorIndex,
+    SegmentHeader, SegmentIndex,
+};
+use subspace_farmer_components::file_ext::FileExt;
+use subspace_farmer_components::plotting::PlottedSector;
+use subspace_farmer_components::sector::{sector_size, SectorMetadata, SectorMetadataChecksummed};
+use subspace_farmer_components::{FarmerProtocolInfo, PieceGetter};
+use subspace_rpc_primitives::{
+    FarmerAppInfo, RewardSignatureResponse, RewardSigningInfo, SlotInfo, SolutionResponse,
+};
+use tempfile::tempdir;
+
+const PIECES_IN_SECTOR: u16 = 1;
+const ALLOCATED_SPACE: u64 = 16 * 1024 * 1024;
+const CACHE_PERCENTAGE: u8 = 1;
+const SECTOR_COUNT: SectorIndex = 3;
+const CACHE_CAPACITY: u32 = 3;
+/// History size reported by the node, newer than history size of any sector in test farm
+const HISTORY_SIZE: HistorySize = HistorySize::new(NonZeroU64::new(10).expect("Not zero; qed"));
+
+#[derive(Debug)]
+struct MockNodeClient;
+
+#[async_trait]
+impl NodeClient for MockNodeClient {
+    async fn farmer_app_info(&self) -> Result<FarmerAppInfo, Error> {
+        // Most of these values make no sense, but they are not used by replotting anyway
+        Ok(FarmerAppInfo {
+            genesis_hash: [0; 32],
+            dsn_bootstrap_nodes: Vec::new(),
+            syncing: false,
+            farming_timeout: Duration::default(),
+            protocol_info: FarmerProtocolInfo {
+                history_size: HISTORY_SIZE,
+                max_pieces_in_sector: PIECES_IN_SECTOR,
+                recent_segments: HistorySize::from(SegmentIndex::ZERO),
+                recent_history_fraction: (
+                    HistorySize::from(NonZeroU64::new(1).unwrap()),
+                    HistorySize::from(NonZeroU64::new(10).unwrap()),
+                ),
+                min_sector_lifetime: HistorySize::from(NonZeroU64::new(4).unwrap()),
+            },
+        })
+    }
+
+    async fn subscribe_slot_info(
+        &self,
+    ) -> Result<Pin<Box<dyn Stream<Item = SlotInfo> + Send + 'static>>, Error> {
+        unimplemented!()
+    }
+
+    async fn submit_solution_response(
+        &self,
+        _solution_response: SolutionResponse,
+    ) -> Result<(), Error> {
+        unimplemented!()
+    }
+
+    async fn subscribe_reward_signing(
+        &self,
+    ) -> Result<Pin<Box<dyn Stream<Item = RewardSigningInfo> + Send + 'static>>, Error> {
+        unimplemented!()
+    }
+
+    async fn submit_reward_signature(
+        &self,
+        _reward_signature: RewardSignatureResponse,
+    ) -> Result<(), Error> {
+        unimplemented!()
+    }
+
+    async fn subscribe_archived_segment_headers(
+        &self,
+    ) -> Result<Pin<Box<dyn Stream<Item = SegmentHeader> + Send + 'static>>, Error> {
+        unimplemented!()
+    }
+
+    async fn segment_headers(
+        &self,
+        _segment_indexes: Vec<SegmentIndex>,
+    ) -> Result<Vec<Option<SegmentHeader>>, Error> {
+        unimplemented!()
+    }
+
+    async fn piece(&self, _piece_index: PieceIndex) -> Result<Option<Piece>, Error> {
+        unimplemented!()
+    }
+
+    async fn acknowledge_archived_segment_header(
+        &self,
+        _segment_index: SegmentIndex,
+    ) -> Result<(), Error> {
+        unimplemented!()
+    }
+}
+
+/// Plotter that fills sectors with sector index
+#[derive(Debug, Default)]
+struct MockPlotter {
+    plotted_sectors: Mutex<Vec<SectorIndex>>,
+}
+
+#[async_trait]
+impl Plotter for MockPlotter {
+    async fn has_free_capacity(&self) -> Result<bool, String> {
+        Ok(true)
+    }
+
+    async fn plot_sector(
+        &self,
+        public_key: PublicKey,
+        sector_index: SectorIndex,
+        farmer_protocol_info: FarmerProtocolInfo,
+        pieces_in_sector: u16,
+        _replotting: bool,
+        mut progress_sender: mpsc::Sender<SectorPlottingProgress>,
+    ) {
+        self.plotted_sectors.lock().push(sector_index);
+
+        let plotted_sector = PlottedSector {
+            sector_id: SectorId::new(public_key.hash(), sector_index),
+            sector_index,
+            sector_metadata: plotted_sector_metadata(
+                sector_index,
+                farmer_protocol_info.history_size,
+            ),
+            piece_indexes: vec![PieceIndex::ZERO; usize::from(pieces_in_sector)],
+        };
+        progress_sender
+            .send(SectorPlottingProgress::Finished {
+                plotted_sector,
+                time: Duration::ZERO,
+                sector: Box::pin(stream::iter([Ok(plotted_sector_bytes(sector_index))])),
+            })
+            .await
+            .unwrap();
+    }
+
+    async fn try_plot_sector(
+        &self,
+        public_key: PublicKey,
+        sector_index: SectorIndex,
+        farmer_protocol_info: FarmerProtocolInfo,
+        pieces_in_sector: u16,
+        replotting: bool,
+        progress_sender: mpsc::Sender<SectorPlottingProgress>,
+    ) -> bool {
+        self.plot_sector(
+            public_key,
+            sector_index,
+            farmer_protocol_info,
+            pieces_in_sector,
+            replotting,
+            progress_sender,
+        )
+        .await;
+        true
+    }
+}
+
+/// Piece getter that returns the same random piece for the same piece index
+#[derive(Debug, Default)]
+struct MockPieceGetter {
+    pieces: Mutex<HashMap<PieceIndex, Piece>>,
+}
+
+#[async_trait]
+impl PieceGetter for MockPieceGetter {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(Some(
+            self.pieces
+                .lock()
+                .entry(piece_index)
+                .or_insert_with(|| {
+                    let mut piece = Piece::default();
+                    thread_rng().fill(piece.as_mut());
+                    piece
+                })
+                .clone(),
+        ))
+    }
+}
+
+fn plotted_sector_metadata(
+    sector_index: SectorIndex,
+    history_size: HistorySize,
+) -> SectorMetadataChecksummed {
+    SectorMetadataChecksummed::from(SectorMetadata {
+        sector_index,
+        pieces_in_sector: PIECES_IN_SECTOR,
+        s_bucket_sizes: Box::new([1; Record::NUM_S_BUCKETS]),
+        history_size,
+    })
+}
+
+fn plotted_sector_bytes(sector_index: SectorIndex) -> Vec<u8> {
+    vec![sector_index as u8 + 1; sector_size(PIECES_IN_SECTOR)]
+}
+
+fn sector_offset(sector_index: SectorIndex) -> u64 {
+    u64::from(sector_index) * sector_size(PIECES_IN_SECTOR) as u64
+}
+
+fn sector_metadata_offset(sector_index: SectorIndex) -> u64 {
+    RESERVED_PLOT_METADATA
+        + u64::from(sector_index) * SectorMetadataChecksummed::encoded_size() as u64
+}
+
+/// Create farm with random sectors, returns contents of each sector with checksum
+fn create_farm(directory: &Path) -> Vec<Vec<u8>> {
+    let identity = Identity::create(directory, None).unwrap();
+    SingleDiskFarmInfo::new(
+        FarmId::new(),
+        [0; 32],
+        *identity.public_key(),
+        PIECES_IN_SECTOR,
+        ALLOCATED_SPACE,
+        None,
+    )
+    .store_to(directory)
+    .unwrap();
+
+    let sector_size = sector_size(PIECES_IN_SECTOR);
+    let sectors = (0..SECTOR_COUNT)
+        .map(|_sector_index| {
+            let mut sector = vec![0u8; sector_size];
+            let (sector_bytes, checksum) = sector.split_at_mut(sector_size - Blake3Hash::SIZE);
+            thread_rng().fill(sector_bytes);
+            checksum.copy_from_slice(blake3::hash(sector_bytes).as_bytes());
+            sector
+        })
+        .collect::<Vec<_>>();
+    fs::write(directory.join(SingleDiskFarm::PLOT_FILE), sectors.concat()).unwrap();
+
+    let mut metadata = PlotMetadataHeader {
+        version: SingleDiskFarm::SUPPORTED_PLOT_VERSION,
+        plotted_sector_count: SECTOR_COUNT,
+    }
+    .encode();
+    metadata.resize(sector_metadata_offset(SECTOR_COUNT) as usize, 0);
+    for sector_index in 0..SECTOR_COUNT {
+        let sector_metadata =
+            plotted_sector_metadata(sector_index, HistorySize::from(SegmentIndex::ONE)).encode();
+        let offset = sector_metadata_offset(sector_index) as usize;
+        metadata[offset..][..sector_metadata.len()].copy_from_slice(&sector_metadata);
+    }
+    fs::write(directory.join(SingleDiskFarm::METADATA_FILE), metadata).unwrap();
+
+    sectors
+}
+
+fn read_sector(directory: &Path, sector_index: SectorIndex) -> Vec<u8> {
+    let plot_file = fs::File::open(directory.join(SingleDiskFarm::PLOT_FILE)).unwrap();
+    let mut sector = vec![0u8; sector_size(PIECES_IN_SECTOR)];
+    plot_file
+        .read_exact_at(&mut sector, sector_offset(sector_index))
+        .unwrap();
+    sector
+}
+
+fn corrupt_sector(directory: &Path, sector_index: SectorIndex) {
+    let plot_file = fs::OpenOptions::new()
+        .write(true)
+        .open(directory.join(SingleDiskFarm::PLOT_FILE))
+        .unwrap();
+    plot_file
+        .write_all_at(&[0xff; 16], sector_offset(sector_index) + 1024)
+        .unwrap();
+}
+
+fn migrate(source: &Path, destination: &Path) {
+    SingleDiskFarm::migrate(source, destination, None, CACHE_PERCENTAGE, false).unwrap();
+}
+
+fn assert_sectors_copied(source: &Path, destination: &Path, sectors: &[Vec<u8>]) {
+    let source_sectors_metadata = SingleDiskFarm::read_all_sectors_metadata(source).unwrap();
+    let sectors_metadata = SingleDiskFarm::read_all_sectors_metadata(destination).unwrap();
+    assert_eq!(sectors_metadata.len(), sectors.len());
+
+    for (sector_index, sector) in (0..).zip(sectors) {
+        assert_eq!(&read_sector(destination, sector_index), sector);
+        assert_eq!(
+            sectors_metadata[usize::from(sector_index)].encode(),
+            source_sectors_metadata[usize::from(sector_index)].encode()
+        );
+    }
+}
+
+#[test]
+fn migrate_farm() {
+    let source = tempdir().unwrap();
+    let destination = tempdir().unwrap();
+    let sectors = create_farm(source.path());
+
+    migrate(source.path(), destination.path());
+
+    assert_sectors_copied(source.path(), destination.path(), &sectors);
+    let source_info = SingleDiskFarmInfo::load_from(source.path())
+        .unwrap()
+        .unwrap();
+    let destination_info = SingleDiskFarmInfo::load_from(destination.path())
+        .unwrap()
+        .unwrap();
+    assert_eq!(destination_info.id(), source_info.id());
+    assert_eq!(
+        fs::read(destination.path().join(Identity::FILE_NAME)).unwrap(),
+        fs::read(source.path().join(Identity::FILE_NAME)).unwrap()
+    );
+
+    // Already migrated farm is left untouched
+    migrate(source.path(), destination.path());
+    assert_sectors_copied(source.path(), destination.path(), &sectors);
+}
+
+#[test]
+fn migrate_farm_corrupted_sector() {
+    let source = tempdir().unwrap();
+    let destination = tempdir().unwrap();
+    let sectors = create_farm(source.path());
+    let corrupted_sector_index = 1;
+    corrupt_sector(source.path(), corrupted_sector_index);
+
+    migrate(source.path(), destination.path());
+
+    let sectors_metadata = SingleDiskFarm::read_all_sectors_metadata(destination.path()).unwrap();
+    assert_eq!(sectors_metadata.len(), sectors.len());
+    for (sector_index, sector) in (0..).zip(&sectors) {
+        let sector_metadata = &sectors_metadata[usize::from(sector_index)];
+        let copied_sector = read_sector(destination.path(), sector_index);
+
+        if sector_index == corrupted_sector_index {
+            // Corrupted sector is replaced with dummy expired sector, such that it is plotted
+            // again, but checksum is still consistent with copied bytes
+            assert_eq!(
+                sector_metadata.history_size,
+                HistorySize::from(SegmentIndex::ZERO)
+            );
+            assert!(sector_metadata.s_bucket_sizes.iter().all(|&size| size == 0));
+            let (sector_bytes, checksum) =
+                copied_sector.split_at(copied_sector.len() - Blake3Hash::SIZE);
+            assert_eq!(blake3::hash(sector_bytes).as_bytes(), checksum);
+        } else {
+            assert_eq!(&copied_sector, sector);
+            assert_eq!(
+                sector_metadata.history_size,
+                HistorySize::from(SegmentIndex::ONE)
+            );
+        }
+    }
+}
+
+#[test]
+fn migrate_farm_corrupted_sector_metadata() {
+    let source = tempdir().unwrap();
+    let destination = tempdir().unwrap();
+    let sectors = create_farm(source.path());
+    let corrupted_sector_index = 2;
+    {
+        let metadata_file = fs::OpenOptions::new()
+            .write(true)
+            .open(source.path().join(SingleDiskFarm::METADATA_FILE))
+            .unwrap();
+        metadata_file
+            .write_all_at(&[0xff; 16], sector_metadata_offset(corrupted_sector_index))
+            .unwrap();
+    }
+
+    migrate(source.path(), destination.path());
+
+    let sectors_metadata = SingleDiskFarm::read_all_sectors_metadata(destination.path()).unwrap();
+    assert_eq!(sectors_metadata.len(), sectors.len());
+    for (sector_index, sector) in (0..).zip(&sectors) {
+        let sector_metadata = &sectors_metadata[usize::from(sector_index)];
+        assert_eq!(sector_metadata.sector_index, sector_index);
+
+        if sector_index == corrupted_sector_index {
+            assert_eq!(
+                sector_metadata.history_size,
+                HistorySize::from(SegmentIndex::ZERO)
+            );
+        } else {
+            assert_eq!(&read_sector(destination.path(), sector_index), sector);
+            assert_eq!(
+                sector_metadata.history_size,
+                HistorySize::from(SegmentIndex::ONE)
+            );
+        }
+    }
+}
+
+#[test]
+fn migrate_farm_locks_destination() {
+    let source = tempdir().unwrap();
+    let destination = tempdir().unwrap();
+    create_farm(source.path());
+
+    // Another migration into the same destination is in progress
+    fs::write(destination.path().join(SingleDiskFarmInfo::FILE_NAME), b"").unwrap();
+    let destination_lock = SingleDiskFarmInfo::try_lock(destination.path()).unwrap();
+
+    assert!(matches!(
+        SingleDiskFarm::migrate(
+            source.path(),
+            destination.path(),
+            None,
+            CACHE_PERCENTAGE,
+            false
+        ),
+        Err(SingleDiskFarmMigrateError::LikelyAlreadyInUse(_))
+    ));
+    assert!(!destination.path().join(SingleDiskFarm::PLOT_FILE).exists());
+
+    drop(destination_lock);
+    migrate(source.path(), destination.path());
+    assert!(SingleDiskFarmInfo::load_from(destination.path())
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+fn migrate_farm_resume() {
+    let source = tempdir().unwrap();
+    let destination = tempdir().unwrap();
+    let sectors = create_farm(source.path());
+
+    migrate(source.path(), destination.path());
+
+    // Simulate migration that was interrupted after copying the first sector and part of the
+    // second one
+    fs::write(destination.path().join(SingleDiskFarmInfo::FILE_NAME), b"").unwrap();
+    {
+        let metadata_file = fs::OpenOptions::new()
+            .write(true)
+            .open(destination.path().join(SingleDiskFarm::METADATA_FILE))
+            .unwrap();
+        let metadata_header = PlotMetadataHeader {
+            version: SingleDiskFarm::SUPPORTED_PLOT_VERSION,
+            plotted_sector_count: 1,
+        };
+        metadata_file
+            .write_all_at(&metadata_header.encode(), 0)
+            .unwrap();
+        metadata_file
+            .write_all_at(
+                &vec![0; SectorMetadataChecksummed::encoded_size()],
+                sector_metadata_offset(1),
+            )
+            .unwrap();
+    }
+    corrupt_sector(destination.path(), 1);
+    corrupt_sector(destination.path(), 2);
+    // Sector that was already copied is not read again from source
+    let copied_sector = sectors[0].clone();
+    corrupt_sector(source.path(), 0);
+
+    migrate(source.path(), destination.path());
+
+    assert_eq!(read_sector(destination.path(), 0), copied_sector);
+    let sectors_metadata = SingleDiskFarm::read_all_sectors_metadata(destination.path()).unwrap();
+    assert_eq!(sectors_metadata.len(), sectors.len());
+    assert_eq!(
+        sectors_metadata[0].history_size,
+        HistorySize::from(SegmentIndex::ONE)
+    );
+    for (sector_index, sector) in (1..).zip(&sectors[1..]) {
+        assert_eq!(&read_sector(destination.path(), sector_index), sector);
+        assert_eq!(
+            sectors_metadata[usize::from(sector_index)].history_size,
+            HistorySize::from(SegmentIndex::ONE)
+        );
+    }
+    assert!(SingleDiskFarmInfo::load_from(destination.path())
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+fn store_info_while_locked() {
+    let directory = tempdir().unwrap();
+    create_farm(directory.path());
+    let single_disk_farm_info = SingleDiskFarmInfo::load_from(directory.path())
+        .unwrap()
+        .unwrap();
+
+    let lock = SingleDiskFarmInfo::try_lock(directory.path()).unwrap();
+    // Shorter than the original info, such that leftovers of the old contents would be noticed
+    let allocated_space = ALLOCATED_SPACE / 1024;
+    lock.store(&SingleDiskFarmInfo::new(
+        *single_disk_farm_info.id(),
+        *single_disk_farm_info.genesis_hash(),
+        *single_disk_farm_info.public_key(),
+        single_disk_farm_info.pieces_in_sector(),
+        allocated_space,
+        single_disk_farm_info.identity_derivation_path().copied(),
+    ))
+    .unwrap();
+
+    assert_eq!(
+        SingleDiskFarmInfo::load_from(directory.path())
+            .unwrap()
+            .unwrap()
+            .allocated_space(),
+        allocated_space
+    );
+    // Farm is still locked after info was stored
+    assert!(SingleDiskFarmInfo::try_lock(directory.path()).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replot_corrupted_sectors() {
+    let directory = tempdir().unwrap();
+    let sectors = create_farm(directory.path());
+    let corrupted_sector_index = 1;
+    corrupt_sector(directory.path(), corrupted_sector_index);
+
+    let report = SingleDiskFarm::scrub(directory.path(), false, ScrubTarget::Plot, false).unwrap();
+    let replaced_sectors = report
+        .corrupted_sectors
+        .iter()
+        .filter(|corrupted_sector| corrupted_sector.replaced)
+        .map(|corrupted_sector| corrupted_sector.sector_index)
+        .collect::<Vec<_>>();
+    assert_eq!(replaced_sectors, vec![corrupted_sector_index]);
+
+    // Farm is in use, sectors can't be replotted
+    let plotter = Arc::new(MockPlotter::default());
+    {
+        let _lock = SingleDiskFarmInfo::try_lock(directory.path()).unwrap();
+        let result = SingleDiskFarm::replot_sectors(
+            directory.path(),
+            &replaced_sectors,
+            Arc::clone(&plotter) as Arc<dyn Plotter + Send + Sync>,
+            &MockNodeClient,
+            false,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(SingleDiskFarmScrubError::LikelyAlreadyInUse(_))
+        ));
+        assert!(plotter.plotted_sectors.lock().is_empty());
+    }
+
+    let replotted_sectors = SingleDiskFarm::replot_sectors(
+        directory.path(),
+        &replaced_sectors,
+        Arc::clone(&plotter) as Arc<dyn Plotter + Send + Sync>,
+        &MockNodeClient,
+        false,
+    )
+    .await
+    .unwrap();
+    assert_eq!(replotted_sectors, replaced_sectors);
+    assert_eq!(*plotter.plotted_sectors.lock(), replaced_sectors);
+
+    let sectors_metadata = SingleDiskFarm::read_all_sectors_metadata(directory.path()).unwrap();
+    assert_eq!(sectors_metadata.len(), sectors.len());
+    for (sector_index, sector) in (0..).zip(&sectors) {
+        let sector_metadata = &sectors_metadata[usize::from(sector_index)];
+        if sector_index == corrupted_sector_index {
+            assert_eq!(
+                read_sector(directory.path(), sector_index),
+                plotted_sector_bytes(sector_index)
+            );
+            assert_eq!(
+                sector_metadata.encode(),
+                plotted_sector_metadata(sector_index, HISTORY_SIZE).encode()
+            );
+        } else {
+            assert_eq!(&read_sector(directory.path(), sector_index), sector);
+            assert_eq!(
+                sector_metadata.history_size,
+                HistorySize::from(SegmentIndex::ONE)
+            );
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn refetch_corrupted_cache_elements() {
+    let directory = tempdir().unwrap();
+    create_farm(directory.path());
+    let piece_getter = MockPieceGetter::default();
+    {
+        let piece_cache =
+            DiskPieceCache::open(directory.path(), CACHE_CAPACITY, None, None).unwrap();
+        for offset in 0..CACHE_CAPACITY {
+            let piece_index = PieceIndex::from(u64::from(offset));
+            let piece = piece_getter.get_piece(piece_index).await.unwrap().unwrap();
+            piece_cache
+                .write_piece(PieceCacheOffset(offset), piece_index, &piece)
+                .unwrap();
+        }
+    }
+
+    // Piece bytes of the first element and piece index of the second element are corrupted, the
+    // third element is intact
+    {
+        let cache_file = fs::OpenOptions::new()
+            .write(true)
+            .open(directory.path().join(DiskPieceCache::FILE_NAME))
+            .unwrap();
+        let element_size = u64::from(DiskPieceCache::element_size());
+        cache_file
+            .write_all_at(&[0xff; 16], PieceIndex::SIZE as u64 + 1024)
+            .unwrap();
+        cache_file.write_all_at(&[0xff; 2], element_size).unwrap();
+    }
+
+    let mut report =
+        SingleDiskFarm::scrub(directory.path(), false, ScrubTarget::Cache, false).unwrap();
+    assert_eq!(
+        report
+            .corrupted_cache_elements
+            .iter()
+            .map(|corrupted_element| corrupted_element.offset)
+            .collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+    assert!(report
+        .corrupted_cache_elements
+        .iter()
+        .all(|corrupted_element| corrupted_element.replaced));
+
+    // Farm is in use, cache can't be written to
+    {
+        let _lock = SingleDiskFarmInfo::try_lock(directory.path()).unwrap();
+        let result = SingleDiskFarm::refetch_cache_elements(
+            directory.path(),
+            &mut report.corrupted_cache_elements,
+            &piece_getter,
+            false,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(SingleDiskFarmScrubError::LikelyAlreadyInUse(_))
+        ));
+    }
+
+    SingleDiskFarm::refetch_cache_elements(
+        directory.path(),
+        &mut report.corrupted_cache_elements,
+        &piece_getter,
+        false,
+    )
+    .await
+    .unwrap();
+
+    // Only element whose piece index can be trusted is restored
+    assert!(report.corrupted_cache_elements[0].refetched);
+    assert!(!report.corrupted_cache_elements[1].refetched);
+
+    let piece_cache = DiskPieceCache::open(directory.path(), CACHE_CAPACITY, None, None).unwrap();
+    let piece_index = PieceIndex::ZERO;
+    assert_eq!(
+        piece_cache.read_piece(PieceCacheOffset(0)).unwrap(),
+        Some((
+            piece_index,
+            piece_getter.get_piece(piece_index).await.unwrap().unwrap()
+        ))
+    );
+    assert_eq!(piece_cache.read_piece(PieceCacheOffset(1)).unwrap(), None);
+    assert!(piece_cache
+        .read_piece(PieceCacheOffset(2))
+        .unwrap()
+        .is_some());
+}
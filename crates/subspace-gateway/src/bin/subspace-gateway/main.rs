@@ -0,0 +1,188 @@
+//! Subspace object gateway
+
+use anyhow::anyhow;
+use clap::{Parser, ValueHint};
+use futures::{select, FutureExt};
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::WsClientBuilder;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::num::NonZeroUsize;
+use subspace_core_primitives::crypto::kzg::{embedded_kzg_settings, Kzg};
+use subspace_core_primitives::{Record, SegmentIndex};
+use subspace_data_retrieval::object_fetcher::ObjectFetcher;
+use subspace_data_retrieval::piece_reconstruction::{PieceReconstruction, SourceFirst};
+use subspace_erasure_coding::ErasureCoding;
+use subspace_gateway::object_mappings::NodeObjectMappings;
+use subspace_gateway::piece_getter::DsnPieceGetter;
+use subspace_gateway::server::start_object_gateway_server;
+use subspace_networking::libp2p::identity::Keypair;
+use subspace_networking::libp2p::kad::Mode;
+use subspace_networking::libp2p::multiaddr::Protocol;
+use subspace_networking::libp2p::Multiaddr;
+use subspace_networking::utils::piece_provider::{NoPieceValidator, PieceProvider};
+use subspace_networking::{construct, Config, KademliaMode};
+use subspace_rpc_primitives::FarmerAppInfo;
+use tracing::{info, Level};
+use tracing_subscriber::fmt::Subscriber;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Default maximum size of an object that gateway will fetch and serve.
+const DEFAULT_MAX_OBJECT_LEN: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Parser)]
+#[clap(about, version)]
+enum Command {
+    /// Run object gateway
+    Run(RunArgs),
+}
+
+/// Arguments for object gateway
+#[derive(Debug, Parser)]
+struct RunArgs {
+    /// WebSocket RPC URL of the Subspace node to connect to, used to look up object mappings.
+    ///
+    /// Node must be started with `--index-object-mappings`.
+    #[arg(long, value_hint = ValueHint::Url, default_value = "ws://127.0.0.1:9944")]
+    node_rpc_url: String,
+    /// Multiaddrs of DSN bootstrap nodes to connect to on startup, multiple are supported.
+    ///
+    /// Defaults to DSN bootstrap nodes of the node the gateway is connected to.
+    #[arg(long)]
+    dsn_bootstrap_nodes: Vec<Multiaddr>,
+    /// Multiaddr to listen on for DSN networking, multiple are supported
+    #[arg(long, default_values_t = [
+        Multiaddr::from(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+            .with(Protocol::Tcp(0)),
+        Multiaddr::from(IpAddr::V6(Ipv6Addr::UNSPECIFIED))
+            .with(Protocol::Tcp(0))
+    ])]
+    dsn_listen_on: Vec<Multiaddr>,
+    /// Determines whether we allow keeping non-global (private, shared, loopback..) addresses in
+    /// Kademlia DHT.
+    #[arg(long, default_value_t = false)]
+    dsn_allow_private_ips: bool,
+    /// Address to listen on for HTTP requests, multiple are supported
+    #[arg(long, default_value = "127.0.0.1:3000")]
+    http_listen_on: Vec<SocketAddr>,
+    /// Maximum size of an object in bytes that gateway will fetch and serve
+    #[arg(long, default_value_t = DEFAULT_MAX_OBJECT_LEN)]
+    max_object_len: usize,
+}
+
+fn init_logging() {
+    // set default log to info if the RUST_LOG is not set.
+    let env_filter = EnvFilter::builder()
+        .with_default_directive(Level::INFO.into())
+        .from_env_lossy();
+
+    let builder = Subscriber::builder().with_env_filter(env_filter).finish();
+
+    builder.init()
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    init_logging();
+
+    let command = Command::parse();
+
+    match command {
+        Command::Run(run_args) => run(run_args).await,
+    }
+}
+
+async fn run(
+    RunArgs {
+        node_rpc_url,
+        mut dsn_bootstrap_nodes,
+        dsn_listen_on,
+        dsn_allow_private_ips,
+        http_listen_on,
+        max_object_len,
+    }: RunArgs,
+) -> anyhow::Result<()> {
+    info!(url = %node_rpc_url, "Connecting to node RPC");
+    let node_rpc_client = WsClientBuilder::default()
+        .build(&node_rpc_url)
+        .await
+        .map_err(|error| anyhow!("Failed to connect to node RPC: {error}"))?;
+
+    let farmer_app_info: FarmerAppInfo = node_rpc_client
+        .request("subspace_getFarmerAppInfo", rpc_params![])
+        .await
+        .map_err(|error| anyhow!("Failed to get farmer app info: {error}"))?;
+
+    if dsn_bootstrap_nodes.is_empty() {
+        dsn_bootstrap_nodes = farmer_app_info.dsn_bootstrap_nodes;
+    }
+
+    let config = Config {
+        listen_on: dsn_listen_on,
+        allow_non_global_addresses_in_dht: dsn_allow_private_ips,
+        bootstrap_addresses: dsn_bootstrap_nodes,
+        kademlia_mode: KademliaMode::Static(Mode::Client),
+        ..Config::new(
+            hex::encode(farmer_app_info.genesis_hash),
+            Keypair::generate_ed25519(),
+            (),
+            None,
+        )
+    };
+    let (node, mut node_runner) =
+        construct(config).map_err(|error| anyhow!("Failed to configure networking: {error}"))?;
+
+    // Pieces are not validated individually, instead the hash of the assembled object is checked
+    // against the requested hash before object is returned to the client
+    let piece_provider = PieceProvider::new(node, None::<NoPieceValidator>);
+    let erasure_coding = ErasureCoding::new(
+        NonZeroUsize::new(Record::NUM_S_BUCKETS.next_power_of_two().ilog2() as usize)
+            .expect("Not zero; qed"),
+    )
+    .map_err(|error| anyhow!("Failed to instantiate erasure coding: {error}"))?;
+    let piece_reconstruction = PieceReconstruction::new(
+        Kzg::new(embedded_kzg_settings()),
+        erasure_coding,
+        SourceFirst,
+    );
+    let object_fetcher = ObjectFetcher::new(
+        DsnPieceGetter::new(piece_provider),
+        Some(piece_reconstruction),
+        Some(max_object_len),
+    );
+
+    let object_mappings_cursor: Option<SegmentIndex> = node_rpc_client
+        .request("subspace_getObjectMappingsCursor", rpc_params![])
+        .await
+        .map_err(|error| {
+            anyhow!(
+                "Failed to get object mappings cursor, make sure node is started with \
+                `--index-object-mappings`: {error}"
+            )
+        })?;
+    info!(
+        ?object_mappings_cursor,
+        "Object mappings are indexed up to segment"
+    );
+
+    let server_fut = start_object_gateway_server(
+        http_listen_on,
+        NodeObjectMappings::new(node_rpc_client),
+        object_fetcher,
+    )?;
+
+    select! {
+        _ = node_runner.run().fuse() => {
+            info!("DSN network runner exited.");
+        },
+        result = server_fut.fuse() => {
+            result?;
+        },
+        _ = tokio::signal::ctrl_c().fuse() => {
+            info!("Received shutdown signal, exiting...");
+        },
+    }
+
+    Ok(())
+}
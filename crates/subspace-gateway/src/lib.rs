@@ -0,0 +1,13 @@
+#![warn(rust_2018_idioms, missing_debug_implementations, missing_docs)]
+
+//! `subspace-gateway` serves objects stored in the archived history of the Subspace Distributed
+//! Storage Network over HTTP.
+//!
+//! Objects are requested by their Blake3 hash, which is resolved into a location in the archived
+//! history using [`object_mappings::ObjectMappings`]. Object data is then fetched from the DSN with
+//! [`subspace_data_retrieval::object_fetcher::ObjectFetcher`], and only returned to the client
+//! after its hash is verified.
+
+pub mod object_mappings;
+pub mod piece_getter;
+pub mod server;
@@ -0,0 +1,65 @@
+//! Object mappings lookup.
+//!
+//! Object mappings resolve object hashes into the location of the object (piece index and offset)
+//! in the archived history.
+
+use async_trait::async_trait;
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::WsClient;
+use std::fmt;
+use std::sync::Arc;
+use subspace_core_primitives::objects::GlobalObject;
+use subspace_core_primitives::Blake3Hash;
+use tracing::error;
+
+/// Something that can resolve object hashes into their location in the archived history
+#[async_trait]
+pub trait ObjectMappings: fmt::Debug + Send + Sync + 'static {
+    /// Get object mapping by object hash, returns `None` if the object is not known
+    async fn get_object_mapping(&self, hash: &Blake3Hash) -> Option<GlobalObject>;
+}
+
+#[async_trait]
+impl<OM> ObjectMappings for Arc<OM>
+where
+    OM: ObjectMappings + ?Sized,
+{
+    #[inline]
+    async fn get_object_mapping(&self, hash: &Blake3Hash) -> Option<GlobalObject> {
+        self.as_ref().get_object_mapping(hash).await
+    }
+}
+
+/// Object mappings looked up in the object mappings index of the node.
+///
+/// Node must be started with `--index-object-mappings`.
+#[derive(Debug)]
+pub struct NodeObjectMappings {
+    client: WsClient,
+}
+
+#[async_trait]
+impl ObjectMappings for NodeObjectMappings {
+    async fn get_object_mapping(&self, hash: &Blake3Hash) -> Option<GlobalObject> {
+        match self
+            .client
+            .request("subspace_getObjectMapping", rpc_params![hash])
+            .await
+        {
+            Ok(maybe_object) => maybe_object,
+            Err(error) => {
+                error!(?hash, %error, "Failed to get object mapping from the node");
+
+                None
+            }
+        }
+    }
+}
+
+impl NodeObjectMappings {
+    /// Create new instance using node RPC client
+    pub fn new(client: WsClient) -> Self {
+        Self { client }
+    }
+}
@@ -0,0 +1,52 @@
+//! Piece getter that retrieves pieces from the DSN for object assembling.
+
+use async_trait::async_trait;
+use std::fmt;
+use subspace_core_primitives::{Piece, PieceIndex};
+use subspace_data_retrieval::object_fetcher::{BoxError, ObjectPieceGetter};
+use subspace_networking::utils::piece_provider::{PieceProvider, PieceValidator};
+
+/// Number of random walking rounds when searching for a piece in archival storage
+const MAX_RANDOM_WALK_ROUNDS: usize = 15;
+
+/// Piece getter that retrieves pieces from piece caches (L2) in the DSN and falls back to archival
+/// storage (L1) when piece is not found there.
+pub struct DsnPieceGetter<PV> {
+    piece_provider: PieceProvider<PV>,
+}
+
+impl<PV> fmt::Debug for DsnPieceGetter<PV> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DsnPieceGetter")
+            .field("piece_provider", &self.piece_provider)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<PV> ObjectPieceGetter for DsnPieceGetter<PV>
+where
+    PV: PieceValidator,
+{
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        if let Some(piece) = self.piece_provider.get_piece_from_cache(piece_index).await {
+            return Ok(Some(piece));
+        }
+
+        Ok(self
+            .piece_provider
+            .get_piece_from_archival_storage(piece_index, MAX_RANDOM_WALK_ROUNDS)
+            .await)
+    }
+}
+
+impl<PV> DsnPieceGetter<PV>
+where
+    PV: PieceValidator,
+{
+    /// Create new instance
+    pub fn new(piece_provider: PieceProvider<PV>) -> Self {
+        Self { piece_provider }
+    }
+}
@@ -0,0 +1,236 @@
+//! HTTP server that serves objects by their hash.
+//!
+//! The only endpoint is `GET /data/{blake3_hash}`. The whole object is fetched and its hash is
+//! checked against the requested hash before any bytes are sent to the client, so clients never
+//! get incorrect data. A single byte range can be requested with the `Range` header, multiple
+//! ranges are not supported and result in the whole object being returned.
+
+#[cfg(test)]
+mod tests;
+
+use crate::object_mappings::ObjectMappings;
+use actix_web::http::{header, StatusCode};
+use actix_web::web::{Data, Path};
+use actix_web::{get, App, HttpRequest, HttpResponse, HttpServer};
+use bytes::Bytes;
+use hex::FromHex;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use subspace_core_primitives::objects::GlobalObject;
+use subspace_core_primitives::Blake3Hash;
+use subspace_data_retrieval::object_fetcher::ObjectFetcher;
+use tracing::{debug, error, info, warn};
+
+/// Number of HTTP server workers
+const SERVER_WORKERS: usize = 2;
+
+/// Inclusive range of bytes of an object
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct ByteRange {
+    /// First byte of the range
+    pub(crate) start: usize,
+    /// Last byte of the range (inclusive)
+    pub(crate) end: usize,
+}
+
+/// Result of interpreting `Range` header for an object of a particular length
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum RangeRequest {
+    /// Whole object should be returned, either because no range was requested or because range
+    /// was invalid or unsupported (in which case it must be ignored according to RFC 9110)
+    Full,
+    /// Part of the object should be returned
+    Partial(ByteRange),
+    /// Range is syntactically valid, but can't be satisfied for this object
+    Unsatisfiable,
+}
+
+/// Interpret `Range` header value for an object of `len` bytes.
+///
+/// Only a single range in `bytes` units is supported.
+pub(crate) fn parse_range(range: &str, len: usize) -> RangeRequest {
+    let Some(range) = range.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+    if range.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = range.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+
+    match (start.trim(), end.trim()) {
+        ("", suffix_length) => {
+            let Ok(suffix_length) = suffix_length.parse::<usize>() else {
+                return RangeRequest::Full;
+            };
+            if suffix_length == 0 || len == 0 {
+                return RangeRequest::Unsatisfiable;
+            }
+
+            RangeRequest::Partial(ByteRange {
+                start: len.saturating_sub(suffix_length),
+                end: len - 1,
+            })
+        }
+        (start, end) => {
+            let Ok(start) = start.parse::<usize>() else {
+                return RangeRequest::Full;
+            };
+            let end = if end.is_empty() {
+                usize::MAX
+            } else {
+                match end.parse::<usize>() {
+                    Ok(end) => end,
+                    Err(_) => {
+                        return RangeRequest::Full;
+                    }
+                }
+            };
+            if end < start {
+                return RangeRequest::Full;
+            }
+            if start >= len {
+                return RangeRequest::Unsatisfiable;
+            }
+
+            RangeRequest::Partial(ByteRange {
+                start,
+                end: end.min(len - 1),
+            })
+        }
+    }
+}
+
+/// Shared state of the server
+struct ServerState {
+    object_mappings: Arc<dyn ObjectMappings>,
+    object_fetcher: ObjectFetcher,
+}
+
+impl fmt::Debug for ServerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerState")
+            .field("object_mappings", &self.object_mappings)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Fetch the whole object and verify its hash.
+///
+/// Returns `None` if the object can't be fetched or its hash doesn't match the requested hash.
+async fn fetch_verified_object(
+    object_fetcher: &ObjectFetcher,
+    hash: Blake3Hash,
+    object: GlobalObject,
+) -> Option<Bytes> {
+    let data = match object_fetcher
+        .fetch_object(object.piece_index, object.offset)
+        .await
+    {
+        Ok(data) => data,
+        Err(error) => {
+            warn!(?hash, ?object, %error, "Failed to fetch object");
+
+            return None;
+        }
+    };
+
+    let data_hash = Blake3Hash::from(*blake3::hash(&data).as_bytes());
+    if data_hash != hash {
+        error!(
+            ?hash,
+            ?data_hash,
+            ?object,
+            len = %data.len(),
+            "Fetched object hash doesn't match requested hash, object mapping is likely invalid"
+        );
+
+        return None;
+    }
+
+    Some(Bytes::from(data))
+}
+
+#[get("/data/{hash}")]
+async fn get_data(
+    state: Data<ServerState>,
+    hash: Path<String>,
+    request: HttpRequest,
+) -> HttpResponse {
+    let hash = match Blake3Hash::from_hex(hash.as_str()) {
+        Ok(hash) => hash,
+        Err(error) => {
+            debug!(hash = %hash.as_str(), %error, "Invalid object hash requested");
+
+            return HttpResponse::BadRequest().body(format!("Invalid object hash: {error}"));
+        }
+    };
+
+    let Some(object) = state.object_mappings.get_object_mapping(&hash).await else {
+        debug!(?hash, "Object mapping not found");
+
+        return HttpResponse::NotFound().finish();
+    };
+
+    // Nothing is sent to the client until the whole object was fetched and verified, including
+    // for range requests
+    let Some(data) = fetch_verified_object(&state.object_fetcher, hash, object).await else {
+        return HttpResponse::BadGateway().body("Failed to fetch object");
+    };
+
+    let len = data.len();
+    let range_request = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+        .map(|range| parse_range(range, len))
+        .unwrap_or(RangeRequest::Full);
+
+    debug!(?hash, ?object, %len, ?range_request, "Serving object");
+
+    match range_request {
+        RangeRequest::Full => HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .insert_header((header::ACCEPT_RANGES, "bytes"))
+            .body(data),
+        RangeRequest::Partial(ByteRange { start, end }) => {
+            HttpResponse::build(StatusCode::PARTIAL_CONTENT)
+                .content_type("application/octet-stream")
+                .insert_header((header::ACCEPT_RANGES, "bytes"))
+                .insert_header((header::CONTENT_RANGE, format!("bytes {start}-{end}/{len}")))
+                .body(data.slice(start..end + 1))
+        }
+        RangeRequest::Unsatisfiable => HttpResponse::build(StatusCode::RANGE_NOT_SATISFIABLE)
+            .insert_header((header::CONTENT_RANGE, format!("bytes */{len}")))
+            .finish(),
+    }
+}
+
+/// Start object gateway HTTP server on the provided addresses.
+pub fn start_object_gateway_server<OM>(
+    endpoints: Vec<SocketAddr>,
+    object_mappings: OM,
+    object_fetcher: ObjectFetcher,
+) -> std::io::Result<impl Future<Output = std::io::Result<()>>>
+where
+    OM: ObjectMappings,
+{
+    let data = Data::new(ServerState {
+        object_mappings: Arc::new(object_mappings),
+        object_fetcher,
+    });
+
+    let server = HttpServer::new(move || App::new().app_data(data.clone()).service(get_data))
+        .workers(SERVER_WORKERS)
+        .bind(endpoints.as_slice())
+        .inspect_err(|error| {
+            error!(?error, "Failed to start object gateway server.");
+        })?;
+
+    info!(endpoints = ?server.addrs(), "Object gateway server started.");
+
+    Ok(server.run())
+}
@@ -0,0 +1,52 @@
+use crate::server::{parse_range, ByteRange, RangeRequest};
+
+#[test]
+fn range_bounded() {
+    assert_eq!(
+        parse_range("bytes=0-9", 100),
+        RangeRequest::Partial(ByteRange { start: 0, end: 9 })
+    );
+    assert_eq!(
+        parse_range("bytes=10-200", 100),
+        RangeRequest::Partial(ByteRange { start: 10, end: 99 })
+    );
+    assert_eq!(
+        parse_range("bytes=100-200", 100),
+        RangeRequest::Unsatisfiable
+    );
+}
+
+#[test]
+fn range_open_ended() {
+    assert_eq!(
+        parse_range("bytes=90-", 100),
+        RangeRequest::Partial(ByteRange { start: 90, end: 99 })
+    );
+    assert_eq!(parse_range("bytes=100-", 100), RangeRequest::Unsatisfiable);
+}
+
+#[test]
+fn range_suffix() {
+    assert_eq!(
+        parse_range("bytes=-10", 100),
+        RangeRequest::Partial(ByteRange { start: 90, end: 99 })
+    );
+    assert_eq!(
+        parse_range("bytes=-1000", 100),
+        RangeRequest::Partial(ByteRange { start: 0, end: 99 })
+    );
+    assert_eq!(parse_range("bytes=-0", 100), RangeRequest::Unsatisfiable);
+    assert_eq!(parse_range("bytes=-10", 0), RangeRequest::Unsatisfiable);
+}
+
+#[test]
+fn range_ignored() {
+    // Unsupported units
+    assert_eq!(parse_range("items=0-9", 100), RangeRequest::Full);
+    // Multiple ranges
+    assert_eq!(parse_range("bytes=0-9,20-29", 100), RangeRequest::Full);
+    // Malformed
+    assert_eq!(parse_range("bytes=9-0", 100), RangeRequest::Full);
+    assert_eq!(parse_range("bytes=a-b", 100), RangeRequest::Full);
+    assert_eq!(parse_range("bytes=10", 100), RangeRequest::Full);
+}
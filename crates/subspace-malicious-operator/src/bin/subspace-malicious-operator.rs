@@ -204,6 +204,7 @@ fn main() -> Result<(), Error> {
                 sync: Default::default(),
                 is_timekeeper: false,
                 timekeeper_cpu_cores: Default::default(),
+                index_object_mappings: false,
             };
 
             let partial_components = subspace_service::new_partial::<PosTable, RuntimeApi>(
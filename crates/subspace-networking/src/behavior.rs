@@ -12,19 +12,27 @@ use crate::protocols::reserved_peers::{
     Behaviour as ReservedPeersBehaviour, Config as ReservedPeersConfig, Event as ReservedPeersEvent,
 };
 use crate::protocols::subspace_connection_limits::Behaviour as ConnectionLimitsBehaviour;
+use crate::utils::bandwidth_limiter::BandwidthLimiter;
 use derive_more::From;
 use libp2p::allow_block_list::{Behaviour as AllowBlockListBehaviour, BlockedPeers};
 use libp2p::autonat::Event as AutonatEvent;
 use libp2p::connection_limits::ConnectionLimits;
+use libp2p::dcutr::{Behaviour as Dcutr, Event as DcutrEvent};
 use libp2p::gossipsub::{
     Behaviour as Gossipsub, Config as GossipsubConfig, Event as GossipsubEvent, MessageAuthenticity,
 };
 use libp2p::identify::{Behaviour as Identify, Config as IdentifyConfig, Event as IdentifyEvent};
 use libp2p::kad::{Behaviour as Kademlia, Config as KademliaConfig, Event as KademliaEvent};
 use libp2p::ping::{Behaviour as Ping, Event as PingEvent};
+use libp2p::relay::client::{Behaviour as RelayClient, Event as RelayClientEvent};
+use libp2p::relay::{
+    Behaviour as RelayServer, Config as RelayServerConfig, Event as RelayServerEvent,
+};
 use libp2p::swarm::behaviour::toggle::Toggle;
 use libp2p::swarm::NetworkBehaviour;
 use libp2p::PeerId;
+use libp2p_stream::Behaviour as StreamBehaviour;
+use std::sync::Arc;
 use void::Void as VoidEvent;
 
 type BlockListBehaviour = AllowBlockListBehaviour<BlockedPeers>;
@@ -42,12 +50,18 @@ pub(crate) struct BehaviorConfig<RecordStore> {
     pub(crate) record_store: RecordStore,
     /// The configuration for the [`RequestResponsesBehaviour`] protocol.
     pub(crate) request_response_protocols: Vec<Box<dyn RequestHandler>>,
+    /// Bandwidth limiter for request-response protocols.
+    pub(crate) bandwidth_limiter: Arc<BandwidthLimiter>,
     /// Connection limits for the swarm.
     pub(crate) connection_limits: ConnectionLimits,
     /// The configuration for the [`ReservedPeersBehaviour`].
     pub(crate) reserved_peers: ReservedPeersConfig,
     /// Autonat configuration.
     pub(crate) autonat: AutonatWrapperConfig,
+    /// Relay client behaviour paired with relay transport.
+    pub(crate) relay_client: RelayClient,
+    /// The configuration for the [`RelayServer`] behaviour, `None` disables relay server.
+    pub(crate) relay_server: Option<RelayServerConfig>,
 }
 
 #[derive(NetworkBehaviour)]
@@ -61,9 +75,13 @@ pub(crate) struct Behavior<RecordStore> {
     pub(crate) gossipsub: Toggle<Gossipsub>,
     pub(crate) ping: Ping,
     pub(crate) request_response: RequestResponseFactoryBehaviour,
+    pub(crate) stream: StreamBehaviour,
     pub(crate) block_list: BlockListBehaviour,
     pub(crate) reserved_peers: ReservedPeersBehaviour,
     pub(crate) autonat: AutonatWrapper,
+    pub(crate) relay_client: RelayClient,
+    pub(crate) relay_server: Toggle<RelayServer>,
+    pub(crate) dcutr: Dcutr,
 }
 
 impl<RecordStore> Behavior<RecordStore>
@@ -89,6 +107,11 @@ where
             })
             .into();
 
+        let relay_server = config
+            .relay_server
+            .map(|relay_config| RelayServer::new(config.peer_id, relay_config))
+            .into();
+
         Self {
             connection_limits: ConnectionLimitsBehaviour::new(config.connection_limits),
             identify: Identify::new(config.identify),
@@ -97,12 +120,17 @@ where
             ping: Ping::default(),
             request_response: RequestResponseFactoryBehaviour::new(
                 config.request_response_protocols,
+                config.bandwidth_limiter,
             )
             //TODO: Convert to an error.
             .expect("RequestResponse protocols registration failed."),
+            stream: StreamBehaviour::new(),
             block_list: BlockListBehaviour::default(),
             reserved_peers: ReservedPeersBehaviour::new(config.reserved_peers),
             autonat: AutonatWrapper::new(config.autonat),
+            relay_client: config.relay_client,
+            relay_server,
+            dcutr: Dcutr::new(config.peer_id),
         }
     }
 }
@@ -116,6 +144,11 @@ pub(crate) enum Event {
     RequestResponse(RequestResponseEvent),
     /// Event stub for connection limits and block list behaviours. We won't receive such events.
     VoidEventStub(VoidEvent),
+    /// Event stub for stream behaviour, it doesn't emit any events.
+    StreamEventStub(()),
     ReservedPeers(ReservedPeersEvent),
     Autonat(AutonatEvent),
+    RelayClient(RelayClientEvent),
+    RelayServer(RelayServerEvent),
+    Dcutr(DcutrEvent),
 }
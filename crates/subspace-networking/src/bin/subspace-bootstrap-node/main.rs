@@ -6,16 +6,21 @@ use clap::Parser;
 use futures::{select, FutureExt};
 use libp2p::identity::ed25519::Keypair;
 use libp2p::kad::Mode;
+use libp2p::relay::Config as RelayServerConfig;
 use libp2p::{identity, Multiaddr, PeerId};
 use prometheus_client::registry::Registry;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::num::NonZeroU64;
 use std::sync::Arc;
+use std::time::Duration;
 use subspace_metrics::{start_prometheus_metrics_server, RegistryAdapter};
 use subspace_networking::libp2p::multiaddr::Protocol;
-use subspace_networking::{peer_id, Config, KademliaMode};
+use subspace_networking::{
+    parse_protocol_bandwidth_limit, peer_id, BandwidthLimits, Config, KademliaMode,
+};
 use tracing::{debug, info, Level};
 use tracing_subscriber::fmt::Subscriber;
 use tracing_subscriber::util::SubscriberInitExt;
@@ -72,6 +77,38 @@ enum Command {
         /// one specified endpoint. Format: 127.0.0.1:8080
         #[arg(long, aliases = ["metrics-endpoint", "metrics-endpoints"])]
         prometheus_listen_on: Vec<SocketAddr>,
+        /// Upload limit for request-response protocols combined in bytes per second, unlimited by
+        /// default.
+        #[arg(long)]
+        upload_limit: Option<NonZeroU64>,
+        /// Download limit for request-response protocols combined in bytes per second, unlimited
+        /// by default.
+        #[arg(long)]
+        download_limit: Option<NonZeroU64>,
+        /// Upload limit for a particular request-response protocol in format
+        /// `PROTOCOL=BYTES_PER_SECOND`, multiple are supported.
+        #[arg(long, value_parser = parse_protocol_bandwidth_limit)]
+        protocol_upload_limit: Vec<(String, NonZeroU64)>,
+        /// Download limit for a particular request-response protocol in format
+        /// `PROTOCOL=BYTES_PER_SECOND`, multiple are supported.
+        #[arg(long, value_parser = parse_protocol_bandwidth_limit)]
+        protocol_download_limit: Vec<(String, NonZeroU64)>,
+        /// Act as a circuit relay server for nodes behind NAT, such that they can be reached by
+        /// other peers and upgrade to direct connections using hole punching.
+        #[arg(long, default_value_t = false)]
+        relay_server: bool,
+        /// Maximum number of reservations relay server accepts.
+        #[arg(long, default_value_t = RelayServerConfig::default().max_reservations)]
+        relay_max_reservations: usize,
+        /// Maximum number of circuits relay server maintains at the same time.
+        #[arg(long, default_value_t = RelayServerConfig::default().max_circuits)]
+        relay_max_circuits: usize,
+        /// Maximum duration of a single relayed circuit in seconds.
+        #[arg(long, default_value_t = RelayServerConfig::default().max_circuit_duration.as_secs())]
+        relay_max_circuit_duration: u64,
+        /// Maximum number of bytes relayed through a single circuit in each direction.
+        #[arg(long, default_value_t = RelayServerConfig::default().max_circuit_bytes)]
+        relay_max_circuit_bytes: u64,
     },
     /// Generate a new keypair
     GenerateKeypair {
@@ -135,6 +172,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
             protocol_version,
             external_addresses,
             prometheus_listen_on,
+            upload_limit,
+            download_limit,
+            protocol_upload_limit,
+            protocol_download_limit,
+            relay_server,
+            relay_max_reservations,
+            relay_max_circuits,
+            relay_max_circuit_duration,
+            relay_max_circuit_bytes,
         } => {
             debug!(
                 "Libp2p protocol stack instantiated with version: {} ",
@@ -150,6 +196,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
             let dsn_metrics_registry =
                 should_start_prometheus_server.then_some(&mut metrics_registry);
 
+            let relay_server = relay_server.then(|| RelayServerConfig {
+                max_reservations: relay_max_reservations,
+                max_circuits: relay_max_circuits,
+                max_circuit_duration: Duration::from_secs(relay_max_circuit_duration),
+                max_circuit_bytes: relay_max_circuit_bytes,
+                ..RelayServerConfig::default()
+            });
+
             let config = Config {
                 listen_on,
                 allow_non_global_addresses_in_dht: allow_private_ips,
@@ -158,9 +212,16 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 max_established_outgoing_connections: out_peers,
                 max_pending_incoming_connections: pending_in_peers,
                 max_pending_outgoing_connections: pending_out_peers,
+                bandwidth_limits: BandwidthLimits::new(
+                    upload_limit,
+                    download_limit,
+                    protocol_upload_limit,
+                    protocol_download_limit,
+                ),
                 bootstrap_addresses: bootstrap_nodes,
                 kademlia_mode: KademliaMode::Static(Mode::Server),
                 external_addresses,
+                relay_server,
 
                 ..Config::new(
                     protocol_version.to_string(),
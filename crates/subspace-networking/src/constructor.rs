@@ -1,5 +1,5 @@
 pub(crate) mod temporary_bans;
-mod transport;
+pub(crate) mod transport;
 
 use crate::behavior::persistent_parameters::{KnownPeersRegistry, StubNetworkingParametersManager};
 use crate::behavior::{Behavior, BehaviorConfig};
@@ -8,13 +8,18 @@ use crate::constructor::transport::build_transport;
 use crate::node::Node;
 use crate::node_runner::{NodeRunner, NodeRunnerConfig};
 use crate::protocols::autonat_wrapper::Config as AutonatWrapperConfig;
+use crate::protocols::pieces_by_index;
+use crate::protocols::pieces_by_index::PiecesByIndexRequestHandler;
 use crate::protocols::request_response::request_response_factory::RequestHandler;
 use crate::protocols::reserved_peers::Config as ReservedPeersConfig;
 use crate::shared::Shared;
+use crate::utils::bandwidth_limiter::{BandwidthLimiter, BandwidthLimits};
+use crate::utils::peer_reputation::PeerReputation;
 use crate::utils::rate_limiter::RateLimiter;
 use crate::utils::{strip_peer_id, SubspaceMetrics};
 use backoff::{ExponentialBackoff, SystemClock};
 use futures::channel::mpsc;
+use futures::FutureExt;
 use libp2p::autonat::Config as AutonatConfig;
 use libp2p::connection_limits::ConnectionLimits;
 use libp2p::gossipsub::{
@@ -29,6 +34,7 @@ use libp2p::kad::{
 };
 use libp2p::metrics::Metrics;
 use libp2p::multiaddr::Protocol;
+use libp2p::relay::Config as RelayServerConfig;
 use libp2p::yamux::Config as YamuxConfig;
 use libp2p::{identity, Multiaddr, PeerId, StreamProtocol, SwarmBuilder, TransportError};
 use parking_lot::Mutex;
@@ -189,6 +195,11 @@ pub struct Config<LocalRecordProvider> {
     pub listen_on: Vec<Multiaddr>,
     /// Fallback to random port if specified (or default) port is already occupied.
     pub listen_on_fallback_to_random_port: bool,
+    /// Enable QUIC transport in addition to TCP.
+    ///
+    /// QUIC addresses (like `/ip4/0.0.0.0/udp/30533/quic-v1`) can then be used in `listen_on` and
+    /// are preferred over TCP addresses when dialing peers.
+    pub enable_quic: bool,
     /// Adds a timeout to the setup and protocol upgrade process for all inbound and outbound
     /// connections established through the transport.
     pub timeout: Duration,
@@ -210,6 +221,9 @@ pub struct Config<LocalRecordProvider> {
     pub known_peers_registry: Box<dyn KnownPeersRegistry>,
     /// The configuration for the `RequestResponsesBehaviour` protocol.
     pub request_response_protocols: Vec<Box<dyn RequestHandler>>,
+    /// Handler for pieces-by-index protocol, `None` means requests are not supported and peers
+    /// will fall back to requesting pieces one by one.
+    pub pieces_by_index_request_handler: Option<PiecesByIndexRequestHandler>,
     /// Defines set of peers with a permanent connection (and reconnection if necessary).
     pub reserved_peers: Vec<Multiaddr>,
     /// Established incoming swarm connection limit.
@@ -220,6 +234,8 @@ pub struct Config<LocalRecordProvider> {
     pub max_pending_incoming_connections: u32,
     /// Pending outgoing swarm connection limit.
     pub max_pending_outgoing_connections: u32,
+    /// Upload and download limits for request-response protocols.
+    pub bandwidth_limits: BandwidthLimits,
     /// How many temporarily banned unreachable peers to keep in memory.
     pub temporary_bans_cache_size: u32,
     /// Backoff policy for temporary banning of unreachable peers.
@@ -239,6 +255,13 @@ pub struct Config<LocalRecordProvider> {
     /// Known external addresses to the local peer. The addresses will be added on the swarm start
     /// and enable peer to notify others about its reachable address.
     pub external_addresses: Vec<Multiaddr>,
+    /// Relay servers (addresses must end with `/p2p/<peer_id>`) to make reservations with when
+    /// node is found to be behind NAT. Other peers will then be able to connect through relay,
+    /// after which direct connection is attempted using hole punching (DCUtR).
+    pub relay_servers: Vec<Multiaddr>,
+    /// Act as a relay server for nodes behind NAT with specified resource limits, `None` disables
+    /// relay server.
+    pub relay_server: Option<RelayServerConfig>,
 }
 
 impl<LocalRecordProvider> fmt::Debug for Config<LocalRecordProvider> {
@@ -334,6 +357,7 @@ where
             keypair,
             listen_on: vec![],
             listen_on_fallback_to_random_port: true,
+            enable_quic: false,
             timeout: Duration::from_secs(10),
             identify,
             kademlia,
@@ -343,12 +367,14 @@ where
             initial_random_query_interval: Duration::from_secs(1),
             known_peers_registry: StubNetworkingParametersManager.boxed(),
             request_response_protocols: Vec::new(),
+            pieces_by_index_request_handler: None,
             yamux_config,
             reserved_peers: Vec::new(),
             max_established_incoming_connections: SWARM_MAX_ESTABLISHED_INCOMING_CONNECTIONS,
             max_established_outgoing_connections: SWARM_MAX_ESTABLISHED_OUTGOING_CONNECTIONS,
             max_pending_incoming_connections: SWARM_MAX_PENDING_INCOMING_CONNECTIONS,
             max_pending_outgoing_connections: SWARM_MAX_PENDING_OUTGOING_CONNECTIONS,
+            bandwidth_limits: BandwidthLimits::default(),
             temporary_bans_cache_size: TEMPORARY_BANS_CACHE_SIZE,
             temporary_ban_backoff,
             libp2p_metrics,
@@ -357,6 +383,8 @@ where
             bootstrap_addresses: Vec::new(),
             kademlia_mode: KademliaMode::Static(Mode::Client),
             external_addresses: Vec::new(),
+            relay_servers: Vec::new(),
+            relay_server: None,
         }
     }
 }
@@ -364,7 +392,7 @@ where
 /// Errors that might happen during network creation.
 #[derive(Debug, Error)]
 pub enum CreationError {
-    /// Circuit relay client error.
+    /// Relay server address doesn't end with `/p2p/<peer_id>`.
     #[error("Expected relay server node.")]
     RelayServerExpected,
     /// I/O error.
@@ -397,6 +425,7 @@ where
         keypair,
         listen_on,
         listen_on_fallback_to_random_port,
+        enable_quic,
         timeout,
         identify,
         kademlia,
@@ -407,11 +436,13 @@ where
         initial_random_query_interval,
         known_peers_registry,
         request_response_protocols,
+        pieces_by_index_request_handler,
         reserved_peers,
         max_established_incoming_connections,
         max_established_outgoing_connections,
         max_pending_incoming_connections,
         max_pending_outgoing_connections,
+        bandwidth_limits,
         temporary_bans_cache_size,
         temporary_ban_backoff,
         libp2p_metrics,
@@ -420,11 +451,23 @@ where
         bootstrap_addresses,
         kademlia_mode,
         external_addresses,
+        relay_servers,
+        relay_server,
     } = config;
     let local_peer_id = peer_id(&keypair);
 
+    let relay_servers = relay_servers
+        .into_iter()
+        .map(|address| match address.iter().last() {
+            Some(Protocol::P2p(relay_peer_id)) => Ok((relay_peer_id, address)),
+            _ => Err(CreationError::RelayServerExpected),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
     info!(
         %allow_non_global_addresses_in_dht,
+        %enable_quic,
+        relay_server = %relay_server.is_some(),
         peer_id = %local_peer_id,
         %protocol_version,
         "DSN instance configured."
@@ -438,6 +481,9 @@ where
         .with_max_established_outgoing(Some(max_established_outgoing_connections));
 
     debug!(?connection_limits, "DSN connection limits set.");
+    debug!(?bandwidth_limits, "DSN bandwidth limits set.");
+
+    let bandwidth_limiter = Arc::new(BandwidthLimiter::new(bandwidth_limits, metrics.clone()));
 
     let autonat_boot_delay = if kademlia_mode.is_static() || !external_addresses.is_empty() {
         AUTONAT_SERVER_PROBE_DELAY
@@ -452,6 +498,18 @@ where
         "Autonat boot delay set."
     );
 
+    if let Some(relay_server) = &relay_server {
+        debug!(
+            max_reservations = %relay_server.max_reservations,
+            max_circuits = %relay_server.max_circuits,
+            max_circuit_duration = ?relay_server.max_circuit_duration,
+            max_circuit_bytes = %relay_server.max_circuit_bytes,
+            "DSN relay server enabled."
+        );
+    }
+
+    let (relay_transport, relay_client) = libp2p::relay::client::new(local_peer_id);
+
     let mut behaviour = Behavior::new(BehaviorConfig {
         peer_id: local_peer_id,
         identify,
@@ -459,6 +517,7 @@ where
         gossipsub,
         record_store: LocalOnlyRecordStore::new(local_records_provider),
         request_response_protocols,
+        bandwidth_limiter: Arc::clone(&bandwidth_limiter),
         connection_limits,
         reserved_peers: ReservedPeersConfig {
             reserved_peers: reserved_peers.clone(),
@@ -475,8 +534,16 @@ where
             local_peer_id,
             servers: bootstrap_addresses.clone(),
         },
+        relay_client,
+        relay_server,
     });
 
+    let (pieces_by_index_client, pieces_by_index_server) = pieces_by_index::create(
+        behaviour.stream.new_control(),
+        pieces_by_index_request_handler,
+        Arc::clone(&bandwidth_limiter),
+    );
+
     match (kademlia_mode, external_addresses.is_empty()) {
         (KademliaMode::Static(mode), _) => {
             behaviour.kademlia.set_mode(Some(mode));
@@ -503,6 +570,8 @@ where
                 Arc::clone(&temporary_bans),
                 timeout,
                 yamux_config,
+                enable_quic,
+                relay_transport,
             )?)
         })
         .map_err(|error| CreationError::TransportCreationError(error.into()))?
@@ -526,9 +595,18 @@ where
 
             let addr_string = addr.to_string();
             // Listen on random port if specified is already occupied
-            if let Some(Protocol::Tcp(_port)) = addr.pop() {
+            let random_port_addr = match addr.pop() {
+                Some(Protocol::Tcp(_port)) => Some(addr.with(Protocol::Tcp(0))),
+                Some(Protocol::QuicV1) => match addr.pop() {
+                    Some(Protocol::Udp(_port)) => {
+                        Some(addr.with(Protocol::Udp(0)).with(Protocol::QuicV1))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(addr) = random_port_addr {
                 info!("Failed to listen on {addr_string} ({error}), falling back to random port");
-                addr.push(Protocol::Tcp(0));
                 swarm.listen_on(addr)?;
             }
         }
@@ -548,16 +626,27 @@ where
         max_pending_outgoing_connections,
     );
 
-    let shared = Arc::new(Shared::new(local_peer_id, command_sender, rate_limiter));
+    let peer_reputation = PeerReputation::new(metrics.clone());
+
+    let shared = Arc::new(Shared::new(
+        local_peer_id,
+        command_sender,
+        rate_limiter,
+        peer_reputation,
+        bandwidth_limiter,
+        pieces_by_index_client,
+    ));
     let shared_weak = Arc::downgrade(&shared);
 
     let node = Node::new(shared);
     let node_runner = NodeRunner::new(NodeRunnerConfig {
         allow_non_global_addresses_in_dht,
+        enable_quic,
         is_listening,
         command_receiver,
         swarm,
         shared_weak,
+        pieces_by_index_server: pieces_by_index_server.map(FutureExt::boxed),
         next_random_query_interval: initial_random_query_interval,
         known_peers_registry,
         reserved_peers: strip_peer_id(reserved_peers).into_iter().collect(),
@@ -566,6 +655,7 @@ where
         metrics,
         protocol_version,
         bootstrap_addresses,
+        relay_servers,
     });
 
     Ok((node, node_runner))
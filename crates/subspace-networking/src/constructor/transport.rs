@@ -1,21 +1,84 @@
+#[cfg(test)]
+mod tests;
+
 use crate::constructor::temporary_bans::TemporaryBans;
+use futures::future::Either;
 use libp2p::core::multiaddr::{Multiaddr, Protocol};
 use libp2p::core::muxing::StreamMuxerBox;
 use libp2p::core::transport::{Boxed, DialOpts, ListenerId, TransportError, TransportEvent};
 use libp2p::core::Transport;
 use libp2p::dns::tokio::Transport as TokioTransport;
+use libp2p::quic::tokio::Transport as TokioQuicTransport;
+use libp2p::quic::Config as QuicConfig;
+use libp2p::relay::client::Transport as RelayClientTransport;
 use libp2p::tcp::tokio::Transport as TokioTcpTransport;
 use libp2p::tcp::Config as GenTcpConfig;
 use libp2p::yamux::Config as YamuxConfig;
 use libp2p::{core, identity, noise, PeerId};
 use parking_lot::Mutex;
-use std::io;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
+use std::{fmt, io};
 use tracing::debug;
 
+/// Transport protocol used by connection or address
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum TransportProtocol {
+    /// TCP with Noise and Yamux on top
+    Tcp,
+    /// QUIC v1
+    Quic,
+    /// Circuit relay, underlying transport is used between peers and relay
+    Relayed,
+}
+
+impl fmt::Display for TransportProtocol {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tcp => "tcp",
+            Self::Quic => "quic",
+            Self::Relayed => "relayed",
+        })
+    }
+}
+
+impl TransportProtocol {
+    /// Transport protocol of the address, `None` for addresses that are not supported
+    pub(crate) fn from_address(address: &Multiaddr) -> Option<Self> {
+        if address
+            .iter()
+            .any(|protocol| protocol == Protocol::P2pCircuit)
+        {
+            return Some(Self::Relayed);
+        }
+
+        address.iter().find_map(|protocol| match protocol {
+            Protocol::Tcp(_) => Some(Self::Tcp),
+            Protocol::QuicV1 => Some(Self::Quic),
+            _ => None,
+        })
+    }
+}
+
+/// Sort addresses of a peer in order they should be dialed in.
+///
+/// QUIC addresses go first when QUIC is enabled since QUIC connections are established faster and
+/// don't suffer from head-of-line blocking, otherwise they go last since they can't be dialed.
+/// Relayed addresses go after all direct addresses. Order of addresses using the same transport
+/// protocol is preserved.
+pub(crate) fn sort_addresses_by_preference(addresses: &mut [Multiaddr], enable_quic: bool) {
+    addresses.sort_by_key(|address| {
+        let transport = TransportProtocol::from_address(address);
+        let is_relayed = transport == Some(TransportProtocol::Relayed);
+        let is_quic = transport == Some(TransportProtocol::Quic);
+
+        (is_relayed, is_quic != enable_quic)
+    });
+}
+
 // Builds the transport stack that LibP2P will communicate over along with a relay client.
 pub(super) fn build_transport(
     allow_non_global_addresses_in_dht: bool,
@@ -23,6 +86,8 @@ pub(super) fn build_transport(
     temporary_bans: Arc<Mutex<TemporaryBans>>,
     timeout: Duration,
     yamux_config: YamuxConfig,
+    enable_quic: bool,
+    relay_transport: RelayClientTransport,
 ) -> io::Result<Boxed<(PeerId, StreamMuxerBox)>> {
     let wrapped_tcp = {
         let tcp_config = GenTcpConfig::default().nodelay(true);
@@ -30,7 +95,7 @@ pub(super) fn build_transport(
         CustomTransportWrapper::new(
             TokioTcpTransport::new(tcp_config),
             allow_non_global_addresses_in_dht,
-            temporary_bans,
+            Arc::clone(&temporary_bans),
         )
     };
 
@@ -39,6 +104,20 @@ pub(super) fn build_transport(
             noise::Config::new(keypair).expect("Signing libp2p-noise static DH keypair failed.");
 
         wrapped_tcp
+            .upgrade(core::upgrade::Version::V1Lazy)
+            .authenticate(noise)
+            .multiplex(yamux_config.clone())
+            .timeout(timeout)
+            .boxed()
+    };
+
+    // Relayed connections are upgraded the same way as TCP connections, the relay itself only
+    // sees encrypted traffic
+    let relay_upgraded = {
+        let noise =
+            noise::Config::new(keypair).expect("Signing libp2p-noise static DH keypair failed.");
+
+        relay_transport
             .upgrade(core::upgrade::Version::V1Lazy)
             .authenticate(noise)
             .multiplex(yamux_config)
@@ -46,7 +125,36 @@ pub(super) fn build_transport(
             .boxed()
     };
 
-    Ok(TokioTransport::system(tcp_upgraded)?.boxed())
+    let transport = if enable_quic {
+        let mut quic_config = QuicConfig::new(keypair);
+        quic_config.handshake_timeout = timeout;
+
+        // QUIC has authentication and multiplexing built-in, no upgrades are necessary
+        let wrapped_quic = CustomTransportWrapper::new(
+            TokioQuicTransport::new(quic_config),
+            allow_non_global_addresses_in_dht,
+            temporary_bans,
+        );
+
+        wrapped_quic
+            .or_transport(tcp_upgraded)
+            .map(|output, _| match output {
+                Either::Left((peer_id, connection)) => (peer_id, StreamMuxerBox::new(connection)),
+                Either::Right((peer_id, muxer)) => (peer_id, muxer),
+            })
+            .boxed()
+    } else {
+        tcp_upgraded
+    };
+
+    // Relay transport goes first since it only handles circuit addresses (`/p2p-circuit`), all
+    // other addresses are passed further
+    let transport = relay_upgraded
+        .or_transport(transport)
+        .map(|output, _| output.into_inner())
+        .boxed();
+
+    Ok(TokioTransport::system(transport)?.boxed())
 }
 
 #[derive(Debug, Clone)]
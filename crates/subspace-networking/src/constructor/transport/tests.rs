@@ -0,0 +1,174 @@
+use crate::constructor::transport::{sort_addresses_by_preference, TransportProtocol};
+use crate::{Config, GenericRequest, GenericRequestHandler};
+use futures::channel::oneshot;
+use futures::future::pending;
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use parity_scale_codec::{Decode, Encode};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+#[derive(Encode, Decode)]
+struct ExampleRequest;
+
+impl GenericRequest for ExampleRequest {
+    const PROTOCOL_NAME: &'static str = "/example";
+    const LOG_TARGET: &'static str = "example_request";
+    type Response = ExampleResponse;
+}
+
+#[derive(Encode, Decode, Debug)]
+struct ExampleResponse {
+    value: u32,
+}
+
+#[test]
+fn transport_protocol_from_address() {
+    let tcp_address: Multiaddr = "/ip4/127.0.0.1/tcp/30433".parse().unwrap();
+    let quic_address: Multiaddr = "/ip4/127.0.0.1/udp/30433/quic-v1".parse().unwrap();
+    let dns_quic_address: Multiaddr = "/dns/example.com/udp/30433/quic-v1".parse().unwrap();
+    let memory_address: Multiaddr = "/memory/1".parse().unwrap();
+    let relayed_address: Multiaddr = format!(
+        "/ip4/127.0.0.1/tcp/30433/p2p/{}/p2p-circuit",
+        PeerId::random()
+    )
+    .parse()
+    .unwrap();
+
+    assert_eq!(
+        TransportProtocol::from_address(&tcp_address),
+        Some(TransportProtocol::Tcp)
+    );
+    assert_eq!(
+        TransportProtocol::from_address(&quic_address),
+        Some(TransportProtocol::Quic)
+    );
+    assert_eq!(
+        TransportProtocol::from_address(&dns_quic_address),
+        Some(TransportProtocol::Quic)
+    );
+    assert_eq!(TransportProtocol::from_address(&memory_address), None);
+    assert_eq!(
+        TransportProtocol::from_address(&relayed_address),
+        Some(TransportProtocol::Relayed)
+    );
+}
+
+#[test]
+fn addresses_sorted_by_preference() {
+    let tcp_address_1: Multiaddr = "/ip4/127.0.0.1/tcp/30433".parse().unwrap();
+    let tcp_address_2: Multiaddr = "/ip4/127.0.0.2/tcp/30433".parse().unwrap();
+    let quic_address_1: Multiaddr = "/ip4/127.0.0.1/udp/30433/quic-v1".parse().unwrap();
+    let quic_address_2: Multiaddr = "/ip4/127.0.0.2/udp/30433/quic-v1".parse().unwrap();
+    let relayed_address: Multiaddr = format!(
+        "/ip4/127.0.0.3/tcp/30433/p2p/{}/p2p-circuit",
+        PeerId::random()
+    )
+    .parse()
+    .unwrap();
+
+    let addresses = vec![
+        relayed_address.clone(),
+        tcp_address_1.clone(),
+        quic_address_1.clone(),
+        tcp_address_2.clone(),
+        quic_address_2.clone(),
+    ];
+
+    let mut quic_enabled = addresses.clone();
+    sort_addresses_by_preference(&mut quic_enabled, true);
+    assert_eq!(
+        quic_enabled,
+        vec![
+            quic_address_1.clone(),
+            quic_address_2.clone(),
+            tcp_address_1.clone(),
+            tcp_address_2.clone(),
+            relayed_address.clone(),
+        ]
+    );
+
+    let mut quic_disabled = addresses;
+    sort_addresses_by_preference(&mut quic_disabled, false);
+    assert_eq!(
+        quic_disabled,
+        vec![
+            tcp_address_1,
+            tcp_address_2,
+            quic_address_1,
+            quic_address_2,
+            relayed_address
+        ]
+    );
+}
+
+#[tokio::test]
+async fn nodes_communicate_over_quic() {
+    let config_1 = Config {
+        listen_on: vec!["/ip4/127.0.0.1/udp/0/quic-v1".parse().unwrap()],
+        allow_non_global_addresses_in_dht: true,
+        enable_quic: true,
+        request_response_protocols: vec![GenericRequestHandler::create(
+            |_, &ExampleRequest| async { Some(ExampleResponse { value: 42 }) },
+        )],
+        ..Config::default()
+    };
+    let (node_1, mut node_runner_1) = crate::construct(config_1).unwrap();
+
+    let (node_1_address_sender, node_1_address_receiver) = oneshot::channel();
+    let on_new_listener_handler = node_1.on_new_listener(Arc::new({
+        let node_1_address_sender = Mutex::new(Some(node_1_address_sender));
+
+        move |address| {
+            if TransportProtocol::from_address(address) == Some(TransportProtocol::Quic) {
+                if let Some(node_1_address_sender) = node_1_address_sender.lock().take() {
+                    node_1_address_sender.send(address.clone()).unwrap();
+                }
+            }
+        }
+    }));
+
+    tokio::spawn(async move {
+        node_runner_1.run().await;
+    });
+
+    // Wait for first node to know its address
+    let node_1_addr = node_1_address_receiver.await.unwrap();
+    drop(on_new_listener_handler);
+
+    // Second node only has QUIC address of the first node, so connection must use QUIC
+    let bootstrap_addresses = vec![node_1_addr.with(Protocol::P2p(node_1.id()))];
+    let config_2 = Config {
+        listen_on: vec!["/ip4/127.0.0.1/udp/0/quic-v1".parse().unwrap()],
+        allow_non_global_addresses_in_dht: true,
+        enable_quic: true,
+        request_response_protocols: vec![GenericRequestHandler::<ExampleRequest>::create(
+            |_, _| async { None },
+        )],
+        bootstrap_addresses,
+        ..Config::default()
+    };
+
+    let (node_2, mut node_runner_2) = crate::construct(config_2).unwrap();
+
+    tokio::spawn({
+        let node = node_2.clone();
+
+        async move {
+            let _ = node.bootstrap().await;
+
+            pending::<()>().await;
+        }
+    });
+
+    tokio::spawn(async move {
+        node_runner_2.run().await;
+    });
+
+    let resp = node_2
+        .send_generic_request(node_1.id(), ExampleRequest)
+        .await
+        .unwrap();
+
+    assert_eq!(resp.value, 42);
+}
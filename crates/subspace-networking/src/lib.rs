@@ -39,6 +39,10 @@ pub use constructor::{
     construct, peer_id, Config, CreationError, KademliaMode, LocalRecordProvider,
 };
 pub use libp2p;
+pub use protocols::pieces_by_index::{
+    GetPiecesByIndexError, PieceByIndexResult, PiecesByIndexRequest, PiecesByIndexRequestHandler,
+    MAX_PIECES_PER_REQUEST,
+};
 pub use protocols::request_response::handlers::generic_request_handler::{
     GenericRequest, GenericRequestHandler,
 };
@@ -48,7 +52,11 @@ pub use protocols::request_response::handlers::piece_by_index::{
 pub use protocols::request_response::handlers::segment_header::{
     SegmentHeaderBySegmentIndexesRequestHandler, SegmentHeaderRequest, SegmentHeaderResponse,
 };
-pub use shared::PeerDiscovered;
+pub use shared::{PeerDiscovered, Reachability};
+pub use utils::bandwidth_limiter::{
+    parse_protocol_bandwidth_limit, BandwidthLimits, PeerBandwidth, ProtocolBandwidthLimits,
+};
 pub use utils::key_with_distance::KeyWithDistance;
 pub use utils::multihash::Multihash;
+pub use utils::peer_reputation::PeerScore;
 pub use utils::PeerAddress;
@@ -1,7 +1,15 @@
+use crate::protocols::pieces_by_index::{
+    GetPiecesByIndexError, PieceByIndexResult, PiecesByIndexRequest,
+};
 use crate::protocols::request_response::handlers::generic_request_handler::GenericRequest;
 use crate::protocols::request_response::request_response_factory;
-use crate::shared::{Command, CreatedSubscription, PeerDiscovered, Shared};
+use crate::protocols::request_response::request_response_factory::{
+    OutboundFailure, RequestFailure,
+};
+use crate::shared::{Command, CreatedSubscription, PeerDiscovered, Reachability, Shared};
+use crate::utils::bandwidth_limiter::{Direction, PeerBandwidth};
 use crate::utils::multihash::Multihash;
+use crate::utils::peer_reputation::{PeerScore, ReputationChange};
 use crate::utils::HandlerFn;
 use bytes::Bytes;
 use event_listener_primitives::HandlerId;
@@ -11,9 +19,12 @@ use libp2p::gossipsub::{Sha256Topic, SubscriptionError};
 use libp2p::kad::{PeerRecord, RecordKey};
 use libp2p::{Multiaddr, PeerId};
 use parity_scale_codec::Decode;
+use std::collections::HashMap;
+use std::io;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Instant;
 use thiserror::Error;
 use tokio::sync::OwnedSemaphorePermit;
 use tracing::{debug, error, trace};
@@ -366,19 +377,67 @@ impl Node {
             None
         };
 
+        let request = request.encode();
+        self.shared
+            .bandwidth_limiter
+            .throttle(
+                Request::PROTOCOL_NAME,
+                peer_id,
+                Direction::Upload,
+                request.len(),
+            )
+            .await;
+
         let (result_sender, result_receiver) = oneshot::channel();
         let command = Command::GenericRequest {
             peer_id,
             protocol_name: Request::PROTOCOL_NAME,
-            request: request.encode(),
+            request,
             result_sender,
         };
 
         self.shared.command_sender.clone().send(command).await?;
 
-        let result = result_receiver.await??;
+        let started = Instant::now();
+        let result = result_receiver.await?;
+        let latency = started.elapsed();
+
+        // Download limit was already enforced while reading the response, so it is only accounted
+        // for
+        if let Ok(response) = &result {
+            self.shared.bandwidth_limiter.account(
+                Request::PROTOCOL_NAME,
+                peer_id,
+                Direction::Download,
+                response.len(),
+            );
+        }
+
+        let result = result.map_err(SendRequestError::from).and_then(|result| {
+            Request::Response::decode(&mut result.as_slice()).map_err(Into::into)
+        });
+
+        let reputation_change = match &result {
+            Ok(_) => Some(ReputationChange::Success { latency }),
+            Err(SendRequestError::ProtocolFailure(RequestFailure::Network(
+                OutboundFailure::Timeout,
+            ))) => Some(ReputationChange::Timeout),
+            // Older peers that don't support the protocol are not penalized, neither are
+            // failures caused by this node
+            Err(SendRequestError::ProtocolFailure(
+                RequestFailure::Network(OutboundFailure::UnsupportedProtocols)
+                | RequestFailure::UnknownProtocol
+                | RequestFailure::Obsolete,
+            )) => None,
+            Err(_) => Some(ReputationChange::Failure),
+        };
+        if let Some(reputation_change) = reputation_change {
+            self.shared
+                .peer_reputation
+                .report(peer_id, reputation_change);
+        }
 
-        Request::Response::decode(&mut result.as_slice()).map_err(Into::into)
+        result
     }
 
     /// Sends the generic request to the peer and awaits the result.
@@ -394,6 +453,32 @@ impl Node {
             .await
     }
 
+    /// Requests multiple pieces from the peer at once, results are yielded as soon as the peer
+    /// sends them (in the order pieces become ready on the peer rather than in the order they were
+    /// requested), stream ends after the last result or after the first error.
+    pub async fn get_pieces_by_index(
+        &self,
+        peer_id: PeerId,
+        request: PiecesByIndexRequest,
+    ) -> Result<
+        impl Stream<Item = io::Result<PieceByIndexResult>> + Send + 'static,
+        GetPiecesByIndexError,
+    > {
+        let permit = self.shared.rate_limiter.acquire_permit().await;
+
+        let results = self
+            .shared
+            .pieces_by_index_client
+            .request(peer_id, request)
+            .await?;
+
+        // Just holding onto permit while results are being received
+        Ok(results.map(move |result| {
+            let _permit = &permit;
+            result
+        }))
+    }
+
     /// Get closest peers by multihash key using Kademlia DHT.
     pub async fn get_closest_peers(
         &self,
@@ -500,6 +585,12 @@ impl Node {
         self.shared.external_addresses.lock().clone()
     }
 
+    /// Current reachability of the node by other peers, only reachable nodes can serve pieces
+    /// they hold.
+    pub fn reachability(&self) -> Reachability {
+        self.shared.reachability.lock().clone()
+    }
+
     /// Callback is called when node starts listening on new address.
     pub fn on_new_listener(&self, callback: HandlerFn<Multiaddr>) -> HandlerId {
         self.shared.handlers.new_listener.add(callback)
@@ -575,6 +666,40 @@ impl Node {
         self.shared.handlers.peer_discovered.add(callback)
     }
 
+    /// Callback is called when reachability of the node by other peers changes.
+    pub fn on_reachability_change(&self, callback: HandlerFn<Reachability>) -> HandlerId {
+        self.shared.handlers.reachability_change.add(callback)
+    }
+
+    /// Returns reputation scores of peers this node sent requests to, see [`PeerScore`] for
+    /// details.
+    pub fn peer_scores(&self) -> HashMap<PeerId, PeerScore> {
+        self.shared.peer_reputation.peer_scores()
+    }
+
+    /// Returns number of bytes exchanged with peers using request-response protocols, see
+    /// [`PeerBandwidth`] for details.
+    pub fn peer_bandwidth(&self) -> HashMap<PeerId, PeerBandwidth> {
+        self.shared.bandwidth_limiter.peer_bandwidth()
+    }
+
+    /// Report that peer returned an invalid piece
+    pub(crate) fn report_invalid_piece(&self, peer_id: PeerId) {
+        self.shared
+            .peer_reputation
+            .report(peer_id, ReputationChange::InvalidPiece);
+    }
+
+    /// Sort peers from the best to the worst reputation
+    pub(crate) fn sort_peers_by_reputation(&self, peers: &mut [PeerId]) {
+        self.shared.peer_reputation.sort_peers(peers);
+    }
+
+    /// Current reputation score of a peer
+    pub(crate) fn peer_score(&self, peer_id: &PeerId) -> f64 {
+        self.shared.peer_reputation.score(peer_id)
+    }
+
     /// Returns the request batch handle with common "connection permit" slot from the shared pool.
     pub async fn get_requests_batch_handle(&self) -> NodeRequestsBatchHandle {
         let _permit = self.shared.rate_limiter.acquire_permit().await;
@@ -1,23 +1,29 @@
+#[cfg(test)]
+mod tests;
+
 use crate::behavior::persistent_parameters::{
     append_p2p_suffix, remove_p2p_suffix, KnownPeersRegistry, PeerAddressRemovedEvent,
 };
 use crate::behavior::{Behavior, Event};
 use crate::constructor;
 use crate::constructor::temporary_bans::TemporaryBans;
+use crate::constructor::transport::{sort_addresses_by_preference, TransportProtocol};
 use crate::constructor::LocalOnlyRecordStore;
 use crate::protocols::request_response::request_response_factory::{
     Event as RequestResponseEvent, IfDisconnected,
 };
-use crate::shared::{Command, CreatedSubscription, PeerDiscovered, Shared};
+use crate::shared::{Command, CreatedSubscription, PeerDiscovered, Reachability, Shared};
 use crate::utils::{is_global_address_or_dns, strip_peer_id, SubspaceMetrics};
 use async_mutex::Mutex as AsyncMutex;
 use bytes::Bytes;
 use event_listener_primitives::HandlerId;
 use futures::channel::mpsc;
-use futures::future::Fuse;
-use futures::{FutureExt, StreamExt};
+use futures::future::{BoxFuture, Fuse};
+use futures::{future, FutureExt, StreamExt};
 use libp2p::autonat::{Event as AutonatEvent, NatStatus, OutboundProbeEvent};
+use libp2p::core::transport::ListenerId;
 use libp2p::core::ConnectedPoint;
+use libp2p::dcutr::Event as DcutrEvent;
 use libp2p::gossipsub::{Event as GossipsubEvent, TopicHash};
 use libp2p::identify::Event as IdentifyEvent;
 use libp2p::kad::{
@@ -28,6 +34,8 @@ use libp2p::kad::{
 };
 use libp2p::metrics::{Metrics, Recorder};
 use libp2p::multiaddr::Protocol;
+use libp2p::relay::client::Event as RelayClientEvent;
+use libp2p::relay::Event as RelayServerEvent;
 use libp2p::swarm::dial_opts::DialOpts;
 use libp2p::swarm::{DialError, SwarmEvent};
 use libp2p::{Multiaddr, PeerId, Swarm, TransportError};
@@ -44,7 +52,7 @@ use std::time::Duration;
 use tokio::sync::OwnedSemaphorePermit;
 use tokio::task::yield_now;
 use tokio::time::Sleep;
-use tracing::{debug, error, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
 enum QueryResultSender {
     Value {
@@ -89,11 +97,15 @@ where
 {
     /// Should non-global addresses be added to the DHT?
     allow_non_global_addresses_in_dht: bool,
+    /// Whether QUIC transport is enabled
+    enable_quic: bool,
     /// Whether node is listening on some addresses
     is_listening: bool,
     command_receiver: mpsc::Receiver<Command>,
     swarm: Swarm<Behavior<LocalOnlyRecordStore<LocalRecordProvider>>>,
     shared_weak: Weak<Shared>,
+    /// Answers incoming pieces-by-index requests, never resolves if requests are not supported
+    pieces_by_index_server: Fuse<BoxFuture<'static, ()>>,
     /// How frequently should random queries be done using Kademlia DHT to populate routing table.
     next_random_query_interval: Duration,
     query_id_receivers: HashMap<QueryId, QueryResultSender>,
@@ -122,6 +134,14 @@ where
     protocol_version: String,
     /// Addresses to bootstrap Kademlia network
     bootstrap_addresses: Vec<Multiaddr>,
+    /// Relay servers to make reservations with when node is behind NAT
+    relay_servers: Vec<(PeerId, Multiaddr)>,
+    /// Listeners on relayed addresses with corresponding relay servers
+    relay_listeners: HashMap<ListenerId, PeerId>,
+    /// Relay servers that accepted our reservation
+    relay_reservations: HashSet<PeerId>,
+    /// The last NAT status reported by autonat
+    nat_status: NatStatus,
     /// Ensures a single bootstrap on run() invocation.
     bootstrap_command_state: Arc<AsyncMutex<BootstrapCommandState>>,
     /// Receives an event on peer address removal from the persistent storage.
@@ -147,11 +167,14 @@ where
     LocalRecordProvider: constructor::LocalRecordProvider + Send + Sync + 'static,
 {
     pub(crate) allow_non_global_addresses_in_dht: bool,
+    /// Whether QUIC transport is enabled
+    pub(crate) enable_quic: bool,
     /// Whether node is listening on some addresses
     pub(crate) is_listening: bool,
     pub(crate) command_receiver: mpsc::Receiver<Command>,
     pub(crate) swarm: Swarm<Behavior<LocalOnlyRecordStore<LocalRecordProvider>>>,
     pub(crate) shared_weak: Weak<Shared>,
+    pub(crate) pieces_by_index_server: Option<BoxFuture<'static, ()>>,
     pub(crate) next_random_query_interval: Duration,
     pub(crate) known_peers_registry: Box<dyn KnownPeersRegistry>,
     pub(crate) reserved_peers: HashMap<PeerId, Multiaddr>,
@@ -160,6 +183,7 @@ where
     pub(crate) metrics: Option<SubspaceMetrics>,
     pub(crate) protocol_version: String,
     pub(crate) bootstrap_addresses: Vec<Multiaddr>,
+    pub(crate) relay_servers: Vec<(PeerId, Multiaddr)>,
 }
 
 impl<LocalRecordProvider> NodeRunner<LocalRecordProvider>
@@ -169,10 +193,12 @@ where
     pub(crate) fn new(
         NodeRunnerConfig {
             allow_non_global_addresses_in_dht,
+            enable_quic,
             is_listening,
             command_receiver,
             swarm,
             shared_weak,
+            pieces_by_index_server,
             next_random_query_interval,
             mut known_peers_registry,
             reserved_peers,
@@ -181,6 +207,7 @@ where
             metrics,
             protocol_version,
             bootstrap_addresses,
+            relay_servers,
         }: NodeRunnerConfig<LocalRecordProvider>,
     ) -> Self {
         // Setup the address removal events exchange between persistent params storage and Kademlia.
@@ -198,10 +225,14 @@ where
 
         Self {
             allow_non_global_addresses_in_dht,
+            enable_quic,
             is_listening,
             command_receiver,
             swarm,
             shared_weak,
+            pieces_by_index_server: pieces_by_index_server
+                .unwrap_or_else(|| future::pending().boxed())
+                .fuse(),
             next_random_query_interval,
             query_id_receivers: HashMap::default(),
             next_subscription_id: 0,
@@ -218,6 +249,10 @@ where
             peer_ip_addresses: HashMap::new(),
             protocol_version,
             bootstrap_addresses,
+            relay_servers,
+            relay_listeners: HashMap::new(),
+            relay_reservations: HashSet::new(),
+            nat_status: NatStatus::Unknown,
             bootstrap_command_state: Arc::new(AsyncMutex::new(BootstrapCommandState::default())),
             removed_addresses_rx,
             _address_removal_task_handler_id: address_removal_task_handler_id,
@@ -273,6 +308,9 @@ where
                 _ = self.known_peers_registry.run().fuse() => {
                     trace!("Network parameters registry runner exited.")
                 },
+                _ = &mut self.pieces_by_index_server => {
+                    trace!("Pieces-by-index server exited.")
+                },
                 _ = &mut self.periodical_tasks_interval => {
                     self.handle_periodical_tasks().await;
 
@@ -302,7 +340,9 @@ where
         let known_peers = self.known_peers_registry.all_known_peers().await;
 
         if !known_peers.is_empty() {
-            for (peer_id, addresses) in known_peers {
+            for (peer_id, mut addresses) in known_peers {
+                sort_addresses_by_preference(&mut addresses, self.enable_quic);
+
                 for address in addresses.clone() {
                     let address = match address.with_p2p(peer_id) {
                         Ok(address) => address,
@@ -407,6 +447,11 @@ where
             addresses.append(&mut external_addresses);
         }
 
+        // Retry reservations with relay servers that closed or rejected them earlier.
+        if self.nat_status == NatStatus::Private {
+            self.listen_on_relays();
+        }
+
         self.log_kademlia_stats();
     }
 
@@ -468,7 +513,22 @@ where
             SwarmEvent::Behaviour(Event::Autonat(event)) => {
                 self.handle_autonat_event(event).await;
             }
+            SwarmEvent::Behaviour(Event::RelayClient(event)) => {
+                self.handle_relay_client_event(event);
+            }
+            SwarmEvent::Behaviour(Event::RelayServer(event)) => {
+                self.handle_relay_server_event(event);
+            }
+            SwarmEvent::Behaviour(Event::Dcutr(event)) => {
+                self.handle_dcutr_event(event);
+            }
             SwarmEvent::NewListenAddr { address, .. } => {
+                // Relayed address is reachable by other peers as long as reservation is active
+                if TransportProtocol::from_address(&address) == Some(TransportProtocol::Relayed) {
+                    debug!(%address, "Adding relayed external address");
+                    self.swarm.add_external_address(address.clone());
+                }
+
                 let shared = match self.shared_weak.upgrade() {
                     Some(shared) => shared,
                     None => {
@@ -478,6 +538,33 @@ where
                 shared.listeners.lock().push(address.clone());
                 shared.handlers.new_listener.call_simple(&address);
             }
+            SwarmEvent::ExpiredListenAddr { address, .. } => {
+                if TransportProtocol::from_address(&address) == Some(TransportProtocol::Relayed) {
+                    debug!(%address, "Removing relayed external address");
+                    self.swarm.remove_external_address(&address);
+                }
+
+                if let Some(shared) = self.shared_weak.upgrade() {
+                    shared
+                        .listeners
+                        .lock()
+                        .retain(|listener| listener != &address);
+                }
+            }
+            SwarmEvent::ListenerClosed {
+                listener_id,
+                reason,
+                ..
+            } => {
+                if let Some(relay_peer_id) = self.relay_listeners.remove(&listener_id) {
+                    debug!(%relay_peer_id, ?reason, "Relay listener closed");
+
+                    self.relay_reservations.remove(&relay_peer_id);
+                    self.update_reachability();
+                } else {
+                    trace!(?listener_id, ?reason, "Listener closed");
+                }
+            }
             SwarmEvent::ConnectionEstablished {
                 peer_id,
                 endpoint,
@@ -544,11 +631,12 @@ where
                 }
 
                 if let Some(metrics) = self.metrics.as_mut() {
-                    metrics.inc_established_connections()
+                    metrics.inc_established_connections(endpoint.get_remote_address())
                 }
             }
             SwarmEvent::ConnectionClosed {
                 peer_id,
+                endpoint,
                 num_established,
                 cause,
                 ..
@@ -586,7 +674,7 @@ where
                 }
 
                 if let Some(metrics) = self.metrics.as_mut() {
-                    metrics.dec_established_connections()
+                    metrics.dec_established_connections(endpoint.get_remote_address())
                 };
             }
             SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
@@ -612,6 +700,9 @@ where
                     DialError::Transport(ref addresses) => {
                         for (addr, _) in addresses {
                             trace!(?error, ?peer_id, %addr, "SwarmEvent::OutgoingConnectionError (DialError::Transport) for peer.");
+                            if let Some(metrics) = self.metrics.as_mut() {
+                                metrics.inc_failed_dials(addr);
+                            }
                             if let Some(peer_id) = peer_id {
                                 self.known_peers_registry
                                     .remove_known_peer_addresses(peer_id, vec![addr.clone()])
@@ -1235,12 +1326,141 @@ where
                     self.swarm.behaviour_mut().kademlia.set_mode(None);
                 }
 
+                self.nat_status = new.clone();
+                match new {
+                    NatStatus::Private => {
+                        self.listen_on_relays();
+                    }
+                    NatStatus::Public(_) => {
+                        self.stop_listening_on_relays();
+                    }
+                    NatStatus::Unknown => {
+                        // Keep relay reservations until status is known
+                    }
+                }
+                self.update_reachability();
+
                 let connected_peers = self.swarm.connected_peers().copied().collect::<Vec<_>>();
                 self.swarm.behaviour_mut().identify.push(connected_peers);
             }
         }
     }
 
+    /// Listen on relayed addresses of relay servers that don't have active listener yet, which
+    /// makes reservations with them.
+    fn listen_on_relays(&mut self) {
+        for (relay_peer_id, relay_address) in &self.relay_servers {
+            if self
+                .relay_listeners
+                .values()
+                .any(|peer_id| peer_id == relay_peer_id)
+            {
+                continue;
+            }
+
+            let address = relay_address.clone().with(Protocol::P2pCircuit);
+            match self.swarm.listen_on(address.clone()) {
+                Ok(listener_id) => {
+                    debug!(%address, "Requesting relay reservation");
+
+                    self.relay_listeners.insert(listener_id, *relay_peer_id);
+                }
+                Err(error) => {
+                    warn!(%address, %error, "Failed to listen on relayed address");
+                }
+            }
+        }
+    }
+
+    /// Remove all relayed listeners, node is reachable directly and doesn't need relays anymore.
+    fn stop_listening_on_relays(&mut self) {
+        if self.relay_listeners.is_empty() {
+            return;
+        }
+
+        debug!(relays = %self.relay_listeners.len(), "Removing relayed listeners");
+
+        for (listener_id, _relay_peer_id) in self.relay_listeners.drain() {
+            self.swarm.remove_listener(listener_id);
+        }
+        self.relay_reservations.clear();
+    }
+
+    fn update_reachability(&mut self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.set_relay_reservations(self.relay_reservations.len());
+        }
+
+        let reachability = Reachability::new(
+            self.nat_status.clone(),
+            self.relay_reservations.iter().copied(),
+        );
+
+        let Some(shared) = self.shared_weak.upgrade() else {
+            return;
+        };
+
+        {
+            let mut current_reachability = shared.reachability.lock();
+            if *current_reachability == reachability {
+                return;
+            }
+            *current_reachability = reachability.clone();
+        }
+
+        info!(?reachability, "DSN reachability changed");
+        shared
+            .handlers
+            .reachability_change
+            .call_simple(&reachability);
+    }
+
+    fn handle_relay_client_event(&mut self, event: RelayClientEvent) {
+        match event {
+            RelayClientEvent::ReservationReqAccepted {
+                relay_peer_id,
+                renewal,
+                ..
+            } => {
+                self.on_relay_reservation_accepted(relay_peer_id, renewal);
+            }
+            event => {
+                debug!(?event, "Relay client event received.");
+            }
+        }
+    }
+
+    fn on_relay_reservation_accepted(&mut self, relay_peer_id: PeerId, renewal: bool) {
+        if renewal {
+            trace!(%relay_peer_id, "Relay reservation renewed");
+        } else {
+            debug!(%relay_peer_id, "Relay reservation accepted");
+        }
+
+        self.relay_reservations.insert(relay_peer_id);
+        self.update_reachability();
+    }
+
+    fn handle_relay_server_event(&mut self, event: RelayServerEvent) {
+        trace!(?event, "Relay server event received.");
+    }
+
+    fn handle_dcutr_event(&mut self, event: DcutrEvent) {
+        let DcutrEvent {
+            remote_peer_id,
+            result,
+        } = event;
+
+        match result {
+            Ok(connection_id) => {
+                debug!(%remote_peer_id, ?connection_id, "Hole punching succeeded");
+            }
+            Err(error) => {
+                debug!(%remote_peer_id, %error, "Hole punching failed");
+            }
+        }
+    }
+
     fn handle_command(&mut self, command: Command) {
         match command {
             Command::GetValue {
@@ -1503,6 +1723,12 @@ where
                 SwarmEvent::Behaviour(Event::Gossipsub(gossipsub_event)) => {
                     metrics.record(gossipsub_event);
                 }
+                SwarmEvent::Behaviour(Event::RelayServer(relay_server_event)) => {
+                    metrics.record(relay_server_event);
+                }
+                SwarmEvent::Behaviour(Event::Dcutr(dcutr_event)) => {
+                    metrics.record(dcutr_event);
+                }
                 // TODO: implement in the upstream repository
                 // SwarmEvent::Behaviour(Event::RequestResponse(request_response_event)) => {
                 //     self.metrics.record(request_response_event);
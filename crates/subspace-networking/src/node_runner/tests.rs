@@ -0,0 +1,146 @@
+use crate::{construct, Config, NodeRunner, Reachability};
+use libp2p::autonat::{Event as AutonatEvent, NatStatus};
+use libp2p::multiaddr::Protocol;
+use libp2p::swarm::SwarmEvent;
+use libp2p::{Multiaddr, PeerId};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+fn relay_server() -> (PeerId, Multiaddr) {
+    let peer_id = PeerId::random();
+    let address = Multiaddr::from(Protocol::Ip4([127, 0, 0, 1].into()))
+        .with(Protocol::Tcp(30433))
+        .with(Protocol::P2p(peer_id));
+
+    (peer_id, address)
+}
+
+async fn change_nat_status(node_runner: &mut NodeRunner<()>, new: NatStatus) {
+    let old = node_runner.nat_status.clone();
+    node_runner
+        .handle_autonat_event(AutonatEvent::StatusChanged { old, new })
+        .await;
+}
+
+fn public_status() -> NatStatus {
+    NatStatus::Public("/ip4/1.2.3.4/tcp/30433".parse().unwrap())
+}
+
+#[tokio::test]
+async fn relay_listeners_follow_nat_status() {
+    let relay_servers = [relay_server(), relay_server()];
+    let (_node, mut node_runner) = construct(Config {
+        relay_servers: relay_servers
+            .iter()
+            .map(|(_peer_id, address)| address.clone())
+            .collect(),
+        ..Config::default()
+    })
+    .unwrap();
+
+    // No reservations until node is known to be behind NAT
+    change_nat_status(&mut node_runner, NatStatus::Unknown).await;
+    assert!(node_runner.relay_listeners.is_empty());
+
+    change_nat_status(&mut node_runner, NatStatus::Private).await;
+    assert_eq!(node_runner.relay_listeners.len(), relay_servers.len());
+    for (relay_peer_id, _address) in &relay_servers {
+        assert!(node_runner
+            .relay_listeners
+            .values()
+            .any(|peer_id| peer_id == relay_peer_id));
+    }
+
+    // Retries don't create duplicate listeners
+    node_runner.listen_on_relays();
+    assert_eq!(node_runner.relay_listeners.len(), relay_servers.len());
+
+    // Reservation that was closed is requested again on retry
+    let (&listener_id, &closed_relay_peer_id) = node_runner.relay_listeners.iter().next().unwrap();
+    node_runner
+        .handle_swarm_event(SwarmEvent::ListenerClosed {
+            listener_id,
+            addresses: Vec::new(),
+            reason: Ok(()),
+        })
+        .await;
+    assert_eq!(node_runner.relay_listeners.len(), relay_servers.len() - 1);
+    node_runner.handle_periodical_tasks().await;
+    assert_eq!(node_runner.relay_listeners.len(), relay_servers.len());
+    assert!(node_runner
+        .relay_listeners
+        .values()
+        .any(|peer_id| *peer_id == closed_relay_peer_id));
+
+    // Listeners are kept while status is unknown
+    change_nat_status(&mut node_runner, NatStatus::Unknown).await;
+    assert_eq!(node_runner.relay_listeners.len(), relay_servers.len());
+
+    // Not needed anymore once node is reachable directly
+    change_nat_status(&mut node_runner, public_status()).await;
+    assert!(node_runner.relay_listeners.is_empty());
+    assert!(node_runner.relay_reservations.is_empty());
+
+    // Retries don't happen for publicly reachable node
+    node_runner.handle_periodical_tasks().await;
+    assert!(node_runner.relay_listeners.is_empty());
+}
+
+#[tokio::test]
+async fn reachability_change_fires_once_per_transition() {
+    let (relay_peer_id, relay_address) = relay_server();
+    let (node, mut node_runner) = construct(Config {
+        relay_servers: vec![relay_address],
+        ..Config::default()
+    })
+    .unwrap();
+
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let _handler_id = node.on_reachability_change(Arc::new({
+        let changes = Arc::clone(&changes);
+
+        move |reachability| {
+            changes.lock().push(reachability.clone());
+        }
+    }));
+
+    change_nat_status(&mut node_runner, NatStatus::Private).await;
+    // Same status again doesn't change reachability
+    change_nat_status(&mut node_runner, NatStatus::Private).await;
+
+    node_runner.on_relay_reservation_accepted(relay_peer_id, false);
+    // Renewal doesn't change reachability
+    node_runner.on_relay_reservation_accepted(relay_peer_id, true);
+
+    let listener_id = *node_runner.relay_listeners.keys().next().unwrap();
+    node_runner
+        .handle_swarm_event(SwarmEvent::ListenerClosed {
+            listener_id,
+            addresses: Vec::new(),
+            reason: Ok(()),
+        })
+        .await;
+
+    let public = public_status();
+    change_nat_status(&mut node_runner, public.clone()).await;
+    change_nat_status(&mut node_runner, public.clone()).await;
+
+    change_nat_status(&mut node_runner, NatStatus::Unknown).await;
+
+    let NatStatus::Public(address) = public else {
+        unreachable!("Public status was created above; qed");
+    };
+    assert_eq!(
+        *changes.lock(),
+        vec![
+            Reachability::Private,
+            Reachability::Relayed {
+                relays: vec![relay_peer_id]
+            },
+            Reachability::Private,
+            Reachability::Public { address },
+            Reachability::Unknown,
+        ]
+    );
+    assert_eq!(node.reachability(), Reachability::Unknown);
+}
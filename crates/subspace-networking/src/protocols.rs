@@ -1,4 +1,5 @@
 pub(crate) mod autonat_wrapper;
+pub(crate) mod pieces_by_index;
 pub mod request_response;
 pub(crate) mod reserved_peers;
 pub(crate) mod subspace_connection_limits;
@@ -120,6 +120,16 @@ impl NetworkBehaviour for Behaviour {
         match event {
             new_listen_addr_event @ FromSwarm::NewListenAddr(_) => {
                 if let FromSwarm::NewListenAddr(addr) = new_listen_addr_event {
+                    // Relayed addresses are not an indication of node's own reachability
+                    if addr
+                        .addr
+                        .iter()
+                        .any(|protocol| protocol == Protocol::P2pCircuit)
+                    {
+                        debug!(addr=?addr.addr, "Skipped relayed listening address in AutonatWrapper.");
+                        return;
+                    }
+
                     //TODO: handle listener address change
                     self.listen_addresses.insert(addr.addr.clone());
 
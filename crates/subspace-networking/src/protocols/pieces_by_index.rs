@@ -0,0 +1,343 @@
+//! Pieces-by-index protocol for requesting multiple pieces from a peer at once.
+//!
+//! This is a newer version of [`PieceByIndexRequest`](crate::PieceByIndexRequest) that reduces
+//! the number of round trips when many pieces are needed from the same peer (like when plotting a
+//! sector or syncing a segment). Unlike request-response protocols that only allow a single
+//! response, results are sent back one by one as soon as each piece is ready, such that one slow
+//! piece doesn't delay the rest of them.
+//!
+//! Requester opens a stream, writes [`PiecesByIndexRequest`] and closes its side of the stream,
+//! responder writes [`PieceByIndexResult`] for every requested piece in the order pieces become
+//! ready and closes the stream afterwards. Every message is SCALE-encoded and prefixed with its
+//! length as unsigned varint.
+
+#[cfg(test)]
+mod tests;
+
+use crate::utils::bandwidth_limiter::{BandwidthLimiter, Direction};
+use futures::stream::BoxStream;
+use futures::{
+    stream, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Future, Stream, StreamExt,
+};
+use libp2p::{PeerId, StreamProtocol};
+use libp2p_stream::{Control, IncomingStreams, OpenStreamError};
+use parity_scale_codec::{Decode, Encode};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, io};
+use subspace_core_primitives::{Piece, PieceIndex};
+use thiserror::Error;
+use tokio::time::timeout;
+use tracing::debug;
+
+/// Max number of pieces in a single [`PiecesByIndexRequest`].
+pub const MAX_PIECES_PER_REQUEST: usize = 32;
+/// Pieces-by-index protocol name.
+pub(crate) const PROTOCOL_NAME: &str = "/subspace/piece-by-index/0.2.0";
+const PROTOCOL: StreamProtocol = StreamProtocol::new(PROTOCOL_NAME);
+/// Max size of a single message: a single piece with some space left for encoding overhead
+const MAX_MESSAGE_SIZE: usize = Piece::SIZE + 1024;
+/// How long to wait for the request after incoming stream was opened
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+/// How long to wait for the next result (reading pieces from plots might take a while)
+const RESULT_TIMEOUT: Duration = Duration::from_secs(20);
+/// How many incoming requests to handle concurrently, the rest will wait for their turn
+const MAX_CONCURRENT_INCOMING_REQUESTS: usize = 50;
+
+/// Pieces-by-index protocol request.
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+pub struct PiecesByIndexRequest {
+    /// Piece indexes, at most [`MAX_PIECES_PER_REQUEST`]
+    pub piece_indexes: Vec<PieceIndex>,
+    /// Only return pieces that are cached, without reading them from plots (which is slower)
+    pub cached_only: bool,
+}
+
+/// Result for a single piece of [`PiecesByIndexRequest`]
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+pub enum PieceByIndexResult {
+    /// Piece was found
+    Found {
+        /// Piece index
+        piece_index: PieceIndex,
+        /// Piece
+        piece: Piece,
+    },
+    /// Piece was not found
+    NotFound {
+        /// Piece index
+        piece_index: PieceIndex,
+    },
+}
+
+impl PieceByIndexResult {
+    /// Piece index of the result
+    pub fn piece_index(&self) -> PieceIndex {
+        match self {
+            Self::Found { piece_index, .. } | Self::NotFound { piece_index } => *piece_index,
+        }
+    }
+}
+
+/// Errors that might happen when requesting pieces from a peer
+#[derive(Debug, Error)]
+pub enum GetPiecesByIndexError {
+    /// Peer doesn't support pieces-by-index protocol
+    #[error("Peer doesn't support pieces-by-index protocol")]
+    UnsupportedProtocol,
+    /// I/O error
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+type HandlerFn = Box<
+    dyn Fn(PeerId, PiecesByIndexRequest) -> BoxStream<'static, PieceByIndexResult> + Send + Sync,
+>;
+
+/// Handler of incoming [`PiecesByIndexRequest`]s.
+pub struct PiecesByIndexRequestHandler {
+    handler: HandlerFn,
+}
+
+impl fmt::Debug for PiecesByIndexRequestHandler {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PiecesByIndexRequestHandler")
+            .finish_non_exhaustive()
+    }
+}
+
+impl PiecesByIndexRequestHandler {
+    /// Creates new [`PiecesByIndexRequestHandler`] by given handler.
+    ///
+    /// Every result yielded by the stream returned from the handler is sent to the requester right
+    /// away, so results should be yielded in the order pieces become ready rather than in the
+    /// order they were requested. Requests with more than [`MAX_PIECES_PER_REQUEST`] pieces are
+    /// rejected before they reach the handler.
+    pub fn create<H, S>(handler: H) -> Self
+    where
+        H: Fn(PeerId, PiecesByIndexRequest) -> S + Send + Sync + 'static,
+        S: Stream<Item = PieceByIndexResult> + Send + 'static,
+    {
+        Self {
+            handler: Box::new(move |peer_id, request| handler(peer_id, request).boxed()),
+        }
+    }
+}
+
+/// Client side of pieces-by-index protocol
+#[derive(Clone)]
+pub(crate) struct PiecesByIndexClient {
+    control: Control,
+    bandwidth_limiter: Arc<BandwidthLimiter>,
+}
+
+impl fmt::Debug for PiecesByIndexClient {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PiecesByIndexClient")
+            .finish_non_exhaustive()
+    }
+}
+
+impl PiecesByIndexClient {
+    pub(crate) fn new(control: Control, bandwidth_limiter: Arc<BandwidthLimiter>) -> Self {
+        Self {
+            control,
+            bandwidth_limiter,
+        }
+    }
+
+    /// Send request to the peer, returned stream yields results as soon as they are received and
+    /// ends after the last result or after the first error
+    pub(crate) async fn request(
+        &self,
+        peer_id: PeerId,
+        request: PiecesByIndexRequest,
+    ) -> Result<
+        impl Stream<Item = io::Result<PieceByIndexResult>> + Send + 'static,
+        GetPiecesByIndexError,
+    > {
+        let mut stream = self
+            .control
+            .clone()
+            .open_stream(peer_id, PROTOCOL)
+            .await
+            .map_err(|error| match error {
+                OpenStreamError::UnsupportedProtocol(_) => {
+                    GetPiecesByIndexError::UnsupportedProtocol
+                }
+                error => GetPiecesByIndexError::Io(io::Error::other(error)),
+            })?;
+
+        write_message(&mut stream, &request, peer_id, &self.bandwidth_limiter).await?;
+        stream.close().await?;
+
+        let bandwidth_limiter = Arc::clone(&self.bandwidth_limiter);
+
+        Ok(stream::unfold(Some(stream), move |maybe_stream| {
+            let bandwidth_limiter = Arc::clone(&bandwidth_limiter);
+
+            async move {
+                let mut stream = maybe_stream?;
+
+                let result = timeout(
+                    RESULT_TIMEOUT,
+                    read_message::<_, PieceByIndexResult>(&mut stream, peer_id, &bandwidth_limiter),
+                )
+                .await
+                .unwrap_or_else(|_elapsed| Err(io::ErrorKind::TimedOut.into()));
+
+                match result {
+                    Ok(Some(result)) => Some((Ok(result), Some(stream))),
+                    Ok(None) => None,
+                    Err(error) => Some((Err(error), None)),
+                }
+            }
+        }))
+    }
+}
+
+/// Create pieces-by-index client and, if handler is provided, a future that answers incoming
+/// requests (needs to be polled for requests to be processed)
+pub(crate) fn create(
+    mut stream_control: Control,
+    handler: Option<PiecesByIndexRequestHandler>,
+    bandwidth_limiter: Arc<BandwidthLimiter>,
+) -> (
+    PiecesByIndexClient,
+    Option<impl Future<Output = ()> + Send + 'static>,
+) {
+    let client = PiecesByIndexClient::new(stream_control.clone(), Arc::clone(&bandwidth_limiter));
+    let server = handler.map(|handler| {
+        let incoming_streams = stream_control
+            .accept(PROTOCOL)
+            .expect("Protocol is only registered once; qed");
+
+        serve(handler, incoming_streams, bandwidth_limiter)
+    });
+
+    (client, server)
+}
+
+async fn serve(
+    handler: PiecesByIndexRequestHandler,
+    incoming_streams: IncomingStreams,
+    bandwidth_limiter: Arc<BandwidthLimiter>,
+) {
+    incoming_streams
+        .for_each_concurrent(MAX_CONCURRENT_INCOMING_REQUESTS, |(peer_id, mut stream)| {
+            let handler = &handler;
+            let bandwidth_limiter = &bandwidth_limiter;
+
+            async move {
+                if let Err(error) =
+                    handle_request(peer_id, &mut stream, handler, bandwidth_limiter).await
+                {
+                    debug!(%peer_id, %error, "Failed to handle pieces request");
+                }
+            }
+        })
+        .await;
+}
+
+/// Read request from the stream and write results back as soon as handler yields them
+async fn handle_request<S>(
+    peer_id: PeerId,
+    stream: &mut S,
+    handler: &PiecesByIndexRequestHandler,
+    bandwidth_limiter: &BandwidthLimiter,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let request = timeout(
+        REQUEST_TIMEOUT,
+        read_message::<_, PiecesByIndexRequest>(stream, peer_id, bandwidth_limiter),
+    )
+    .await
+    .unwrap_or_else(|_elapsed| Err(io::ErrorKind::TimedOut.into()))?
+    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+
+    if request.piece_indexes.len() > MAX_PIECES_PER_REQUEST {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Too many pieces requested: {} > {MAX_PIECES_PER_REQUEST}",
+                request.piece_indexes.len()
+            ),
+        ));
+    }
+
+    let mut results = (handler.handler)(peer_id, request);
+    while let Some(result) = results.next().await {
+        write_message(stream, &result, peer_id, bandwidth_limiter).await?;
+    }
+
+    stream.close().await
+}
+
+/// Read length-prefixed message, returns `None` if stream was closed before the next message
+async fn read_message<S, T>(
+    stream: &mut S,
+    peer_id: PeerId,
+    bandwidth_limiter: &BandwidthLimiter,
+) -> io::Result<Option<T>>
+where
+    S: AsyncRead + Unpin,
+    T: Decode,
+{
+    let length = match unsigned_varint::aio::read_usize(&mut *stream).await {
+        Ok(length) => length,
+        Err(unsigned_varint::io::ReadError::Io(error))
+            if error.kind() == io::ErrorKind::UnexpectedEof =>
+        {
+            return Ok(None);
+        }
+        Err(error) => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+        }
+    };
+    if length > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Message too large: {length} > {MAX_MESSAGE_SIZE}"),
+        ));
+    }
+
+    // Remote peer can't send faster than allowed since nothing is read until limits allow it
+    bandwidth_limiter
+        .wait(PROTOCOL_NAME, Direction::Download, length)
+        .await;
+    let mut buffer = vec![0; length];
+    stream.read_exact(&mut buffer).await?;
+    bandwidth_limiter.account(PROTOCOL_NAME, peer_id, Direction::Download, length);
+
+    T::decode(&mut buffer.as_slice())
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Write length-prefixed message and flush it, such that it is sent right away
+async fn write_message<S, T>(
+    stream: &mut S,
+    message: &T,
+    peer_id: PeerId,
+    bandwidth_limiter: &BandwidthLimiter,
+) -> io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+    T: Encode,
+{
+    let message = message.encode();
+    bandwidth_limiter
+        .throttle(PROTOCOL_NAME, peer_id, Direction::Upload, message.len())
+        .await;
+
+    let mut buffer = unsigned_varint::encode::usize_buffer();
+    stream
+        .write_all(unsigned_varint::encode::usize(message.len(), &mut buffer))
+        .await?;
+    stream.write_all(&message).await?;
+    stream.flush().await
+}
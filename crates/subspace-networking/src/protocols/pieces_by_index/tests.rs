@@ -0,0 +1,184 @@
+use crate::protocols::pieces_by_index::{
+    handle_request, read_message, write_message, PieceByIndexResult, PiecesByIndexRequest,
+    PiecesByIndexRequestHandler, MAX_PIECES_PER_REQUEST,
+};
+use crate::utils::bandwidth_limiter::{BandwidthLimiter, BandwidthLimits};
+use futures::io::Cursor;
+use futures::{stream, AsyncRead, AsyncWrite, StreamExt};
+use libp2p::PeerId;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use subspace_core_primitives::{Piece, PieceIndex};
+
+/// Stream that reads from a pre-filled buffer and writes into a separate one
+struct TestStream {
+    input: Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+impl AsyncRead for TestStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.input).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TestStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.output).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.output).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.output).poll_close(cx)
+    }
+}
+
+async fn test_stream(
+    request: &PiecesByIndexRequest,
+    peer_id: PeerId,
+    bandwidth_limiter: &BandwidthLimiter,
+) -> TestStream {
+    let mut input = Vec::new();
+    write_message(&mut input, request, peer_id, bandwidth_limiter)
+        .await
+        .unwrap();
+
+    TestStream {
+        input: Cursor::new(input),
+        output: Vec::new(),
+    }
+}
+
+async fn read_results(
+    output: Vec<u8>,
+    peer_id: PeerId,
+    bandwidth_limiter: &BandwidthLimiter,
+) -> Vec<PieceByIndexResult> {
+    let mut output = Cursor::new(output);
+    let mut results = Vec::new();
+    while let Some(result) = read_message(&mut output, peer_id, bandwidth_limiter)
+        .await
+        .unwrap()
+    {
+        results.push(result);
+    }
+
+    results
+}
+
+#[tokio::test]
+async fn results_are_sent_as_soon_as_they_are_ready() {
+    let peer_id = PeerId::random();
+    let bandwidth_limiter = BandwidthLimiter::new(BandwidthLimits::default(), None);
+    let request = PiecesByIndexRequest {
+        piece_indexes: vec![PieceIndex::from(1), PieceIndex::from(2)],
+        cached_only: false,
+    };
+
+    // The first piece is ready right away, while the second one never is
+    let handler = PiecesByIndexRequestHandler::create(|_peer_id, request| {
+        stream::iter([PieceByIndexResult::Found {
+            piece_index: request.piece_indexes[0],
+            piece: Piece::default(),
+        }])
+        .chain(stream::pending())
+    });
+
+    let mut stream = test_stream(&request, peer_id, &bandwidth_limiter).await;
+    tokio::time::timeout(
+        Duration::from_millis(100),
+        handle_request(peer_id, &mut stream, &handler, &bandwidth_limiter),
+    )
+    .await
+    .unwrap_err();
+
+    assert_eq!(
+        read_results(stream.output, peer_id, &bandwidth_limiter).await,
+        vec![PieceByIndexResult::Found {
+            piece_index: PieceIndex::from(1),
+            piece: Piece::default(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn results_are_sent_in_order_of_readiness() {
+    let peer_id = PeerId::random();
+    let bandwidth_limiter = BandwidthLimiter::new(BandwidthLimits::default(), None);
+    let request = PiecesByIndexRequest {
+        piece_indexes: vec![PieceIndex::from(1), PieceIndex::from(2)],
+        cached_only: true,
+    };
+
+    let handler = PiecesByIndexRequestHandler::create(|_peer_id, request| {
+        assert!(request.cached_only);
+
+        stream::iter(request.piece_indexes.into_iter().rev()).map(|piece_index| {
+            if piece_index == PieceIndex::from(1) {
+                PieceByIndexResult::NotFound { piece_index }
+            } else {
+                PieceByIndexResult::Found {
+                    piece_index,
+                    piece: Piece::default(),
+                }
+            }
+        })
+    });
+
+    let mut stream = test_stream(&request, peer_id, &bandwidth_limiter).await;
+    handle_request(peer_id, &mut stream, &handler, &bandwidth_limiter)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        read_results(stream.output, peer_id, &bandwidth_limiter).await,
+        vec![
+            PieceByIndexResult::Found {
+                piece_index: PieceIndex::from(2),
+                piece: Piece::default(),
+            },
+            PieceByIndexResult::NotFound {
+                piece_index: PieceIndex::from(1),
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn too_many_pieces_are_rejected() {
+    let peer_id = PeerId::random();
+    let bandwidth_limiter = BandwidthLimiter::new(BandwidthLimits::default(), None);
+    let request = PiecesByIndexRequest {
+        piece_indexes: (0..=MAX_PIECES_PER_REQUEST as u64)
+            .map(PieceIndex::from)
+            .collect(),
+        cached_only: false,
+    };
+
+    let handler = PiecesByIndexRequestHandler::create(
+        |_peer_id, _request| -> stream::Empty<PieceByIndexResult> {
+            unreachable!("Request must be rejected before it reaches the handler");
+        },
+    );
+
+    let mut stream = test_stream(&request, peer_id, &bandwidth_limiter).await;
+    let error = handle_request(peer_id, &mut stream, &handler, &bandwidth_limiter)
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(stream.output.is_empty());
+}
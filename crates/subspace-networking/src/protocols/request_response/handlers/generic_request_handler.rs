@@ -15,7 +15,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::protocols::request_response::request_response_factory::{
-    IncomingRequest, OutgoingResponse, ProtocolConfig, RequestHandler,
+    IncomingRequest, OutgoingResponse, ProtocolConfig, RequestHandler, DEFAULT_MAX_RESPONSE_SIZE,
 };
 use async_trait::async_trait;
 use futures::channel::mpsc;
@@ -35,6 +35,8 @@ pub trait GenericRequest: Encode + Decode + Send + Sync + 'static {
     const PROTOCOL_NAME: &'static str;
     /// Specifies log-parameters for tracing.
     const LOG_TARGET: &'static str;
+    /// Maximum allowed size of the response in bytes.
+    const MAX_RESPONSE_SIZE: u64 = DEFAULT_MAX_RESPONSE_SIZE;
     /// Response type that corresponds to this request
     type Response: Encode + Decode + Send + Sync + 'static;
 }
@@ -67,6 +69,7 @@ impl<Request: GenericRequest> GenericRequestHandler<Request> {
         let (request_sender, request_receiver) = mpsc::channel(REQUESTS_BUFFER_SIZE);
 
         let mut protocol_config = ProtocolConfig::new(Request::PROTOCOL_NAME);
+        protocol_config.max_response_size = Request::MAX_RESPONSE_SIZE;
         protocol_config.inbound_queue = Some(request_sender);
 
         Box::new(Self {
@@ -160,6 +163,7 @@ impl<Request: GenericRequest> RequestHandler for GenericRequestHandler<Request>
         let (request_sender, request_receiver) = mpsc::channel(REQUESTS_BUFFER_SIZE);
 
         let mut protocol_config = ProtocolConfig::new(Request::PROTOCOL_NAME);
+        protocol_config.max_response_size = Request::MAX_RESPONSE_SIZE;
         protocol_config.inbound_queue = Some(request_sender);
 
         Box::new(Self {
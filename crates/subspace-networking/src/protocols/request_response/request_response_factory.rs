@@ -37,6 +37,7 @@
 #[cfg(test)]
 mod tests;
 
+use crate::utils::bandwidth_limiter::{BandwidthLimiter, Direction};
 use async_trait::async_trait;
 use futures::channel::{mpsc, oneshot};
 use futures::prelude::*;
@@ -59,12 +60,16 @@ use std::borrow::Cow;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
 use std::{io, iter};
 use tracing::{debug, error, warn};
 
 const LOG_TARGET: &str = "request-response-protocols";
+/// Size of chunks requests and responses are read in, download bandwidth limits are enforced
+/// before every chunk is read from the stream
+const READ_CHUNK_SIZE: usize = 64 * 1024;
 
 /// Defines a handler for the request-response protocol factory.
 #[async_trait]
@@ -88,6 +93,9 @@ impl Clone for Box<dyn RequestHandler> {
     }
 }
 
+/// Default maximum allowed size of the response in bytes, see [`ProtocolConfig::max_response_size`]
+pub const DEFAULT_MAX_RESPONSE_SIZE: u64 = 16 * 1024 * 1024;
+
 /// Configuration for a single request-response protocol.
 #[derive(Debug, Clone)]
 pub struct ProtocolConfig {
@@ -140,7 +148,7 @@ impl ProtocolConfig {
         ProtocolConfig {
             name: protocol_name,
             max_request_size: 1024 * 1024,
-            max_response_size: 16 * 1024 * 1024,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
             request_timeout: Duration::from_secs(20),
             inbound_queue: None,
         }
@@ -298,6 +306,9 @@ pub struct RequestResponseFactoryBehaviour {
 
     /// Request handlers future collection.
     request_handlers: Vec<Pin<Box<dyn Future<Output = ()> + Send>>>,
+
+    /// Bandwidth limiter used for throttling of responses to incoming requests.
+    bandwidth_limiter: Arc<BandwidthLimiter>,
 }
 
 // This is a state of processing incoming request Message.
@@ -321,8 +332,9 @@ struct RequestProcessingOutcome {
 impl RequestResponseFactoryBehaviour {
     /// Creates a new behaviour. Must be passed a list of supported protocols. Returns an error if
     /// the same protocol is passed twice.
-    pub fn new(
+    pub(crate) fn new(
         list: impl IntoIterator<Item = Box<dyn RequestHandler>>,
+        bandwidth_limiter: Arc<BandwidthLimiter>,
     ) -> Result<Self, RegisterError> {
         let mut protocols = HashMap::new();
         let mut request_handlers = Vec::new();
@@ -337,8 +349,10 @@ impl RequestResponseFactoryBehaviour {
 
             let rq_rp = RequestResponse::with_codec(
                 GenericCodec {
+                    protocol_name: config.name,
                     max_request_size: config.max_request_size,
                     max_response_size: config.max_response_size,
+                    bandwidth_limiter: Arc::clone(&bandwidth_limiter),
                 },
                 iter::once(StreamProtocol::new(config.name)).zip(iter::repeat(protocol_support)),
                 RequestResponseConfig::default().with_request_timeout(config.request_timeout),
@@ -363,6 +377,7 @@ impl RequestResponseFactoryBehaviour {
             pending_responses: Default::default(),
             message_request: None,
             request_handlers,
+            bandwidth_limiter,
         })
     }
 
@@ -615,6 +630,11 @@ impl NetworkBehaviour for RequestResponseFactoryBehaviour {
 
                 let (tx, rx) = oneshot::channel();
 
+                // Download limit was already enforced while reading the request, so it is only
+                // accounted for
+                self.bandwidth_limiter
+                    .account(&protocol, peer, Direction::Download, request.len());
+
                 // Submit the request to the "response builder" passed by the user at
                 // initialization.
                 if let Some(mut response_builder) = response_builder {
@@ -630,11 +650,18 @@ impl NetworkBehaviour for RequestResponseFactoryBehaviour {
                     debug_assert!(false, "Received message on outbound-only protocol.");
                 }
 
+                let bandwidth_limiter = Arc::clone(&self.bandwidth_limiter);
                 self.pending_responses.push(Box::pin(async move {
                     // The `tx` created above can be dropped if we are not capable of
                     // processing this request, which is reflected as a
                     // `InboundFailure::Omission` event.
                     if let Ok(response) = rx.await {
+                        if let Ok(payload) = &response.result {
+                            bandwidth_limiter
+                                .throttle(&protocol, peer, Direction::Upload, payload.len())
+                                .await;
+                        }
+
                         Some(RequestProcessingOutcome {
                             request_id,
                             protocol: Cow::from(protocol),
@@ -932,8 +959,29 @@ pub enum ResponseFailure {
 #[derive(Debug, Clone)]
 #[doc(hidden)] // Needs to be public in order to satisfy the Rust compiler.
 pub struct GenericCodec {
+    protocol_name: &'static str,
     max_request_size: u64,
     max_response_size: u64,
+    bandwidth_limiter: Arc<BandwidthLimiter>,
+}
+
+impl GenericCodec {
+    /// Read payload of `length` bytes in chunks, waiting for download bandwidth limits before each
+    /// chunk is read from the stream
+    async fn read_payload<T>(&self, io: &mut T, length: usize) -> io::Result<Vec<u8>>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut buffer = vec![0; length];
+        for chunk in buffer.chunks_mut(READ_CHUNK_SIZE) {
+            self.bandwidth_limiter
+                .wait(self.protocol_name, Direction::Download, chunk.len())
+                .await;
+            io.read_exact(chunk).await?;
+        }
+
+        Ok(buffer)
+    }
 }
 
 #[async_trait::async_trait]
@@ -965,9 +1013,7 @@ impl RequestResponseCodec for GenericCodec {
         }
 
         // Read the payload.
-        let mut buffer = vec![0; length];
-        io.read_exact(&mut buffer).await?;
-        Ok(buffer)
+        self.read_payload(io, length).await
     }
 
     async fn read_response<T>(
@@ -1005,9 +1051,7 @@ impl RequestResponseCodec for GenericCodec {
         }
 
         // Read the payload.
-        let mut buffer = vec![0; length];
-        io.read_exact(&mut buffer).await?;
-        Ok(Ok(buffer))
+        self.read_payload(io, length).await.map(Ok)
     }
 
     async fn write_request<T>(
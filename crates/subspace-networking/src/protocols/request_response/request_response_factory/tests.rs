@@ -1,17 +1,23 @@
 use crate::protocols::request_response::request_response_factory::{
-    Event, IfDisconnected, IncomingRequest, OutboundFailure, OutgoingResponse, ProtocolConfig,
-    RequestFailure, RequestHandler, RequestResponseFactoryBehaviour,
+    Event, GenericCodec, IfDisconnected, IncomingRequest, OutboundFailure, OutgoingResponse,
+    ProtocolConfig, RequestFailure, RequestHandler, RequestResponseFactoryBehaviour,
 };
+use crate::utils::bandwidth_limiter::{BandwidthLimiter, BandwidthLimits, ProtocolBandwidthLimits};
 use async_trait::async_trait;
 use futures::channel::{mpsc, oneshot};
+use futures::io::Cursor;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use libp2p::core::transport::{MemoryTransport, Transport};
 use libp2p::core::upgrade;
+use libp2p::request_response::Codec;
 use libp2p::swarm::{Swarm, SwarmEvent};
-use libp2p::{noise, SwarmBuilder};
+use libp2p::{noise, StreamProtocol, SwarmBuilder};
 use libp2p_swarm_test::SwarmExt;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{io, iter};
 
 #[derive(Clone)]
@@ -41,7 +47,8 @@ async fn build_swarm(
         .into_iter()
         .map(|config| Box::new(MockRunner(config)) as Box<dyn RequestHandler>)
         .collect::<Vec<_>>();
-    let behaviour = RequestResponseFactoryBehaviour::new(configs).unwrap();
+    let bandwidth_limiter = Arc::new(BandwidthLimiter::new(BandwidthLimits::default(), None));
+    let behaviour = RequestResponseFactoryBehaviour::new(configs, bandwidth_limiter).unwrap();
 
     let mut swarm = SwarmBuilder::with_new_identity()
         .with_tokio()
@@ -367,3 +374,42 @@ async fn request_id_collision() {
     assert_eq!(receiver_1.await.unwrap().unwrap(), b"this is a response 1");
     assert_eq!(receiver_2.await.unwrap().unwrap(), b"this is a response 2");
 }
+
+#[tokio::test]
+async fn download_limit_is_enforced_while_reading() {
+    const PROTOCOL_NAME: &str = "/test/bandwidth/1";
+    let download_limit = 128 * 1024;
+
+    let mut codec = GenericCodec {
+        protocol_name: PROTOCOL_NAME,
+        max_request_size: 1024 * 1024,
+        max_response_size: 1024 * 1024,
+        bandwidth_limiter: Arc::new(BandwidthLimiter::new(
+            BandwidthLimits {
+                global: ProtocolBandwidthLimits {
+                    upload: None,
+                    download: NonZeroU64::new(download_limit),
+                },
+                protocols: HashMap::new(),
+            },
+            None,
+        )),
+    };
+    let protocol = StreamProtocol::new(PROTOCOL_NAME);
+
+    let response = vec![1; download_limit as usize * 2];
+    let mut encoded_response = Vec::new();
+    codec
+        .write_response(&protocol, &mut encoded_response, Ok(response.clone()))
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    let received_response = codec
+        .read_response(&protocol, &mut Cursor::new(encoded_response))
+        .await
+        .unwrap();
+    assert_eq!(received_response, Ok(response));
+    // The first second worth of bytes is a burst, the rest is read no faster than the limit
+    assert!(started.elapsed() >= Duration::from_millis(900));
+}
@@ -1,12 +1,16 @@
 //! Data structures shared between node and node runner, facilitating exchange and creation of
 //! queries, subscriptions, various events and shared information.
 
+use crate::protocols::pieces_by_index::PiecesByIndexClient;
 use crate::protocols::request_response::request_response_factory::RequestFailure;
+use crate::utils::bandwidth_limiter::BandwidthLimiter;
 use crate::utils::multihash::Multihash;
+use crate::utils::peer_reputation::PeerReputation;
 use crate::utils::rate_limiter::RateLimiter;
 use crate::utils::Handler;
 use bytes::Bytes;
 use futures::channel::{mpsc, oneshot};
+use libp2p::autonat::NatStatus;
 use libp2p::gossipsub::{PublishError, Sha256Topic, SubscriptionError};
 use libp2p::kad::{PeerRecord, RecordKey};
 use libp2p::{Multiaddr, PeerId};
@@ -43,6 +47,53 @@ impl PeerDiscovered {
     }
 }
 
+/// Reachability of the node by other peers in the network.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Reachability {
+    /// Reachability is not determined yet.
+    #[default]
+    Unknown,
+    /// Node is reachable directly.
+    Public {
+        /// Confirmed public address
+        address: Multiaddr,
+    },
+    /// Node is behind NAT and not reachable by other peers, it can't serve pieces it holds.
+    Private,
+    /// Node is behind NAT, but reachable through relays, after which direct connection is
+    /// attempted using hole punching.
+    Relayed {
+        /// Peer IDs of relays with active reservations
+        relays: Vec<PeerId>,
+    },
+}
+
+impl Reachability {
+    pub(crate) fn new<I>(nat_status: NatStatus, relays: I) -> Self
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        match nat_status {
+            NatStatus::Public(address) => Self::Public { address },
+            NatStatus::Private => {
+                let relays = relays.into_iter().collect::<Vec<_>>();
+
+                if relays.is_empty() {
+                    Self::Private
+                } else {
+                    Self::Relayed { relays }
+                }
+            }
+            NatStatus::Unknown => Self::Unknown,
+        }
+    }
+
+    /// Whether other peers can connect to the node (directly or through relays).
+    pub fn is_reachable(&self) -> bool {
+        matches!(self, Self::Public { .. } | Self::Relayed { .. })
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct CreatedSubscription {
     /// Subscription ID to be used for unsubscribing.
@@ -115,6 +166,7 @@ pub(crate) struct Handlers {
     pub(crate) connected_peer: Handler<PeerId>,
     pub(crate) disconnected_peer: Handler<PeerId>,
     pub(crate) peer_discovered: Handler<PeerDiscovered>,
+    pub(crate) reachability_change: Handler<Reachability>,
 }
 
 #[derive(Debug)]
@@ -124,10 +176,15 @@ pub(crate) struct Shared {
     /// Addresses on which node is listening for incoming requests.
     pub(crate) listeners: Mutex<Vec<Multiaddr>>,
     pub(crate) external_addresses: Mutex<Vec<Multiaddr>>,
+    /// Current reachability of the node by other peers.
+    pub(crate) reachability: Mutex<Reachability>,
     pub(crate) num_established_peer_connections: Arc<AtomicUsize>,
     /// Sender end of the channel for sending commands to the swarm.
     pub(crate) command_sender: mpsc::Sender<Command>,
     pub(crate) rate_limiter: RateLimiter,
+    pub(crate) peer_reputation: PeerReputation,
+    pub(crate) bandwidth_limiter: Arc<BandwidthLimiter>,
+    pub(crate) pieces_by_index_client: PiecesByIndexClient,
 }
 
 impl Shared {
@@ -135,15 +192,22 @@ impl Shared {
         id: PeerId,
         command_sender: mpsc::Sender<Command>,
         rate_limiter: RateLimiter,
+        peer_reputation: PeerReputation,
+        bandwidth_limiter: Arc<BandwidthLimiter>,
+        pieces_by_index_client: PiecesByIndexClient,
     ) -> Self {
         Self {
             handlers: Handlers::default(),
             id,
             listeners: Mutex::default(),
             external_addresses: Mutex::default(),
+            reachability: Mutex::default(),
             num_established_peer_connections: Arc::new(AtomicUsize::new(0)),
             command_sender,
             rate_limiter,
+            peer_reputation,
+            bandwidth_limiter,
+            pieces_by_index_client,
         }
     }
 }
@@ -1,20 +1,27 @@
 //! Miscellaneous utilities for networking.
 
+pub mod bandwidth_limiter;
 pub(crate) mod key_with_distance;
 pub mod multihash;
+pub mod peer_reputation;
 pub mod piece_provider;
 pub(crate) mod rate_limiter;
 
+use crate::constructor::transport::TransportProtocol;
 use event_listener_primitives::Bag;
 use futures::future::{Fuse, FusedFuture, FutureExt};
 use libp2p::multiaddr::Protocol;
 use libp2p::{Multiaddr, PeerId};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
 use prometheus_client::metrics::gauge::Gauge;
-use prometheus_client::registry::Registry;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::{Registry, Unit};
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 use tokio::runtime::Handle;
 use tokio::task;
 use tracing::warn;
@@ -22,8 +29,16 @@ use tracing::warn;
 const NETWORKING_REGISTRY_PREFIX: &str = "subspace";
 
 /// Metrics for Subspace networking
+#[derive(Clone)]
 pub struct SubspaceMetrics {
     established_connections: Gauge,
+    established_connections_by_transport: Family<Vec<(&'static str, String)>, Gauge>,
+    failed_dials_by_transport: Family<Vec<(&'static str, String)>, Counter>,
+    peer_reputation_changes: Family<Vec<(&'static str, String)>, Counter>,
+    request_latency: Histogram,
+    bandwidth_bytes: Family<Vec<(&'static str, String)>, Counter>,
+    bandwidth_throttled: Family<Vec<(&'static str, String)>, Counter>,
+    relay_reservations: Gauge,
 }
 
 impl SubspaceMetrics {
@@ -38,18 +53,125 @@ impl SubspaceMetrics {
             gauge.clone(),
         );
 
+        let established_connections_by_transport = Family::default();
+        sub_registry.register(
+            "established_connections_by_transport",
+            "The current number of established connections by transport protocol",
+            established_connections_by_transport.clone(),
+        );
+
+        let failed_dials_by_transport = Family::default();
+        sub_registry.register(
+            "failed_dials_by_transport",
+            "Number of addresses that failed to be dialed by transport protocol",
+            failed_dials_by_transport.clone(),
+        );
+
+        let peer_reputation_changes = Family::default();
+        sub_registry.register(
+            "peer_reputation_changes",
+            "Number of peer reputation changes by kind",
+            peer_reputation_changes.clone(),
+        );
+
+        let request_latency = Histogram::new(exponential_buckets(0.01, 2.0, 12));
+        sub_registry.register_with_unit(
+            "request_latency",
+            "Latency of successful requests to peers",
+            Unit::Seconds,
+            request_latency.clone(),
+        );
+
+        let bandwidth_bytes = Family::default();
+        sub_registry.register_with_unit(
+            "bandwidth",
+            "Bytes transferred by request-response protocols by protocol and direction",
+            Unit::Bytes,
+            bandwidth_bytes.clone(),
+        );
+
+        let bandwidth_throttled = Family::default();
+        sub_registry.register(
+            "bandwidth_throttled",
+            "Number of times transfers were delayed due to bandwidth limits by direction",
+            bandwidth_throttled.clone(),
+        );
+
+        let relay_reservations = Gauge::default();
+        sub_registry.register(
+            "relay_reservations",
+            "The current number of accepted reservations on relay servers",
+            relay_reservations.clone(),
+        );
+
         Self {
             established_connections: gauge,
+            established_connections_by_transport,
+            failed_dials_by_transport,
+            peer_reputation_changes,
+            request_latency,
+            bandwidth_bytes,
+            bandwidth_throttled,
+            relay_reservations,
         }
     }
 
-    pub(crate) fn inc_established_connections(&mut self) {
+    pub(crate) fn inc_established_connections(&mut self, address: &Multiaddr) {
         self.established_connections.inc();
+        self.established_connections_by_transport
+            .get_or_create(&transport_labels(address))
+            .inc();
     }
 
-    pub(crate) fn dec_established_connections(&mut self) {
+    pub(crate) fn dec_established_connections(&mut self, address: &Multiaddr) {
         self.established_connections.dec();
+        self.established_connections_by_transport
+            .get_or_create(&transport_labels(address))
+            .dec();
     }
+
+    pub(crate) fn inc_failed_dials(&mut self, address: &Multiaddr) {
+        self.failed_dials_by_transport
+            .get_or_create(&transport_labels(address))
+            .inc();
+    }
+
+    pub(crate) fn inc_peer_reputation_changes(&self, change: &str) {
+        self.peer_reputation_changes
+            .get_or_create(&vec![("change", change.to_string())])
+            .inc();
+    }
+
+    pub(crate) fn observe_request_latency(&self, latency: Duration) {
+        self.request_latency.observe(latency.as_secs_f64());
+    }
+
+    pub(crate) fn inc_bandwidth_bytes(&self, protocol: &str, direction: &str, bytes: u64) {
+        self.bandwidth_bytes
+            .get_or_create(&vec![
+                ("protocol", protocol.to_string()),
+                ("direction", direction.to_string()),
+            ])
+            .inc_by(bytes);
+    }
+
+    pub(crate) fn inc_bandwidth_throttled(&self, direction: &str) {
+        self.bandwidth_throttled
+            .get_or_create(&vec![("direction", direction.to_string())])
+            .inc();
+    }
+
+    pub(crate) fn set_relay_reservations(&self, relay_reservations: usize) {
+        self.relay_reservations.set(relay_reservations as i64);
+    }
+}
+
+fn transport_labels(address: &Multiaddr) -> Vec<(&'static str, String)> {
+    let transport = TransportProtocol::from_address(address)
+        .map(|transport| transport.to_string())
+        .unwrap_or_else(|| "other".to_string());
+
+    vec![("transport", transport)]
 }
 
 /// Joins async join handle on drop
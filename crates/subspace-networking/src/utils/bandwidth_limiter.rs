@@ -0,0 +1,302 @@
+//! Bandwidth limiting and accounting for request-response protocols.
+//!
+//! Limits are enforced with token buckets that allow bursts of up to one second worth of traffic.
+//! Bytes transferred beyond available tokens are accounted as debt that the caller has to wait
+//! out. Uploads wait before requests and responses are sent, downloads wait before every chunk of
+//! requests and responses is read from the stream, such that remote peers can't send faster than
+//! allowed.
+
+#[cfg(test)]
+mod tests;
+
+use crate::utils::SubspaceMetrics;
+use libp2p::PeerId;
+use parking_lot::Mutex;
+use schnellru::{ByLength, LruMap};
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroU64;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tracing::trace;
+
+/// How many peers to keep bandwidth statistics for
+const PEER_BANDWIDTH_CACHE_SIZE: u32 = 10_000;
+
+/// Upload and download limits in bytes per second, `None` means unlimited
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct ProtocolBandwidthLimits {
+    /// Upload limit in bytes per second
+    pub upload: Option<NonZeroU64>,
+    /// Download limit in bytes per second
+    pub download: Option<NonZeroU64>,
+}
+
+/// Bandwidth limits for request-response protocols, no limits by default
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct BandwidthLimits {
+    /// Limits for all request-response protocols combined
+    pub global: ProtocolBandwidthLimits,
+    /// Limits for individual protocols by protocol name (like `/subspace/piece-by-index/0.2.0`),
+    /// applied in addition to global limits
+    pub protocols: HashMap<String, ProtocolBandwidthLimits>,
+}
+
+impl BandwidthLimits {
+    /// Create bandwidth limits from global limits and per-protocol limits, per-protocol limits
+    /// are pairs of protocol name and limit in bytes per second (see
+    /// [`parse_protocol_bandwidth_limit()`])
+    pub fn new<U, D>(
+        upload: Option<NonZeroU64>,
+        download: Option<NonZeroU64>,
+        protocol_upload_limits: U,
+        protocol_download_limits: D,
+    ) -> Self
+    where
+        U: IntoIterator<Item = (String, NonZeroU64)>,
+        D: IntoIterator<Item = (String, NonZeroU64)>,
+    {
+        let mut protocols = HashMap::<String, ProtocolBandwidthLimits>::new();
+        for (protocol, limit) in protocol_upload_limits {
+            protocols.entry(protocol).or_default().upload = Some(limit);
+        }
+        for (protocol, limit) in protocol_download_limits {
+            protocols.entry(protocol).or_default().download = Some(limit);
+        }
+
+        Self {
+            global: ProtocolBandwidthLimits { upload, download },
+            protocols,
+        }
+    }
+}
+
+/// Parse protocol bandwidth limit in format `PROTOCOL=BYTES_PER_SECOND`, meant to be used as
+/// value parser for CLI arguments.
+pub fn parse_protocol_bandwidth_limit(s: &str) -> Result<(String, NonZeroU64), String> {
+    let (protocol, limit) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("Expected `PROTOCOL=BYTES_PER_SECOND`, got `{s}`"))?;
+
+    if protocol.is_empty() {
+        return Err(format!("Protocol name is missing in `{s}`"));
+    }
+
+    let limit = limit
+        .parse::<NonZeroU64>()
+        .map_err(|error| format!("Invalid limit `{limit}`: {error}"))?;
+
+    Ok((protocol.to_string(), limit))
+}
+
+/// Number of bytes exchanged with a peer using request-response protocols
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct PeerBandwidth {
+    /// Bytes uploaded to the peer
+    pub uploaded: u64,
+    /// Bytes downloaded from the peer
+    pub downloaded: u64,
+}
+
+/// Direction of the traffic
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum Direction {
+    Upload,
+    Download,
+}
+
+impl Direction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Upload => "upload",
+            Self::Download => "download",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucketState {
+    /// Available tokens (bytes), negative value means debt
+    available: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket with capacity of one second worth of tokens
+#[derive(Debug)]
+struct TokenBucket {
+    /// Bytes per second
+    rate: f64,
+    state: Mutex<TokenBucketState>,
+}
+
+impl TokenBucket {
+    fn new(rate: NonZeroU64, now: Instant) -> Self {
+        let rate = rate.get() as f64;
+
+        Self {
+            rate,
+            state: Mutex::new(TokenBucketState {
+                available: rate,
+                last_refill: now,
+            }),
+        }
+    }
+
+    /// Consume specified number of bytes and return how long to wait until the debt is paid off
+    fn consume(&self, bytes: usize, now: Instant) -> Duration {
+        let mut state = self.state.lock();
+
+        let elapsed = now.saturating_duration_since(state.last_refill);
+        state.available = (state.available + elapsed.as_secs_f64() * self.rate).min(self.rate);
+        state.last_refill = now;
+        state.available -= bytes as f64;
+
+        if state.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.available / self.rate)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    upload: Option<TokenBucket>,
+    download: Option<TokenBucket>,
+}
+
+impl Buckets {
+    fn new(limits: ProtocolBandwidthLimits, now: Instant) -> Self {
+        Self {
+            upload: limits.upload.map(|rate| TokenBucket::new(rate, now)),
+            download: limits.download.map(|rate| TokenBucket::new(rate, now)),
+        }
+    }
+
+    fn consume(&self, direction: Direction, bytes: usize, now: Instant) -> Duration {
+        let bucket = match direction {
+            Direction::Upload => &self.upload,
+            Direction::Download => &self.download,
+        };
+
+        bucket
+            .as_ref()
+            .map(|bucket| bucket.consume(bytes, now))
+            .unwrap_or_default()
+    }
+}
+
+/// Enforces bandwidth limits and collects bandwidth statistics
+pub(crate) struct BandwidthLimiter {
+    global: Buckets,
+    protocols: HashMap<String, Buckets>,
+    peers: Mutex<LruMap<PeerId, PeerBandwidth>>,
+    metrics: Option<SubspaceMetrics>,
+}
+
+impl fmt::Debug for BandwidthLimiter {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BandwidthLimiter").finish_non_exhaustive()
+    }
+}
+
+impl BandwidthLimiter {
+    pub(crate) fn new(limits: BandwidthLimits, metrics: Option<SubspaceMetrics>) -> Self {
+        let now = Instant::now();
+
+        Self {
+            global: Buckets::new(limits.global, now),
+            protocols: limits
+                .protocols
+                .into_iter()
+                .map(|(protocol, limits)| (protocol, Buckets::new(limits, now)))
+                .collect(),
+            peers: Mutex::new(LruMap::new(ByLength::new(PEER_BANDWIDTH_CACHE_SIZE))),
+            metrics,
+        }
+    }
+
+    /// Account transferred bytes and wait for as long as necessary to stay within limits
+    pub(crate) async fn throttle(
+        &self,
+        protocol: &str,
+        peer_id: PeerId,
+        direction: Direction,
+        bytes: usize,
+    ) {
+        self.account(protocol, peer_id, direction, bytes);
+        self.wait(protocol, direction, bytes).await;
+    }
+
+    /// Consume limits for bytes that are about to be transferred and wait for as long as
+    /// necessary to stay within limits.
+    ///
+    /// Used for reading from streams before bytes are accepted, such that download limits are
+    /// enforced by not reading from the stream faster than allowed. Bytes are not accounted, use
+    /// [`Self::account()`] once the peer is known.
+    pub(crate) async fn wait(&self, protocol: &str, direction: Direction, bytes: usize) {
+        let delay = self.delay_at(protocol, direction, bytes, Instant::now());
+
+        if !delay.is_zero() {
+            trace!(%protocol, ?direction, ?delay, "Throttling due to bandwidth limit");
+
+            if let Some(metrics) = &self.metrics {
+                metrics.inc_bandwidth_throttled(direction.as_str());
+            }
+
+            sleep(delay).await;
+        }
+    }
+
+    /// Account transferred bytes in metrics and per-peer statistics without consuming limits
+    pub(crate) fn account(
+        &self,
+        protocol: &str,
+        peer_id: PeerId,
+        direction: Direction,
+        bytes: usize,
+    ) {
+        if let Some(metrics) = &self.metrics {
+            metrics.inc_bandwidth_bytes(protocol, direction.as_str(), bytes as u64);
+        }
+
+        if let Some(peer_bandwidth) = self
+            .peers
+            .lock()
+            .get_or_insert(peer_id, PeerBandwidth::default)
+        {
+            match direction {
+                Direction::Upload => peer_bandwidth.uploaded += bytes as u64,
+                Direction::Download => peer_bandwidth.downloaded += bytes as u64,
+            }
+        }
+    }
+
+    /// Consume limits for transferred bytes, returns delay that is necessary to stay within limits
+    fn delay_at(
+        &self,
+        protocol: &str,
+        direction: Direction,
+        bytes: usize,
+        now: Instant,
+    ) -> Duration {
+        let global_delay = self.global.consume(direction, bytes, now);
+        let protocol_delay = self
+            .protocols
+            .get(protocol)
+            .map(|buckets| buckets.consume(direction, bytes, now))
+            .unwrap_or_default();
+
+        global_delay.max(protocol_delay)
+    }
+
+    /// Bandwidth statistics of peers
+    pub(crate) fn peer_bandwidth(&self) -> HashMap<PeerId, PeerBandwidth> {
+        self.peers
+            .lock()
+            .iter()
+            .map(|(peer_id, peer_bandwidth)| (*peer_id, *peer_bandwidth))
+            .collect()
+    }
+}
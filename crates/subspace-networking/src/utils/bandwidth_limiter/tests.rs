@@ -0,0 +1,165 @@
+use crate::utils::bandwidth_limiter::{
+    parse_protocol_bandwidth_limit, BandwidthLimiter, BandwidthLimits, Direction, PeerBandwidth,
+    ProtocolBandwidthLimits,
+};
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::time::{Duration, Instant};
+
+const PIECE_PROTOCOL: &str = "/subspace/piece-by-index/0.2.0";
+const SEGMENT_HEADER_PROTOCOL: &str = "/subspace/segment-headers-by-indexes/0.1.0";
+
+#[test]
+fn global_and_protocol_limits() {
+    let limiter = BandwidthLimiter::new(
+        BandwidthLimits {
+            global: ProtocolBandwidthLimits {
+                upload: NonZeroU64::new(1_000),
+                download: None,
+            },
+            protocols: HashMap::from([(
+                PIECE_PROTOCOL.to_string(),
+                ProtocolBandwidthLimits {
+                    upload: None,
+                    download: NonZeroU64::new(100),
+                },
+            )]),
+        },
+        None,
+    );
+    let now = Instant::now();
+
+    // Burst of one second worth of traffic is allowed
+    assert_eq!(
+        limiter.delay_at(PIECE_PROTOCOL, Direction::Upload, 1_000, now),
+        Duration::ZERO
+    );
+    // Anything beyond that needs to be waited out
+    assert_eq!(
+        limiter.delay_at(SEGMENT_HEADER_PROTOCOL, Direction::Upload, 500, now),
+        Duration::from_millis(500)
+    );
+    // Debt is paid off over time
+    assert_eq!(
+        limiter.delay_at(
+            PIECE_PROTOCOL,
+            Direction::Upload,
+            500,
+            now + Duration::from_secs(1)
+        ),
+        Duration::ZERO
+    );
+
+    // Download is only limited for piece protocol
+    assert_eq!(
+        limiter.delay_at(SEGMENT_HEADER_PROTOCOL, Direction::Download, 10_000, now),
+        Duration::ZERO
+    );
+    assert_eq!(
+        limiter.delay_at(PIECE_PROTOCOL, Direction::Download, 300, now),
+        Duration::from_secs(2)
+    );
+}
+
+#[test]
+fn unused_bandwidth_is_not_accumulated() {
+    let limiter = BandwidthLimiter::new(
+        BandwidthLimits {
+            global: ProtocolBandwidthLimits {
+                upload: None,
+                download: NonZeroU64::new(1_000),
+            },
+            protocols: HashMap::new(),
+        },
+        None,
+    );
+    let now = Instant::now() + Duration::from_secs(60);
+
+    assert_eq!(
+        limiter.delay_at(PIECE_PROTOCOL, Direction::Download, 2_000, now),
+        Duration::from_secs(1)
+    );
+}
+
+#[test]
+fn peer_bandwidth_is_accounted() {
+    let limiter = BandwidthLimiter::new(BandwidthLimits::default(), None);
+    let peer_1 = PeerId::random();
+    let peer_2 = PeerId::random();
+
+    limiter.account(PIECE_PROTOCOL, peer_1, Direction::Upload, 100);
+    limiter.account(SEGMENT_HEADER_PROTOCOL, peer_1, Direction::Upload, 10);
+    limiter.account(PIECE_PROTOCOL, peer_1, Direction::Download, 5);
+    limiter.account(PIECE_PROTOCOL, peer_2, Direction::Download, 1_000);
+
+    let peer_bandwidth = limiter.peer_bandwidth();
+    assert_eq!(
+        peer_bandwidth[&peer_1],
+        PeerBandwidth {
+            uploaded: 110,
+            downloaded: 5,
+        }
+    );
+    assert_eq!(
+        peer_bandwidth[&peer_2],
+        PeerBandwidth {
+            uploaded: 0,
+            downloaded: 1_000,
+        }
+    );
+}
+
+#[test]
+fn protocol_bandwidth_limit_parsing() {
+    assert_eq!(
+        parse_protocol_bandwidth_limit("/subspace/piece-by-index/0.2.0=1000"),
+        Ok((PIECE_PROTOCOL.to_string(), NonZeroU64::new(1_000).unwrap()))
+    );
+    assert!(parse_protocol_bandwidth_limit("/subspace/piece-by-index/0.2.0").is_err());
+    assert!(parse_protocol_bandwidth_limit("=1000").is_err());
+    assert!(parse_protocol_bandwidth_limit("/subspace/piece-by-index/0.2.0=0").is_err());
+    assert!(parse_protocol_bandwidth_limit("/subspace/piece-by-index/0.2.0=fast").is_err());
+}
+
+#[test]
+fn bandwidth_limits_from_cli_arguments() {
+    let limits = BandwidthLimits::new(
+        NonZeroU64::new(1_000),
+        None,
+        [(PIECE_PROTOCOL.to_string(), NonZeroU64::new(100).unwrap())],
+        [
+            (PIECE_PROTOCOL.to_string(), NonZeroU64::new(200).unwrap()),
+            (
+                SEGMENT_HEADER_PROTOCOL.to_string(),
+                NonZeroU64::new(300).unwrap(),
+            ),
+        ],
+    );
+
+    assert_eq!(
+        limits,
+        BandwidthLimits {
+            global: ProtocolBandwidthLimits {
+                upload: NonZeroU64::new(1_000),
+                download: None,
+            },
+            protocols: HashMap::from([
+                (
+                    PIECE_PROTOCOL.to_string(),
+                    ProtocolBandwidthLimits {
+                        upload: NonZeroU64::new(100),
+                        download: NonZeroU64::new(200),
+                    }
+                ),
+                (
+                    SEGMENT_HEADER_PROTOCOL.to_string(),
+                    ProtocolBandwidthLimits {
+                        upload: None,
+                        download: NonZeroU64::new(300),
+                    }
+                ),
+            ]),
+        }
+    );
+}
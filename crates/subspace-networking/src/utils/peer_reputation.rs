@@ -0,0 +1,235 @@
+//! Reputation of peers based on how they respond to requests.
+//!
+//! Every peer has a score that is increased by successful requests (the faster peer responds, the
+//! larger the reward) and decreased by failed or timed out requests as well as invalid pieces.
+//! Score decays towards neutral value over time, such that both good and bad behavior are
+//! eventually forgotten.
+
+#[cfg(test)]
+mod tests;
+
+use crate::utils::SubspaceMetrics;
+use libp2p::PeerId;
+use parking_lot::Mutex;
+use schnellru::{ByLength, LruMap};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Score of a peer without any history, score decays towards this value over time
+pub const NEUTRAL_SCORE: f64 = 0.0;
+/// Minimum possible score of a peer
+pub const MIN_SCORE: f64 = -100.0;
+/// Maximum possible score of a peer
+pub const MAX_SCORE: f64 = 100.0;
+/// Peers with score below this value are considered to be misbehaving and only used as the last
+/// resort
+pub const LOW_SCORE_THRESHOLD: f64 = -20.0;
+/// Maximum reward for a successful request, actual reward depends on response latency
+const SUCCESS_REWARD: f64 = 1.0;
+/// Latency at which reward for successful request is half of [`SUCCESS_REWARD`]
+const REFERENCE_LATENCY: Duration = Duration::from_secs(1);
+/// Penalty for a failed request
+const FAILURE_PENALTY: f64 = -5.0;
+/// Penalty for a timed out request
+const TIMEOUT_PENALTY: f64 = -10.0;
+/// Penalty for an invalid piece
+const INVALID_PIECE_PENALTY: f64 = -50.0;
+/// Time after which score is halved
+const SCORE_HALF_LIFE: Duration = Duration::from_secs(10 * 60);
+/// Weight of the latest latency sample in average latency
+const LATENCY_SMOOTHING_FACTOR: f64 = 0.2;
+/// How many peers to keep reputation for
+const PEER_REPUTATION_CACHE_SIZE: u32 = 10_000;
+
+/// Reputation score of a peer
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct PeerScore {
+    /// Current score, between [`MIN_SCORE`] and [`MAX_SCORE`], [`NEUTRAL_SCORE`] for peers without
+    /// history
+    pub score: f64,
+    /// Number of successful requests
+    pub successful_requests: u64,
+    /// Number of failed requests, not including timed out requests
+    pub failed_requests: u64,
+    /// Number of timed out requests
+    pub timed_out_requests: u64,
+    /// Number of invalid pieces received from peer
+    pub invalid_pieces: u64,
+    /// Exponential moving average of successful requests latency
+    pub average_latency: Option<Duration>,
+}
+
+impl PeerScore {
+    /// Share of successful requests among all requests sent to the peer, `None` if no requests
+    /// were sent yet
+    pub fn success_rate(&self) -> Option<f64> {
+        let total_requests =
+            self.successful_requests + self.failed_requests + self.timed_out_requests;
+
+        (total_requests > 0).then(|| self.successful_requests as f64 / total_requests as f64)
+    }
+}
+
+/// Event that changes reputation of a peer
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum ReputationChange {
+    /// Request succeeded with specified latency
+    Success { latency: Duration },
+    /// Request failed
+    Failure,
+    /// Request timed out
+    Timeout,
+    /// Peer returned invalid piece
+    InvalidPiece,
+}
+
+impl ReputationChange {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success { .. } => "success",
+            Self::Failure => "failure",
+            Self::Timeout => "timeout",
+            Self::InvalidPiece => "invalid_piece",
+        }
+    }
+
+    fn score_change(&self) -> f64 {
+        match self {
+            Self::Success { latency } => {
+                SUCCESS_REWARD * REFERENCE_LATENCY.as_secs_f64()
+                    / (REFERENCE_LATENCY.as_secs_f64() + latency.as_secs_f64())
+            }
+            Self::Failure => FAILURE_PENALTY,
+            Self::Timeout => TIMEOUT_PENALTY,
+            Self::InvalidPiece => INVALID_PIECE_PENALTY,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct PeerReputationEntry {
+    score: PeerScore,
+    last_update: Instant,
+}
+
+impl PeerReputationEntry {
+    /// Score with decay applied up to specified moment
+    fn decayed_score(&self, now: Instant) -> PeerScore {
+        let elapsed = now.saturating_duration_since(self.last_update);
+        let decay = 0.5_f64.powf(elapsed.as_secs_f64() / SCORE_HALF_LIFE.as_secs_f64());
+
+        PeerScore {
+            score: NEUTRAL_SCORE + (self.score.score - NEUTRAL_SCORE) * decay,
+            ..self.score
+        }
+    }
+}
+
+/// Reputation of peers the node interacted with
+pub(crate) struct PeerReputation {
+    peers: Mutex<LruMap<PeerId, PeerReputationEntry>>,
+    metrics: Option<SubspaceMetrics>,
+}
+
+impl fmt::Debug for PeerReputation {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerReputation").finish_non_exhaustive()
+    }
+}
+
+impl PeerReputation {
+    pub(crate) fn new(metrics: Option<SubspaceMetrics>) -> Self {
+        Self {
+            peers: Mutex::new(LruMap::new(ByLength::new(PEER_REPUTATION_CACHE_SIZE))),
+            metrics,
+        }
+    }
+
+    /// Update reputation of a peer
+    pub(crate) fn report(&self, peer_id: PeerId, change: ReputationChange) {
+        self.report_at(peer_id, change, Instant::now());
+    }
+
+    fn report_at(&self, peer_id: PeerId, change: ReputationChange, now: Instant) {
+        if let Some(metrics) = &self.metrics {
+            metrics.inc_peer_reputation_changes(change.as_str());
+            if let ReputationChange::Success { latency } = change {
+                metrics.observe_request_latency(latency);
+            }
+        }
+
+        let mut peers = self.peers.lock();
+        let Some(entry) = peers.get_or_insert(peer_id, || PeerReputationEntry {
+            score: PeerScore::default(),
+            last_update: now,
+        }) else {
+            return;
+        };
+
+        let mut score = entry.decayed_score(now);
+        score.score = (score.score + change.score_change()).clamp(MIN_SCORE, MAX_SCORE);
+        match change {
+            ReputationChange::Success { latency } => {
+                score.successful_requests += 1;
+                score.average_latency = Some(match score.average_latency {
+                    Some(average_latency) => {
+                        average_latency.mul_f64(1.0 - LATENCY_SMOOTHING_FACTOR)
+                            + latency.mul_f64(LATENCY_SMOOTHING_FACTOR)
+                    }
+                    None => latency,
+                });
+            }
+            ReputationChange::Failure => {
+                score.failed_requests += 1;
+            }
+            ReputationChange::Timeout => {
+                score.timed_out_requests += 1;
+            }
+            ReputationChange::InvalidPiece => {
+                score.invalid_pieces += 1;
+            }
+        }
+
+        *entry = PeerReputationEntry {
+            score,
+            last_update: now,
+        };
+    }
+
+    /// Current score of a peer, [`NEUTRAL_SCORE`] for unknown peers
+    pub(crate) fn score(&self, peer_id: &PeerId) -> f64 {
+        self.peers
+            .lock()
+            .peek(peer_id)
+            .map(|entry| entry.decayed_score(Instant::now()).score)
+            .unwrap_or(NEUTRAL_SCORE)
+    }
+
+    /// Current scores of all known peers
+    pub(crate) fn peer_scores(&self) -> HashMap<PeerId, PeerScore> {
+        let now = Instant::now();
+
+        self.peers
+            .lock()
+            .iter()
+            .map(|(peer_id, entry)| (*peer_id, entry.decayed_score(now)))
+            .collect()
+    }
+
+    /// Sort peers from the best to the worst score, order of peers with the same score is
+    /// preserved
+    pub(crate) fn sort_peers(&self, peers: &mut [PeerId]) {
+        let now = Instant::now();
+        let peers_reputation = self.peers.lock();
+        let score = |peer_id: &PeerId| {
+            peers_reputation
+                .peek(peer_id)
+                .map(|entry| entry.decayed_score(now).score)
+                .unwrap_or(NEUTRAL_SCORE)
+        };
+
+        peers.sort_by(|a, b| score(b).total_cmp(&score(a)));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::utils::peer_reputation::{
+    PeerReputation, ReputationChange, MAX_SCORE, MIN_SCORE, NEUTRAL_SCORE, SCORE_HALF_LIFE,
+};
+use libp2p::PeerId;
+use std::time::{Duration, Instant};
+
+#[test]
+fn score_reflects_request_outcomes() {
+    let peer_reputation = PeerReputation::new(None);
+    let fast_peer = PeerId::random();
+    let slow_peer = PeerId::random();
+    let failing_peer = PeerId::random();
+    let timing_out_peer = PeerId::random();
+    let malicious_peer = PeerId::random();
+    let unknown_peer = PeerId::random();
+    let now = Instant::now();
+
+    peer_reputation.report_at(
+        fast_peer,
+        ReputationChange::Success {
+            latency: Duration::from_millis(10),
+        },
+        now,
+    );
+    peer_reputation.report_at(
+        slow_peer,
+        ReputationChange::Success {
+            latency: Duration::from_secs(5),
+        },
+        now,
+    );
+    peer_reputation.report_at(failing_peer, ReputationChange::Failure, now);
+    peer_reputation.report_at(timing_out_peer, ReputationChange::Timeout, now);
+    peer_reputation.report_at(malicious_peer, ReputationChange::InvalidPiece, now);
+
+    let scores = peer_reputation.peer_scores();
+    assert_eq!(scores.len(), 5);
+    assert!(scores[&fast_peer].score > scores[&slow_peer].score);
+    assert!(scores[&slow_peer].score > NEUTRAL_SCORE);
+    assert!(scores[&failing_peer].score < NEUTRAL_SCORE);
+    assert!(scores[&timing_out_peer].score < scores[&failing_peer].score);
+    assert!(scores[&malicious_peer].score < scores[&timing_out_peer].score);
+    assert_eq!(peer_reputation.score(&unknown_peer), NEUTRAL_SCORE);
+
+    let mut peers = vec![
+        unknown_peer,
+        malicious_peer,
+        slow_peer,
+        timing_out_peer,
+        fast_peer,
+        failing_peer,
+    ];
+    peer_reputation.sort_peers(&mut peers);
+    assert_eq!(
+        peers,
+        vec![
+            fast_peer,
+            slow_peer,
+            unknown_peer,
+            failing_peer,
+            timing_out_peer,
+            malicious_peer
+        ]
+    );
+}
+
+#[test]
+fn score_statistics_and_bounds() {
+    let peer_reputation = PeerReputation::new(None);
+    let peer_id = PeerId::random();
+    let now = Instant::now();
+
+    for _ in 0..3 {
+        peer_reputation.report_at(
+            peer_id,
+            ReputationChange::Success {
+                latency: Duration::from_millis(100),
+            },
+            now,
+        );
+    }
+    peer_reputation.report_at(peer_id, ReputationChange::Timeout, now);
+
+    let score = peer_reputation.peer_scores()[&peer_id];
+    assert_eq!(score.successful_requests, 3);
+    assert_eq!(score.timed_out_requests, 1);
+    assert_eq!(score.success_rate(), Some(0.75));
+    assert_eq!(score.average_latency, Some(Duration::from_millis(100)));
+
+    for _ in 0..10 {
+        peer_reputation.report_at(peer_id, ReputationChange::InvalidPiece, now);
+    }
+    let score = peer_reputation.peer_scores()[&peer_id];
+    assert_eq!(score.invalid_pieces, 10);
+    assert!(score.score >= MIN_SCORE);
+    assert!(score.score <= MAX_SCORE);
+}
+
+#[test]
+fn score_decays_over_time() {
+    let peer_reputation = PeerReputation::new(None);
+    let peer_id = PeerId::random();
+    let now = Instant::now();
+
+    peer_reputation.report_at(peer_id, ReputationChange::InvalidPiece, now);
+    let entry = *peer_reputation.peers.lock().peek(&peer_id).unwrap();
+
+    let initial_score = entry.decayed_score(now).score;
+    let half_life_score = entry.decayed_score(now + SCORE_HALF_LIFE).score;
+    let much_later_score = entry.decayed_score(now + SCORE_HALF_LIFE * 100).score;
+
+    assert!(initial_score < NEUTRAL_SCORE);
+    assert!((half_life_score - initial_score / 2.0).abs() < 0.001);
+    assert!((much_later_score - NEUTRAL_SCORE).abs() < 0.001);
+    // Counters are not affected by decay
+    assert_eq!(entry.decayed_score(now + SCORE_HALF_LIFE).invalid_pieces, 1);
+}
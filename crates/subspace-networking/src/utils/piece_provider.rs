@@ -1,16 +1,35 @@
 //! Provides methods to retrieve pieces from DSN.
 
+#[cfg(test)]
+mod tests;
+
 use crate::utils::multihash::ToMultihash;
-use crate::{Node, PieceByIndexRequest, PieceByIndexResponse};
+use crate::utils::peer_reputation::LOW_SCORE_THRESHOLD;
+use crate::{
+    GetPiecesByIndexError, Node, PieceByIndexRequest, PieceByIndexResponse, PieceByIndexResult,
+    PiecesByIndexRequest, MAX_PIECES_PER_REQUEST,
+};
 use async_trait::async_trait;
-use futures::StreamExt;
+use futures::stream::{BoxStream, FuturesUnordered, SelectAll};
+use futures::{stream, FutureExt, Stream, StreamExt};
 use libp2p::kad::RecordKey;
 use libp2p::PeerId;
-use std::collections::HashSet;
-use std::fmt;
+use parking_lot::Mutex;
+use schnellru::{ByLength, LruMap};
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::sync::Arc;
+use std::{fmt, io};
 use subspace_core_primitives::{Piece, PieceIndex};
+use subspace_data_retrieval::object_fetcher::BoxError;
+use subspace_data_retrieval::piece_fetcher::{PieceGetter, PieceStream};
 use tracing::{debug, trace, warn};
 
+/// How many peers without support for batched piece requests to remember
+const PEERS_WITHOUT_BATCH_SUPPORT_CACHE_SIZE: u32 = 1_000;
+/// How many providers lookups to run concurrently when getting multiple pieces from cache
+const PROVIDERS_LOOKUP_CONCURRENCY: usize = 32;
+
 /// Validates piece against using its commitment.
 #[async_trait]
 pub trait PieceValidator: Sync + Send {
@@ -37,6 +56,8 @@ impl PieceValidator for NoPieceValidator {
 pub struct PieceProvider<PV> {
     node: Node,
     piece_validator: Option<PV>,
+    /// Peers that responded to [`PiecesByIndexRequest`] with unsupported protocol error
+    peers_without_batch_support: Mutex<LruMap<PeerId, ()>>,
 }
 
 impl<PV> fmt::Debug for PieceProvider<PV> {
@@ -46,6 +67,28 @@ impl<PV> fmt::Debug for PieceProvider<PV> {
     }
 }
 
+/// Gets pieces from farmers' piece cache on DSN (L2), see
+/// [`PieceProvider::get_piece_from_archival_storage()`] for getting pieces from archival storage.
+#[async_trait]
+impl<PV> PieceGetter for PieceProvider<PV>
+where
+    PV: PieceValidator,
+{
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        Ok(self.get_piece_from_cache(piece_index).await)
+    }
+
+    fn get_pieces<'a>(&'a self, piece_indexes: Vec<PieceIndex>) -> PieceStream<'a>
+    where
+        Self: Sync,
+    {
+        Box::pin(
+            self.get_pieces_from_cache(piece_indexes)
+                .map(|(piece_index, maybe_piece)| (piece_index, Ok(maybe_piece))),
+        )
+    }
+}
+
 impl<PV> PieceProvider<PV>
 where
     PV: PieceValidator,
@@ -55,6 +98,9 @@ where
         Self {
             node,
             piece_validator,
+            peers_without_batch_support: Mutex::new(LruMap::new(ByLength::new(
+                PEERS_WITHOUT_BATCH_SUPPORT_CACHE_SIZE,
+            ))),
         }
     }
 
@@ -88,13 +134,7 @@ where
                                 "Piece request succeeded"
                             );
 
-                            if let Some(validator) = &self.piece_validator {
-                                return validator
-                                    .validate_piece(provider_id, piece_index, piece)
-                                    .await;
-                            } else {
-                                return Some(piece);
-                            }
+                            return self.validate_piece(provider_id, piece_index, piece).await;
                         }
                         Ok(PieceByIndexResponse { piece: None }) => {
                             debug!(
@@ -139,11 +179,7 @@ where
             Ok(PieceByIndexResponse { piece: Some(piece) }) => {
                 trace!(%peer_id, %piece_index, "Piece request succeeded");
 
-                if let Some(validator) = &self.piece_validator {
-                    return validator.validate_piece(peer_id, piece_index, piece).await;
-                } else {
-                    return Some(piece);
-                }
+                return self.validate_piece(peer_id, piece_index, piece).await;
             }
             Ok(PieceByIndexResponse { piece: None }) => {
                 debug!(%peer_id, %piece_index, "Piece request returned empty piece");
@@ -156,6 +192,80 @@ where
         None
     }
 
+    /// Returns pieces by their indexes from farmers' piece cache (L2).
+    ///
+    /// Providers of all pieces are looked up first, then pieces are requested in batches from
+    /// providers that have the most of them, see [`Self::get_pieces_from_peer()`]. Pieces are
+    /// yielded in the order they are retrieved, pieces that no provider returned are yielded as
+    /// `None`. Duplicate piece indexes are yielded only once.
+    pub fn get_pieces_from_cache(
+        &self,
+        piece_indexes: Vec<PieceIndex>,
+    ) -> impl Stream<Item = (PieceIndex, Option<Piece>)> + Send + '_ {
+        get_pieces_from_cache(self, piece_indexes)
+    }
+
+    /// Same as [`Self::get_pieces_from_cache()`], but returned stream owns piece provider instead
+    /// of borrowing it
+    pub fn get_pieces_from_cache_owned(
+        self: Arc<Self>,
+        piece_indexes: Vec<PieceIndex>,
+    ) -> impl Stream<Item = (PieceIndex, Option<Piece>)> + Send + 'static
+    where
+        PV: 'static,
+    {
+        get_pieces_from_cache(self, piece_indexes)
+    }
+
+    /// Returns providers of the piece in farmers' piece cache (L2)
+    async fn get_piece_providers(&self, piece_index: PieceIndex) -> Vec<PeerId> {
+        let key = RecordKey::from(piece_index.to_multihash());
+
+        match self.node.get_providers(key.clone()).await {
+            Ok(get_providers_stream) => get_providers_stream.collect().await,
+            Err(err) => {
+                warn!(%piece_index, ?key, ?err, "get_providers returned an error");
+
+                Vec::new()
+            }
+        }
+    }
+
+    /// Get pieces from a particular peer.
+    ///
+    /// Pieces are requested in batches of up to [`MAX_PIECES_PER_REQUEST`] pieces and yielded as
+    /// soon as the peer sends them, peers that don't support batched requests are queried one
+    /// piece at a time. Every piece index is yielded once, pieces that peer didn't return are
+    /// yielded as `None`. With `cached_only` peer is asked to not read pieces from plots, which
+    /// only has effect for batched requests.
+    pub fn get_pieces_from_peer(
+        &self,
+        peer_id: PeerId,
+        piece_indexes: Vec<PieceIndex>,
+        cached_only: bool,
+    ) -> impl Stream<Item = (PieceIndex, Option<Piece>)> + Send + '_ {
+        get_pieces_from_peer(self, peer_id, piece_indexes, cached_only)
+    }
+
+    /// Validate piece received from a peer, peers that return invalid pieces lose reputation
+    async fn validate_piece(
+        &self,
+        peer_id: PeerId,
+        piece_index: PieceIndex,
+        piece: Piece,
+    ) -> Option<Piece> {
+        let Some(validator) = &self.piece_validator else {
+            return Some(piece);
+        };
+
+        let maybe_piece = validator.validate_piece(peer_id, piece_index, piece).await;
+        if maybe_piece.is_none() {
+            self.node.report_invalid_piece(peer_id);
+        }
+
+        maybe_piece
+    }
+
     /// Get piece from archival storage (L1). The algorithm tries to get a piece from currently
     /// connected peers and falls back to random walking.
     pub async fn get_piece_from_archival_storage(
@@ -176,7 +286,12 @@ where
                 }
             };
 
-            HashSet::<PeerId>::from_iter(connected_peers)
+            let mut connected_peers = HashSet::<PeerId>::from_iter(connected_peers)
+                .into_iter()
+                .collect::<Vec<_>>();
+            self.node.sort_peers_by_reputation(&mut connected_peers);
+
+            connected_peers
         };
 
         if connected_peers.is_empty() {
@@ -263,11 +378,7 @@ where
                         Ok(PieceByIndexResponse { piece: Some(piece) }) => {
                             trace!(%peer_id, %piece_index, ?key, %round,  "Piece request succeeded.");
 
-                            if let Some(validator) = &self.piece_validator {
-                                return validator.validate_piece(peer_id, piece_index, piece).await;
-                            } else {
-                                return Some(piece);
-                            }
+                            return self.validate_piece(peer_id, piece_index, piece).await;
                         }
                         Ok(PieceByIndexResponse { piece: None }) => {
                             debug!(%peer_id, %piece_index, ?key, %round, "Piece request returned empty piece.");
@@ -286,3 +397,413 @@ where
         None
     }
 }
+
+/// Piece retrieved as part of the batch requested from a provider, `None` once the batch is
+/// finished
+type BatchItem = (PeerId, Option<(PieceIndex, Option<Piece>)>);
+
+struct GetPiecesFromCacheState<'a> {
+    /// Remaining pieces along with providers that were not tried yet
+    piece_providers: HashMap<PieceIndex, Vec<PeerId>>,
+    /// Pieces that are currently requested as part of one of the batches in progress
+    pieces_in_progress: HashSet<PieceIndex>,
+    /// Providers that currently have a batch in progress, only one batch per provider is requested
+    /// at a time
+    providers_in_progress: HashSet<PeerId>,
+    batches_in_progress: SelectAll<BoxStream<'a, BatchItem>>,
+}
+
+fn get_pieces_from_cache<'a, PV, P>(
+    piece_provider: P,
+    piece_indexes: Vec<PieceIndex>,
+) -> impl Stream<Item = (PieceIndex, Option<Piece>)> + Send + 'a
+where
+    PV: PieceValidator + 'a,
+    P: Deref<Target = PieceProvider<PV>> + Clone + Send + Sync + 'a,
+{
+    let providers_lookup = {
+        let piece_provider = piece_provider.clone();
+
+        async move {
+            stream::iter(piece_indexes)
+                .map(|piece_index| {
+                    let piece_provider = piece_provider.clone();
+
+                    async move {
+                        let providers = piece_provider.get_piece_providers(piece_index).await;
+                        (piece_index, providers)
+                    }
+                })
+                .buffer_unordered(PROVIDERS_LOOKUP_CONCURRENCY)
+                .collect::<HashMap<_, _>>()
+                .await
+        }
+    };
+
+    providers_lookup
+        .map(move |piece_providers| {
+            let state = GetPiecesFromCacheState {
+                piece_providers,
+                pieces_in_progress: HashSet::new(),
+                providers_in_progress: HashSet::new(),
+                batches_in_progress: SelectAll::new(),
+            };
+
+            stream::unfold(state, move |mut state| {
+                let piece_provider = piece_provider.clone();
+
+                async move {
+                    let results = get_next_pieces_from_cache(piece_provider, &mut state).await?;
+                    Some((stream::iter(results), state))
+                }
+            })
+            .flatten()
+        })
+        .flatten_stream()
+}
+
+/// Waits for the next piece to be retrieved from any of the batches in progress, starting new
+/// batches as necessary.
+///
+/// Pieces that are not in progress are reassigned to idle providers every time any piece is
+/// retrieved or any batch finishes, such that a single slow provider doesn't delay retrieval of
+/// pieces it doesn't have.
+///
+/// Returns `None` once there are no more pieces left.
+async fn get_next_pieces_from_cache<'a, PV, P>(
+    piece_provider: P,
+    state: &mut GetPiecesFromCacheState<'a>,
+) -> Option<Vec<(PieceIndex, Option<Piece>)>>
+where
+    PV: PieceValidator + 'a,
+    P: Deref<Target = PieceProvider<PV>> + Clone + Send + Sync + 'a,
+{
+    let mut results = Vec::new();
+    state.piece_providers.retain(|piece_index, providers| {
+        if providers.is_empty() && !state.pieces_in_progress.contains(piece_index) {
+            debug!(%piece_index, "No more providers to get piece from");
+            results.push((*piece_index, None));
+            false
+        } else {
+            true
+        }
+    });
+
+    let pending_piece_providers = state
+        .piece_providers
+        .iter()
+        .filter(|(piece_index, _providers)| !state.pieces_in_progress.contains(*piece_index))
+        .filter_map(|(&piece_index, providers)| {
+            let providers = providers
+                .iter()
+                .filter(|provider_id| !state.providers_in_progress.contains(*provider_id))
+                .copied()
+                .collect::<Vec<_>>();
+
+            (!providers.is_empty()).then_some((piece_index, providers))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let batches = assign_batches(&pending_piece_providers, |peer_id| {
+        piece_provider.node.peer_score(peer_id)
+    });
+    for (provider_id, piece_indexes) in batches {
+        // Only one batch per provider is started at a time, the rest of the pieces will be
+        // assigned once any batch in progress finishes
+        if !state.providers_in_progress.insert(provider_id) {
+            continue;
+        }
+        trace!(
+            %provider_id,
+            pieces_count = %piece_indexes.len(),
+            "Requesting pieces from provider"
+        );
+
+        state
+            .pieces_in_progress
+            .extend(piece_indexes.iter().copied());
+        state.batches_in_progress.push(
+            get_pieces_from_peer(piece_provider.clone(), provider_id, piece_indexes, true)
+                .map(move |result| (provider_id, Some(result)))
+                .chain(stream::once(async move { (provider_id, None) }))
+                .boxed(),
+        );
+    }
+
+    if !results.is_empty() {
+        return Some(results);
+    }
+
+    let (provider_id, maybe_result) = state.batches_in_progress.next().await?;
+    let Some((piece_index, maybe_piece)) = maybe_result else {
+        // Batch is finished, provider can be assigned more pieces
+        state.providers_in_progress.remove(&provider_id);
+        return Some(Vec::new());
+    };
+
+    state.pieces_in_progress.remove(&piece_index);
+    if maybe_piece.is_some() {
+        state.piece_providers.remove(&piece_index);
+        return Some(vec![(piece_index, maybe_piece)]);
+    }
+
+    if let Some(providers) = state.piece_providers.get_mut(&piece_index) {
+        providers.retain(|&peer_id| peer_id != provider_id);
+    }
+
+    Some(Vec::new())
+}
+
+/// Get pieces from a particular peer, see [`PieceProvider::get_pieces_from_peer()`] for details
+fn get_pieces_from_peer<'a, PV, P>(
+    piece_provider: P,
+    peer_id: PeerId,
+    piece_indexes: Vec<PieceIndex>,
+    cached_only: bool,
+) -> impl Stream<Item = (PieceIndex, Option<Piece>)> + Send + 'a
+where
+    PV: PieceValidator + 'a,
+    P: Deref<Target = PieceProvider<PV>> + Clone + Send + Sync + 'a,
+{
+    let batches = piece_indexes
+        .chunks(MAX_PIECES_PER_REQUEST)
+        .map(<[PieceIndex]>::to_vec)
+        .collect::<Vec<_>>();
+
+    stream::iter(batches).flat_map(move |piece_indexes| {
+        get_pieces_batch_from_peer(piece_provider.clone(), peer_id, piece_indexes, cached_only)
+    })
+}
+
+/// Request up to [`MAX_PIECES_PER_REQUEST`] pieces from a peer in a single request, falling back
+/// to one piece per request if peer doesn't support batched requests
+fn get_pieces_batch_from_peer<'a, PV, P>(
+    piece_provider: P,
+    peer_id: PeerId,
+    piece_indexes: Vec<PieceIndex>,
+    cached_only: bool,
+) -> BoxStream<'a, (PieceIndex, Option<Piece>)>
+where
+    PV: PieceValidator + 'a,
+    P: Deref<Target = PieceProvider<PV>> + Clone + Send + Sync + 'a,
+{
+    async move {
+        let batch_supported = piece_provider
+            .peers_without_batch_support
+            .lock()
+            .peek(&peer_id)
+            .is_none();
+
+        if batch_supported {
+            let request = PiecesByIndexRequest {
+                piece_indexes: piece_indexes.clone(),
+                cached_only,
+            };
+
+            match piece_provider
+                .node
+                .get_pieces_by_index(peer_id, request)
+                .await
+            {
+                Ok(results) => {
+                    return validate_batch_results(piece_provider, peer_id, piece_indexes, results)
+                        .boxed();
+                }
+                Err(GetPiecesByIndexError::UnsupportedProtocol) => {
+                    debug!(%peer_id, "Peer doesn't support batched piece requests");
+
+                    piece_provider
+                        .peers_without_batch_support
+                        .lock()
+                        .insert(peer_id, ());
+                }
+                Err(error) => {
+                    debug!(%peer_id, ?error, "Pieces request failed");
+
+                    return stream::iter(
+                        piece_indexes
+                            .into_iter()
+                            .map(|piece_index| (piece_index, None)),
+                    )
+                    .boxed();
+                }
+            }
+        }
+
+        get_pieces_one_by_one(piece_provider, peer_id, piece_indexes).boxed()
+    }
+    .flatten_stream()
+    .boxed()
+}
+
+/// Validate results of batched request as they are received, every requested piece index is
+/// yielded once, pieces that peer didn't return are yielded as `None`
+fn validate_batch_results<'a, PV, P, R>(
+    piece_provider: P,
+    peer_id: PeerId,
+    piece_indexes: Vec<PieceIndex>,
+    results: R,
+) -> impl Stream<Item = (PieceIndex, Option<Piece>)> + Send + 'a
+where
+    PV: PieceValidator + 'a,
+    P: Deref<Target = PieceProvider<PV>> + Clone + Send + Sync + 'a,
+    R: Stream<Item = io::Result<PieceByIndexResult>> + Send + 'a,
+{
+    let remaining = piece_indexes.into_iter().collect::<HashSet<_>>();
+
+    stream::unfold(
+        (results.boxed(), remaining),
+        move |(mut results, mut remaining)| {
+            let piece_provider = piece_provider.clone();
+
+            async move {
+                if remaining.is_empty() {
+                    return None;
+                }
+
+                let pieces = loop {
+                    match results.next().await {
+                        Some(Ok(result)) => {
+                            let piece_index = result.piece_index();
+                            if !remaining.remove(&piece_index) {
+                                debug!(
+                                    %peer_id,
+                                    %piece_index,
+                                    "Peer returned piece that wasn't requested, ignoring"
+                                );
+                                continue;
+                            }
+
+                            let maybe_piece = match result {
+                                PieceByIndexResult::Found { piece_index, piece } => {
+                                    trace!(%peer_id, %piece_index, "Piece request succeeded");
+
+                                    piece_provider
+                                        .validate_piece(peer_id, piece_index, piece)
+                                        .await
+                                }
+                                PieceByIndexResult::NotFound { piece_index } => {
+                                    debug!(
+                                        %peer_id,
+                                        %piece_index,
+                                        "Piece request returned empty piece"
+                                    );
+
+                                    None
+                                }
+                            };
+
+                            break vec![(piece_index, maybe_piece)];
+                        }
+                        Some(Err(error)) => {
+                            debug!(%peer_id, ?error, "Pieces request failed");
+                        }
+                        None => {
+                            debug!(
+                                %peer_id,
+                                missing = %remaining.len(),
+                                "Peer didn't return some of the requested pieces"
+                            );
+                        }
+                    }
+
+                    break remaining
+                        .drain()
+                        .map(|piece_index| (piece_index, None))
+                        .collect();
+                };
+
+                Some((stream::iter(pieces), (results, remaining)))
+            }
+        },
+    )
+    .flatten()
+}
+
+/// Get pieces from a peer that doesn't support batched requests, one piece per request, pieces are
+/// yielded as soon as they are received
+fn get_pieces_one_by_one<'a, PV, P>(
+    piece_provider: P,
+    peer_id: PeerId,
+    piece_indexes: Vec<PieceIndex>,
+) -> impl Stream<Item = (PieceIndex, Option<Piece>)> + Send + 'a
+where
+    PV: PieceValidator + 'a,
+    P: Deref<Target = PieceProvider<PV>> + Clone + Send + Sync + 'a,
+{
+    piece_indexes
+        .into_iter()
+        .map(|piece_index| {
+            let piece_provider = piece_provider.clone();
+
+            async move {
+                let maybe_piece = piece_provider
+                    .get_piece_from_peer(peer_id, piece_index)
+                    .await;
+                (piece_index, maybe_piece)
+            }
+        })
+        .collect::<FuturesUnordered<_>>()
+}
+
+/// Greedily assign pieces to providers, such that every piece is requested from exactly one of its
+/// providers and providers that have more of the pieces are preferred.
+///
+/// Providers with score below [`LOW_SCORE_THRESHOLD`] are only used for pieces that no other
+/// provider has, among providers with the same number of pieces higher score is preferred.
+///
+/// Every returned batch has at most [`MAX_PIECES_PER_REQUEST`] pieces.
+fn assign_batches<F>(
+    piece_providers: &HashMap<PieceIndex, Vec<PeerId>>,
+    peer_score: F,
+) -> Vec<(PeerId, Vec<PieceIndex>)>
+where
+    F: Fn(&PeerId) -> f64,
+{
+    let mut provider_pieces = HashMap::<PeerId, Vec<PieceIndex>>::new();
+    for (&piece_index, providers) in piece_providers {
+        for &provider_id in providers {
+            provider_pieces
+                .entry(provider_id)
+                .or_default()
+                .push(piece_index);
+        }
+    }
+
+    let provider_scores = provider_pieces
+        .keys()
+        .map(|provider_id| (*provider_id, peer_score(provider_id)))
+        .collect::<HashMap<_, _>>();
+
+    let mut assigned_pieces = HashSet::<PieceIndex>::new();
+    let mut batches = Vec::new();
+
+    loop {
+        for pieces in provider_pieces.values_mut() {
+            pieces.retain(|piece_index| !assigned_pieces.contains(piece_index));
+        }
+        provider_pieces.retain(|_provider_id, pieces| !pieces.is_empty());
+
+        let Some((&provider_id, pieces)) =
+            provider_pieces.iter_mut().max_by(|(a_id, a), (b_id, b)| {
+                let a_score = provider_scores[*a_id];
+                let b_score = provider_scores[*b_id];
+
+                (a_score >= LOW_SCORE_THRESHOLD)
+                    .cmp(&(b_score >= LOW_SCORE_THRESHOLD))
+                    .then(a.len().cmp(&b.len()))
+                    .then(a_score.total_cmp(&b_score))
+            })
+        else {
+            break;
+        };
+
+        let batch = pieces
+            .drain(..pieces.len().min(MAX_PIECES_PER_REQUEST))
+            .collect::<Vec<_>>();
+        assigned_pieces.extend(batch.iter().copied());
+        batches.push((provider_id, batch));
+    }
+
+    batches
+}
@@ -0,0 +1,95 @@
+use crate::utils::peer_reputation::{LOW_SCORE_THRESHOLD, NEUTRAL_SCORE};
+use crate::utils::piece_provider::assign_batches;
+use crate::MAX_PIECES_PER_REQUEST;
+use libp2p::PeerId;
+use std::collections::{HashMap, HashSet};
+use subspace_core_primitives::PieceIndex;
+
+#[test]
+fn batches_prefer_providers_with_more_pieces() {
+    let big_provider = PeerId::random();
+    let small_provider = PeerId::random();
+
+    let piece_providers = (0..10)
+        .map(|index| {
+            let mut providers = vec![big_provider];
+            if index < 3 {
+                providers.push(small_provider);
+            }
+            (PieceIndex::from(index), providers)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let batches = assign_batches(&piece_providers, |_| NEUTRAL_SCORE);
+
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].0, big_provider);
+    assert_eq!(
+        batches[0].1.iter().copied().collect::<HashSet<_>>(),
+        piece_providers.keys().copied().collect::<HashSet<_>>()
+    );
+}
+
+#[test]
+fn batches_cover_every_piece_once() {
+    let shared_provider = PeerId::random();
+    let exclusive_provider = PeerId::random();
+    let pieces_count = MAX_PIECES_PER_REQUEST as u64 * 2 + 5;
+
+    let mut piece_providers = (0..pieces_count)
+        .map(|index| (PieceIndex::from(index), vec![shared_provider]))
+        .collect::<HashMap<_, _>>();
+    piece_providers.insert(PieceIndex::from(pieces_count), vec![exclusive_provider]);
+
+    let batches = assign_batches(&piece_providers, |_| NEUTRAL_SCORE);
+
+    let mut assigned_pieces = HashSet::new();
+    for (provider_id, piece_indexes) in &batches {
+        assert!(piece_indexes.len() <= MAX_PIECES_PER_REQUEST);
+        for piece_index in piece_indexes {
+            assert!(piece_providers[piece_index].contains(provider_id));
+            assert!(assigned_pieces.insert(*piece_index));
+        }
+    }
+    assert_eq!(assigned_pieces.len(), piece_providers.len());
+    // Three batches for shared provider and one for exclusive
+    assert_eq!(batches.len(), 4);
+
+    assert!(assign_batches(&HashMap::new(), |_| NEUTRAL_SCORE).is_empty());
+}
+
+#[test]
+fn batches_avoid_providers_with_low_score() {
+    let bad_provider = PeerId::random();
+    let good_provider = PeerId::random();
+    let fast_provider = PeerId::random();
+
+    let piece_providers = (0..10)
+        .map(|index| {
+            let mut providers = vec![bad_provider];
+            if index < 5 {
+                providers.push(good_provider);
+            } else {
+                providers.push(fast_provider);
+            }
+            (PieceIndex::from(index), providers)
+        })
+        .collect::<HashMap<_, _>>();
+    let peer_score = |peer_id: &PeerId| {
+        if *peer_id == bad_provider {
+            LOW_SCORE_THRESHOLD - 1.0
+        } else if *peer_id == fast_provider {
+            NEUTRAL_SCORE + 1.0
+        } else {
+            NEUTRAL_SCORE
+        }
+    };
+
+    let batches = assign_batches(&piece_providers, peer_score);
+
+    // Bad provider has all pieces, but is not used since every piece has another provider, with
+    // the same number of pieces provider with higher score goes first
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[0].0, fast_provider);
+    assert_eq!(batches[1].0, good_provider);
+}
@@ -15,7 +15,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::chain_spec;
-use crate::commands::{RunOptions, WipeOptions};
+use crate::commands::{ImportHistoryCmd, RunOptions, WipeOptions};
 use clap::Parser;
 use sc_chain_spec::GenericChainSpec;
 use sc_cli::SubstrateCli;
@@ -44,6 +44,9 @@ pub enum Cli {
     /// Import blocks.
     ImportBlocks(sc_cli::ImportBlocksCmd),
 
+    /// Import blocks from archived history files, verifying them against segment headers.
+    ImportHistory(ImportHistoryCmd),
+
     /// Remove all node's data
     Wipe(WipeOptions),
 
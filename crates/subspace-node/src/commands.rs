@@ -1,4 +1,5 @@
 mod domain_key;
+mod import_history;
 mod run;
 mod shared;
 mod wipe;
@@ -6,5 +7,6 @@ mod wipe;
 pub use domain_key::{
     create_domain_key, insert_domain_key, CreateDomainKeyOptions, InsertDomainKeyOptions,
 };
+pub use import_history::ImportHistoryCmd;
 pub use run::{run, RunOptions};
 pub use wipe::{wipe, WipeOptions};
@@ -0,0 +1,79 @@
+use sc_cli::{CliConfiguration, DatabaseParams, ImportParams, SharedParams};
+use sc_client_api::{AuxStore, BlockBackend};
+use sc_consensus_subspace::archiver::SegmentHeadersStore;
+use sc_consensus_subspace::SubspaceLink;
+use sc_service::ImportQueue;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::traits::Block as BlockT;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+
+/// Import blocks from archived history files, for instance created with
+/// `subspace-farmer export-history`
+#[derive(Debug, clap::Parser)]
+pub struct ImportHistoryCmd {
+    /// Directory with history files
+    #[arg(long)]
+    from: PathBuf,
+
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    shared_params: SharedParams,
+
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    import_params: ImportParams,
+
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    database_params: DatabaseParams,
+}
+
+impl ImportHistoryCmd {
+    /// Run the import-history command
+    pub async fn run<Block, AS, Client, IQ>(
+        &self,
+        client: Arc<Client>,
+        import_queue: IQ,
+        segment_headers_store: SegmentHeadersStore<AS>,
+        subspace_link: SubspaceLink<Block>,
+    ) -> Result<(), sc_service::Error>
+    where
+        Block: BlockT,
+        AS: AuxStore + Send + Sync + 'static,
+        Client: HeaderBackend<Block> + BlockBackend<Block> + Send + Sync + 'static,
+        IQ: ImportQueue<Block>,
+    {
+        info!(directory = %self.from.display(), "Importing history");
+
+        let mut import_queue_service = import_queue.service();
+        let imported_blocks = subspace_service::sync_from_dsn::import_history(
+            &self.from,
+            &segment_headers_store,
+            client.as_ref(),
+            import_queue_service.as_mut(),
+            subspace_link.kzg(),
+            subspace_link.erasure_coding(),
+        )
+        .await?;
+
+        info!(%imported_blocks, "Imported blocks from history files");
+
+        Ok(())
+    }
+}
+
+impl CliConfiguration for ImportHistoryCmd {
+    fn shared_params(&self) -> &SharedParams {
+        &self.shared_params
+    }
+
+    fn import_params(&self) -> Option<&ImportParams> {
+        Some(&self.import_params)
+    }
+
+    fn database_params(&self) -> Option<&DatabaseParams> {
+        Some(&self.database_params)
+    }
+}
@@ -410,6 +410,14 @@ pub(super) struct ConsensusChainOptions {
     /// Examples: `snap`, `full`
     #[arg(long, default_value = None)]
     sync: Option<ChainSyncMode>,
+
+    /// Store object mappings of archived segments, such that objects can be looked up by their
+    /// hash with `subspace_getObjectMapping` RPC method.
+    ///
+    /// Only segments archived by this node after this option was enabled are indexed, segments
+    /// downloaded from DSN during sync are not.
+    #[arg(long)]
+    index_object_mappings: bool,
 }
 
 pub(super) struct PrometheusConfiguration {
@@ -453,6 +461,7 @@ pub(super) fn create_consensus_chain_configuration(
         storage_monitor,
         mut timekeeper_options,
         mut sync,
+        index_object_mappings,
     } = consensus_node_options;
 
     let transaction_pool;
@@ -685,6 +694,7 @@ pub(super) fn create_consensus_chain_configuration(
             sync,
             is_timekeeper: timekeeper_options.timekeeper,
             timekeeper_cpu_cores: timekeeper_options.timekeeper_cpu_cores,
+            index_object_mappings,
         },
         dev,
         pot_external_entropy,
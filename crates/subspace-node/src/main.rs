@@ -217,6 +217,33 @@ fn main() -> Result<(), Error> {
                 ))
             })?;
         }
+        Cli::ImportHistory(cmd) => {
+            let runner = SubspaceCliPlaceholder.create_runner(&cmd)?;
+            set_default_ss58_version(runner.config().chain_spec.as_ref());
+            runner.async_run(|config| {
+                let PartialComponents {
+                    client,
+                    import_queue,
+                    task_manager,
+                    other,
+                    ..
+                } = subspace_service::new_partial::<PosTable, RuntimeApi>(
+                    &config,
+                    false,
+                    &derive_pot_external_entropy(&config, None)?,
+                )?;
+                Ok((
+                    cmd.run(
+                        client,
+                        import_queue,
+                        other.segment_headers_store,
+                        other.subspace_link,
+                    )
+                    .map_err(Error::SubstrateService),
+                    task_manager,
+                ))
+            })?;
+        }
         Cli::Wipe(wipe_options) => {
             commands::wipe(wipe_options).map_err(|error| Error::Other(error.to_string()))?;
         }
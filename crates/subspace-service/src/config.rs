@@ -281,6 +281,9 @@ pub struct SubspaceConfiguration {
     pub timekeeper_cpu_cores: HashSet<usize>,
     /// Defines blockchain sync mode
     pub sync: ChainSyncMode,
+    /// Store object mappings of segments archived by this node, such that objects can be looked up
+    /// by their hash over RPC
+    pub index_object_mappings: bool,
 }
 
 /// Syncing mode.
@@ -41,6 +41,7 @@ use crate::domains::request_handler::LastDomainBlockERRequestHandler;
 use crate::dsn::{create_dsn_instance, DsnConfigurationError};
 use crate::metrics::NodeMetrics;
 use crate::mmr::request_handler::MmrRequestHandler;
+use crate::sync_from_dsn::piece_fetcher::dsn_sync_piece_fetcher;
 use crate::sync_from_dsn::piece_validator::SegmentCommitmentPieceValidator;
 use crate::sync_from_dsn::snap_sync::snap_sync;
 use crate::transaction_pool::FullPool;
@@ -66,7 +67,7 @@ use sc_consensus::{
 };
 use sc_consensus_slots::SlotProportion;
 use sc_consensus_subspace::archiver::{
-    create_subspace_archiver, ArchivedSegmentNotification, SegmentHeadersStore,
+    create_subspace_archiver, ArchivedSegmentNotification, ObjectMappingsStore, SegmentHeadersStore,
 };
 use sc_consensus_subspace::block_import::{BlockImportingNotification, SubspaceBlockImport};
 use sc_consensus_subspace::notification::SubspaceNotificationStream;
@@ -970,9 +971,14 @@ where
         sync_service.clone(),
     );
 
+    let object_mappings_store = config
+        .index_object_mappings
+        .then(|| ObjectMappingsStore::new(client.clone()));
+
     let subspace_archiver = tokio::task::block_in_place(|| {
         create_subspace_archiver(
             segment_headers_store.clone(),
+            object_mappings_store.clone(),
             subspace_link.clone(),
             client.clone(),
             sync_oracle.clone(),
@@ -996,14 +1002,14 @@ where
     network_wrapper.set(network_service.clone());
 
     let dsn_sync_piece_getter = config.dsn_piece_getter.unwrap_or_else(|| {
-        Arc::new(PieceProvider::new(
+        Arc::new(dsn_sync_piece_fetcher(PieceProvider::new(
             node.clone(),
             Some(SegmentCommitmentPieceValidator::new(
                 node.clone(),
                 subspace_link.kzg().clone(),
                 segment_headers_store.clone(),
             )),
-        ))
+        )))
     });
 
     if !config.base.network.force_synced {
@@ -1245,6 +1251,7 @@ where
                         .clone(),
                     dsn_bootstrap_nodes: dsn_bootstrap_nodes.clone(),
                     segment_headers_store: segment_headers_store.clone(),
+                    object_mappings_store: object_mappings_store.clone(),
                     sync_oracle: sync_oracle.clone(),
                     kzg: subspace_link.kzg().clone(),
                     erasure_coding: subspace_link.erasure_coding().clone(),
@@ -25,7 +25,9 @@ use jsonrpsee::RpcModule;
 use mmr_rpc::{Mmr, MmrApiServer};
 use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApiServer};
 use sc_client_api::{AuxStore, BlockBackend};
-use sc_consensus_subspace::archiver::{ArchivedSegmentNotification, SegmentHeadersStore};
+use sc_consensus_subspace::archiver::{
+    ArchivedSegmentNotification, ObjectMappingsStore, SegmentHeadersStore,
+};
 use sc_consensus_subspace::notification::SubspaceNotificationStream;
 use sc_consensus_subspace::slot_worker::{
     NewSlotNotification, RewardSigningNotification, SubspaceSyncOracle,
@@ -77,6 +79,8 @@ where
     pub dsn_bootstrap_nodes: Vec<Multiaddr>,
     /// Segment header provider.
     pub segment_headers_store: SegmentHeadersStore<AS>,
+    /// Object mappings store, if object mappings are indexed.
+    pub object_mappings_store: Option<ObjectMappingsStore<AS>>,
     /// Subspace sync oracle.
     pub sync_oracle: SubspaceSyncOracle<SO>,
     /// Kzg instance.
@@ -123,6 +127,7 @@ where
         archived_segment_notification_stream,
         dsn_bootstrap_nodes,
         segment_headers_store,
+        object_mappings_store,
         sync_oracle,
         kzg,
         erasure_coding,
@@ -146,6 +151,7 @@ where
             archived_segment_notification_stream,
             dsn_bootstrap_nodes,
             segment_headers_store,
+            object_mappings_store,
             sync_oracle,
             kzg,
             erasure_coding,
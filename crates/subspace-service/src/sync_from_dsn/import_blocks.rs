@@ -16,8 +16,6 @@
 
 use crate::sync_from_dsn::segment_header_downloader::SegmentHeaderDownloader;
 use crate::sync_from_dsn::DsnSyncPieceGetter;
-use futures::stream::FuturesUnordered;
-use futures::StreamExt;
 use sc_client_api::{AuxStore, BlockBackend, HeaderBackend};
 use sc_consensus::import_queue::ImportQueueService;
 use sc_consensus::IncomingBlock;
@@ -30,13 +28,9 @@ use sp_runtime::traits::{Block as BlockT, Header, NumberFor, One};
 use sp_runtime::Saturating;
 use std::time::Duration;
 use subspace_archiving::reconstructor::Reconstructor;
-use subspace_core_primitives::{
-    ArchivedHistorySegment, BlockNumber, Piece, RecordedHistorySegment, SegmentIndex,
-};
+use subspace_core_primitives::{BlockNumber, SegmentIndex};
+use subspace_data_retrieval::piece_fetcher::download_segment_pieces;
 use subspace_erasure_coding::ErasureCoding;
-use subspace_networking::utils::multihash::ToMultihash;
-use tokio::sync::Semaphore;
-use tracing::warn;
 
 /// How many blocks to queue before pausing and waiting for blocks to be imported, this is
 /// essentially used to ensure we use a bounded amount of RAM during sync process.
@@ -118,7 +112,6 @@ where
 
         let last_archived_block_number = NumberFor::<Block>::from(last_archived_block_number);
 
-        let info = client.info();
         // We have already processed this block, it can't change
         if last_archived_block_number <= *last_processed_block_number {
             *last_processed_segment_index = segment_index;
@@ -141,96 +134,125 @@ where
             download_and_reconstruct_blocks(segment_index, piece_getter, &mut reconstructor)
                 .await?;
 
-        let mut blocks_to_import = Vec::with_capacity(QUEUED_BLOCKS_LIMIT as usize);
-
-        let mut best_block_number = info.best_number;
-        for (block_number, block_bytes) in blocks {
-            let block_number = block_number.into();
-            if block_number == 0u32.into() {
-                let signed_block = client
-                    .block(
-                        client
-                            .hash(block_number)?
-                            .expect("Block before best block number must always be found; qed"),
-                    )?
-                    .expect("Block before best block number must always be found; qed");
-
-                if encode_block(signed_block) != block_bytes {
-                    return Err(Error::Other(
-                        "Wrong genesis block, block import failed".to_string(),
-                    ));
-                }
-            }
-
-            // Limit number of queued blocks for import
-            // NOTE: Since best block number might be non-canonical, we might actually have more
-            // than `QUEUED_BLOCKS_LIMIT` elements in the queue, but it should be rare and
-            // insignificant. Feel free to address this in case you have a good strategy, but it
-            // seems like complexity is not worth it.
-            while block_number.saturating_sub(best_block_number) >= QUEUED_BLOCKS_LIMIT.into() {
-                if !blocks_to_import.is_empty() {
-                    // Import queue handles verification and importing it into the client
-                    import_queue_service
-                        .import_blocks(BlockOrigin::NetworkInitialSync, blocks_to_import.clone());
-                    blocks_to_import.clear();
-                }
-                trace!(
-                    %block_number,
-                    %best_block_number,
-                    "Number of importing blocks reached queue limit, waiting before retrying"
-                );
-                tokio::time::sleep(WAIT_FOR_BLOCKS_TO_IMPORT).await;
-                best_block_number = client.info().best_number;
-            }
+        imported_blocks += queue_blocks_for_import::<Block, _, _>(
+            client,
+            import_queue_service,
+            blocks,
+            last_archived_block_number,
+            last_processed_block_number,
+        )
+        .await?;
 
-            let signed_block =
-                decode_block::<Block>(&block_bytes).map_err(|error| error.to_string())?;
+        *last_processed_segment_index = segment_index;
+    }
 
-            *last_processed_block_number = last_archived_block_number;
+    Ok(imported_blocks)
+}
 
-            // No need to import blocks that are already present, if block is not present it might
-            // correspond to a short fork, so we need to import it even if we already have another
-            // block at this height
-            if client.expect_header(signed_block.block.hash()).is_ok() {
-                continue;
+/// Queues reconstructed blocks for import, waiting for previously queued blocks to be imported if
+/// there are too many of them already.
+///
+/// Blocks that are already present are skipped. Returns number of queued blocks.
+pub(super) async fn queue_blocks_for_import<Block, Client, IQS>(
+    client: &Client,
+    import_queue_service: &mut IQS,
+    blocks: Vec<(BlockNumber, Vec<u8>)>,
+    last_archived_block_number: NumberFor<Block>,
+    last_processed_block_number: &mut NumberFor<Block>,
+) -> Result<u64, Error>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block> + BlockBackend<Block> + Send + Sync + 'static,
+    IQS: ImportQueueService<Block> + ?Sized,
+{
+    let mut queued_blocks = 0;
+    let mut blocks_to_import = Vec::with_capacity(QUEUED_BLOCKS_LIMIT as usize);
+
+    let mut best_block_number = client.info().best_number;
+    for (block_number, block_bytes) in blocks {
+        let block_number = block_number.into();
+        if block_number == 0u32.into() {
+            let signed_block = client
+                .block(
+                    client
+                        .hash(block_number)?
+                        .expect("Block before best block number must always be found; qed"),
+                )?
+                .expect("Block before best block number must always be found; qed");
+
+            if encode_block(signed_block) != block_bytes {
+                return Err(Error::Other(
+                    "Wrong genesis block, block import failed".to_string(),
+                ));
             }
+        }
 
-            let SignedBlock {
-                block,
-                justifications,
-            } = signed_block;
-            let (header, extrinsics) = block.deconstruct();
-            let hash = header.hash();
-
-            blocks_to_import.push(IncomingBlock {
-                hash,
-                header: Some(header),
-                body: Some(extrinsics),
-                indexed_body: None,
-                justifications,
-                origin: None,
-                allow_missing_state: false,
-                import_existing: false,
-                state: None,
-                skip_execution: false,
-            });
-
-            imported_blocks += 1;
-
-            if imported_blocks % 1000 == 0 {
-                debug!("Adding block {} from DSN to the import queue", block_number);
+        // Limit number of queued blocks for import
+        // NOTE: Since best block number might be non-canonical, we might actually have more
+        // than `QUEUED_BLOCKS_LIMIT` elements in the queue, but it should be rare and
+        // insignificant. Feel free to address this in case you have a good strategy, but it
+        // seems like complexity is not worth it.
+        while block_number.saturating_sub(best_block_number) >= QUEUED_BLOCKS_LIMIT.into() {
+            if !blocks_to_import.is_empty() {
+                // Import queue handles verification and importing it into the client
+                import_queue_service
+                    .import_blocks(BlockOrigin::NetworkInitialSync, blocks_to_import.clone());
+                blocks_to_import.clear();
             }
+            trace!(
+                %block_number,
+                %best_block_number,
+                "Number of importing blocks reached queue limit, waiting before retrying"
+            );
+            tokio::time::sleep(WAIT_FOR_BLOCKS_TO_IMPORT).await;
+            best_block_number = client.info().best_number;
         }
 
-        if !blocks_to_import.is_empty() {
-            // Import queue handles verification and importing it into the client
-            import_queue_service.import_blocks(BlockOrigin::NetworkInitialSync, blocks_to_import);
+        let signed_block =
+            decode_block::<Block>(&block_bytes).map_err(|error| error.to_string())?;
+
+        *last_processed_block_number = last_archived_block_number;
+
+        // No need to import blocks that are already present, if block is not present it might
+        // correspond to a short fork, so we need to import it even if we already have another
+        // block at this height
+        if client.expect_header(signed_block.block.hash()).is_ok() {
+            continue;
         }
 
-        *last_processed_segment_index = segment_index;
+        let SignedBlock {
+            block,
+            justifications,
+        } = signed_block;
+        let (header, extrinsics) = block.deconstruct();
+        let hash = header.hash();
+
+        blocks_to_import.push(IncomingBlock {
+            hash,
+            header: Some(header),
+            body: Some(extrinsics),
+            indexed_body: None,
+            justifications,
+            origin: None,
+            allow_missing_state: false,
+            import_existing: false,
+            state: None,
+            skip_execution: false,
+        });
+
+        queued_blocks += 1;
+
+        if queued_blocks % 1000 == 0 {
+            debug!("Adding block {} to the import queue", block_number);
+        }
     }
 
-    Ok(imported_blocks)
+    if !blocks_to_import.is_empty() {
+        // Import queue handles verification and importing it into the client
+        import_queue_service.import_blocks(BlockOrigin::NetworkInitialSync, blocks_to_import);
+    }
+
+    Ok(queued_blocks)
 }
 
 pub(super) async fn download_and_reconstruct_blocks<PG>(
@@ -241,85 +263,9 @@ pub(super) async fn download_and_reconstruct_blocks<PG>(
 where
     PG: DsnSyncPieceGetter,
 {
-    debug!(%segment_index, "Retrieving pieces of the segment");
-
-    let semaphore = &Semaphore::new(RecordedHistorySegment::NUM_RAW_RECORDS);
-
-    let mut received_segment_pieces = segment_index
-        .segment_piece_indexes_source_first()
-        .into_iter()
-        .map(|piece_index| {
-            // Source pieces will acquire permit here right away
-            let maybe_permit = semaphore.try_acquire().ok();
-
-            async move {
-                let permit = match maybe_permit {
-                    Some(permit) => permit,
-                    None => {
-                        // Other pieces will acquire permit here instead
-                        match semaphore.acquire().await {
-                            Ok(permit) => permit,
-                            Err(error) => {
-                                warn!(
-                                    %piece_index,
-                                    %error,
-                                    "Semaphore was closed, interrupting piece retrieval"
-                                );
-                                return None;
-                            }
-                        }
-                    }
-                };
-                let maybe_piece = match piece_getter.get_piece(piece_index).await {
-                    Ok(maybe_piece) => maybe_piece,
-                    Err(error) => {
-                        trace!(
-                            %error,
-                            ?piece_index,
-                            "Piece request failed",
-                        );
-                        return None;
-                    }
-                };
-
-                let key =
-                    subspace_networking::libp2p::kad::RecordKey::from(piece_index.to_multihash());
-                trace!(
-                    ?piece_index,
-                    key = hex::encode(&key),
-                    piece_found = maybe_piece.is_some(),
-                    "Piece request succeeded",
-                );
-
-                maybe_piece.map(|received_piece| {
-                    // Piece was received successfully, "remove" this slot from semaphore
-                    permit.forget();
-                    (piece_index, received_piece)
-                })
-            }
-        })
-        .collect::<FuturesUnordered<_>>();
-
-    let mut segment_pieces = vec![None::<Piece>; ArchivedHistorySegment::NUM_PIECES];
-    let mut pieces_received = 0;
-
-    while let Some(maybe_result) = received_segment_pieces.next().await {
-        let Some((piece_index, piece)) = maybe_result else {
-            continue;
-        };
-
-        segment_pieces
-            .get_mut(piece_index.position() as usize)
-            .expect("Piece position is by definition within segment; qed")
-            .replace(piece);
-
-        pieces_received += 1;
-
-        if pieces_received >= RecordedHistorySegment::NUM_RAW_RECORDS {
-            trace!(%segment_index, "Received half of the segment.");
-            break;
-        }
-    }
+    let segment_pieces = download_segment_pieces(segment_index, piece_getter)
+        .await
+        .map_err(|error| error.to_string())?;
 
     let reconstructed_contents = reconstructor
         .add_segment(segment_pieces.as_ref())
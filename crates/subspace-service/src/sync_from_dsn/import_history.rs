@@ -0,0 +1,439 @@
+// Copyright (C) 2024 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+#[cfg(test)]
+mod tests;
+
+use crate::sync_from_dsn::import_blocks::queue_blocks_for_import;
+use sc_client_api::{AuxStore, BlockBackend, HeaderBackend};
+use sc_consensus::import_queue::ImportQueueService;
+use sc_consensus_subspace::archiver::{encode_block, SegmentHeadersStore};
+use sc_service::Error;
+use sc_tracing::tracing::{debug, info, warn};
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use subspace_archiving::archiver::Archiver;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::objects::BlockObjectMapping;
+use subspace_core_primitives::{BlockNumber, SegmentHeader, SegmentIndex};
+use subspace_data_retrieval::exported_history::{
+    history_files, read_history_file, ExportedSegment,
+};
+use subspace_erasure_coding::ErasureCoding;
+
+/// Time to wait between checks whether queued blocks were imported
+const CHECK_IMPORTED_BLOCKS_INTERVAL: Duration = Duration::from_secs(1);
+/// How long to wait for import of the next block before giving up
+const NO_IMPORTED_BLOCKS_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Imports blocks from history files in `directory`, see
+/// [`subspace_data_retrieval::exported_history`] for file format.
+///
+/// Blocks are archived again and resulting segment headers are compared to segment headers known
+/// to the node and to segment headers in history files. Segment headers that node doesn't know
+/// yet are added to `segment_headers_store` only after blocks of the segment were imported (and
+/// verified by the node as a result), but before blocks of the following segments that depend on
+/// them are queued for import.
+///
+/// Blocks of a segment are only imported after the whole segment was verified. The end of the
+/// segment is only known with blocks of the next segment, so blocks of the last history file are
+/// not imported.
+///
+/// Returns number of imported blocks once they are all imported.
+pub async fn import_history<Block, AS, Client, IQS>(
+    directory: &Path,
+    segment_headers_store: &SegmentHeadersStore<AS>,
+    client: &Client,
+    import_queue_service: &mut IQS,
+    kzg: &Kzg,
+    erasure_coding: &ErasureCoding,
+) -> Result<u64, Error>
+where
+    Block: BlockT,
+    AS: AuxStore + Send + Sync + 'static,
+    Client: HeaderBackend<Block> + BlockBackend<Block> + Send + Sync + 'static,
+    IQS: ImportQueueService<Block> + ?Sized,
+{
+    let history_files = history_files(directory).map_err(|error| {
+        Error::Other(format!(
+            "Failed to read history files from {}: {error}",
+            directory.display()
+        ))
+    })?;
+
+    let Some(&(first_segment_index, _)) = history_files.first() else {
+        return Err(Error::Other(format!(
+            "No history files found in {}",
+            directory.display()
+        )));
+    };
+
+    for window in history_files.windows(2) {
+        let [(segment_index, _), (next_segment_index, _)] = window else {
+            unreachable!("Window size is 2; qed");
+        };
+
+        if *next_segment_index != *segment_index + SegmentIndex::ONE {
+            return Err(Error::Other(format!(
+                "History file for segment {} is missing",
+                *segment_index + SegmentIndex::ONE
+            )));
+        }
+    }
+
+    // Blocks up to last archived block were already archived (or buffered by archiver) before the
+    // first segment and must not be added to archiver again
+    let (mut archiver, last_archived_block_number) = if first_segment_index == SegmentIndex::ZERO {
+        (Archiver::new(kzg.clone(), erasure_coding.clone()), None)
+    } else {
+        let previous_segment_index = first_segment_index - SegmentIndex::ONE;
+        let previous_segment_header = segment_headers_store
+            .get_segment_header(previous_segment_index)
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "Segment header {previous_segment_index} is not known, history files \
+                    must not start after segment {}",
+                    segment_headers_store
+                        .max_segment_index()
+                        .map(|segment_index| segment_index + SegmentIndex::ONE)
+                        .unwrap_or(SegmentIndex::ZERO)
+                ))
+            })?;
+
+        let previous_last_archived_block_number =
+            previous_segment_header.last_archived_block().number;
+        let last_archived_block = client
+            .hash(previous_last_archived_block_number.into())?
+            .map(|block_hash| client.block(block_hash))
+            .transpose()?
+            .flatten()
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "Block {previous_last_archived_block_number} archived in segment \
+                    {previous_segment_index} is not imported, history files must start with \
+                    an earlier segment"
+                ))
+            })?;
+
+        let archiver = Archiver::with_initial_state(
+            kzg.clone(),
+            erasure_coding.clone(),
+            previous_segment_header,
+            &encode_block(last_archived_block),
+            BlockObjectMapping::default(),
+        )
+        .map_err(|error| error.to_string())?;
+
+        (archiver, Some(previous_last_archived_block_number))
+    };
+
+    info!(
+        %first_segment_index,
+        last_segment_index = %history_files
+            .last()
+            .map(|(segment_index, _)| *segment_index)
+            .unwrap_or(first_segment_index),
+        "Importing history"
+    );
+
+    let mut imported_blocks = 0;
+    let mut last_processed_block_number = client.info().best_number;
+    let mut last_queued_block_number = None::<NumberFor<Block>>;
+    let mut history_verifier = HistoryVerifier::new(archiver, last_archived_block_number);
+
+    for (segment_index, path) in history_files {
+        let exported_segment =
+            tokio::task::block_in_place(|| read_history_file(&path)).map_err(|error| {
+                Error::Other(format!(
+                    "Failed to read history file {}: {error}",
+                    path.display()
+                ))
+            })?;
+
+        check_exported_segment::<Block, _, _>(
+            segment_headers_store,
+            client,
+            segment_index,
+            &exported_segment,
+        )?;
+
+        debug!(%segment_index, blocks = exported_segment.blocks.len(), "Archiving segment blocks");
+
+        let verified_segments =
+            tokio::task::block_in_place(|| history_verifier.add_segment(exported_segment))?;
+
+        for VerifiedSegment {
+            segment_header,
+            importable_segments,
+        } in verified_segments
+        {
+            debug!(segment_index = %segment_header.segment_index(), "Segment verified");
+
+            for exported_segment in importable_segments {
+                imported_blocks += import_verified_segment::<Block, _, _>(
+                    client,
+                    import_queue_service,
+                    exported_segment,
+                    &mut last_processed_block_number,
+                    &mut last_queued_block_number,
+                )
+                .await?;
+            }
+
+            if let Some(last_queued_block_number) = last_queued_block_number {
+                wait_for_blocks_import::<Block, _>(client, last_queued_block_number).await?;
+            }
+
+            segment_headers_store.add_segment_headers(&[segment_header])?;
+        }
+    }
+
+    if let (Some(first_pending), Some(last_pending)) = (
+        history_verifier.pending_segments.front(),
+        history_verifier.pending_segments.back(),
+    ) {
+        warn!(
+            first_segment_index = %first_pending.segment_header.segment_index(),
+            last_segment_index = %last_pending.segment_header.segment_index(),
+            "Blocks of the last segments can't be verified without the next segment, not importing"
+        );
+    }
+
+    if let Some(last_queued_block_number) = last_queued_block_number {
+        wait_for_blocks_import::<Block, _>(client, last_queued_block_number).await?;
+    }
+
+    info!(%imported_blocks, "History import finished");
+
+    Ok(imported_blocks)
+}
+
+/// Segment whose header was verified by archiving blocks from history files again
+#[derive(Debug)]
+struct VerifiedSegment {
+    /// Segment header produced by archiver, matches segment header in history file
+    segment_header: SegmentHeader,
+    /// Segments from history files whose blocks can be imported now, in order
+    importable_segments: Vec<ExportedSegment>,
+}
+
+/// Archives blocks from history files again and compares resulting segment headers to segment
+/// headers in history files
+struct HistoryVerifier {
+    archiver: Archiver,
+    /// Blocks up to this one were already archived before the first segment
+    last_archived_block_number: Option<BlockNumber>,
+    /// Segments whose blocks are waiting for verification
+    pending_segments: VecDeque<ExportedSegment>,
+}
+
+impl HistoryVerifier {
+    fn new(archiver: Archiver, last_archived_block_number: Option<BlockNumber>) -> Self {
+        Self {
+            archiver,
+            last_archived_block_number,
+            pending_segments: VecDeque::new(),
+        }
+    }
+
+    /// Archive blocks of the next segment read from history file, returns segments that were
+    /// verified as the result
+    fn add_segment(
+        &mut self,
+        exported_segment: ExportedSegment,
+    ) -> Result<Vec<VerifiedSegment>, Error> {
+        let last_archived_block_number = self.last_archived_block_number;
+        let new_segment_headers = exported_segment
+            .blocks
+            .iter()
+            .filter(|(block_number, _block_bytes)| {
+                last_archived_block_number.map_or(true, |last_archived_block_number| {
+                    *block_number > last_archived_block_number
+                })
+            })
+            .flat_map(|(_block_number, block_bytes)| {
+                self.archiver
+                    .add_block(block_bytes.clone(), BlockObjectMapping::default(), false)
+            })
+            .map(|new_archived_segment| new_archived_segment.segment_header)
+            .collect::<Vec<_>>();
+        self.pending_segments.push_back(exported_segment);
+
+        let mut verified_segments = Vec::with_capacity(new_segment_headers.len());
+        for new_segment_header in new_segment_headers {
+            let new_segment_index = new_segment_header.segment_index();
+            let expected_segment_header = self
+                .pending_segments
+                .iter()
+                .find(|exported_segment| {
+                    exported_segment.segment_header.segment_index() == new_segment_index
+                })
+                .map(|exported_segment| exported_segment.segment_header)
+                .ok_or_else(|| {
+                    Error::Other(format!(
+                        "Blocks in history files produced unexpected segment {new_segment_index}"
+                    ))
+                })?;
+
+            if new_segment_header != expected_segment_header {
+                return Err(Error::Other(format!(
+                    "Segment {new_segment_index} doesn't match its segment header, history files \
+                    are corrupted"
+                )));
+            }
+
+            let mut importable_segments = Vec::new();
+            while self
+                .pending_segments
+                .front()
+                .is_some_and(|exported_segment| {
+                    exported_segment.segment_header.segment_index() <= new_segment_index
+                })
+            {
+                importable_segments.extend(self.pending_segments.pop_front());
+            }
+
+            verified_segments.push(VerifiedSegment {
+                segment_header: new_segment_header,
+                importable_segments,
+            });
+        }
+
+        Ok(verified_segments)
+    }
+}
+
+/// Check segment read from history file against what node already knows
+fn check_exported_segment<Block, AS, Client>(
+    segment_headers_store: &SegmentHeadersStore<AS>,
+    client: &Client,
+    segment_index: SegmentIndex,
+    exported_segment: &ExportedSegment,
+) -> Result<(), Error>
+where
+    Block: BlockT,
+    AS: AuxStore,
+    Client: HeaderBackend<Block> + BlockBackend<Block>,
+{
+    if exported_segment.segment_header.segment_index() != segment_index {
+        return Err(Error::Other(format!(
+            "History file for segment {segment_index} contains segment {}",
+            exported_segment.segment_header.segment_index()
+        )));
+    }
+
+    if segment_headers_store
+        .get_segment_header(segment_index)
+        .is_some_and(|known_segment_header| known_segment_header != exported_segment.segment_header)
+    {
+        return Err(Error::Other(format!(
+            "Segment header {segment_index} in history file doesn't match segment header known to \
+            the node"
+        )));
+    }
+
+    // Everything else is verified against genesis block, which must match the node's chain
+    if segment_index == SegmentIndex::ZERO {
+        let genesis_block = client
+            .block(client.info().genesis_hash)?
+            .ok_or_else(|| Error::Other("Genesis block is missing".to_string()))?;
+
+        let genesis_block_bytes = encode_block(genesis_block);
+
+        if !matches!(
+            exported_segment.blocks.first(),
+            Some((0, block_bytes)) if *block_bytes == genesis_block_bytes
+        ) {
+            return Err(Error::Other(
+                "History files are for a different chain, genesis block doesn't match".to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Queue blocks of a verified segment for import, returns number of queued blocks
+async fn import_verified_segment<Block, Client, IQS>(
+    client: &Client,
+    import_queue_service: &mut IQS,
+    exported_segment: ExportedSegment,
+    last_processed_block_number: &mut NumberFor<Block>,
+    last_queued_block_number: &mut Option<NumberFor<Block>>,
+) -> Result<u64, Error>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block> + BlockBackend<Block> + Send + Sync + 'static,
+    IQS: ImportQueueService<Block> + ?Sized,
+{
+    let ExportedSegment {
+        segment_header,
+        blocks,
+    } = exported_segment;
+
+    if let Some((block_number, _)) = blocks.last() {
+        last_queued_block_number.replace((*block_number).into());
+    }
+
+    let queued_blocks = queue_blocks_for_import::<Block, _, _>(
+        client,
+        import_queue_service,
+        blocks,
+        NumberFor::<Block>::from(segment_header.last_archived_block().number),
+        last_processed_block_number,
+    )
+    .await?;
+
+    debug!(
+        segment_index = %segment_header.segment_index(),
+        %queued_blocks,
+        "Segment blocks queued for import"
+    );
+
+    Ok(queued_blocks)
+}
+
+/// Wait for queued blocks to be imported
+async fn wait_for_blocks_import<Block, Client>(
+    client: &Client,
+    last_queued_block_number: NumberFor<Block>,
+) -> Result<(), Error>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block>,
+{
+    let mut best_block_number = client.info().best_number;
+    let mut last_import = Instant::now();
+
+    while best_block_number < last_queued_block_number {
+        tokio::time::sleep(CHECK_IMPORTED_BLOCKS_INTERVAL).await;
+
+        let new_best_block_number = client.info().best_number;
+        if new_best_block_number > best_block_number {
+            best_block_number = new_best_block_number;
+            last_import = Instant::now();
+        } else if last_import.elapsed() > NO_IMPORTED_BLOCKS_TIMEOUT {
+            return Err(Error::Other(format!(
+                "Block import stopped at block {best_block_number}, expected to import up to \
+                block {last_queued_block_number}"
+            )));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use crate::sync_from_dsn::import_history::{HistoryVerifier, VerifiedSegment};
+use std::fs;
+use std::num::NonZeroUsize;
+use subspace_archiving::archiver::{Archiver, NewArchivedSegment};
+use subspace_archiving::reconstructor::Reconstructor;
+use subspace_core_primitives::crypto::kzg::{embedded_kzg_settings, Kzg};
+use subspace_core_primitives::objects::BlockObjectMapping;
+use subspace_core_primitives::{BlockNumber, Record, RecordedHistorySegment, SegmentIndex};
+use subspace_data_retrieval::exported_history::{
+    history_files, read_history_file, write_history_file, ExportedSegment,
+};
+use subspace_erasure_coding::ErasureCoding;
+use tempfile::tempdir;
+
+fn archiver() -> (Archiver, ErasureCoding) {
+    let kzg = Kzg::new(embedded_kzg_settings());
+    let erasure_coding = ErasureCoding::new(
+        NonZeroUsize::new(Record::NUM_S_BUCKETS.next_power_of_two().ilog2() as usize)
+            .expect("Not zero; qed"),
+    )
+    .unwrap();
+
+    (Archiver::new(kzg, erasure_coding.clone()), erasure_coding)
+}
+
+/// Archive blocks that fill a bit more than two segments, returns blocks and archived segments
+fn archive_blocks() -> (Vec<Vec<u8>>, Vec<NewArchivedSegment>, ErasureCoding) {
+    let (mut archiver, erasure_coding) = archiver();
+
+    let blocks = (0..7u8)
+        .map(|block_number| {
+            (0..RecordedHistorySegment::SIZE / 3)
+                .map(|index| (index as u8).wrapping_mul(block_number + 1))
+                .collect::<Vec<u8>>()
+        })
+        .collect::<Vec<_>>();
+    let archived_segments = blocks
+        .iter()
+        .flat_map(|block| archiver.add_block(block.clone(), BlockObjectMapping::default(), false))
+        .collect::<Vec<_>>();
+    assert_eq!(archived_segments.len(), 2);
+
+    (blocks, archived_segments, erasure_coding)
+}
+
+/// Export archived segments into history files the same way `subspace-farmer export-history` does
+fn export_segments(
+    directory: &std::path::Path,
+    archived_segments: &[NewArchivedSegment],
+    erasure_coding: ErasureCoding,
+) {
+    let mut reconstructor = Reconstructor::new(erasure_coding);
+
+    for archived_segment in archived_segments {
+        let segment_pieces = archived_segment
+            .pieces
+            .pieces()
+            .map(Some)
+            .collect::<Vec<_>>();
+        let reconstructed_contents = reconstructor.add_segment(&segment_pieces).unwrap();
+
+        write_history_file(
+            directory,
+            &ExportedSegment {
+                segment_header: archived_segment.segment_header,
+                blocks: reconstructed_contents.blocks,
+            },
+        )
+        .unwrap();
+    }
+}
+
+/// Read history files and verify them, returns verified segments
+fn import_segments(directory: &std::path::Path) -> Result<Vec<VerifiedSegment>, String> {
+    let (archiver, _erasure_coding) = archiver();
+    let mut history_verifier = HistoryVerifier::new(archiver, None);
+    let mut verified_segments = Vec::new();
+
+    for (_segment_index, path) in history_files(directory).unwrap() {
+        let exported_segment = read_history_file(&path).map_err(|error| error.to_string())?;
+        verified_segments.extend(
+            history_verifier
+                .add_segment(exported_segment)
+                .map_err(|error| error.to_string())?,
+        );
+    }
+
+    Ok(verified_segments)
+}
+
+#[test]
+fn export_import_roundtrip() {
+    let directory = tempdir().unwrap();
+    let (blocks, archived_segments, erasure_coding) = archive_blocks();
+    export_segments(directory.path(), &archived_segments, erasure_coding);
+
+    let verified_segments = import_segments(directory.path()).unwrap();
+
+    // The last segment can't be verified without the next one
+    assert_eq!(verified_segments.len(), 1);
+    let VerifiedSegment {
+        segment_header,
+        importable_segments,
+    } = &verified_segments[0];
+    assert_eq!(*segment_header, archived_segments[0].segment_header);
+    assert_eq!(importable_segments.len(), 1);
+
+    // Blocks that end in the first segment are imported unchanged and in order
+    let imported_blocks = &importable_segments[0].blocks;
+    assert!(!imported_blocks.is_empty());
+    for (index, (block_number, block)) in imported_blocks.iter().enumerate() {
+        assert_eq!(*block_number, index as BlockNumber);
+        assert_eq!(block, &blocks[index]);
+    }
+}
+
+#[test]
+fn corrupted_history_rejected() {
+    let directory = tempdir().unwrap();
+    let (_blocks, archived_segments, erasure_coding) = archive_blocks();
+    export_segments(directory.path(), &archived_segments, erasure_coding);
+
+    let (_segment_index, path) = history_files(directory.path())
+        .unwrap()
+        .into_iter()
+        .find(|(segment_index, _path)| *segment_index == SegmentIndex::ZERO)
+        .unwrap();
+
+    // Block contents changed, segment header produced by archiver doesn't match
+    let mut exported_segment = read_history_file(&path).unwrap();
+    exported_segment.blocks[0].1[0] ^= 1;
+    write_history_file(directory.path(), &exported_segment).unwrap();
+    let error = import_segments(directory.path()).unwrap_err();
+    assert!(error.contains("history files are corrupted"), "{error}");
+
+    // History file is truncated and can't be decoded
+    exported_segment.blocks[0].1[0] ^= 1;
+    write_history_file(directory.path(), &exported_segment).unwrap();
+    let contents = fs::read(&path).unwrap();
+    fs::write(&path, &contents[..contents.len() / 2]).unwrap();
+    assert!(import_segments(directory.path()).is_err());
+}
@@ -0,0 +1,25 @@
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use subspace_data_retrieval::piece_fetcher::{
+    PieceFetcher, PieceGetter, PieceSourceTier, RetryPolicy,
+};
+use subspace_networking::utils::piece_provider::{PieceProvider, PieceValidator};
+
+/// Max number of pieces requested from DSN at the same time
+const PIECE_REQUEST_CONCURRENCY: NonZeroUsize = NonZeroUsize::new(128).expect("Not zero; qed");
+
+/// Create piece fetcher for DSN sync that gets pieces from farmers' piece cache on DSN (L2)
+pub(crate) fn dsn_sync_piece_fetcher<PV>(piece_provider: PieceProvider<PV>) -> PieceFetcher
+where
+    PV: PieceValidator + Send + 'static,
+{
+    PieceFetcher::new(
+        vec![PieceSourceTier {
+            name: "cache",
+            sources: vec![Arc::new(piece_provider) as Arc<dyn PieceGetter + Send + Sync>],
+            retry_policy: RetryPolicy::no_retries(),
+            validation_hook: None,
+        }],
+        PIECE_REQUEST_CONCURRENCY,
+    )
+}
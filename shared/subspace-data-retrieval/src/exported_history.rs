@@ -0,0 +1,122 @@
+// Copyright (C) 2024 Subspace Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! File format of the archived history exported to disk.
+//!
+//! History is exported into a directory with one file per segment, named with
+//! [`history_file_name()`], each file contains SCALE-encoded [`ExportedSegment`].
+//!
+//! Blocks are stored in the file of the segment where they end, which is how
+//! [`Reconstructor`](subspace_archiving::reconstructor::Reconstructor) returns them, so every
+//! block is stored exactly once and blocks are in order when files are read in segment order. The
+//! last block of the last exported segment is not exported if it doesn't fit into that segment
+//! completely.
+
+#[cfg(test)]
+mod tests;
+
+use parity_scale_codec::{Decode, Encode, IoReader};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use subspace_core_primitives::{BlockNumber, SegmentHeader, SegmentIndex};
+
+/// Prefix of history file names
+const HISTORY_FILE_PREFIX: &str = "history-";
+/// Extension of history file names
+const HISTORY_FILE_EXTENSION: &str = "bin";
+
+/// Exported history errors.
+#[derive(Debug, thiserror::Error)]
+pub enum ExportedHistoryError {
+    /// I/O error
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    /// History file decoding error
+    #[error("History file decoding error: {0}")]
+    Decoding(#[from] parity_scale_codec::Error),
+}
+
+/// Contents of a single segment of exported history
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+pub struct ExportedSegment {
+    /// Header of the segment
+    pub segment_header: SegmentHeader,
+    /// Encoded blocks that end in this segment with their block numbers
+    pub blocks: Vec<(BlockNumber, Vec<u8>)>,
+}
+
+/// File name of the history file for a segment, zero-padded so that files are sorted by segment
+/// index
+pub fn history_file_name(segment_index: SegmentIndex) -> String {
+    format!(
+        "{HISTORY_FILE_PREFIX}{:010}.{HISTORY_FILE_EXTENSION}",
+        u64::from(segment_index)
+    )
+}
+
+/// History files in `directory` with their segment indices, sorted by segment index
+pub fn history_files(directory: &Path) -> io::Result<Vec<(SegmentIndex, PathBuf)>> {
+    let mut history_files = fs::read_dir(directory)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let file_name = entry.file_name();
+            let segment_index = file_name
+                .to_str()?
+                .strip_prefix(HISTORY_FILE_PREFIX)?
+                .strip_suffix(HISTORY_FILE_EXTENSION)?
+                .strip_suffix('.')?
+                .parse::<u64>()
+                .ok()?;
+
+            Some((SegmentIndex::from(segment_index), entry.path()))
+        })
+        .collect::<Vec<_>>();
+    history_files.sort_unstable_by_key(|(segment_index, _path)| *segment_index);
+
+    Ok(history_files)
+}
+
+/// Write history file for the segment into `directory`, returns path to the file.
+///
+/// File is written to a temporary file first and renamed after that, so history file is either
+/// complete or doesn't exist.
+pub fn write_history_file(
+    directory: &Path,
+    exported_segment: &ExportedSegment,
+) -> io::Result<PathBuf> {
+    let path = directory.join(history_file_name(
+        exported_segment.segment_header.segment_index(),
+    ));
+    let tmp_path = path.with_extension("tmp");
+
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+    exported_segment.encode_to(&mut writer);
+    writer.flush()?;
+    writer.into_inner()?.sync_all()?;
+
+    fs::rename(tmp_path, &path)?;
+
+    Ok(path)
+}
+
+/// Read history file
+pub fn read_history_file(path: &Path) -> Result<ExportedSegment, ExportedHistoryError> {
+    let mut reader = IoReader(BufReader::new(File::open(path)?));
+
+    Ok(ExportedSegment::decode(&mut reader)?)
+}
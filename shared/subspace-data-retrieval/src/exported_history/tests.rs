@@ -0,0 +1,58 @@
+use crate::exported_history::{
+    history_file_name, history_files, read_history_file, write_history_file, ExportedSegment,
+};
+use std::fs;
+use subspace_core_primitives::{
+    ArchivedBlockProgress, Blake3Hash, LastArchivedBlock, SegmentCommitment, SegmentHeader,
+    SegmentIndex,
+};
+use tempfile::tempdir;
+
+fn exported_segment(segment_index: u64) -> ExportedSegment {
+    ExportedSegment {
+        segment_header: SegmentHeader::V0 {
+            segment_index: SegmentIndex::from(segment_index),
+            segment_commitment: SegmentCommitment::default(),
+            prev_segment_header_hash: Blake3Hash::default(),
+            last_archived_block: LastArchivedBlock {
+                number: segment_index as u32,
+                archived_progress: ArchivedBlockProgress::Complete,
+            },
+        },
+        blocks: vec![(segment_index as u32, vec![segment_index as u8; 100])],
+    }
+}
+
+#[test]
+fn history_files_roundtrip() {
+    let directory = tempdir().unwrap();
+
+    // Written out of order on purpose
+    let exported_segments = [2, 10, 1].map(exported_segment);
+    for exported_segment in &exported_segments {
+        write_history_file(directory.path(), exported_segment).unwrap();
+    }
+    // Unrelated files are ignored
+    fs::write(directory.path().join("history-1.tmp"), []).unwrap();
+    fs::write(directory.path().join("notes.txt"), []).unwrap();
+
+    let history_files = history_files(directory.path()).unwrap();
+    assert_eq!(
+        history_files
+            .iter()
+            .map(|(segment_index, _path)| u64::from(*segment_index))
+            .collect::<Vec<_>>(),
+        vec![1, 2, 10]
+    );
+    assert_eq!(
+        history_files[0].1,
+        directory.path().join(history_file_name(SegmentIndex::ONE))
+    );
+
+    for (segment_index, path) in history_files {
+        assert_eq!(
+            read_history_file(&path).unwrap(),
+            exported_segment(u64::from(segment_index))
+        );
+    }
+}
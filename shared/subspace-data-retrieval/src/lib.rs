@@ -15,6 +15,8 @@
 
 //! Fetching data from the archived history of the Subspace Distributed Storage Network.
 
+pub mod exported_history;
 pub mod object_fetcher;
 pub mod piece_fetcher;
+pub mod piece_reconstruction;
 pub mod segment_fetcher;
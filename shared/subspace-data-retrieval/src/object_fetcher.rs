@@ -15,14 +15,27 @@
 
 //! Fetching objects stored in the archived history of Subspace Network.
 
+#[cfg(test)]
+mod tests;
+
+use crate::piece_reconstruction::{PieceReconstruction, PieceReconstructionError};
 use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{BoxStream, FuturesOrdered};
+use futures::{future, stream, AsyncRead, Stream, StreamExt, TryStreamExt};
 use parity_scale_codec::{Compact, CompactLen, Decode, Encode};
-use std::fmt;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::{fmt, io, mem};
 use subspace_archiving::archiver::{NewArchivedSegment, Segment, SegmentItem};
 use subspace_core_primitives::{
-    Piece, PieceIndex, RawRecord, RecordedHistorySegment, SegmentIndex,
+    ArchivedHistorySegment, Piece, PieceIndex, RawRecord, RecordedHistorySegment, SegmentIndex,
 };
+use tokio::sync::OnceCell;
 use tracing::{debug, trace};
 
 /// Object fetching errors.
@@ -121,6 +134,13 @@ pub enum Error {
     /// Piece getter custom error type
     #[error("Getting piece failed permanently: {source:?}")]
     PieceGetterPermanent { source: BoxError },
+
+    /// Piece reconstruction error
+    #[error("Reconstructing missing pieces failed: {source:?}")]
+    PieceReconstruction {
+        #[from]
+        source: PieceReconstructionError,
+    },
 }
 
 /// Segment getter errors.
@@ -227,11 +247,90 @@ impl ObjectPieceGetter for Vec<(PieceIndex, Piece)> {
     }
 }
 
+/// The beginning of an object that doesn't cross segment boundaries.
+struct FastObjectStart {
+    /// Object bytes read so far, starting with the object length prefix
+    data: Vec<u8>,
+    /// Length of the object including its length prefix
+    data_length: usize,
+    /// The next source piece that contains object bytes, if there are any left to read
+    next_source_piece_index: PieceIndex,
+    /// Pieces of the segment the object is read from
+    segment_pieces: Arc<FastSegmentPieces>,
+}
+
+/// Pieces of the segment an object is read from during fast object assembling (which never
+/// crosses segment boundaries).
+///
+/// Pieces that can't be downloaded are taken from the segment, which is reconstructed at most
+/// once no matter how many of its pieces are missing.
+#[derive(Default)]
+struct FastSegmentPieces {
+    /// Pieces that were downloaded so far, reused during reconstruction
+    downloaded: Mutex<Vec<(PieceIndex, Piece)>>,
+    /// All pieces of the segment, once it was reconstructed
+    reconstructed: OnceCell<Vec<Piece>>,
+}
+
+/// The state of streaming an object that can cross segment boundaries.
+struct RegularStreamState<'a> {
+    /// The last segment that was read
+    segment_index: SegmentIndex,
+    /// Segments after the first segment of the object, with pieces prefetched across segment
+    /// boundaries
+    next_segments: BoxStream<'a, Result<Segment, Error>>,
+    /// Bytes that were read, but not yielded yet, starting with the object length prefix until
+    /// the length is decoded
+    pending: Vec<u8>,
+    /// The number of object bytes that were not yielded yet, `None` until the length is decoded
+    remaining: Option<usize>,
+}
+
+/// Stream of object bytes, created by [`ObjectFetcher::open_object_stream()`].
+///
+/// The object length is decoded before the stream is created, so it is known before any object
+/// bytes are yielded.
+pub struct ObjectStream<'a> {
+    len: usize,
+    stream: BoxStream<'a, Result<Bytes, Error>>,
+}
+
+impl fmt::Debug for ObjectStream<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObjectStream")
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Stream for ObjectStream<'_> {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.stream.poll_next_unpin(cx)
+    }
+}
+
+impl ObjectStream<'_> {
+    /// Length of the object in bytes, not including its length prefix.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the object is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
 /// Object fetcher for the Subspace DSN.
 pub struct ObjectFetcher {
     /// The piece getter used to fetch pieces.
     piece_getter: Arc<dyn ObjectPieceGetter + Send + Sync + 'static>,
 
+    /// Reconstruction of pieces that can't be downloaded, if enabled.
+    piece_reconstruction: Option<PieceReconstruction>,
+
     /// The maximum number of data bytes we'll read for a single object.
     max_object_len: usize,
 }
@@ -239,14 +338,22 @@ pub struct ObjectFetcher {
 impl ObjectFetcher {
     /// Create a new object fetcher with the given piece getter.
     ///
+    /// `piece_reconstruction` is used to reconstruct pieces that can't be downloaded from other
+    /// pieces of the same segment, or `None` to fail object fetching instead.
+    ///
     /// `max_object_len` is the amount of data bytes we'll read for a single object before giving
     /// up and returning an error, or `None` for no limit (`usize::MAX`).
-    pub fn new<PG>(piece_getter: PG, max_object_len: Option<usize>) -> Self
+    pub fn new<PG>(
+        piece_getter: PG,
+        piece_reconstruction: Option<PieceReconstruction>,
+        max_object_len: Option<usize>,
+    ) -> Self
     where
         PG: ObjectPieceGetter + Send + Sync + 'static,
     {
         Self {
             piece_getter: Arc::new(piece_getter),
+            piece_reconstruction,
             max_object_len: max_object_len.unwrap_or(usize::MAX),
         }
     }
@@ -299,6 +406,273 @@ impl ObjectFetcher {
         Ok(data)
     }
 
+    /// Stream the object in `piece_index` at `piece_offset`, yielding object bytes as soon as
+    /// contiguous data is available, without holding the whole object in memory.
+    ///
+    /// Up to `prefetch_window` pieces are fetched concurrently ahead of the data that was yielded
+    /// so far. If the object crosses segment boundaries, pieces of the next segments are
+    /// prefetched the same way, and the object is assembled one segment at a time.
+    ///
+    /// The caller should check the object's hash once the stream ends, bytes yielded before that
+    /// are not verified.
+    pub fn fetch_object_stream(
+        &self,
+        piece_index: PieceIndex,
+        piece_offset: u32,
+        prefetch_window: NonZeroUsize,
+    ) -> impl Stream<Item = Result<Bytes, Error>> + Send + '_ {
+        stream::once(self.open_object_stream(piece_index, piece_offset, prefetch_window))
+            .try_flatten()
+    }
+
+    /// Same as [`Self::fetch_object_stream()`], but exposes object bytes as [`AsyncRead`].
+    pub fn fetch_object_reader(
+        &self,
+        piece_index: PieceIndex,
+        piece_offset: u32,
+        prefetch_window: NonZeroUsize,
+    ) -> impl AsyncRead + Send + '_ {
+        Box::pin(self.fetch_object_stream(piece_index, piece_offset, prefetch_window))
+            .map_err(io::Error::other)
+            .into_async_read()
+    }
+
+    /// Same as [`Self::fetch_object_stream()`], but reads enough of the object to decode its
+    /// length before returning the stream of object bytes.
+    ///
+    /// Useful when the object length needs to be known before any object bytes are used, for
+    /// example to send it to a client ahead of the object data.
+    pub async fn open_object_stream(
+        &self,
+        piece_index: PieceIndex,
+        piece_offset: u32,
+        prefetch_window: NonZeroUsize,
+    ) -> Result<ObjectStream<'_>, Error> {
+        if !piece_index.is_source() {
+            debug!(
+                %piece_index,
+                piece_offset,
+                "Invalid piece index for object: must be a source piece",
+            );
+
+            // Parity pieces contain effectively random data, and can't be used to fetch objects
+            return Err(Error::NotSourcePiece {
+                piece_index,
+                piece_offset,
+            });
+        }
+
+        // Try streaming object from individual pieces
+        if let Some(FastObjectStart {
+            mut data,
+            data_length,
+            next_source_piece_index,
+            segment_pieces,
+        }) = self
+            .read_object_start_fast(piece_index, piece_offset)
+            .await?
+        {
+            let Compact(object_len) = Compact::<u64>::decode(&mut data.as_slice())?;
+            let object_len = object_len as usize;
+
+            debug!(
+                %piece_index,
+                piece_offset,
+                len = %object_len,
+                "Streaming object using fast object assembling",
+            );
+
+            // Strip length prefix and anything after the object
+            data.drain(..data_length - object_len);
+            data.truncate(object_len);
+            let mut remaining = object_len - data.len();
+
+            let remaining_piece_indexes = (next_source_piece_index..)
+                .filter(|i| i.is_source())
+                .take(remaining.div_ceil(RawRecord::SIZE));
+            let remaining_pieces = stream::iter(remaining_piece_indexes)
+                .map(move |next_piece_index| {
+                    let segment_pieces = Arc::clone(&segment_pieces);
+
+                    async move {
+                        self.read_piece(
+                            next_piece_index,
+                            &segment_pieces,
+                            piece_index,
+                            piece_offset,
+                        )
+                        .await
+                    }
+                })
+                .buffered(prefetch_window.get())
+                .map_ok(move |piece| {
+                    let mut record_data = piece
+                        .record()
+                        .to_raw_record_chunks()
+                        .flatten()
+                        .copied()
+                        .collect::<Vec<u8>>();
+                    record_data.truncate(remaining);
+                    remaining -= record_data.len();
+
+                    Bytes::from(record_data)
+                });
+
+            let stream = stream::once(async move { Ok(Bytes::from(data)) })
+                .chain(remaining_pieces)
+                .try_filter(|bytes| future::ready(!bytes.is_empty()))
+                .boxed();
+
+            return Ok(ObjectStream {
+                len: object_len,
+                stream,
+            });
+        }
+
+        debug!(
+            %piece_index,
+            piece_offset,
+            "Streaming object using regular object assembling",
+        );
+
+        // Regular object assembling from segments, reading extra segments until the object
+        // length can be decoded. The first segment is prefetched the same way as the following
+        // segments, so no more than `prefetch_window` pieces are requested at a time.
+        let mut next_segments = self
+            .read_segments_prefetched(
+                piece_index.segment_index(),
+                prefetch_window,
+                piece_index,
+                piece_offset,
+            )
+            .boxed();
+        let first_segment = next_segments
+            .next()
+            .await
+            .expect("Segment stream never ends; qed")?;
+        let mut state = RegularStreamState {
+            segment_index: piece_index.segment_index(),
+            next_segments,
+            pending: self.first_segment_data(first_segment, piece_index, piece_offset)?,
+            remaining: None,
+        };
+        let object_len = loop {
+            if let Some(data_length) =
+                self.decode_data_length(&state.pending, piece_index, piece_offset)?
+            {
+                let Compact(object_len) = Compact::<u64>::decode(&mut state.pending.as_slice())?;
+                let object_len = object_len as usize;
+                state.pending.drain(..data_length - object_len);
+                state.remaining.replace(object_len);
+
+                break object_len;
+            }
+
+            self.read_next_stream_segment(&mut state, piece_index, piece_offset)
+                .await?;
+        };
+
+        let stream = stream::try_unfold(state, move |state| {
+            self.next_regular_stream_chunk(state, piece_index, piece_offset)
+        })
+        .boxed();
+
+        Ok(ObjectStream {
+            len: object_len,
+            stream,
+        })
+    }
+
+    /// Produce the next chunk of an object that can cross segment boundaries, reading the next
+    /// segment if there is no pending data, returns `Ok(None)` once the whole object was yielded.
+    async fn next_regular_stream_chunk<'a>(
+        &self,
+        mut state: RegularStreamState<'a>,
+        piece_index: PieceIndex,
+        piece_offset: u32,
+    ) -> Result<Option<(Bytes, RegularStreamState<'a>)>, Error> {
+        loop {
+            let remaining = state
+                .remaining
+                .expect("Length is decoded before the stream is created; qed");
+            if remaining == 0 {
+                return Ok(None);
+            }
+
+            if !state.pending.is_empty() {
+                state.pending.truncate(remaining);
+                let chunk = Bytes::from(mem::take(&mut state.pending));
+                state.remaining = Some(remaining - chunk.len());
+
+                return Ok(Some((chunk, state)));
+            }
+
+            self.read_next_stream_segment(&mut state, piece_index, piece_offset)
+                .await?;
+        }
+    }
+
+    /// Read the segment after the last segment that was read while streaming an object, appending
+    /// its object bytes to the pending data.
+    async fn read_next_stream_segment(
+        &self,
+        state: &mut RegularStreamState<'_>,
+        piece_index: PieceIndex,
+        piece_offset: u32,
+    ) -> Result<(), Error> {
+        state.segment_index += SegmentIndex::ONE;
+        let segment_index = state.segment_index;
+        let Segment::V0 { items } = state
+            .next_segments
+            .next()
+            .await
+            .expect("Segment stream never ends; qed")?;
+        for segment_item in items {
+            match segment_item {
+                SegmentItem::BlockContinuation { bytes, .. } => {
+                    state.pending.extend_from_slice(&bytes);
+                }
+
+                // Padding at the end of segments and the parent segment header at the start of
+                // segments can be skipped, they are not part of the object data
+                SegmentItem::Padding | SegmentItem::ParentSegmentHeader(_) => {}
+
+                // We should not see these items while collecting data for a single object,
+                // unless the object already ended in the data collected so far
+                SegmentItem::Block { .. } | SegmentItem::BlockStart { .. } => {
+                    let object_complete = match state.remaining {
+                        Some(remaining) => state.pending.len() >= remaining,
+                        None => self
+                            .decode_data_length(&state.pending, piece_index, piece_offset)?
+                            .is_some_and(|data_length| state.pending.len() >= data_length),
+                    };
+                    if object_complete {
+                        break;
+                    }
+
+                    debug!(
+                        collected_data = ?state.pending.len(),
+                        %segment_index,
+                        %piece_index,
+                        piece_offset,
+                        ?segment_item,
+                        "Unexpected segment item in continuing segment",
+                    );
+
+                    return Err(Error::UnexpectedContinuingSegmentItem {
+                        collected_data: state.pending.len(),
+                        segment_index,
+                        piece_index,
+                        piece_offset,
+                        segment_item: Box::new(segment_item),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Fast object fetching and assembling where the object doesn't cross piece (super fast) or
     /// segment (just fast) boundaries, returns `Ok(None)` if fast retrieval is not guaranteed.
     // TODO: return already downloaded pieces from fetch_object_fast() and pass them to fetch_object_regular()
@@ -307,6 +681,49 @@ impl ObjectFetcher {
         piece_index: PieceIndex,
         piece_offset: u32,
     ) -> Result<Option<Vec<u8>>, Error> {
+        let Some(FastObjectStart {
+            mut data,
+            data_length,
+            next_source_piece_index,
+            segment_pieces,
+        }) = self
+            .read_object_start_fast(piece_index, piece_offset)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        // Read more pieces until we have enough data, reusing pieces that were already read (or
+        // the segment that was already reconstructed) while reading the start of the object
+        let remaining_piece_count = data_length
+            .saturating_sub(data.len())
+            .div_ceil(RawRecord::SIZE);
+        let remaining_pieces = (next_source_piece_index..)
+            .filter(|i| i.is_source())
+            .take(remaining_piece_count)
+            .map(|next_piece_index| {
+                self.read_piece(next_piece_index, &segment_pieces, piece_index, piece_offset)
+            })
+            .collect::<FuturesOrdered<_>>()
+            .try_collect::<Vec<_>>()
+            .await?;
+        for piece in remaining_pieces {
+            data.extend(piece.record().to_raw_record_chunks().flatten().copied());
+        }
+
+        // Decode the data, and return it if it's valid
+        let data = Vec::<u8>::decode(&mut data.as_slice())?;
+
+        Ok(Some(data))
+    }
+
+    /// Read the beginning of an object that doesn't cross segment boundaries, returns `Ok(None)`
+    /// if fast retrieval is not guaranteed.
+    async fn read_object_start_fast(
+        &self,
+        piece_index: PieceIndex,
+        piece_offset: u32,
+    ) -> Result<Option<FastObjectStart>, Error> {
         // If the offset is before the last 2 bytes of a segment, we might be able to do very fast
         // object retrieval without assembling and processing the whole segment.
         //
@@ -331,12 +748,18 @@ impl ObjectFetcher {
         // Data from pieces that were already read, starting with piece at index `piece_index`
         let mut read_records_data = Vec::<u8>::with_capacity(RawRecord::SIZE * 2);
         let mut next_source_piece_index = piece_index;
+        let segment_pieces = Arc::new(FastSegmentPieces::default());
 
         let piece = self
-            .read_piece(next_source_piece_index, piece_index, piece_offset)
+            .read_piece(
+                next_source_piece_index,
+                &segment_pieces,
+                piece_index,
+                piece_offset,
+            )
             .await?;
-        next_source_piece_index = next_source_piece_index.next_source_index();
         read_records_data.extend(piece.record().to_raw_record_chunks().flatten().copied());
+        next_source_piece_index = next_source_piece_index.next_source_index();
 
         if last_data_piece_in_segment {
             // The last 2 bytes might contain segment padding, so we can't use them for object length or object data.
@@ -355,10 +778,15 @@ impl ObjectFetcher {
             // Need the next piece to read the length of data, but we can only use it if there was
             // no segment padding
             let piece = self
-                .read_piece(next_source_piece_index, piece_index, piece_offset)
+                .read_piece(
+                    next_source_piece_index,
+                    &segment_pieces,
+                    piece_index,
+                    piece_offset,
+                )
                 .await?;
-            next_source_piece_index = next_source_piece_index.next_source_index();
             read_records_data.extend(piece.record().to_raw_record_chunks().flatten().copied());
+            next_source_piece_index = next_source_piece_index.next_source_index();
 
             self.decode_data_length(
                 &read_records_data[piece_offset as usize..],
@@ -378,25 +806,14 @@ impl ObjectFetcher {
         }
 
         // Discard piece data before the offset
-        let mut data = read_records_data[piece_offset as usize..].to_vec();
-        drop(read_records_data);
-
-        // Read more pieces until we have enough data
-        let remaining_piece_count = (data_length as usize - data.len()) / RawRecord::SIZE;
-        let remaining_piece_indexes = (next_source_piece_index..)
-            .filter(|i| i.is_source())
-            .take(remaining_piece_count);
-        self.read_pieces(remaining_piece_indexes, piece_index, piece_offset)
-            .await?
-            .into_iter()
-            .for_each(|piece| {
-                data.extend(piece.record().to_raw_record_chunks().flatten().copied())
-            });
+        let data = read_records_data[piece_offset as usize..].to_vec();
 
-        // Decode the data, and return it if it's valid
-        let data = Vec::<u8>::decode(&mut data.as_slice())?;
-
-        Ok(Some(data))
+        Ok(Some(FastObjectStart {
+            data,
+            data_length,
+            next_source_piece_index,
+            segment_pieces,
+        }))
     }
 
     /// Fetch and assemble an object that can cross segment boundaries, which requires assembling
@@ -407,82 +824,9 @@ impl ObjectFetcher {
         piece_offset: u32,
     ) -> Result<Vec<u8>, Error> {
         let segment_index = piece_index.segment_index();
-        let piece_position_in_segment = piece_index.position();
-        // Used to access the data after it is converted to raw bytes
-        let offset_in_segment =
-            piece_position_in_segment as usize * RawRecord::SIZE + piece_offset as usize;
-
-        let mut data = {
-            let Segment::V0 { items } = self
-                .read_segment(segment_index, piece_index, piece_offset)
-                .await?;
-            // Go through the segment until we reach the offset.
-            // Unconditional progress is enum variant + compact encoding of number of elements
-            let mut progress = 1 + Compact::compact_len(&(items.len() as u64));
-            let segment_item = items
-                .into_iter()
-                .find(|item| {
-                    // Add number of bytes in encoded version of segment item
-                    progress += item.encoded_size();
-
-                    // Our data is within another segment item, which will have wrapping data
-                    // structure, hence strictly `>` here
-                    progress > offset_in_segment
-                })
-                .ok_or_else(|| {
-                    debug!(
-                        progress,
-                        offset_in_segment,
-                        ?segment_index,
-                        %piece_index,
-                        piece_offset,
-                        "Failed to find item at offset in segment"
-                    );
-
-                    Error::NoSegmentItem {
-                        progress,
-                        offset_in_segment,
-                        segment_index,
-                        piece_index,
-                        piece_offset,
-                    }
-                })?;
-
-            // Look at the item after the offset, collecting block bytes
-            match segment_item {
-                SegmentItem::Block { bytes, .. }
-                | SegmentItem::BlockStart { bytes, .. }
-                | SegmentItem::BlockContinuation { bytes, .. } => {
-                    // Rewind back progress to the beginning of the number of bytes
-                    progress -= bytes.len();
-                    // Get a chunk of the bytes starting at the position we care about
-                    Vec::from(&bytes[offset_in_segment - progress..])
-                }
-                segment_item @ SegmentItem::Padding
-                | segment_item @ SegmentItem::ParentSegmentHeader(_) => {
-                    // TODO: create a Display impl for SegmentItem that is shorter than the entire
-                    // data contained in it
-                    debug!(
-                        segment_progress = progress,
-                        offset_in_segment,
-                        %segment_index,
-                        %piece_index,
-                        piece_offset,
-                        ?segment_item,
-                        "Unexpected segment item in first segment",
-                    );
-
-                    return Err(Error::UnexpectedFirstSegmentItem {
-                        segment_progress: progress,
-                        offset_in_segment,
-                        segment_index,
-                        piece_index,
-                        piece_offset,
-                        segment_item: Box::new(segment_item),
-                    });
-                }
-            }
-        };
+        let mut data = self
+            .read_first_segment_data(piece_index, piece_offset)
+            .await?;
 
         // Return an error if the length is unreasonably large, before we get the next segment
         if let Some(data_length) =
@@ -498,9 +842,11 @@ impl ObjectFetcher {
         // We need to read extra segments to get the object length, or the full object.
         // We don't attempt to calculate the number of segments needed, because it involves
         // headers and optional padding.
+        let mut segment_index = segment_index;
         loop {
+            segment_index += SegmentIndex::ONE;
             let Segment::V0 { items } = self
-                .read_segment(segment_index + SegmentIndex::ONE, piece_index, piece_offset)
+                .read_segment(segment_index, piece_index, piece_offset)
                 .await?;
             for segment_item in items {
                 match segment_item {
@@ -516,13 +862,12 @@ impl ObjectFetcher {
                         }
                     }
 
-                    // Padding at the end of segments can be skipped, it's not part of the object data
-                    SegmentItem::Padding => {}
+                    // Padding at the end of segments and the parent segment header at the start of
+                    // segments can be skipped, they are not part of the object data
+                    SegmentItem::Padding | SegmentItem::ParentSegmentHeader(_) => {}
 
                     // We should not see these items while collecting data for a single object
-                    SegmentItem::Block { .. }
-                    | SegmentItem::BlockStart { .. }
-                    | SegmentItem::ParentSegmentHeader(_) => {
+                    SegmentItem::Block { .. } | SegmentItem::BlockStart { .. } => {
                         debug!(
                             collected_data = ?data.len(),
                             %segment_index,
@@ -545,36 +890,415 @@ impl ObjectFetcher {
         }
     }
 
+    /// Read the first segment of an object that can cross segment boundaries, returning segment
+    /// item bytes starting at the object offset (object length prefix followed by object data,
+    /// possibly incomplete).
+    async fn read_first_segment_data(
+        &self,
+        piece_index: PieceIndex,
+        piece_offset: u32,
+    ) -> Result<Vec<u8>, Error> {
+        let segment = self
+            .read_segment(piece_index.segment_index(), piece_index, piece_offset)
+            .await?;
+
+        self.first_segment_data(segment, piece_index, piece_offset)
+    }
+
+    /// Extract bytes starting at the object offset from the first `segment` of an object, see
+    /// [`Self::read_first_segment_data()`].
+    fn first_segment_data(
+        &self,
+        segment: Segment,
+        piece_index: PieceIndex,
+        piece_offset: u32,
+    ) -> Result<Vec<u8>, Error> {
+        let segment_index = piece_index.segment_index();
+        let piece_position_in_segment = piece_index.position();
+        // Used to access the data after it is converted to raw bytes
+        let offset_in_segment =
+            piece_position_in_segment as usize * RawRecord::SIZE + piece_offset as usize;
+
+        let Segment::V0 { items } = segment;
+        // Go through the segment until we reach the offset.
+        // Unconditional progress is enum variant + compact encoding of number of elements
+        let mut progress = 1 + Compact::compact_len(&(items.len() as u64));
+        let segment_item = items
+            .into_iter()
+            .find(|item| {
+                // Add number of bytes in encoded version of segment item
+                progress += item.encoded_size();
+
+                // Our data is within another segment item, which will have wrapping data
+                // structure, hence strictly `>` here
+                progress > offset_in_segment
+            })
+            .ok_or_else(|| {
+                debug!(
+                    progress,
+                    offset_in_segment,
+                    ?segment_index,
+                    %piece_index,
+                    piece_offset,
+                    "Failed to find item at offset in segment"
+                );
+
+                Error::NoSegmentItem {
+                    progress,
+                    offset_in_segment,
+                    segment_index,
+                    piece_index,
+                    piece_offset,
+                }
+            })?;
+
+        // Look at the item after the offset, collecting block bytes
+        match segment_item {
+            SegmentItem::Block { bytes, .. }
+            | SegmentItem::BlockStart { bytes, .. }
+            | SegmentItem::BlockContinuation { bytes, .. } => {
+                // Rewind back progress to the beginning of the number of bytes
+                progress -= bytes.len();
+                // Get a chunk of the bytes starting at the position we care about
+                Ok(Vec::from(&bytes[offset_in_segment - progress..]))
+            }
+            segment_item @ SegmentItem::Padding
+            | segment_item @ SegmentItem::ParentSegmentHeader(_) => {
+                // TODO: create a Display impl for SegmentItem that is shorter than the entire
+                // data contained in it
+                debug!(
+                    segment_progress = progress,
+                    offset_in_segment,
+                    %segment_index,
+                    %piece_index,
+                    piece_offset,
+                    ?segment_item,
+                    "Unexpected segment item in first segment",
+                );
+
+                Err(Error::UnexpectedFirstSegmentItem {
+                    segment_progress: progress,
+                    offset_in_segment,
+                    segment_index,
+                    piece_index,
+                    piece_offset,
+                    segment_item: Box::new(segment_item),
+                })
+            }
+        }
+    }
+
     /// Read the whole segment by its index (just records, skipping witnesses).
     ///
+    /// Only source pieces are downloaded, so this doesn't need erasure coding, but it also can't
+    /// recover from missing source pieces.
+    ///
     /// The mapping piece index and offset are only used for error reporting.
     // TODO: replace with a refactored subspace-service::sync_from_dsn::import_blocks::download_and_reconstruct_blocks()
     async fn read_segment(
         &self,
-        _segment_index: SegmentIndex,
-        _mapping_piece_index: PieceIndex,
-        _mapping_piece_offset: u32,
+        segment_index: SegmentIndex,
+        mapping_piece_index: PieceIndex,
+        mapping_piece_offset: u32,
     ) -> Result<Segment, Error> {
-        unimplemented!("read_segment will be implemented as part of a refactoring")
+        let source_piece_indexes = segment_index
+            .segment_piece_indexes_source_first()
+            .into_iter()
+            .take(RecordedHistorySegment::NUM_RAW_RECORDS);
+        let pieces = self
+            .read_pieces(
+                source_piece_indexes,
+                mapping_piece_index,
+                mapping_piece_offset,
+            )
+            .await?;
+
+        self.decode_segment(
+            segment_index,
+            pieces,
+            mapping_piece_index,
+            mapping_piece_offset,
+        )
     }
 
-    /// Concurrently read multiple pieces by their indexes
+    /// Read segments starting at `first_segment_index` one after another (just records, skipping
+    /// witnesses), fetching up to `prefetch_window` pieces concurrently, including pieces of the
+    /// following segments once the current segment's pieces are requested.
+    ///
+    /// Missing pieces are reconstructed the same way as in [`Self::read_segment()`].
+    ///
+    /// The mapping piece index and offset are only used for error reporting.
+    fn read_segments_prefetched(
+        &self,
+        first_segment_index: SegmentIndex,
+        prefetch_window: NonZeroUsize,
+        mapping_piece_index: PieceIndex,
+        mapping_piece_offset: u32,
+    ) -> impl Stream<Item = Result<Segment, Error>> + Send + '_ {
+        let source_piece_indexes = (first_segment_index..).flat_map(|segment_index| {
+            segment_index
+                .segment_piece_indexes_source_first()
+                .into_iter()
+                .take(RecordedHistorySegment::NUM_RAW_RECORDS)
+        });
+
+        stream::iter(source_piece_indexes)
+            .map(move |piece_index| async move {
+                let result = self
+                    .download_piece(piece_index, mapping_piece_index, mapping_piece_offset)
+                    .await;
+
+                (piece_index, result)
+            })
+            .buffered(prefetch_window.get())
+            .chunks(RecordedHistorySegment::NUM_RAW_RECORDS)
+            .then(move |results| async move {
+                let (piece_indexes, results) = results.into_iter().unzip::<_, _, Vec<_>, _>();
+                let segment_index = piece_indexes[0].segment_index();
+                let pieces = self
+                    .reconstruct_missing_pieces(
+                        &piece_indexes,
+                        results,
+                        mapping_piece_index,
+                        mapping_piece_offset,
+                    )
+                    .await?;
+
+                self.decode_segment(
+                    segment_index,
+                    pieces,
+                    mapping_piece_index,
+                    mapping_piece_offset,
+                )
+            })
+    }
+
+    /// Decode a segment from its source pieces, in order.
+    ///
+    /// The mapping piece index and offset are only used for error reporting.
+    fn decode_segment(
+        &self,
+        segment_index: SegmentIndex,
+        pieces: Vec<Piece>,
+        mapping_piece_index: PieceIndex,
+        mapping_piece_offset: u32,
+    ) -> Result<Segment, Error> {
+        let mut segment_data = RecordedHistorySegment::new_boxed();
+        for (piece, raw_record) in pieces.iter().zip(segment_data.iter_mut()) {
+            piece
+                .record()
+                .to_raw_record_chunks()
+                .zip(raw_record.iter_mut())
+                .for_each(|(source, target)| {
+                    target.copy_from_slice(source);
+                });
+        }
+        drop(pieces);
+
+        let segment = Segment::decode(&mut AsRef::<[u8]>::as_ref(segment_data.as_ref())).map_err(
+            |source| {
+                debug!(
+                    %segment_index,
+                    ?source,
+                    %mapping_piece_index,
+                    mapping_piece_offset,
+                    "Failed to decode segment during object assembling"
+                );
+
+                SegmentGetterError::SegmentDecoding { source }
+            },
+        )?;
+
+        trace!(
+            %segment_index,
+            %mapping_piece_index,
+            mapping_piece_offset,
+            "Fetched segment during object assembling"
+        );
+
+        Ok(segment)
+    }
+
+    /// Concurrently read multiple pieces by their indexes, returning them in the same order as
+    /// `piece_indexes`.
     ///
     /// The mapping piece index and offset are only used for error reporting.
     // TODO: replace with a refactored method that fetches pieces
     async fn read_pieces(
         &self,
-        _piece_indexes: impl IntoIterator<Item = PieceIndex>,
-        _mapping_piece_index: PieceIndex,
-        _mapping_piece_offset: u32,
+        piece_indexes: impl IntoIterator<Item = PieceIndex>,
+        mapping_piece_index: PieceIndex,
+        mapping_piece_offset: u32,
     ) -> Result<Vec<Piece>, Error> {
-        unimplemented!("read_pieces will be implemented as part of a refactoring")
+        let piece_indexes = piece_indexes.into_iter().collect::<Vec<_>>();
+        let results = piece_indexes
+            .iter()
+            .map(|&piece_index| {
+                self.download_piece(piece_index, mapping_piece_index, mapping_piece_offset)
+            })
+            .collect::<FuturesOrdered<_>>()
+            .collect::<Vec<_>>()
+            .await;
+
+        self.reconstruct_missing_pieces(
+            &piece_indexes,
+            results,
+            mapping_piece_index,
+            mapping_piece_offset,
+        )
+        .await
+    }
+
+    /// Take download `results` for `piece_indexes` and reconstruct pieces that couldn't be
+    /// downloaded, if piece reconstruction is enabled, returning pieces in the same order as
+    /// `piece_indexes`.
+    ///
+    /// The mapping piece index and offset are only used for error reporting.
+    async fn reconstruct_missing_pieces(
+        &self,
+        piece_indexes: &[PieceIndex],
+        results: Vec<Result<Piece, Error>>,
+        mapping_piece_index: PieceIndex,
+        mapping_piece_offset: u32,
+    ) -> Result<Vec<Piece>, Error> {
+        let Some(piece_reconstruction) = &self.piece_reconstruction else {
+            return results.into_iter().collect();
+        };
+
+        // Indexes of pieces that couldn't be downloaded in `piece_indexes`, grouped by segment
+        let mut missing = BTreeMap::<SegmentIndex, Vec<usize>>::new();
+        let mut pieces = results
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| {
+                if result.is_err() {
+                    missing
+                        .entry(piece_indexes[index].segment_index())
+                        .or_default()
+                        .push(index);
+                }
+
+                result.ok()
+            })
+            .collect::<Vec<_>>();
+
+        for (segment_index, missing_indexes) in missing {
+            // Reuse pieces of the same segment that were downloaded successfully
+            let mut segment_pieces = vec![None; ArchivedHistorySegment::NUM_PIECES];
+            for (piece_index, piece) in piece_indexes.iter().zip(&pieces) {
+                if piece_index.segment_index() == segment_index && piece.is_some() {
+                    segment_pieces[piece_index.position() as usize].clone_from(piece);
+                }
+            }
+            let missing_positions = missing_indexes
+                .iter()
+                .map(|&index| piece_indexes[index].position())
+                .collect::<Vec<_>>();
+
+            debug!(
+                %segment_index,
+                ?missing_positions,
+                %mapping_piece_index,
+                mapping_piece_offset,
+                "Reconstructing pieces that couldn't be downloaded"
+            );
+
+            let reconstructed_pieces = piece_reconstruction
+                .reconstruct_pieces(
+                    &*self.piece_getter,
+                    segment_index,
+                    segment_pieces,
+                    &missing_positions,
+                )
+                .await?;
+
+            for (index, piece) in missing_indexes.into_iter().zip(reconstructed_pieces) {
+                pieces[index].replace(piece);
+            }
+        }
+
+        Ok(pieces
+            .into_iter()
+            .map(|piece| piece.expect("Missing pieces were reconstructed above; qed"))
+            .collect())
     }
 
-    /// Read and return a single piece.
+    /// Read and return a single piece of the segment an object is read from during fast object
+    /// assembling.
+    ///
+    /// If the piece can't be downloaded and piece reconstruction is enabled, the whole segment is
+    /// reconstructed (reusing pieces downloaded so far) and its pieces are used for this and all
+    /// other pieces of `segment_pieces` afterwards.
     ///
     /// The mapping piece index and offset are only used for error reporting.
     async fn read_piece(
+        &self,
+        piece_index: PieceIndex,
+        segment_pieces: &FastSegmentPieces,
+        mapping_piece_index: PieceIndex,
+        mapping_piece_offset: u32,
+    ) -> Result<Piece, Error> {
+        if let Some(reconstructed_pieces) = segment_pieces.reconstructed.get() {
+            return Ok(reconstructed_pieces[piece_index.position() as usize].clone());
+        }
+
+        let error = match self
+            .download_piece(piece_index, mapping_piece_index, mapping_piece_offset)
+            .await
+        {
+            Ok(piece) => {
+                segment_pieces
+                    .downloaded
+                    .lock()
+                    .push((piece_index, piece.clone()));
+                return Ok(piece);
+            }
+            Err(error) => error,
+        };
+
+        let Some(piece_reconstruction) = &self.piece_reconstruction else {
+            return Err(error);
+        };
+
+        // Concurrent reads of other missing pieces wait for the same reconstruction
+        let reconstructed_pieces = segment_pieces
+            .reconstructed
+            .get_or_try_init(|| async {
+                debug!(
+                    %piece_index,
+                    %error,
+                    %mapping_piece_index,
+                    mapping_piece_offset,
+                    "Reconstructing segment of piece that couldn't be downloaded"
+                );
+
+                let segment_index = piece_index.segment_index();
+                let mut known_pieces = vec![None; ArchivedHistorySegment::NUM_PIECES];
+                for (known_piece_index, piece) in segment_pieces.downloaded.lock().iter() {
+                    if known_piece_index.segment_index() == segment_index {
+                        known_pieces[known_piece_index.position() as usize].replace(piece.clone());
+                    }
+                }
+
+                piece_reconstruction
+                    .reconstruct_segment(
+                        &*self.piece_getter,
+                        segment_index,
+                        known_pieces,
+                        &[piece_index.position()],
+                    )
+                    .await
+                    .map_err(Error::from)
+            })
+            .await?;
+
+        Ok(reconstructed_pieces[piece_index.position() as usize].clone())
+    }
+
+    /// Download and return a single piece.
+    ///
+    /// The mapping piece index and offset are only used for error reporting.
+    async fn download_piece(
         &self,
         piece_index: PieceIndex,
         mapping_piece_index: PieceIndex,
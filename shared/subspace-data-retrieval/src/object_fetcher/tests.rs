@@ -0,0 +1,389 @@
+use crate::object_fetcher::{BoxError, ObjectFetcher, ObjectPieceGetter};
+use crate::piece_reconstruction::{PieceReconstruction, SourceFirst};
+use async_trait::async_trait;
+use futures::{AsyncReadExt, StreamExt, TryStreamExt};
+use parity_scale_codec::Encode;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock};
+use subspace_archiving::archiver::Archiver;
+use subspace_core_primitives::crypto::blake3_hash;
+use subspace_core_primitives::crypto::kzg::{embedded_kzg_settings, Kzg};
+use subspace_core_primitives::objects::{BlockObject, BlockObjectMapping, GlobalObject};
+use subspace_core_primitives::{
+    Piece, PieceIndex, RawRecord, Record, RecordedHistorySegment, SegmentIndex,
+};
+use subspace_erasure_coding::ErasureCoding;
+
+/// Archived history with objects of different sizes and positions
+static HISTORY: LazyLock<TestHistory> = LazyLock::new(TestHistory::new);
+
+struct TestHistory {
+    kzg: Kzg,
+    erasure_coding: ErasureCoding,
+    pieces: HashMap<PieceIndex, Piece>,
+    /// Object that fits in a single piece
+    small: TestObject,
+    /// Object that crosses piece boundaries, but not segment boundaries
+    multi_piece: TestObject,
+    /// Object that crosses segment boundary
+    cross_segment: TestObject,
+}
+
+struct TestObject {
+    data: Vec<u8>,
+    mapping: GlobalObject,
+}
+
+impl TestHistory {
+    fn new() -> Self {
+        let kzg = Kzg::new(embedded_kzg_settings());
+        let erasure_coding = ErasureCoding::new(
+            NonZeroUsize::new(Record::NUM_S_BUCKETS.next_power_of_two().ilog2() as usize)
+                .expect("Not zero; qed"),
+        )
+        .unwrap();
+        let mut archiver = Archiver::new(kzg.clone(), erasure_coding.clone());
+
+        let objects = [
+            (0, object_data(100, 1)),
+            (1000, object_data(RawRecord::SIZE * 3, 2)),
+            (
+                RecordedHistorySegment::SIZE - RawRecord::SIZE,
+                object_data(RawRecord::SIZE * 2, 3),
+            ),
+        ];
+
+        let mut block = vec![0u8; RecordedHistorySegment::SIZE / 2 * 3];
+        let mut block_objects = Vec::new();
+        for (offset, data) in &objects {
+            let encoded = data.encode();
+            block[*offset..][..encoded.len()].copy_from_slice(&encoded);
+            block_objects.push(BlockObject {
+                hash: blake3_hash(data),
+                offset: *offset as u32,
+            });
+        }
+
+        let mut archived_segments = archiver.add_block(
+            block,
+            BlockObjectMapping::from_objects(block_objects),
+            false,
+        );
+        // Fill the second segment, so it is archived too
+        archived_segments.extend(archiver.add_block(
+            vec![0u8; RecordedHistorySegment::SIZE],
+            BlockObjectMapping::default(),
+            false,
+        ));
+        assert!(archived_segments.len() >= 2);
+
+        let mappings = archived_segments
+            .iter()
+            .flat_map(|segment| segment.global_object_mappings())
+            .collect::<Vec<_>>();
+        let pieces = archived_segments
+            .iter()
+            .flat_map(|segment| {
+                segment
+                    .segment_header
+                    .segment_index()
+                    .segment_piece_indexes()
+                    .into_iter()
+                    .zip(segment.pieces.pieces())
+            })
+            .collect();
+
+        let [small, multi_piece, cross_segment] = objects.map(|(_offset, data)| {
+            let hash = blake3_hash(&data);
+            let mapping = *mappings
+                .iter()
+                .find(|mapping| mapping.hash == hash)
+                .expect("All objects are mapped; qed");
+
+            TestObject { data, mapping }
+        });
+
+        Self {
+            kzg,
+            erasure_coding,
+            pieces,
+            small,
+            multi_piece,
+            cross_segment,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TestPieceGetter;
+
+#[async_trait]
+impl ObjectPieceGetter for TestPieceGetter {
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        Ok(HISTORY.pieces.get(&piece_index).cloned())
+    }
+}
+
+/// Piece getter that counts requests and doesn't have some of the pieces
+#[derive(Debug, Default)]
+struct CountingPieceGetter {
+    unavailable: HashSet<PieceIndex>,
+    requests: AtomicUsize,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+#[async_trait]
+impl ObjectPieceGetter for CountingPieceGetter {
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        // Give other requests a chance to start concurrently
+        tokio::task::yield_now().await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        if self.unavailable.contains(&piece_index) {
+            return Ok(None);
+        }
+
+        Ok(HISTORY.pieces.get(&piece_index).cloned())
+    }
+}
+
+fn object_data(len: usize, seed: u8) -> Vec<u8> {
+    (0..len)
+        .map(|index| (index as u8).wrapping_mul(31).wrapping_add(seed))
+        .collect()
+}
+
+async fn stream_chunks(object: &TestObject, prefetch_window: usize) -> Vec<Vec<u8>> {
+    let object_fetcher = ObjectFetcher::new(TestPieceGetter, None, None);
+    let object_stream = object_fetcher
+        .open_object_stream(
+            object.mapping.piece_index,
+            object.mapping.offset,
+            NonZeroUsize::new(prefetch_window).unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(object_stream.len(), object.data.len());
+
+    object_stream
+        .map_ok(|chunk| chunk.to_vec())
+        .try_collect()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn stream_within_piece() {
+    let object = &HISTORY.small;
+    let chunks = stream_chunks(object, 1).await;
+
+    assert_eq!(chunks, vec![object.data.clone()]);
+}
+
+#[tokio::test]
+async fn stream_across_pieces() {
+    let object = &HISTORY.multi_piece;
+    assert_eq!(
+        object.mapping.piece_index.segment_index(),
+        SegmentIndex::ZERO
+    );
+
+    for prefetch_window in [1, 4] {
+        let chunks = stream_chunks(object, prefetch_window).await;
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= RawRecord::SIZE));
+        assert_eq!(chunks.concat(), object.data);
+    }
+}
+
+#[tokio::test]
+async fn stream_across_segments() {
+    let object = &HISTORY.cross_segment;
+    assert_eq!(
+        object.mapping.piece_index.segment_index(),
+        SegmentIndex::ZERO
+    );
+
+    for prefetch_window in [1, 4, RecordedHistorySegment::NUM_RAW_RECORDS * 2] {
+        let chunks = stream_chunks(object, prefetch_window).await;
+
+        // The object ends in the second segment
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), object.data);
+    }
+}
+
+#[tokio::test]
+async fn stream_across_segments_respects_prefetch_window() {
+    let object = &HISTORY.cross_segment;
+
+    for prefetch_window in [1, 4] {
+        let piece_getter = Arc::new(CountingPieceGetter::default());
+        let object_fetcher = ObjectFetcher::new(Arc::clone(&piece_getter), None, None);
+
+        let streamed = object_fetcher
+            .fetch_object_stream(
+                object.mapping.piece_index,
+                object.mapping.offset,
+                NonZeroUsize::new(prefetch_window).unwrap(),
+            )
+            .map_ok(|chunk| chunk.to_vec())
+            .try_concat()
+            .await
+            .unwrap();
+        assert_eq!(streamed, object.data);
+
+        // Including pieces of the first segment
+        assert!(piece_getter.max_in_flight.load(Ordering::SeqCst) <= prefetch_window);
+    }
+}
+
+#[tokio::test]
+async fn stream_matches_fetch_object() {
+    let object_fetcher = ObjectFetcher::new(TestPieceGetter, None, None);
+
+    for object in [&HISTORY.small, &HISTORY.multi_piece, &HISTORY.cross_segment] {
+        let GlobalObject {
+            piece_index,
+            offset,
+            ..
+        } = object.mapping;
+        let fetched = object_fetcher
+            .fetch_object(piece_index, offset)
+            .await
+            .unwrap();
+        assert_eq!(fetched, object.data);
+
+        let streamed = object_fetcher
+            .fetch_object_stream(piece_index, offset, NonZeroUsize::new(2).unwrap())
+            .map_ok(|chunk| chunk.to_vec())
+            .try_concat()
+            .await
+            .unwrap();
+        assert_eq!(streamed, fetched);
+
+        let mut read = Vec::new();
+        object_fetcher
+            .fetch_object_reader(piece_index, offset, NonZeroUsize::new(2).unwrap())
+            .read_to_end(&mut read)
+            .await
+            .unwrap();
+        assert_eq!(read, fetched);
+    }
+}
+
+#[tokio::test]
+async fn stream_missing_piece() {
+    let object_fetcher = ObjectFetcher::new(Vec::<(PieceIndex, Piece)>::new(), None, None);
+    let object = &HISTORY.small;
+
+    let result = object_fetcher
+        .fetch_object_stream(
+            object.mapping.piece_index,
+            object.mapping.offset,
+            NonZeroUsize::new(1).unwrap(),
+        )
+        .next()
+        .await
+        .unwrap();
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn stream_reconstructs_segment_once() {
+    let object = &HISTORY.multi_piece;
+    let first_piece_index = object.mapping.piece_index;
+    // All pieces of the object except the first one are missing
+    let missing_piece_indexes = (first_piece_index.next_source_index()..)
+        .filter(|piece_index| piece_index.is_source())
+        .take((object.data.len() + object.mapping.offset as usize) / RawRecord::SIZE)
+        .collect::<HashSet<_>>();
+    assert!(missing_piece_indexes.len() > 1);
+
+    for prefetch_window in [1, 4] {
+        let piece_getter = Arc::new(CountingPieceGetter {
+            unavailable: missing_piece_indexes.clone(),
+            ..CountingPieceGetter::default()
+        });
+        let object_fetcher = ObjectFetcher::new(
+            Arc::clone(&piece_getter),
+            Some(PieceReconstruction::new(
+                HISTORY.kzg.clone(),
+                HISTORY.erasure_coding.clone(),
+                SourceFirst,
+            )),
+            None,
+        );
+
+        let streamed = object_fetcher
+            .fetch_object_stream(
+                first_piece_index,
+                object.mapping.offset,
+                NonZeroUsize::new(prefetch_window).unwrap(),
+            )
+            .map_ok(|chunk| chunk.to_vec())
+            .try_concat()
+            .await
+            .unwrap();
+        assert_eq!(streamed, object.data);
+
+        // The first piece, missing pieces that were requested before the segment was
+        // reconstructed and pieces for a single reconstruction (the first missing piece is not
+        // requested again, other missing pieces are requested and fail)
+        let reconstruction_requests =
+            RecordedHistorySegment::NUM_RAW_RECORDS - 1 + missing_piece_indexes.len() - 1;
+        let requests = piece_getter.requests.load(Ordering::SeqCst);
+        if prefetch_window == 1 {
+            assert_eq!(requests, 1 + 1 + reconstruction_requests);
+        } else {
+            assert!(requests <= 1 + missing_piece_indexes.len() + reconstruction_requests);
+        }
+    }
+}
+
+#[tokio::test]
+async fn fetch_object_reuses_pieces_of_object_start() {
+    let object = &HISTORY.multi_piece;
+    let first_piece_index = object.mapping.piece_index;
+    // All pieces of the object except the first one are missing
+    let missing_piece_indexes = (first_piece_index.next_source_index()..)
+        .filter(|piece_index| piece_index.is_source())
+        .take((object.data.len() + object.mapping.offset as usize) / RawRecord::SIZE)
+        .collect::<HashSet<_>>();
+    assert!(missing_piece_indexes.len() > 1);
+
+    let piece_getter = Arc::new(CountingPieceGetter {
+        unavailable: missing_piece_indexes.clone(),
+        ..CountingPieceGetter::default()
+    });
+    let object_fetcher = ObjectFetcher::new(
+        Arc::clone(&piece_getter),
+        Some(PieceReconstruction::new(
+            HISTORY.kzg.clone(),
+            HISTORY.erasure_coding.clone(),
+            SourceFirst,
+        )),
+        None,
+    );
+
+    let fetched = object_fetcher
+        .fetch_object(first_piece_index, object.mapping.offset)
+        .await
+        .unwrap();
+    assert_eq!(fetched, object.data);
+
+    // The first piece is not requested again for reconstruction, which happens only once
+    let reconstruction_requests =
+        RecordedHistorySegment::NUM_RAW_RECORDS - 1 + missing_piece_indexes.len() - 1;
+    assert!(
+        piece_getter.requests.load(Ordering::SeqCst)
+            <= 1 + missing_piece_indexes.len() + reconstruction_requests
+    );
+}
@@ -15,4 +15,547 @@
 
 //! Fetching pieces of the archived history of Subspace Network.
 //!
-//! TODO: move piece fetching here
+//! [`PieceGetter`] is the common abstraction over anything that can return pieces by index.
+//! [`PieceFetcher`] combines multiple piece getters into one: sources are grouped into tiers that
+//! are tried in priority order (for example local cache, then L2 cache on DSN, then archival
+//! storage on DSN), each tier has its own retry policy and optional validation hook. Concurrent
+//! requests for the same piece are deduplicated and the total number of concurrent requests is
+//! limited.
+
+#[cfg(test)]
+mod tests;
+
+use crate::object_fetcher::{BoxError, ObjectPieceGetter};
+use async_trait::async_trait;
+use backoff::backoff::Backoff;
+use backoff::future::retry;
+use backoff::ExponentialBackoff;
+use futures::future::{BoxFuture, Shared};
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt, Stream, StreamExt};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use subspace_core_primitives::{
+    ArchivedHistorySegment, Piece, PieceIndex, RecordedHistorySegment, SegmentIndex,
+};
+use tokio::sync::Semaphore;
+use tracing::{debug, trace, warn};
+
+/// Piece fetching errors.
+#[derive(Debug, thiserror::Error)]
+pub enum PieceFetcherError {
+    /// Not enough pieces of a segment were downloaded to reconstruct it
+    #[error(
+        "Not enough pieces of segment {segment_index} downloaded: {downloaded} downloaded, \
+        {required} required"
+    )]
+    NotEnoughPieces {
+        segment_index: SegmentIndex,
+        downloaded: usize,
+        required: usize,
+    },
+}
+
+/// Stream of pieces returned by [`PieceGetter::get_pieces()`]
+pub type PieceStream<'a> =
+    Pin<Box<dyn Stream<Item = (PieceIndex, Result<Option<Piece>, BoxError>)> + Send + 'a>>;
+
+/// Trait representing a way to get pieces
+#[async_trait]
+pub trait PieceGetter {
+    /// Get piece by index.
+    ///
+    /// Returns `Ok(None)` if the piece was not found and `Err(_)` if getting the piece failed.
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError>;
+
+    /// Get multiple pieces by index, pieces are returned in the order they become available.
+    ///
+    /// Default implementation gets pieces one by one concurrently, implementations that can get
+    /// multiple pieces at once more efficiently (for example, with batched requests to DSN peers)
+    /// should override it.
+    fn get_pieces<'a>(&'a self, piece_indexes: Vec<PieceIndex>) -> PieceStream<'a>
+    where
+        Self: Sync,
+    {
+        Box::pin(
+            piece_indexes
+                .into_iter()
+                .map(|piece_index| async move { (piece_index, self.get_piece(piece_index).await) })
+                .collect::<FuturesUnordered<_>>(),
+        )
+    }
+}
+
+#[async_trait]
+impl<T> PieceGetter for Arc<T>
+where
+    T: PieceGetter + Send + Sync + ?Sized,
+{
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        self.as_ref().get_piece(piece_index).await
+    }
+
+    fn get_pieces<'a>(&'a self, piece_indexes: Vec<PieceIndex>) -> PieceStream<'a>
+    where
+        Self: Sync,
+    {
+        self.as_ref().get_pieces(piece_indexes)
+    }
+}
+
+// Convenience methods, mainly used in testing
+#[async_trait]
+impl PieceGetter for ArchivedHistorySegment {
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        let position = usize::try_from(u64::from(piece_index))?;
+
+        Ok(self.pieces().nth(position))
+    }
+}
+
+/// Hook for validating pieces before they are returned by [`PieceFetcher`]
+#[async_trait]
+pub trait PieceValidationHook: fmt::Debug + Send + Sync {
+    /// Returns the piece if it is valid and `None` otherwise
+    async fn validate_piece(&self, piece_index: PieceIndex, piece: Piece) -> Option<Piece>;
+}
+
+/// Retry policy for a tier of piece sources
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Max number of retries after the first attempt
+    pub max_retries: u16,
+    /// Exponential backoff between retries
+    pub backoff: ExponentialBackoff,
+}
+
+impl RetryPolicy {
+    /// Retry policy that doesn't retry
+    pub fn no_retries() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: ExponentialBackoff::default(),
+        }
+    }
+}
+
+/// Group of piece sources with the same priority
+pub struct PieceSourceTier {
+    /// Name of the tier, used in logs
+    pub name: &'static str,
+    /// Sources that are tried in order until one of them returns a piece, sources are identified
+    /// by their position in logs
+    pub sources: Vec<Arc<dyn PieceGetter + Send + Sync>>,
+    /// Retry policy for the tier as a whole
+    pub retry_policy: RetryPolicy,
+    /// Validation of pieces returned by sources of this tier, pieces are not validated if `None`
+    pub validation_hook: Option<Arc<dyn PieceValidationHook>>,
+}
+
+impl fmt::Debug for PieceSourceTier {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PieceSourceTier")
+            .field("name", &self.name)
+            .field("sources", &self.sources.len())
+            .field("retry_policy", &self.retry_policy)
+            .field("validation_hook", &self.validation_hook)
+            .finish()
+    }
+}
+
+/// Piece index, tiers and retry mode of a piece request
+type InProgressPieceKey = (PieceIndex, Range<usize>, bool);
+type InProgressPiece = Shared<BoxFuture<'static, Option<Piece>>>;
+/// Requests for the same piece from the same tiers with the same retry mode that are in progress
+type InProgressPieces = HashMap<InProgressPieceKey, InProgressPiece>;
+
+/// Removes in progress piece from the map once the request is finished or the last request
+/// waiting for it was dropped.
+///
+/// In progress piece holds a reference to the fetcher, so removal can't be done by the future
+/// itself: if all requests waiting for it are dropped before it finishes, it would stay in the map
+/// forever and keep the fetcher alive.
+struct RemoveInProgressPieceOnDrop<'a> {
+    in_progress_pieces: &'a Mutex<InProgressPieces>,
+    key: InProgressPieceKey,
+    in_progress_piece: InProgressPiece,
+}
+
+impl Drop for RemoveInProgressPieceOnDrop<'_> {
+    fn drop(&mut self) {
+        let mut in_progress_pieces = self.in_progress_pieces.lock();
+
+        let Some(stored_in_progress_piece) = in_progress_pieces.get(&self.key) else {
+            return;
+        };
+
+        let remove = if stored_in_progress_piece.peek().is_some() {
+            // Request has finished, no need to keep it around
+            true
+        } else {
+            // Entry might belong to a newer request, otherwise check whether this is the last
+            // request waiting for it (the other reference is held by the map)
+            stored_in_progress_piece.ptr_eq(&self.in_progress_piece)
+                && self
+                    .in_progress_piece
+                    .strong_count()
+                    .is_some_and(|strong_count| strong_count <= 2)
+        };
+
+        if remove {
+            in_progress_pieces.remove(&self.key);
+        }
+    }
+}
+
+struct Inner {
+    tiers: Vec<PieceSourceTier>,
+    in_progress_pieces: Mutex<InProgressPieces>,
+    request_semaphore: Semaphore,
+}
+
+/// Concurrent piece fetcher that gets pieces from multiple tiers of sources in priority order.
+#[derive(Clone)]
+pub struct PieceFetcher {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for PieceFetcher {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PieceFetcher")
+            .field("tiers", &self.inner.tiers)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl PieceGetter for PieceFetcher {
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        Ok(self.get_piece(piece_index).await)
+    }
+}
+
+#[async_trait]
+impl ObjectPieceGetter for PieceFetcher {
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        Ok(self.get_piece(piece_index).await)
+    }
+}
+
+impl PieceFetcher {
+    /// Create new instance.
+    ///
+    /// `tiers` are tried in order, `request_concurrency` limits the number of pieces that are
+    /// requested at the same time.
+    pub fn new(tiers: Vec<PieceSourceTier>, request_concurrency: NonZeroUsize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                tiers,
+                in_progress_pieces: Mutex::default(),
+                request_semaphore: Semaphore::new(request_concurrency.get()),
+            }),
+        }
+    }
+
+    /// Get piece from all tiers, respecting their retry policies
+    pub async fn get_piece(&self, piece_index: PieceIndex) -> Option<Piece> {
+        self.get_piece_from_tiers(piece_index, 0..self.inner.tiers.len(), true)
+            .await
+    }
+
+    /// Get piece from a subset of tiers with a single attempt, ignoring retry policies
+    pub async fn try_get_piece(
+        &self,
+        piece_index: PieceIndex,
+        tiers: Range<usize>,
+    ) -> Option<Piece> {
+        self.get_piece_from_tiers(piece_index, tiers, false).await
+    }
+
+    /// Get multiple pieces from all tiers concurrently, pieces are returned in the order they
+    /// become available.
+    ///
+    /// Concurrency is limited by the request concurrency the fetcher was created with.
+    pub fn get_pieces<PieceIndexes>(
+        &self,
+        piece_indexes: PieceIndexes,
+    ) -> impl Stream<Item = (PieceIndex, Option<Piece>)> + Send + '_
+    where
+        PieceIndexes: IntoIterator<Item = PieceIndex>,
+    {
+        piece_indexes
+            .into_iter()
+            .map(|piece_index| async move { (piece_index, self.get_piece(piece_index).await) })
+            .collect::<FuturesUnordered<_>>()
+    }
+
+    async fn get_piece_from_tiers(
+        &self,
+        piece_index: PieceIndex,
+        tiers: Range<usize>,
+        with_retries: bool,
+    ) -> Option<Piece> {
+        let key = (piece_index, tiers.clone(), with_retries);
+        let (in_progress_piece, already_in_progress) = {
+            let mut in_progress_pieces = self.inner.in_progress_pieces.lock();
+            match in_progress_pieces.get(&key) {
+                Some(in_progress_piece) => (in_progress_piece.clone(), true),
+                None => {
+                    let fetcher = self.clone();
+                    let tiers = tiers.clone();
+                    let in_progress_piece = async move {
+                        // Only the request that actually fetches the piece holds a permit,
+                        // duplicate requests just wait for its result
+                        let _permit = fetcher.inner.request_semaphore.acquire().await;
+                        fetcher
+                            .fetch_from_tiers(piece_index, tiers, with_retries)
+                            .await
+                    }
+                    .boxed()
+                    .shared();
+                    in_progress_pieces.insert(key.clone(), in_progress_piece.clone());
+
+                    (in_progress_piece, false)
+                }
+            }
+        };
+
+        if already_in_progress {
+            trace!(
+                %piece_index,
+                ?tiers,
+                with_retries,
+                "Piece is already in progress, waiting for result"
+            );
+        }
+
+        let mut remove_in_progress_piece_on_drop = RemoveInProgressPieceOnDrop {
+            in_progress_pieces: &self.inner.in_progress_pieces,
+            key,
+            in_progress_piece,
+        };
+
+        (&mut remove_in_progress_piece_on_drop.in_progress_piece).await
+    }
+
+    async fn fetch_from_tiers(
+        &self,
+        piece_index: PieceIndex,
+        tiers: Range<usize>,
+        with_retries: bool,
+    ) -> Option<Piece> {
+        let Some(tiers) = self.inner.tiers.get(tiers.clone()) else {
+            warn!(%piece_index, ?tiers, "Requested piece source tiers don't exist");
+            return None;
+        };
+
+        for tier in tiers {
+            let maybe_piece = if with_retries {
+                Self::fetch_from_tier_with_retries(tier, piece_index).await
+            } else {
+                Self::fetch_from_tier(tier, piece_index).await
+            };
+
+            if let Some(piece) = maybe_piece {
+                trace!(%piece_index, tier = tier.name, "Got piece");
+                return Some(piece);
+            }
+        }
+
+        debug!(
+            %piece_index,
+            "Cannot acquire piece: all sources yielded empty result"
+        );
+        None
+    }
+
+    async fn fetch_from_tier_with_retries(
+        tier: &PieceSourceTier,
+        piece_index: PieceIndex,
+    ) -> Option<Piece> {
+        let retries = AtomicU32::new(0);
+        let max_retries = u32::from(tier.retry_policy.max_retries);
+        let mut backoff = tier.retry_policy.backoff.clone();
+        backoff.reset();
+
+        let maybe_piece_fut = retry(backoff, || async {
+            let current_attempt = retries.fetch_add(1, Ordering::Relaxed);
+
+            if let Some(piece) = Self::fetch_from_tier(tier, piece_index).await {
+                trace!(%piece_index, tier = tier.name, current_attempt, "Got piece from tier");
+                return Ok(Some(piece));
+            }
+            if current_attempt >= max_retries {
+                if max_retries > 0 {
+                    debug!(
+                        %piece_index,
+                        tier = tier.name,
+                        current_attempt,
+                        max_retries,
+                        "Couldn't get a piece from tier. No retries left"
+                    );
+                }
+                return Ok(None);
+            }
+
+            trace!(
+                %piece_index,
+                tier = tier.name,
+                current_attempt,
+                "Couldn't get a piece from tier, retrying..."
+            );
+
+            Err(backoff::Error::transient("Couldn't get piece from tier"))
+        });
+
+        maybe_piece_fut.await.ok().flatten()
+    }
+
+    async fn fetch_from_tier(tier: &PieceSourceTier, piece_index: PieceIndex) -> Option<Piece> {
+        for (source_index, source) in tier.sources.iter().enumerate() {
+            trace!(%piece_index, tier = tier.name, source = source_index, "Getting piece from source");
+
+            let piece = match source.get_piece(piece_index).await {
+                Ok(Some(piece)) => piece,
+                Ok(None) => {
+                    trace!(%piece_index, tier = tier.name, source = source_index, "Piece not found in source");
+                    continue;
+                }
+                Err(error) => {
+                    debug!(
+                        %piece_index,
+                        tier = tier.name,
+                        source = source_index,
+                        %error,
+                        "Failed to get piece from source"
+                    );
+                    continue;
+                }
+            };
+
+            let Some(validation_hook) = &tier.validation_hook else {
+                return Some(piece);
+            };
+
+            if let Some(piece) = validation_hook.validate_piece(piece_index, piece).await {
+                return Some(piece);
+            }
+
+            debug!(%piece_index, tier = tier.name, source = source_index, "Piece from source is invalid");
+        }
+
+        None
+    }
+}
+
+/// Download pieces of a segment necessary to reconstruct it.
+///
+/// Source pieces are requested first, parity pieces are only requested when source pieces fail to
+/// download. Returns pieces at their positions in the segment as soon as
+/// [`RecordedHistorySegment::NUM_RAW_RECORDS`] pieces were downloaded.
+pub async fn download_segment_pieces<PG>(
+    segment_index: SegmentIndex,
+    piece_getter: &PG,
+) -> Result<Vec<Option<Piece>>, PieceFetcherError>
+where
+    PG: PieceGetter + ?Sized,
+{
+    debug!(%segment_index, "Retrieving pieces of the segment");
+
+    let required_pieces = RecordedHistorySegment::NUM_RAW_RECORDS;
+    let semaphore = &Semaphore::new(required_pieces);
+
+    let mut received_segment_pieces = segment_index
+        .segment_piece_indexes_source_first()
+        .into_iter()
+        .map(|piece_index| {
+            // Source pieces will acquire permit here right away
+            let maybe_permit = semaphore.try_acquire().ok();
+
+            async move {
+                let permit = match maybe_permit {
+                    Some(permit) => permit,
+                    None => {
+                        // Other pieces will acquire permit here instead
+                        match semaphore.acquire().await {
+                            Ok(permit) => permit,
+                            Err(error) => {
+                                warn!(
+                                    %piece_index,
+                                    %error,
+                                    "Semaphore was closed, interrupting piece retrieval"
+                                );
+                                return None;
+                            }
+                        }
+                    }
+                };
+                let maybe_piece = match piece_getter.get_piece(piece_index).await {
+                    Ok(maybe_piece) => maybe_piece,
+                    Err(error) => {
+                        trace!(
+                            %error,
+                            %piece_index,
+                            "Piece request failed",
+                        );
+                        return None;
+                    }
+                };
+
+                trace!(
+                    %piece_index,
+                    piece_found = maybe_piece.is_some(),
+                    "Piece request succeeded",
+                );
+
+                maybe_piece.map(|received_piece| {
+                    // Piece was received successfully, "remove" this slot from semaphore
+                    permit.forget();
+                    (piece_index, received_piece)
+                })
+            }
+        })
+        .collect::<FuturesUnordered<_>>();
+
+    let mut segment_pieces = vec![None::<Piece>; ArchivedHistorySegment::NUM_PIECES];
+    let mut pieces_received = 0;
+
+    while let Some(maybe_result) = received_segment_pieces.next().await {
+        let Some((piece_index, piece)) = maybe_result else {
+            continue;
+        };
+
+        segment_pieces
+            .get_mut(piece_index.position() as usize)
+            .expect("Piece position is by definition within segment; qed")
+            .replace(piece);
+
+        pieces_received += 1;
+
+        if pieces_received >= required_pieces {
+            trace!(%segment_index, "Received half of the segment.");
+            return Ok(segment_pieces);
+        }
+    }
+
+    debug!(
+        %segment_index,
+        %pieces_received,
+        %required_pieces,
+        "Not enough pieces of the segment downloaded"
+    );
+
+    Err(PieceFetcherError::NotEnoughPieces {
+        segment_index,
+        downloaded: pieces_received,
+        required: required_pieces,
+    })
+}
@@ -0,0 +1,349 @@
+use crate::object_fetcher::BoxError;
+use crate::piece_fetcher::{
+    download_segment_pieces, PieceFetcher, PieceFetcherError, PieceGetter, PieceSourceTier,
+    PieceValidationHook, RetryPolicy,
+};
+use async_trait::async_trait;
+use backoff::ExponentialBackoff;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use subspace_core_primitives::{
+    ArchivedHistorySegment, Piece, PieceIndex, RecordedHistorySegment, SegmentIndex,
+};
+
+#[derive(Debug, Default)]
+struct TestPieceSource {
+    pieces: HashMap<PieceIndex, Piece>,
+    delay: Duration,
+    requests: AtomicUsize,
+}
+
+impl TestPieceSource {
+    fn new(piece_indexes: impl IntoIterator<Item = PieceIndex>) -> Arc<Self> {
+        Arc::new(Self {
+            pieces: piece_indexes
+                .into_iter()
+                .map(|piece_index| (piece_index, test_piece(piece_index)))
+                .collect(),
+            ..Self::default()
+        })
+    }
+
+    fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl PieceGetter for TestPieceSource {
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+
+        Ok(self.pieces.get(&piece_index).cloned())
+    }
+}
+
+#[derive(Debug)]
+struct RejectAll;
+
+#[async_trait]
+impl PieceValidationHook for RejectAll {
+    async fn validate_piece(&self, _piece_index: PieceIndex, _piece: Piece) -> Option<Piece> {
+        None
+    }
+}
+
+fn test_piece(piece_index: PieceIndex) -> Piece {
+    let mut piece = Piece::default();
+    piece.as_mut()[..8].copy_from_slice(&u64::from(piece_index).to_le_bytes());
+    piece
+}
+
+fn tier(name: &'static str, sources: Vec<Arc<TestPieceSource>>) -> PieceSourceTier {
+    PieceSourceTier {
+        name,
+        sources: sources
+            .into_iter()
+            .map(|source| source as Arc<dyn PieceGetter + Send + Sync>)
+            .collect(),
+        retry_policy: RetryPolicy::no_retries(),
+        validation_hook: None,
+    }
+}
+
+fn fetcher(tiers: Vec<PieceSourceTier>) -> PieceFetcher {
+    PieceFetcher::new(tiers, NonZeroUsize::new(10).unwrap())
+}
+
+#[tokio::test]
+async fn tiers_priority() {
+    let piece_index = PieceIndex::from(2);
+    let first = TestPieceSource::new([piece_index]);
+    let second = TestPieceSource::new([piece_index, PieceIndex::from(4)]);
+    let piece_fetcher = fetcher(vec![
+        tier("first", vec![Arc::clone(&first)]),
+        tier("second", vec![Arc::clone(&second)]),
+    ]);
+
+    assert_eq!(
+        piece_fetcher.get_piece(piece_index).await,
+        Some(test_piece(piece_index))
+    );
+    assert_eq!(first.requests(), 1);
+    assert_eq!(second.requests(), 0);
+
+    // Not in the first tier, falls back to the second one
+    assert_eq!(
+        piece_fetcher.get_piece(PieceIndex::from(4)).await,
+        Some(test_piece(PieceIndex::from(4)))
+    );
+    assert_eq!(first.requests(), 2);
+    assert_eq!(second.requests(), 1);
+
+    // Not found anywhere
+    assert_eq!(piece_fetcher.get_piece(PieceIndex::from(6)).await, None);
+    assert_eq!(first.requests(), 3);
+    assert_eq!(second.requests(), 2);
+
+    // Only the second tier
+    assert_eq!(
+        piece_fetcher.try_get_piece(piece_index, 1..2).await,
+        Some(test_piece(piece_index))
+    );
+    assert_eq!(first.requests(), 3);
+    assert_eq!(second.requests(), 3);
+}
+
+#[tokio::test]
+async fn retry_policy() {
+    let source = TestPieceSource::new([]);
+    let piece_fetcher = fetcher(vec![PieceSourceTier {
+        retry_policy: RetryPolicy {
+            max_retries: 3,
+            backoff: ExponentialBackoff {
+                initial_interval: Duration::from_millis(1),
+                max_interval: Duration::from_millis(1),
+                ..ExponentialBackoff::default()
+            },
+        },
+        ..tier("retrying", vec![Arc::clone(&source)])
+    }]);
+
+    assert_eq!(piece_fetcher.get_piece(PieceIndex::from(0)).await, None);
+    assert_eq!(source.requests(), 4);
+
+    // Retry policy is ignored for single attempt
+    assert_eq!(
+        piece_fetcher.try_get_piece(PieceIndex::from(0), 0..1).await,
+        None
+    );
+    assert_eq!(source.requests(), 5);
+}
+
+#[tokio::test]
+async fn validation_hook() {
+    let piece_index = PieceIndex::from(0);
+    let invalid = TestPieceSource::new([piece_index]);
+    let valid = TestPieceSource::new([piece_index]);
+    let piece_fetcher = fetcher(vec![
+        PieceSourceTier {
+            validation_hook: Some(Arc::new(RejectAll)),
+            ..tier("invalid", vec![Arc::clone(&invalid)])
+        },
+        tier("valid", vec![Arc::clone(&valid)]),
+    ]);
+
+    assert_eq!(
+        piece_fetcher.get_piece(piece_index).await,
+        Some(test_piece(piece_index))
+    );
+    assert_eq!(invalid.requests(), 1);
+    assert_eq!(valid.requests(), 1);
+}
+
+#[tokio::test]
+async fn concurrent_requests_deduplicated() {
+    let piece_index = PieceIndex::from(0);
+    let source = Arc::new(TestPieceSource {
+        delay: Duration::from_millis(100),
+        ..Arc::into_inner(TestPieceSource::new([piece_index])).unwrap()
+    });
+    let piece_fetcher = fetcher(vec![tier("slow", vec![Arc::clone(&source)])]);
+
+    let (first, second) = tokio::join!(
+        piece_fetcher.get_piece(piece_index),
+        piece_fetcher.get_piece(piece_index)
+    );
+    assert_eq!(first, Some(test_piece(piece_index)));
+    assert_eq!(second, Some(test_piece(piece_index)));
+    assert_eq!(source.requests(), 1);
+}
+
+#[tokio::test]
+async fn failed_concurrent_requests_not_repeated() {
+    let piece_index = PieceIndex::from(0);
+    let source = Arc::new(TestPieceSource {
+        delay: Duration::from_millis(100),
+        ..Arc::into_inner(TestPieceSource::new([])).unwrap()
+    });
+    let piece_fetcher = fetcher(vec![tier("slow", vec![Arc::clone(&source)])]);
+
+    let (first, second) = tokio::join!(
+        piece_fetcher.get_piece(piece_index),
+        piece_fetcher.get_piece(piece_index)
+    );
+    assert_eq!(first, None);
+    assert_eq!(second, None);
+    assert_eq!(source.requests(), 1);
+}
+
+#[tokio::test]
+async fn concurrent_requests_with_different_retries_not_deduplicated() {
+    let piece_index = PieceIndex::from(0);
+    let source = Arc::new(TestPieceSource {
+        delay: Duration::from_millis(100),
+        ..Arc::into_inner(TestPieceSource::new([])).unwrap()
+    });
+    let piece_fetcher = fetcher(vec![PieceSourceTier {
+        retry_policy: RetryPolicy {
+            max_retries: 2,
+            backoff: ExponentialBackoff {
+                initial_interval: Duration::from_millis(1),
+                max_interval: Duration::from_millis(1),
+                ..ExponentialBackoff::default()
+            },
+        },
+        ..tier("retrying", vec![Arc::clone(&source)])
+    }]);
+
+    let (with_retries, single_attempt) = tokio::join!(
+        piece_fetcher.get_piece(piece_index),
+        piece_fetcher.try_get_piece(piece_index, 0..1)
+    );
+    assert_eq!(with_retries, None);
+    assert_eq!(single_attempt, None);
+    // Request with retries didn't reuse the result of a single attempt and vice versa
+    assert_eq!(source.requests(), 3 + 1);
+}
+
+#[tokio::test]
+async fn duplicate_requests_do_not_hold_permits() {
+    let slow_piece_index = PieceIndex::from(0);
+    let fast_piece_index = PieceIndex::from(2);
+    let fast = TestPieceSource::new([fast_piece_index]);
+    let slow = Arc::new(TestPieceSource {
+        delay: Duration::from_secs(10),
+        ..Arc::into_inner(TestPieceSource::new([slow_piece_index])).unwrap()
+    });
+    let request_concurrency = 2;
+    let piece_fetcher = PieceFetcher::new(
+        vec![
+            tier("fast", vec![Arc::clone(&fast)]),
+            tier("slow", vec![Arc::clone(&slow)]),
+        ],
+        NonZeroUsize::new(request_concurrency).unwrap(),
+    );
+
+    // More duplicate requests than concurrency allows
+    for _ in 0..request_concurrency * 2 {
+        tokio::spawn({
+            let piece_fetcher = piece_fetcher.clone();
+
+            async move { piece_fetcher.get_piece(slow_piece_index).await }
+        });
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(slow.requests(), 1);
+
+    // Only one permit is used by the slow piece, so other pieces are not blocked by it
+    assert_eq!(
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            piece_fetcher.get_piece(fast_piece_index)
+        )
+        .await
+        .unwrap(),
+        Some(test_piece(fast_piece_index))
+    );
+}
+
+#[tokio::test]
+async fn cancelled_requests_do_not_leak_fetcher() {
+    let piece_index = PieceIndex::from(0);
+    let source = Arc::new(TestPieceSource {
+        delay: Duration::from_secs(10),
+        ..Arc::into_inner(TestPieceSource::new([piece_index])).unwrap()
+    });
+    let piece_fetcher = fetcher(vec![tier("slow", vec![Arc::clone(&source)])]);
+
+    // All requests waiting for the piece are dropped before it is fetched
+    assert!(tokio::time::timeout(Duration::from_millis(100), async {
+        tokio::join!(
+            piece_fetcher.get_piece(piece_index),
+            piece_fetcher.get_piece(piece_index)
+        )
+    })
+    .await
+    .is_err());
+    assert_eq!(source.requests(), 1);
+
+    assert!(piece_fetcher.inner.in_progress_pieces.lock().is_empty());
+    assert_eq!(Arc::strong_count(&piece_fetcher.inner), 1);
+}
+
+#[tokio::test]
+async fn finished_requests_removed() {
+    let piece_index = PieceIndex::from(0);
+    let source = TestPieceSource::new([piece_index]);
+    let piece_fetcher = fetcher(vec![tier("first", vec![Arc::clone(&source)])]);
+
+    assert_eq!(
+        piece_fetcher.get_piece(piece_index).await,
+        Some(test_piece(piece_index))
+    );
+    assert_eq!(
+        piece_fetcher.get_piece(piece_index).await,
+        Some(test_piece(piece_index))
+    );
+    // Finished request is not reused
+    assert_eq!(source.requests(), 2);
+
+    assert!(piece_fetcher.inner.in_progress_pieces.lock().is_empty());
+    assert_eq!(Arc::strong_count(&piece_fetcher.inner), 1);
+}
+
+#[tokio::test]
+async fn segment_pieces() {
+    let segment_index = SegmentIndex::ONE;
+    let segment_piece_indexes = segment_index.segment_piece_indexes();
+
+    // Some source pieces are missing, parity pieces are used instead
+    let source = TestPieceSource::new(segment_piece_indexes.into_iter().skip(10));
+    let segment_pieces = download_segment_pieces(segment_index, source.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(segment_pieces.len(), ArchivedHistorySegment::NUM_PIECES);
+    assert_eq!(
+        segment_pieces.iter().flatten().count(),
+        RecordedHistorySegment::NUM_RAW_RECORDS
+    );
+    assert!(segment_pieces[..10].iter().all(Option::is_none));
+
+    // Not enough pieces
+    let source = TestPieceSource::new(
+        segment_piece_indexes
+            .into_iter()
+            .take(RecordedHistorySegment::NUM_RAW_RECORDS - 1),
+    );
+    assert!(matches!(
+        download_segment_pieces(segment_index, source.as_ref()).await,
+        Err(PieceFetcherError::NotEnoughPieces { downloaded, .. })
+            if downloaded == RecordedHistorySegment::NUM_RAW_RECORDS - 1
+    ));
+}
@@ -0,0 +1,312 @@
+// Copyright (C) 2024 Subspace Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reconstruction of pieces of the archived history that can't be downloaded, using other pieces
+//! of the same segment.
+//!
+//! Any [`RecordedHistorySegment::NUM_RAW_RECORDS`] pieces of a segment are enough to reconstruct
+//! the rest of the segment, so only the number of pieces that is still missing is downloaded,
+//! reusing pieces that the caller already has.
+
+#[cfg(test)]
+mod tests;
+
+use crate::object_fetcher::ObjectPieceGetter;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use std::fmt;
+use std::sync::Arc;
+use subspace_archiving::piece_reconstructor::{PiecesReconstructor, ReconstructorError};
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    ArchivedHistorySegment, Piece, RecordedHistorySegment, SegmentIndex,
+};
+use subspace_erasure_coding::ErasureCoding;
+use tokio::task::JoinError;
+use tracing::{debug, trace};
+
+/// Piece reconstruction errors.
+#[derive(Debug, thiserror::Error)]
+pub enum PieceReconstructionError {
+    /// Not enough pieces of the segment could be downloaded
+    #[error(
+        "Not enough pieces to reconstruct pieces of segment {segment_index}: {available} \
+        available, {required} required"
+    )]
+    NotEnoughPieces {
+        segment_index: SegmentIndex,
+        available: usize,
+        required: usize,
+    },
+
+    /// Reconstructor error
+    #[error("Reconstructing pieces of segment {segment_index} failed: {source}")]
+    Reconstruction {
+        segment_index: SegmentIndex,
+        source: ReconstructorError,
+    },
+
+    /// Reconstruction task failed
+    #[error("Piece reconstruction task failed: {source}")]
+    Task {
+        #[from]
+        source: JoinError,
+    },
+}
+
+/// Strategy for choosing which pieces of a segment to download in order to reconstruct missing
+/// pieces.
+pub trait PieceSelectionStrategy: fmt::Debug + Send + Sync + 'static {
+    /// Positions of pieces in segment `segment_index` in the order they should be downloaded.
+    ///
+    /// Only as many pieces as necessary for reconstruction are downloaded, the rest are only used
+    /// if some of the downloads fail. Positions of missing pieces and pieces that are already
+    /// available are skipped.
+    fn download_order(&self, segment_index: SegmentIndex, missing_positions: &[u32]) -> Vec<u32>;
+}
+
+/// Downloads source pieces before parity pieces.
+///
+/// Good default when callers are likely to need other source pieces of the same segment anyway.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SourceFirst;
+
+impl PieceSelectionStrategy for SourceFirst {
+    fn download_order(&self, _segment_index: SegmentIndex, _missing_positions: &[u32]) -> Vec<u32> {
+        let num_pieces = ArchivedHistorySegment::NUM_PIECES as u32;
+
+        (0..num_pieces)
+            .step_by(2)
+            .chain((1..num_pieces).step_by(2))
+            .collect()
+    }
+}
+
+/// Downloads parity pieces before source pieces.
+///
+/// Useful when source pieces of a segment are likely to be unavailable together, since parity
+/// pieces are stored and cached independently of them.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ParityFirst;
+
+impl PieceSelectionStrategy for ParityFirst {
+    fn download_order(&self, _segment_index: SegmentIndex, _missing_positions: &[u32]) -> Vec<u32> {
+        let num_pieces = ArchivedHistorySegment::NUM_PIECES as u32;
+
+        (1..num_pieces)
+            .step_by(2)
+            .chain((0..num_pieces).step_by(2))
+            .collect()
+    }
+}
+
+/// Reconstructs missing pieces of a segment, downloading the minimum number of other pieces of
+/// the same segment.
+#[derive(Debug, Clone)]
+pub struct PieceReconstruction {
+    reconstructor: PiecesReconstructor,
+    strategy: Arc<dyn PieceSelectionStrategy>,
+}
+
+impl PieceReconstruction {
+    /// Create a new instance that chooses pieces to download using `strategy`.
+    pub fn new<S>(kzg: Kzg, erasure_coding: ErasureCoding, strategy: S) -> Self
+    where
+        S: PieceSelectionStrategy,
+    {
+        Self {
+            reconstructor: PiecesReconstructor::new(kzg, erasure_coding),
+            strategy: Arc::new(strategy),
+        }
+    }
+
+    /// Reconstruct pieces at `missing_positions` of segment `segment_index`.
+    ///
+    /// `segment_pieces` contains pieces of the segment that are already available, at their
+    /// positions in the segment, they reduce the number of pieces that need to be downloaded.
+    /// Reconstructed pieces are returned in the same order as `missing_positions`.
+    ///
+    /// Downloaded pieces are not verified, the caller should verify the data it gets from
+    /// reconstructed pieces.
+    pub async fn reconstruct_pieces(
+        &self,
+        piece_getter: &(dyn ObjectPieceGetter + Send + Sync),
+        segment_index: SegmentIndex,
+        segment_pieces: Vec<Option<Piece>>,
+        missing_positions: &[u32],
+    ) -> Result<Vec<Piece>, PieceReconstructionError> {
+        let segment_pieces = self
+            .download_pieces(
+                piece_getter,
+                segment_index,
+                segment_pieces,
+                missing_positions,
+            )
+            .await?;
+
+        let reconstructor = self.reconstructor.clone();
+        let missing_positions = missing_positions.to_vec();
+        let reconstructed_pieces = tokio::task::spawn_blocking(move || {
+            // Reconstructing a single piece is much cheaper than creating witnesses for all
+            // pieces of the segment
+            if let [position] = missing_positions.as_slice() {
+                return reconstructor
+                    .reconstruct_piece(&segment_pieces, *position as usize)
+                    .map(|piece| vec![piece]);
+            }
+
+            let segment = reconstructor.reconstruct_segment(&segment_pieces)?;
+
+            Ok(missing_positions
+                .iter()
+                .map(|position| Piece::from(&segment[*position as usize]))
+                .collect())
+        })
+        .await?
+        .map_err(|source| PieceReconstructionError::Reconstruction {
+            segment_index,
+            source,
+        })?;
+
+        debug!(
+            %segment_index,
+            reconstructed = reconstructed_pieces.len(),
+            "Reconstructed missing pieces"
+        );
+
+        Ok(reconstructed_pieces)
+    }
+
+    /// Reconstruct all pieces of segment `segment_index`.
+    ///
+    /// Same as [`Self::reconstruct_pieces()`], but returns all pieces of the segment in segment
+    /// order, which is useful when multiple pieces of the segment might be needed.
+    /// `unavailable_positions` are positions of pieces that are known to be unavailable, they are
+    /// not downloaded.
+    pub async fn reconstruct_segment(
+        &self,
+        piece_getter: &(dyn ObjectPieceGetter + Send + Sync),
+        segment_index: SegmentIndex,
+        segment_pieces: Vec<Option<Piece>>,
+        unavailable_positions: &[u32],
+    ) -> Result<Vec<Piece>, PieceReconstructionError> {
+        let segment_pieces = self
+            .download_pieces(
+                piece_getter,
+                segment_index,
+                segment_pieces,
+                unavailable_positions,
+            )
+            .await?;
+
+        let reconstructor = self.reconstructor.clone();
+        let segment =
+            tokio::task::spawn_blocking(move || reconstructor.reconstruct_segment(&segment_pieces))
+                .await?
+                .map_err(|source| PieceReconstructionError::Reconstruction {
+                    segment_index,
+                    source,
+                })?;
+
+        debug!(%segment_index, "Reconstructed segment");
+
+        Ok(segment.pieces().collect())
+    }
+
+    /// Download pieces of the segment until there are enough of them for reconstruction, skipping
+    /// `missing_positions`, returns segment pieces at their positions in the segment.
+    async fn download_pieces(
+        &self,
+        piece_getter: &(dyn ObjectPieceGetter + Send + Sync),
+        segment_index: SegmentIndex,
+        mut segment_pieces: Vec<Option<Piece>>,
+        missing_positions: &[u32],
+    ) -> Result<Vec<Option<Piece>>, PieceReconstructionError> {
+        segment_pieces.resize(ArchivedHistorySegment::NUM_PIECES, None);
+
+        let required = RecordedHistorySegment::NUM_RAW_RECORDS;
+        let mut available = segment_pieces.iter().flatten().count();
+        let segment_piece_indexes = segment_index.segment_piece_indexes();
+        let mut candidates = self
+            .strategy
+            .download_order(segment_index, missing_positions)
+            .into_iter()
+            .filter(|position| {
+                (*position as usize) < ArchivedHistorySegment::NUM_PIECES
+                    && !missing_positions.contains(position)
+            });
+
+        while available < required {
+            let mut batch = Vec::with_capacity(required - available);
+            for position in candidates.by_ref() {
+                if segment_pieces[position as usize].is_none() && !batch.contains(&position) {
+                    batch.push(position);
+                }
+                if batch.len() == required - available {
+                    break;
+                }
+            }
+
+            if batch.is_empty() {
+                debug!(
+                    %segment_index,
+                    available,
+                    required,
+                    ?missing_positions,
+                    "Not enough pieces to reconstruct missing pieces"
+                );
+
+                return Err(PieceReconstructionError::NotEnoughPieces {
+                    segment_index,
+                    available,
+                    required,
+                });
+            }
+
+            let mut downloads = batch
+                .into_iter()
+                .map(|position| async move {
+                    let piece_index = segment_piece_indexes[position as usize];
+
+                    (position, piece_getter.get_piece(piece_index).await)
+                })
+                .collect::<FuturesUnordered<_>>();
+
+            while let Some((position, result)) = downloads.next().await {
+                match result {
+                    Ok(Some(piece)) => {
+                        trace!(%segment_index, position, "Downloaded piece for reconstruction");
+
+                        segment_pieces[position as usize].replace(piece);
+                        available += 1;
+                    }
+                    Ok(None) => {
+                        debug!(%segment_index, position, "Piece for reconstruction not found");
+                    }
+                    Err(error) => {
+                        debug!(
+                            %segment_index,
+                            position,
+                            %error,
+                            "Failed to download piece for reconstruction"
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(segment_pieces)
+    }
+}
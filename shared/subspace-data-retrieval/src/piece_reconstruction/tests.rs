@@ -0,0 +1,175 @@
+use crate::object_fetcher::{BoxError, ObjectPieceGetter, PieceGetterError};
+use crate::piece_reconstruction::{
+    ParityFirst, PieceReconstruction, PieceReconstructionError, SourceFirst,
+};
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::LazyLock;
+use subspace_archiving::archiver::Archiver;
+use subspace_core_primitives::crypto::kzg::{embedded_kzg_settings, Kzg};
+use subspace_core_primitives::objects::BlockObjectMapping;
+use subspace_core_primitives::{
+    ArchivedHistorySegment, Piece, PieceIndex, Record, RecordedHistorySegment, SegmentIndex,
+};
+use subspace_erasure_coding::ErasureCoding;
+
+static KZG: LazyLock<Kzg> = LazyLock::new(|| Kzg::new(embedded_kzg_settings()));
+static ERASURE_CODING: LazyLock<ErasureCoding> = LazyLock::new(|| {
+    ErasureCoding::new(
+        NonZeroUsize::new(Record::NUM_S_BUCKETS.next_power_of_two().ilog2() as usize)
+            .expect("Not zero; qed"),
+    )
+    .unwrap()
+});
+/// Pieces of the first segment, in segment order
+static SEGMENT_PIECES: LazyLock<Vec<Piece>> = LazyLock::new(|| {
+    let mut archiver = Archiver::new(KZG.clone(), ERASURE_CODING.clone());
+    let block = (0..RecordedHistorySegment::SIZE)
+        .map(|index| (index as u8).wrapping_mul(17))
+        .collect();
+    let archived_segments = archiver.add_block(block, BlockObjectMapping::default(), true);
+    assert_eq!(archived_segments.len(), 1);
+
+    archived_segments[0].pieces.pieces().collect()
+});
+
+/// Piece getter that serves pieces of the first segment, except for unavailable positions
+#[derive(Debug, Default)]
+struct TestPieceGetter {
+    unavailable: HashSet<u32>,
+    requests: AtomicUsize,
+}
+
+#[async_trait]
+impl ObjectPieceGetter for TestPieceGetter {
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+
+        let position = piece_index.position();
+        if piece_index.segment_index() != SegmentIndex::ZERO || self.unavailable.contains(&position)
+        {
+            return Err(PieceGetterError::NotFound { piece_index }.into());
+        }
+
+        Ok(Some(SEGMENT_PIECES[position as usize].clone()))
+    }
+}
+
+fn piece_getter(unavailable: impl IntoIterator<Item = u32>) -> TestPieceGetter {
+    TestPieceGetter {
+        unavailable: unavailable.into_iter().collect(),
+        requests: AtomicUsize::new(0),
+    }
+}
+
+#[tokio::test]
+async fn reconstruct_missing_source_pieces() {
+    let missing_positions = [0, 2, 4];
+    let piece_getter = piece_getter(missing_positions);
+    let piece_reconstruction =
+        PieceReconstruction::new(KZG.clone(), ERASURE_CODING.clone(), SourceFirst);
+
+    let reconstructed_pieces = piece_reconstruction
+        .reconstruct_pieces(
+            &piece_getter,
+            SegmentIndex::ZERO,
+            Vec::new(),
+            &missing_positions,
+        )
+        .await
+        .unwrap();
+
+    for (position, piece) in missing_positions.iter().zip(&reconstructed_pieces) {
+        assert_eq!(piece, &SEGMENT_PIECES[*position as usize]);
+    }
+    // Only the minimum number of pieces was downloaded
+    assert_eq!(
+        piece_getter.requests.load(Ordering::SeqCst),
+        RecordedHistorySegment::NUM_RAW_RECORDS
+    );
+}
+
+#[tokio::test]
+async fn reconstruct_missing_parity_pieces() {
+    let missing_positions = [1, 3, 255];
+    let piece_getter = piece_getter(missing_positions);
+    let piece_reconstruction =
+        PieceReconstruction::new(KZG.clone(), ERASURE_CODING.clone(), ParityFirst);
+
+    let reconstructed_pieces = piece_reconstruction
+        .reconstruct_pieces(
+            &piece_getter,
+            SegmentIndex::ZERO,
+            Vec::new(),
+            &missing_positions,
+        )
+        .await
+        .unwrap();
+
+    for (position, piece) in missing_positions.iter().zip(&reconstructed_pieces) {
+        assert_eq!(piece, &SEGMENT_PIECES[*position as usize]);
+    }
+    assert_eq!(
+        piece_getter.requests.load(Ordering::SeqCst),
+        RecordedHistorySegment::NUM_RAW_RECORDS
+    );
+}
+
+#[tokio::test]
+async fn reconstruct_reuses_available_pieces() {
+    let missing_positions = [0];
+    let piece_getter = piece_getter(missing_positions);
+    let piece_reconstruction =
+        PieceReconstruction::new(KZG.clone(), ERASURE_CODING.clone(), SourceFirst);
+
+    // The caller already has some pieces of the segment
+    let mut segment_pieces = vec![None; ArchivedHistorySegment::NUM_PIECES];
+    for position in 100..200 {
+        segment_pieces[position].replace(SEGMENT_PIECES[position].clone());
+    }
+
+    let reconstructed_pieces = piece_reconstruction
+        .reconstruct_pieces(
+            &piece_getter,
+            SegmentIndex::ZERO,
+            segment_pieces,
+            &missing_positions,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(reconstructed_pieces, vec![SEGMENT_PIECES[0].clone()]);
+    assert_eq!(
+        piece_getter.requests.load(Ordering::SeqCst),
+        RecordedHistorySegment::NUM_RAW_RECORDS - 100
+    );
+}
+
+#[tokio::test]
+async fn reconstruct_too_few_pieces() {
+    // Only one piece less than necessary is available
+    let available = RecordedHistorySegment::NUM_RAW_RECORDS as u32 - 1;
+    let piece_getter = piece_getter(available..ArchivedHistorySegment::NUM_PIECES as u32);
+    let piece_reconstruction =
+        PieceReconstruction::new(KZG.clone(), ERASURE_CODING.clone(), SourceFirst);
+
+    let result = piece_reconstruction
+        .reconstruct_pieces(
+            &piece_getter,
+            SegmentIndex::ZERO,
+            Vec::new(),
+            &[ArchivedHistorySegment::NUM_PIECES as u32 - 1],
+        )
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(PieceReconstructionError::NotEnoughPieces {
+            available: 127,
+            required: 128,
+            ..
+        })
+    ));
+}
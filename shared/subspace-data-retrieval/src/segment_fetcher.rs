@@ -15,4 +15,503 @@
 
 //! Fetching segments of the archived history of Subspace Network.
 //!
-//! TODO: move segment fetching here
+//! [`SegmentFetcher`] downloads pieces necessary to reconstruct segments and persists every piece
+//! in [`SegmentStore`] as soon as it is downloaded, so interrupted downloads resume where they
+//! stopped instead of downloading the whole segment again. Both stored and downloaded pieces are
+//! verified against segment commitment, so corrupted pieces are never used.
+//!
+//! Segments can also be exported as files, each file contains SCALE-encoded `Vec<Piece>` with all
+//! [`ArchivedHistorySegment::NUM_PIECES`] pieces of the reconstructed segment in segment order.
+
+#[cfg(test)]
+mod tests;
+
+use crate::piece_fetcher::{PieceFetcherError, PieceGetter};
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use parity_scale_codec::{Decode, Encode, IoReader};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use subspace_archiving::archiver::is_piece_valid;
+use subspace_archiving::piece_reconstructor::{
+    PiecesReconstructor, ReconstructorError as PiecesReconstructorError,
+};
+use subspace_archiving::reconstructor::{ReconstructedContents, Reconstructor, ReconstructorError};
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    ArchivedHistorySegment, Piece, PieceIndex, RecordedHistorySegment, SegmentCommitment,
+    SegmentHeader, SegmentIndex,
+};
+use subspace_erasure_coding::ErasureCoding;
+use tokio::task::{self, JoinError};
+use tracing::{debug, trace, warn};
+
+/// Segment fetching errors.
+#[derive(Debug, thiserror::Error)]
+pub enum SegmentFetcherError {
+    /// I/O error
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    /// Exported segment decoding error
+    #[error("Exported segment decoding error: {0}")]
+    Decoding(#[from] parity_scale_codec::Error),
+
+    /// Exported segment has unexpected number of pieces
+    #[error(
+        "Exported segment has {0} pieces, expected {}",
+        ArchivedHistorySegment::NUM_PIECES
+    )]
+    InvalidPieceCount(usize),
+
+    /// Piece fetching error
+    #[error("Piece fetching error: {0}")]
+    PieceFetcher(#[from] PieceFetcherError),
+
+    /// Segment reconstruction error
+    #[error("Segment {segment_index} reconstruction failed: {error}")]
+    Reconstruction {
+        segment_index: SegmentIndex,
+        error: ReconstructorError,
+    },
+
+    /// Segment pieces reconstruction error
+    #[error("Segment {segment_index} pieces reconstruction failed: {error}")]
+    PiecesReconstruction {
+        segment_index: SegmentIndex,
+        error: PiecesReconstructorError,
+    },
+
+    /// Blocking task error
+    #[error("Segment store task failed: {0}")]
+    Task(#[from] JoinError),
+}
+
+/// File name of the exported segment, zero-padded so that files are sorted by segment index
+pub fn exported_segment_file_name(segment_index: SegmentIndex) -> String {
+    format!("segment-{:010}.bin", u64::from(segment_index))
+}
+
+/// Read pieces of a segment previously exported with [`SegmentFetcher::export_segment()`]
+pub async fn read_exported_segment(path: &Path) -> Result<Vec<Piece>, SegmentFetcherError> {
+    let path = path.to_path_buf();
+
+    task::spawn_blocking(move || {
+        let mut reader = IoReader(BufReader::new(File::open(path)?));
+        let segment_pieces = Vec::<Piece>::decode(&mut reader)?;
+
+        if segment_pieces.len() != ArchivedHistorySegment::NUM_PIECES {
+            return Err(SegmentFetcherError::InvalidPieceCount(segment_pieces.len()));
+        }
+
+        Ok(segment_pieces)
+    })
+    .await?
+}
+
+/// On-disk store of pieces of segments that are being downloaded.
+///
+/// Each segment has its own directory with one file per piece named after piece position in the
+/// segment. Pieces are written to a temporary file first and renamed after that, so a piece file
+/// is either complete or doesn't exist.
+#[derive(Debug, Clone)]
+pub struct SegmentStore {
+    directory: PathBuf,
+}
+
+impl SegmentStore {
+    /// Open segment store in `directory`, creating it if it doesn't exist
+    pub fn open(directory: &Path) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+
+        Ok(Self {
+            directory: directory.to_path_buf(),
+        })
+    }
+
+    /// Segments that have some pieces stored
+    pub fn segments(&self) -> io::Result<Vec<SegmentIndex>> {
+        let mut segments = fs::read_dir(&self.directory)?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                if !entry.file_type().ok()?.is_dir() {
+                    return None;
+                }
+
+                entry
+                    .file_name()
+                    .to_str()?
+                    .parse::<u64>()
+                    .ok()
+                    .map(SegmentIndex::from)
+            })
+            .collect::<Vec<_>>();
+        segments.sort_unstable();
+
+        Ok(segments)
+    }
+
+    /// Read stored pieces of the segment at their positions, positions of pieces that are not
+    /// stored are `None`
+    pub async fn read_pieces(
+        &self,
+        segment_index: SegmentIndex,
+    ) -> Result<Vec<Option<Piece>>, SegmentFetcherError> {
+        let segment_directory = self.segment_directory(segment_index);
+
+        let segment_pieces = task::spawn_blocking(move || {
+            (0..ArchivedHistorySegment::NUM_PIECES)
+                .map(|position| {
+                    let path = segment_directory.join(position.to_string());
+                    let bytes = match fs::read(&path) {
+                        Ok(bytes) => bytes,
+                        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                            return Ok(None);
+                        }
+                        Err(error) => {
+                            return Err(error);
+                        }
+                    };
+
+                    match Piece::try_from(bytes) {
+                        Ok(piece) => Ok(Some(piece)),
+                        Err(()) => {
+                            warn!(
+                                %segment_index,
+                                path = %path.display(),
+                                "Stored piece has incorrect size, removing"
+                            );
+                            fs::remove_file(&path)?;
+                            Ok(None)
+                        }
+                    }
+                })
+                .collect::<io::Result<Vec<_>>>()
+        })
+        .await??;
+
+        Ok(segment_pieces)
+    }
+
+    /// Store downloaded piece
+    pub async fn store_piece(
+        &self,
+        piece_index: PieceIndex,
+        piece: Piece,
+    ) -> Result<(), SegmentFetcherError> {
+        let segment_directory = self.segment_directory(piece_index.segment_index());
+
+        task::spawn_blocking(move || {
+            fs::create_dir_all(&segment_directory)?;
+
+            let path = segment_directory.join(piece_index.position().to_string());
+            let tmp_path = path.with_extension("tmp");
+            fs::write(&tmp_path, piece.as_ref())?;
+            fs::rename(tmp_path, path)
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Remove stored piece, typically because it turned out to be invalid
+    pub async fn remove_piece(&self, piece_index: PieceIndex) -> Result<(), SegmentFetcherError> {
+        let path = self
+            .segment_directory(piece_index.segment_index())
+            .join(piece_index.position().to_string());
+
+        task::spawn_blocking(move || match fs::remove_file(path) {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(error),
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    /// Remove all stored pieces of the segment
+    pub async fn remove_segment(
+        &self,
+        segment_index: SegmentIndex,
+    ) -> Result<(), SegmentFetcherError> {
+        let segment_directory = self.segment_directory(segment_index);
+
+        task::spawn_blocking(move || match fs::remove_dir_all(segment_directory) {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(error),
+        })
+        .await??;
+
+        Ok(())
+    }
+
+    fn segment_directory(&self, segment_index: SegmentIndex) -> PathBuf {
+        self.directory.join(segment_index.to_string())
+    }
+}
+
+/// Segment fetcher that downloads pieces of segments and stores them on disk, so that downloads
+/// can be resumed after restart.
+#[derive(Debug)]
+pub struct SegmentFetcher<PG> {
+    piece_getter: PG,
+    store: SegmentStore,
+    kzg: Kzg,
+    pieces_reconstructor: PiecesReconstructor,
+}
+
+impl<PG> SegmentFetcher<PG>
+where
+    PG: PieceGetter + Send + Sync,
+{
+    /// Create new instance
+    pub fn new(
+        piece_getter: PG,
+        store: SegmentStore,
+        kzg: Kzg,
+        erasure_coding: ErasureCoding,
+    ) -> Self {
+        Self {
+            piece_getter,
+            store,
+            pieces_reconstructor: PiecesReconstructor::new(kzg.clone(), erasure_coding),
+            kzg,
+        }
+    }
+
+    /// Segment store used by this fetcher
+    pub fn store(&self) -> &SegmentStore {
+        &self.store
+    }
+
+    /// Get pieces necessary to reconstruct the segment at their positions in the segment.
+    ///
+    /// Valid pieces that were stored before are reused and invalid ones are removed, only as many
+    /// missing pieces as necessary to have [`RecordedHistorySegment::NUM_RAW_RECORDS`] valid
+    /// pieces in total are downloaded. Source pieces are downloaded first, parity pieces are only
+    /// downloaded when source pieces fail to download.
+    pub async fn fetch_segment_pieces(
+        &self,
+        segment_header: &SegmentHeader,
+    ) -> Result<Vec<Option<Piece>>, SegmentFetcherError> {
+        let segment_index = segment_header.segment_index();
+        let segment_commitment = segment_header.segment_commitment();
+        let required_pieces = RecordedHistorySegment::NUM_RAW_RECORDS;
+
+        let mut segment_pieces = self.store.read_pieces(segment_index).await?;
+        for (piece_index, maybe_piece) in segment_index
+            .segment_piece_indexes()
+            .into_iter()
+            .zip(&mut segment_pieces)
+        {
+            let Some(piece) = maybe_piece.take() else {
+                continue;
+            };
+
+            match self
+                .verify_piece(piece_index, piece, segment_commitment)
+                .await?
+            {
+                Some(piece) => {
+                    maybe_piece.replace(piece);
+                }
+                None => {
+                    warn!(%piece_index, "Stored piece is invalid, removing");
+                    self.store.remove_piece(piece_index).await?;
+                }
+            }
+        }
+
+        let mut available_pieces = segment_pieces.iter().flatten().count();
+        if available_pieces >= required_pieces {
+            debug!(%segment_index, "All necessary segment pieces were already stored");
+            return Ok(segment_pieces);
+        }
+
+        debug!(
+            %segment_index,
+            stored_pieces = %available_pieces,
+            "Downloading segment pieces"
+        );
+
+        let mut candidates = segment_index
+            .segment_piece_indexes_source_first()
+            .into_iter()
+            .filter(|piece_index| segment_pieces[piece_index.position() as usize].is_none())
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        while available_pieces < required_pieces {
+            let mut downloads = candidates
+                .by_ref()
+                .take(required_pieces - available_pieces)
+                .map(|piece_index| async move {
+                    (
+                        piece_index,
+                        self.download_piece(piece_index, segment_commitment).await,
+                    )
+                })
+                .collect::<FuturesUnordered<_>>();
+
+            if downloads.is_empty() {
+                debug!(
+                    %segment_index,
+                    %available_pieces,
+                    %required_pieces,
+                    "Not enough pieces of the segment downloaded"
+                );
+
+                return Err(PieceFetcherError::NotEnoughPieces {
+                    segment_index,
+                    downloaded: available_pieces,
+                    required: required_pieces,
+                }
+                .into());
+            }
+
+            while let Some((piece_index, result)) = downloads.next().await {
+                if let Some(piece) = result? {
+                    segment_pieces[piece_index.position() as usize].replace(piece);
+                    available_pieces += 1;
+                }
+            }
+        }
+
+        Ok(segment_pieces)
+    }
+
+    /// Fetch the segment and reconstruct its contents with `reconstructor`.
+    ///
+    /// Stored pieces are kept until [`SegmentStore::remove_segment()`] is called, unless they turn
+    /// out to be unusable for reconstruction.
+    pub async fn fetch_segment(
+        &self,
+        segment_header: &SegmentHeader,
+        reconstructor: &mut Reconstructor,
+    ) -> Result<ReconstructedContents, SegmentFetcherError> {
+        let segment_index = segment_header.segment_index();
+        let segment_pieces = self.fetch_segment_pieces(segment_header).await?;
+
+        match reconstructor.add_segment(&segment_pieces) {
+            Ok(reconstructed_contents) => Ok(reconstructed_contents),
+            Err(error) => {
+                if matches!(
+                    error,
+                    ReconstructorError::DataShardsReconstruction(_)
+                        | ReconstructorError::SegmentDecoding(_)
+                ) {
+                    warn!(
+                        %segment_index,
+                        %error,
+                        "Failed to reconstruct segment, removing stored pieces"
+                    );
+                    self.store.remove_segment(segment_index).await?;
+                }
+
+                Err(SegmentFetcherError::Reconstruction {
+                    segment_index,
+                    error,
+                })
+            }
+        }
+    }
+
+    /// Fetch the segment, reconstruct all of its pieces and export them as a file in `directory`,
+    /// returns path to the file.
+    ///
+    /// See [`exported_segment_file_name()`] for file name and [`read_exported_segment()`] for
+    /// reading it back.
+    pub async fn export_segment(
+        &self,
+        segment_header: &SegmentHeader,
+        directory: &Path,
+    ) -> Result<PathBuf, SegmentFetcherError> {
+        let segment_index = segment_header.segment_index();
+        let segment_pieces = self.fetch_segment_pieces(segment_header).await?;
+        let path = directory.join(exported_segment_file_name(segment_index));
+
+        task::spawn_blocking({
+            let pieces_reconstructor = self.pieces_reconstructor.clone();
+            let path = path.clone();
+
+            move || {
+                let segment = pieces_reconstructor
+                    .reconstruct_segment(&segment_pieces)
+                    .map_err(|error| SegmentFetcherError::PiecesReconstruction {
+                        segment_index,
+                        error,
+                    })?;
+
+                let tmp_path = path.with_extension("tmp");
+                let mut writer = BufWriter::new(File::create(&tmp_path)?);
+                segment.pieces().collect::<Vec<_>>().encode_to(&mut writer);
+                writer.flush()?;
+                writer.into_inner().map_err(io::Error::from)?.sync_all()?;
+
+                fs::rename(tmp_path, path)?;
+
+                Ok::<_, SegmentFetcherError>(())
+            }
+        })
+        .await??;
+
+        debug!(%segment_index, path = %path.display(), "Segment exported");
+
+        Ok(path)
+    }
+
+    /// Download piece and store it if it is valid, returns `None` if piece wasn't found or is
+    /// invalid
+    async fn download_piece(
+        &self,
+        piece_index: PieceIndex,
+        segment_commitment: SegmentCommitment,
+    ) -> Result<Option<Piece>, SegmentFetcherError> {
+        let piece = match self.piece_getter.get_piece(piece_index).await {
+            Ok(Some(piece)) => piece,
+            Ok(None) => {
+                trace!(%piece_index, "Piece not found");
+                return Ok(None);
+            }
+            Err(error) => {
+                trace!(%error, %piece_index, "Piece request failed");
+                return Ok(None);
+            }
+        };
+
+        let Some(piece) = self
+            .verify_piece(piece_index, piece, segment_commitment)
+            .await?
+        else {
+            warn!(%piece_index, "Downloaded piece is invalid");
+            return Ok(None);
+        };
+
+        // Failing to store the piece only means it'll need to be downloaded again later
+        if let Err(error) = self.store.store_piece(piece_index, piece.clone()).await {
+            warn!(%piece_index, %error, "Failed to store downloaded piece");
+        }
+
+        Ok(Some(piece))
+    }
+
+    /// Returns piece back if it is valid
+    async fn verify_piece(
+        &self,
+        piece_index: PieceIndex,
+        piece: Piece,
+        segment_commitment: SegmentCommitment,
+    ) -> Result<Option<Piece>, SegmentFetcherError> {
+        let kzg = self.kzg.clone();
+
+        Ok(task::spawn_blocking(move || {
+            is_piece_valid(&kzg, &piece, &segment_commitment, piece_index.position())
+                .then_some(piece)
+        })
+        .await?)
+    }
+}
@@ -0,0 +1,237 @@
+use crate::object_fetcher::BoxError;
+use crate::piece_fetcher::PieceGetter;
+use crate::segment_fetcher::{
+    exported_segment_file_name, read_exported_segment, SegmentFetcher, SegmentStore,
+};
+use async_trait::async_trait;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use subspace_archiving::archiver::{Archiver, NewArchivedSegment};
+use subspace_core_primitives::crypto::kzg::{embedded_kzg_settings, Kzg};
+use subspace_core_primitives::objects::BlockObjectMapping;
+use subspace_core_primitives::{Piece, PieceIndex, Record, RecordedHistorySegment};
+use subspace_erasure_coding::ErasureCoding;
+use tempfile::tempdir;
+
+#[derive(Debug)]
+struct TestPieceGetter {
+    archived_segment: NewArchivedSegment,
+    requests: AtomicUsize,
+}
+
+#[async_trait]
+impl PieceGetter for TestPieceGetter {
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, BoxError> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+
+        Ok(self
+            .archived_segment
+            .pieces
+            .pieces()
+            .nth(piece_index.position() as usize))
+    }
+}
+
+impl TestPieceGetter {
+    fn new(archived_segment: &NewArchivedSegment) -> Self {
+        Self {
+            archived_segment: archived_segment.clone(),
+            requests: AtomicUsize::new(0),
+        }
+    }
+
+    fn piece(&self, piece_index: PieceIndex) -> Piece {
+        self.archived_segment
+            .pieces
+            .pieces()
+            .nth(piece_index.position() as usize)
+            .unwrap()
+    }
+}
+
+fn kzg_and_erasure_coding() -> (Kzg, ErasureCoding) {
+    let kzg = Kzg::new(embedded_kzg_settings());
+    let erasure_coding = ErasureCoding::new(
+        NonZeroUsize::new(Record::NUM_S_BUCKETS.next_power_of_two().ilog2() as usize)
+            .expect("Not zero; qed"),
+    )
+    .unwrap();
+
+    (kzg, erasure_coding)
+}
+
+/// Archive enough blocks to produce the first segment
+fn archived_segment() -> NewArchivedSegment {
+    let (kzg, erasure_coding) = kzg_and_erasure_coding();
+    let mut archiver = Archiver::new(kzg, erasure_coding);
+
+    (0..4u8)
+        .flat_map(|block_number| {
+            let block = (0..RecordedHistorySegment::SIZE / 3)
+                .map(|index| (index as u8).wrapping_mul(block_number + 1))
+                .collect::<Vec<u8>>();
+            archiver.add_block(block, BlockObjectMapping::default(), false)
+        })
+        .next()
+        .unwrap()
+}
+
+fn segment_fetcher(
+    archived_segment: &NewArchivedSegment,
+    store: SegmentStore,
+) -> SegmentFetcher<TestPieceGetter> {
+    let (kzg, erasure_coding) = kzg_and_erasure_coding();
+
+    SegmentFetcher::new(
+        TestPieceGetter::new(archived_segment),
+        store,
+        kzg,
+        erasure_coding,
+    )
+}
+
+#[tokio::test]
+async fn resume_download() {
+    let directory = tempdir().unwrap();
+    let archived_segment = archived_segment();
+    let segment_header = archived_segment.segment_header;
+    let segment_index = segment_header.segment_index();
+    let source_piece_indexes = segment_index.segment_piece_indexes_source_first();
+
+    // Pretend previous download was interrupted after some pieces were stored
+    let store = SegmentStore::open(directory.path()).unwrap();
+    let segment_fetcher = segment_fetcher(&archived_segment, store.clone());
+    for &piece_index in &source_piece_indexes[..100] {
+        store
+            .store_piece(piece_index, segment_fetcher.piece_getter.piece(piece_index))
+            .await
+            .unwrap();
+    }
+    assert_eq!(store.segments().unwrap(), vec![segment_index]);
+
+    let segment_pieces = segment_fetcher
+        .fetch_segment_pieces(&segment_header)
+        .await
+        .unwrap();
+    assert_eq!(
+        segment_pieces.iter().flatten().count(),
+        RecordedHistorySegment::NUM_RAW_RECORDS
+    );
+    assert_eq!(
+        segment_fetcher.piece_getter.requests.load(Ordering::SeqCst),
+        RecordedHistorySegment::NUM_RAW_RECORDS - 100
+    );
+
+    // Everything is stored now, nothing is downloaded
+    let segment_fetcher = self::segment_fetcher(
+        &archived_segment,
+        SegmentStore::open(directory.path()).unwrap(),
+    );
+    assert_eq!(
+        segment_fetcher
+            .fetch_segment_pieces(&segment_header)
+            .await
+            .unwrap(),
+        segment_pieces
+    );
+    assert_eq!(
+        segment_fetcher.piece_getter.requests.load(Ordering::SeqCst),
+        0
+    );
+
+    segment_fetcher
+        .store()
+        .remove_segment(segment_index)
+        .await
+        .unwrap();
+    assert!(segment_fetcher.store().segments().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn invalid_stored_pieces_are_replaced() {
+    let directory = tempdir().unwrap();
+    let archived_segment = archived_segment();
+    let segment_header = archived_segment.segment_header;
+    let segment_index = segment_header.segment_index();
+    let source_piece_indexes = segment_index.segment_piece_indexes_source_first();
+
+    let store = SegmentStore::open(directory.path()).unwrap();
+    let segment_fetcher = segment_fetcher(&archived_segment, store.clone());
+    for &piece_index in &source_piece_indexes[..RecordedHistorySegment::NUM_RAW_RECORDS] {
+        store
+            .store_piece(piece_index, segment_fetcher.piece_getter.piece(piece_index))
+            .await
+            .unwrap();
+    }
+    // Corrupt one of the stored pieces
+    let corrupted_piece_index = source_piece_indexes[0];
+    let mut corrupted_piece = segment_fetcher.piece_getter.piece(corrupted_piece_index);
+    corrupted_piece.as_mut()[0] ^= 1;
+    store
+        .store_piece(corrupted_piece_index, corrupted_piece)
+        .await
+        .unwrap();
+
+    let segment_pieces = segment_fetcher
+        .fetch_segment_pieces(&segment_header)
+        .await
+        .unwrap();
+    assert_eq!(
+        segment_pieces.iter().flatten().count(),
+        RecordedHistorySegment::NUM_RAW_RECORDS
+    );
+    // Corrupted piece is downloaded again and nothing else is
+    assert_eq!(
+        segment_pieces[corrupted_piece_index.position() as usize],
+        Some(segment_fetcher.piece_getter.piece(corrupted_piece_index))
+    );
+    assert_eq!(
+        segment_fetcher.piece_getter.requests.load(Ordering::SeqCst),
+        1
+    );
+    for (piece_index, maybe_piece) in segment_index
+        .segment_piece_indexes()
+        .into_iter()
+        .zip(&segment_pieces)
+    {
+        if let Some(piece) = maybe_piece {
+            assert_eq!(piece, &segment_fetcher.piece_getter.piece(piece_index));
+        }
+    }
+}
+
+#[tokio::test]
+async fn export_segment() {
+    let store_directory = tempdir().unwrap();
+    let export_directory = tempdir().unwrap();
+    let archived_segment = archived_segment();
+    let segment_header = archived_segment.segment_header;
+    let segment_index = segment_header.segment_index();
+
+    let segment_fetcher = segment_fetcher(
+        &archived_segment,
+        SegmentStore::open(store_directory.path()).unwrap(),
+    );
+    let path = segment_fetcher
+        .export_segment(&segment_header, export_directory.path())
+        .await
+        .unwrap();
+    assert_eq!(
+        path,
+        export_directory
+            .path()
+            .join(exported_segment_file_name(segment_index))
+    );
+    // Only pieces necessary for reconstruction were downloaded
+    assert_eq!(
+        segment_fetcher.piece_getter.requests.load(Ordering::SeqCst),
+        RecordedHistorySegment::NUM_RAW_RECORDS
+    );
+
+    // Exported file contains all pieces of the segment, including those that were not downloaded
+    let segment_pieces = read_exported_segment(&path).await.unwrap();
+    assert_eq!(
+        segment_pieces,
+        archived_segment.pieces.pieces().collect::<Vec<_>>()
+    );
+}